@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use sync_dis_boi::plex::PlexApi;
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt().without_time().with_target(false).init();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let token_path = if args.len() > 1 {
+        PathBuf::from(&args[1])
+    } else {
+        // Default to config directory
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| color_eyre::eyre::eyre!("couldn't find system config dir"))?
+            .join("SyncDisBoi");
+
+        if !config_dir.exists() {
+            std::fs::create_dir_all(&config_dir)?;
+        }
+
+        config_dir.join("plex_token.json")
+    };
+
+    println!("Plex PIN Login Setup");
+    println!("====================\n");
+    println!("This tool logs in to Plex without copying the token from the browser.");
+    println!("Enter the code printed below on https://plex.tv/link while signed in to Plex.\n");
+
+    PlexApi::login_with_pin(&token_path).await?;
+
+    println!("\n✓ Token saved to: {}", token_path.display());
+    println!("\nExample usage, without --plex-token:");
+    println!("  sync_dis_boi spotify --client-id ... --client-secret ... --owner \"YourName\" \\");
+    println!("    plex --server-url \"http://localhost:32400\" --music-library \"Music\" --owner \"YourName\"");
+
+    Ok(())
+}
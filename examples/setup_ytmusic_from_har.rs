@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+use sync_dis_boi::yt_music::YtMusicApi;
+
+/// Non-interactive setup example reading the headers from a HAR file
+///
+/// Usage:
+///   `cargo run --example setup_ytmusic_from_har <music.youtube.com.har> [output_headers.json]`
+///
+/// This reads the headers of the newest `YouTube` Music API request recorded in a HAR file
+/// exported by the browser, and converts them to JSON format.
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Usage: {} <music.youtube.com.har> [output_headers.json]", args[0]);
+        eprintln!();
+        eprintln!("Browser authentication setup for YouTube Music from a HAR file");
+        eprintln!();
+        eprintln!("Arguments:");
+        eprintln!("  music.youtube.com.har   - HAR file exported by the browser (required)");
+        eprintln!("  output_headers.json     - Output JSON file (optional, defaults to config dir)");
+        eprintln!();
+        eprintln!("Exporting the HAR file:");
+        eprintln!("  1. Open https://music.youtube.com logged in, then the DevTools network tab");
+        eprintln!("  2. Browse a bit (e.g. open your library) to record some requests");
+        eprintln!("  3. Chrome: \"Export HAR (with sensitive data)\", Firefox: \"Save All As HAR\"");
+        std::process::exit(1);
+    }
+
+    let har_file = PathBuf::from(&args[1]);
+
+    if !har_file.exists() {
+        return Err(color_eyre::eyre::eyre!("HAR file not found: {}", har_file.display()));
+    }
+
+    let output_file = if args.len() > 2 {
+        Some(PathBuf::from(&args[2]))
+    } else {
+        // Default to config directory
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| color_eyre::eyre::eyre!("couldn't find system config dir"))?
+            .join("SyncDisBoi");
+
+        if !config_dir.exists() {
+            std::fs::create_dir_all(&config_dir)?;
+        }
+
+        Some(config_dir.join("ytmusic_headers.json"))
+    };
+
+    println!("YouTube Music Browser Authentication Setup (HAR)");
+    println!("================================================\n");
+    println!("Reading headers from: {}", har_file.display());
+
+    let _headers_json = YtMusicApi::setup_browser_from_har(&har_file, output_file.clone())?;
+
+    println!("\n✓ Headers processed successfully!");
+
+    if let Some(ref path) = output_file {
+        println!("✓ Saved to: {}", path.display());
+    }
+
+    let output_path_str = output_file
+        .as_ref()
+        .map_or_else(|| "ytmusic_headers.json".to_string(), |p| p.display().to_string());
+
+    println!("\nExample usage:");
+    println!("  sync_dis_boi yt-music --headers {} --owner \"YourName\" \\",
+             output_path_str);
+    println!("    spotify --client-id ... --client-secret ... --owner \"YourName\"");
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use sync_dis_boi::export::ExportFormat;
+use sync_dis_boi::music_api::MusicApiType;
+use sync_dis_boi::playlist_files::{CsvColumns, ImportFormat};
+use sync_dis_boi::sync::PlaylistFilter;
 use sync_dis_boi::{ConfigArgs, spotify::SpotifyApi};
 use tracing::Level;
 
@@ -18,9 +23,32 @@ pub struct RootArgs {
     #[arg(short, long, value_enum, default_value_t = LoggingLevel::Info, env = "LOGGING_LEVEL")]
     pub logging: LoggingLevel,
 
+    /// Never show progress bars, they are only shown when stdout is a terminal and the
+    /// logging level is not debug
+    #[arg(long, default_value = "false", env = "NO_PROGRESS")]
+    pub no_progress: bool,
+
     /// List of playlist names to skip, separated by '|'
     #[arg(long, use_value_delimiter = true, value_delimiter = '|', env = "SKIP_PLAYLISTS")]
     pub skip_playlists: Vec<String>,
+
+    /// Only synchronize these playlists, names separated by '|' (case insensitive)
+    #[arg(long, use_value_delimiter = true, value_delimiter = '|', env = "PLAYLISTS")]
+    pub playlists: Vec<String>,
+
+    /// Only synchronize the playlists whose name matches this regex, use ^ and $ to match the whole name
+    #[arg(long, value_parser = Regex::new, env = "PLAYLISTS_REGEX")]
+    pub playlists_regex: Option<Regex>,
+}
+
+impl RootArgs {
+    pub fn playlist_filter(&self) -> PlaylistFilter {
+        PlaylistFilter {
+            skip: self.skip_playlists.clone(),
+            names: self.playlists.clone(),
+            regex: self.playlists_regex.clone(),
+        }
+    }
 }
 
 const TIDAL_DEFAULT_CLIENT_ID: &str =
@@ -34,16 +62,12 @@ pub enum MusicPlatformSrc {
         /// The path to the headers JSON file
         #[arg(long, env = "YTMUSIC_HEADERS")]
         headers: Option<PathBuf>,
-        /// The client ID for the Youtube API application
-        #[arg(
-            long,
-            env = "YTMUSIC_CLIENT_ID",
-            conflicts_with = "headers",
-            requires = "client_secret"
-        )]
+        /// The client ID for the Youtube API application. With --headers, OAuth is the fallback
+        /// used once the headers are no longer logged in
+        #[arg(long, env = "YTMUSIC_CLIENT_ID", requires = "client_secret")]
         client_id: Option<String>,
         /// The client secret for the Youtube API application
-        #[arg(long, env = "YTMUSIC_CLIENT_SECRET", conflicts_with = "headers")]
+        #[arg(long, env = "YTMUSIC_CLIENT_SECRET")]
         client_secret: Option<String>,
         /// Clear the cached ytmusic_oauth.json file
         #[arg(long, requires = "client_id", requires = "client_secret")]
@@ -52,6 +76,12 @@ pub enum MusicPlatformSrc {
         #[arg(long,
             env = "YTMUSIC_OWNER")]
         owner: String,
+        /// Developer option: answer requests with the fixtures recorded in this directory
+        #[arg(long, hide = true, conflicts_with_all = ["headers", "client_id", "record"])]
+        replay: Option<PathBuf>,
+        /// Developer option: record the responses as fixtures in this directory (redacted)
+        #[arg(long, hide = true)]
+        record: Option<PathBuf>,
         /// The destination music platform
         #[command(subcommand)]
         dst: MusicPlatformDst,
@@ -60,9 +90,10 @@ pub enum MusicPlatformSrc {
         /// The client ID for the Spotify API application
         #[arg(long, env = "SPOTIFY_CLIENT_ID")]
         client_id: String,
-        /// The client secret for the Spotify API application
+        /// The client secret for the Spotify API application, the app is authorized with
+        /// PKCE without it
         #[arg(long, env = "SPOTIFY_CLIENT_SECRET")]
-        client_secret: String,
+        client_secret: Option<String>,
         /// Clear the cached `spotify_oauth.json` file
         #[arg(long)]
         clear_cache: bool,
@@ -93,28 +124,39 @@ pub enum MusicPlatformSrc {
         /// Clear the cached `tidal_oauth.json` file
         #[arg(long)]
         clear_cache: bool,
-        /// The owner of the playlists, this is required to know which playlists to skip
+        /// The owner of the playlists, this is required to know which playlists to skip.
+        /// Defaults to the name of the authenticated user
         #[arg(long,
             env = "TIDAL_OWNER")]
-        owner: String,
+        owner: Option<String>,
         /// The destination music platform
         #[command(subcommand)]
         dst: MusicPlatformDst,
     },
     Plex {
+        /// The URL of the Plex server, takes precedence over --plex-server-name
         #[arg(
             long,
             env = "PLEX_SERVER_URL",
+            required_unless_present = "plex_server_name",
             //default_value = "http://localhost:32400"
         )]
-        server_url: String,
-        /// The plex token to authenticate with the Plex server
+        server_url: Option<String>,
+        /// The name of the Plex server, its connections are found on plex.tv and the first
+        /// reachable one is used (local, then remote, then relayed)
+        #[arg(long, env = "PLEX_SERVER_NAME")]
+        plex_server_name: Option<String>,
+        /// The plex token to authenticate with the Plex server. Without it, the token saved by
+        /// a previous PIN login is used, or a new PIN login is started
         #[arg(
             long,
             env = "PLEX_TOKEN",
             //default_value = "SboVhoG9s0rNafixCSGGKXAT"
         )]
-        plex_token: String,
+        plex_token: Option<String>,
+        /// Clear the cached `plex_token.json` file of the PIN login
+        #[arg(long, conflicts_with = "plex_token")]
+        clear_cache: bool,
         /// Music library to create playlists in
         #[arg(
             long,
@@ -130,6 +172,22 @@ pub enum MusicPlatformSrc {
         #[command(subcommand)]
         dst: MusicPlatformDst,
     },
+    /// Review the songs that could not be matched and record manual overrides
+    Triage {
+        /// The unmatched songs report generated in debug mode, or a report written with
+        /// `--report`, defaults to `missing_songs.json` in the debug directory
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// The destination platform the overrides are recorded for
+        #[arg(long)]
+        dst: MusicApiType,
+        /// The overrides file, defaults to `overrides.json` in the config directory
+        #[arg(long)]
+        overrides: Option<PathBuf>,
+        /// Open the songs in the browser, this many at a time
+        #[arg(long)]
+        open: Option<usize>,
+    },
 }
 
 // INFO: Hack to support command chaining with clap
@@ -141,16 +199,12 @@ pub enum MusicPlatformDst {
         /// The path to the headers JSON file
         #[arg(long, env = "YTMUSIC_HEADERS")]
         headers: Option<PathBuf>,
-        /// The client ID for the Youtube API application
-        #[arg(
-            long,
-            env = "YTMUSIC_CLIENT_ID",
-            conflicts_with = "headers",
-            requires = "client_secret"
-        )]
+        /// The client ID for the Youtube API application. With --headers, OAuth is the fallback
+        /// used once the headers are no longer logged in
+        #[arg(long, env = "YTMUSIC_CLIENT_ID", requires = "client_secret")]
         client_id: Option<String>,
         /// The client secret for the Youtube API application
-        #[arg(long, env = "YTMUSIC_CLIENT_SECRET", conflicts_with = "headers")]
+        #[arg(long, env = "YTMUSIC_CLIENT_SECRET")]
         client_secret: Option<String>,
         /// Clear the cached ytmusic_oauth.json file
         #[arg(long, requires = "client_id", requires = "client_secret")]
@@ -159,14 +213,21 @@ pub enum MusicPlatformDst {
         #[arg(long,
             env = "YTMUSIC_OWNER")]
         owner: String,
+        /// Developer option: answer requests with the fixtures recorded in this directory
+        #[arg(long, hide = true, conflicts_with_all = ["headers", "client_id", "record"])]
+        replay: Option<PathBuf>,
+        /// Developer option: record the responses as fixtures in this directory (redacted)
+        #[arg(long, hide = true)]
+        record: Option<PathBuf>,
     },
     Spotify {
         /// The client ID for the Spotify API application
         #[arg(long, env = "SPOTIFY_CLIENT_ID")]
         client_id: String,
-        /// The client secret for the Spotify API application
+        /// The client secret for the Spotify API application, the app is authorized with
+        /// PKCE without it
         #[arg(long, env = "SPOTIFY_CLIENT_SECRET")]
-        client_secret: String,
+        client_secret: Option<String>,
         /// Clear the cached `spotify_oauth.json` file
         #[arg(long)]
         clear_cache: bool,
@@ -194,26 +255,37 @@ pub enum MusicPlatformDst {
         /// Clear the cached `tidal_oauth.json` file
         #[arg(long)]
         clear_cache: bool,
-        /// The owner of the playlists, this is required to know which playlists to skip
+        /// The owner of the playlists, this is required to know which playlists to skip.
+        /// Defaults to the name of the authenticated user
         #[arg(long,
             env = "TIDAL_OWNER")]
-        owner: String,
+        owner: Option<String>,
     },
     
     Plex {
+        /// The URL of the Plex server, takes precedence over --plex-server-name
         #[arg(
             long,
             env = "PLEX_SERVER_URL",
+            required_unless_present = "plex_server_name",
             //default_value = "http://localhost:32400"
         )]
-        server_url: String,
-        /// The plex token to authenticate with the Plex server
+        server_url: Option<String>,
+        /// The name of the Plex server, its connections are found on plex.tv and the first
+        /// reachable one is used (local, then remote, then relayed)
+        #[arg(long, env = "PLEX_SERVER_NAME")]
+        plex_server_name: Option<String>,
+        /// The plex token to authenticate with the Plex server. Without it, the token saved by
+        /// a previous PIN login is used, or a new PIN login is started
         #[arg(
             long,
             env = "PLEX_TOKEN",
             //default_value = "SboVhoG9s0rNafixCSGGKXAT"
         )]
-        plex_token: String,
+        plex_token: Option<String>,
+        /// Clear the cached `plex_token.json` file of the PIN login
+        #[arg(long, conflicts_with = "plex_token")]
+        clear_cache: bool,
         /// Music library name to create playlists in
         #[arg(
             long,
@@ -227,17 +299,32 @@ pub enum MusicPlatformDst {
         owner: String,
     },
     Export {
-        /// The path to the file to export the playlists to
+        /// The path to the file to export the playlists to, a directory with `--format m3u`
         #[arg(short, long)]
         output: PathBuf,
+        /// Format of the export, only JSON exports can be imported
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
         /// Minify the exported JSON file
         #[arg(long, default_value = "false")]
         minify: bool,
+        /// Also export the liked songs
+        #[arg(long, default_value = "false")]
+        include_likes: bool,
+        /// Also export the saved albums, on the platforms supporting them
+        #[arg(long, default_value = "false")]
+        include_albums: bool,
     },
     Import {
-        /// The path to the file to import the playlists from
+        /// The path to the file to import the playlists from, or to a directory of CSV or text
+        /// files
         #[arg(short, long)]
         input: PathBuf,
+        /// Format of the imported file
+        #[arg(long, value_enum, default_value_t = ImportFormat::Json)]
+        input_format: ImportFormat,
+        #[command(flatten)]
+        columns: CsvColumns,
     },
 }
 
@@ -27,11 +27,24 @@ macro_rules! impl_build_api {
                         client_secret,
                         clear_cache,
                         headers,
+                        replay,
+                        record,
                         ..
                     } => {
-                        if let Some(headers) = headers {
-                            // Browser authentication
-                            Box::new(YtMusicApi::new_browser(headers.clone(), args.config.clone()).await?)
+                        let api = if let Some(replay) = replay {
+                            // Offline replay of recorded responses
+                            YtMusicApi::new_replay(replay.clone(), args.config.clone())?
+                        } else if let Some(headers) = headers {
+                            // Browser authentication, OAuth when the headers are stale
+                            YtMusicApi::new_headers(
+                                headers.clone(),
+                                client_id.as_deref(),
+                                client_secret.as_deref(),
+                                config_dir.join("ytmusic_oauth.json"),
+                                *clear_cache,
+                                args.config.clone(),
+                            )
+                            .await?
                         } else {
                             // OAuth authentication
                             let Some(client_id) = client_id else {
@@ -41,16 +54,18 @@ macro_rules! impl_build_api {
                                 return Err(eyre!("Missing Youtube Music client_secret. Either provide --headers for browser auth or --client-id and --client-secret for OAuth."));
                             };
                             let oauth_token_path = config_dir.join("ytmusic_oauth.json");
-                            Box::new(
-                                YtMusicApi::new_oauth(
-                                    client_id,
-                                    client_secret,
-                                    oauth_token_path,
-                                    *clear_cache,
-                                    args.config.clone(),
-                                )
-                                .await?,
+                            YtMusicApi::new_oauth(
+                                client_id,
+                                client_secret,
+                                oauth_token_path,
+                                *clear_cache,
+                                args.config.clone(),
                             )
+                            .await?
+                        };
+                        match record {
+                            Some(record) => Box::new(api.record_fixtures(record.clone())?),
+                            None => Box::new(api),
                         }
                     }
                     Self::Tidal {
@@ -84,7 +99,7 @@ macro_rules! impl_build_api {
                         Box::new(
                             SpotifyApi::new(
                                 &client_id,
-                                &client_secret,
+                                client_secret.as_deref(),
                                 oauth_token_path,
                                 &redirect_uri,
                                 *clear_cache,
@@ -97,14 +112,39 @@ macro_rules! impl_build_api {
                     }
                     Self::Plex {
                         server_url,
+                        plex_server_name,
                         plex_token,
                         music_library,
+                        clear_cache,
                         ..
                     } => {
+                        // the token given explicitly wins over the one of the PIN login
+                        let token = match plex_token {
+                            Some(plex_token) => plex_token.clone(),
+                            None => {
+                                PlexApi::pin_token(&config_dir.join("plex_token.json"), *clear_cache)
+                                    .await?
+                            }
+                        };
+                        let (server_url, token) = match (server_url, plex_server_name) {
+                            (Some(server_url), _) => (server_url.clone(), token),
+                            (None, Some(name)) => {
+                                let connection = PlexApi::resolve_server(
+                                    &token,
+                                    name,
+                                    Some(&config_dir.join("plex_server.json")),
+                                )
+                                .await?;
+                                (connection.url, connection.token)
+                            }
+                            (None, None) => {
+                                return Err(eyre!("Missing Plex server. Either provide --server-url or --plex-server-name."));
+                            }
+                        };
                         Box::new(
                             PlexApi::new(
                                 &server_url,
-                                &plex_token,
+                                &token,
                                 &music_library,
                                 args.config.clone(),
                             )
@@ -126,9 +166,10 @@ impl_build_api!(MusicPlatformSrc);
 impl_build_api!(MusicPlatformDst);
 
 impl MusicPlatformSrc {
-    pub fn get_dst(&self) -> &MusicPlatformDst {
+    pub fn get_dst(&self) -> Option<&MusicPlatformDst> {
         match self {
-            Self::YtMusic { dst, .. } | Self::Spotify { dst, .. } | Self::Tidal { dst, .. } | Self::Plex { dst, .. } => dst,
+            Self::YtMusic { dst, .. } | Self::Spotify { dst, .. } | Self::Tidal { dst, .. } | Self::Plex { dst, .. } => Some(dst),
+            Self::Triage { .. } => None,
         }
     }
 
@@ -136,8 +177,9 @@ impl MusicPlatformSrc {
         match self {
             Self::YtMusic { owner, .. } => owner,
             Self::Spotify { owner, .. } => owner,
-            Self::Tidal { owner, .. } => owner,
+            Self::Tidal { owner, .. } => owner.as_deref().unwrap_or_default(),
             Self::Plex { owner, .. } => owner,
+            Self::Triage { .. } => "",
         }
     }
 }
@@ -147,7 +189,7 @@ impl MusicPlatformDst {
         match self {
             Self::YtMusic { owner, .. } => owner,
             Self::Spotify { owner, .. } => owner,
-            Self::Tidal { owner, .. } => owner,
+            Self::Tidal { owner, .. } => owner.as_deref().unwrap_or_default(),
             Self::Plex { owner, .. } => owner,
             MusicPlatformDst::Export { .. } => "", // Export does not have an owner field
             MusicPlatformDst::Import {  .. } => ""
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::music_api::MusicApiType;
+use crate::utils::unix_timestamp;
+
+/// Progress of a playlist in an interrupted synchronization
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlaylistProgress {
+    pub name: String,
+    pub songs_added: usize,
+    pub complete: bool,
+    /// Unix timestamp of the last update
+    pub updated: u64,
+}
+
+/// Progress of a synchronization, stored as JSON in the config directory and used by `--resume`
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Source platform of the synchronized songs, `None` when they come from several platforms
+    pub src_api: Option<MusicApiType>,
+    pub dst_api: MusicApiType,
+    /// source playlist id -> progress
+    #[serde(default)]
+    pub playlists: BTreeMap<String, PlaylistProgress>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    pub const FILE_NAME: &'static str = "sync_checkpoint.json";
+
+    pub fn new(path: &Path, src_api: Option<MusicApiType>, dst_api: MusicApiType) -> Self {
+        Self {
+            src_api,
+            dst_api,
+            playlists: BTreeMap::new(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Load the checkpoint of a previous run between the same platforms,
+    /// starting over when there is none
+    pub fn load(path: &Path, src_api: Option<MusicApiType>, dst_api: MusicApiType) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(path, src_api, dst_api));
+        }
+        let mut checkpoint: Self = serde_json::from_reader(std::fs::File::open(path)?)?;
+        checkpoint.path = path.to_path_buf();
+        if checkpoint.src_api != src_api || checkpoint.dst_api != dst_api {
+            warn!(
+                "checkpoint {:?} was made for a synchronization from {:?} to {:?}, ignoring it",
+                path, checkpoint.src_api, checkpoint.dst_api
+            );
+            return Ok(Self::new(path, src_api, dst_api));
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint file, once the synchronization is over
+    pub fn remove(&self) -> Result<()> {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self, src_id: &str) -> bool {
+        self.playlists.get(src_id).is_some_and(|p| p.complete)
+    }
+
+    pub fn get(&self, src_id: &str) -> Option<&PlaylistProgress> {
+        self.playlists.get(src_id)
+    }
+
+    /// Record songs added to a playlist, the playlist is complete once all its songs are added
+    pub fn record(&mut self, src_id: &str, name: &str, songs_added: usize, complete: bool) {
+        let updated = unix_timestamp();
+        let progress = self
+            .playlists
+            .entry(src_id.to_string())
+            .or_insert_with(|| PlaylistProgress {
+                name: name.to_string(),
+                songs_added: 0,
+                complete: false,
+                updated,
+            });
+        progress.songs_added += songs_added;
+        progress.complete = complete;
+        progress.updated = updated;
+    }
+}
@@ -0,0 +1,160 @@
+use color_eyre::eyre::{Result, eyre};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use reqwest::StatusCode;
+
+use crate::ConfigArgs;
+
+/// Largest cover image downloaded for `--sync-covers`, platforms accept smaller images anyway
+pub const MAX_COVER_BYTES: usize = 4 * 1024 * 1024;
+/// Qualities tried in turn when a cover is encoded as JPEG, before making it smaller
+const JPEG_QUALITIES: [u8; 3] = [90, 75, 60];
+/// Side under which a cover isn't made smaller
+const MIN_COVER_SIDE: u32 = 64;
+
+/// Cover image of a source playlist, downloaded for `--sync-covers`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverImage {
+    pub bytes: Vec<u8>,
+    /// Content type sent by the server, e.g. `image/jpeg`
+    pub content_type: Option<String>,
+}
+
+impl CoverImage {
+    /// Whether the image is a JPEG, by its magic number rather than its content type
+    pub fn is_jpeg(&self) -> bool {
+        self.bytes.starts_with(&[0xFF, 0xD8, 0xFF])
+    }
+
+    /// The image as a JPEG whose base64 encoding fits in `max_base64_len` bytes. Other formats
+    /// are converted, and images too large lose quality then size until they fit
+    pub fn to_jpeg(&self, max_base64_len: usize) -> Result<Vec<u8>> {
+        let base64_len = |len: usize| len.div_ceil(3) * 4;
+        if self.is_jpeg() && base64_len(self.bytes.len()) <= max_base64_len {
+            return Ok(self.bytes.clone());
+        }
+        let mut image = image::load_from_memory(&self.bytes)
+            .map_err(|e| eyre!("failed to decode the {} cover: {}", self.mime_type(), e))?;
+        loop {
+            for quality in JPEG_QUALITIES {
+                let mut jpeg = vec![];
+                image
+                    .to_rgb8()
+                    .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, quality))?;
+                if base64_len(jpeg.len()) <= max_base64_len {
+                    return Ok(jpeg);
+                }
+            }
+            if image.width() <= MIN_COVER_SIDE || image.height() <= MIN_COVER_SIDE {
+                return Err(eyre!("cover can't be made smaller than {} KB", max_base64_len / 1024));
+            }
+            image = image.resize(image.width() * 3 / 4, image.height() * 3 / 4, FilterType::Triangle);
+        }
+    }
+
+    /// Content type of the image, from its magic number when the server didn't send one
+    pub fn mime_type(&self) -> &str {
+        if let Some(content_type) = &self.content_type {
+            return content_type;
+        }
+        if self.is_jpeg() {
+            "image/jpeg"
+        } else if self.bytes.starts_with(b"\x89PNG") {
+            "image/png"
+        } else {
+            "application/octet-stream"
+        }
+    }
+}
+
+/// Download the cover image at `url`, failing on images larger than `max_bytes` without
+/// reading them entirely
+pub async fn download_cover(url: &str, max_bytes: usize, config: &ConfigArgs) -> Result<CoverImage> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = &config.proxy {
+        builder = builder
+            .proxy(reqwest::Proxy::all(proxy)?)
+            .danger_accept_invalid_certs(true);
+    }
+    let mut res = builder.build()?.get(url).send().await?;
+    if res.status() != StatusCode::OK {
+        return Err(eyre!("failed to download cover {}: HTTP {}", url, res.status()));
+    }
+    let too_large = || eyre!("cover {} is larger than {} KB", url, max_bytes / 1024);
+    if res.content_length().is_some_and(|l| l > max_bytes as u64) {
+        return Err(too_large());
+    }
+    let content_type = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .map(str::to_string);
+    let mut bytes = vec![];
+    while let Some(chunk) = res.chunk().await? {
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    if bytes.is_empty() {
+        return Err(eyre!("cover {} is empty", url));
+    }
+    Ok(CoverImage { bytes, content_type })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::serve_image;
+
+    #[test]
+    fn test_cover_mime_type() {
+        let jpeg = CoverImage { bytes: vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00], content_type: None };
+        assert!(jpeg.is_jpeg());
+        assert_eq!(jpeg.mime_type(), "image/jpeg");
+
+        let png = CoverImage { bytes: b"\x89PNG\r\n".to_vec(), content_type: None };
+        assert!(!png.is_jpeg());
+        assert_eq!(png.mime_type(), "image/png");
+
+        let sent = CoverImage { bytes: b"RIFF".to_vec(), content_type: Some("image/webp".to_string()) };
+        assert_eq!(sent.mime_type(), "image/webp");
+    }
+
+    #[test]
+    fn test_cover_to_jpeg() {
+        // noise doesn't compress, the image has to lose quality and size
+        let noise = image::RgbImage::from_fn(400, 400, |x, y| {
+            let n = (x * 7919 + y * 104_729) ^ (x * y);
+            image::Rgb([(n % 251) as u8, (n % 241) as u8, (n % 239) as u8])
+        });
+        let mut png = std::io::Cursor::new(vec![]);
+        noise.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let cover = CoverImage { bytes: png.into_inner(), content_type: Some("image/png".to_string()) };
+        let jpeg = CoverImage { bytes: cover.to_jpeg(16 * 1024).unwrap(), content_type: None };
+        assert!(jpeg.is_jpeg());
+        assert!(jpeg.bytes.len().div_ceil(3) * 4 <= 16 * 1024);
+        let converted = image::load_from_memory(&jpeg.bytes).unwrap();
+        assert!(converted.width() < 400);
+
+        // a JPEG small enough is kept as is
+        assert_eq!(jpeg.to_jpeg(16 * 1024).unwrap(), jpeg.bytes);
+        let err = CoverImage { bytes: b"RIFF".to_vec(), content_type: None }.to_jpeg(1024).unwrap_err();
+        assert!(err.to_string().starts_with("failed to decode"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_download_cover_size_cap() {
+        use clap::Parser;
+
+        let config = ConfigArgs::parse_from(["sync_dis_boi"]);
+        let jpeg = [vec![0xFF, 0xD8, 0xFF], vec![0; 2045]].concat();
+        let url = serve_image(jpeg.clone(), 2).await;
+        let cover = download_cover(&url, 4096, &config).await.unwrap();
+        assert_eq!(cover.bytes, jpeg);
+        assert_eq!(cover.mime_type(), "image/jpeg");
+
+        let err = download_cover(&url, 1024, &config).await.unwrap_err();
+        assert!(err.to_string().contains("larger than 1 KB"));
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use strsim::normalized_levenshtein;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::music_api::{Artist, MusicApiType, Song};
+use crate::utils::{clean_enclosure, clean_isrc, generic_name_clean, normalize_title};
+
+const MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2/recording";
+/// `MusicBrainz` allows one request per second
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+/// Minimum `MusicBrainz` search score of a recording, out of 100
+const MIN_SCORE: u32 = 80;
+/// Lookups stop for the run after this many consecutive failed requests
+const MAX_FAILURES: usize = 3;
+
+/// ISRCs of the songs found on `MusicBrainz`, stored as JSON in the config directory
+///
+/// Songs `MusicBrainz` doesn't know are cached as `None` so they are not looked up on every run,
+/// failed requests are not cached.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct IsrcCache {
+    /// videoId of `YtMusic` songs, or `MusicBrainz` recording id -> ISRC
+    #[serde(default)]
+    pub isrcs: BTreeMap<String, Option<String>>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl IsrcCache {
+    pub const FILE_NAME: &'static str = "isrc_cache.json";
+
+    /// Load the cache file, a missing file is equivalent to an empty cache
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let cache = serde_json::from_reader(std::fs::File::open(path)?)?;
+        Ok(cache)
+    }
+
+    /// Write the cache file if it was modified since it was loaded
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if self.dirty {
+            std::fs::write(path, serde_json::to_string(self)?)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct MusicBrainzResponse {
+    #[serde(default)]
+    recordings: Vec<MusicBrainzRecording>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MusicBrainzRecording {
+    #[serde(default)]
+    score: u32,
+    title: String,
+    /// Duration in milliseconds
+    length: Option<usize>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<MusicBrainzArtistCredit>,
+    #[serde(default)]
+    isrcs: Vec<String>,
+}
+
+/// Recording looked up by its id
+#[derive(Deserialize, Debug)]
+struct MusicBrainzRecordingIsrcs {
+    #[serde(default)]
+    isrcs: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MusicBrainzArtistCredit {
+    name: String,
+}
+
+/// Fills the missing ISRC of `YtMusic` songs with the ISRC of their `MusicBrainz` recording,
+/// `--enrich-isrc`. The recording of the songs with a `MusicBrainz` id (Plex) is looked up by
+/// its id, the other ones are searched by name.
+///
+/// The destination platforms are then searched by ISRC instead of by name, which tells live
+/// versions and remasters apart. Songs that can't be enriched keep the name search.
+pub struct IsrcEnricher {
+    client: reqwest::Client,
+    cache: IsrcCache,
+    duration_tolerance_ms: usize,
+    last_request: Option<Instant>,
+    failures: usize,
+}
+
+impl IsrcEnricher {
+    pub fn new(cache: IsrcCache, duration_tolerance_ms: usize) -> Result<Self> {
+        // MusicBrainz rejects anonymous clients
+        let client = reqwest::Client::builder()
+            .user_agent(format!(
+                "SyncDisBoi/{} ( {} )",
+                env!("CARGO_PKG_VERSION"),
+                env!("CARGO_PKG_REPOSITORY")
+            ))
+            .build()?;
+        Ok(Self {
+            client,
+            cache,
+            duration_tolerance_ms,
+            last_request: None,
+            failures: 0,
+        })
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.cache.save(path)
+    }
+
+    /// Fill the ISRC hint of the `YtMusic` songs and of the songs with a `MusicBrainz` id without
+    /// an ISRC, except the ones `skip` returns true for. Returns the number of songs that got a
+    /// hint.
+    pub async fn enrich(&mut self, songs: &mut [Song], skip: impl Fn(&Song) -> bool) -> usize {
+        let mut enriched = 0;
+        for song in songs
+            .iter_mut()
+            .filter(|s| {
+                (s.source == MusicApiType::YtMusic || s.mbid.is_some()) && s.isrc.is_none() && s.isrc_hint.is_none()
+            })
+        {
+            if skip(song) {
+                continue;
+            }
+            let key = song.mbid.clone().unwrap_or_else(|| song.id.clone());
+            let isrc = match self.cache.isrcs.get(&key) {
+                Some(isrc) => isrc.clone(),
+                None if self.failures >= MAX_FAILURES => continue,
+                None => match self.lookup(song).await {
+                    Ok(isrc) => {
+                        self.failures = 0;
+                        self.cache.isrcs.insert(key, isrc.clone());
+                        self.cache.dirty = true;
+                        isrc
+                    }
+                    Err(e) => {
+                        self.failures += 1;
+                        warn!("failed to look up the ISRC of {} on MusicBrainz: {}", song, e);
+                        if self.failures >= MAX_FAILURES {
+                            warn!("MusicBrainz is unavailable, the next songs are searched by name");
+                        }
+                        continue;
+                    }
+                },
+            };
+            if isrc.is_some() {
+                song.isrc_hint = isrc;
+                enriched += 1;
+            }
+        }
+        enriched
+    }
+
+    async fn lookup(&mut self, song: &Song) -> Result<Option<String>> {
+        if let Some(last) = self.last_request {
+            tokio::time::sleep_until(last + REQUEST_INTERVAL).await;
+        }
+        self.last_request = Some(Instant::now());
+
+        if let Some(mbid) = &song.mbid {
+            let res = self
+                .client
+                .get(format!("{}/{}", MUSICBRAINZ_URL, mbid))
+                .query(&[("inc", "isrcs"), ("fmt", "json")])
+                .send()
+                .await?;
+            // merged or deleted recordings
+            if res.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let res: MusicBrainzRecordingIsrcs = res.error_for_status()?.json().await?;
+            let isrc = clean_isrc(res.isrcs.into_iter().next());
+            debug!("MusicBrainz ISRC of {} ({}): {:?}", song, mbid, isrc);
+            return Ok(isrc);
+        }
+
+        let title = clean_enclosure(&clean_enclosure(&song.name, '(', ')'), '[', ']');
+        let artist = song
+            .artists
+            .first()
+            .map(|a| format!(" AND artist:\"{}\"", escape_query(&a.name)))
+            .unwrap_or_default();
+        let query = format!("recording:\"{}\"{}", escape_query(&title), artist);
+        let res: MusicBrainzResponse = self
+            .client
+            .get(MUSICBRAINZ_URL)
+            .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "10")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let isrc = pick_isrc(song, &res.recordings, self.duration_tolerance_ms);
+        debug!("MusicBrainz ISRC of {}: {:?}", song, isrc);
+        Ok(isrc)
+    }
+}
+
+/// Escape a phrase of a `MusicBrainz` (Lucene) search query
+fn escape_query(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// ISRC of the first recording that is the same song, with the same checks as `Song::compare`
+fn pick_isrc(song: &Song, recordings: &[MusicBrainzRecording], duration_tolerance_ms: usize) -> Option<String> {
+    let title = song.clean_name();
+    let artists: Vec<String> = song.artists.iter().map(Artist::clean_name).collect();
+    recordings
+        .iter()
+        .filter(|r| r.score >= MIN_SCORE && !r.isrcs.is_empty())
+        .filter(|r| normalized_levenshtein(&normalize_title(&r.title).0, &title) >= 0.8)
+        .filter(|r| {
+            r.length
+                .is_none_or(|l| song.duration_ms == 0 || l.abs_diff(song.duration_ms) <= duration_tolerance_ms)
+        })
+        .filter(|r| {
+            artists.is_empty()
+                || r.artist_credit.iter().any(|c| {
+                    let name = generic_name_clean(&c.name);
+                    artists.iter().any(|a| normalized_levenshtein(a, &name) >= 0.8)
+                })
+        })
+        .find_map(|r| clean_isrc(r.isrcs.first().cloned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{credited, song};
+
+    #[test]
+    fn test_pick_isrc() {
+        let song = Song {
+            artists: vec![credited("Queen")],
+            duration_ms: 355_000,
+            ..song(MusicApiType::YtMusic, "fJ9rUzIMcZQ", "Bohemian Rhapsody (Remastered 2011)")
+        };
+        let response = r#"{
+            "recordings": [
+                {
+                    "score": 100,
+                    "title": "Bohemian Rhapsody (live at Wembley)",
+                    "length": 362000,
+                    "artist-credit": [{"name": "Queen"}],
+                    "isrcs": ["GBUM71029605"]
+                },
+                {
+                    "score": 100,
+                    "title": "Bohemian Rhapsody",
+                    "length": 354320,
+                    "artist-credit": [{"name": "Queen"}]
+                },
+                {
+                    "score": 98,
+                    "title": "Bohemian Rhapsody",
+                    "length": 354000,
+                    "artist-credit": [{"name": "Queen"}],
+                    "isrcs": ["GBUM71029604"]
+                }
+            ]
+        }"#;
+        let response: MusicBrainzResponse = serde_json::from_str(response).unwrap();
+
+        // the live version is too long, the studio recording without ISRC is skipped
+        assert_eq!(pick_isrc(&song, &response.recordings, 5000), Some("GBUM71029604".to_string()));
+        assert_eq!(pick_isrc(&song, &response.recordings, 500), None);
+
+        let mut other = song.clone();
+        other.artists[0].name = "Panic! at the Disco".to_string();
+        assert_eq!(pick_isrc(&other, &response.recordings, 5000), None);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_cached() {
+        let song = |id: &str, source: MusicApiType| {
+            let mut song = crate::mock_api::song(source, id, id);
+            song.duration_ms = 0;
+            song
+        };
+        let mut cache = IsrcCache::default();
+        cache.isrcs.insert("v1".to_string(), Some("GBUM71029604".to_string()));
+        cache.isrcs.insert("v2".to_string(), None);
+        let mut enricher = IsrcEnricher::new(cache, 5000).unwrap();
+        // lookups would fail, as if MusicBrainz was unavailable
+        enricher.failures = MAX_FAILURES;
+
+        let mut songs = vec![
+            song("v1", MusicApiType::YtMusic),
+            song("v2", MusicApiType::YtMusic),
+            song("v3", MusicApiType::YtMusic),
+            song("v1", MusicApiType::Spotify),
+        ];
+        assert_eq!(enricher.enrich(&mut songs, |_| false).await, 1);
+        let hints: Vec<Option<&str>> = songs.iter().map(|s| s.isrc_hint.as_deref()).collect();
+        assert_eq!(hints, vec![Some("GBUM71029604"), None, None, None]);
+        // the ISRC of the song is left alone
+        assert!(songs.iter().all(|s| s.isrc.is_none()));
+
+        songs[0].isrc_hint = None;
+        assert_eq!(enricher.enrich(&mut songs, |s| s.id == "v1").await, 0);
+
+        // looked up by their MusicBrainz id
+        enricher
+            .cache
+            .isrcs
+            .insert("b1a9c0e9-d987-4042-ae91-78d6a3267d69".to_string(), Some("GBUM71029604".to_string()));
+        let mut plex = vec![song("1", MusicApiType::Plex), song("2", MusicApiType::Plex)];
+        plex[0].mbid = Some("b1a9c0e9-d987-4042-ae91-78d6a3267d69".to_string());
+        assert_eq!(enricher.enrich(&mut plex, |_| false).await, 1);
+        assert_eq!(plex[0].isrc_hint.as_deref(), Some("GBUM71029604"));
+        assert_eq!(plex[1].isrc_hint, None);
+    }
+}
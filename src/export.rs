@@ -1,21 +1,340 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::Write;
 use std::path::Path;
 
-use color_eyre::eyre::Result;
-use tracing::info;
+use clap::ValueEnum;
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
-use crate::music_api::DynMusicApi;
+use crate::music_api::{DynMusicApi, MusicApiType, Playlist, SavedAlbum, Song};
+use crate::utils::unix_timestamp;
 
-pub async fn export(mut src_api: DynMusicApi, output: &Path, minify: bool) -> Result<()> {
+/// Version of the export files written, the first exports were a bare array of playlists
+pub const EXPORT_VERSION: u32 = 2;
+
+/// Format of the exported playlists, `--format`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// Versioned JSON file, that can be imported back
+    #[default]
+    Json,
+    /// CSV file with a row per song of the playlists, for spreadsheets
+    Csv,
+    /// M3U8 file per playlist in the output directory, for media players
+    M3u,
+}
+
+/// Content of an export file
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Export {
+    pub version: u32,
+    /// Platform the export comes from, `None` in the first exports
+    pub source: Option<MusicApiType>,
+    /// Unix timestamp of the export, `None` in the first exports
+    pub exported_at: Option<u64>,
+    pub playlists: Vec<Playlist>,
+    /// Liked songs, with `--include-likes`
+    #[serde(default)]
+    pub likes: Vec<Song>,
+    /// Saved albums, with `--include-albums`
+    #[serde(default)]
+    pub albums: Vec<SavedAlbum>,
+}
+
+/// Formats of the export files
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExportFile {
+    Envelope(Export),
+    Playlists(Vec<Playlist>),
+}
+
+impl Export {
+    /// Read an export file of any version
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: ExportFile = serde_json::from_reader(std::fs::File::open(path)?)
+            .map_err(|e| eyre!("invalid export file {}: {}", path.display(), e))?;
+        let export = match file {
+            ExportFile::Envelope(export) => export,
+            ExportFile::Playlists(playlists) => Self {
+                version: 1,
+                source: None,
+                exported_at: None,
+                playlists,
+                likes: vec![],
+                albums: vec![],
+            },
+        };
+        if export.version > EXPORT_VERSION {
+            return Err(eyre!(
+                "export file {} has version {}, this version of SyncDisBoi reads up to version {}",
+                path.display(),
+                export.version,
+                EXPORT_VERSION
+            ));
+        }
+        Ok(export)
+    }
+}
+
+pub async fn export(
+    mut src_api: DynMusicApi,
+    output: &Path,
+    format: ExportFormat,
+    minify: bool,
+    include_likes: bool,
+    include_albums: bool,
+) -> Result<()> {
     info!("retrieving playlists...");
-    let src_playlists = src_api.get_playlists_full().await?;
+    let playlists = src_api.get_playlists_full().await?;
 
+    match format {
+        ExportFormat::Json => {}
+        ExportFormat::Csv => {
+            info!("exporting playlists...");
+            let mut file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            write_csv(&playlists, &mut file)?;
+            file.flush()?;
+        }
+        ExportFormat::M3u => {
+            info!("exporting playlists...");
+            std::fs::create_dir_all(output)?;
+            let mut used = HashSet::new();
+            for playlist in &playlists {
+                let file_name = m3u_file_name(&playlist.name, &mut used);
+                let (content, skipped) = m3u_playlist(playlist);
+                if skipped > 0 {
+                    warn!(
+                        "{} songs of playlist \"{}\" have no URL and were left out",
+                        skipped, playlist.name
+                    );
+                }
+                std::fs::write(output.join(file_name), content)?;
+            }
+        }
+    }
+    if format != ExportFormat::Json {
+        if include_likes || include_albums {
+            warn!("likes and albums are only exported in JSON, ignoring --include-likes and --include-albums");
+        }
+        info!("successfully exported {} playlists to: {:?}", playlists.len(), output);
+        return Ok(());
+    }
+
+    let mut likes = vec![];
+    if include_likes {
+        info!("retrieving likes...");
+        likes = src_api.get_likes().await?;
+    }
+    let mut albums = vec![];
+    if include_albums {
+        if src_api.supports_saved_albums() {
+            info!("retrieving saved albums...");
+            albums = src_api.get_saved_albums().await?;
+        } else {
+            warn!("{:?} does not support saved albums, ignoring --include-albums", src_api.api_type());
+        }
+    }
+
+    let export = Export {
+        version: EXPORT_VERSION,
+        source: Some(src_api.api_type()),
+        exported_at: Some(unix_timestamp()),
+        playlists,
+        likes,
+        albums,
+    };
     info!("exporting playlists...");
     if minify {
-        serde_json::to_writer(std::fs::File::create(output)?, &src_playlists)?;
+        serde_json::to_writer(std::fs::File::create(output)?, &export)?;
     } else {
-        serde_json::to_writer_pretty(std::fs::File::create(output)?, &src_playlists)?;
+        serde_json::to_writer_pretty(std::fs::File::create(output)?, &export)?;
     }
-    info!("successfully exported playlists to: {:?}", output);
+    info!(
+        "successfully exported {} playlists, {} likes and {} albums to: {:?}",
+        export.playlists.len(),
+        export.likes.len(),
+        export.albums.len(),
+        output
+    );
 
     Ok(())
 }
+
+/// Write a row per song of the playlists, after a header row
+fn write_csv(playlists: &[Playlist], out: impl Write) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new().terminator(csv::Terminator::CRLF).from_writer(out);
+    writer.write_record(["playlist", "title", "artists", "album", "duration_ms", "isrc", "source", "id"])?;
+    for playlist in playlists {
+        for song in &playlist.songs {
+            let artists: Vec<&str> = song.artists.iter().map(|a| a.name.as_str()).collect();
+            writer.write_record([
+                playlist.name.as_str(),
+                song.name.as_str(),
+                &artists.join(", "),
+                song.album.as_ref().map_or("", |a| a.name.as_str()),
+                &song.duration_ms.to_string(),
+                song.isrc.as_deref().unwrap_or_default(),
+                song.source.short_name(),
+                song.id.as_str(),
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Extended M3U playlist of the songs with a URL, and the number of songs without one (Plex
+/// songs, Spotify local files)
+fn m3u_playlist(playlist: &Playlist) -> (String, usize) {
+    // entries are a line each, the line breaks of the names would start a new entry
+    let one_line = |s: &str| s.replace(['\r', '\n'], " ");
+    let mut content = format!("#EXTM3U\n#PLAYLIST:{}\n", one_line(&playlist.name));
+    let mut skipped = 0;
+    for song in &playlist.songs {
+        let Some(url) = song.url() else {
+            skipped += 1;
+            continue;
+        };
+        let artists: Vec<&str> = song.artists.iter().map(|a| a.name.as_str()).collect();
+        let title = if artists.is_empty() {
+            one_line(&song.name)
+        } else {
+            one_line(&format!("{} - {}", artists.join(", "), song.name))
+        };
+        let _ = writeln!(content, "#EXTINF:{},{}\n{}", song.duration_ms / 1000, title, url);
+    }
+    (content, skipped)
+}
+
+/// File name of the M3U8 file of a playlist, without the characters forbidden in file names.
+/// Playlists with the same name are numbered
+fn m3u_file_name(name: &str, used: &mut HashSet<String>) -> String {
+    let clean: String = name
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    let clean = clean.trim_matches([' ', '.']);
+    let stem = if clean.is_empty() { "playlist" } else { clean };
+    let mut file_name = format!("{}.m3u8", stem);
+    let mut n = 2;
+    while !used.insert(file_name.to_lowercase()) {
+        file_name = format!("{} ({}).m3u8", stem, n);
+        n += 1;
+    }
+    file_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{MockApi, album, credited, playlist, song, test_dir};
+    use crate::music_api::Album;
+
+    #[tokio::test]
+    async fn test_export_formats() {
+        let path = test_dir("export").join("export.json");
+        let exported = song(MusicApiType::Spotify, "s1", "First");
+        let mut api = MockApi::new(MusicApiType::Spotify, vec![]);
+        api.library = true;
+        {
+            let mut state = api.state.lock().unwrap();
+            state.playlists.push(playlist("p1", "Chill", vec![exported.clone()]));
+            state.likes.push(exported.clone());
+            state.saved_albums.push(SavedAlbum {
+                source: MusicApiType::Spotify,
+                album: Album {
+                    id: Some("a1".to_string()),
+                    name: "Album".to_string(),
+                    year: Some(2020),
+                },
+                artists: exported.artists.clone(),
+                upc: Some("00602577000000".to_string()),
+            });
+        }
+
+        export(api.boxed(), &path, ExportFormat::Json, false, true, true).await.unwrap();
+        let file = Export::load(&path).unwrap();
+        assert_eq!(file.version, EXPORT_VERSION);
+        assert_eq!(file.source, Some(MusicApiType::Spotify));
+        assert!(file.exported_at.is_some());
+        assert_eq!(file.playlists[0].songs, vec![exported.clone()]);
+        assert_eq!(file.likes, vec![exported.clone()]);
+        assert_eq!(file.albums.len(), 1);
+        assert_eq!(file.albums[0].upc.as_deref(), Some("00602577000000"));
+
+        // likes and albums are only exported when asked
+        export(api.boxed(), &path, ExportFormat::Json, true, false, false).await.unwrap();
+        let file = Export::load(&path).unwrap();
+        assert!(file.likes.is_empty() && file.albums.is_empty());
+
+        // exports of the previous versions, a bare array of playlists
+        std::fs::write(&path, serde_json::to_string(&vec![playlist("p1", "Chill", vec![exported])]).unwrap())
+            .unwrap();
+        let file = Export::load(&path).unwrap();
+        assert_eq!(file.version, 1);
+        assert_eq!(file.source, None);
+        assert_eq!(file.playlists[0].name, "Chill");
+        assert!(file.likes.is_empty());
+
+        std::fs::write(&path, r#"{"version": 3, "source": "Tidal", "exported_at": 0, "playlists": []}"#).unwrap();
+        assert!(Export::load(&path).unwrap_err().to_string().contains("has version 3"));
+    }
+
+    fn tricky_playlist() -> Playlist {
+        let mut quoted = song(MusicApiType::YtMusic, "dQw4w9WgXcQ", "Say \"Hello\", World");
+        quoted.artists[0].name = "Simon, Garfunkel".to_string();
+        quoted.isrc = Some("USUM71703861".to_string());
+        quoted.album = Some(album("Live\nin Paris"));
+        let mut plain = song(MusicApiType::Spotify, "4uLU6hMCjMI75M1A2tKUQC", "Plain");
+        plain.artists.push(credited("Guest"));
+        let plex = song(MusicApiType::Plex, "123", "Server only");
+        playlist("p1", "Road trip: \"best\"", vec![quoted, plain, plex])
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let mut out = vec![];
+        write_csv(&[tricky_playlist()], &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(rows[0], "playlist,title,artists,album,duration_ms,isrc,source,id");
+        assert_eq!(
+            rows[1],
+            "\"Road trip: \"\"best\"\"\",\"Say \"\"Hello\"\", World\",\"Simon, Garfunkel\",\
+            \"Live\nin Paris\",180000,USUM71703861,ytmusic,dQw4w9WgXcQ"
+        );
+        assert_eq!(
+            rows[2],
+            "\"Road trip: \"\"best\"\"\",Plain,\"Artist, Guest\",,180000,,spotify,4uLU6hMCjMI75M1A2tKUQC"
+        );
+        assert!(rows[3].ends_with(",plex,123"));
+        assert_eq!(rows[4], "");
+    }
+
+    #[test]
+    fn test_m3u_export() {
+        let mut tricky = tricky_playlist();
+        tricky.songs[1].name = "Two\r\nlines".to_string();
+        let (content, skipped) = m3u_playlist(&tricky);
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            content,
+            "#EXTM3U\n\
+            #PLAYLIST:Road trip: \"best\"\n\
+            #EXTINF:180,Simon, Garfunkel - Say \"Hello\", World\n\
+            https://music.youtube.com/watch?v=dQw4w9WgXcQ\n\
+            #EXTINF:180,Artist, Guest - Two  lines\n\
+            https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC\n"
+        );
+
+        let mut used = HashSet::new();
+        assert_eq!(m3u_file_name("Road trip: \"best\"", &mut used), "Road trip_ _best_.m3u8");
+        assert_eq!(m3u_file_name("AC/DC\n", &mut used), "AC_DC_.m3u8");
+        assert_eq!(m3u_file_name("ac/dc\n", &mut used), "ac_dc_ (2).m3u8");
+        assert_eq!(m3u_file_name("AC/DC\t", &mut used), "AC_DC_ (3).m3u8");
+        assert_eq!(m3u_file_name(" .. ", &mut used), "playlist.m3u8");
+    }
+}
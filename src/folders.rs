@@ -0,0 +1,106 @@
+//! Playlist folders given with `--folder-map`. The source playlists are put in a folder by name,
+//! the folders missing on the destination platform are created before the playlists
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use color_eyre::eyre::{Result, eyre};
+
+use crate::music_api::Playlist;
+
+/// Source playlist name -> folder path, stored as JSON at the path given with `--folder-map`
+#[derive(Debug, Default, PartialEq)]
+pub struct FolderMap(pub BTreeMap<String, String>);
+
+/// Folder path with its names trimmed and the empty ones removed, `None` for the top level
+fn normalize(path: &str) -> Option<String> {
+    let names: Vec<&str> = path.split('/').map(str::trim).filter(|n| !n.is_empty()).collect();
+    (!names.is_empty()).then(|| names.join("/"))
+}
+
+impl FolderMap {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| eyre!("failed to open folder map {}: {}", path.display(), e))?;
+        let map: BTreeMap<String, String> = serde_json::from_reader(file)
+            .map_err(|e| eyre!("invalid folder map {}: {}", path.display(), e))?;
+        Ok(Self(map))
+    }
+
+    /// Set the folder of the mapped playlists, a playlist mapped to an empty path is moved to
+    /// the top level
+    pub fn assign(&self, playlists: &mut [Playlist]) {
+        for playlist in playlists {
+            if let Some(path) = self.0.get(&playlist.name) {
+                playlist.folder = normalize(path);
+            }
+        }
+    }
+}
+
+/// Folders to create so that every path of `wanted` exists, parents before their children
+pub fn folders_to_create<'a>(wanted: impl IntoIterator<Item = &'a str>, existing: &[String]) -> Vec<String> {
+    let mut missing = BTreeSet::new();
+    for path in wanted.into_iter().filter_map(normalize) {
+        let mut parent = String::new();
+        for name in path.split('/') {
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent.push_str(name);
+            if !existing.contains(&parent) {
+                missing.insert(parent.clone());
+            }
+        }
+    }
+    // a path sorts before the paths it's a prefix of, the parents come first
+    missing.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{playlist, test_dir};
+
+    #[test]
+    fn test_folder_assignment() {
+        let map = FolderMap(BTreeMap::from([
+            ("Chill".to_string(), " Moods / Calm ".to_string()),
+            ("Running".to_string(), "Sport".to_string()),
+            ("Old".to_string(), "/".to_string()),
+        ]));
+        let mut playlists = vec![
+            playlist("p1", "Chill", vec![]),
+            playlist("p2", "Running", vec![]),
+            playlist("p3", "Old", vec![]),
+            playlist("p4", "Unmapped", vec![]),
+        ];
+        playlists[2].folder = Some("Archive".to_string());
+        playlists[3].folder = Some("Archive".to_string());
+        map.assign(&mut playlists);
+        let folders: Vec<Option<&str>> = playlists.iter().map(|p| p.folder.as_deref()).collect();
+        assert_eq!(folders, vec![Some("Moods/Calm"), Some("Sport"), None, Some("Archive")]);
+
+        let path = test_dir("folder_map").join("folders.json");
+        std::fs::write(&path, r#"{"Chill": "Moods"}"#).unwrap();
+        assert_eq!(
+            FolderMap::load(&path).unwrap(),
+            FolderMap(BTreeMap::from([("Chill".to_string(), "Moods".to_string())]))
+        );
+        std::fs::write(&path, r#"["Chill"]"#).unwrap();
+        assert!(FolderMap::load(&path).unwrap_err().to_string().contains("invalid folder map"));
+    }
+
+    #[test]
+    fn test_folders_to_create() {
+        let existing = vec!["Sport".to_string(), "Moods".to_string()];
+        let wanted = ["Sport/Running/Trail", "Moods", "Work/Focus", "Sport/Running", "Work", "", "Archive"];
+        assert_eq!(
+            folders_to_create(wanted, &existing),
+            vec!["Archive", "Sport/Running", "Sport/Running/Trail", "Work", "Work/Focus"]
+        );
+        // nothing to create once they exist
+        let existing: Vec<String> = folders_to_create(wanted, &existing).into_iter().chain(existing).collect();
+        assert!(folders_to_create(wanted, &existing).is_empty());
+    }
+}
@@ -1,21 +1,243 @@
 use std::path::Path;
 
 use color_eyre::eyre::Result;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::ConfigArgs;
-use crate::music_api::{DynMusicApi, Playlist};
-use crate::sync::synchronize_playlists;
+use crate::export::Export;
+use crate::likes::like_songs;
+use crate::music_api::{DynMusicApi, MusicApiType, Playlist, Song};
+use crate::playlist_files::{self, CsvColumns, ImportFormat};
+use crate::sync::{PlaylistFilter, save_albums, save_report, synchronize_playlists};
+use crate::utils::utc_date;
 
-pub async fn import(src_json: &Path, mut dst_api: DynMusicApi, config: ConfigArgs, skip_playlists: Vec<String>, dst_owner: String) -> Result<()> {
-    let src_playlists: Vec<Playlist> = serde_json::from_reader(std::fs::File::open(src_json)?)?;
+/// Song of an export whose id no longer exists on its platform
+#[derive(Debug)]
+pub struct Relink {
+    pub playlist: String,
+    pub old: Song,
+    /// Current version of the song, `None` when no replacement was found
+    pub new: Option<Song>,
+}
+
+pub async fn import(
+    input: &Path,
+    format: ImportFormat,
+    columns: &CsvColumns,
+    mut dst_api: DynMusicApi,
+    config: ConfigArgs,
+    filter: PlaylistFilter,
+    dst_owner: String,
+) -> Result<()> {
+    let (source, playlists, likes, albums) = if format == ImportFormat::Json {
+        let export = Export::load(input)?;
+        if let (Some(source), Some(exported_at)) = (&export.source, export.exported_at) {
+            info!("importing the export of {:?} from {}", source, utc_date(exported_at));
+        }
+        (export.source, export.playlists, export.likes, export.albums)
+    } else {
+        // songs listed by their metadata, searched like the songs of another platform
+        (Some(MusicApiType::File), playlist_files::load(input, format, columns)?, vec![], vec![])
+    };
+    let mut src_playlists = filter.select(playlists)?;
+
+    if dst_api.supports_song_lookup() {
+        info!("verifying exported song ids...");
+        relink_songs(&mut dst_api, &mut src_playlists).await?;
+    }
 
     info!("importing playlists...");
-    synchronize_playlists(src_playlists, &mut dst_api, &config, skip_playlists, dst_owner).await?;
+    let mut report = synchronize_playlists(src_playlists, source, &mut dst_api, &config, dst_owner).await?;
     info!(
         "successfully imported playlists to {:?}",
         dst_api.api_type()
     );
 
+    // the likes and albums of the export are imported like --sync-likes and --sync-albums do
+    if !likes.is_empty() {
+        info!("importing {} likes...", likes.len());
+        report.push_likes(like_songs(&likes, &mut dst_api, &config).await?);
+        save_report(&mut report, &config)?;
+    }
+    if !albums.is_empty() {
+        info!("importing {} albums...", albums.len());
+        save_albums(&albums, &mut dst_api, &config).await?;
+    }
+
+    if let Some(threshold) = &config.fail_on_missing {
+        report.check_missing(threshold)?;
+    }
     Ok(())
 }
+
+/// Verify the ids of the exported songs coming from the destination platform itself
+///
+/// Songs merged or replaced by the platform since the export are substituted with their current
+/// version, looked up by id or else searched by their metadata. Songs with no replacement are
+/// removed from the playlists so they can't make the whole import fail.
+pub async fn relink_songs(dst_api: &mut DynMusicApi, playlists: &mut [Playlist]) -> Result<Vec<Relink>> {
+    let mut relinks = vec![];
+    for playlist in playlists.iter_mut() {
+        let own_songs: Vec<Song> = playlist
+            .songs
+            .iter()
+            .filter(|s| s.source == dst_api.api_type() && !s.is_local_file())
+            .cloned()
+            .collect();
+        if own_songs.is_empty() {
+            continue;
+        }
+
+        // the relinks of this playlist only, several playlists may share a name
+        let mut playlist_relinks = vec![];
+        let current = dst_api.lookup_songs(&own_songs).await?;
+        for (old, current) in own_songs.into_iter().zip(current) {
+            let new = match current {
+                Some(song) if song.id == old.id => continue,
+                Some(song) => Some(song),
+                None => dst_api.search_song(&old).await?,
+            };
+            if let Some(new) = &new {
+                info!(
+                    "song \"{}\" in playlist \"{}\" was relinked: {} -> {}",
+                    old, playlist.name, old.id, new.id
+                );
+            } else {
+                warn!(
+                    "song \"{}\" ({}) in playlist \"{}\" no longer exists and was not found, skipping it",
+                    old, old.id, playlist.name
+                );
+            }
+            playlist_relinks.push(Relink {
+                playlist: playlist.name.clone(),
+                old,
+                new,
+            });
+        }
+
+        for relink in &playlist_relinks {
+            let Some(i) = playlist.songs.iter().position(|s| s.id == relink.old.id) else {
+                continue;
+            };
+            if let Some(new) = &relink.new {
+                playlist.songs[i] = new.clone();
+            } else {
+                playlist.songs.remove(i);
+            }
+        }
+        relinks.extend(playlist_relinks);
+    }
+
+    if !relinks.is_empty() {
+        info!(
+            "{} exported songs no longer exist, {} of them were relinked",
+            relinks.len(),
+            relinks.iter().filter(|r| r.new.is_some()).count()
+        );
+    }
+    Ok(relinks)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::export::EXPORT_VERSION;
+    use crate::mock_api::{MockApi, playlist, saved_album, song, test_dir};
+    use crate::report::SyncReport;
+
+    #[tokio::test]
+    async fn test_relink_dead_ids() {
+        let catalog = vec![
+            song(MusicApiType::Tidal, "1", "Still available"),
+            song(MusicApiType::Tidal, "20", "Merged since the export"),
+        ];
+        let mut api = MockApi::new(MusicApiType::Tidal, catalog);
+        api.lookup = true;
+
+        let exported = vec![
+            song(MusicApiType::Tidal, "1", "Still available"),
+            song(MusicApiType::Tidal, "2", "Merged since the export"),
+            song(MusicApiType::Tidal, "3", "Gone for good"),
+        ];
+        let mut playlists = vec![playlist("p0", "Backup", exported)];
+        let relinks = relink_songs(&mut api.boxed(), &mut playlists).await.unwrap();
+
+        assert_eq!(relinks.len(), 2);
+        assert_eq!(relinks[0].old.id, "2");
+        assert_eq!(relinks[0].new.as_ref().unwrap().id, "20");
+        assert_eq!(relinks[1].old.id, "3");
+        assert!(relinks[1].new.is_none());
+        let ids: Vec<&str> = playlists[0].songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "20"]);
+
+        // playlists sharing a name only get their own relinks
+        let mut homonyms = vec![
+            playlist("p1", "Backup", vec![song(MusicApiType::Tidal, "2", "Merged since the export")]),
+            playlist("p2", "Backup", vec![song(MusicApiType::Tidal, "1", "Still available"), song(MusicApiType::Tidal, "3", "Gone for good")]),
+        ];
+        let relinks = relink_songs(&mut api.boxed(), &mut homonyms).await.unwrap();
+        assert_eq!(relinks.len(), 2);
+        let ids: Vec<Vec<&str>> = homonyms.iter().map(|p| p.songs.iter().map(|s| s.id.as_str()).collect()).collect();
+        assert_eq!(ids, vec![vec!["20"], vec!["1"]]);
+
+        // verified songs are added by id, without searching them again
+        let config_dir = test_dir("import");
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap(), "--no-cache"]);
+        let mut search_api = api.clone();
+        search_api.catalog = std::sync::Arc::new(vec![]);
+        synchronize_playlists(playlists, Some(MusicApiType::Tidal), &mut search_api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        let ids: Vec<&str> = state.playlists[0].songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "20"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_likes_and_albums() {
+        let tidal = MusicApiType::Tidal;
+        let spotify = MusicApiType::Spotify;
+        let catalog = vec![song(tidal.clone(), "1", "Heroes"), song(tidal.clone(), "2", "Creep")];
+        let mut api = MockApi::new(tidal.clone(), catalog);
+        api.library = true;
+        api.rejected = vec!["2".to_string()];
+        api.album_catalog = std::sync::Arc::new(vec![saved_album(&tidal, "t1", "Discovery", "Daft Punk", None)]);
+
+        let dir = test_dir("import_likes");
+        let export = Export {
+            version: EXPORT_VERSION,
+            source: Some(spotify.clone()),
+            exported_at: Some(1_700_000_000),
+            playlists: vec![],
+            likes: vec![song(spotify.clone(), "s1", "Heroes"), song(spotify.clone(), "s2", "Creep")],
+            albums: vec![saved_album(&spotify, "s3", "Discovery", "Daft Punk", None)],
+        };
+        let input = dir.join("export.json");
+        std::fs::write(&input, serde_json::to_string(&export).unwrap()).unwrap();
+        let report_path = dir.join("report.json");
+        let config = ConfigArgs::parse_from([
+            "sync_dis_boi",
+            "--config-dir",
+            dir.to_str().unwrap(),
+            "--no-cache",
+            "--report",
+            report_path.to_str().unwrap(),
+        ]);
+        let columns = CsvColumns::default();
+        import(&input, ImportFormat::Json, &columns, api.clone().boxed(), config, PlaylistFilter::default(), "owner".to_string())
+            .await
+            .unwrap();
+
+        let state = api.state.lock().unwrap();
+        let likes: Vec<&str> = state.likes.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(likes, vec!["1"]);
+        assert_eq!(state.saved_albums.len(), 1);
+        // the songs the platform refused to like are reported
+        let report: SyncReport = serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+        let not_added: Vec<&str> = report.likes.as_ref().unwrap().not_added.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(not_added, vec!["2"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
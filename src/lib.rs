@@ -1,14 +1,48 @@
+pub mod checkpoint;
+pub mod cover;
+pub mod enrich;
 pub mod export;
+pub mod folders;
 pub mod import;
+pub mod likes;
+pub mod mapping;
+pub mod match_cache;
+#[cfg(test)]
+pub(crate) mod mock_api;
 pub mod music_api;
+pub mod overrides;
+pub mod playlist_files;
+pub mod report;
+pub mod retry;
+pub mod review;
+pub mod snapshot_cache;
 pub mod spotify;
 pub mod sync;
 pub mod tidal;
+pub mod triage;
 pub mod utils;
 pub mod yt_music;
 pub mod plex;
 
-use clap::Parser;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{Result, eyre};
+
+use crate::music_api::MatchOptions;
+use crate::retry::RetryPolicy;
+use crate::overrides::Overrides;
+use crate::report::MissingThreshold;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// Only add the missing songs to the destination playlists
+    #[default]
+    Additive,
+    /// Also remove the destination songs that are no longer in the source playlist
+    Mirror,
+}
 
 // TODO: I don't really like depending on clap for the library,
 // but it's the easiest way to share a configuration structure with the bin
@@ -19,6 +53,15 @@ pub struct ConfigArgs {
     #[arg(long, default_value = "false", env = "DEBUG")]
     pub debug: bool,
 
+    /// Directory of the debug files and of the diagnostic files of failed requests, defaults to
+    /// `debug` in the config directory
+    #[arg(long, env = "DEBUG_DIR")]
+    pub debug_dir: Option<PathBuf>,
+
+    /// Number of diagnostic files of failed requests to keep, the oldest are removed
+    #[arg(long, default_value = "50", env = "KEEP_DIAGNOSTICS")]
+    pub keep_diagnostics: usize,
+
     /// Like all songs that will be synchronized on the destination platform
     #[arg(long, default_value = "false")]
     pub like_all: bool,
@@ -27,12 +70,89 @@ pub struct ConfigArgs {
     #[arg(long, default_value = "false")]
     pub sync_likes: bool,
 
+    /// With --sync-likes, also remove the destination likes with no counterpart among the
+    /// source likes
+    #[arg(long, default_value = "false", requires = "sync_likes", env = "PRUNE_LIKES")]
+    pub prune_likes: bool,
+
+    /// Save the albums saved on the source platform on the destination platform, when both
+    /// platforms have saved albums
+    #[arg(long, default_value = "false", env = "SYNC_ALBUMS")]
+    pub sync_albums: bool,
+
+    /// Follow the artists followed on the source platform on the destination platform, when
+    /// both platforms have followed artists
+    #[arg(long, default_value = "false", env = "SYNC_ARTISTS")]
+    pub sync_artists: bool,
+
+    /// Set the cover image of the source playlists on the destination playlists created by the
+    /// synchronization, when the destination platform supports it
+    #[arg(long, default_value = "false", env = "SYNC_COVERS")]
+    pub sync_covers: bool,
+
+    /// Also synchronize the songs uploaded to the source platform, as a playlist named
+    /// "Uploads". Only `YtMusic` has uploaded songs
+    #[arg(long, default_value = "false", env = "INCLUDE_UPLOADS")]
+    pub include_uploads: bool,
+
+    /// Also synchronize the playlists generated by the source platform (mixes, recommendations...)
+    #[arg(long, default_value = "false", env = "INCLUDE_GENERATED")]
+    pub include_generated: bool,
+
+    /// Also synchronize to the collaborative destination playlists owned by other users, the
+    /// others are skipped
+    #[arg(long, default_value = "false", env = "INCLUDE_COLLABORATIVE")]
+    pub include_collaborative: bool,
+
+    /// Create the destination playlists as collaborative playlists, which are always private.
+    /// Only Spotify has collaborative playlists
+    #[arg(long, default_value = "false", env = "COLLABORATIVE")]
+    pub collaborative: bool,
+
+    /// Names of platform-generated playlists, separated by '|'. Only used for the playlists
+    /// whose owner is unknown, the others are detected by their owner
+    #[arg(
+        long,
+        value_delimiter = '|',
+        env = "GENERATED_PLAYLIST_NAMES",
+        default_values = sync::GENERATED_PLAYLIST_NAMES
+    )]
+    pub generated_playlist_names: Vec<String>,
+
     /// Allow the synchronization between platforms with different countries.
     /// Be aware that this can lead to invalid sync results, as some songs will
     /// have different ISRC codes.
     #[arg(long, default_value = "false")]
     pub diff_country: bool,
 
+    /// Maximum difference between the durations of matching songs, in milliseconds. Defaults
+    /// to 5000, and 8000 when searching on Plex whose durations are the ones of the local files
+    #[arg(long, env = "DURATION_TOLERANCE_MS")]
+    pub duration_tolerance_ms: Option<usize>,
+
+    /// Match songs on their name, artists and duration only, ignoring their album
+    #[arg(long, default_value = "false", conflicts_with = "strict", env = "IGNORE_ALBUM")]
+    pub ignore_album: bool,
+
+    /// Only match songs from the same album, even when one of them is a single
+    #[arg(long, default_value = "false", env = "STRICT")]
+    pub strict: bool,
+
+    /// Minimum score, between 0 and 1, of a search result to be picked as the match of a song.
+    /// The score weighs the ISRC, title, artists, duration and album resemblance
+    #[arg(long, default_value_t = MatchOptions::DEFAULT_MIN_SCORE, env = "MIN_MATCH_SCORE")]
+    pub min_match_score: f64,
+
+    /// Weight, between 0 and 1, of the release year in the score of a search result, only
+    /// used when both songs have a year
+    #[arg(long, default_value_t = MatchOptions::DEFAULT_YEAR_WEIGHT, env = "YEAR_WEIGHT")]
+    pub year_weight: f64,
+
+    /// Among the search results of a song, prefer the ones with the same explicit lyrics flag,
+    /// e.g. the explicit version over a clean radio edit
+    #[arg(long, default_value = "false", env = "PREFER_EXPLICIT_MATCH")]
+    pub prefer_explicit_match: bool,
+
     /// Proxy to use for all requests in the format http://<ip>:<port>
     #[arg(long, env = "PROXY")]
     pub proxy: Option<String>,
@@ -40,4 +160,309 @@ pub struct ConfigArgs {
     /// Optionally replace the config directory
     #[arg(long, env = "CONFIG_DIR")]
     pub config_dir: Option<String>,
+
+    /// Number of playlists to synchronize concurrently, only used when the
+    /// destination platform supports it (all but `YtMusic`)
+    #[arg(long, default_value = "1", env = "PARALLEL_PLAYLISTS")]
+    pub parallel_playlists: usize,
+
+    /// Pause after this many `YtMusic` requests to avoid its rate limits, 0 to never pause
+    #[arg(long, default_value = "150", env = "YT_PAUSE_EVERY")]
+    pub yt_pause_every: usize,
+
+    /// Duration of the first `YtMusic` pause in seconds, each following pause is a minute longer
+    #[arg(long, default_value = "180", env = "YT_PAUSE_SECS")]
+    pub yt_pause_secs: u64,
+
+    /// Retries of a rate limited `YtMusic` request before giving up, the wait before each
+    /// retry doubles from a minute. Defaults to `--max-retries`
+    #[arg(long, env = "YT_MAX_RETRIES")]
+    pub yt_max_retries: Option<u32>,
+
+    /// Longest wait before retrying a rate limited `YtMusic` request, in seconds. Defaults to
+    /// `--max-backoff-secs`, or 900
+    #[arg(long, env = "YT_MAX_BACKOFF_SECS")]
+    pub yt_max_backoff_secs: Option<u64>,
+
+    /// Hours during which the `YtMusic` visitor id saved in the headers file is reused instead
+    /// of being fetched from the homepage again
+    #[arg(long, default_value = "168", env = "YT_VISITOR_ID_TTL_HOURS")]
+    pub yt_visitor_id_ttl_hours: u64,
+
+    /// Number of songs added to a `YtMusic` playlist with a single request
+    #[arg(long, default_value = "50", env = "YT_ADD_CHUNK_SIZE")]
+    pub yt_add_chunk_size: usize,
+
+    /// Check that the videos exist before adding them to a `YtMusic` playlist, with one request
+    /// per song. Catches the wrong video ids of overrides and import files
+    #[arg(long, default_value = "false", env = "VERIFY_IDS")]
+    pub verify_ids: bool,
+
+    /// Delay between two `YtMusic` like requests, in milliseconds
+    #[arg(long, default_value = "250", env = "YT_LIKE_DELAY_MS")]
+    pub yt_like_delay_ms: u64,
+
+    /// Fraction of the `YtMusic` likes, between 0 and 1, that can fail with only a warning
+    /// instead of an error once all the songs were liked
+    #[arg(long, default_value = "0.0", env = "YT_LIKE_FAILURE_TOLERANCE")]
+    pub yt_like_failure_tolerance: f64,
+
+    /// Index of the Google account of the `YtMusic` cookies to use (x-goog-authuser), defaults
+    /// to the one of the headers file
+    #[arg(long, env = "YT_AUTH_USER")]
+    pub yt_auth_user: Option<u32>,
+
+    /// Id of the `YtMusic` brand account to act on behalf of instead of the main account,
+    /// as shown in the advanced settings of the account
+    #[arg(long, env = "YT_BRAND_ACCOUNT")]
+    pub yt_brand_account: Option<String>,
+
+    /// Language of the `YtMusic` metadata (`hl`), e.g. `ja` for titles in Japanese script
+    /// instead of romanized ones
+    #[arg(long, default_value = "en", env = "YT_LANG")]
+    pub yt_lang: String,
+
+    /// Region of the `YtMusic` catalog (`gl`), e.g. `JP`, defaults to the country of the account
+    /// with browser headers, and to the region of the IP address with `OAuth`
+    #[arg(long, env = "YT_REGION")]
+    pub yt_region: Option<String>,
+
+    /// Retries of a rate limited (429) or failed (5xx) Tidal request before giving up.
+    /// Defaults to `--max-retries`
+    #[arg(long, env = "TIDAL_MAX_RETRIES")]
+    pub tidal_max_retries: Option<u32>,
+
+    /// Longest wait before retrying a Tidal request, in seconds. The waits double from 2
+    /// seconds, except for the rate limits with a `Retry-After`, which wait for the time it
+    /// asks. Both are capped at this value. Defaults to `--max-backoff-secs`, or 300
+    #[arg(long, env = "TIDAL_MAX_BACKOFF_SECS")]
+    pub tidal_max_backoff_secs: Option<u64>,
+
+    /// Fail a whole chunk of Tidal likes when one of its tracks is not available, instead of
+    /// skipping the unavailable tracks
+    #[arg(long, default_value = "false", env = "TIDAL_STRICT_LIKES")]
+    pub tidal_strict_likes: bool,
+
+    /// Name of the Tidal playlist folder where the playlists are created, created when missing.
+    /// The playlists are created at the root of the collection without it
+    #[arg(long, env = "TIDAL_FOLDER")]
+    pub tidal_folder: Option<String>,
+
+    /// Items requested at a time from the Plex server, the playlists and the searches are
+    /// fetched page by page
+    #[arg(long, default_value = "500", env = "PLEX_PAGE_SIZE")]
+    pub plex_page_size: usize,
+
+    /// Songs added at a time to a Plex playlist, in a single request
+    #[arg(long, default_value = "200", env = "PLEX_ADD_CHUNK_SIZE")]
+    pub plex_add_chunk_size: usize,
+
+    /// Synchronize the playlists to collections of tracks of the Plex music library instead of
+    /// Plex playlists
+    #[arg(long, default_value = "false", env = "PLEX_AS_COLLECTIONS")]
+    pub plex_as_collections: bool,
+
+    /// Lowest star rating of the Plex tracks considered liked, from 0 to 10 (half stars
+    /// included). The liked songs are rated 10
+    #[arg(long, default_value = "9", env = "PLEX_LIKE_RATING")]
+    pub plex_like_rating: f32,
+
+    /// Retries of a rate limited (429) or failed (5xx) request before giving up, on every
+    /// platform. Defaults to 5
+    #[arg(long, env = "MAX_RETRIES")]
+    pub max_retries: Option<u32>,
+
+    /// Longest wait before retrying a request, in seconds, on every platform. The waits asked
+    /// by the rate limits are capped to it too. Defaults to 900 on `YtMusic` and 300 elsewhere
+    #[arg(long, env = "MAX_BACKOFF_SECS")]
+    pub max_backoff_secs: Option<u64>,
+
+    /// Number of songs of a playlist searched concurrently on the destination
+    /// platform, `YtMusic` searches are always sequential
+    #[arg(long, default_value = "1", env = "CONCURRENCY")]
+    pub concurrency: usize,
+
+    /// Search songs and compute the synchronization without modifying the
+    /// destination platform, the changes that would be made are printed
+    #[arg(long, default_value = "false")]
+    pub dry_run: bool,
+
+    /// Write the plan computed in dry-run mode to this JSON file, along with a
+    /// human readable version (.txt)
+    #[arg(long, requires = "dry_run")]
+    pub dry_run_report: Option<PathBuf>,
+
+    /// Add exactly the songs of a plan written with --dry-run-report, without
+    /// searching them again
+    #[arg(long, conflicts_with = "dry_run")]
+    pub apply: Option<PathBuf>,
+
+    /// Write a JSON report of the synchronization to this file: per-playlist matched,
+    /// missing and added songs, along with run totals
+    #[arg(long, env = "REPORT")]
+    pub report: Option<PathBuf>,
+
+    /// Exit with an error once synchronized when more songs than this couldn't be matched,
+    /// either a number of songs or a percentage of the searched songs like 5%
+    #[arg(long, env = "FAIL_ON_MISSING")]
+    pub fail_on_missing: Option<MissingThreshold>,
+
+    /// Synchronize into existing destination playlists even when they look unrelated
+    /// to the source playlist (see --pairing-size-ratio and --pairing-min-overlap)
+    #[arg(long, default_value = "false")]
+    pub force_pairing: bool,
+
+    /// A destination playlist this many times bigger than the source playlist is
+    /// suspicious when it shares too few songs with it
+    #[arg(long, default_value = "3.0")]
+    pub pairing_size_ratio: f64,
+
+    /// Minimum fraction of the source songs found in a suspicious destination
+    /// playlist for the pairing to be accepted
+    #[arg(long, default_value = "0.1")]
+    pub pairing_min_overlap: f64,
+
+    /// How destination playlists are updated
+    #[arg(long, value_enum, default_value_t = SyncMode::Additive)]
+    pub mode: SyncMode,
+
+    /// After synchronizing a playlist, remove the destination songs that duplicate an earlier
+    /// song of the playlist: same ISRC, or same title, primary artist and duration
+    #[arg(long, default_value = "false", env = "DEDUP_DST")]
+    pub dedup_dst: bool,
+
+    /// Don't add the "Synced from `<platform>` by `SyncDisBoi` on `<date>`" line to the description of
+    /// the destination playlists
+    #[arg(long, default_value = "false")]
+    pub no_provenance: bool,
+
+    /// Search every song again instead of using the matches cached by previous runs
+    #[arg(long, default_value = "false")]
+    pub no_cache: bool,
+
+    /// Fetch the songs of every source playlist, even the Spotify playlists unchanged since
+    /// the last run, and synchronize the ones already synchronized at their current version
+    #[arg(long, default_value = "false", env = "FORCE_REFRESH")]
+    pub force_refresh: bool,
+
+    /// JSON file mapping source playlist names to folder paths (folder names joined by "/").
+    /// The playlists are created in their folder on the platforms with folders, the missing
+    /// folders are created
+    #[arg(long, env = "FOLDER_MAP")]
+    pub folder_map: Option<PathBuf>,
+
+    /// Empty the cache of songs matched by previous runs before synchronizing
+    #[arg(long, default_value = "false", conflicts_with = "no_cache")]
+    pub clear_match_cache: bool,
+
+    /// Resume an interrupted synchronization, skipping the playlists it completed
+    #[arg(long, default_value = "false", conflicts_with = "dry_run")]
+    pub resume: bool,
+
+    /// Authorize Spotify by pasting the URL the browser was redirected to, instead of
+    /// receiving it on the callback port. For machines the browser can't reach, e.g. over SSH
+    #[arg(long, default_value = "false", env = "SPOTIFY_CALLBACK_MANUAL")]
+    pub callback_manual: bool,
+
+    /// Seconds to wait for the Spotify authorization on the callback port
+    #[arg(long, default_value_t = 300, env = "SPOTIFY_CALLBACK_TIMEOUT_SECS")]
+    pub callback_timeout_secs: u64,
+
+    /// Let pressing enter check right away whether the app was authorized during a device code
+    /// login, the authorization is polled either way
+    #[arg(long, default_value = "false", env = "PRESS_ENTER")]
+    pub press_enter: bool,
+
+    /// Never prompt for user input, even when stdin is a terminal
+    #[arg(long, default_value = "false", env = "NON_INTERACTIVE")]
+    pub non_interactive: bool,
+
+    /// Choose the destination song among the search results when no match is found,
+    /// ignored when stdin is not a terminal
+    #[arg(long, default_value = "false")]
+    pub interactive: bool,
+
+    /// Number of search results offered for each song in --interactive mode
+    #[arg(long, default_value_t = 5)]
+    pub interactive_candidates: usize,
+
+    /// Manual song matches (JSON, or TOML with a .toml extension), defaults to
+    /// `overrides.json` in the config directory
+    #[arg(long, env = "OVERRIDES")]
+    pub overrides: Option<PathBuf>,
+
+    /// Look up the ISRC of `YtMusic` songs, and of Plex songs with a `MusicBrainz` id, on
+    /// `MusicBrainz`, to search them by ISRC on the destination platform
+    #[arg(long, default_value = "false", env = "ENRICH_ISRC")]
+    pub enrich_isrc: bool,
+}
+
+impl ConfigArgs {
+    pub fn is_interactive(&self) -> bool {
+        !self.non_interactive && atty::is(atty::Stream::Stdin)
+    }
+
+    /// Whether songs with no match are reviewed by the user, see `--interactive`
+    pub fn review_matches(&self) -> bool {
+        self.interactive && self.is_interactive()
+    }
+
+    /// Path of the overrides file, see `--overrides`
+    pub fn overrides_path(&self) -> Result<PathBuf> {
+        match &self.overrides {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.config_dir()?.join(Overrides::FILE_NAME)),
+        }
+    }
+
+    pub fn match_options(&self) -> MatchOptions {
+        self.match_options_over(&MatchOptions::default())
+    }
+
+    /// Match options of the command line, the ones left out taken from `defaults`, the
+    /// `MusicApi::default_match_options` of the platform searched
+    pub fn match_options_over(&self, defaults: &MatchOptions) -> MatchOptions {
+        MatchOptions {
+            duration_tolerance_ms: self.duration_tolerance_ms.unwrap_or(defaults.duration_tolerance_ms),
+            ignore_album: self.ignore_album,
+            strict: self.strict,
+            min_score: self.min_match_score,
+            year_weight: self.year_weight,
+            prefer_explicit: self.prefer_explicit_match,
+            prefer_album: defaults.prefer_album,
+        }
+    }
+
+    /// Retry policy of a platform: its own `max_retries` and `max_backoff_secs` options, then
+    /// `--max-retries` and `--max-backoff-secs`, the ones left out taken from `defaults`
+    pub fn retry_policy(
+        &self,
+        defaults: &RetryPolicy,
+        max_retries: Option<u32>,
+        max_backoff_secs: Option<u64>,
+    ) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: max_retries.or(self.max_retries).unwrap_or(defaults.max_retries),
+            max_backoff: max_backoff_secs
+                .or(self.max_backoff_secs)
+                .map_or(defaults.max_backoff, Duration::from_secs),
+            ..*defaults
+        }
+    }
+
+    pub fn debug_dir(&self) -> Result<PathBuf> {
+        match &self.debug_dir {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.config_dir()?.join("debug")),
+        }
+    }
+
+    pub fn config_dir(&self) -> Result<PathBuf> {
+        if let Some(config_dir) = &self.config_dir {
+            return Ok(PathBuf::from(config_dir));
+        }
+        Ok(dirs::config_dir()
+            .ok_or(eyre!("couldn't find system config dir"))?
+            .join("SyncDisBoi"))
+    }
 }
@@ -0,0 +1,376 @@
+use color_eyre::eyre::Result;
+use tracing::{debug, info, warn};
+
+use crate::ConfigArgs;
+use crate::match_cache::MatchCache;
+use crate::music_api::{DynMusicApi, Song};
+use crate::overrides::Overrides;
+use crate::report::PlaylistReport;
+use crate::sync::{known_match, search_songs};
+use crate::utils::{dedup_songs, progress_bar};
+
+/// Name of the liked songs in the reports and debug files
+pub const LIKES_NAME: &str = "Liked songs";
+/// Number of likes searched between two progress logs
+const PROGRESS_STEP: usize = 100;
+/// Number of songs liked per `add_likes` call
+const LIKE_CHUNK_SIZE: usize = 50;
+
+/// Source likes that are not liked on the destination platform yet, without duplicates
+pub fn likes_to_search(src_likes: &[Song], dst_likes: &[Song]) -> Vec<Song> {
+    let mut songs: Vec<Song> = src_likes
+        .iter()
+        .filter(|s| !dst_likes.contains(s))
+        .cloned()
+        .collect();
+    dedup_songs(&mut songs);
+    songs
+}
+
+/// Destination songs to like among the matches of the source likes, along with the number of
+/// matches that were already liked
+///
+/// Songs without ISRC can match a liked song when their source song was not found in the
+/// destination likes, and several source likes can resolve to the same destination song.
+pub fn new_likes(matched: &[Song], dst_likes: &[Song]) -> (Vec<Song>, usize) {
+    let mut likes: Vec<Song> = vec![];
+    let mut already_liked = 0;
+    for song in matched {
+        if dst_likes.contains(song) {
+            debug!("discrepancy, song already liked: {}", song);
+            already_liked += 1;
+        } else if !likes.iter().any(|l| l.id == song.id) {
+            likes.push(song.clone());
+        }
+    }
+    (likes, already_liked)
+}
+
+/// Destination likes with no counterpart among the source likes
+///
+/// A destination like is kept when it is the same song as a source like, or when it is the
+/// match of a source like found by this run.
+pub fn stale_likes(src_likes: &[Song], dst_likes: &[Song], matched: &[Song]) -> Vec<Song> {
+    dst_likes
+        .iter()
+        .filter(|d| !src_likes.contains(d) && !matched.iter().any(|m| m.id == d.id))
+        .cloned()
+        .collect()
+}
+
+/// Remove the destination likes that are no longer liked on the source platform,
+/// `--prune-likes`
+async fn prune_likes(
+    dst_api: &mut DynMusicApi,
+    src_likes: &[Song],
+    dst_likes: &[Song],
+    matched: &[Song],
+    config: &ConfigArgs,
+) -> Result<()> {
+    if !dst_api.supports_like_removal() {
+        warn!("{:?} does not support removing likes, skipping --prune-likes", dst_api.api_type());
+        return Ok(());
+    }
+    // most likely a failure to retrieve them, pruning would remove every like
+    if src_likes.is_empty() {
+        warn!("no source likes, skipping --prune-likes");
+        return Ok(());
+    }
+
+    let stale = stale_likes(src_likes, dst_likes, matched);
+    if stale.is_empty() {
+        info!("[ok] no stale likes to remove");
+    } else if config.dry_run {
+        info!("[dry-run] would remove {} likes", stale.len());
+        for song in &stale {
+            info!("[dry-run]   - {}", song);
+        }
+    } else {
+        let mut done = 0;
+        for chunk in stale.chunks(LIKE_CHUNK_SIZE) {
+            dst_api.remove_likes(chunk).await?;
+            done += chunk.len();
+            info!("removed {}/{} stale likes", done, stale.len());
+        }
+        info!("[ok] removed stale likes");
+    }
+    Ok(())
+}
+
+/// Like the source likes on the destination platform, `--sync-likes`
+///
+/// Likes are matched like playlist songs: overrides and match cache first, then searched with
+/// the pacing and concurrency of the destination platform.
+pub async fn sync_likes(
+    src_api: &mut DynMusicApi,
+    dst_api: &mut DynMusicApi,
+    config: &ConfigArgs,
+) -> Result<PlaylistReport> {
+    info!("retrieving source likes...");
+    let src_likes = src_api.get_likes().await?;
+    like_songs(&src_likes, dst_api, config).await
+}
+
+/// Like `src_likes`, the likes of the source platform or of an export, on the destination
+/// platform
+pub async fn like_songs(src_likes: &[Song], dst_api: &mut DynMusicApi, config: &ConfigArgs) -> Result<PlaylistReport> {
+    info!("retrieving destination likes...");
+    let dst_likes = dst_api.get_likes().await?;
+
+    let overrides = Overrides::load(&config.overrides_path()?)?;
+    let cache_path = config.config_dir()?.join(MatchCache::FILE_NAME);
+    let cache = if config.no_cache {
+        None
+    } else {
+        Some(std::sync::Mutex::new(MatchCache::load(&cache_path)?))
+    };
+
+    let mut to_search = likes_to_search(src_likes, &dst_likes);
+    let duplicates = src_likes.iter().filter(|s| !dst_likes.contains(s)).count() - to_search.len();
+    to_search.retain(|s| {
+        let blacklisted = overrides.is_blacklisted(s);
+        if blacklisted {
+            debug!("song is blacklisted in the overrides, skipping: {}", s);
+        }
+        !blacklisted
+    });
+
+    info!("searching for {} missing likes on destination platform...", to_search.len());
+    let mut matched = vec![];
+    let mut missing = vec![];
+    let mut searched_count = 0;
+    let progress = progress_bar(to_search.len(), LIKES_NAME);
+    for chunk in to_search.chunks(PROGRESS_STEP) {
+        let lookups: Vec<(&Song, Option<Song>)> = chunk
+            .iter()
+            .map(|s| (s, known_match(s, dst_api, &overrides, cache.as_ref()).map(|(dst_song, _)| dst_song)))
+            .collect();
+        let unknown: Vec<&Song> = lookups
+            .iter()
+            .filter(|(_, known)| known.is_none())
+            .map(|(src_song, _)| *src_song)
+            .collect();
+        let mut searched = search_songs(dst_api, &unknown, config, &progress).await?.into_iter();
+
+        for (src_song, known) in lookups {
+            let dst_song = if known.is_some() {
+                known
+            } else {
+                let dst_song = searched.next().flatten();
+                if let (Some(cache), Some(dst_song)) = (&cache, &dst_song) {
+                    cache.lock().unwrap().insert(src_song, dst_song);
+                }
+                dst_song
+            };
+            if let Some(dst_song) = dst_song {
+                matched.push(dst_song);
+            } else {
+                debug!("no match found for song: {}", src_song);
+                missing.push(src_song.clone());
+            }
+        }
+        if let Some(cache) = &cache {
+            cache.lock().unwrap().save(&cache_path)?;
+        }
+        searched_count += chunk.len();
+        info!("searched {}/{} likes", searched_count, to_search.len());
+    }
+    progress.finish_and_clear();
+
+    let (likes, already_liked) = new_likes(&matched, &dst_likes);
+    let attempts = u32::try_from(to_search.len() - already_liked)?;
+    let success = u32::try_from(matched.len() - already_liked)?;
+    let mut conversion_rate = 1.0;
+    if attempts != 0 {
+        conversion_rate = f64::from(success) / f64::from(attempts);
+        info!(
+            "synchronizing {}/{} ({:.2}%) new likes",
+            success,
+            attempts,
+            conversion_rate * 100.0
+        );
+    }
+
+    let mut not_added: Vec<Song> = vec![];
+    if likes.is_empty() {
+        info!("[ok] no new likes to synchronize");
+    } else if config.dry_run {
+        info!("[dry-run] would like {} songs", likes.len());
+        for song in &likes {
+            info!("[dry-run]   + {}", song);
+        }
+    } else {
+        let mut done = 0;
+        for chunk in likes.chunks(LIKE_CHUNK_SIZE) {
+            not_added.extend(dst_api.add_likes(chunk).await?);
+            done += chunk.len();
+            info!("liked {}/{} songs", done, likes.len());
+        }
+        if not_added.is_empty() {
+            info!("[ok] synchronized new likes");
+        } else {
+            warn!("{} songs could not be liked:", not_added.len());
+            for song in &not_added {
+                warn!("  - {}", song);
+            }
+        }
+    }
+
+    if config.prune_likes {
+        prune_likes(dst_api, src_likes, &dst_likes, &matched, config).await?;
+    }
+
+    Ok(PlaylistReport {
+        src_id: String::new(),
+        src_name: LIKES_NAME.to_string(),
+        dst_name: LIKES_NAME.to_string(),
+        created: false,
+        skipped: false,
+        unchanged: false,
+        unavailable: None,
+        matched: success,
+        attempts,
+        conversion_rate,
+        local_files: missing.iter().filter(|s| s.is_local_file()).cloned().collect(),
+        missing,
+        duplicates_skipped: duplicates + matched.len() - already_liked - likes.len(),
+        added: likes
+            .into_iter()
+            .filter(|l| !not_added.iter().any(|n| n.id == l.id))
+            .collect(),
+        not_added,
+        isrc_enriched: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::mock_api::{MockApi, song, test_dir};
+    use crate::music_api::MusicApiType;
+
+    fn likes(source: &MusicApiType, prefix: &str, names: &[&str]) -> Vec<Song> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| song(source.clone(), &format!("{}{}", prefix, i), name))
+            .collect()
+    }
+
+    #[test]
+    fn test_likes_to_search() {
+        let mut src = likes(&MusicApiType::Spotify, "s", &["Heroes", "Creep", "Lemon", "Africa"]);
+        // liked twice on the source platform
+        src.push(src[2].clone());
+        let dst = likes(&MusicApiType::Tidal, "t", &["Creep", "Hotel California"]);
+
+        let ids: Vec<String> = likes_to_search(&src, &dst).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["s0", "s2", "s3"]);
+        assert!(likes_to_search(&src, &src).is_empty());
+        assert_eq!(likes_to_search(&src, &[]).len(), 4);
+    }
+
+    #[test]
+    fn test_new_likes() {
+        let dst_likes = vec![song(MusicApiType::YtMusic, "m1", "Creep")];
+        let mut matched = likes(&MusicApiType::YtMusic, "m", &["Heroes", "Creep", "Lemon"]);
+        // two source likes resolving to the same destination song
+        matched.push(matched[0].clone());
+
+        let (to_like, already_liked) = new_likes(&matched, &dst_likes);
+        let ids: Vec<&str> = to_like.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["m0", "m2"]);
+        assert_eq!(already_liked, 1);
+    }
+
+    #[test]
+    fn test_stale_likes() {
+        let src = likes(&MusicApiType::Spotify, "s", &["Heroes", "Creep"]);
+        let dst = likes(&MusicApiType::Tidal, "t", &["Creep", "Lemon", "Africa"]);
+        // "Lemon" is the match found for "Heroes" by the search
+        let matched = vec![dst[1].clone()];
+
+        let ids: Vec<String> = stale_likes(&src, &dst, &matched).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["t2"]);
+        assert!(stale_likes(&dst, &dst, &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prune_likes() {
+        let config_dir = test_dir("prune_likes");
+        let config = |args: &[&str]| {
+            let base = ["sync_dis_boi", "--no-cache", "--config-dir", config_dir.to_str().unwrap()];
+            ConfigArgs::parse_from(base.iter().chain(args))
+        };
+
+        let src = MockApi::new(MusicApiType::Spotify, vec![]);
+        src.state.lock().unwrap().likes = likes(&MusicApiType::Spotify, "s", &["Heroes", "Creep"]);
+        let catalog = likes(&MusicApiType::Tidal, "t", &["Heroes", "Creep", "Africa"]);
+        let dst = MockApi::new(MusicApiType::Tidal, catalog.clone());
+        dst.state.lock().unwrap().likes = catalog[1..].to_vec();
+
+        // stale likes are never removed without --prune-likes
+        sync_likes(&mut src.boxed(), &mut dst.boxed(), &config(&["--sync-likes"])).await.unwrap();
+        assert_eq!(dst.state.lock().unwrap().writes, vec!["add_likes 1"]);
+        assert_eq!(dst.state.lock().unwrap().likes.len(), 3);
+
+        let dry_run = config(&["--sync-likes", "--prune-likes", "--dry-run"]);
+        sync_likes(&mut src.boxed(), &mut dst.boxed(), &dry_run).await.unwrap();
+        assert_eq!(dst.state.lock().unwrap().writes.len(), 1);
+
+        let prune = config(&["--sync-likes", "--prune-likes"]);
+        sync_likes(&mut src.boxed(), &mut dst.boxed(), &prune).await.unwrap();
+        {
+            let state = dst.state.lock().unwrap();
+            assert_eq!(state.writes, vec!["add_likes 1", "remove_likes 1"]);
+            let ids: Vec<&str> = state.likes.iter().map(|s| s.id.as_str()).collect();
+            assert_eq!(ids, vec!["t1", "t0"]);
+        }
+
+        // nothing is removed when the source likes are empty
+        src.state.lock().unwrap().likes.clear();
+        sync_likes(&mut src.boxed(), &mut dst.boxed(), &prune).await.unwrap();
+        assert_eq!(dst.state.lock().unwrap().likes.len(), 2);
+
+        assert!(ConfigArgs::try_parse_from(["sync_dis_boi", "--prune-likes"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_likes() {
+        let config_dir = test_dir("likes");
+        std::fs::write(
+            config_dir.join(Overrides::FILE_NAME),
+            r#"{"overrides": {"spotify:s3": {"plex": "pinned"}}}"#,
+        )
+        .unwrap();
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap()]);
+
+        let names: Vec<String> = (1..=120u64)
+            .map(|i| format!("like {}", i.wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let src = MockApi::new(MusicApiType::Spotify, vec![]);
+        src.state.lock().unwrap().likes = likes(&MusicApiType::Spotify, "s", &names);
+        // the last 10 songs are not on the destination platform
+        let dst = MockApi::new(MusicApiType::Plex, likes(&MusicApiType::Plex, "d", &names[..110]));
+        dst.state.lock().unwrap().likes = likes(&MusicApiType::Plex, "d", &names[..2]);
+
+        let report = sync_likes(&mut src.boxed(), &mut dst.boxed(), &config).await.unwrap();
+        assert_eq!((report.matched, report.attempts, report.missing.len()), (108, 118, 10));
+        {
+            let state = dst.state.lock().unwrap();
+            // the overridden song isn't searched
+            assert_eq!(state.searches, 117);
+            assert_eq!(state.writes, vec!["add_likes 50", "add_likes 50", "add_likes 8"]);
+            assert!(state.likes.iter().any(|s| s.id == "pinned"));
+        }
+
+        // matches are cached, only the missing likes are searched again
+        dst.state.lock().unwrap().likes.truncate(2);
+        let report = sync_likes(&mut src.boxed(), &mut dst.boxed(), &config).await.unwrap();
+        assert_eq!(report.added.len(), 108);
+        assert_eq!(dst.state.lock().unwrap().searches, 127);
+    }
+}
@@ -1,15 +1,17 @@
 mod args;
 mod build_api;
 
-use std::path::Path;
 
-use args::{MusicPlatformDst, RootArgs};
+use args::{MusicPlatformDst, MusicPlatformSrc, RootArgs};
 use build_api::BuildApi;
 use clap::Parser;
 use color_eyre::eyre::{Result, eyre};
 use sync_dis_boi::export::export;
 use sync_dis_boi::import::import;
+use sync_dis_boi::music_api::DynMusicApi;
 use sync_dis_boi::sync::synchronize;
+use sync_dis_boi::triage::triage;
+use sync_dis_boi::utils::{LogWriter, enable_progress};
 use tracing::{Level, debug, info};
 use tracing_subscriber::filter::Targets;
 use tracing_subscriber::prelude::*;
@@ -28,27 +30,28 @@ async fn main() -> Result<()> {
         .with_max_level(level)
         .with_target(true)
         .without_time()
+        .with_writer(|| LogWriter)
         .finish()
         .with(filter)
         .init();
     debug!("logging level: {}", level);
+    if !args.no_progress && level < Level::DEBUG && atty::is(atty::Stream::Stdout) {
+        enable_progress();
+    }
+
+    let config_dir = args.config.config_dir()?;
+
 
-    let config_dir = if let Ok(env_config_dir) = std::env::var("CONFIG_DIR") {
-        std::path::PathBuf::from(env_config_dir)
-    } else {
-        dirs::config_dir().ok_or(eyre!("couldn't find system config dir"))?.join("SyncDisBoi")
-    };
-    
     if !config_dir.exists() {
         info!("creating SyncDisBoi config directory: {:?}", config_dir);
         std::fs::create_dir_all(&config_dir)?;
     }
 
     if args.config.debug {
-        let debug_dir = Path::new("debug");
+        let debug_dir = args.config.debug_dir()?;
         if !debug_dir.exists() {
-            debug!("creating debug directory");
-            std::fs::create_dir("debug")?;
+            debug!("creating debug directory: {:?}", debug_dir);
+            std::fs::create_dir_all(&debug_dir)?;
         }
     }
 
@@ -56,19 +59,57 @@ async fn main() -> Result<()> {
         info!("skipping playlists: {:?}", args.skip_playlists);
     }
 
+    if let MusicPlatformSrc::Triage { report, dst, overrides, open } = &args.src {
+        let overrides = match overrides {
+            Some(path) => path.clone(),
+            None => args.config.overrides_path()?,
+        };
+        let report = match report {
+            Some(path) => path.clone(),
+            None => args.config.debug_dir()?.join("missing_songs.json"),
+        };
+        return triage(&report, &overrides, dst.clone(), *open, &args.config);
+    }
+
+    let filter = args.playlist_filter();
+    let dst = args.src.get_dst().ok_or(eyre!("missing destination platform"))?;
     let src_api = args.src.parse(&args, &config_dir).await?;
-    match args.src.get_dst() {
-        MusicPlatformDst::Export { output, minify } => {
-            export(src_api, output, *minify).await?;
+    match dst {
+        MusicPlatformDst::Export {
+            output,
+            format,
+            minify,
+            include_likes,
+            include_albums,
+        } => {
+            export(src_api, output, *format, *minify, *include_likes, *include_albums).await?;
         }
-        MusicPlatformDst::Import { input } => {
-            import(input, src_api, args.config, args.skip_playlists, args.src.get_dst().get_owner().to_string()).await?;
+        MusicPlatformDst::Import {
+            input,
+            input_format,
+            columns,
+        } => {
+            let owner = default_owner(dst.get_owner(), &src_api);
+            import(input, *input_format, columns, src_api, args.config, filter, owner).await?;
         }
         _ => {
-            let dst_api = args.src.get_dst().parse(&args, &config_dir).await?;
-            synchronize(src_api, dst_api, args.config, args.skip_playlists, args.src.get_owner().to_string(), args.src.get_dst().get_owner().to_string()).await?;
+            let dst_api = dst.parse(&args, &config_dir).await?;
+            let src_owner = default_owner(args.src.get_owner(), &src_api);
+            let dst_owner = default_owner(dst.get_owner(), &dst_api);
+            synchronize(src_api, dst_api, args.config, filter, src_owner, dst_owner).await?;
         }
     }
 
     Ok(())
 }
+
+/// The `--owner` of a platform, the authenticated user when it is omitted
+fn default_owner(owner: &str, api: &DynMusicApi) -> String {
+    match api.display_name() {
+        Some(name) if owner.is_empty() => {
+            info!("using the authenticated user \"{}\" as owner of the playlists", name);
+            name.to_string()
+        }
+        _ => owner.to_string(),
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tracing::warn;
+
+use crate::music_api::{MusicApiType, Playlist, PlaylistKind};
+
+/// Destination playlist a source playlist is synchronized to
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlaylistLink {
+    pub name: String,
+    /// Destination playlist id, known once the playlist was synchronized
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Source playlist name `name` was computed from, the link is renamed along with the source
+    /// playlist. `None` for names set before renames were detected, that are kept as is
+    #[serde(default)]
+    pub src_name: Option<String>,
+    /// The user confirmed the pairing, even if it looks wrong
+    #[serde(default)]
+    pub confirmed: bool,
+    /// What the source playlist was synchronized to, links of another kind than the current one
+    /// are ignored
+    #[serde(default)]
+    pub kind: PlaylistKind,
+}
+
+/// Persistent source playlist -> destination playlist pairing, stored as JSON in the config directory
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+pub struct PlaylistMapping {
+    /// source playlist id -> destination platform short name -> destination playlist
+    #[serde(default)]
+    pub playlists: BTreeMap<String, BTreeMap<String, PlaylistLink>>,
+}
+
+/// Source playlist whose sanitized name was already taken on the destination
+#[derive(Debug, PartialEq)]
+pub struct NameCollision {
+    pub src_name: String,
+    pub sanitized: String,
+    pub resolved: String,
+}
+
+fn short_hash(id: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(id.as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    hash[..6].to_string()
+}
+
+impl PlaylistMapping {
+    pub const FILE_NAME: &'static str = "playlist_mapping.json";
+
+    /// Load the mapping file, a missing file is equivalent to an empty one
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let mapping = serde_json::from_reader(std::fs::File::open(path)?)?;
+        Ok(mapping)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, src_id: &str, dst: &MusicApiType) -> Option<&PlaylistLink> {
+        self.playlists.get(src_id)?.get(dst.short_name())
+    }
+
+    pub fn set(&mut self, src_id: &str, dst: &MusicApiType, link: PlaylistLink) {
+        self.playlists
+            .entry(src_id.to_string())
+            .or_default()
+            .insert(dst.short_name().to_string(), link);
+    }
+
+    /// Record the destination playlist, or collection, a source playlist was synchronized to,
+    /// returns whether the mapping changed
+    pub fn link(
+        &mut self,
+        src_id: &str,
+        src_name: &str,
+        dst: &MusicApiType,
+        dst_id: &str,
+        dst_name: &str,
+        kind: PlaylistKind,
+    ) -> bool {
+        let previous = self.get(src_id, dst).filter(|l| l.kind == kind);
+        let link = PlaylistLink {
+            name: dst_name.to_string(),
+            id: Some(dst_id.to_string()),
+            src_name: Some(src_name.to_string()),
+            confirmed: previous.is_some_and(|l| l.confirmed),
+            kind,
+        };
+        if previous == Some(&link) {
+            return false;
+        }
+        self.set(src_id, dst, link);
+        true
+    }
+
+    /// Compute the destination playlist name of each source playlist
+    ///
+    /// Playlists whose sanitized names collide keep the plain name for the first one,
+    /// the later ones get a short hash of their source id appended. Every playlist
+    /// involved in a collision is recorded in the mapping so the names stay stable
+    /// across runs, whatever order the source platform returns the playlists in.
+    /// Names of renamed source playlists are computed again.
+    pub fn resolve_names(
+        &mut self,
+        src_playlists: &[Playlist],
+        dst: &MusicApiType,
+        sanitize: impl Fn(&str) -> String,
+    ) -> (Vec<String>, Vec<NameCollision>) {
+        let mut names: Vec<Option<String>> = src_playlists
+            .iter()
+            .map(|p| {
+                self.get(&p.id, dst)
+                    .filter(|l| l.src_name.as_ref().is_none_or(|n| *n == p.name))
+                    .map(|l| l.name.clone())
+            })
+            .collect();
+        let sanitized: Vec<String> = src_playlists.iter().map(|p| sanitize(&p.name)).collect();
+
+        let mut collisions = vec![];
+        for (i, playlist) in src_playlists.iter().enumerate() {
+            if names[i].is_some() {
+                continue;
+            }
+            let taken = |name: &str, names: &[Option<String>]| {
+                names.iter().any(|n| n.as_deref() == Some(name))
+            };
+            let colliding = src_playlists
+                .iter()
+                .enumerate()
+                .any(|(j, _)| j != i && sanitized[j] == sanitized[i]);
+
+            let mut name = sanitized[i].clone();
+            if taken(&name, &names) {
+                name = format!("{} ({})", sanitized[i], short_hash(&playlist.id));
+                collisions.push(NameCollision {
+                    src_name: playlist.name.clone(),
+                    sanitized: sanitized[i].clone(),
+                    resolved: name.clone(),
+                });
+            }
+            if colliding {
+                let previous = self.get(&playlist.id, dst);
+                let link = PlaylistLink {
+                    name: name.clone(),
+                    id: previous.and_then(|l| l.id.clone()),
+                    src_name: Some(playlist.name.clone()),
+                    confirmed: previous.is_some_and(|l| l.confirmed),
+                    kind: previous.map(|l| l.kind).unwrap_or_default(),
+                };
+                self.set(&playlist.id, dst, link);
+            }
+            names[i] = Some(name);
+        }
+
+        if !collisions.is_empty() {
+            warn!("==========================================================");
+            warn!("{} playlist name collision(s) on the destination platform:", collisions.len());
+            for c in &collisions {
+                warn!(
+                    "  \"{}\" would be named \"{}\" like another playlist, renamed to \"{}\"",
+                    c.src_name, c.sanitized, c.resolved
+                );
+            }
+            warn!("==========================================================");
+        }
+
+        (names.into_iter().map(Option::unwrap).collect(), collisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::playlist;
+    use crate::utils::sanitize_playlist_name;
+
+
+    #[test]
+    fn test_resolve_names_collisions() {
+        let playlists = vec![
+            playlist("p1", "Mix/2023", vec![]),
+            playlist("p2", "Mix 2023", vec![]),
+            playlist("p3", "Chill", vec![]),
+            playlist("p4", "Mix  2023", vec![]),
+        ];
+        let mut mapping = PlaylistMapping::default();
+        let (names, collisions) =
+            mapping.resolve_names(&playlists, &MusicApiType::Plex, sanitize_playlist_name);
+
+        assert_eq!(names[0], "Mix 2023");
+        assert_eq!(names[1], format!("Mix 2023 ({})", short_hash("p2")));
+        assert_eq!(names[2], "Chill");
+        assert_eq!(names[3], format!("Mix 2023 ({})", short_hash("p4")));
+        assert_ne!(names[1], names[3]);
+        assert_eq!(collisions.len(), 2);
+        // non colliding playlists are not recorded
+        assert!(mapping.get("p3", &MusicApiType::Plex).is_none());
+
+        // second run, playlists returned in another order
+        let json = serde_json::to_string(&mapping).unwrap();
+        let mut mapping: PlaylistMapping = serde_json::from_str(&json).unwrap();
+        let reversed: Vec<Playlist> = playlists.iter().rev().cloned().collect();
+        let (names_2, collisions_2) =
+            mapping.resolve_names(&reversed, &MusicApiType::Plex, sanitize_playlist_name);
+        let names_2: Vec<String> = names_2.into_iter().rev().collect();
+
+        assert_eq!(names, names_2);
+        assert!(collisions_2.is_empty());
+
+        // other destinations are resolved independently
+        assert!(mapping.get("p2", &MusicApiType::Tidal).is_none());
+    }
+
+    #[test]
+    fn test_link_kind() {
+        // links written before collections existed are playlists
+        let json = r#"{"playlists": {"p1": {"plex": {"name": "Chill", "id": "10", "confirmed": true}}}}"#;
+        let mut mapping: PlaylistMapping = serde_json::from_str(json).unwrap();
+        assert_eq!(mapping.get("p1", &MusicApiType::Plex).unwrap().kind, PlaylistKind::Playlist);
+        mapping.link("p1", "Chill", &MusicApiType::Plex, "10", "Chill", PlaylistKind::Playlist);
+        assert!(mapping.get("p1", &MusicApiType::Plex).unwrap().confirmed);
+
+        // synchronized as a collection, the confirmation of the playlist doesn't carry over
+        assert!(mapping.link("p1", "Chill", &MusicApiType::Plex, "20", "Chill", PlaylistKind::Collection));
+        let link = mapping.get("p1", &MusicApiType::Plex).unwrap();
+        assert_eq!(link.kind, PlaylistKind::Collection);
+        assert_eq!(link.id.as_deref(), Some("20"));
+        assert!(!link.confirmed);
+        let json = serde_json::to_string(&mapping).unwrap();
+        assert!(json.contains(r#""kind":"collection""#), "{}", json);
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::music_api::{MusicApiType, Song};
+
+/// Songs previously found on a destination platform, stored as JSON in the config directory
+///
+/// Only successful searches are cached, songs that weren't found are searched again on the
+/// next run as they may have been added to the destination platform since.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct MatchCache {
+    /// "source platform:source song id:destination platform" -> destination song
+    #[serde(default)]
+    pub matches: BTreeMap<String, Song>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+fn key(src_song: &Song, dst: &MusicApiType) -> String {
+    format!(
+        "{}:{}:{}",
+        src_song.source.short_name(),
+        src_song.id,
+        dst.short_name()
+    )
+}
+
+impl MatchCache {
+    pub const FILE_NAME: &'static str = "match_cache.json";
+
+    /// Load the cache file, a missing file is equivalent to an empty cache
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let cache = serde_json::from_reader(std::fs::File::open(path)?)?;
+        Ok(cache)
+    }
+
+    /// Write the cache file if it was modified since it was loaded
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if self.dirty {
+            std::fs::write(path, serde_json::to_string(self)?)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, src_song: &Song, dst: &MusicApiType) -> Option<&Song> {
+        self.matches.get(&key(src_song, dst))
+    }
+
+    pub fn insert(&mut self, src_song: &Song, dst_song: &Song) {
+        self.matches.insert(key(src_song, &dst_song.source), dst_song.clone());
+        self.dirty = true;
+    }
+
+    /// Forget the match of a source song, when its destination song turned out to be invalid
+    pub fn invalidate(&mut self, src_song: &Song, dst: &MusicApiType) {
+        if self.matches.remove(&key(src_song, dst)).is_some() {
+            self.dirty = true;
+        }
+    }
+}
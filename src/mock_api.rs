@@ -0,0 +1,546 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, eyre};
+
+use crate::cover::CoverImage;
+use crate::music_api::{Album, Artist, DynMusicApi, MusicApi, MusicApiType, Playlist, SavedAlbum, Song};
+
+/// Account state shared by all the handles of a `MockApi`
+#[derive(Default, Debug)]
+pub struct MockState {
+    pub playlists: Vec<Playlist>,
+    pub likes: Vec<Song>,
+    pub saved_albums: Vec<SavedAlbum>,
+    pub followed_artists: Vec<Artist>,
+    /// Paths of the playlist folders
+    pub folders: Vec<String>,
+    /// Every mutating call, in order
+    pub writes: Vec<String>,
+    pub searches: usize,
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+}
+
+/// In-memory music platform, songs are searched in `catalog` by name
+#[derive(Clone)]
+pub struct MockApi {
+    pub api_type: MusicApiType,
+    pub state: Arc<Mutex<MockState>>,
+    pub catalog: Arc<Vec<Song>>,
+    /// Albums found by `search_album`
+    pub album_catalog: Arc<Vec<SavedAlbum>>,
+    /// Artists found by `search_artist`
+    pub artist_catalog: Arc<Vec<Artist>>,
+    /// Whether saved albums and followed artists are supported
+    pub library: bool,
+    /// Artificial latency of every call
+    pub latency: Duration,
+    /// Whether `clone_handle` is supported
+    pub concurrent: bool,
+    /// Whether `supports_removal` and `supports_like_removal` are true
+    pub removal: bool,
+    /// Whether `lookup_songs` is supported, songs are looked up in `catalog` by id
+    pub lookup: bool,
+    /// Song ids silently skipped by `add_songs_to_playlist`, and not liked by `add_likes`
+    pub rejected: Vec<String>,
+    /// Fail the mutating call with this index, as if the process was interrupted
+    pub fail_on_write: Option<usize>,
+    /// Whether `set_playlist_cover` is supported
+    pub covers: bool,
+    /// Whether playlist folders are supported
+    pub folders: bool,
+    /// Whether the listed playlists have their description and visibility
+    pub details: bool,
+}
+
+/// Empty directory of its own for a test, as the tests run concurrently and the runs of other
+/// processes may share the temporary directory
+pub fn test_dir(name: &str) -> PathBuf {
+    static DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("sync_dis_boi_tests/{}-{}-{}", std::process::id(), n, name));
+    // the files of an earlier run with the same process id aren't kept
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn song(source: MusicApiType, id: &str, name: &str) -> Song {
+    Song {
+        source,
+        id: id.to_string(),
+        sid: None,
+        isrc: None,
+        name: name.to_string(),
+        album: None,
+        artists: vec![credited("Artist")],
+        duration_ms: 180_000,
+        uploaded: false,
+        explicit: None,
+        alt_names: vec![],
+        mbid: None,
+        isrc_hint: None,
+    }
+}
+
+/// Artist credited on a song, without id
+pub fn credited(name: &str) -> Artist {
+    Artist {
+        id: None,
+        name: name.to_string(),
+    }
+}
+
+/// Album of a song, without id nor year
+pub fn album(name: &str) -> Album {
+    Album {
+        id: None,
+        name: name.to_string(),
+        year: None,
+    }
+}
+
+pub fn artist(id: &str, name: &str) -> Artist {
+    Artist {
+        id: Some(id.to_string()),
+        name: name.to_string(),
+    }
+}
+
+pub fn saved_album(source: &MusicApiType, id: &str, name: &str, artist_name: &str, upc: Option<&str>) -> SavedAlbum {
+    SavedAlbum {
+        source: source.clone(),
+        album: Album {
+            id: Some(id.to_string()),
+            name: name.to_string(),
+            year: None,
+        },
+        artists: vec![artist(id, artist_name)],
+        upc: upc.map(str::to_string),
+    }
+}
+
+pub fn playlist(id: &str, name: &str, songs: Vec<Song>) -> Playlist {
+    Playlist {
+        id: id.to_string(),
+        name: name.to_string(),
+        songs,
+        owner: Some("owner".to_string()),
+        description: None,
+        public: None,
+        generated: false,
+        cover_url: None,
+        unavailable: None,
+        owner_name: None,
+        collaborative: false,
+        snapshot_id: None,
+        folder: None,
+    }
+}
+
+/// Serve `body` as `image/jpeg` to the next `count` requests on a local port, returning its URL
+pub async fn serve_image(body: Vec<u8>, count: usize) -> String {
+    let responses = vec![(200, "Content-Type: image/jpeg\r\n".to_string(), body); count];
+    let (url, _) = serve(responses).await;
+    format!("{}/cover.jpg", url)
+}
+
+/// Answer the next requests on a local port with the given JSON `(status, body)` responses, in
+/// order, returning the base URL and the requests received as their request line (e.g.
+/// `PUT /v1/playlists/1`) and body
+pub async fn serve_json(responses: Vec<(u16, &str)>) -> (String, Requests) {
+    let responses = responses.into_iter().map(|(status, body)| (status, "", body)).collect();
+    serve_json_headers(responses).await
+}
+
+/// Same as `serve_json` with the `(status, headers, body)` responses, the headers being raw
+/// header lines (e.g. `ETag: "1"`)
+pub async fn serve_json_headers(responses: Vec<(u16, &str, &str)>) -> (String, Requests) {
+    let responses = responses
+        .into_iter()
+        .map(|(status, headers, body)| {
+            let mut headers: String = headers.lines().map(|l| format!("{}\r\n", l)).collect();
+            headers.push_str("Content-Type: application/json\r\n");
+            (status, headers, body.as_bytes().to_vec())
+        })
+        .collect();
+    serve(responses).await
+}
+
+/// Requests received by a local server, as their request line and body
+pub type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+async fn serve(responses: Vec<(u16, String, Vec<u8>)>) -> (String, Requests) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let received = requests.clone();
+    tokio::spawn(async move {
+        for (status, headers, body) in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // read the whole request so that closing the connection doesn't reset it
+            let mut request = vec![];
+            let mut buf = [0; 1024];
+            loop {
+                let Ok(n) = stream.read(&mut buf).await else { break };
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            let request = String::from_utf8_lossy(&request);
+            let (head, content) = request.split_once("\r\n\r\n").unwrap_or((&request, ""));
+            let line = head.lines().next().unwrap_or("").trim_end_matches(" HTTP/1.1");
+            received.lock().unwrap().push((line.to_string(), content.to_string()));
+
+            let head = format!(
+                "HTTP/1.1 {} OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                headers,
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+        }
+    });
+    (url, requests)
+}
+
+impl MockApi {
+    pub fn new(api_type: MusicApiType, catalog: Vec<Song>) -> Self {
+        Self {
+            api_type,
+            state: Arc::new(Mutex::new(MockState::default())),
+            catalog: Arc::new(catalog),
+            album_catalog: Arc::new(vec![]),
+            artist_catalog: Arc::new(vec![]),
+            library: true,
+            latency: Duration::ZERO,
+            concurrent: true,
+            removal: true,
+            lookup: false,
+            rejected: vec![],
+            fail_on_write: None,
+            covers: false,
+            folders: false,
+            details: true,
+        }
+    }
+
+    pub fn boxed(&self) -> DynMusicApi {
+        Box::new(self.clone())
+    }
+
+    async fn call(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.in_flight += 1;
+            state.max_in_flight = state.max_in_flight.max(state.in_flight);
+        }
+        tokio::time::sleep(self.latency).await;
+        self.state.lock().unwrap().in_flight -= 1;
+    }
+
+    fn write(&self, call: String) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if self.fail_on_write == Some(state.writes.len()) {
+            return Err(eyre!("interrupted before {}", call));
+        }
+        state.writes.push(call);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MusicApi for MockApi {
+    fn api_type(&self) -> MusicApiType {
+        self.api_type.clone()
+    }
+
+    fn country_code(&self) -> &'static str {
+        "US"
+    }
+
+    fn clone_handle(&self) -> Option<DynMusicApi> {
+        self.concurrent.then(|| self.boxed())
+    }
+
+    fn supports_removal(&self) -> bool {
+        self.removal
+    }
+
+    async fn create_playlist(&mut self, name: &str, description: &str, public: bool) -> Result<Playlist> {
+        self.call().await;
+        self.write(format!("create_playlist {}", name))?;
+        let mut state = self.state.lock().unwrap();
+        let id = (0..=state.playlists.len())
+            .map(|n| format!("mock{}", n))
+            .find(|id| state.playlists.iter().all(|p| &p.id != id))
+            .unwrap();
+        let mut playlist = playlist(&id, name, vec![]);
+        playlist.description = Some(description.to_string());
+        playlist.public = Some(public);
+        state.playlists.push(playlist.clone());
+        Ok(playlist)
+    }
+
+    fn supports_playlist_details(&self) -> bool {
+        self.details
+    }
+
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        self.call().await;
+        self.write(format!("update_playlist_details {}", playlist.name))?;
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .playlists
+            .iter_mut()
+            .find(|p| p.id == playlist.id)
+            .ok_or_else(|| eyre!("unknown playlist {}", playlist.id))?;
+        if let Some(description) = description {
+            stored.description = Some(description.to_string());
+        }
+        stored.public = public.or(stored.public);
+        playlist.description = stored.description.clone();
+        playlist.public = stored.public;
+        Ok(())
+    }
+
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()> {
+        self.call().await;
+        self.write(format!("rename_playlist {} {}", playlist.name, name))?;
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .playlists
+            .iter_mut()
+            .find(|p| p.id == playlist.id)
+            .ok_or_else(|| eyre!("unknown playlist {}", playlist.id))?;
+        stored.name = name.to_string();
+        playlist.name = name.to_string();
+        Ok(())
+    }
+
+    fn supports_playlist_cover(&self) -> bool {
+        self.covers
+    }
+
+    async fn set_playlist_cover(&mut self, playlist: &Playlist, cover: &CoverImage) -> Result<()> {
+        self.call().await;
+        self.write(format!("set_playlist_cover {} {}", playlist.name, cover.bytes.len()))
+    }
+
+    fn supports_folders(&self) -> bool {
+        self.folders
+    }
+
+    async fn get_folders(&mut self) -> Result<Vec<String>> {
+        self.call().await;
+        Ok(self.state.lock().unwrap().folders.clone())
+    }
+
+    async fn create_folder(&mut self, path: &str) -> Result<()> {
+        self.call().await;
+        self.write(format!("create_folder {}", path))?;
+        let mut state = self.state.lock().unwrap();
+        if let Some((parent, _)) = path.rsplit_once('/')
+            && !state.folders.iter().any(|f| f == parent)
+        {
+            return Err(eyre!("unknown folder {}", parent));
+        }
+        state.folders.push(path.to_string());
+        Ok(())
+    }
+
+    async fn move_to_folder(&mut self, playlist: &Playlist, path: &str) -> Result<()> {
+        self.call().await;
+        self.write(format!("move_to_folder {} {}", playlist.name, path))?;
+        let mut state = self.state.lock().unwrap();
+        if !state.folders.iter().any(|f| f == path) {
+            return Err(eyre!("unknown folder {}", path));
+        }
+        let stored = state
+            .playlists
+            .iter_mut()
+            .find(|p| p.id == playlist.id)
+            .ok_or_else(|| eyre!("unknown playlist {}", playlist.id))?;
+        stored.folder = Some(path.to_string());
+        Ok(())
+    }
+
+    async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
+        self.call().await;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .playlists
+            .iter()
+            .map(|p| Playlist {
+                songs: vec![],
+                ..p.clone()
+            })
+            .collect())
+    }
+
+    async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>> {
+        self.call().await;
+        let state = self.state.lock().unwrap();
+        let playlist = state
+            .playlists
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or(eyre!("no playlist {}", id))?;
+        Ok(playlist.songs.clone())
+    }
+
+    async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
+        self.call().await;
+        self.write(format!("add_songs_to_playlist {} {}", playlist.name, songs.len()))?;
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .playlists
+            .iter_mut()
+            .find(|p| p.id == playlist.id)
+            .ok_or(eyre!("no playlist {}", playlist.id))?;
+        let songs: Vec<Song> = songs
+            .iter()
+            .filter(|s| !self.rejected.contains(&s.id))
+            .cloned()
+            .collect();
+        stored.songs.extend_from_slice(&songs);
+        playlist.songs.extend(songs);
+        Ok(())
+    }
+
+    async fn remove_songs_from_playlist(
+        &mut self,
+        playlist: &mut Playlist,
+        songs: &[Song],
+    ) -> Result<()> {
+        self.call().await;
+        self.write(format!("remove_songs_from_playlist {} {}", playlist.name, songs.len()))?;
+        let mut state = self.state.lock().unwrap();
+        let stored = state
+            .playlists
+            .iter_mut()
+            .find(|p| p.id == playlist.id)
+            .ok_or(eyre!("no playlist {}", playlist.id))?;
+        stored.songs.retain(|s| !songs.iter().any(|r| r.id == s.id));
+        playlist.songs.retain(|s| !songs.iter().any(|r| r.id == s.id));
+        Ok(())
+    }
+
+    async fn delete_playlist(&mut self, playlist: Playlist) -> Result<()> {
+        self.call().await;
+        self.write(format!("delete_playlist {}", playlist.name))?;
+        self.state.lock().unwrap().playlists.retain(|p| p.id != playlist.id);
+        Ok(())
+    }
+
+    async fn search_song(&mut self, song: &Song) -> Result<Option<Song>> {
+        self.call().await;
+        self.state.lock().unwrap().searches += 1;
+        Ok(self.catalog.iter().find(|s| s.name == song.name).cloned())
+    }
+
+    fn supports_song_lookup(&self) -> bool {
+        self.lookup
+    }
+
+    async fn lookup_songs(&mut self, songs: &[Song]) -> Result<Vec<Option<Song>>> {
+        self.call().await;
+        Ok(songs
+            .iter()
+            .map(|song| self.catalog.iter().find(|s| s.id == song.id).cloned())
+            .collect())
+    }
+
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
+        self.call().await;
+        self.write(format!("add_likes {}", songs.len()))?;
+        let (rejected, liked): (Vec<Song>, Vec<Song>) =
+            songs.iter().cloned().partition(|s| self.rejected.contains(&s.id));
+        self.state.lock().unwrap().likes.extend(liked);
+        Ok(rejected)
+    }
+
+    async fn get_likes(&mut self) -> Result<Vec<Song>> {
+        self.call().await;
+        Ok(self.state.lock().unwrap().likes.clone())
+    }
+
+    fn supports_like_removal(&self) -> bool {
+        self.removal
+    }
+
+    async fn remove_likes(&mut self, songs: &[Song]) -> Result<()> {
+        self.call().await;
+        self.write(format!("remove_likes {}", songs.len()))?;
+        self.state.lock().unwrap().likes.retain(|s| !songs.iter().any(|r| r.id == s.id));
+        Ok(())
+    }
+
+    fn supports_saved_albums(&self) -> bool {
+        self.library
+    }
+
+    async fn get_saved_albums(&mut self) -> Result<Vec<SavedAlbum>> {
+        self.call().await;
+        Ok(self.state.lock().unwrap().saved_albums.clone())
+    }
+
+    async fn add_saved_albums(&mut self, albums: &[SavedAlbum]) -> Result<()> {
+        self.call().await;
+        self.write(format!("add_saved_albums {}", albums.len()))?;
+        self.state.lock().unwrap().saved_albums.extend_from_slice(albums);
+        Ok(())
+    }
+
+    async fn search_album(&mut self, album: &SavedAlbum) -> Result<Option<SavedAlbum>> {
+        self.call().await;
+        self.state.lock().unwrap().searches += 1;
+        Ok(album.best_match(self.album_catalog.to_vec()))
+    }
+
+    fn supports_followed_artists(&self) -> bool {
+        self.library
+    }
+
+    async fn get_followed_artists(&mut self) -> Result<Vec<Artist>> {
+        self.call().await;
+        Ok(self.state.lock().unwrap().followed_artists.clone())
+    }
+
+    async fn follow_artists(&mut self, artists: &[Artist]) -> Result<()> {
+        self.call().await;
+        self.write(format!("follow_artists {}", artists.len()))?;
+        self.state.lock().unwrap().followed_artists.extend_from_slice(artists);
+        Ok(())
+    }
+
+    async fn search_artist(&mut self, artist: &Artist) -> Result<Option<Artist>> {
+        self.call().await;
+        self.state.lock().unwrap().searches += 1;
+        Ok(self.artist_catalog.iter().find(|a| a.is_same(artist)).cloned())
+    }
+}
@@ -1,12 +1,25 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
 use async_trait::async_trait;
-use color_eyre::eyre::Result;
+use atty::Stream;
+use color_eyre::eyre::{Result, eyre};
+use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use strsim::normalized_levenshtein;
-use tracing::debug;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
 
-use crate::utils::generic_name_clean;
+use crate::ConfigArgs;
+use crate::cover::CoverImage;
+use crate::utils::{
+    fold_name, generic_name_clean, normalize_title, progress_bar, save_error_diagnostic, write_diagnostic,
+};
 
 pub const PLAYLIST_DESC: &str = "Playlist created by SyncDisBoi";
+/// Country code of the platforms whose country couldn't be determined
+pub const UNKNOWN_COUNTRY: &str = "UNKNOWN";
 
 pub type DynMusicApi = Box<dyn MusicApi + Sync + Send>;
 
@@ -15,19 +28,173 @@ pub trait MusicApi {
     fn api_type(&self) -> MusicApiType;
     fn country_code(&self) -> &str;
 
-    async fn create_playlist(&mut self, name: &str, public: bool) -> Result<Playlist>;
+    /// Name of the authenticated user, the owner of the playlists it creates, for the
+    /// platforms where the `--owner` can be omitted
+    fn display_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Resemblance required from the search results of the platform, the match options given
+    /// on the command line take precedence
+    fn default_match_options(&self) -> MatchOptions {
+        MatchOptions::default()
+    }
+
+    /// Time spent waiting before retrying rate limited or failed requests
+    fn backoff_time(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// Independent handle on the same account, used to process several playlists or search
+    /// several songs concurrently, as the other methods take `&mut self`.
+    /// Platforms with strict rate limits don't provide one.
+    fn clone_handle(&self) -> Option<DynMusicApi> {
+        None
+    }
+
+    /// Name a playlist named `name` on the source platform gets on this platform, the same
+    /// name unless the platform restricts the characters of the names
+    fn sanitize_playlist_name(&self, name: &str) -> String {
+        name.to_string()
+    }
+
+    /// What the source playlists are synchronized to, recorded in the playlist mapping so a
+    /// playlist is never paired with a destination of another kind
+    fn playlist_kind(&self) -> PlaylistKind {
+        PlaylistKind::Playlist
+    }
+
+    /// Whether `create_playlist` creates playlists without songs, the source playlists none of
+    /// whose songs were found are not created otherwise
+    fn supports_empty_playlists(&self) -> bool {
+        true
+    }
+
+    /// Whether `remove_songs_from_playlist` is implemented, required by the mirror mode
+    fn supports_removal(&self) -> bool {
+        true
+    }
+
+    /// Whether `get_playlists_info` only lists the playlists of the user, the ones whose owner
+    /// is unknown are then considered ours
+    fn lists_own_playlists_only(&self) -> bool {
+        false
+    }
+
+    async fn create_playlist(&mut self, name: &str, description: &str, public: bool) -> Result<Playlist>;
+
+    /// Create a playlist already containing `songs`. Platforms able to do it in a single
+    /// request override it, the others create the playlist then add the songs.
+    async fn create_playlist_with_songs(
+        &mut self,
+        name: &str,
+        description: &str,
+        public: bool,
+        songs: &[Song],
+    ) -> Result<Playlist> {
+        let mut playlist = self.create_playlist(name, description, public).await?;
+        if !songs.is_empty() {
+            self.add_songs_to_playlist(&mut playlist, songs).await?;
+        }
+        Ok(playlist)
+    }
+
+    /// Whether the playlists of `get_playlists_info` have their description and visibility. The
+    /// details of existing playlists can't be compared with the source ones otherwise, they are
+    /// only set when the playlists are created
+    fn supports_playlist_details(&self) -> bool {
+        true
+    }
+
+    /// Update the description and visibility of an existing playlist, `None` values are left
+    /// unchanged. Platforms without a concept of visibility ignore `public`.
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()>;
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()>;
+
+    /// Whether `set_playlist_cover` is implemented, required by `--sync-covers`
+    fn supports_playlist_cover(&self) -> bool {
+        false
+    }
+
+    /// Replace the cover image of a playlist, converting it to the format of the platform
+    async fn set_playlist_cover(&mut self, _playlist: &Playlist, _cover: &CoverImage) -> Result<()> {
+        Err(eyre!("{:?} does not support playlist covers", self.api_type()))
+    }
+
+    /// Whether playlists can be put in folders, required by `--folder-map`
+    fn supports_folders(&self) -> bool {
+        false
+    }
+
+    /// Paths of the existing playlist folders, their names joined by "/"
+    async fn get_folders(&mut self) -> Result<Vec<String>> {
+        Err(eyre!("{:?} does not support playlist folders", self.api_type()))
+    }
+
+    /// Create the folder `path`, whose parent folder already exists
+    async fn create_folder(&mut self, _path: &str) -> Result<()> {
+        Err(eyre!("{:?} does not support playlist folders", self.api_type()))
+    }
+
+    /// Move a playlist to the existing folder `path`
+    async fn move_to_folder(&mut self, _playlist: &Playlist, _path: &str) -> Result<()> {
+        Err(eyre!("{:?} does not support playlist folders", self.api_type()))
+    }
+
     async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>>;
     async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>>;
 
+    /// Fill the songs of a playlist retrieved with `get_playlists_info`, along with its
+    /// description and visibility on the platforms only returning them with the songs
+    async fn fetch_playlist(&mut self, playlist: &mut Playlist) -> Result<()> {
+        playlist.songs = self.get_playlist_songs(&playlist.id).await?;
+        Ok(())
+    }
+
     async fn get_playlists_full(&mut self) -> Result<Vec<Playlist>> {
         let mut playlists = self.get_playlists_info().await?;
+        self.fetch_playlists_songs(&mut playlists).await?;
+        Ok(playlists)
+    }
 
-        for playlist in &mut playlists {
-            let songs = self.get_playlist_songs(&playlist.id).await?;
-            playlist.songs = songs;
+    /// Fetch the songs of playlists retrieved with `get_playlists_info`. Inaccessible and
+    /// deleted playlists are left empty, with their `unavailable` reason.
+    async fn fetch_playlists_songs(&mut self, playlists: &mut [Playlist]) -> Result<()> {
+        let progress = progress_bar(playlists.len(), "playlists");
+        for playlist in playlists {
+            progress.set_message(playlist.name.clone());
+            match self.fetch_playlist(playlist).await {
+                Ok(()) => {}
+                Err(e) => {
+                    let Some(unavailable) = e.downcast_ref::<PlaylistUnavailable>() else {
+                        return Err(e);
+                    };
+                    warn!(
+                        "playlist \"{}\" is {} on {:?} ({}), it will NOT be synchronized",
+                        playlist.name,
+                        unavailable.reason,
+                        self.api_type(),
+                        unavailable.message
+                    );
+                    playlist.songs = vec![];
+                    playlist.unavailable = Some(unavailable.reason);
+                }
+            }
+            progress.inc(1);
         }
+        progress.finish_and_clear();
+        Ok(())
+    }
 
-        Ok(playlists)
+    /// Pseudo-playlist of the songs uploaded by the user, for `--include-uploads`. Its songs
+    /// are retrieved with `get_playlist_songs`.
+    fn uploads_playlist(&self) -> Option<Playlist> {
+        None
     }
 
     async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()>;
@@ -36,10 +203,39 @@ pub trait MusicApi {
         playlist: &mut Playlist,
         songs_ids: &[Song],
     ) -> Result<()>;
+
+    /// Whether `reorder_playlist` is implemented
+    fn supports_reorder(&self) -> bool {
+        false
+    }
+
+    /// Reorder the songs of a playlist to follow `order`, the songs of the playlist missing
+    /// from `order` are kept after the ordered ones
+    async fn reorder_playlist(&mut self, _playlist: &mut Playlist, _order: &[Song]) -> Result<()> {
+        Err(eyre!("{:?} does not support reordering playlists", self.api_type()))
+    }
+
     async fn delete_playlist(&mut self, playlist: Playlist) -> Result<()>;
 
     async fn search_song(&mut self, song: &Song) -> Result<Option<Song>>;
 
+    /// Top search results for `song`, whether they are the same song or not, offered to the
+    /// user when no match was found in `--interactive` mode
+    async fn search_candidates(&mut self, _song: &Song, _limit: usize) -> Result<Vec<Song>> {
+        Ok(vec![])
+    }
+
+    /// Whether `lookup_songs` is implemented
+    fn supports_song_lookup(&self) -> bool {
+        false
+    }
+
+    /// Current version of songs of this platform by their id, `None` when the id no longer exists.
+    /// Tracks merged or replaced by the platform are returned with their new id.
+    async fn lookup_songs(&mut self, _songs: &[Song]) -> Result<Vec<Option<Song>>> {
+        Err(eyre!("{:?} does not support looking up songs by id", self.api_type()))
+    }
+
     async fn search_songs(&mut self, songs: &[Song]) -> Result<Vec<Option<Song>>> {
         let mut results = Vec::new();
 
@@ -50,8 +246,65 @@ pub trait MusicApi {
         Ok(results)
     }
 
-    async fn add_likes(&mut self, songs: &[Song]) -> Result<()>;
+    /// Like the songs, returning the ones the platform couldn't like
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>>;
     async fn get_likes(&mut self) -> Result<Vec<Song>>;
+
+    /// Whether `remove_likes` is implemented, required by `--prune-likes`
+    fn supports_like_removal(&self) -> bool {
+        false
+    }
+
+    async fn remove_likes(&mut self, _songs: &[Song]) -> Result<()> {
+        Err(eyre!("{:?} does not support removing likes", self.api_type()))
+    }
+
+    /// Whether the saved albums methods are implemented, required by `--sync-albums`
+    fn supports_saved_albums(&self) -> bool {
+        false
+    }
+
+    async fn get_saved_albums(&mut self) -> Result<Vec<SavedAlbum>> {
+        Err(eyre!("{:?} does not support saved albums", self.api_type()))
+    }
+
+    async fn add_saved_albums(&mut self, _albums: &[SavedAlbum]) -> Result<()> {
+        Err(eyre!("{:?} does not support saved albums", self.api_type()))
+    }
+
+    /// Album of this platform that is the same release as `album`, searched by UPC first and
+    /// then by name and artist
+    async fn search_album(&mut self, _album: &SavedAlbum) -> Result<Option<SavedAlbum>> {
+        Err(eyre!("{:?} does not support saved albums", self.api_type()))
+    }
+
+    /// Whether the followed artists methods are implemented, required by `--sync-artists`
+    fn supports_followed_artists(&self) -> bool {
+        false
+    }
+
+    async fn get_followed_artists(&mut self) -> Result<Vec<Artist>> {
+        Err(eyre!("{:?} does not support followed artists", self.api_type()))
+    }
+
+    async fn follow_artists(&mut self, _artists: &[Artist]) -> Result<()> {
+        Err(eyre!("{:?} does not support followed artists", self.api_type()))
+    }
+
+    /// Artist of this platform with the same name as `artist`
+    async fn search_artist(&mut self, _artist: &Artist) -> Result<Option<Artist>> {
+        Err(eyre!("{:?} does not support followed artists", self.api_type()))
+    }
+}
+
+/// What the source playlists become on a destination platform
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistKind {
+    #[default]
+    Playlist,
+    /// Plex collection of tracks, `--plex-as-collections`
+    Collection,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
@@ -59,7 +312,9 @@ pub enum MusicApiType {
     Spotify,
     YtMusic,
     Tidal,
-    Plex
+    Plex,
+    /// Songs of the CSV and text files imported, known by their metadata only
+    File,
 }
 
 impl MusicApiType {
@@ -68,9 +323,49 @@ impl MusicApiType {
             MusicApiType::Spotify => "spotify",
             MusicApiType::YtMusic => "ytmusic",
             MusicApiType::Tidal => "tidal",
-            MusicApiType::Plex => "plex"
+            MusicApiType::Plex => "plex",
+            MusicApiType::File => "file",
+        }
+    }
+
+    /// Public web URL of a song, Plex songs only live on the user's server
+    pub fn song_url(&self, id: &str) -> Option<String> {
+        match self {
+            MusicApiType::Spotify => Some(format!("https://open.spotify.com/track/{}", id)),
+            MusicApiType::YtMusic => Some(format!("https://music.youtube.com/watch?v={}", id)),
+            MusicApiType::Tidal => Some(format!("https://tidal.com/browse/track/{}", id)),
+            MusicApiType::Plex | MusicApiType::File => None,
         }
     }
+
+    /// Public web URL of a playlist, Plex playlists only live on the user's server
+    pub fn playlist_url(&self, id: &str) -> Option<String> {
+        match self {
+            MusicApiType::Spotify => Some(format!("https://open.spotify.com/playlist/{}", id)),
+            MusicApiType::YtMusic => {
+                Some(format!("https://music.youtube.com/playlist?list={}", id))
+            }
+            MusicApiType::Tidal => Some(format!("https://tidal.com/browse/playlist/{}", id)),
+            MusicApiType::Plex | MusicApiType::File => None,
+        }
+    }
+}
+
+impl std::str::FromStr for MusicApiType {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase().replace(['-', '_'], "");
+        [
+            MusicApiType::Spotify,
+            MusicApiType::YtMusic,
+            MusicApiType::Tidal,
+            MusicApiType::Plex,
+        ]
+        .into_iter()
+        .find(|t| t.short_name() == s)
+        .ok_or_else(|| color_eyre::eyre::eyre!("unknown music platform: {}", s))
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -85,6 +380,120 @@ pub struct Playlist {
     pub name: String,
     pub songs: Vec<Song>,
     pub owner: Option<String>,
+    /// Display name of the owner, when `owner` is a user id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_name: Option<String>,
+    /// Other users can add songs to the playlist
+    #[serde(default)]
+    pub collaborative: bool,
+    /// `None` when unknown or not supported by the platform
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub public: Option<bool>,
+    /// Made by the platform itself (mixes, recommendations...), not by a user
+    #[serde(default)]
+    pub generated: bool,
+    /// URL of the cover image, `None` when unknown or not supported by the platform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_url: Option<String>,
+    /// Set by `fetch_playlists_songs` when the songs of the playlist couldn't be retrieved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unavailable: Option<Unavailability>,
+    /// Version of the songs of the playlist, changed by every edit. `None` when the platform
+    /// has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<String>,
+    /// Path of the folder containing the playlist, folder names joined by "/". `None` at the
+    /// top level or when the platform has no folders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+impl Playlist {
+    /// Whether `owner` is the id or the display name of the owner of the playlist
+    pub fn is_owned_by(&self, owner: &str) -> bool {
+        self.owner.as_deref() == Some(owner) || self.owner_name.as_deref() == Some(owner)
+    }
+}
+
+/// Why the songs of a playlist listed in the library can't be retrieved
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Unavailability {
+    /// Private playlist of another user, or otherwise not visible to the account
+    Inaccessible,
+    /// The playlist doesn't exist anymore
+    Deleted,
+}
+
+impl std::fmt::Display for Unavailability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inaccessible => f.write_str("inaccessible"),
+            Self::Deleted => f.write_str("deleted"),
+        }
+    }
+}
+
+/// Error of `get_playlist_songs` for an inaccessible or deleted playlist, an empty playlist
+/// has no songs instead
+#[derive(Debug)]
+pub struct PlaylistUnavailable {
+    pub id: String,
+    pub reason: Unavailability,
+    /// Message given by the platform
+    pub message: String,
+}
+
+impl std::fmt::Display for PlaylistUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "playlist {} is {}: {}", self.id, self.reason, self.message)
+    }
+}
+
+impl std::error::Error for PlaylistUnavailable {}
+
+/// Resemblance required between songs of different platforms to consider them the same
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchOptions {
+    /// Maximum difference between the durations
+    pub duration_tolerance_ms: usize,
+    /// Match on the name, artists and duration only
+    pub ignore_album: bool,
+    /// Require the same album, even for singles
+    pub strict: bool,
+    /// Minimum `Song::match_score` of a search result to be picked
+    pub min_score: f64,
+    /// Weight, between 0 and 1, of the release year in `Song::match_score` when both songs
+    /// have one
+    pub year_weight: f64,
+    /// Pick the search result with the explicitness of the song over a better scored one,
+    /// when both reach `min_score`
+    pub prefer_explicit: bool,
+    /// Pick the search result from the album of the song over a better scored one, e.g. the
+    /// album edit over the single one, when both reach `min_score`
+    pub prefer_album: bool,
+}
+
+impl MatchOptions {
+    pub const DEFAULT_DURATION_TOLERANCE_MS: usize = 5000;
+    pub const DEFAULT_MIN_SCORE: f64 = 0.6;
+    pub const DEFAULT_YEAR_WEIGHT: f64 = 0.1;
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            duration_tolerance_ms: Self::DEFAULT_DURATION_TOLERANCE_MS,
+            ignore_album: false,
+            strict: false,
+            min_score: Self::DEFAULT_MIN_SCORE,
+            year_weight: Self::DEFAULT_YEAR_WEIGHT,
+            prefer_explicit: false,
+            prefer_album: false,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -97,21 +506,68 @@ pub struct Song {
     pub album: Option<Album>,
     pub artists: Vec<Artist>,
     pub duration_ms: usize,
+    /// Uploaded by the user to the source platform, or a Spotify local file, it can only be
+    /// matched on other platforms by its metadata
+    #[serde(default)]
+    pub uploaded: bool,
+    /// Whether the song has explicit lyrics, `None` when the platform doesn't tell
+    #[serde(default)]
+    pub explicit: Option<bool>,
+    /// Other titles given by the platform, e.g. the title in another script, also compared
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alt_names: Vec<String>,
+    /// `MusicBrainz` recording id, known for the Plex songs scanned by the Plex Music agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mbid: Option<String>,
+    /// ISRC found on `MusicBrainz` with `--enrich-isrc`. It's only a hint to search and rank the
+    /// candidates, unlike `isrc` a different ISRC doesn't reject a candidate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isrc_hint: Option<String>,
 }
 
 impl Song {
+    pub fn url(&self) -> Option<String> {
+        if self.is_local_file() {
+            return None;
+        }
+        self.source.song_url(&self.id)
+    }
+
+    /// Spotify local file, its id is a `spotify:local:` uri no other request accepts
+    pub fn is_local_file(&self) -> bool {
+        self.source == MusicApiType::Spotify && self.uploaded
+    }
+
     pub fn clean_name(&self) -> String {
         match self.source {
-            MusicApiType::Spotify | MusicApiType::Tidal | MusicApiType::YtMusic | MusicApiType::Plex => {
-                let name = generic_name_clean(&self.name);
-                let name = name.split(" - ").next().unwrap_or(&name);
-                let name = name.split(" pts. ").next().unwrap_or(name);
-                let name = name.split(" feat. ").next().unwrap_or(name);
-                name.trim_end().to_string()
+            MusicApiType::Spotify
+            | MusicApiType::Tidal
+            | MusicApiType::YtMusic
+            | MusicApiType::Plex
+            | MusicApiType::File => {
+                normalize_title(&self.name).0
             }
         }
     }
 
+    /// This song on platform `api_type` when only its id is known there,
+    /// the other metadata are kept
+    #[must_use]
+    pub fn with_id(&self, api_type: MusicApiType, id: &str) -> Song {
+        Song {
+            source: api_type,
+            id: id.to_string(),
+            sid: None,
+            isrc: None,
+            ..self.clone()
+        }
+    }
+
+    /// Release year of the album of the song
+    pub fn year(&self) -> Option<u16> {
+        self.album.as_ref()?.year
+    }
+
     pub fn is_single(&self) -> bool {
         // TODO: improve this, leverage metadata from APIs when it exists
         if let Some(album) = &self.album {
@@ -122,6 +578,11 @@ impl Song {
     }
 
     pub fn compare(&self, other: &Self) -> bool {
+        self.compare_with(other, &MatchOptions::default())
+    }
+
+    /// Whether `other` is the same song, with the resemblance required by `options`
+    pub fn compare_with(&self, other: &Self, options: &MatchOptions) -> bool {
         if self.source == other.source {
             return self.id == other.id;
         }
@@ -130,9 +591,7 @@ impl Song {
         }
 
         // Check song name resemblance
-        let name1 = self.clean_name();
-        let name2 = other.clean_name();
-        let score = normalized_levenshtein(&name1, &name2).abs();
+        let score = self.title_similarity(other);
         if score < 0.8 {
             return false;
         }
@@ -144,16 +603,33 @@ impl Song {
 
         // Check song duration resemblance
         // NOTE: YtMusic duration is sometimes garbage, it's incorrect on certain songs
-        // it's still better to use it for accuracy
-        let dur1 = self.duration_ms / 1000;
-        let dur2 = other.duration_ms / 1000;
-
-        // we allow a 5 second difference
-        if !(dur1 - 5..=dur1 + 5).contains(&dur2) {
-            debug!("Duration: {} vs {} --> {} VS {}", dur1, dur2, self, other);
+        // it's still better to use it for accuracy. A duration of 0 is unknown (imported files)
+        if self.duration_ms != 0
+            && other.duration_ms != 0
+            && self.duration_ms.abs_diff(other.duration_ms) > options.duration_tolerance_ms
+        {
+            debug!(
+                "Duration: {} vs {} --> {} VS {}",
+                self.duration_ms / 1000,
+                other.duration_ms / 1000,
+                self,
+                other
+            );
             return false;
         }
 
+        if options.ignore_album {
+            // without the album, at least one artist must be shared
+            return self.artists.is_empty() || other.artists.is_empty() || self.shares_artist(other);
+        }
+
+        if options.strict {
+            return match (&self.album, &other.album) {
+                (Some(album1), Some(album2)) => album1.clean_name() == album2.clean_name(),
+                _ => false,
+            };
+        }
+
         if let (Some(album1), Some(album2)) = (&self.album, &other.album) {
             // INFO: Sometimes Youtube Music maps the album song to the Youtube Video
             // Sometimes, the album song is just suppressed from the 'Songs' filter
@@ -173,32 +649,195 @@ impl Song {
         true
     }
 
-    pub fn build_queries(&self) -> Vec<String> {
-        let mut queries = vec![];
-        let track_name = self.clean_name();
+    /// Titles compared between platforms, folded so diacritics and letter case don't matter:
+    /// the title, then the alternative titles
+    fn compared_names(&self) -> Vec<String> {
+        std::iter::once(self.clean_name())
+            .chain(self.alt_names.iter().map(|n| normalize_title(n).0))
+            .map(|n| fold_name(&n))
+            .collect()
+    }
 
-        // Query: Track + Album
-        if let Some(album) = self.album.as_ref() {
-            let album_name = album.clean_name();
-            let tr_al_query = format!("{} {}", track_name, album_name);
-            queries.push(tr_al_query);
+    /// Resemblance between the closest titles of both songs, between 0 and 1
+    fn title_similarity(&self, other: &Self) -> f64 {
+        let names2 = other.compared_names();
+        self.compared_names()
+            .iter()
+            .flat_map(|n1| names2.iter().map(move |n2| normalized_levenshtein(n1, n2)))
+            .fold(0.0, f64::max)
+    }
+
+    /// Names of each artist of the song, including the ones only credited in its title,
+    /// see `Artist::match_names`
+    fn credited_artists(&self) -> Vec<Vec<String>> {
+        let mut artists: Vec<Vec<String>> = self.artists.iter().map(Artist::match_names).collect();
+        for featured in normalize_title(&self.name).1 {
+            let featured = fold_name(&featured);
+            if !artists.iter().any(|names| names.contains(&featured)) {
+                artists.push(vec![featured]);
+            }
+        }
+        artists
+    }
+
+    /// Whether any artist of the song is also an artist of `other`
+    fn shares_artist(&self, other: &Self) -> bool {
+        let artists2 = other.credited_artists();
+        self.credited_artists()
+            .iter()
+            .any(|a1| artists2.iter().any(|a2| Artist::names_overlap(a1, a2)))
+    }
+
+    /// Confidence in `other` being the same song, between 0 and 1
+    pub fn match_confidence(&self, other: &Self) -> f64 {
+        if self.source == other.source && self.id == other.id {
+            return 1.0;
+        }
+        if self.isrc.is_some() && self.isrc == other.isrc {
+            return 1.0;
+        }
+        let mut score = self.title_similarity(other);
+        if let (Some(album1), Some(album2)) = (&self.album, &other.album)
+            && !self.is_single()
+            && !other.is_single()
+        {
+            let album_score = normalized_levenshtein(&album1.clean_name(), &album2.clean_name());
+            score = f64::midpoint(score, album_score);
+        }
+        score
+    }
+
+    /// Score of `other` as a search result for this song, between 0 and 1
+    ///
+    /// An identical ISRC scores 1, otherwise the score weighs the title resemblance, the
+    /// shared artists, the duration difference and the album resemblance, in that order.
+    /// Terms that can't be computed (no artists, duration or album) count as 0.5. When both
+    /// songs have a release year, it weighs `options.year_weight` of the score, telling apart
+    /// re-recordings and remasters with the same title.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn match_score(&self, other: &Self, options: &MatchOptions) -> f64 {
+        if self.isrc.is_some() && self.isrc == other.isrc {
+            return 1.0;
+        }
+        // the recording found on MusicBrainz wins among the candidates that compare equal
+        if self.isrc_hint.is_some() && self.isrc_hint == other.isrc {
+            return 1.0;
+        }
+        let title = self.title_similarity(other);
+
+        let artists1 = self.credited_artists();
+        let artists2 = other.credited_artists();
+        let artists = if artists1.is_empty() || artists2.is_empty() {
+            0.5
+        } else {
+            let shared = artists1
+                .iter()
+                .filter(|a1| artists2.iter().any(|a2| Artist::names_overlap(a1, a2)))
+                .count();
+            shared as f64 / artists1.len().max(artists2.len()) as f64
+        };
+
+        let duration = if self.duration_ms == 0 || other.duration_ms == 0 {
+            0.5
+        } else {
+            let delta = self.duration_ms.abs_diff(other.duration_ms) as f64;
+            (1.0 - delta / options.duration_tolerance_ms.max(1) as f64).max(0.0)
+        };
+
+        let album = match (&self.album, &other.album) {
+            (Some(album1), Some(album2)) if !self.is_single() && !other.is_single() => {
+                normalized_levenshtein(&album1.clean_name(), &album2.clean_name())
+            }
+            _ => 0.5,
+        };
+
+        let score = 0.4 * title + 0.25 * artists + 0.2 * duration + 0.15 * album;
+        match (self.year(), other.year()) {
+            (Some(year1), Some(year2)) => {
+                let year = if year1 == year2 { 1.0 } else { 0.0 };
+                (1.0 - options.year_weight) * score + options.year_weight * year
+            }
+            _ => score,
         }
-        // Query: Track + Artist
-        for artist in self.artists.iter().rev() {
-            let artist_name = artist.clean_name();
-            let tr_ar_query = format!("{} {}", track_name, artist_name);
-            queries.push(tr_ar_query);
+    }
+
+    /// Search result with the highest `match_score` among the ones matching this song,
+    /// `None` when no result reaches `options.min_score`
+    ///
+    /// With `options.prefer_explicit`, a result as explicit as this song wins over a better
+    /// scored one that isn't, e.g. the album version over a clean radio edit. With
+    /// `options.prefer_album`, a result from the album of this song wins over the other ones.
+    pub fn best_match(&self, results: impl IntoIterator<Item = Song>, options: &MatchOptions) -> Option<Song> {
+        let same_explicit = |result: &Song| {
+            options.prefer_explicit && self.explicit.is_some() && result.explicit == self.explicit
+        };
+        let same_album = |result: &Song| {
+            options.prefer_album
+                && match (&self.album, &result.album) {
+                    (Some(album1), Some(album2)) => album1.clean_name() == album2.clean_name(),
+                    _ => false,
+                }
+        };
+        let mut best: Option<((bool, bool), f64, Song)> = None;
+        for result in results {
+            if !self.compare_with(&result, options) {
+                debug!("search result {} rejected for {}", result, self);
+                continue;
+            }
+            let score = self.match_score(&result, options);
+            debug!("search result {} scored {:.3} for {}", result, score, self);
+            if score < options.min_score {
+                continue;
+            }
+            let preferred = (same_explicit(&result), same_album(&result));
+            if best
+                .as_ref()
+                .is_none_or(|(p, s, _)| (preferred, score) > (*p, *s))
+            {
+                best = Some((preferred, score, result));
+            }
+        }
+        best.map(|(_, _, song)| song)
+    }
+
+    /// Search queries for the song, most promising first:
+    /// 1. title + primary artist
+    /// 2. title + main artists, when some artists are only featured in the title
+    /// 3. title + all artists
+    /// 4. title + album
+    /// 5. title alone
+    ///
+    /// The list is returned reversed since search implementations pop the queries
+    pub fn build_queries(&self) -> Vec<String> {
+        let (track_name, featured) = normalize_title(&self.name);
+        let artists: Vec<String> = self.artists.iter().map(Artist::clean_name).collect();
+        let main_artists: Vec<&str> = artists
+            .iter()
+            .filter(|a| !featured.contains(a))
+            .map(String::as_str)
+            .collect();
+
+        let mut queries = vec![];
+        if let Some(primary) = artists.first() {
+            queries.push(format!("{} {}", track_name, primary));
+            if !main_artists.is_empty() {
+                queries.push(format!("{} {}", track_name, main_artists.join(" ")));
+            }
+            queries.push(format!("{} {}", track_name, artists.join(" ")));
         }
-        // Query: Track + Artist + Album
         if let Some(album) = self.album.as_ref() {
-            let album_name = album.clean_name();
-            for artist in self.artists.iter().rev() {
-                let artist_name = artist.clean_name();
-                let tr_ar_al_query = format!("{} {} {}", track_name, artist_name, album_name);
-                queries.push(tr_ar_al_query);
+            queries.push(format!("{} {}", track_name, album.clean_name()));
+        }
+        queries.push(track_name);
+
+        let mut ladder: Vec<String> = vec![];
+        for query in queries {
+            if !ladder.contains(&query) {
+                ladder.push(query);
             }
         }
-        queries
+        ladder.reverse();
+        ladder
     }
 }
 
@@ -228,10 +867,25 @@ impl std::fmt::Display for Song {
     }
 }
 
+/// Add search results to `candidates`, without duplicates and up to `limit` songs
+pub fn push_candidates(candidates: &mut Vec<Song>, results: Vec<Song>, limit: usize) {
+    for song in results {
+        if candidates.len() >= limit {
+            break;
+        }
+        if !candidates.iter().any(|c| c.id == song.id) {
+            candidates.push(song);
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Album {
     pub id: Option<String>,
     pub name: String,
+    /// Release year, missing from older exports and from the responses without it
+    #[serde(default)]
+    pub year: Option<u16>,
 }
 
 impl Album {
@@ -240,6 +894,69 @@ impl Album {
     }
 }
 
+/// Album saved in the library of the user, `--sync-albums`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SavedAlbum {
+    pub source: MusicApiType,
+    pub album: Album,
+    pub artists: Vec<Artist>,
+    /// Barcode of the release, see `clean_upc`
+    pub upc: Option<String>,
+}
+
+impl SavedAlbum {
+    /// Whether both albums are the same release: same id on the same platform, same UPC, or
+    /// resembling names with an artist in common
+    ///
+    /// Different UPCs don't rule out a match, as the same album gets a barcode per region.
+    pub fn compare(&self, other: &Self) -> bool {
+        if self.source == other.source {
+            return self.album.id == other.album.id;
+        }
+        if self.upc.is_some() && self.upc == other.upc {
+            return true;
+        }
+
+        let name1 = generic_name_clean(&fold_name(&self.album.name));
+        let name2 = generic_name_clean(&fold_name(&other.album.name));
+        if normalized_levenshtein(&name1, &name2) < 0.8 {
+            return false;
+        }
+        if self.artists.is_empty() || other.artists.is_empty() {
+            return true;
+        }
+        self.artists.iter().any(|a1| {
+            let match_names = a1.match_names();
+            other
+                .artists
+                .iter()
+                .any(|a2| Artist::names_overlap(&match_names, &a2.match_names()))
+        })
+    }
+
+    /// Result with the same UPC, otherwise the first result that is the same album
+    pub fn best_match(&self, mut results: Vec<SavedAlbum>) -> Option<SavedAlbum> {
+        if self.upc.is_some()
+            && let Some(i) = results.iter().position(|a| a.upc == self.upc)
+        {
+            return Some(results.swap_remove(i));
+        }
+        results.into_iter().find(|a| self.compare(a))
+    }
+}
+
+impl std::fmt::Display for SavedAlbum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let artists = self
+            .artists
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        f.write_fmt(format_args!("{} - {}", self.album.name, artists))
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Artist {
     pub id: Option<String>,
@@ -251,6 +968,44 @@ impl Artist {
         // TODO: Add ' - ' parsing?
         generic_name_clean(&self.name)
     }
+
+    /// Folded names the artist is compared on: its full name, each artist of a combined
+    /// credit such as "DJ Snake, Lil Jon" and the alias of "米津玄師 (Kenshi Yonezu)"
+    pub fn match_names(&self) -> Vec<String> {
+        static PATTERNS: OnceLock<[Regex; 2]> = OnceLock::new();
+        let [split_re, alias_re] = PATTERNS.get_or_init(|| {
+            [
+                Regex::new(r"(?i)\s*(?:,|;|&|\s(?:x|and|feat\.?|ft\.?|featuring)\s)\s*").unwrap(),
+                Regex::new(r"[(\[]([^)\]]+)[)\]]").unwrap(),
+            ]
+        });
+
+        let folded = fold_name(&self.name);
+        let mut names = vec![generic_name_clean(&folded)];
+        names.extend(split_re.split(&folded).map(generic_name_clean));
+        names.extend(alias_re.captures_iter(&folded).map(|cap| generic_name_clean(&cap[1])));
+
+        let mut unique: Vec<String> = vec![];
+        for name in names {
+            if !name.is_empty() && !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        unique
+    }
+
+    /// Whether both artists have the same folded name, `--sync-artists`
+    pub fn is_same(&self, other: &Self) -> bool {
+        let name = generic_name_clean(&fold_name(&self.name));
+        !name.is_empty() && name == generic_name_clean(&fold_name(&other.name))
+    }
+
+    /// Whether any name of `names1` resembles a name of `names2`
+    fn names_overlap(names1: &[String], names2: &[String]) -> bool {
+        names1
+            .iter()
+            .any(|n1| names2.iter().any(|n2| normalized_levenshtein(n1, n2) >= 0.8))
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -276,4 +1031,546 @@ pub struct OAuthRefreshToken {
     pub expires_in: u64,
     pub scope: String,
     pub token_type: String,
+    /// Set when the refresh token is replaced on each refresh
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+/// Error of a token endpoint, e.g. `authorization_pending` during the device flow
+#[derive(Deserialize, Debug)]
+pub struct OAuthErrorRes {
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+/// Resolved once a line is read on stdin. The read is done on its own thread rather than with
+/// `spawn_blocking`, the runtime would otherwise wait for it on shutdown when enter is never pressed
+fn read_enter() -> tokio::sync::oneshot::Receiver<()> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        if std::io::stdin().read_line(&mut String::new()).is_ok() {
+            let _ = tx.send(());
+        }
+    });
+    rx
+}
+
+/// Polling of the token endpoint during the OAuth device flow (RFC 8628)
+pub struct DeviceFlowPoll {
+    pub interval: Duration,
+    deadline: Instant,
+    map_error: fn(reqwest::StatusCode, &str) -> color_eyre::Report,
+}
+
+impl DeviceFlowPoll {
+    /// Added to the interval each time the server answers `slow_down`
+    const SLOW_DOWN_STEP: Duration = Duration::from_secs(5);
+
+    /// Poll every `interval` seconds until the device code expires in `expires_in` seconds
+    pub fn new(interval: u64, expires_in: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(interval),
+            deadline: Instant::now() + Duration::from_secs(expires_in),
+            map_error: Self::token_error,
+        }
+    }
+
+    /// Map the errors of the token endpoint that aren't part of the device flow (e.g. a
+    /// rejected client) with the platform's own OAuth error messages
+    #[must_use]
+    pub fn with_error_mapping(
+        mut self,
+        map_error: fn(reqwest::StatusCode, &str) -> color_eyre::Report,
+    ) -> Self {
+        self.map_error = map_error;
+        self
+    }
+
+    /// Poll the token endpoint with `request` until the app is authorized. With `--press-enter`
+    /// in a terminal, pressing enter polls right away instead of waiting for the interval.
+    /// A failed response that ends the polling is saved in the debug directory
+    pub async fn run<T, F>(&mut self, request: F, config: &ConfigArgs, platform: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut enter = if config.press_enter && atty::is(Stream::Stdin) {
+            info!("Please authorize the app in your browser and press enter");
+            Some(read_enter())
+        } else {
+            info!(
+                "Waiting for user authorization, the code expires in {} minutes",
+                self.deadline.saturating_duration_since(Instant::now()).as_secs() / 60
+            );
+            None
+        };
+        loop {
+            let pressed = if let Some(enter) = &mut enter {
+                tokio::select! {
+                    () = tokio::time::sleep(self.interval) => false,
+                    _ = enter => true,
+                }
+            } else {
+                tokio::time::sleep(self.interval).await;
+                false
+            };
+            if pressed {
+                enter = None;
+            }
+            let res = request().send().await?;
+            let status = res.status();
+            let text = res.text().await?;
+            if config.debug {
+                write_diagnostic(config, &format!("{}_last_res.json", platform), text.as_bytes())?;
+            }
+            match self.handle(status, &text) {
+                Ok(Some(token)) => return Ok(token),
+                Ok(None) => {}
+                Err(e) if status.is_success() => return Err(e),
+                Err(e) => {
+                    let error_file = save_error_diagnostic(config, status.as_u16(), &text)?;
+                    return Err(eyre!("{}\nResponse saved to: {}", e, error_file.display()));
+                }
+            }
+            debug!("app not authorized yet, polling again in {:?}", self.interval);
+        }
+    }
+
+    /// Handle a response of the token endpoint, the token is returned once the app is authorized
+    pub fn handle<T: DeserializeOwned>(
+        &mut self,
+        status: reqwest::StatusCode,
+        text: &str,
+    ) -> Result<Option<T>> {
+        if status.is_success() {
+            return Ok(Some(serde_json::from_str(text)?));
+        }
+        let Ok(res) = serde_json::from_str::<OAuthErrorRes>(text) else {
+            return Err((self.map_error)(status, text));
+        };
+        match res.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => {
+                self.interval += Self::SLOW_DOWN_STEP;
+                debug!("token endpoint asked to slow down, polling every {:?}", self.interval);
+            }
+            "expired_token" => return Err(Self::expired()),
+            "access_denied" => return Err(eyre!("The authorization of the app was denied")),
+            _ => return Err((self.map_error)(status, text)),
+        }
+        if Instant::now() >= self.deadline {
+            return Err(Self::expired());
+        }
+        Ok(None)
+    }
+
+    /// Default message for the errors of the token endpoint
+    fn token_error(status: reqwest::StatusCode, text: &str) -> color_eyre::Report {
+        match serde_json::from_str::<OAuthErrorRes>(text) {
+            Ok(res) => eyre!(
+                "Failed to request the OAuth token ({}): {}",
+                status,
+                res.error_description.as_deref().unwrap_or(&res.error)
+            ),
+            Err(_) => eyre!("Invalid HTTP status: {}", status),
+        }
+    }
+
+    pub fn expired() -> color_eyre::Report {
+        eyre!(
+            "The authorization code expired before the app was authorized. \
+            Run SyncDisBoi again and authorize the app with the new code."
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{self, credited, saved_album};
+    use crate::utils::clean_upc;
+
+    fn song(source: MusicApiType, name: &str, album: &str, artist: &str, duration_ms: usize) -> Song {
+        Song {
+            album: Some(mock_api::album(album)),
+            artists: vec![credited(artist)],
+            duration_ms,
+            ..mock_api::song(source, name, name)
+        }
+    }
+
+    fn with_artists(mut song: Song, artists: &[&str]) -> Song {
+        song.artists = artists.iter().map(|a| credited(a)).collect();
+        song
+    }
+
+    fn options(duration_tolerance_ms: usize, ignore_album: bool, strict: bool) -> MatchOptions {
+        MatchOptions {
+            duration_tolerance_ms,
+            ignore_album,
+            strict,
+            min_score: MatchOptions::DEFAULT_MIN_SCORE,
+            year_weight: MatchOptions::DEFAULT_YEAR_WEIGHT,
+            prefer_explicit: false,
+            prefer_album: false,
+        }
+    }
+
+    #[test]
+    fn test_compare_remaster() {
+        let original = song(MusicApiType::Spotify, "Heroes", "Heroes", "David Bowie", 371_000);
+        let remaster = song(
+            MusicApiType::YtMusic,
+            "Heroes - 2017 Remaster",
+            "Heroes (2017 Remaster)",
+            "David Bowie",
+            377_500,
+        );
+        assert!(!original.compare(&remaster));
+        assert!(original.compare_with(&remaster, &options(7000, false, false)));
+        assert!(original.compare_with(&remaster, &options(7000, false, true)));
+    }
+
+    #[test]
+    fn test_compare_live() {
+        // a longer live recording never matches
+        let studio = song(MusicApiType::Spotify, "Hotel California", "Hotel California", "Eagles", 391_000);
+        let live = song(MusicApiType::Tidal, "Hotel California - Live", "Hell Freezes Over", "Eagles", 434_000);
+        assert!(!studio.compare(&live));
+        assert!(!studio.compare_with(&live, &options(5000, true, false)));
+
+        // a live recording of the same length only matches when ignoring the album
+        let studio = song(MusicApiType::Spotify, "Creep", "Pablo Honey", "Radiohead", 238_000);
+        let live = song(MusicApiType::Tidal, "Creep - Live", "Live at Glastonbury", "Radiohead", 239_000);
+        assert!(!studio.compare(&live));
+        assert!(studio.compare_with(&live, &options(5000, true, false)));
+        let cover = song(MusicApiType::Tidal, "Creep", "Covers", "Scala & Kolacny Brothers", 239_000);
+        assert!(!studio.compare_with(&cover, &options(5000, true, false)));
+    }
+
+    #[test]
+    fn test_compare_clean_explicit() {
+        let mut explicit = song(MusicApiType::Spotify, "HUMBLE.", "DAMN.", "Kendrick Lamar", 177_000);
+        let mut clean = song(MusicApiType::YtMusic, "HUMBLE. (Clean)", "DAMN.", "Kendrick Lamar", 177_000);
+        assert!(explicit.compare(&clean));
+        assert!(explicit.compare_with(&clean, &options(5000, false, true)));
+
+        // different recordings when both have an ISRC
+        explicit.isrc = Some("USUM71703861".to_string());
+        clean.isrc = Some("USUM71703862".to_string());
+        assert!(!explicit.compare(&clean));
+        assert!(!explicit.compare_with(&clean, &options(60_000, true, false)));
+    }
+
+    #[test]
+    fn test_compare_single() {
+        let single = song(MusicApiType::Spotify, "Blinding Lights", "Blinding Lights", "The Weeknd", 200_000);
+        let album = song(MusicApiType::Tidal, "Blinding Lights", "After Hours", "The Weeknd", 200_000);
+        assert!(single.compare(&album));
+        assert!(!single.compare_with(&album, &options(5000, false, true)));
+    }
+
+    #[test]
+    fn test_compare_folded_artists() {
+        let pairs = [
+            ("Beyoncé", "Beyonce"),
+            ("Mötley Crüe", "Motley Crue"),
+            ("Tiësto", "Tiesto"),
+            ("DJ Snake, Lil Jon", "DJ Snake"),
+            ("Sigur Rós", "Sigur Ros"),
+            ("Björk", "Bjork"),
+            ("Røyksopp", "Royksopp"),
+            ("Maná", "Mana"),
+            ("Michael Bublé", "Michael Buble"),
+            ("Blue Öyster Cult", "Blue Oyster Cult"),
+            ("ROSALÍA", "Rosalía"),
+            ("Måneskin", "Maneskin"),
+            ("ＹＯＡＳＯＢＩ", "YOASOBI"),
+            ("米津玄師 (Kenshi Yonezu)", "Kenshi Yonezu"),
+            ("Daddy Yankee & Snow", "Snow"),
+            ("Stromae x Pomme", "Pomme"),
+        ];
+        // without the album, a shared artist is required
+        let options = options(5000, true, false);
+        for (artist1, artist2) in pairs {
+            let song1 = song(MusicApiType::Spotify, "Song", "Album", artist1, 200_000);
+            let song2 = song(MusicApiType::Tidal, "Song", "Other Album", artist2, 200_000);
+            assert!(song1.compare_with(&song2, &options), "{} vs {}", artist1, artist2);
+            assert!(song2.compare_with(&song1, &options), "{} vs {}", artist2, artist1);
+        }
+
+        let pairs = [("Tiësto", "Tove Lo"), ("Sing King", "Toto"), ("Stromae x Pomme", "Stromae Pomme Duo")];
+        for (artist1, artist2) in pairs {
+            let song1 = song(MusicApiType::Spotify, "Song", "Album", artist1, 200_000);
+            let song2 = song(MusicApiType::Tidal, "Song", "Other Album", artist2, 200_000);
+            assert!(!song1.compare_with(&song2, &options), "{} vs {}", artist1, artist2);
+        }
+
+        // titles are folded too
+        let title1 = song(MusicApiType::Spotify, "Déjà Vu", "B'Day", "Beyoncé", 240_000);
+        let title2 = song(MusicApiType::YtMusic, "DEJA VU", "B'Day", "Beyonce", 240_000);
+        assert!(title1.compare(&title2));
+        let title2 = song(MusicApiType::YtMusic, "Ｌｅｍｏｎ", "STRAY SHEEP", "米津玄師", 255_000);
+        let title1 = song(MusicApiType::Spotify, "Lemon", "STRAY SHEEP", "Kenshi Yonezu", 255_000);
+        assert!(title1.compare(&title2));
+
+        // only the comparison is folded, the names searched for are kept
+        assert_eq!(credited("Mötley Crüe").clean_name(), "mötley crüe");
+    }
+
+    #[test]
+    fn test_compare_saved_albums() {
+        let album = |source: MusicApiType, id: &str, name: &str, artist: &str, upc: Option<&str>| SavedAlbum {
+            artists: vec![credited(artist)],
+            upc: clean_upc(upc.map(str::to_string)),
+            ..saved_album(&source, id, name, artist, None)
+        };
+        let homogenic = album(MusicApiType::Spotify, "s1", "Homogenic", "Björk", Some("75992248722"));
+
+        // the 13-digit EAN of the same release
+        let same_upc = album(MusicApiType::Tidal, "t1", "Homogenic (Remastered)", "Bjork", Some("0075992248722"));
+        assert!(homogenic.compare(&same_upc));
+        let by_name = album(MusicApiType::Tidal, "t2", "HOMOGENIC (Deluxe Edition)", "Bjork", Some("123"));
+        assert!(homogenic.compare(&by_name));
+        let other_artist = album(MusicApiType::Tidal, "t3", "Homogenic", "Homogenic Tribute Band", None);
+        assert!(!homogenic.compare(&other_artist));
+        let other_album = album(MusicApiType::Tidal, "t4", "Vespertine", "Björk", None);
+        assert!(!homogenic.compare(&other_album));
+        // same platform, compared by id
+        assert!(!homogenic.compare(&album(MusicApiType::Spotify, "s2", "Homogenic", "Björk", None)));
+
+        assert!(credited("Björk").is_same(&credited("BJORK")));
+        assert!(!credited("Björk").is_same(&credited("Björk & Brodsky Quartet")));
+    }
+
+    #[test]
+    fn test_best_match() {
+        let original = song(MusicApiType::Spotify, "Africa", "Toto IV", "Toto", 295_000);
+        let karaoke = song(MusicApiType::Tidal, "Africa", "Africa", "Sing King", 295_500);
+        let mut real = song(MusicApiType::Tidal, "Africa", "Toto IV", "Toto", 297_000);
+        real.id = "real".to_string();
+        let remix = song(MusicApiType::Tidal, "Africa (Remix)", "Africa", "Toto", 410_000);
+
+        // the karaoke single also passes compare, the first result isn't blindly taken
+        assert!(original.compare(&karaoke));
+        let options = MatchOptions::default();
+        assert!(original.match_score(&karaoke, &options) < original.match_score(&real, &options));
+        let results = vec![karaoke.clone(), real.clone(), remix];
+        let best = original.best_match(results, &options);
+        assert_eq!(best.unwrap().id, "real");
+
+        // results under the threshold are never picked
+        let options = MatchOptions {
+            min_score: 0.7,
+            ..MatchOptions::default()
+        };
+        assert!(original.best_match(vec![karaoke.clone()], &options).is_none());
+        assert_eq!(original.best_match(vec![karaoke, real.clone()], &options).unwrap().id, "real");
+
+        // an identical ISRC has the maximum score
+        let mut isrc1 = original.clone();
+        isrc1.isrc = Some("USSM18200001".to_string());
+        let mut isrc2 = song(MusicApiType::Tidal, "Africa - 2018 Remaster", "Africa", "TOTO", 290_000);
+        isrc2.isrc = isrc1.isrc.clone();
+        assert!((isrc1.match_score(&isrc2, &options) - 1.0).abs() < f64::EPSILON);
+
+        // an ISRC hint ranks its recording first, but doesn't reject the others
+        let mut hinted = original.clone();
+        hinted.isrc_hint = Some("USSM18200002".to_string());
+        let mut other_isrc = real.clone();
+        other_isrc.isrc = Some("USSM18200003".to_string());
+        let mut hint_isrc = song(MusicApiType::Tidal, "Africa", "Africa", "Toto", 296_000);
+        hint_isrc.id = "hinted".to_string();
+        hint_isrc.isrc = hinted.isrc_hint.clone();
+        assert!(hinted.compare_with(&other_isrc, &options));
+        let best = hinted.best_match(vec![other_isrc.clone(), hint_isrc], &options);
+        assert_eq!(best.unwrap().id, "hinted");
+        assert_eq!(hinted.best_match(vec![other_isrc], &options).unwrap().id, "real");
+    }
+
+    #[test]
+    fn test_best_match_year() {
+        fn with_year(mut song: Song, id: &str, year: u16) -> Song {
+            song.id = id.to_string();
+            song.album.as_mut().unwrap().year = Some(year);
+            song
+        }
+        let hurt = song(MusicApiType::Spotify, "Hurt", "Hurt", "Johnny Cash", 218_000);
+        let original = with_year(hurt, "original", 2002);
+        // the same song re-released under the same name
+        let rerelease = with_year(original.with_id(MusicApiType::Tidal, ""), "rerelease", 2021);
+        let same = with_year(original.with_id(MusicApiType::Tidal, ""), "same", 2002);
+
+        let options = MatchOptions::default();
+        assert!(original.match_score(&rerelease, &options) < original.match_score(&same, &options));
+        let best = original.best_match(vec![rerelease.clone(), same.clone()], &options);
+        assert_eq!(best.unwrap().id, "same");
+
+        // without a weight, or without a year on one side, the first result is picked
+        let options = MatchOptions {
+            year_weight: 0.0,
+            ..MatchOptions::default()
+        };
+        let best = original.best_match(vec![rerelease.clone(), same.clone()], &options);
+        assert_eq!(best.unwrap().id, "rerelease");
+        let mut unknown = original.clone();
+        unknown.album.as_mut().unwrap().year = None;
+        let best = unknown.best_match(vec![rerelease, same], &MatchOptions::default());
+        assert_eq!(best.unwrap().id, "rerelease");
+
+        // exports from before the year was recorded are still readable
+        let album: Album = serde_json::from_str(r#"{"id": null, "name": "Hurt"}"#).unwrap();
+        assert_eq!(album.year, None);
+    }
+
+    #[test]
+    fn test_best_match_explicit() {
+        let mut original = song(MusicApiType::Spotify, "Humble", "DAMN.", "Kendrick Lamar", 177_000);
+        original.explicit = Some(true);
+        let mut clean = original.with_id(MusicApiType::YtMusic, "clean");
+        clean.explicit = Some(false);
+        // a slightly different duration lowers the score of the explicit version
+        let mut explicit = original.with_id(MusicApiType::YtMusic, "explicit");
+        explicit.duration_ms += 3000;
+
+        // the best scored result by default, the explicit one when preferred
+        let results = vec![clean.clone(), explicit.clone()];
+        assert_eq!(original.best_match(results.clone(), &MatchOptions::default()).unwrap().id, "clean");
+        let options = MatchOptions {
+            prefer_explicit: true,
+            ..MatchOptions::default()
+        };
+        assert_eq!(original.best_match(results.clone(), &options).unwrap().id, "explicit");
+
+        // unknown on the source, the score decides
+        original.explicit = None;
+        assert_eq!(original.best_match(results, &options).unwrap().id, "clean");
+
+        // exports from before the flag was recorded are still readable
+        let song: Song = serde_json::from_str(&serde_json::to_string(&clean).unwrap().replace(r#","explicit":false"#, "")).unwrap();
+        assert_eq!(song.explicit, None);
+    }
+
+    #[test]
+    fn test_alt_names_match() {
+        let original = song(MusicApiType::Spotify, "夜に駆ける", "THE BOOK", "YOASOBI", 261_000);
+        let mut romanized = song(MusicApiType::YtMusic, "Yoru ni Kakeru", "THE BOOK", "YOASOBI", 261_000);
+        assert!(!original.compare(&romanized));
+
+        // the title in its original script is compared too
+        romanized.alt_names = vec!["夜に駆ける".to_string()];
+        assert!(original.compare(&romanized));
+        assert!(romanized.compare(&original));
+        let score = original.match_score(&romanized, &MatchOptions::default());
+        assert!(score > 0.99, "{}", score);
+    }
+
+    #[test]
+    fn test_build_queries() {
+        let collab = with_artists(
+            song(
+                MusicApiType::Spotify,
+                "Don't You Worry Child (feat. John Martin)",
+                "Until Now",
+                "",
+                0,
+            ),
+            &["Axwell", "Sebastian Ingrosso", "Steve Angello", "John Martin"],
+        );
+        let mut queries = collab.build_queries();
+        queries.reverse();
+        assert_eq!(
+            queries,
+            vec![
+                "dont you worry child axwell",
+                "dont you worry child axwell sebastian ingrosso steve angello",
+                "dont you worry child axwell sebastian ingrosso steve angello john martin",
+                "dont you worry child until now",
+                "dont you worry child",
+            ]
+        );
+        // the first query to try is popped
+        assert_eq!(collab.build_queries().pop().unwrap(), "dont you worry child axwell");
+
+        // featured artists credited only in the title
+        let featured = with_artists(
+            song(MusicApiType::YtMusic, "Lean On (feat. MØ & DJ Snake)", "Peace Is The Mission", "", 0),
+            &["Major Lazer", "MØ", "DJ Snake"],
+        );
+        let mut queries = featured.build_queries();
+        queries.reverse();
+        assert_eq!(
+            queries,
+            vec![
+                "lean on major lazer",
+                "lean on major lazer mø dj snake",
+                "lean on peace is the mission",
+                "lean on",
+            ]
+        );
+
+        let mut single = song(MusicApiType::Tidal, "Strobe", "", "deadmau5", 0);
+        single.album = None;
+        assert_eq!(single.build_queries(), vec!["strobe", "strobe deadmau5"]);
+    }
+
+    #[test]
+    fn test_device_flow_poll() {
+        use reqwest::StatusCode;
+
+        let mut poll = DeviceFlowPoll::new(5, 1800);
+        assert_eq!(poll.interval, Duration::from_secs(5));
+
+        let pending = r#"{"error":"authorization_pending","error_description":"Precondition Required"}"#;
+        assert!(poll.handle::<OAuthToken>(StatusCode::PRECONDITION_REQUIRED, pending).unwrap().is_none());
+        assert_eq!(poll.interval, Duration::from_secs(5));
+
+        let slow_down = r#"{"error":"slow_down","error_description":"Forbidden"}"#;
+        assert!(poll.handle::<OAuthToken>(StatusCode::FORBIDDEN, slow_down).unwrap().is_none());
+        assert_eq!(poll.interval, Duration::from_secs(10));
+
+        let token = r#"{"access_token":"AT","expires_in":3599,"refresh_token":"RT","scope":"https://www.googleapis.com/auth/youtube","token_type":"Bearer"}"#;
+        let token: OAuthToken = poll.handle(StatusCode::OK, token).unwrap().unwrap();
+        assert_eq!((token.access_token.as_str(), token.refresh_token.as_str()), ("AT", "RT"));
+
+        let expired = r#"{"error":"expired_token","error_description":"Bad Request"}"#;
+        let err = poll.handle::<OAuthToken>(StatusCode::BAD_REQUEST, expired).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+        let denied = r#"{"error":"access_denied","error_description":"Forbidden"}"#;
+        assert!(poll.handle::<OAuthToken>(StatusCode::FORBIDDEN, denied).is_err());
+        assert!(poll.handle::<OAuthToken>(StatusCode::INTERNAL_SERVER_ERROR, "<html></html>").is_err());
+
+        // Tidal answers 400 while pending, with a sub status
+        let mut poll = DeviceFlowPoll::new(2, 300);
+        let pending = r#"{"status":400,"error":"authorization_pending","sub_status":1002,"error_description":"Device Authorization code is not authorized yet"}"#;
+        assert!(poll.handle::<OAuthToken>(StatusCode::BAD_REQUEST, pending).unwrap().is_none());
+        let client = r#"{"status":401,"error":"invalid_client","sub_status":1002,"error_description":"Client not found"}"#;
+        let err = poll.handle::<OAuthToken>(StatusCode::UNAUTHORIZED, client).unwrap_err();
+        assert!(err.to_string().contains("Client not found"));
+
+        // a code still pending fails once expired
+        let mut poll = DeviceFlowPoll::new(5, 0);
+        let err = poll.handle::<OAuthToken>(StatusCode::BAD_REQUEST, pending).unwrap_err();
+        assert!(err.to_string().contains("expired"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_flow_run() {
+        use clap::Parser;
+
+        use crate::mock_api::serve_json;
+
+        let pending = r#"{"error":"authorization_pending","error_description":"Precondition Required"}"#;
+        let token = r#"{"access_token":"AT","expires_in":3599,"refresh_token":"RT","scope":"","token_type":"Bearer"}"#;
+        // enter is never pressed, the polling goes on until the app is authorized
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--press-enter"]);
+        let (url, requests) = serve_json(vec![(428, pending), (428, pending), (200, token)]).await;
+        let start = Instant::now();
+        let mut poll = DeviceFlowPoll::new(5, 1800);
+        let client = reqwest::Client::new();
+        let token: OAuthToken = poll.run(|| client.post(&url), &config, "test").await.unwrap();
+        assert_eq!(token.access_token, "AT");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        assert!(start.elapsed() >= Duration::from_secs(15));
+    }
 }
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::de::{Error, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::music_api::{MusicApiType, Song};
+
+/// Manual match decisions, stored as JSON (or TOML with a `.toml` extension) in the config
+/// directory or at the path given with `--overrides`
+///
+/// Source songs are identified by the keys returned by `song_keys`, the first
+/// matching key wins.
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+    /// source song key -> destination platform short name -> destination song id
+    #[serde(default, deserialize_with = "deserialize_overrides")]
+    pub overrides: BTreeMap<String, BTreeMap<String, String>>,
+    /// source song keys that should never be synchronized
+    #[serde(default, deserialize_with = "deserialize_blacklist")]
+    pub blacklist: Vec<String>,
+}
+
+/// Check a source song key has one of the formats of `song_keys`
+fn validate_key(key: &str) -> Result<(), String> {
+    if let Some(isrc) = key.strip_prefix("isrc:") {
+        if isrc.is_empty() || !isrc.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Err(format!("invalid ISRC in song key \"{}\"", key));
+        }
+        return Ok(());
+    }
+    if let Some((platform, id)) = key.split_once(':')
+        && validate_platform(platform).is_ok()
+    {
+        if id.is_empty() {
+            return Err(format!("missing song id in song key \"{}\"", key));
+        }
+        return Ok(());
+    }
+    if key.split_once(" - ").is_some_and(|(a, t)| !a.trim().is_empty() && !t.trim().is_empty()) {
+        return Ok(());
+    }
+    Err(format!(
+        "invalid song key \"{}\", expected \"isrc:<ISRC>\", \"<platform>:<song id>\" or \"<artists> - <title>\"",
+        key
+    ))
+}
+
+fn validate_platform(name: &str) -> Result<(), String> {
+    if name.parse::<MusicApiType>().is_ok_and(|p| p.short_name() == name) {
+        return Ok(());
+    }
+    Err(format!(
+        "unknown platform \"{}\", expected one of spotify, ytmusic, tidal, plex",
+        name
+    ))
+}
+
+fn validate_id(id: &str) -> Result<(), String> {
+    if id.trim().is_empty() {
+        return Err("empty destination song id".to_string());
+    }
+    Ok(())
+}
+
+/// Read a string checked by `validate`, failing while it's read so the error points at its line
+fn validated_string<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    validate: fn(&str) -> Result<(), String>,
+) -> Result<String, D::Error> {
+    struct Validated(fn(&str) -> Result<(), String>);
+
+    impl Visitor<'_> for Validated {
+        type Value = String;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string")
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<String, E> {
+            (self.0)(v).map_err(E::custom)?;
+            Ok(v.to_string())
+        }
+    }
+
+    deserializer.deserialize_str(Validated(validate))
+}
+
+/// Source song key
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct SongKey(String);
+
+impl<'de> Deserialize<'de> for SongKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        validated_string(deserializer, validate_key).map(Self)
+    }
+}
+
+/// Destination platform short name
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct PlatformName(String);
+
+impl<'de> Deserialize<'de> for PlatformName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        validated_string(deserializer, validate_platform).map(Self)
+    }
+}
+
+/// Destination song id
+struct SongId(String);
+
+impl<'de> Deserialize<'de> for SongId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        validated_string(deserializer, validate_id).map(Self)
+    }
+}
+
+fn deserialize_overrides<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, BTreeMap<String, String>>, D::Error> {
+    let overrides = BTreeMap::<SongKey, BTreeMap<PlatformName, SongId>>::deserialize(deserializer)?;
+    Ok(overrides
+        .into_iter()
+        .map(|(key, ids)| (key.0, ids.into_iter().map(|(p, id)| (p.0, id.0)).collect()))
+        .collect())
+}
+
+fn deserialize_blacklist<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let blacklist = Vec::<SongKey>::deserialize(deserializer)?;
+    Ok(blacklist.into_iter().map(|key| key.0).collect())
+}
+
+/// Keys identifying a source song, from the most to the least specific
+pub fn song_keys(song: &Song) -> Vec<String> {
+    let mut keys = vec![];
+    if let Some(isrc) = &song.isrc {
+        keys.push(format!("isrc:{}", isrc));
+    }
+    keys.push(format!("{}:{}", song.source.short_name(), song.id));
+    let artists = song
+        .artists
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    keys.push(format!("{} - {}", artists, song.name));
+    keys
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "toml")
+}
+
+impl Overrides {
+    pub const FILE_NAME: &'static str = "overrides.json";
+
+    /// Load the overrides file, a missing file is equivalent to an empty one
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content, is_toml(path)).map_err(|e| eyre!("invalid overrides file {:?}: {}", path, e))
+    }
+
+    fn parse(content: &str, toml: bool) -> Result<Self> {
+        if toml {
+            Ok(toml::from_str(content)?)
+        } else {
+            Ok(serde_json::from_str(content)?)
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = if is_toml(path) {
+            toml::to_string_pretty(self)?
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, song: &Song, dst: &MusicApiType) -> Option<&str> {
+        self.find(song, dst).map(|(_, id)| id)
+    }
+
+    /// Overridden destination song id of `song`, along with the key it was found with
+    pub fn find(&self, song: &Song, dst: &MusicApiType) -> Option<(String, &str)> {
+        song_keys(song).into_iter().find_map(|key| {
+            let id = self.overrides.get(&key)?.get(dst.short_name())?;
+            Some((key, id.as_str()))
+        })
+    }
+
+    pub fn set(&mut self, song: &Song, dst: &MusicApiType, dst_id: &str) {
+        let key = song_keys(song).swap_remove(0);
+        self.overrides
+            .entry(key)
+            .or_default()
+            .insert(dst.short_name().to_string(), dst_id.to_string());
+    }
+
+    pub fn is_blacklisted(&self, song: &Song) -> bool {
+        song_keys(song).iter().any(|key| self.blacklist.contains(key))
+    }
+
+    pub fn add_to_blacklist(&mut self, song: &Song) {
+        if !self.is_blacklisted(song) {
+            self.blacklist.push(song_keys(song).swap_remove(0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(isrc: Option<&str>) -> Song {
+        Song {
+            isrc: isrc.map(str::to_string),
+            duration_ms: 0,
+            ..crate::mock_api::song(MusicApiType::Spotify, "abc", "Song")
+        }
+    }
+
+    #[test]
+    fn test_overrides_keys() {
+        let mut overrides = Overrides::default();
+        overrides.set(&song(Some("US123")), &MusicApiType::YtMusic, "yt1");
+        overrides.add_to_blacklist(&song(None));
+        overrides.add_to_blacklist(&song(None));
+
+        assert_eq!(
+            overrides.get(&song(Some("US123")), &MusicApiType::YtMusic),
+            Some("yt1")
+        );
+        assert_eq!(overrides.get(&song(None), &MusicApiType::YtMusic), None);
+        assert_eq!(overrides.get(&song(Some("US123")), &MusicApiType::Tidal), None);
+        assert!(overrides.is_blacklisted(&song(Some("US123"))));
+        assert_eq!(overrides.blacklist, vec!["spotify:abc".to_string()]);
+
+        let json = serde_json::to_string(&overrides).unwrap();
+        assert_eq!(serde_json::from_str::<Overrides>(&json).unwrap(), overrides);
+        assert_eq!(serde_json::from_str::<Overrides>("{}").unwrap(), Overrides::default());
+    }
+
+    #[test]
+    fn test_overrides_validation() {
+        let json = r#"{
+  "overrides": {
+    "isrc:USUM71703861": { "ytmusic": "yt1" },
+    "spotify:abc": { "tidal": "12345" },
+    "Artist, Other - Song": { "plex": "42" }
+  },
+  "blacklist": ["ytmusic:def"]
+}"#;
+        let overrides = Overrides::parse(json, false).unwrap();
+        assert_eq!(overrides.overrides.len(), 3);
+        assert_eq!(overrides.find(&song(None), &MusicApiType::Tidal), Some(("spotify:abc".to_string(), "12345")));
+
+        let invalid = [
+            (r#"{"overrides": {"isrc:us-123": {"ytmusic": "yt1"}}}"#, "line 1", "invalid ISRC"),
+            ("{\n  \"overrides\": {\n    \"spotify:abc\": {\"youtube\": \"yt1\"}\n  }\n}", "line 3", "unknown platform"),
+            ("{\n  \"overrides\": {\n    \"spotify:abc\": {\"tidal\": \"\"}\n  }\n}", "line 3", "empty destination"),
+            ("{\n  \"blacklist\": [\n    \"Song\"\n  ]\n}", "line 3", "invalid song key"),
+            ("{\n  \"overide\": {}\n}", "line 2", "unknown field"),
+        ];
+        for (json, line, message) in invalid {
+            let err = Overrides::parse(json, false).unwrap_err().to_string();
+            assert!(err.contains(line) && err.contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_overrides_toml() {
+        let toml = r#"
+blacklist = ["spotify:def"]
+
+[overrides."isrc:USUM71703861"]
+ytmusic = "yt1"
+tidal = "12345"
+"#;
+        let overrides = Overrides::parse(toml, true).unwrap();
+        assert_eq!(overrides.get(&song(Some("USUM71703861")), &MusicApiType::Tidal), Some("12345"));
+        assert_eq!(overrides.blacklist, vec!["spotify:def".to_string()]);
+
+        let saved = toml::to_string_pretty(&overrides).unwrap();
+        assert_eq!(Overrides::parse(&saved, true).unwrap(), overrides);
+
+        let err = Overrides::parse("[overrides.\"spotify:abc\"]\nytmusic = \"yt1\"\nspotify = 3\n", true)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("line 3"), "{}", err);
+    }
+}
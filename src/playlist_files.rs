@@ -0,0 +1,403 @@
+//! Playlists imported from CSV files (Exportify, `TuneMyMusic`...) and from text files listing
+//! "Artist - Title" lines, `import --input-format csv|txt`. Their songs have no id on any
+//! platform, they are searched by their metadata
+
+use std::path::Path;
+
+use clap::{Args, ValueEnum};
+use color_eyre::eyre::{Result, eyre};
+
+use crate::music_api::{Album, Artist, MusicApiType, Playlist, Song};
+
+/// Format of the imported file, `--input-format`
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportFormat {
+    /// File written by the `export` destination
+    #[default]
+    Json,
+    /// CSV file with a row per song, see the `--*-column` options
+    Csv,
+    /// Text file with an "Artist - Title" line per song, a `[Name]` line starts a new playlist
+    Txt,
+}
+
+impl ImportFormat {
+    /// Extension of the files imported from a directory
+    fn extension(self) -> &'static str {
+        match self {
+            ImportFormat::Json => "json",
+            ImportFormat::Csv => "csv",
+            ImportFormat::Txt => "txt",
+        }
+    }
+}
+
+/// Header names of the columns of the imported CSV files, compared ignoring case. The defaults
+/// are the columns of Exportify
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct CsvColumns {
+    /// Column of the song titles in the imported CSV files
+    #[arg(long = "title-column", value_name = "COLUMN", default_value = "Track Name")]
+    pub title: String,
+    /// Column of the artists in the imported CSV files, separated by "," or ";"
+    #[arg(long = "artist-column", value_name = "COLUMN", default_value = "Artist Name(s)")]
+    pub artist: String,
+    /// Column of the album names in the imported CSV files, optional
+    #[arg(long = "album-column", value_name = "COLUMN", default_value = "Album Name")]
+    pub album: String,
+    /// Column of the ISRCs in the imported CSV files, optional
+    #[arg(long = "isrc-column", value_name = "COLUMN", default_value = "ISRC")]
+    pub isrc: String,
+    /// Column of the durations in milliseconds in the imported CSV files, optional
+    #[arg(long = "duration-column", value_name = "COLUMN", default_value = "Duration (ms)")]
+    pub duration: String,
+    /// Column of the playlist names in the imported CSV files, optional. The playlist is named
+    /// after the file without it
+    #[arg(long = "playlist-column", value_name = "COLUMN", default_value = "Playlist name")]
+    pub playlist: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            title: "Track Name".to_string(),
+            artist: "Artist Name(s)".to_string(),
+            album: "Album Name".to_string(),
+            isrc: "ISRC".to_string(),
+            duration: "Duration (ms)".to_string(),
+            playlist: "Playlist name".to_string(),
+        }
+    }
+}
+
+/// Song listed in a file, identified by its artists and title. Its duration is unknown when 0
+fn listed_song(title: &str, artists: &[&str], album: Option<&str>, isrc: Option<&str>, duration_ms: usize) -> Song {
+    Song {
+        source: MusicApiType::File,
+        id: format!("{} - {}", artists.join(", "), title),
+        sid: None,
+        isrc: isrc.map(str::to_uppercase),
+        name: title.to_string(),
+        album: album.map(|name| Album {
+            id: None,
+            name: name.to_string(),
+            year: None,
+        }),
+        artists: artists
+            .iter()
+            .map(|name| Artist {
+                id: None,
+                name: (*name).to_string(),
+            })
+            .collect(),
+        duration_ms,
+        uploaded: false,
+        explicit: None,
+        alt_names: vec![],
+        mbid: None,
+        isrc_hint: None,
+    }
+}
+
+/// Append `song` to the playlist `name`, created when missing. Playlists are identified by
+/// their name, the same playlist is found again by the following imports
+fn push_song(playlists: &mut Vec<Playlist>, name: &str, song: Song) {
+    if let Some(playlist) = playlists.iter_mut().find(|p| p.name == name) {
+        playlist.songs.push(song);
+        return;
+    }
+    playlists.push(Playlist {
+        id: name.to_string(),
+        name: name.to_string(),
+        songs: vec![song],
+        owner: None,
+        owner_name: None,
+        collaborative: false,
+        description: None,
+        public: None,
+        generated: false,
+        cover_url: None,
+        unavailable: None,
+        snapshot_id: None,
+        folder: None,
+    });
+}
+
+/// Records of a CSV file with the line they start at, from 1. The empty lines are skipped, the
+/// line breaks may be LF or CRLF
+fn csv_records(content: &str) -> Result<Vec<(u64, Vec<String>)>> {
+    let bytes = content.trim_start_matches('\u{feff}').as_bytes();
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(bytes);
+    let mut records = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            let line = e.position().map_or(0, |position| csv_line(bytes, position));
+            match e.kind() {
+                csv::ErrorKind::UnequalLengths { expected_len, len, .. } => {
+                    eyre!("line {}: {} fields instead of {}", line, len, expected_len)
+                }
+                _ => eyre!("line {}: {}", line, e),
+            }
+        })?;
+        let line = record.position().map_or(0, |position| csv_line(bytes, position));
+        records.push((line, record.iter().map(str::to_string).collect()));
+    }
+    Ok(records)
+}
+
+/// Line of a record from its position, which is the end of the previous record: the line
+/// breaks up to the record, the LF of a CRLF or blank lines, aren't counted yet
+fn csv_line(bytes: &[u8], position: &csv::Position) -> u64 {
+    let start = usize::try_from(position.byte()).map_or(bytes.len(), |b| b.min(bytes.len()));
+    let breaks = bytes[start..]
+        .iter()
+        .take_while(|b| matches!(b, b'\r' | b'\n'))
+        .filter(|b| **b == b'\n')
+        .count();
+    position.line() + breaks as u64
+}
+
+/// Playlists of a CSV file, `name` is the playlist of the rows without a playlist column
+pub fn parse_csv(content: &str, name: &str, columns: &CsvColumns) -> Result<Vec<Playlist>> {
+    let mut records = csv_records(content)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(vec![]);
+    };
+    let column = |column: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(column.trim()));
+    let required = |column_name: &str, option: &str| {
+        column(column_name).ok_or_else(|| {
+            eyre!(
+                "column \"{}\" not found, set it with --{} (columns: {})",
+                column_name,
+                option,
+                header.join(", ")
+            )
+        })
+    };
+    let title_col = required(&columns.title, "title-column")?;
+    let artist_col = required(&columns.artist, "artist-column")?;
+    let album_col = column(&columns.album);
+    let isrc_col = column(&columns.isrc);
+    let duration_col = column(&columns.duration);
+    let playlist_col = column(&columns.playlist);
+
+    let mut playlists = vec![];
+    for (line, record) in records {
+        let field = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let title = field(Some(title_col)).ok_or_else(|| eyre!("line {}: missing song title", line))?;
+        let artists: Vec<&str> = field(Some(artist_col))
+            .unwrap_or_default()
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+        let duration_ms = match field(duration_col) {
+            Some(duration) => duration
+                .parse()
+                .map_err(|_| eyre!("line {}: invalid duration \"{}\"", line, duration))?,
+            None => 0,
+        };
+        let song = listed_song(title, &artists, field(album_col), field(isrc_col), duration_ms);
+        push_song(&mut playlists, field(playlist_col).unwrap_or(name), song);
+    }
+    Ok(playlists)
+}
+
+/// Playlists of a text file listing "Artist - Title" lines. The songs before the first `[Name]`
+/// section line are in the playlist `name`, the empty lines and the `#` comments are skipped
+pub fn parse_txt(content: &str, name: &str) -> Result<Vec<Playlist>> {
+    let mut playlists = vec![];
+    let mut section = name.to_string();
+    for (i, line) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = header.trim().to_string();
+            continue;
+        }
+        let parsed = [" - ", " – ", " — "]
+            .iter()
+            .find_map(|separator| line.split_once(separator))
+            .map(|(artist, title)| (artist.trim(), title.trim()))
+            .filter(|(artist, title)| !artist.is_empty() && !title.is_empty());
+        let Some((artist, title)) = parsed else {
+            return Err(eyre!("line {}: expected \"Artist - Title\", got \"{}\"", i + 1, line));
+        };
+        push_song(&mut playlists, &section, listed_song(title, &[artist], None, None, 0));
+    }
+    Ok(playlists)
+}
+
+/// Playlists of a CSV or text file, or of the files of a directory with the extension of
+/// `format`. The errors tell the file and the line
+pub fn load(path: &Path, format: ImportFormat, columns: &CsvColumns) -> Result<Vec<Playlist>> {
+    let files = if path.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        files.retain(|f| f.extension().is_some_and(|e| e.eq_ignore_ascii_case(format.extension())));
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut playlists: Vec<Playlist> = vec![];
+    for file in files {
+        let content = std::fs::read_to_string(&file)?;
+        let name = file.file_stem().map_or_else(String::new, |s| s.to_string_lossy().to_string());
+        let parsed = match format {
+            ImportFormat::Csv => parse_csv(&content, &name, columns),
+            ImportFormat::Txt => parse_txt(&content, &name),
+            ImportFormat::Json => return Err(eyre!("JSON exports are loaded with Export::load")),
+        }
+        .map_err(|e| eyre!("{}: {}", file.display(), e))?;
+        for playlist in parsed {
+            for song in playlist.songs {
+                push_song(&mut playlists, &playlist.name, song);
+            }
+        }
+    }
+    Ok(playlists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::test_dir;
+    use crate::music_api::MatchOptions;
+
+    // Exportify export, trimmed
+    const EXPORTIFY: &str = "\"Track URI\",\"Track Name\",\"Album Name\",\"Artist Name(s)\",\"Release Date\",\"Duration (ms)\",\"Popularity\",\"Explicit\",\"Added By\",\"Added At\",\"Genres\",\"Record Label\",\"ISRC\"\r\n\
+        \"spotify:track:0DiWol3AO6WpXZgp0goxAV\",\"One More Time\",\"Discovery\",\"Daft Punk\",\"2001-03-12\",\"320357\",\"78\",\"false\",\"spotify:user:jane\",\"2024-01-02T10:00:00Z\",\"filter house\",\"Parlophone (France)\",\"gbduw0000053\"\r\n\
+        \"spotify:track:2Foc5Q5nqNiosCNqttzHof\",\"Get Lucky (feat. Pharrell Williams and Nile Rodgers)\",\"Random Access Memories\",\"Daft Punk,Pharrell Williams,Nile Rodgers\",\"2013-05-17\",\"369626\",\"80\",\"false\",\"spotify:user:jane\",\"2024-01-02T10:01:00Z\",\"\",\"Columbia\",\"USQX91300108\"\r\n\
+        \"spotify:track:6fxVffaTuwjgEk5h9QyRjy\",\"Say \"\"Yes\"\", Again\",\"\",\"Tyler, The Creator\",\"2019\",\"\",\"50\",\"true\",\"\",\"\",\"\",\"\",\"\"\r\n";
+
+    #[test]
+    fn test_parse_exportify() {
+        let playlists = parse_csv(EXPORTIFY, "Favorites", &CsvColumns::default()).unwrap();
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(playlists[0].name, "Favorites");
+        let songs = &playlists[0].songs;
+        assert_eq!(songs.len(), 3);
+        assert_eq!(songs[0].name, "One More Time");
+        assert_eq!(songs[0].source, MusicApiType::File);
+        assert_eq!(songs[0].id, "Daft Punk - One More Time");
+        assert_eq!(songs[0].isrc.as_deref(), Some("GBDUW0000053"));
+        assert_eq!(songs[0].album.as_ref().unwrap().name, "Discovery");
+        assert_eq!(songs[0].duration_ms, 320_357);
+        let artists: Vec<&str> = songs[1].artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(artists, vec!["Daft Punk", "Pharrell Williams", "Nile Rodgers"]);
+        assert_eq!(songs[2].name, "Say \"Yes\", Again");
+        assert!(songs[2].album.is_none() && songs[2].isrc.is_none());
+        assert_eq!(songs[2].duration_ms, 0);
+
+        // the unknown duration is not compared
+        let mut found = songs[2].with_id(MusicApiType::Tidal, "1");
+        found.duration_ms = 200_000;
+        assert!(songs[2].compare_with(&found, &MatchOptions::default()));
+    }
+
+    #[test]
+    fn test_csv_records() {
+        let content = "plain,\"with, comma\",\"say \"\"hi\"\"\"\r\n\
+            \"two\nlines\",,\"crlf\r\nend\"\r\n\
+            \"\"\"\",\",\",last\r\n";
+        let records = csv_records(content).unwrap();
+        let lines: Vec<u64> = records.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![1, 2, 5]);
+        let lf_records = csv_records(&content.replace("\r\n", "\n")).unwrap();
+        assert_eq!(lf_records.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![1, 2, 5]);
+        let fields: Vec<Vec<&str>> = records.iter().map(|(_, r)| r.iter().map(String::as_str).collect()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                vec!["plain", "with, comma", "say \"hi\""],
+                vec!["two\nlines", "", "crlf\r\nend"],
+                vec!["\"", ",", "last"],
+            ]
+        );
+
+        // byte order mark, blank lines, LF line breaks and no final line break
+        let records = csv_records("\u{feff}a,b\n\n\"\",c").unwrap();
+        assert_eq!(records, vec![(1, vec!["a".to_string(), "b".to_string()]), (3, vec![String::new(), "c".to_string()])]);
+
+        // the rows must have as many fields as the header
+        let err = csv_records("a,b\n\nc,d,e").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: 3 fields instead of 2");
+    }
+
+    #[test]
+    fn test_parse_csv_columns() {
+        // TuneMyMusic export, a playlist per row
+        let tune_my_music = "Track name,Artist name,Album,Playlist name,Type,ISRC\n\
+            Around the World,Daft Punk,Homework,Road trip,Playlist,GBDUW9700012\n\
+            Harder Better Faster Stronger,Daft Punk,Discovery,Workout,Playlist,\n\
+            Veridis Quo,Daft Punk,Discovery,Road trip,Playlist,\n";
+        let err = parse_csv(tune_my_music, "export", &CsvColumns::default()).unwrap_err();
+        assert!(err.to_string().starts_with("column \"Artist Name(s)\" not found"), "{}", err);
+        let columns = CsvColumns {
+            title: "track name".to_string(),
+            artist: "Artist name".to_string(),
+            album: "Album".to_string(),
+            ..CsvColumns::default()
+        };
+        let playlists = parse_csv(tune_my_music, "export", &columns).unwrap();
+        let names: Vec<(&str, usize)> = playlists.iter().map(|p| (p.name.as_str(), p.songs.len())).collect();
+        assert_eq!(names, vec![("Road trip", 2), ("Workout", 1)]);
+        assert_eq!(playlists[0].songs[1].album.as_ref().unwrap().name, "Discovery");
+
+        // the line of the unparseable rows is reported
+        let broken = "Track Name,Artist Name(s),Duration (ms)\nOne,Someone,1000\n\"Two\nlines\",Someone,2000\n,Someone,3000\n";
+        let err = parse_csv(broken, "broken", &CsvColumns::default()).unwrap_err();
+        assert_eq!(err.to_string(), "line 5: missing song title");
+        let err = parse_csv("Track Name,Artist Name(s),Duration (ms)\nOne,Someone,3:20\n", "broken", &CsvColumns::default())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid duration \"3:20\"");
+    }
+
+    #[test]
+    fn test_parse_txt() {
+        let text = "# my old mixtape\n\
+            Daft Punk - One More Time\n\
+            \n\
+            [Road trip]\n\
+            Justice – D.A.N.C.E.\n\
+            Simon & Garfunkel - Mrs. Robinson - Live\n\
+            [Mixtape]\n\
+            Daft Punk - Digital Love\n";
+        let playlists = parse_txt(text, "Mixtape").unwrap();
+        let names: Vec<(&str, usize)> = playlists.iter().map(|p| (p.name.as_str(), p.songs.len())).collect();
+        assert_eq!(names, vec![("Mixtape", 2), ("Road trip", 2)]);
+        let road_trip = &playlists[1].songs;
+        assert_eq!(road_trip[0].artists[0].name, "Justice");
+        assert_eq!(road_trip[0].name, "D.A.N.C.E.");
+        assert_eq!(road_trip[1].artists[0].name, "Simon & Garfunkel");
+        assert_eq!(road_trip[1].name, "Mrs. Robinson - Live");
+        assert_eq!(road_trip[1].duration_ms, 0);
+
+        let err = parse_txt("Daft Punk - One More Time\nJust a title\n", "Mixtape").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected \"Artist - Title\", got \"Just a title\"");
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let dir = test_dir("playlist_files");
+        std::fs::write(dir.join("Chill.txt"), "Air - La femme d'argent\n").unwrap();
+        std::fs::write(dir.join("Mixtape.txt"), "[Chill]\nZero 7 - Destiny\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "not a playlist").unwrap();
+        let playlists = load(&dir, ImportFormat::Txt, &CsvColumns::default()).unwrap();
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(playlists[0].songs.len(), 2);
+
+        std::fs::write(dir.join("Broken.txt"), "\n\nnope\n").unwrap();
+        let err = load(&dir, ImportFormat::Txt, &CsvColumns::default()).unwrap_err();
+        assert!(err.to_string().ends_with("Broken.txt: line 3: expected \"Artist - Title\", got \"nope\""), "{}", err);
+    }
+}
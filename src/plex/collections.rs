@@ -0,0 +1,285 @@
+//! Collections of tracks of the music library, synchronized instead of the Plex playlists with
+//! `--plex-as-collections`. Unlike playlists, collections are not ordered and can't be empty
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use tracing::warn;
+
+use super::PlexApi;
+use super::model::{PlexCollection, PlexCollectionsResponse, PlexPlaylistSongsResponse};
+use crate::music_api::{Playlist, Song, Songs};
+
+/// Metadata type of the collections, for the edits of their fields
+const COLLECTION_TYPE: &str = "18";
+/// Metadata type of the tracks, the type of the items of a collection
+const TRACK_TYPE: &str = "10";
+
+impl From<PlexCollection> for Playlist {
+    fn from(collection: PlexCollection) -> Self {
+        Playlist {
+            id: collection.rating_key,
+            name: collection.title,
+            songs: vec![],
+            owner: None,
+            description: Some(collection.summary).filter(|s| !s.is_empty()),
+            public: None,
+            // smart collections are filled by the server
+            generated: collection.smart == "1",
+            cover_url: None,
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
+        }
+    }
+}
+
+impl PlexApi {
+    /// Metadata uri of `songs`, for the creation of a collection and the addition of items
+    fn items_uri(&self, songs: &[Song]) -> String {
+        let rating_keys: Vec<&str> = songs.iter().map(|song| song.id.as_str()).collect();
+        format!("{}/library/metadata/{}", self.uri_root, rating_keys.join(","))
+    }
+
+    /// Collections of tracks of the music library
+    pub(super) async fn get_collections_info(&mut self) -> Result<Vec<Playlist>> {
+        let library_id = self.library_id().await?;
+        let container: PlexCollectionsResponse = self
+            .get_paged(&format!("{}/library/sections/{}/collections", self.server_url, library_id))
+            .await?;
+        // collections are shared by the users of the library and have no owner
+        Ok(container
+            .collections
+            .into_iter()
+            .filter(|c| c.subtype == "track")
+            .map(Playlist::from)
+            .collect())
+    }
+
+    pub(super) async fn get_collection_songs(&self, id: &str) -> Result<Vec<Song>> {
+        let container: PlexPlaylistSongsResponse = self
+            .get_paged(&format!("{}/library/collections/{}/children?includeGuids=1", self.server_url, id))
+            .await?;
+        let songs: Songs = container.try_into()?;
+        Ok(songs.0)
+    }
+
+    /// Create a collection holding the first `--plex-add-chunk-size` songs, the others are added
+    /// afterwards
+    pub(super) async fn create_collection(&mut self, name: &str, description: &str, songs: &[Song]) -> Result<Playlist> {
+        if songs.is_empty() {
+            return Err(eyre!("Plex collections can't be empty, collection \"{}\" was not created", name));
+        }
+        let library_id = self.library_id().await?;
+        let (seed, rest) = songs.split_at(songs.len().min(self.config.plex_add_chunk_size.max(1)));
+        let response = self.client
+            .post(format!("{}/library/collections", self.server_url))
+            .query(&[
+                ("type", TRACK_TYPE),
+                ("title", name),
+                ("smart", "0"),
+                ("sectionId", library_id.as_str()),
+                ("uri", self.items_uri(seed).as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let container: PlexCollectionsResponse = serde_xml_rs::from_str(&response)?;
+        let mut collection: Playlist = container
+            .collections
+            .into_iter()
+            .find(|c| c.title == name)
+            .ok_or_else(|| eyre!("Plex did not return the created collection \"{}\"", name))?
+            .into();
+        collection.songs = seed.to_vec();
+
+        self.edit_collection(&collection, "summary", description).await?;
+        collection.description = Some(description.to_string());
+        self.add_to_collection(&mut collection, rest).await?;
+        Ok(collection)
+    }
+
+    /// Add `songs` to a collection, `--plex-add-chunk-size` at a time
+    pub(super) async fn add_to_collection(&self, collection: &mut Playlist, songs: &[Song]) -> Result<()> {
+        for chunk in songs.chunks(self.config.plex_add_chunk_size.max(1)) {
+            let response = self.client
+                .put(format!("{}/library/collections/{}/items", self.server_url, collection.id))
+                .query(&[("uri", self.items_uri(chunk).as_str())])
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            collection.songs.extend_from_slice(chunk);
+
+            // the server tells how many items the collection holds, not which were added
+            let container: Option<PlexCollectionsResponse> = serde_xml_rs::from_str(&response).ok();
+            let child_count = container.and_then(|c| c.collections.first().and_then(|c| c.child_count));
+            if let Some(child_count) = child_count
+                && (child_count as usize) < collection.songs.len()
+            {
+                warn!(
+                    "Plex collection \"{}\" holds {} songs, {} were expected",
+                    collection.name,
+                    child_count,
+                    collection.songs.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn remove_from_collection(&self, collection: &mut Playlist, songs: &[Song]) -> Result<()> {
+        // collection items are removed by the rating key of their track
+        for song in songs {
+            self.client
+                .delete(format!("{}/library/collections/{}/items/{}", self.server_url, collection.id, song.id))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        collection.songs.retain(|s| !songs.iter().any(|r| r.id == s.id));
+        Ok(())
+    }
+
+    /// Set `field` of a collection, locked so the metadata agents don't replace it
+    pub(super) async fn edit_collection(&mut self, collection: &Playlist, field: &str, value: &str) -> Result<()> {
+        let library_id = self.library_id().await?;
+        let (value_key, locked_key) = (format!("{}.value", field), format!("{}.locked", field));
+        self.client
+            .put(format!("{}/library/sections/{}/all", self.server_url, library_id))
+            .query(&[
+                ("type", COLLECTION_TYPE),
+                ("id", collection.id.as_str()),
+                (value_key.as_str(), value),
+                (locked_key.as_str(), "1"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub(super) async fn delete_collection(&self, collection: Playlist) -> Result<()> {
+        self.client
+            .delete(format!("{}/library/collections/{}", self.server_url, collection.id))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::ConfigArgs;
+    use crate::mock_api::{serve_json, song};
+    use crate::music_api::{MusicApi, MusicApiType, PlaylistKind};
+
+    fn mocked_api(server_url: String) -> PlexApi {
+        let mut api = super::super::tests::mocked_api(server_url);
+        api.config = ConfigArgs::parse_from([
+            "sync_dis_boi",
+            "--plex-page-size",
+            "2",
+            "--plex-add-chunk-size",
+            "2",
+            "--plex-as-collections",
+        ]);
+        api.library_id = Some("3".to_string());
+        api
+    }
+
+    fn request_lines(requests: &crate::mock_api::Requests) -> Vec<String> {
+        requests.lock().unwrap().iter().map(|r| r.0.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_collections_info() {
+        let xml = r#"<MediaContainer size="3" totalSize="3">
+            <Directory ratingKey="50" key="/library/collections/50/children" type="collection" title="Road trip" subtype="track" summary="Synced" smart="0" childCount="12" />
+            <Directory ratingKey="51" type="collection" title="Best albums" subtype="album" smart="0" childCount="4" />
+            <Directory ratingKey="52" type="collection" title="Top rated" subtype="track" smart="1" />
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, xml)]).await;
+        let mut api = mocked_api(url);
+        assert_eq!(api.playlist_kind(), PlaylistKind::Collection);
+        assert!(!api.supports_empty_playlists());
+
+        let collections = api.get_playlists_info().await.unwrap();
+        assert_eq!(
+            request_lines(&requests),
+            vec!["GET /library/sections/3/collections?X-Plex-Container-Start=0&X-Plex-Container-Size=2"]
+        );
+        // the collections of albums are left out
+        let names: Vec<(&str, bool)> = collections.iter().map(|c| (c.name.as_str(), c.generated)).collect();
+        assert_eq!(names, vec![("Road trip", false), ("Top rated", true)]);
+        assert_eq!(collections[0].description.as_deref(), Some("Synced"));
+        assert!(collections.iter().all(|c| c.owner.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_create_collection() {
+        let created = r#"<MediaContainer size="1">
+            <Directory ratingKey="50" type="collection" title="Road trip" subtype="track" smart="0" childCount="2" />
+        </MediaContainer>"#;
+        let added = created.replace(r#"childCount="2""#, r#"childCount="3""#);
+        let (url, requests) = serve_json(vec![(200, created), (200, ""), (200, &added)]).await;
+        let mut api = mocked_api(url);
+        let songs: Vec<Song> = ["1", "2", "3"].iter().map(|id| song(MusicApiType::Spotify, id, id)).collect();
+        let collection = api
+            .create_playlist_with_songs("Road trip", "Synced", false, &songs)
+            .await
+            .unwrap();
+        assert_eq!(collection.id, "50");
+        assert_eq!(collection.songs, songs);
+        assert_eq!(collection.description.as_deref(), Some("Synced"));
+
+        let lines = request_lines(&requests);
+        assert_eq!(
+            lines[0],
+            "POST /library/collections?type=10&title=Road+trip&smart=0&sectionId=3\
+            &uri=server%3A%2F%2Fabc%2Fcom.plexapp.plugins.library%2Flibrary%2Fmetadata%2F1%2C2"
+        );
+        assert_eq!(
+            lines[1],
+            "PUT /library/sections/3/all?type=18&id=50&summary.value=Synced&summary.locked=1"
+        );
+        assert!(lines[2].starts_with("PUT /library/collections/50/items?uri="), "{}", lines[2]);
+        assert!(lines[2].ends_with("library%2Fmetadata%2F3"), "{}", lines[2]);
+
+        // collections can't be empty
+        let err = api.create_playlist("Empty", "Synced", false).await.unwrap_err();
+        assert!(err.to_string().contains("can't be empty"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_collection_items() {
+        let xml = r#"<MediaContainer size="1" totalSize="1">
+            <Track ratingKey="7" title="Song"><Media id="1"><Part id="1" /></Media></Track>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, xml), (200, ""), (200, "")]).await;
+        let mut api = mocked_api(url);
+        let songs = api.get_playlist_songs("50").await.unwrap();
+        assert_eq!(songs.len(), 1);
+
+        let mut collection = crate::mock_api::playlist("50", "Road trip", songs.clone());
+        api.remove_songs_from_playlist(&mut collection, &songs).await.unwrap();
+        assert!(collection.songs.is_empty());
+        api.delete_playlist(collection).await.unwrap();
+        assert_eq!(
+            request_lines(&requests),
+            vec![
+                "GET /library/collections/50/children?includeGuids=1&X-Plex-Container-Start=0&X-Plex-Container-Size=2",
+                "DELETE /library/collections/50/items/7",
+                "DELETE /library/collections/50",
+            ]
+        );
+    }
+}
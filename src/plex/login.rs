@@ -0,0 +1,265 @@
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{Result, eyre};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const PLEX_TV_URL: &str = "https://plex.tv";
+/// Page where the code of a PIN is entered
+const LINK_URL: &str = "https://plex.tv/link";
+const PRODUCT: &str = "SyncDisBoi";
+
+/// Token obtained with a PIN, saved in the config directory and reused on the next runs
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexSavedToken {
+    pub token: String,
+    /// The token is bound to the client that requested it
+    pub client_identifier: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PlexPin {
+    id: u64,
+    code: String,
+    /// Set once the PIN is claimed
+    auth_token: Option<String>,
+    /// Seconds before the PIN expires
+    expires_in: Option<u64>,
+}
+
+/// Login with a PIN claimed by the user on plex.tv
+pub struct PlexPinLogin {
+    client: reqwest::Client,
+    url: String,
+    client_identifier: String,
+    poll_interval: Duration,
+}
+
+impl PlexPinLogin {
+    /// Used when plex.tv doesn't tell when a PIN expires
+    const DEFAULT_EXPIRES_IN: u64 = 900;
+
+    fn new(url: &str, client_identifier: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            client_identifier,
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// Identifier of a new client, unique enough to tell installations apart
+    pub(super) fn new_client_identifier() -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        format!("syncdisboi-{:x}-{:x}", nanos, std::process::id())
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.url, path))
+            .header("Accept", "application/json")
+            .header("X-Plex-Product", PRODUCT)
+            .header("X-Plex-Client-Identifier", &self.client_identifier)
+    }
+
+    /// Token of the saved file when plex.tv still accepts it, otherwise a new one obtained with
+    /// a PIN and saved in its place
+    pub async fn saved_or_login(path: &Path, clear_cache: bool) -> Result<String> {
+        Self::saved_or_login_with(path, clear_cache, PLEX_TV_URL, Duration::from_secs(2)).await
+    }
+
+    async fn saved_or_login_with(
+        path: &Path,
+        clear_cache: bool,
+        url: &str,
+        poll_interval: Duration,
+    ) -> Result<String> {
+        let saved: Option<PlexSavedToken> = if path.exists() && !clear_cache {
+            Some(serde_json::from_reader(std::fs::File::open(path)?)?)
+        } else {
+            None
+        };
+        if let Some(saved) = &saved {
+            let login = Self::new(url, saved.client_identifier.clone());
+            if login.is_valid(&saved.token).await? {
+                return Ok(saved.token.clone());
+            }
+            warn!("the saved Plex token expired or was revoked, logging in again");
+        }
+
+        // the identifier is kept, plex.tv lists the logins of a client as a single device
+        let client_identifier = saved.map_or_else(Self::new_client_identifier, |s| s.client_identifier);
+        let mut login = Self::new(url, client_identifier);
+        login.poll_interval = poll_interval;
+        let token = login.login().await?;
+        let saved = PlexSavedToken {
+            token: token.clone(),
+            client_identifier: login.client_identifier,
+        };
+        std::fs::write(path, serde_json::to_string(&saved)?)?;
+        info!("Plex token saved to {}", path.display());
+        Ok(token)
+    }
+
+    /// Whether plex.tv still accepts `token`
+    async fn is_valid(&self, token: &str) -> Result<bool> {
+        let res = self
+            .request(reqwest::Method::GET, "/api/v2/user")
+            .header("X-Plex-Token", token)
+            .send()
+            .await?;
+        match res.status() {
+            StatusCode::UNAUTHORIZED => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(eyre!(
+                "Failed to check the Plex token, plex.tv answered HTTP {}: {}",
+                status,
+                res.text().await?.trim()
+            )),
+        }
+    }
+
+    /// Token of a new PIN, once the user claimed it
+    async fn login(&self) -> Result<String> {
+        let res = self
+            .request(reqwest::Method::POST, "/api/v2/pins")
+            .send()
+            .await?;
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(eyre!("Failed to request a Plex PIN, plex.tv answered HTTP {}: {}", status, text.trim()));
+        }
+        let pin: PlexPin = serde_json::from_str(&text)?;
+        info!(
+            "Please go to {} and enter the code {} to log in to Plex",
+            LINK_URL, pin.code
+        );
+
+        let expires_in = pin.expires_in.unwrap_or(Self::DEFAULT_EXPIRES_IN);
+        let deadline = Instant::now() + Duration::from_secs(expires_in);
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            let res = self
+                .request(reqwest::Method::GET, &format!("/api/v2/pins/{}", pin.id))
+                .send()
+                .await?;
+            let status = res.status();
+            let text = res.text().await?;
+            if let Some(token) = Self::handle_pin(status, &text, Instant::now() >= deadline)? {
+                info!("logged in to Plex");
+                return Ok(token);
+            }
+        }
+    }
+
+    /// Token of a polled PIN, `None` while it isn't claimed
+    fn handle_pin(status: StatusCode, text: &str, expired: bool) -> Result<Option<String>> {
+        // plex.tv deletes the expired PINs
+        if status == StatusCode::NOT_FOUND {
+            return Err(eyre!("The Plex PIN expired before it was entered, please try again"));
+        }
+        if !status.is_success() {
+            return Err(eyre!("Failed to poll the Plex PIN, plex.tv answered HTTP {}: {}", status, text.trim()));
+        }
+        let pin: PlexPin = serde_json::from_str(text)?;
+        if let Some(token) = pin.auth_token.filter(|t| !t.is_empty()) {
+            return Ok(Some(token));
+        }
+        if expired {
+            return Err(eyre!("The Plex PIN expired before it was entered, please try again"));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{serve_json, test_dir};
+
+    const PENDING: &str = r#"{"id": 1357, "code": "4VZQ", "product": "SyncDisBoi", "trusted": false, "clientIdentifier": "syncdisboi-1", "expiresIn": 900, "createdAt": "2024-05-01T10:00:00Z", "expiresAt": "2024-05-01T10:15:00Z", "authToken": null}"#;
+    const CLAIMED: &str = r#"{"id": 1357, "code": "4VZQ", "product": "SyncDisBoi", "trusted": false, "clientIdentifier": "syncdisboi-1", "expiresIn": 850, "authToken": "xyz-token"}"#;
+
+    #[test]
+    fn test_handle_pin() {
+        assert_eq!(PlexPinLogin::handle_pin(StatusCode::OK, PENDING, false).unwrap(), None);
+        assert_eq!(
+            PlexPinLogin::handle_pin(StatusCode::OK, CLAIMED, false).unwrap().as_deref(),
+            Some("xyz-token")
+        );
+        // claimed just before it expired
+        assert!(PlexPinLogin::handle_pin(StatusCode::OK, CLAIMED, true).unwrap().is_some());
+
+        let err = PlexPinLogin::handle_pin(StatusCode::OK, PENDING, true).unwrap_err();
+        assert!(err.to_string().contains("expired"), "{}", err);
+        let not_found = r#"{"errors": [{"code": 1020, "message": "Code not found or expired"}]}"#;
+        let err = PlexPinLogin::handle_pin(StatusCode::NOT_FOUND, not_found, false).unwrap_err();
+        assert!(err.to_string().contains("expired"), "{}", err);
+        let err = PlexPinLogin::handle_pin(StatusCode::TOO_MANY_REQUESTS, "", false).unwrap_err();
+        assert!(err.to_string().contains("429"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_pin_login() {
+        let path = test_dir("plex_login").join("plex_token.json");
+
+        // new login, polled until claimed
+        let (url, requests) = serve_json(vec![(201, PENDING), (200, PENDING), (200, CLAIMED)]).await;
+        let token = PlexPinLogin::saved_or_login_with(&path, false, &url, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(token, "xyz-token");
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(
+            lines,
+            vec!["POST /api/v2/pins", "GET /api/v2/pins/1357", "GET /api/v2/pins/1357"]
+        );
+        let saved: PlexSavedToken = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.token, "xyz-token");
+        assert!(saved.client_identifier.starts_with("syncdisboi-"));
+
+        // reused while valid
+        let (url, requests) = serve_json(vec![(200, r#"{"id": 1, "username": "jane"}"#)]).await;
+        let token = PlexPinLogin::saved_or_login_with(&path, false, &url, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(token, "xyz-token");
+        assert_eq!(request_line(&requests, 0), "GET /api/v2/user");
+
+        // expired, logging in again with the same client identifier
+        let claimed = CLAIMED.replace("xyz-token", "new-token");
+        let (url, requests) = serve_json(vec![
+            (401, r#"{"errors": [{"code": 1001, "message": "User could not be authenticated"}]}"#),
+            (201, PENDING),
+            (200, &claimed),
+        ])
+        .await;
+        let token = PlexPinLogin::saved_or_login_with(&path, false, &url, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(token, "new-token");
+        assert_eq!(request_line(&requests, 1), "POST /api/v2/pins");
+        let resaved: PlexSavedToken = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(resaved.token, "new-token");
+        assert_eq!(resaved.client_identifier, saved.client_identifier);
+
+        // the PIN expired
+        let (url, _) = serve_json(vec![(201, PENDING), (404, "")]).await;
+        let err = PlexPinLogin::saved_or_login_with(&path, true, &url, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("expired"), "{}", err);
+    }
+
+    fn request_line(requests: &crate::mock_api::Requests, index: usize) -> String {
+        requests.lock().unwrap()[index].0.clone()
+    }
+}
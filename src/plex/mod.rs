@@ -1,30 +1,51 @@
 use std::collections::HashMap;
+use std::path::Path;
 
 use async_trait::async_trait;
-use color_eyre::eyre::{eyre, Ok};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
-use model::{PlexCreatePlaylistResponse, PlexHubSearchResponse, PlexLibrarySectionsResponse, PlexPlaylist, PlexPlaylistSongsResponse, PlexPlaylistsResponse, PlexSearchTrackResponse, PlexUriResponse, PlexUserResponse, Track};
+use model::{PlexAddItemsResponse, PlexCreatePlaylistResponse, PlexHubSearchResponse, PlexLibrarySectionsResponse, PlexPlaylist, PlexPlaylistSongsResponse, PlexPlaylistsResponse, PlexPrefsResponse, PlexSearchTrackResponse, PlexUriResponse, PlexUserResponse, Track};
 use reqwest::header::HeaderMap;
+use response::PagedContainer;
 use tracing::{info, warn};
-use urlencoding::encode;
 
-use crate::music_api::{MusicApi, MusicApiType, Playlist, Playlists, Song, Songs};
+use crate::music_api::{
+    DynMusicApi, MatchOptions, MusicApi, MusicApiType, Playlist, PlaylistKind, Playlists, Song, Songs, UNKNOWN_COUNTRY, push_candidates,
+};
 use crate::ConfigArgs;
+use crate::cover::CoverImage;
+use crate::utils;
 
+mod collections;
+mod login;
 mod model;
+mod resources;
 mod response;
 
+pub use resources::PlexServerConnection;
+
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct PlexApi {
     client: reqwest::Client,
     server_url: String,
     config: ConfigArgs,
     user_id: String,
     music_library: String,
-    uri_root: String
+    uri_root: String,
+    server_version: String,
+    /// Section id of `music_library`, looked up once
+    library_id: Option<String>,
+    /// Two-letter code from the server preferences, `UNKNOWN` when not set
+    country_code: String,
 }
 
 impl PlexApi {
+    /// Default `--duration-tolerance-ms` of the searches on Plex
+    const DURATION_TOLERANCE_MS: usize = 8000;
+    /// First server version creating playlists without items
+    const EMPTY_PLAYLIST_MIN_VERSION: [u32; 3] = [1, 20, 0];
+
     pub async fn new(server: &str, token: &str, music_library: &String, config: ConfigArgs) -> Result<Self> {
         let mut headers = HeaderMap::new();
         headers.insert("X-Plex-Token", token.parse()?);
@@ -64,27 +85,183 @@ impl PlexApi {
 
         let uri_root = format!("server://{}/com.plexapp.plugins.library", uri_response_parsed.machine_identifier);
 
+        let country_code = match Self::server_country_code(&client, server).await {
+            Ok(Some(country_code)) => country_code,
+            Ok(None) => {
+                info!(
+                    "the Plex server has no country set, set it in the server settings to compare \
+                    it with the other platform"
+                );
+                UNKNOWN_COUNTRY.to_string()
+            }
+            Err(e) => {
+                info!(
+                    "the Plex server settings couldn't be read ({}), only the token of the server \
+                    owner can read its country",
+                    e
+                );
+                UNKNOWN_COUNTRY.to_string()
+            }
+        };
+
         Ok(Self {
             client,
             server_url: server.into(),
             config,
             user_id: logged_in_user.username,
             music_library: music_library.into(),
-            uri_root: uri_root.into()
+            uri_root,
+            server_version: uri_response_parsed.version,
+            library_id: None,
+            country_code,
         })
 
     }
-    
-    async fn get_library_id_by_name(&self) -> Result<String> {
+
+    /// Country set in the server preferences, which only the owner of the server can read
+    async fn server_country_code(client: &reqwest::Client, server: &str) -> Result<Option<String>> {
+        let prefs_response = client
+            .get(format!("{}/:/prefs", server))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let prefs: PlexPrefsResponse = serde_xml_rs::from_str(&prefs_response)?;
+        Ok(Self::prefs_country_code(&prefs))
+    }
+
+    /// Two-letter code of the "country" setting of the server preferences
+    fn prefs_country_code(prefs: &PlexPrefsResponse) -> Option<String> {
+        prefs
+            .settings
+            .iter()
+            .find(|s| s.id.eq_ignore_ascii_case("country"))
+            .map(|s| s.value.trim())
+            .filter(|c| c.len() == 2 && c.chars().all(|c| c.is_ascii_alphabetic()))
+            .map(str::to_uppercase)
+    }
+
+    /// Container of all the pages of `url`, requested `--plex-page-size` items at a time
+    /// until its `totalSize` is reached. Large containers are truncated by the server otherwise
+    async fn get_paged<T: PagedContainer>(&self, url: &str) -> Result<T> {
+        let page_size = self.config.plex_page_size.max(1);
+        let mut container: T = self.get_page(url, 0, page_size).await?;
+        let mut start = container.page_len();
+        // the containers without a total size aren't paginated
+        while let Some(total_size) = container.total_size()
+            && start < total_size as usize
+        {
+            let page: T = self.get_page(url, start, page_size).await?;
+            if page.page_len() == 0 {
+                warn!("Plex returned {} items of {} for {}, stopping there", start, total_size, url);
+                break;
+            }
+            start += page.page_len();
+            container.append(page);
+        }
+        Ok(container)
+    }
+
+    async fn get_page<T: PagedContainer>(&self, url: &str, start: usize, size: usize) -> Result<T> {
+        let response = self.client
+            .get(url)
+            .query(&[("X-Plex-Container-Start", start), ("X-Plex-Container-Size", size)])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(serde_xml_rs::from_str(&response)?)
+    }
+
+    /// Connect with the token saved in `token_path` by a previous PIN login, or log in with a
+    /// new PIN when there is none or it expired
+    pub async fn new_with_pin(
+        server: &str,
+        music_library: &String,
+        token_path: &Path,
+        clear_cache: bool,
+        config: ConfigArgs,
+    ) -> Result<Self> {
+        let token = Self::pin_token(token_path, clear_cache).await?;
+        Self::new(server, &token, music_library, config).await
+    }
+
+    /// Token saved in `token_path` by a previous PIN login, or of a new PIN login
+    pub async fn pin_token(token_path: &Path, clear_cache: bool) -> Result<String> {
+        login::PlexPinLogin::saved_or_login(token_path, clear_cache).await
+    }
+
+    /// Reachable connection of the server named `name` of the account, found on plex.tv
+    pub async fn resolve_server(token: &str, name: &str, cache_path: Option<&Path>) -> Result<PlexServerConnection> {
+        resources::resolve_server(token, name, cache_path).await
+    }
+
+    /// Log in with a new PIN and save the token in `token_path`, for `new_with_pin`
+    pub async fn login_with_pin(token_path: &Path) -> Result<String> {
+        login::PlexPinLogin::saved_or_login(token_path, true).await
+    }
+
+    /// Whether the server creates empty playlists, older versions require a first item
+    fn supports_empty_playlists(version: &str) -> bool {
+        let numbers: Vec<u32> = version
+            .split(['.', '-'])
+            .take(3)
+            .map_while(|n| n.parse().ok())
+            .collect();
+        numbers.as_slice() >= Self::EMPTY_PLAYLIST_MIN_VERSION.as_slice()
+    }
+
+    /// Playlist titled `name` in the response to its creation
+    fn created_playlist(container: PlexCreatePlaylistResponse, name: &str) -> Result<Playlist> {
+        let playlists: Playlists = container.try_into()?;
+        playlists
+            .0
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| eyre!("Plex did not return the created playlist \"{}\"", name))
+    }
+
+    async fn post_playlist(&self, name: &str, uri: &str) -> Result<Playlist> {
         let response = self.client
-            .get(format!("{}/library/sections", self.server_url))
+            .post(format!("{}/playlists", self.server_url))
+            .query(&[
+                ("uri", uri),
+                ("type", "audio"),
+                ("title", name),
+                ("smart", "0"),
+            ])
             .send()
             .await?
             .error_for_status()?
             .text()
             .await?;
+        let container: PlexCreatePlaylistResponse = serde_xml_rs::from_str(&response)?;
+        Self::created_playlist(container, name)
+    }
+
+    /// Create a playlist seeded with the first track of the library, removed afterwards, for
+    /// the servers that can't create empty playlists
+    async fn create_seeded_playlist(&mut self, name: &str) -> Result<Playlist> {
+        let first_track = self.get_first_library_track().await?;
+        let uri = format!("{}/library/metadata/{}", self.uri_root, first_track.id);
+        let playlist = self.post_playlist(name, &uri).await?;
+
+        for track in self.get_playlist_tracks(&playlist).await? {
+            self.client
+                .delete(format!("{}/playlists/{}/items/{}", self.server_url, playlist.id, track.playlist_item_id))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(playlist)
+    }
 
-        let parsed_res: PlexLibrarySectionsResponse = serde_xml_rs::from_str(&response)?;
+    async fn get_library_id_by_name(&self) -> Result<String> {
+        let parsed_res: PlexLibrarySectionsResponse = self
+            .get_paged(&format!("{}/library/sections", self.server_url))
+            .await?;
 
         
         if let Some(directories) = parsed_res.directories {
@@ -103,9 +280,18 @@ impl PlexApi {
         Err(eyre!("No library found for name: {}", self.music_library))
     }
 
+    async fn library_id(&mut self) -> Result<String> {
+        if let Some(id) = &self.library_id {
+            return Ok(id.clone());
+        }
+        let id = self.get_library_id_by_name().await?;
+        self.library_id = Some(id.clone());
+        Ok(id)
+    }
+
 
-    async fn get_first_library_track(&self) -> Result<Song> {
-        let library_id = self.get_library_id_by_name().await?;
+    async fn get_first_library_track(&mut self) -> Result<Song> {
+        let library_id = self.library_id().await?;
 
         let response = self.client
             .get(format!("{}/library/sections/{}/all?type=10&limit=10", self.server_url, library_id))
@@ -129,17 +315,10 @@ impl PlexApi {
 
     async fn get_playlist_tracks(&self, playlist: &Playlist) -> Result<Vec<Track>> {
         // get all songs in a playlist
-        let response = self.client
-            .get(format!("{}/playlists/{}/items", self.server_url, playlist.id))
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
+        let container: PlexPlaylistSongsResponse = self
+            .get_paged(&format!("{}/playlists/{}/items", self.server_url, playlist.id))
             .await?;
 
-        // 2) Deserialize XML into your PlexPlaylistSongsResponse struct
-        let container: PlexPlaylistSongsResponse = serde_xml_rs::from_str(&response)?;
-
         if let Some(songs) = container.tracks {
             return Ok(songs);
         }
@@ -147,54 +326,109 @@ impl PlexApi {
         Ok(vec![])
     }
 
-    async fn encode_query(&self, query: &str) -> Result<String> {
-        let encoded = encode(&query);
-
-        // Remove invalid trailing characters from encoded query
-        // This causes a crash in Plex search
-        // Manually encode periods to %2E since urlencoding doesn't encode them
-        let encoded = encoded
-            .trim_end_matches("%2F")
-            .trim_end_matches("%3F")
-            .to_string()
-            .replace("%20%2A", "%20")
-            .replace("%29", "")
-            .replace(".", "%2E");
-
-        // Replace 3 or more consecutive encoded periods with just 2
-        // Plex crashes on three or more consecutive encoded periods
-        let encoded = regex::Regex::new(r"(%2E){3,}")
-            .unwrap()
-            .replace_all(&encoded, "%2E%2E")
-            .to_string();
-
-        Ok(encoded)
-    }
-
-    #[allow(dead_code)]
-    async fn search_song_strict(&self, query: &str) -> Result<Vec<Song>> {
-        let encoded_query = self.encode_query(query).await?;
+    /// Add `songs` to a playlist in a single request, returning the number of items the server
+    /// added, `None` when it doesn't tell
+    async fn put_playlist_items(&self, playlist_id: &str, songs: &[Song]) -> Result<Option<u32>> {
+        let rating_keys: Vec<&str> = songs.iter().map(|song| song.id.as_str()).collect();
+        let uri = format!("{}/library/metadata/{}", self.uri_root, rating_keys.join(","));
         let response = self.client
-            .get(format!("{}/search?type=10&query={}",
-                self.server_url, encoded_query))
+            .put(format!("{}/playlists/{}/items", self.server_url, playlist_id))
+            .query(&[("uri", uri.as_str())])
             .send()
             .await?
             .error_for_status()?
             .text()
             .await?;
+        if response.trim().is_empty() {
+            return Ok(None);
+        }
+        let parsed: PlexAddItemsResponse = serde_xml_rs::from_str(&response)?;
+        Ok(parsed.leaf_count_added)
+    }
 
-        let parsed_res: PlexSearchTrackResponse = serde_xml_rs::from_str(&response)?;
+    /// Songs that are not in the playlist on the server
+    async fn missing_items(&self, playlist: &Playlist, songs: &[Song]) -> Result<Vec<Song>> {
+        let tracks = self.get_playlist_tracks(playlist).await?;
+        Ok(songs
+            .iter()
+            .filter(|s| !tracks.iter().any(|t| t.rating_key == s.id))
+            .cloned()
+            .collect())
+    }
 
-        let res_songs: Songs = parsed_res.try_into()?;
+    /// Playlist item ids of the tracks matching the songs to remove, by rating key
+    fn playlist_items_to_remove(tracks: &[Track], songs: &[Song]) -> Vec<u32> {
+        tracks
+            .iter()
+            .filter(|t| songs.iter().any(|s| s.id == t.rating_key))
+            .map(|t| t.playlist_item_id)
+            .collect()
+    }
+
+    /// Search query without what makes the Plex search fail, the query itself is URL-encoded by
+    /// reqwest. Plex chokes on:
+    /// - a trailing "/" or "?", the search fails
+    /// - three or more consecutive periods, the search crashes while two are fine
+    /// - "*", taken as a wildcard
+    /// - a ")" closing no parenthesis, balanced parentheses as in "(Don't Fear) The Reaper" are
+    ///   searched as is
+    fn sanitize_query(query: &str) -> String {
+        let mut depth = 0;
+        let balanced: String = query
+            .chars()
+            .filter(|c| match c {
+                '(' => {
+                    depth += 1;
+                    true
+                }
+                ')' if depth == 0 => false,
+                ')' => {
+                    depth -= 1;
+                    true
+                }
+                _ => true,
+            })
+            .map(|c| if c == '*' { ' ' } else { c })
+            .collect();
+        let periods = regex::Regex::new(r"\.{3,}").unwrap().replace_all(&balanced, "..");
+        let words: Vec<&str> = periods.split_whitespace().collect();
+        words.join(" ").trim_end_matches(['/', '?', ' ']).to_string()
+    }
+
+    /// Songs found by a search in the music library, or in all the libraries when it fails
+    async fn search_songs(&mut self, query: &str) -> Result<Vec<Song>> {
+        match self.search_song_library(query).await {
+            Ok(songs) => Ok(songs),
+            Err(e) => {
+                warn!("failed to search \"{}\" in the Plex library, searching all the libraries: {}", query, e);
+                self.search_song_hub(query).await
+            }
+        }
+    }
 
+    /// Search limited to the music library, other libraries can hold copies of the same songs
+    async fn search_song_library(&mut self, query: &str) -> Result<Vec<Song>> {
+        let library_id = self.library_id().await?;
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/library/sections/{}/search", self.server_url, library_id),
+            &[("type", "10"), ("query", &Self::sanitize_query(query))],
+        )?;
+        // only the first page, the best results come first
+        let parsed_res: PlexSearchTrackResponse =
+            self.get_page(url.as_str(), 0, self.config.plex_page_size.max(1)).await?;
+        let res_songs: Songs = parsed_res.try_into()?;
         Ok(res_songs.0)
     }
 
     async fn search_song_hub(&self, query: &str) -> Result<Vec<Song>> {
-        let encoded_query = self.encode_query(query).await?;
+        // the hubs aren't paginated, their number of results is limited instead
         let response = self.client
-            .get(format!("{}/library/search?searchTypes=music&query={}",
-                self.server_url, encoded_query))
+            .get(format!("{}/library/search", self.server_url))
+            .query(&[
+                ("searchTypes", "music"),
+                ("limit", &self.config.plex_page_size.to_string()),
+                ("query", &Self::sanitize_query(query)),
+            ])
             .send()
             .await?
             .error_for_status()?
@@ -224,69 +458,145 @@ impl MusicApi for PlexApi {
     }
 
     fn country_code(&self) -> &str {
-        // TODO: it seems that plex has a country code, just need to convert it properly
-        "UNKNOWN"
+        &self.country_code
     }
 
-    async fn create_playlist(&mut self, name: &str, _public: bool) -> Result<Playlist> {
-        // Get first track from library
-        let first_track = self.get_first_library_track().await?;
+    fn default_match_options(&self) -> MatchOptions {
+        // the durations of the local files differ from the streaming metadata by a few seconds,
+        // and libraries hold both the album and the single edits of songs
+        MatchOptions {
+            duration_tolerance_ms: Self::DURATION_TOLERANCE_MS,
+            prefer_album: true,
+            ..MatchOptions::default()
+        }
+    }
 
-        // Construct the URI
-        let uri = format!("{}/library/metadata/{}", self.uri_root, first_track.id);
+    fn clone_handle(&self) -> Option<DynMusicApi> {
+        Some(Box::new(self.clone()))
+    }
 
-        // Create a new playlist on Plex
-        let response = self.client
-            .post(format!("{}/playlists", self.server_url))
-            .query(&[
-                ("uri", uri.as_str()),
-                ("type", "audio"),
-                ("title", name),
-                ("smart", "0"),
-            ])
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
+    fn sanitize_playlist_name(&self, name: &str) -> String {
+        utils::sanitize_playlist_name(name)
+    }
 
-        // Deserialize XML into your PlexPlaylist struct
-        let container: PlexCreatePlaylistResponse = serde_xml_rs::from_str(&response)?;
+    fn playlist_kind(&self) -> PlaylistKind {
+        if self.config.plex_as_collections {
+            PlaylistKind::Collection
+        } else {
+            PlaylistKind::Playlist
+        }
+    }
 
-        // Convert to Playlist
-        let playlists: Playlists = container.try_into()?;
+    fn supports_empty_playlists(&self) -> bool {
+        !self.config.plex_as_collections
+    }
+
+    fn lists_own_playlists_only(&self) -> bool {
+        true
+    }
 
-        // Get the playlist tracks
-        let tracks = self.get_playlist_tracks(&playlists.0[0]).await?;
-
-        if !tracks.is_empty() {
-            for track in tracks {
-                let playlist_item_id = track.playlist_item_id.to_string();
-            
-                self.client
-                    .delete(format!("{}/playlists/{}/items/{}", self.server_url, playlists.0[0].id, playlist_item_id))
-                    .send()
-                    .await?
-                    .error_for_status()?;
+    async fn create_playlist(&mut self, name: &str, description: &str, _public: bool) -> Result<Playlist> {
+        if self.config.plex_as_collections {
+            return self.create_collection(name, description, &[]).await;
+        }
+        let mut playlist = if Self::supports_empty_playlists(&self.server_version) {
+            let uri = format!("{}/library/metadata/", self.uri_root);
+            match self.post_playlist(name, &uri).await {
+                Ok(playlist) => playlist,
+                Err(e) => {
+                    warn!("failed to create empty Plex playlist \"{}\", seeding it: {}", name, e);
+                    self.create_seeded_playlist(name).await?
+                }
             }
+        } else {
+            self.create_seeded_playlist(name).await?
+        };
+        self.update_playlist_details(&mut playlist, Some(description), None).await?;
+        Ok(playlist)
+    }
+
+    async fn create_playlist_with_songs(
+        &mut self,
+        name: &str,
+        description: &str,
+        public: bool,
+        songs: &[Song],
+    ) -> Result<Playlist> {
+        // collections are created with their first songs
+        if self.config.plex_as_collections {
+            return self.create_collection(name, description, songs).await;
         }
-        
-        Ok(playlists.0[0].clone())
+        let mut playlist = self.create_playlist(name, description, public).await?;
+        if !songs.is_empty() {
+            self.add_songs_to_playlist(&mut playlist, songs).await?;
+        }
+        Ok(playlist)
+    }
+
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        _public: Option<bool>,
+    ) -> Result<()> {
+        let Some(description) = description else {
+            return Ok(());
+        };
+        if self.config.plex_as_collections {
+            self.edit_collection(playlist, "summary", description).await?;
+            playlist.description = Some(description.to_string());
+            return Ok(());
+        }
+        self.client
+            .put(format!("{}/playlists/{}", self.server_url, playlist.id))
+            .query(&[("summary", description)])
+            .send()
+            .await?
+            .error_for_status()?;
+        playlist.description = Some(description.to_string());
+        Ok(())
     }
     
-    async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
-        // get all playlist names and ids
-        let response = self.client
-            .get(format!("{}/playlists", self.server_url))
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()> {
+        if self.config.plex_as_collections {
+            self.edit_collection(playlist, "title", name).await?;
+            playlist.name = name.to_string();
+            return Ok(());
+        }
+        self.client
+            .put(format!("{}/playlists/{}", self.server_url, playlist.id))
+            .query(&[("title", name)])
             .send()
             .await?
-            .error_for_status()?
-            .text()
-            .await?;
+            .error_for_status()?;
+        playlist.name = name.to_string();
+        Ok(())
+    }
 
+    fn supports_playlist_cover(&self) -> bool {
+        true
+    }
+
+    async fn set_playlist_cover(&mut self, playlist: &Playlist, cover: &CoverImage) -> Result<()> {
+        // the uploaded poster is selected as the playlist poster
+        self.client
+            .post(format!("{}/library/metadata/{}/posters", self.server_url, playlist.id))
+            .header("content-type", cover.mime_type())
+            .body(cover.bytes.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 
-        // 2) Deserialize XML into your PlexPlaylistsResponse struct
-        let container: PlexPlaylistsResponse = serde_xml_rs::from_str(&response)?;
+    async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
+        if self.config.plex_as_collections {
+            return self.get_collections_info().await;
+        }
+        // get all playlist names and ids, the server leaves out the video and photo playlists
+        let container: PlexPlaylistsResponse = self
+            .get_paged(&format!("{}/playlists?playlistType=audio", self.server_url))
+            .await?;
         let playlists: Vec<PlexPlaylist> = container.playlists.clone();
 
         /* filter down to audio playlists */
@@ -299,17 +609,16 @@ impl MusicApi for PlexApi {
         // Construct a new PlexPlaylistsResponse with only the filtered playlists
         let audio_container = PlexPlaylistsResponse {
             size: Some(filtered.len() as u32),
+            total_size: None,
             playlists: filtered,
         };
     
         // Convert to Playlists
         let mid_playlists: Playlists = audio_container.try_into()?;
 
-        // Add ourselves as owner of every playlist
-        let res_playlists: Vec<Playlist> = mid_playlists.0.into_iter().map(|mut p| {
-            p.owner = Some(self.user_id.clone());
-            p
-        }).collect();
+        // Plex returns no owner, the smart playlists are marked as generated and skipped as
+        // destination
+        let res_playlists: Vec<Playlist> = mid_playlists.0;
 
         // Deduplicate playlists by ID to handle Plex API returning duplicates
         let mut seen_ids = HashMap::new();
@@ -333,78 +642,678 @@ impl MusicApi for PlexApi {
     }
 
     async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>> {
+        if self.config.plex_as_collections {
+            return self.get_collection_songs(id).await;
+        }
         // get all songs in a playlist
-        let response = self.client
-            .get(format!("{}/playlists/{}/items", self.server_url, id))
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
+        let container: PlexPlaylistSongsResponse = self
+            // the guids hold the MusicBrainz id of the tracks
+            .get_paged(&format!("{}/playlists/{}/items?includeGuids=1", self.server_url, id))
             .await?;
 
-        // 2) Deserialize XML into your PlexPlaylistSongsResponse struct
-        let container: PlexPlaylistSongsResponse = serde_xml_rs::from_str(&response)?;
-
         // Convert to Songs
         let res_songs: Songs = container.try_into()?;
         Ok(res_songs.0)
     }
 
     async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
-        // add songs to a playlist in batches of 5
-        for chunk in songs.chunks(5) {
-            let rating_keys: Vec<String> = chunk.iter()
-                .map(|song| song.id.clone())
-                .collect();
-            let rating_keys_str = rating_keys.join(",");
-            let uri = format!("{}/library/metadata/{}", self.uri_root, rating_keys_str);
+        if self.config.plex_as_collections {
+            return self.add_to_collection(playlist, songs).await;
+        }
+        // songs are added in source order, --plex-add-chunk-size at a time
+        let mut not_added = 0;
+        for chunk in songs.chunks(self.config.plex_add_chunk_size.max(1)) {
+            let added = self.put_playlist_items(&playlist.id, chunk).await?;
+            if added.is_none_or(|added| added as usize >= chunk.len()) {
+                playlist.songs.extend_from_slice(chunk);
+                continue;
+            }
 
-            self.client
-                .put(format!("{}/playlists/{}/items", self.server_url, playlist.id))
-                .query(&[
-                    ("uri", uri.as_str())
-                ])
-                .send()
-                .await?
-                .error_for_status()?;
+            // the songs missing from the playlist are added again, once
+            let missing = self.missing_items(playlist, chunk).await?;
+            warn!(
+                "only {} of {} songs were added to Plex playlist \"{}\", adding the {} others again",
+                added.unwrap_or_default(),
+                chunk.len(),
+                playlist.name,
+                missing.len()
+            );
+            let retried = self.put_playlist_items(&playlist.id, &missing).await?;
+            let missing = if retried.is_none_or(|added| added as usize >= missing.len()) {
+                vec![]
+            } else {
+                self.missing_items(playlist, &missing).await?
+            };
+            for song in &missing {
+                warn!("failed to add song {} ({}) to Plex playlist \"{}\"", song.id, song, playlist.name);
+            }
+            not_added += missing.len();
+            playlist
+                .songs
+                .extend(chunk.iter().filter(|s| !missing.contains(s)).cloned());
         }
 
+        if !songs.is_empty() && not_added == songs.len() {
+            return Err(eyre!("failed to add any of the {} songs to Plex playlist \"{}\"", songs.len(), playlist.name));
+        }
         Ok(())
     }
     async fn remove_songs_from_playlist(
         &mut self,
-        _playlist: &mut Playlist,
-        _songs_ids: &[Song],
+        playlist: &mut Playlist,
+        songs_ids: &[Song],
     ) -> Result<()> {
-        todo!()
+        if self.config.plex_as_collections {
+            return self.remove_from_collection(playlist, songs_ids).await;
+        }
+        // Playlist item ids are needed for removal, fetch them fresh
+        let tracks = self.get_playlist_tracks(playlist).await?;
+
+        for playlist_item_id in Self::playlist_items_to_remove(&tracks, songs_ids) {
+            self.client
+                .delete(format!("{}/playlists/{}/items/{}", self.server_url, playlist.id, playlist_item_id))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        playlist
+            .songs
+            .retain(|s| !songs_ids.iter().any(|r| r.id == s.id));
+        Ok(())
     }
-    async fn delete_playlist(&mut self, _playlist: Playlist) -> Result<()> {
-        todo!()
+    async fn delete_playlist(&mut self, playlist: Playlist) -> Result<()> {
+        if self.config.plex_as_collections {
+            return self.delete_collection(playlist).await;
+        }
+        self.client
+            .delete(format!("{}/playlists/{}", self.server_url, playlist.id))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
     }
 
     async fn search_song(&mut self, song: &Song) -> Result<Option<Song>> {
         let mut queries = song.build_queries();
 
         while let Some(query) = queries.pop() {
-            // let res_songs = self.search_song_strict(&query).await?; // Second option, this gets less results
-            let res_songs = self.search_song_hub(&query).await?;
-            
-            for res_song in res_songs.into_iter() {
-                if song.compare(&res_song) {
-                    return Ok(Some(res_song));
-                }
+            let res_songs = self.search_songs(&query).await?;
+            if let Some(res_song) = song.best_match(res_songs, &self.config.match_options_over(&self.default_match_options())) {
+                return Ok(Some(res_song));
             }
         }
 
         Ok(None)
     }
 
-    async fn add_likes(&mut self, _songs: &[Song]) -> Result<()> {
-        Ok(())
-        // todo!()
+    async fn search_candidates(&mut self, song: &Song, limit: usize) -> Result<Vec<Song>> {
+        let mut candidates = vec![];
+        let mut queries = song.build_queries();
+        while candidates.len() < limit
+            && let Some(query) = queries.pop()
+        {
+            let res_songs = self.search_songs(&query).await?;
+            push_candidates(&mut candidates, res_songs, limit);
+        }
+        Ok(candidates)
+    }
+
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
+        // plex has no likes, the songs are rated 5 stars instead
+        let mut not_added = vec![];
+        let mut last_error = None;
+        for song in songs {
+            let res = self.client
+                .put(format!("{}/:/rate", self.server_url))
+                .query(&[
+                    ("identifier", "com.plexapp.plugins.library"),
+                    ("key", song.id.as_str()),
+                    ("rating", "10"),
+                ])
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            if let Err(e) = res {
+                warn!("failed to rate Plex track {} ({}): {}", song.id, song, e);
+                not_added.push(song.clone());
+                last_error = Some(e);
+            }
+        }
+        if not_added.len() == songs.len()
+            && let Some(e) = last_error
+        {
+            return Err(e.into());
+        }
+        Ok(not_added)
     }
+
     async fn get_likes(&mut self) -> Result<Vec<Song>> {
-        Ok(vec![])
-        //todo!()
+        let library_id = self.library_id().await?;
+        let threshold = self.config.plex_like_rating;
+        let mut container: PlexPlaylistSongsResponse = self
+            .get_paged(&format!(
+                "{}/library/sections/{}/all?type=10&includeGuids=1&userRating>={}",
+                self.server_url, library_id, threshold
+            ))
+            .await?;
+        // the filter is applied again, in case the server ignored it
+        if let Some(tracks) = &mut container.tracks {
+            tracks.retain(|t| t.user_rating.is_some_and(|r| r >= threshold));
+        }
+        let res_songs: Songs = container.try_into()?;
+        Ok(res_songs.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::mock_api::{serve_json, song};
+
+    pub(super) fn mocked_api(server_url: String) -> PlexApi {
+        PlexApi {
+            client: reqwest::Client::new(),
+            server_url,
+            config: ConfigArgs::parse_from(["sync_dis_boi", "--plex-page-size", "2"]),
+            user_id: "jane".to_string(),
+            music_library: "Music".to_string(),
+            uri_root: "server://abc/com.plexapp.plugins.library".to_string(),
+            server_version: "1.40.2.8395-c67dce28e".to_string(),
+            library_id: None,
+            country_code: "US".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paged_playlist_songs() {
+        let page_1 = r#"<MediaContainer size="2" totalSize="3" title="Road trip" ratingKey="100">
+            <Track ratingKey="1" playlistItemID="11" title="a"><Media id="1"><Part id="1" /></Media></Track>
+            <Track ratingKey="2" playlistItemID="12" title="b"><Media id="2"><Part id="2" /></Media></Track>
+        </MediaContainer>"#;
+        let page_2 = r#"<MediaContainer size="1" totalSize="3" title="Road trip" ratingKey="100">
+            <Track ratingKey="3" playlistItemID="13" title="c"><Media id="3"><Part id="3" /></Media></Track>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, page_1), (200, page_2)]).await;
+        let mut api = mocked_api(url);
+        let songs = api.get_playlist_songs("100").await.unwrap();
+        let ids: Vec<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "GET /playlists/100/items?includeGuids=1&X-Plex-Container-Start=0&X-Plex-Container-Size=2",
+                "GET /playlists/100/items?includeGuids=1&X-Plex-Container-Start=2&X-Plex-Container-Size=2",
+            ]
+        );
+
+        // not paginated
+        let sections = r#"<MediaContainer size="1" title1="Plex Library">
+            <Directory key="4" type="artist" title="Music" />
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, sections)]).await;
+        let api = mocked_api(url);
+        assert_eq!(api.get_library_id_by_name().await.unwrap(), "4");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // the server stops returning items before the total size
+        let (url, requests) = serve_json(vec![(200, page_1), (200, r#"<MediaContainer size="0" totalSize="3" />"#)]).await;
+        let mut api = mocked_api(url);
+        assert_eq!(api.get_playlist_songs("100").await.unwrap().len(), 2);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_in_library() {
+        let sections = r#"<MediaContainer size="2">
+            <Directory key="3" type="artist" title="Music (lossy)" />
+            <Directory key="4" type="artist" title="Music" />
+        </MediaContainer>"#;
+        let results = r#"<MediaContainer size="1" librarySectionID="4" librarySectionTitle="Music">
+            <Track ratingKey="1" title="Africa" grandparentTitle="TOTO" parentTitle="Toto IV" duration="295000">
+                <Media id="1"><Part id="1" /></Media>
+            </Track>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, sections), (200, results), (200, results)]).await;
+        let mut api = mocked_api(url);
+        let songs = api.search_songs("Africa TOTO").await.unwrap();
+        assert_eq!(songs[0].name, "Africa");
+        assert_eq!(songs[0].album.as_ref().unwrap().name, "Toto IV");
+        // the library id is looked up once
+        api.search_songs("Africa").await.unwrap();
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("GET /library/sections?"), "{}", lines[0]);
+        assert_eq!(
+            lines[1],
+            "GET /library/sections/4/search?type=10&query=Africa+TOTO&X-Plex-Container-Start=0&X-Plex-Container-Size=2"
+        );
+
+        // falls back to the hub search
+        let hubs = r#"<MediaContainer size="1">
+            <SearchResult score="0.9">
+                <Track ratingKey="1" title="Africa" grandparentTitle="TOTO"><Media id="1"><Part id="1" /></Media></Track>
+            </SearchResult>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, sections), (500, ""), (200, hubs)]).await;
+        let mut api = mocked_api(url);
+        let songs = api.search_songs("Africa").await.unwrap();
+        assert_eq!(songs[0].id, "1");
+        let line = requests.lock().unwrap()[2].0.clone();
+        assert!(line.starts_with("GET /library/search?searchTypes=music&"), "{}", line);
+    }
+
+    #[test]
+    fn test_sanitize_query() {
+        let cases = [
+            ("AC/DC", "AC/DC"),
+            ("What's Up?", "What's Up"),
+            ("(Don't Fear) The Reaper", "(Don't Fear) The Reaper"),
+            ("Whatever Happened to My Rock 'n' Roll (Punk Song)", "Whatever Happened to My Rock 'n' Roll (Punk Song)"),
+            ("Love Song) Remix", "Love Song Remix"),
+            ("...Baby One More Time", "..Baby One More Time"),
+            ("Why?/", "Why"),
+            ("P*nk Rock", "P nk Rock"),
+            ("Blink-182 #1; 50% Off & More", "Blink-182 #1; 50% Off & More"),
+            ("Sigur Rós ágætis byrjun", "Sigur Rós ágætis byrjun"),
+        ];
+        for (query, sanitized) in cases {
+            assert_eq!(PlexApi::sanitize_query(query), sanitized, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_nasty_titles() {
+        let sections = r#"<MediaContainer size="1"><Directory key="4" type="artist" title="Music" /></MediaContainer>"#;
+        let track = |id: &str, title: &str, artist: &str| {
+            format!(
+                r#"<MediaContainer size="1"><Track ratingKey="{}" title="{}" grandparentTitle="{}" duration="240000"><Media id="1"><Part id="1" /></Media></Track></MediaContainer>"#,
+                id, title, artist
+            )
+        };
+        let results = [
+            track("1", "Back in Black", "AC/DC"),
+            track("2", "What&apos;s Up?", "4 Non Blondes"),
+            track("3", "(Don&apos;t Fear) The Reaper", "Blue Öyster Cult"),
+        ];
+        let (url, requests) = serve_json(vec![
+            (200, sections),
+            (200, &results[0]),
+            (200, &results[1]),
+            (200, &results[2]),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+
+        let searched = |name: &str, artist: &str| {
+            let mut song = song(MusicApiType::Spotify, "s", name);
+            song.artists = vec![crate::music_api::Artist { id: None, name: artist.to_string() }];
+            song.duration_ms = 240000;
+            song
+        };
+        for (song, id) in [
+            (searched("Back in Black", "AC/DC"), "1"),
+            (searched("What's Up?", "4 Non Blondes"), "2"),
+            (searched("(Don't Fear) The Reaper", "Blue Öyster Cult"), "3"),
+        ] {
+            let found = api.search_song(&song).await.unwrap();
+            assert_eq!(found.map(|s| s.id).as_deref(), Some(id), "{}", song.name);
+        }
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert!(lines[1].contains("query=back+in+black+ac%2Fdc&"), "{}", lines[1]);
+        // only a trailing "?" is stripped
+        assert!(lines[2].contains("query=whats+up%3F+4+non+blondes&"), "{}", lines[2]);
+        assert!(lines[3].contains("query=the+reaper+blue+%C3%B6yster+cult&"), "{}", lines[3]);
+    }
+
+    #[test]
+    fn test_match_options() {
+        let xml = r#"<MediaContainer size="3">
+            <Track ratingKey="1" title="Africa" grandparentTitle="TOTO" parentTitle="Toto IV" duration="301412"><Media id="1"><Part id="1" /></Media></Track>
+            <Track ratingKey="2" title="Africa" grandparentTitle="TOTO" parentTitle="Africa" duration="295000"><Media id="2"><Part id="2" /></Media></Track>
+            <Track ratingKey="3" title="Rosanna" grandparentTitle="TOTO" parentTitle="Toto IV" duration="331499"><Media id="3"><Part id="3" /></Media></Track>
+        </MediaContainer>"#;
+        let container: PlexSearchTrackResponse = serde_xml_rs::from_str(xml).unwrap();
+        let plex: Songs = container.try_into().unwrap();
+        let spotify = |name: &str, duration_ms: usize| {
+            let mut song = song(MusicApiType::Spotify, name, name);
+            song.album = Some(crate::music_api::Album { id: None, name: "Toto IV".to_string(), year: None });
+            song.artists = vec![crate::music_api::Artist { id: None, name: "TOTO".to_string() }];
+            song.duration_ms = duration_ms;
+            song
+        };
+
+        // Plex as destination: the album edit, 6 seconds longer, over the single
+        let africa = spotify("Africa", 295_000);
+        let results = plex.0[..2].to_vec();
+        let best = africa.best_match(results.clone(), &MatchOptions::default());
+        assert_eq!(best.unwrap().id, "2");
+        let api = mocked_api(String::new());
+        let options = api.config.match_options_over(&api.default_match_options());
+        assert_eq!(options.duration_tolerance_ms, 8000);
+        assert_eq!(africa.best_match(results.clone(), &options).unwrap().id, "1");
+        // the tolerance given on the command line wins
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--duration-tolerance-ms", "5000"]);
+        let options = config.match_options_over(&api.default_match_options());
+        assert_eq!(africa.best_match(results, &options).unwrap().id, "2");
+
+        // Plex as source: durations rounded to the second, 331.499s is 5 seconds longer
+        let rosanna = &plex.0[2];
+        assert_eq!(rosanna.duration_ms, 331_000);
+        assert!(rosanna.compare_with(&spotify("Rosanna", 326_000), &MatchOptions::default()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_track_mbid() {
+        // captured from a playlist items with includeGuids=1, trimmed
+        let xml = r#"<MediaContainer size="2" totalSize="2" title="Road trip" ratingKey="100">
+            <Track ratingKey="1" title="Bohemian Rhapsody" grandparentTitle="Queen" guid="plex://track/5d07cdc6403c640290f5f6a2">
+                <Media id="1"><Part id="1" /></Media>
+                <Guid id="mbid://b1a9c0e9-d987-4042-ae91-78d6a3267d69" />
+            </Track>
+            <Track ratingKey="2" title="Home recording" guid="local://2">
+                <Media id="2"><Part id="2" /></Media>
+            </Track>
+        </MediaContainer>"#;
+        let container: PlexPlaylistSongsResponse = serde_xml_rs::from_str(xml).unwrap();
+        let songs: Songs = container.try_into().unwrap();
+        assert_eq!(songs.0[0].mbid.as_deref(), Some("b1a9c0e9-d987-4042-ae91-78d6a3267d69"));
+        assert_eq!(songs.0[0].artists[0].name, "Queen");
+        assert_eq!(songs.0[1].mbid, None);
+    }
+
+    #[test]
+    fn test_track_credited_artists() {
+        let xml = r#"<MediaContainer size="6" totalSize="6" title="Collabs" ratingKey="100">
+            <Track ratingKey="1" title="Stay" grandparentRatingKey="10" grandparentTitle="The Kid LAROI" originalTitle="The Kid LAROI &amp; Justin Bieber">
+                <Media id="1"><Part id="1" /></Media>
+            </Track>
+            <Track ratingKey="2" title="Peaches" grandparentRatingKey="11" grandparentTitle="Justin Bieber" originalTitle="Justin Bieber feat. Daniel Caesar, Giveon">
+                <Media id="2"><Part id="2" /></Media>
+            </Track>
+            <Track ratingKey="3" title="Old Town Road" grandparentRatingKey="12" grandparentTitle="Various Artists" originalTitle="Lil Nas X ft. Billy Ray Cyrus">
+                <Media id="3"><Part id="3" /></Media>
+            </Track>
+            <Track ratingKey="4" title="Monster" grandparentRatingKey="13" grandparentTitle="Shawn Mendes" originalTitle="Shawn Mendes x Justin Bieber">
+                <Media id="4"><Part id="4" /></Media>
+            </Track>
+            <Track ratingKey="5" title="Bohemian Rhapsody" grandparentRatingKey="14" grandparentTitle="Queen">
+                <Media id="5"><Part id="5" /></Media>
+            </Track>
+            <Track ratingKey="6" title="Untitled" originalTitle="">
+                <Media id="6"><Part id="6" /></Media>
+            </Track>
+        </MediaContainer>"#;
+        let container: PlexPlaylistSongsResponse = serde_xml_rs::from_str(xml).unwrap();
+        let songs: Songs = container.try_into().unwrap();
+        let names = |i: usize| -> Vec<&str> { songs.0[i].artists.iter().map(|a| a.name.as_str()).collect() };
+
+        assert_eq!(names(0), vec!["The Kid LAROI", "Justin Bieber"]);
+        // the album artist keeps its id
+        assert_eq!(songs.0[0].artists[0].id.as_deref(), Some("10"));
+        assert_eq!(songs.0[0].artists[1].id, None);
+        assert_eq!(names(1), vec!["Justin Bieber", "Daniel Caesar", "Giveon"]);
+        // an uppercase "X" is part of the name
+        assert_eq!(names(2), vec!["Lil Nas X", "Billy Ray Cyrus"]);
+        assert_eq!(songs.0[2].artists[0].id, None);
+        assert_eq!(names(3), vec!["Shawn Mendes", "Justin Bieber"]);
+        // no originalTitle, the album artist
+        assert_eq!(names(4), vec!["Queen"]);
+        assert_eq!(songs.0[4].artists[0].id.as_deref(), Some("14"));
+        assert!(songs.0[5].artists.is_empty());
+    }
+
+    #[test]
+    fn test_prefs_country_code() {
+        // captured from /:/prefs, trimmed
+        let xml = r#"<MediaContainer size="3">
+            <Setting id="FriendlyName" label="Friendly name" type="text" default="" value="Home" />
+            <Setting id="language" label="Language" type="text" default="en" value="en-gb" />
+            <Setting id="country" label="Country" type="text" default="" value="" />
+        </MediaContainer>"#;
+        // the region of the language isn't the country of the server
+        let prefs: PlexPrefsResponse = serde_xml_rs::from_str(xml).unwrap();
+        assert_eq!(PlexApi::prefs_country_code(&prefs), None);
+
+        let with_country = xml.replace(r#"default="" value="""#, r#"default="" value="fr""#);
+        let prefs: PlexPrefsResponse = serde_xml_rs::from_str(&with_country).unwrap();
+        assert_eq!(PlexApi::prefs_country_code(&prefs).as_deref(), Some("FR"));
+    }
+
+    #[tokio::test]
+    async fn test_server_country_code() {
+        let prefs = r#"<MediaContainer size="1"><Setting id="country" value="de" /></MediaContainer>"#;
+        // a token of a shared server can't read the preferences
+        let (url, _) = serve_json(vec![(200, prefs), (401, "<html><head><title>Unauthorized</title></head></html>")]).await;
+        let client = reqwest::Client::new();
+        let country = PlexApi::server_country_code(&client, &url).await.unwrap();
+        assert_eq!(country.as_deref(), Some("DE"));
+        assert!(PlexApi::server_country_code(&client, &url).await.is_err());
+    }
+
+    #[test]
+    fn test_supports_empty_playlists() {
+        assert!(PlexApi::supports_empty_playlists("1.40.2.8395-c67dce28e"));
+        assert!(PlexApi::supports_empty_playlists("1.20.0.3125-b8b6e0a55"));
+        assert!(!PlexApi::supports_empty_playlists("1.19.5.3112-b23ab3896"));
+        assert!(!PlexApi::supports_empty_playlists(""));
+    }
+
+    #[tokio::test]
+    async fn test_update_playlist_summary() {
+        let (url, requests) = serve_json(vec![(200, "")]).await;
+        let mut api = mocked_api(url);
+        let mut playlist = crate::mock_api::playlist("100", "Road trip", vec![]);
+        let summary = "Summer songs\nSynced from Spotify by SyncDisBoi on 2024-02-29";
+        api.update_playlist_details(&mut playlist, Some(summary), Some(true)).await.unwrap();
+        assert_eq!(
+            requests.lock().unwrap()[0].0,
+            "PUT /playlists/100?summary=Summer+songs%0ASynced+from+Spotify+by+SyncDisBoi+on+2024-02-29"
+        );
+        assert_eq!(playlist.description.as_deref(), Some(summary));
+    }
+
+    #[tokio::test]
+    async fn test_add_songs_partially_added() {
+        let added = |count: u32, requested: u32| {
+            format!(
+                r#"<MediaContainer size="1" leafCountAdded="{}" leafCountRequested="{}">
+                    <Playlist ratingKey="100" title="Road trip" smart="0" playlistType="audio" />
+                </MediaContainer>"#,
+                count, requested
+            )
+        };
+        let items = |ids: &[&str]| {
+            let tracks: Vec<String> = ids
+                .iter()
+                .map(|id| format!(r#"<Track ratingKey="{0}" title="{0}"><Media id="{0}"><Part id="{0}" /></Media></Track>"#, id))
+                .collect();
+            format!(r#"<MediaContainer size="{0}" totalSize="{0}">{1}</MediaContainer>"#, ids.len(), tracks.concat())
+        };
+        let responses = [
+            // the first chunk misses a song, added again
+            added(1, 2),
+            items(&["1"]),
+            added(1, 1),
+            // the second chunk fails twice
+            added(0, 1),
+            items(&["1", "2"]),
+            added(0, 1),
+            items(&["1", "2"]),
+        ];
+        let (url, requests) = serve_json(responses.iter().map(|r| (200, r.as_str())).collect()).await;
+        let mut api = mocked_api(url);
+        api.config = ConfigArgs::parse_from(["sync_dis_boi", "--plex-page-size", "2", "--plex-add-chunk-size", "2"]);
+        let mut playlist = crate::mock_api::playlist("100", "Road trip", vec![]);
+        let songs: Vec<Song> = ["1", "2", "3"].iter().map(|id| song(MusicApiType::Spotify, id, id)).collect();
+        api.add_songs_to_playlist(&mut playlist, &songs).await.unwrap();
+
+        let ids: Vec<&str> = playlist.songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].ends_with("library%2Fmetadata%2F1%2C2"), "{}", lines[0]);
+        assert!(lines[1].starts_with("GET /playlists/100/items?"), "{}", lines[1]);
+        assert!(lines[2].ends_with("library%2Fmetadata%2F2"), "{}", lines[2]);
+        assert!(lines[3].ends_with("library%2Fmetadata%2F3"), "{}", lines[3]);
+
+        // nothing added
+        let responses = [added(0, 1), items(&[]), added(0, 1), items(&[])];
+        let (url, _) = serve_json(responses.iter().map(|r| (200, r.as_str())).collect()).await;
+        let mut api = mocked_api(url);
+        let err = api.add_songs_to_playlist(&mut playlist, &songs[2..]).await.unwrap_err();
+        assert!(err.to_string().contains("failed to add any"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist() {
+        let created = r#"<MediaContainer size="1">
+            <Playlist ratingKey="100" title="Road trip" smart="0" playlistType="audio" />
+        </MediaContainer>"#;
+        let request_lines = |requests: &crate::mock_api::Requests| -> Vec<String> {
+            requests.lock().unwrap().iter().map(|r| r.0.clone()).collect()
+        };
+
+        // empty playlist
+        let (url, requests) = serve_json(vec![(200, created), (200, "")]).await;
+        let mut api = mocked_api(url);
+        let playlist = api.create_playlist("Road trip", "Summer songs", false).await.unwrap();
+        assert_eq!(playlist.id, "100");
+        assert_eq!(playlist.description.as_deref(), Some("Summer songs"));
+        assert_eq!(
+            request_lines(&requests),
+            vec![
+                "POST /playlists?uri=server%3A%2F%2Fabc%2Fcom.plexapp.plugins.library%2Flibrary%2Fmetadata%2F&type=audio&title=Road+trip&smart=0",
+                "PUT /playlists/100?summary=Summer+songs",
+            ]
+        );
+
+        // seeded with the first track of the library on older servers
+        let sections = r#"<MediaContainer size="1"><Directory key="4" type="artist" title="Music" /></MediaContainer>"#;
+        let first_track = r#"<MediaContainer size="1">
+            <Track ratingKey="7" title="a"><Media id="7"><Part id="7" /></Media></Track>
+        </MediaContainer>"#;
+        let seeded = r#"<MediaContainer size="1">
+            <Track ratingKey="7" playlistItemID="70" title="a"><Media id="7"><Part id="7" /></Media></Track>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![
+            (200, sections),
+            (200, first_track),
+            (200, created),
+            (200, seeded),
+            (200, ""),
+            (200, ""),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        api.server_version = "1.19.5.3112-b23ab3896".to_string();
+        api.create_playlist("Road trip", "", false).await.unwrap();
+        let lines = request_lines(&requests);
+        assert!(lines[2].starts_with("POST /playlists?uri=server%3A%2F%2Fabc%2Fcom.plexapp.plugins.library%2Flibrary%2Fmetadata%2F7&"), "{}", lines[2]);
+        assert_eq!(lines[4], "DELETE /playlists/100/items/70");
+
+        // the empty creation fails
+        let (url, requests) = serve_json(vec![(400, ""), (200, sections), (200, first_track), (200, created), (200, seeded), (200, ""), (200, "")]).await;
+        let mut api = mocked_api(url);
+        api.create_playlist("Road trip", "", false).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 7);
+
+        // another playlist is returned
+        let (url, _) = serve_json(vec![(200, sections), (200, first_track), (200, created)]).await;
+        let mut api = mocked_api(url);
+        api.server_version = "1.19.5.3112-b23ab3896".to_string();
+        let err = api.create_playlist("Summer", "", false).await.unwrap_err();
+        assert!(err.to_string().contains("\"Summer\""), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_likes_ratings() {
+        let (url, requests) = serve_json(vec![(200, ""), (404, "")]).await;
+        let mut api = mocked_api(url);
+        let songs = vec![
+            song(MusicApiType::Plex, "1", "a"),
+            song(MusicApiType::Plex, "2", "b"),
+        ];
+        let not_added = api.add_likes(&songs).await.unwrap();
+        assert_eq!(not_added.len(), 1);
+        assert_eq!(not_added[0].id, "2");
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(
+            lines[0],
+            "PUT /:/rate?identifier=com.plexapp.plugins.library&key=1&rating=10"
+        );
+
+        let sections = r#"<MediaContainer size="1">
+            <Directory key="4" type="artist" title="Music" />
+        </MediaContainer>"#;
+        let rated = r#"<MediaContainer size="2" totalSize="2" title1="Music">
+            <Track ratingKey="1" title="a" userRating="10.0"><Media id="1"><Part id="1" /></Media></Track>
+            <Track ratingKey="3" title="c" userRating="6.0"><Media id="3"><Part id="3" /></Media></Track>
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, sections), (200, rated)]).await;
+        let mut api = mocked_api(url);
+        let likes = api.get_likes().await.unwrap();
+        assert_eq!(likes.len(), 1);
+        assert_eq!(likes[0].id, "1");
+        let line = requests.lock().unwrap()[1].0.clone();
+        assert!(line.starts_with("GET /library/sections/4/all?type=10&includeGuids=1&userRating%3E=9&"), "{}", line);
+    }
+
+    #[test]
+    fn test_playlist_items_to_remove() {
+        let xml = r#"<MediaContainer size="3" title="Mix" ratingKey="100">
+            <Track ratingKey="1" playlistItemID="11" title="a"><Media id="1"><Part id="1" /></Media></Track>
+            <Track ratingKey="2" playlistItemID="12" title="b"><Media id="2"><Part id="2" /></Media></Track>
+            <Track ratingKey="1" playlistItemID="13" title="a"><Media id="1"><Part id="1" /></Media></Track>
+        </MediaContainer>"#;
+        let container: PlexPlaylistSongsResponse = serde_xml_rs::from_str(xml).unwrap();
+        let tracks = container.tracks.unwrap();
+        let songs: Songs = PlexPlaylistSongsResponse {
+            tracks: Some(tracks[..1].to_vec()),
+            ..container
+        }
+        .try_into()
+        .unwrap();
+
+        // every occurrence of the song is removed
+        assert_eq!(PlexApi::playlist_items_to_remove(&tracks, &songs.0), vec![11, 13]);
+        assert!(PlexApi::playlist_items_to_remove(&tracks, &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_playlists_info() {
+        let xml = r#"<MediaContainer size="3" totalSize="3">
+            <Playlist ratingKey="1" title="All Music" smart="1" playlistType="audio" summary="" />
+            <Playlist ratingKey="2" title="Road trip" smart="0" playlistType="audio" summary="" />
+            <Playlist ratingKey="3" title="Workout" smart="0" playlistType="audio" summary="" />
+        </MediaContainer>"#;
+        let (url, requests) = serve_json(vec![(200, xml)]).await;
+        let mut api = mocked_api(url);
+        let playlists = api.get_playlists_info().await.unwrap();
+        assert_eq!(
+            requests.lock().unwrap()[0].0,
+            "GET /playlists?playlistType=audio&X-Plex-Container-Start=0&X-Plex-Container-Size=2"
+        );
+        let names: Vec<(&str, bool)> = playlists.iter().map(|p| (p.name.as_str(), p.generated)).collect();
+        assert_eq!(names, vec![("All Music", true), ("Road trip", false), ("Workout", false)]);
+        assert!(playlists.iter().all(|p| p.owner.is_none()));
+        assert!(api.lists_own_playlists_only());
+    }
+
+    #[test]
+    fn test_smart_playlists_generated() {
+        let xml = r#"<MediaContainer size="2">
+            <Playlist ratingKey="1" title="All Music" smart="1" summary="" />
+            <Playlist ratingKey="2" title="Road trip" smart="0" summary="summer 2024" />
+        </MediaContainer>"#;
+        let container: PlexPlaylistsResponse = serde_xml_rs::from_str(xml).unwrap();
+        let playlists: Playlists = container.try_into().unwrap();
+        assert!(playlists.0[0].generated);
+        assert_eq!(playlists.0[0].description, None);
+        assert!(!playlists.0[1].generated);
+        assert_eq!(playlists.0[1].description.as_deref(), Some("summer 2024"));
+    }
+}
@@ -14,6 +14,23 @@ pub struct PlexUserResponse {
     pub mapping_state: Option<String>,
 }
 
+/// Preferences of the server, `/:/prefs`
+#[derive(Debug, Deserialize)]
+#[serde(rename = "MediaContainer")]
+pub struct PlexPrefsResponse {
+    #[serde(rename = "Setting", default)]
+    pub settings: Vec<PlexSetting>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlexSetting {
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "value", default)]
+    pub value: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename = "MediaContainer")]
 #[allow(dead_code)]
@@ -23,6 +40,10 @@ pub struct PlexUriResponse {
 
     #[serde(rename = "machineIdentifier")]
     pub machine_identifier: String,
+
+    /// Version of the Plex Media Server, e.g. `1.40.2.8395-c67dce28e`
+    #[serde(rename = "version", default)]
+    pub version: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,6 +53,10 @@ pub struct PlexPlaylistsResponse {
     #[serde(rename = "size", default)]
     pub size: Option<u32>,
 
+    /// Number of items of all the pages, only set for paginated requests
+    #[serde(rename = "totalSize", default)]
+    pub total_size: Option<u32>,
+
     #[serde(rename = "Playlist", default)]
     pub playlists: Vec<PlexPlaylist>,
 }
@@ -137,6 +162,10 @@ pub struct PlexPlaylistSongsResponse {
     #[serde(rename = "size", default)]
     pub size: u32,
 
+    /// Number of items of all the pages, only set for paginated requests
+    #[serde(rename = "totalSize", default)]
+    pub total_size: Option<u32>,
+
     #[serde(rename = "composite", default)]
     pub composite: String,
 
@@ -217,6 +246,11 @@ pub struct Track {
     #[serde(rename = "grandparentTitle", default)]
     pub grandparent_title: String,
 
+    /// Artists credited on the track when they differ from the album artist, e.g.
+    /// "Artist A feat. Artist B"
+    #[serde(rename = "originalTitle", default)]
+    pub original_title: String,
+
     #[serde(rename = "grandparentType", default)]
     pub grandparent_type: String,
 
@@ -238,6 +272,10 @@ pub struct Track {
     #[serde(rename = "ratingCount", default)]
     pub rating_count: u32,
 
+    /// Star rating given by the user, from 0 to 10, `None` for unrated tracks
+    #[serde(rename = "userRating", default)]
+    pub user_rating: Option<f32>,
+
     #[serde(rename = "parentYear", default)]
     pub parent_year: u32,
 
@@ -279,6 +317,17 @@ pub struct Track {
 
     #[serde(rename = "Genre")]
     pub genres: Option<Vec<Genre>>,
+
+    /// Ids of the track in other databases, e.g. `mbid://<recording id>`, only returned
+    /// with `includeGuids=1`
+    #[serde(rename = "Guid", default)]
+    pub guids: Vec<Guid>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Guid {
+    #[serde(rename = "id", default)]
+    pub id: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -347,6 +396,10 @@ pub struct PlexSearchTrackResponse {
     #[serde(rename = "size", default)]
     pub size: Option<u32>,
 
+    /// Number of items of all the pages, only set for paginated requests
+    #[serde(rename = "totalSize", default)]
+    pub total_size: Option<u32>,
+
     #[serde(rename = "Track", default)]
     pub tracks: Option<Vec<Track>>,
 }
@@ -358,6 +411,10 @@ pub struct PlexLibrarySectionsResponse {
     #[serde(rename = "size", default)]
     pub size: Option<u32>,
 
+    /// Number of items of all the pages, only set for paginated requests
+    #[serde(rename = "totalSize", default)]
+    pub total_size: Option<u32>,
+
     #[serde(rename = "allowSync", default)]
     pub allow_sync: Option<u32>,
 
@@ -458,6 +515,50 @@ pub struct PlexCreatePlaylistResponse {
     pub playlists: Vec<PlexPlaylist>,
 }
 
+/// Response to the addition of items to a playlist
+#[derive(Debug, Deserialize)]
+#[serde(rename = "MediaContainer")]
+pub struct PlexAddItemsResponse {
+    /// Fewer than requested when some items were not added
+    #[serde(rename = "leafCountAdded", default)]
+    pub leaf_count_added: Option<u32>,
+}
+
+/// Collections of a library section, also the response to the creation of a collection and to
+/// the addition of items to it
+#[derive(Debug, Deserialize)]
+#[serde(rename = "MediaContainer")]
+pub struct PlexCollectionsResponse {
+    /// Number of items of all the pages, only set for paginated requests
+    #[serde(rename = "totalSize", default)]
+    pub total_size: Option<u32>,
+
+    #[serde(rename = "Directory", default)]
+    pub collections: Vec<PlexCollection>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PlexCollection {
+    #[serde(rename = "ratingKey")]
+    pub rating_key: String,
+
+    #[serde(rename = "title", default)]
+    pub title: String,
+
+    #[serde(rename = "summary", default)]
+    pub summary: String,
+
+    /// Type of the items, `track` for the collections of tracks
+    #[serde(rename = "subtype", default)]
+    pub subtype: String,
+
+    #[serde(rename = "smart", default)]
+    pub smart: String,
+
+    #[serde(rename = "childCount", default)]
+    pub child_count: Option<u32>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename = "MediaContainer")]
 #[allow(dead_code)]
@@ -480,4 +581,36 @@ pub struct SearchResult {
 
     #[serde(rename = "Track", default)]
     pub tracks: Option<Vec<Track>>,
-}
\ No newline at end of file
+}
+
+/// Server or client of the account, from the plex.tv resources
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexResource {
+    pub name: String,
+
+    /// Comma separated roles, e.g. `server` or `client,player`
+    #[serde(default)]
+    pub provides: String,
+
+    /// Token of the server, differs from the token of the account for shared servers
+    #[serde(default)]
+    pub access_token: Option<String>,
+
+    #[serde(default)]
+    pub connections: Vec<PlexConnection>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexConnection {
+    pub uri: String,
+
+    /// Reachable from the local network only
+    #[serde(default)]
+    pub local: bool,
+
+    /// Relayed by plex.tv, the slowest
+    #[serde(default)]
+    pub relay: bool,
+}
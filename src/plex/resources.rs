@@ -0,0 +1,215 @@
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::login::PlexPinLogin;
+use super::model::{PlexConnection, PlexResource};
+
+const PLEX_TV_URL: &str = "https://plex.tv";
+/// Unreachable connections are given up quickly, the next one is tried
+const IDENTITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connection to a Plex server found on plex.tv
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlexServerConnection {
+    pub url: String,
+    /// Token of the server, the token of the account for the servers it owns
+    pub token: String,
+}
+
+/// Connection of a server that worked on the previous run, tried first
+#[derive(Deserialize, Serialize, Debug)]
+struct PlexCachedConnection {
+    name: String,
+    uri: String,
+}
+
+/// The first reachable connection of the server named `name` among the resources of the
+/// account. The connection that worked is saved in `cache_path` and tried first next time
+pub async fn resolve_server(token: &str, name: &str, cache_path: Option<&Path>) -> Result<PlexServerConnection> {
+    resolve_server_with(PLEX_TV_URL, token, name, cache_path).await
+}
+
+async fn resolve_server_with(
+    url: &str,
+    token: &str,
+    name: &str,
+    cache_path: Option<&Path>,
+) -> Result<PlexServerConnection> {
+    let client = reqwest::Client::builder().timeout(IDENTITY_TIMEOUT).build()?;
+    let text = client
+        .get(format!("{}/api/v2/resources", url))
+        .query(&[("includeHttps", "1"), ("includeRelay", "1")])
+        .header("Accept", "application/json")
+        .header("X-Plex-Token", token)
+        .header("X-Plex-Client-Identifier", PlexPinLogin::new_client_identifier())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let resources: Vec<PlexResource> = serde_json::from_str(&text)?;
+    let server = find_server(&resources, name)?;
+
+    let cached = cache_path
+        .filter(|p| p.exists())
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str::<PlexCachedConnection>(&s).ok())
+        .filter(|c| c.name == server.name)
+        .map(|c| c.uri);
+    let token = server.access_token.clone().unwrap_or_else(|| token.to_string());
+    for uri in connection_order(&server.connections, cached.as_deref()) {
+        let res = client
+            .get(format!("{}/identity", uri))
+            .header("X-Plex-Token", &token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = res {
+            debug!("Plex server connection {} is unreachable: {}", uri, e);
+            continue;
+        }
+        info!("using the connection {} of Plex server \"{}\"", uri, server.name);
+        if let Some(path) = cache_path {
+            let cached = PlexCachedConnection {
+                name: server.name.clone(),
+                uri: uri.to_string(),
+            };
+            if let Err(e) = std::fs::write(path, serde_json::to_string(&cached)?) {
+                warn!("failed to save the Plex server connection: {}", e);
+            }
+        }
+        return Ok(PlexServerConnection {
+            url: uri.trim_end_matches('/').to_string(),
+            token,
+        });
+    }
+    Err(eyre!("None of the connections of Plex server \"{}\" is reachable", server.name))
+}
+
+/// Server named `name`, ignoring the case, among the resources of the account
+fn find_server<'a>(resources: &'a [PlexResource], name: &str) -> Result<&'a PlexResource> {
+    let servers: Vec<&PlexResource> = resources
+        .iter()
+        .filter(|r| r.provides.split(',').any(|p| p.trim() == "server"))
+        .collect();
+    servers
+        .iter()
+        .find(|r| r.name.eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<&str> = servers.iter().map(|r| r.name.as_str()).collect();
+            eyre!(
+                "No Plex server named \"{}\" found, the servers of the account are: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// Uris of the connections in the order they are tried: the cached one, the local ones, the
+/// remote ones, then the relayed ones
+fn connection_order<'a>(connections: &'a [PlexConnection], cached: Option<&str>) -> Vec<&'a str> {
+    let rank = |c: &PlexConnection| {
+        if Some(c.uri.as_str()) == cached {
+            0
+        } else if c.relay {
+            3
+        } else if c.local {
+            1
+        } else {
+            2
+        }
+    };
+    let mut ordered: Vec<&PlexConnection> = connections.iter().collect();
+    ordered.sort_by_key(|c| rank(c));
+    ordered.into_iter().map(|c| c.uri.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{serve_json, test_dir};
+
+    fn resources(remote: &str) -> String {
+        format!(
+            r#"[
+            {{"name": "Living room TV", "product": "Plex for Android (TV)", "provides": "client,player", "connections": []}},
+            {{
+                "name": "Home",
+                "product": "Plex Media Server",
+                "provides": "server",
+                "owned": true,
+                "accessToken": "server-token",
+                "connections": [
+                    {{"protocol": "https", "address": "1.2.3.4", "port": 443, "uri": "https://1-2-3-4.abc.plex.direct:8443", "local": false, "relay": true, "IPv6": false}},
+                    {{"protocol": "http", "address": "127.0.0.1", "port": 1, "uri": "http://127.0.0.1:1", "local": true, "relay": false, "IPv6": false}},
+                    {{"protocol": "http", "address": "127.0.0.1", "port": 80, "uri": "{}", "local": false, "relay": false, "IPv6": false}}
+                ]
+            }}
+        ]"#,
+            remote
+        )
+    }
+
+    #[test]
+    fn test_connection_order() {
+        let resources: Vec<PlexResource> = serde_json::from_str(&resources("https://5-6-7-8.abc.plex.direct:32400")).unwrap();
+        let server = find_server(&resources, "home").unwrap();
+        assert_eq!(server.access_token.as_deref(), Some("server-token"));
+        assert_eq!(
+            connection_order(&server.connections, None),
+            vec![
+                "http://127.0.0.1:1",
+                "https://5-6-7-8.abc.plex.direct:32400",
+                "https://1-2-3-4.abc.plex.direct:8443"
+            ]
+        );
+        // the connection of the previous run first
+        assert_eq!(
+            connection_order(&server.connections, Some("https://1-2-3-4.abc.plex.direct:8443"))[0],
+            "https://1-2-3-4.abc.plex.direct:8443"
+        );
+
+        // clients are not servers
+        let err = find_server(&resources, "Living room TV").unwrap_err();
+        assert!(err.to_string().contains("the servers of the account are: Home"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_server() {
+        // the identity of the remote connection, served after the resources
+        let (url, requests) = serve_json(vec![(200, ""), (200, "")]).await;
+        let resources = resources(&url);
+        let (plex_tv, _) = serve_json(vec![(200, &resources), (200, &resources)]).await;
+
+        let cache = test_dir("plex_server").join("plex_server.json");
+        // the local connection is unreachable
+        let connection = resolve_server_with(&plex_tv, "user-token", "Home", Some(&cache)).await.unwrap();
+        assert_eq!(
+            connection,
+            PlexServerConnection {
+                url: url.clone(),
+                token: "server-token".to_string(),
+            }
+        );
+        assert!(std::fs::read_to_string(&cache).unwrap().contains(&url));
+
+        // the cached connection is tried first
+        resolve_server_with(&plex_tv, "user-token", "Home", Some(&cache)).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert_eq!(requests.lock().unwrap()[1].0, "GET /identity");
+        std::fs::remove_file(&cache).unwrap();
+
+        // nothing is reachable
+        let resources = resources
+            .replace(&url, "http://127.0.0.1:1")
+            .replace("https://1-2-3-4.abc.plex.direct:8443", "http://127.0.0.1:2");
+        let (plex_tv, _) = serve_json(vec![(200, &resources)]).await;
+        let err = resolve_server_with(&plex_tv, "user-token", "Home", None).await.unwrap_err();
+        assert!(err.to_string().contains("\"Home\""), "{}", err);
+    }
+}
@@ -1,9 +1,91 @@
 use std::convert::TryInto;
+use std::sync::OnceLock;
 use color_eyre::eyre::{Error, Result};
+use regex::Regex;
 use tracing::{error, warn};
 
 use crate::music_api::{Album, Artist, MusicApiType, Playlist, Playlists, Song, Songs};
-use super::model::{PlexCreatePlaylistResponse, PlexPlaylist, PlexPlaylistSongsResponse, PlexPlaylistsResponse, PlexSearchTrackResponse, Track};
+use super::model::{PlexCollectionsResponse, PlexCreatePlaylistResponse, PlexLibrarySectionsResponse, PlexPlaylist, PlexPlaylistSongsResponse, PlexPlaylistsResponse, PlexSearchTrackResponse, Track};
+
+/// Media container the server returns page by page
+pub trait PagedContainer: serde::de::DeserializeOwned {
+    /// Number of items of this page
+    fn page_len(&self) -> usize;
+    /// Number of items of all the pages, `None` when the container isn't paginated
+    fn total_size(&self) -> Option<u32>;
+    /// Append the items of the following page
+    fn append(&mut self, page: Self);
+}
+
+impl PagedContainer for PlexPlaylistsResponse {
+    fn page_len(&self) -> usize {
+        self.playlists.len()
+    }
+
+    fn total_size(&self) -> Option<u32> {
+        self.total_size
+    }
+
+    fn append(&mut self, page: Self) {
+        self.playlists.extend(page.playlists);
+    }
+}
+
+impl PagedContainer for PlexPlaylistSongsResponse {
+    fn page_len(&self) -> usize {
+        self.tracks.as_ref().map_or(0, Vec::len)
+    }
+
+    fn total_size(&self) -> Option<u32> {
+        self.total_size
+    }
+
+    fn append(&mut self, page: Self) {
+        self.tracks.get_or_insert_default().extend(page.tracks.unwrap_or_default());
+    }
+}
+
+impl PagedContainer for PlexSearchTrackResponse {
+    fn page_len(&self) -> usize {
+        self.tracks.as_ref().map_or(0, Vec::len)
+    }
+
+    fn total_size(&self) -> Option<u32> {
+        self.total_size
+    }
+
+    fn append(&mut self, page: Self) {
+        self.tracks.get_or_insert_default().extend(page.tracks.unwrap_or_default());
+    }
+}
+
+impl PagedContainer for PlexLibrarySectionsResponse {
+    fn page_len(&self) -> usize {
+        self.directories.as_ref().map_or(0, Vec::len)
+    }
+
+    fn total_size(&self) -> Option<u32> {
+        self.total_size
+    }
+
+    fn append(&mut self, page: Self) {
+        self.directories.get_or_insert_default().extend(page.directories.unwrap_or_default());
+    }
+}
+
+impl PagedContainer for PlexCollectionsResponse {
+    fn page_len(&self) -> usize {
+        self.collections.len()
+    }
+
+    fn total_size(&self) -> Option<u32> {
+        self.total_size
+    }
+
+    fn append(&mut self, page: Self) {
+        self.collections.extend(page.collections);
+    }
+}
 
 impl TryInto<Playlist> for PlexPlaylist {
     type Error = Error;
@@ -14,6 +96,17 @@ impl TryInto<Playlist> for PlexPlaylist {
             name: self.title,
             songs: vec![],
             owner: None,
+            description: Some(self.summary).filter(|s| !s.is_empty()),
+            // plex playlists have no visibility
+            public: None,
+            // smart playlists are filled by the server
+            generated: self.smart == "1",
+            cover_url: None,
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
         })
     }
 }
@@ -62,12 +155,24 @@ impl TryInto<Song> for Track {
             Some(Album {
                 id: Some(self.parent_rating_key),
                 name: self.parent_title,
+                year: u16::try_from(self.parent_year).ok().filter(|year| *year != 0),
             })
         } else {
             None
         };
 
-        let artists = if !self.grandparent_title.is_empty() {
+        // the album artist is only a fallback for the artists credited on the track
+        let credited = credited_artists(&self.original_title);
+        let artists = if !credited.is_empty() {
+            credited
+                .into_iter()
+                .map(|name| Artist {
+                    id: Some(self.grandparent_rating_key.clone())
+                        .filter(|_| name.eq_ignore_ascii_case(&self.grandparent_title)),
+                    name,
+                })
+                .collect()
+        } else if !self.grandparent_title.is_empty() {
             vec![Artist {
                 id: Some(self.grandparent_rating_key),
                 name: self.grandparent_title,
@@ -76,6 +181,13 @@ impl TryInto<Song> for Track {
             vec![]
         };
 
+        let mbid = self
+            .guids
+            .iter()
+            .find_map(|g| g.id.strip_prefix("mbid://"))
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+
         let title = if !self.title.is_empty() {
             self.title
         } else {
@@ -87,7 +199,14 @@ impl TryInto<Song> for Track {
             name: title,
             album,
             artists,
-            duration_ms: self.duration as usize,
+            // the durations of the files, to the millisecond, are rounded to the second like
+            // the ones of the streaming platforms
+            duration_ms: (self.duration as usize + 500) / 1000 * 1000,
+            uploaded: false,
+            explicit: None,
+            alt_names: vec![],
+            mbid,
+            isrc_hint: None,
             source: MusicApiType::Plex,
             sid: None,
             isrc: None,
@@ -95,6 +214,21 @@ impl TryInto<Song> for Track {
     }
 }
 
+/// Artists of the `originalTitle` of a track, split on "feat.", "ft.", "&", "x" and ",". Only a
+/// lowercase "x" separates artists, "Lil Nas X" is a name
+fn credited_artists(original_title: &str) -> Vec<String> {
+    static SPLIT_RE: OnceLock<Regex> = OnceLock::new();
+    let split_re = SPLIT_RE
+        .get_or_init(|| Regex::new(r"(?i)\s*(?:,|&|\s(?:feat\.?|ft\.?|featuring|(?-i:x))\s)\s*").unwrap());
+    let mut artists: Vec<String> = vec![];
+    for name in split_re.split(original_title).map(str::trim) {
+        if !name.is_empty() && !artists.iter().any(|a| a.eq_ignore_ascii_case(name)) {
+            artists.push(name.to_string());
+        }
+    }
+    artists
+}
+
 impl TryInto<Songs> for PlexPlaylistSongsResponse {
     type Error = Error;
 
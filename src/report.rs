@@ -0,0 +1,489 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::music_api::{MusicApiType, Song, Unavailability};
+use crate::utils::unix_timestamp;
+
+pub const REPORT_VERSION: u32 = 1;
+
+/// Outcome of a synchronization, written with `--report`
+///
+/// The schema is meant to be consumed by other tools: fields may be added, but existing fields
+/// are not renamed or removed without bumping `version`.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct SyncReport {
+    pub version: u32,
+    /// Source platform of the synchronized songs, `None` when they come from several platforms
+    pub src_api: Option<MusicApiType>,
+    pub dst_api: MusicApiType,
+    pub dry_run: bool,
+    /// Unix timestamp of the start of the synchronization
+    pub started_at: u64,
+    /// Unix timestamp of the end of the synchronization
+    pub ended_at: u64,
+    pub totals: ReportTotals,
+    /// In the source playlists order
+    pub playlists: Vec<PlaylistReport>,
+    /// Liked songs, with `--sync-likes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub likes: Option<PlaylistReport>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+pub struct ReportTotals {
+    pub playlists: usize,
+    pub created_playlists: usize,
+    pub skipped_playlists: usize,
+    /// Source playlists whose songs couldn't be retrieved, also counted as skipped
+    #[serde(default)]
+    pub unavailable_playlists: usize,
+    pub matched: u32,
+    pub attempts: u32,
+    pub missing: usize,
+    /// Spotify local files among the missing songs
+    #[serde(default)]
+    pub local_files: usize,
+    pub added: usize,
+    pub duplicates_skipped: usize,
+    #[serde(default)]
+    pub not_added: usize,
+    #[serde(default)]
+    pub isrc_enriched: usize,
+    /// Seconds spent by both platforms waiting before retrying rate limited or failed requests
+    #[serde(default)]
+    pub backoff_secs: u64,
+}
+
+/// Outcome of the synchronization of a source playlist
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct PlaylistReport {
+    pub src_id: String,
+    pub src_name: String,
+    pub dst_name: String,
+    /// The destination playlist was created by this run
+    pub created: bool,
+    /// The playlist was not synchronized, because of a suspicious pairing or because the
+    /// source playlist is unavailable
+    pub skipped: bool,
+    /// The source playlist was skipped as it didn't change since its last synchronization
+    #[serde(default)]
+    pub unchanged: bool,
+    /// The source playlist is inaccessible or deleted, its songs couldn't be retrieved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unavailable: Option<Unavailability>,
+    /// Source songs found on the destination platform
+    pub matched: u32,
+    pub attempts: u32,
+    pub conversion_rate: f64,
+    /// Source songs with no match on the destination platform
+    pub missing: Vec<Song>,
+    /// Spotify local files among the missing songs, they were only searched by their metadata
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub local_files: Vec<Song>,
+    /// Destination songs added to the playlist
+    pub added: Vec<Song>,
+    /// Duplicate songs in the source playlist, or resolving to the same destination song
+    pub duplicates_skipped: usize,
+    /// Destination songs the platform refused to add, e.g. not available in the region
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_added: Vec<Song>,
+    /// Source songs whose ISRC was found with `--enrich-isrc`
+    #[serde(default)]
+    pub isrc_enriched: usize,
+}
+
+/// Number or percentage of songs that may be missing for a synchronization to succeed,
+/// `--fail-on-missing`
+#[derive(Clone, Debug, PartialEq)]
+pub enum MissingThreshold {
+    Count(usize),
+    Percent(f64),
+}
+
+impl std::str::FromStr for MissingThreshold {
+    type Err = color_eyre::eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| eyre!("invalid percentage: {}", s))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(eyre!("percentage out of range: {}", s));
+            }
+            return Ok(Self::Percent(percent));
+        }
+        let count = s
+            .parse()
+            .map_err(|_| eyre!("expected a number of songs or a percentage like 5%: {}", s))?;
+        Ok(Self::Count(count))
+    }
+}
+
+impl std::fmt::Display for MissingThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Count(count) => write!(f, "{} songs", count),
+            Self::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+impl MissingThreshold {
+    /// Whether more source songs than the threshold couldn't be matched
+    pub fn exceeded(&self, totals: &ReportTotals) -> bool {
+        match self {
+            Self::Count(count) => totals.missing > *count,
+            Self::Percent(_) if totals.attempts == 0 => false,
+            #[allow(clippy::cast_precision_loss)]
+            Self::Percent(percent) => totals.missing as f64 * 100.0 / f64::from(totals.attempts) > *percent,
+        }
+    }
+}
+
+/// Conversion rate of a playlist, as written in `debug/conversion_rate.json`
+#[derive(Serialize)]
+struct DebugConversionRate {
+    percentage: f64,
+    number: String,
+}
+
+impl SyncReport {
+    pub fn new(src_api: Option<MusicApiType>, dst_api: MusicApiType, dry_run: bool) -> Self {
+        let started_at = unix_timestamp();
+        Self {
+            version: REPORT_VERSION,
+            src_api,
+            dst_api,
+            dry_run,
+            started_at,
+            ended_at: started_at,
+            totals: ReportTotals::default(),
+            playlists: vec![],
+            likes: None,
+        }
+    }
+
+    pub fn push(&mut self, playlist: PlaylistReport) {
+        self.totals.playlists += 1;
+        if playlist.skipped {
+            self.totals.skipped_playlists += 1;
+        }
+        if playlist.unavailable.is_some() {
+            self.totals.unavailable_playlists += 1;
+        }
+        if playlist.created {
+            self.totals.created_playlists += 1;
+        }
+        self.add_songs(&playlist);
+        self.playlists.push(playlist);
+    }
+
+    /// Record the synchronization of the liked songs, counted in the song totals
+    pub fn push_likes(&mut self, likes: PlaylistReport) {
+        self.add_songs(&likes);
+        self.likes = Some(likes);
+    }
+
+    fn add_songs(&mut self, playlist: &PlaylistReport) {
+        let totals = &mut self.totals;
+        totals.matched += playlist.matched;
+        totals.attempts += playlist.attempts;
+        totals.missing += playlist.missing.len();
+        totals.local_files += playlist.local_files.len();
+        totals.added += playlist.added.len();
+        totals.duplicates_skipped += playlist.duplicates_skipped;
+        totals.not_added += playlist.not_added.len();
+        totals.isrc_enriched += playlist.isrc_enriched;
+    }
+
+    pub fn finish(&mut self) {
+        self.ended_at = unix_timestamp();
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Fail when more songs than `threshold` couldn't be matched
+    pub fn check_missing(&self, threshold: &MissingThreshold) -> Result<()> {
+        if threshold.exceeded(&self.totals) {
+            return Err(eyre!(
+                "{} of {} songs couldn't be matched, more than the {} allowed by --fail-on-missing",
+                self.totals.missing,
+                self.totals.attempts,
+                threshold
+            ));
+        }
+        Ok(())
+    }
+
+    /// Summary of the synchronization as table lines: playlist, matched/searched songs and
+    /// whether the destination playlist was created, then the totals and the time spent backing
+    /// off
+    pub fn summary(&self) -> Vec<String> {
+        let mut rows: Vec<[String; 3]> = vec![[
+            "playlist".to_string(),
+            "matched".to_string(),
+            "destination".to_string(),
+        ]];
+        for playlist in &self.playlists {
+            let status = if let Some(reason) = playlist.unavailable {
+                reason.to_string()
+            } else if playlist.unchanged {
+                "unchanged".to_string()
+            } else if playlist.skipped {
+                "skipped".to_string()
+            } else if playlist.created {
+                "created".to_string()
+            } else {
+                "existing".to_string()
+            };
+            rows.push([
+                playlist.dst_name.clone(),
+                format!("{}/{}", playlist.matched, playlist.attempts),
+                status,
+            ]);
+        }
+        let local_files = if self.totals.local_files > 0 {
+            format!(" ({} local files)", self.totals.local_files)
+        } else {
+            String::new()
+        };
+        let total_status = if self.totals.not_added > 0 {
+            format!("{} missing{}, {} not added", self.totals.missing, local_files, self.totals.not_added)
+        } else {
+            format!("{} missing{}", self.totals.missing, local_files)
+        };
+        rows.push([
+            "total".to_string(),
+            format!("{}/{}", self.totals.matched, self.totals.attempts),
+            total_status,
+        ]);
+        if self.totals.backoff_secs > 0 {
+            rows.push([
+                "backoff".to_string(),
+                format!("{}s", self.totals.backoff_secs),
+                "waiting to retry rate limited or failed requests".to_string(),
+            ]);
+        }
+
+        let width = |column: usize| rows.iter().map(|r| r[column].chars().count()).max().unwrap_or(0);
+        let (name_width, matched_width) = (width(0), width(1));
+        rows.iter()
+            .map(|[name, matched, status]| {
+                format!("{:<name_width$}  {:>matched_width$}  {}", name, matched, status)
+            })
+            .collect()
+    }
+
+    /// Write the debug files of `--debug` in `dir`, keyed by source playlist name, the liked
+    /// songs being a playlist of their own
+    pub fn write_debug_files(&self, dir: &Path) -> Result<()> {
+        let synchronized = self.playlists.iter().chain(&self.likes).filter(|p| !p.skipped);
+        let stats: BTreeMap<&str, DebugConversionRate> = synchronized
+            .clone()
+            .map(|p| {
+                let rate = DebugConversionRate {
+                    percentage: p.conversion_rate,
+                    number: format!("{}/{}", p.matched, p.attempts),
+                };
+                (p.src_name.as_str(), rate)
+            })
+            .collect();
+        std::fs::write(
+            dir.join("conversion_rate.json"),
+            serde_json::to_string_pretty(&stats)?,
+        )?;
+
+        let new_songs: BTreeMap<&str, &[Song]> = synchronized
+            .clone()
+            .filter(|p| !p.added.is_empty())
+            .map(|p| (p.src_name.as_str(), p.added.as_slice()))
+            .collect();
+        if !new_songs.is_empty() {
+            std::fs::write(
+                dir.join("new_songs.json"),
+                serde_json::to_string_pretty(&new_songs)?,
+            )?;
+        }
+
+        let not_added_songs: BTreeMap<&str, &[Song]> = synchronized
+            .clone()
+            .filter(|p| !p.not_added.is_empty())
+            .map(|p| (p.src_name.as_str(), p.not_added.as_slice()))
+            .collect();
+        if !not_added_songs.is_empty() {
+            std::fs::write(
+                dir.join("not_added_songs.json"),
+                serde_json::to_string_pretty(&not_added_songs)?,
+            )?;
+        }
+
+        let local_files: BTreeMap<&str, &[Song]> = synchronized
+            .clone()
+            .filter(|p| !p.local_files.is_empty())
+            .map(|p| (p.src_name.as_str(), p.local_files.as_slice()))
+            .collect();
+        if !local_files.is_empty() {
+            std::fs::write(
+                dir.join("missing_local_files.json"),
+                serde_json::to_string_pretty(&local_files)?,
+            )?;
+        }
+
+        let missing_songs: BTreeMap<&str, &[Song]> = synchronized
+            .filter(|p| !p.missing.is_empty())
+            .map(|p| (p.src_name.as_str(), p.missing.as_slice()))
+            .collect();
+        if !missing_songs.is_empty() {
+            std::fs::write(
+                dir.join("missing_songs.json"),
+                serde_json::to_string_pretty(&missing_songs)?,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::song;
+
+    #[test]
+    fn test_report_roundtrip() {
+        let mut report = SyncReport::new(Some(MusicApiType::Spotify), MusicApiType::Tidal, false);
+        report.push(PlaylistReport {
+            src_id: "p1".to_string(),
+            src_name: "Chill".to_string(),
+            dst_name: "Chill".to_string(),
+            created: true,
+            skipped: false,
+            unchanged: false,
+            unavailable: None,
+            matched: 2,
+            attempts: 3,
+            conversion_rate: 2.0 / 3.0,
+            missing: vec![song(MusicApiType::Spotify, "s3", "Gone")],
+            local_files: vec![],
+            added: vec![
+                song(MusicApiType::Tidal, "d1", "First"),
+                song(MusicApiType::Tidal, "d2", "Second"),
+            ],
+            duplicates_skipped: 1,
+            not_added: vec![],
+            isrc_enriched: 0,
+        });
+        report.finish();
+
+        assert_eq!(report.totals.created_playlists, 1);
+        assert_eq!(report.totals.missing, 1);
+        assert_eq!(report.totals.added, 2);
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: SyncReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, report);
+
+        // stable field names, relied upon by consumers of the report
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], REPORT_VERSION);
+        assert_eq!(value["src_api"], "Spotify");
+        assert_eq!(value["totals"]["matched"], 2);
+        assert_eq!(value["playlists"][0]["missing"][0]["name"], "Gone");
+    }
+
+    fn playlist_report(name: &str, created: bool, matched: u32, attempts: u32) -> PlaylistReport {
+        PlaylistReport {
+            src_id: name.to_lowercase(),
+            src_name: name.to_string(),
+            dst_name: name.to_string(),
+            created,
+            skipped: false,
+            unchanged: false,
+            unavailable: None,
+            matched,
+            attempts,
+            conversion_rate: 0.0,
+            missing: (matched..attempts)
+                .map(|i| song(MusicApiType::Spotify, &format!("s{}", i), "Missing"))
+                .collect(),
+            local_files: vec![],
+            added: vec![],
+            duplicates_skipped: 0,
+            not_added: vec![],
+            isrc_enriched: 0,
+        }
+    }
+
+    #[test]
+    fn test_missing_threshold() {
+        let parse = |s: &str| s.parse::<MissingThreshold>();
+        assert_eq!(parse("3").unwrap(), MissingThreshold::Count(3));
+        assert_eq!(parse("2.5%").unwrap(), MissingThreshold::Percent(2.5));
+        assert_eq!(parse(" 10 % ").unwrap(), MissingThreshold::Percent(10.0));
+        assert!(parse("150%").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("some").is_err());
+
+        let mut report = SyncReport::new(Some(MusicApiType::Spotify), MusicApiType::Tidal, false);
+        // nothing searched, nothing missing
+        assert!(!MissingThreshold::Count(0).exceeded(&report.totals));
+        assert!(!MissingThreshold::Percent(0.0).exceeded(&report.totals));
+
+        report.push(playlist_report("Chill", false, 18, 20));
+        assert!(report.check_missing(&MissingThreshold::Count(2)).is_ok());
+        assert!(report.check_missing(&MissingThreshold::Percent(10.0)).is_ok());
+        let err = report.check_missing(&MissingThreshold::Count(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 of 20 songs couldn't be matched, more than the 1 songs allowed by --fail-on-missing"
+        );
+        assert!(report.check_missing(&MissingThreshold::Percent(9.9)).is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut report = SyncReport::new(Some(MusicApiType::Spotify), MusicApiType::Tidal, false);
+        report.push(playlist_report("Chill", true, 2, 3));
+        report.push(playlist_report("Workout Mix", false, 120, 120));
+        let mut skipped = playlist_report("Café", false, 0, 0);
+        skipped.skipped = true;
+        report.push(skipped);
+
+        assert_eq!(
+            report.summary(),
+            vec![
+                "playlist     matched  destination",
+                "Chill            2/3  created",
+                "Workout Mix  120/120  existing",
+                "Café             0/0  skipped",
+                "total        122/123  1 missing",
+            ]
+        );
+
+        // liked songs count in the song totals, not as a playlist
+        let mut likes = playlist_report("Liked songs", false, 7, 10);
+        likes.not_added = vec![song(MusicApiType::Tidal, "t1", "Region locked")];
+        likes.local_files = vec![likes.missing[0].clone()];
+        report.push_likes(likes);
+        assert_eq!((report.totals.playlists, report.totals.attempts, report.totals.missing), (3, 133, 4));
+        assert_eq!(report.totals.local_files, 1);
+        assert_eq!(
+            report.summary().last().unwrap(),
+            "total        129/133  4 missing (1 local files), 1 not added"
+        );
+
+        // the time spent backing off is a row of its own
+        report.totals.backoff_secs = 180;
+        assert_eq!(
+            report.summary().last().unwrap(),
+            "backoff         180s  waiting to retry rate limited or failed requests"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Retries of the requests rate limited or failed by the platforms. Each platform has its own
+//! defaults, `--max-retries` and `--max-backoff-secs` apply to all of them
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use color_eyre::eyre::{Result, eyre};
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use tracing::warn;
+
+/// How many times and after how long a failed request is sent again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries of a request before giving up
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled before each following one
+    pub initial_backoff: Duration,
+    /// Longest wait before a retry, the `Retry-After` of the rate limits included
+    pub max_backoff: Duration,
+}
+
+/// Action to take after a response
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetryAction {
+    /// Send the request again after waiting
+    Retry(Duration),
+    /// Not rate limited nor a server error, use the response
+    Continue,
+    /// Still failing after `max_retries` retries
+    MaxRetriesExceeded,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff, `initial_backoff * 2^retry_count` capped at `max_backoff`
+    pub fn backoff(&self, retry_count: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry_count))
+            .min(self.max_backoff)
+    }
+
+    /// Whether to retry a request with the `status` response after `retry_count` retries.
+    /// Rate limits wait for their `retry_after` when given, the server errors the backoff
+    pub fn action(&self, status: StatusCode, retry_after: Option<Duration>, retry_count: u32) -> RetryAction {
+        let rate_limited = status == StatusCode::TOO_MANY_REQUESTS;
+        let transient = matches!(status.as_u16(), 500 | 502 | 503 | 504);
+        if !rate_limited && !transient {
+            return RetryAction::Continue;
+        }
+        if retry_count >= self.max_retries {
+            return RetryAction::MaxRetriesExceeded;
+        }
+        let backoff = retry_after
+            .filter(|_| rate_limited)
+            .map_or_else(|| self.backoff(retry_count), |wait| wait.min(self.max_backoff));
+        RetryAction::Retry(backoff)
+    }
+}
+
+/// Wait requested by the `Retry-After` header, only understood in seconds
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Send a request to `platform`, retrying it after rate limits and server errors as `policy`
+/// says. The time spent waiting is added to `backoff_time`
+pub async fn send(
+    request: reqwest::RequestBuilder,
+    policy: &RetryPolicy,
+    platform: &str,
+    backoff_time: &Mutex<Duration>,
+) -> Result<reqwest::Response> {
+    let mut retry_count = 0;
+    loop {
+        let attempt = request
+            .try_clone()
+            .ok_or_else(|| eyre!("{} request can't be sent again", platform))?;
+        let res = attempt.send().await?;
+        let status = res.status();
+        let retry_after = retry_after(res.headers());
+        match policy.action(status, retry_after, retry_count) {
+            RetryAction::Continue => return Ok(res),
+            RetryAction::Retry(backoff) => {
+                if let Some(asked) = retry_after.filter(|asked| *asked > backoff) {
+                    warn!(
+                        "{} asked to wait {} seconds before retrying, waiting {} seconds only",
+                        platform,
+                        asked.as_secs(),
+                        backoff.as_secs()
+                    );
+                }
+                warn!(
+                    "{} HTTP status {} (attempt {}/{}). Waiting {} seconds before retry...",
+                    platform,
+                    status,
+                    retry_count + 1,
+                    policy.max_retries + 1,
+                    backoff.as_secs()
+                );
+                tokio::time::sleep(backoff).await;
+                *backoff_time.lock().unwrap() += backoff;
+                retry_count += 1;
+            }
+            RetryAction::MaxRetriesExceeded => {
+                return Err(eyre!(
+                    "{} HTTP status {} after {} attempts, please wait before retrying",
+                    platform,
+                    status,
+                    retry_count + 1
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::serve_json_headers;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 3,
+        initial_backoff: Duration::from_secs(2),
+        max_backoff: Duration::from_mins(5),
+    };
+
+    #[test]
+    fn test_retry_action() {
+        let action = |status: u16, retry_after: Option<u64>, retry_count: u32| {
+            POLICY.action(
+                StatusCode::from_u16(status).unwrap(),
+                retry_after.map(Duration::from_secs),
+                retry_count,
+            )
+        };
+
+        assert_eq!(action(200, None, 0), RetryAction::Continue);
+        assert_eq!(action(404, None, 0), RetryAction::Continue);
+        assert_eq!(action(412, Some(10), 0), RetryAction::Continue);
+        // Retry-After is honored for rate limits, capped at the longest wait
+        assert_eq!(action(429, Some(7), 0), RetryAction::Retry(Duration::from_secs(7)));
+        assert_eq!(action(429, Some(3600), 0), RetryAction::Retry(Duration::from_mins(5)));
+        // exponential backoff otherwise
+        let schedule: Vec<RetryAction> = (0..3).map(|n| action(429, None, n)).collect();
+        assert_eq!(
+            schedule,
+            [2, 4, 8].map(|s| RetryAction::Retry(Duration::from_secs(s)))
+        );
+        assert_eq!(action(503, Some(60), 1), RetryAction::Retry(Duration::from_secs(4)));
+        assert_eq!(action(500, None, 12), RetryAction::MaxRetriesExceeded);
+        assert_eq!(action(429, None, 3), RetryAction::MaxRetriesExceeded);
+        assert_eq!(POLICY.backoff(40), Duration::from_mins(5));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, "12".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(12)));
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2026 07:28:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_waits() {
+        let (url, requests) = serve_json_headers(vec![
+            (429, "Retry-After: 30", ""),
+            (429, "Retry-After: 3600", ""),
+            (502, "", ""),
+            (200, "", "{}"),
+        ])
+        .await;
+        let backoff_time = Mutex::new(Duration::ZERO);
+        let start = tokio::time::Instant::now();
+        let res = send(reqwest::Client::new().get(&url), &POLICY, "Test", &backoff_time)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(requests.lock().unwrap().len(), 4);
+        // the asked 30 seconds, the capped hour, then the backoff of the third retry
+        let waited = Duration::from_secs(30) + Duration::from_mins(5) + Duration::from_secs(8);
+        assert_eq!(*backoff_time.lock().unwrap(), waited);
+        assert!(start.elapsed() >= waited);
+
+        let (url, requests) = serve_json_headers(vec![(429, "Retry-After: 1", ""); 4]).await;
+        let err = send(reqwest::Client::new().get(&url), &POLICY, "Test", &backoff_time)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("429 Too Many Requests after 4 attempts"), "{}", err);
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::ConfigArgs;
+use crate::music_api::{DynMusicApi, MusicApiType, Song};
+use crate::overrides::song_keys;
+use crate::utils::suspend_progress;
+
+/// Decision of the user on a song no match was found for
+#[derive(Debug, PartialEq)]
+pub enum ReviewChoice {
+    /// Index of the chosen search result
+    Candidate(usize),
+    /// Destination song id entered manually
+    Id(String),
+    Skip,
+}
+
+/// Songs skipped by the user in `--interactive` mode, stored as JSON in the config directory
+///
+/// Unlike the match cache, the skips are kept with `--no-cache`, a skipped song is never
+/// reviewed again. Removing the file brings them back.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct ReviewSkips {
+    /// destination platform short name -> keys of the skipped source songs, see `song_keys`
+    #[serde(default)]
+    pub skipped: BTreeMap<String, BTreeSet<String>>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl ReviewSkips {
+    pub const FILE_NAME: &'static str = "review_skips.json";
+
+    /// Load the skips file, a missing file is equivalent to an empty one
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let skips = serde_json::from_reader(std::fs::File::open(path)?)?;
+        Ok(skips)
+    }
+
+    /// Write the skips file if it was modified since it was loaded
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if self.dirty {
+            std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, song: &Song, dst: &MusicApiType) -> bool {
+        self.skipped
+            .get(dst.short_name())
+            .is_some_and(|keys| song_keys(song).iter().any(|key| keys.contains(key)))
+    }
+
+    pub fn insert(&mut self, song: &Song, dst: &MusicApiType) {
+        let key = song_keys(song).swap_remove(0);
+        self.dirty |= self.skipped.entry(dst.short_name().to_string()).or_default().insert(key);
+    }
+}
+
+fn format_duration(duration_ms: usize) -> String {
+    format!("{}:{:02}", duration_ms / 60_000, duration_ms / 1000 % 60)
+}
+
+/// Let the user choose the destination song of `song` among the destination search results,
+/// `--interactive` mode
+///
+/// The chosen song is recorded in the match cache by the caller, and a skipped song in `skips`,
+/// so it's never asked twice.
+pub async fn review_song(
+    dst_api: &mut DynMusicApi,
+    song: &Song,
+    config: &ConfigArgs,
+    skips: &Mutex<ReviewSkips>,
+) -> Result<Option<Song>> {
+    let dst = dst_api.api_type();
+    if skips.lock().unwrap().contains(song, &dst) {
+        debug!("song skipped in a previous review: {}", song);
+        return Ok(None);
+    }
+    let candidates = dst_api
+        .search_candidates(song, config.interactive_candidates)
+        .await?;
+    let choice = suspend_progress(|| {
+        prompt_choice(song, &candidates, &dst, std::io::stdin().lock(), std::io::stdout())
+    })?;
+    if choice == ReviewChoice::Skip {
+        skips.lock().unwrap().insert(song, &dst);
+    }
+    resolve_choice(dst_api, song, candidates, choice).await
+}
+
+/// Show the search results for `song` and read the user's choice
+///
+/// The user can enter the number of a search result, a destination song id or nothing to
+/// skip the song. Numbers are only read as search results when in range, numeric song ids
+/// are much longer.
+pub fn prompt_choice<R: BufRead, W: Write>(
+    song: &Song,
+    candidates: &[Song],
+    dst: &MusicApiType,
+    mut input: R,
+    mut output: W,
+) -> Result<ReviewChoice> {
+    writeln!(output, "no match found for {}", song)?;
+    for (i, candidate) in candidates.iter().enumerate() {
+        let artists = candidate
+            .artists
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let album = candidate.album.as_ref().map_or("no album", |a| a.name.as_str());
+        writeln!(
+            output,
+            "  {}. {} - {} ({}) [{}]",
+            i + 1,
+            candidate.name,
+            artists,
+            album,
+            format_duration(candidate.duration_ms)
+        )?;
+    }
+    write!(
+        output,
+        "search result number, {} song id or empty to skip: ",
+        dst.short_name()
+    )?;
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(ReviewChoice::Skip);
+    }
+    let choice = line.trim();
+    if choice.is_empty() {
+        return Ok(ReviewChoice::Skip);
+    }
+    match choice.parse::<usize>() {
+        Ok(n) if (1..=candidates.len()).contains(&n) => Ok(ReviewChoice::Candidate(n - 1)),
+        _ => Ok(ReviewChoice::Id(choice.to_string())),
+    }
+}
+
+/// Destination song of a choice, ids entered manually are looked up when the platform
+/// supports it, otherwise the rest of the metadata is taken from the source song
+pub async fn resolve_choice(
+    dst_api: &mut DynMusicApi,
+    song: &Song,
+    mut candidates: Vec<Song>,
+    choice: ReviewChoice,
+) -> Result<Option<Song>> {
+    match choice {
+        ReviewChoice::Skip => Ok(None),
+        ReviewChoice::Candidate(i) => Ok(Some(candidates.swap_remove(i))),
+        ReviewChoice::Id(id) => {
+            let dst_song = song.with_id(dst_api.api_type(), &id);
+            if !dst_api.supports_song_lookup() {
+                return Ok(Some(dst_song));
+            }
+            let found = dst_api.lookup_songs(&[dst_song]).await?.pop().flatten();
+            if found.is_none() {
+                warn!("song {} does not exist on {:?}, skipping", id, dst_api.api_type());
+            }
+            Ok(found)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{MockApi, song, test_dir};
+
+    #[test]
+    fn test_prompt_choice() {
+        let src = song(MusicApiType::Spotify, "s1", "Song 1");
+        let candidates = vec![
+            song(MusicApiType::Tidal, "123456", "Song 1 (Live)"),
+            song(MusicApiType::Tidal, "234567", "Song 1 - Remix"),
+        ];
+        let choice = |input: &str| {
+            prompt_choice(&src, &candidates, &MusicApiType::Tidal, input.as_bytes(), vec![]).unwrap()
+        };
+
+        assert_eq!(choice("2\n"), ReviewChoice::Candidate(1));
+        assert_eq!(choice(" 1 \n"), ReviewChoice::Candidate(0));
+        assert_eq!(choice("3\n"), ReviewChoice::Id("3".to_string()));
+        assert_eq!(choice("98765432\n"), ReviewChoice::Id("98765432".to_string()));
+        assert_eq!(choice("\n"), ReviewChoice::Skip);
+        assert_eq!(choice(""), ReviewChoice::Skip);
+
+        let mut output = vec![];
+        prompt_choice(&src, &candidates, &MusicApiType::Tidal, "\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  1. Song 1 (Live) - Artist (no album) [3:00]"));
+        assert!(output.contains("  2. Song 1 - Remix - Artist (no album) [3:00]"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_choice() {
+        let src = song(MusicApiType::Spotify, "s1", "Song 1");
+        let candidates = vec![song(MusicApiType::Tidal, "t1", "Song 1 (Live)")];
+        let catalog = vec![song(MusicApiType::Tidal, "t2", "Song One")];
+        let mut api = MockApi::new(MusicApiType::Tidal, catalog).boxed();
+
+        let res = resolve_choice(&mut api, &src, candidates.clone(), ReviewChoice::Candidate(0)).await;
+        assert_eq!(res.unwrap().unwrap().id, "t1");
+        let res = resolve_choice(&mut api, &src, candidates.clone(), ReviewChoice::Skip).await;
+        assert!(res.unwrap().is_none());
+
+        // without lookup, the id is trusted
+        let res = resolve_choice(&mut api, &src, vec![], ReviewChoice::Id("t3".to_string())).await;
+        let res = res.unwrap().unwrap();
+        assert_eq!((res.source, res.id.as_str(), res.name.as_str()), (MusicApiType::Tidal, "t3", "Song 1"));
+
+        let mut mock = MockApi::new(MusicApiType::Tidal, vec![song(MusicApiType::Tidal, "t2", "Song One")]);
+        mock.lookup = true;
+        let mut api = mock.boxed();
+        let res = resolve_choice(&mut api, &src, vec![], ReviewChoice::Id("t2".to_string())).await;
+        assert_eq!(res.unwrap().unwrap().name, "Song One");
+        let res = resolve_choice(&mut api, &src, vec![], ReviewChoice::Id("t3".to_string())).await;
+        assert!(res.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_review_skips() {
+        let src = song(MusicApiType::Spotify, "s1", "Song 1");
+        let mut skips = ReviewSkips::default();
+        skips.insert(&src, &MusicApiType::Tidal);
+        assert!(skips.contains(&src, &MusicApiType::Tidal));
+        assert!(!skips.contains(&src, &MusicApiType::YtMusic));
+        assert!(!skips.contains(&song(MusicApiType::Spotify, "s2", "Song 2"), &MusicApiType::Tidal));
+
+        let path = test_dir("review_skips").join("review_skips.json");
+        skips.save(&path).unwrap();
+        let mut loaded = ReviewSkips::load(&path).unwrap();
+        assert!(loaded.contains(&src, &MusicApiType::Tidal));
+        // skipping it again changes nothing
+        loaded.insert(&src, &MusicApiType::Tidal);
+        assert!(!loaded.dirty);
+    }
+}
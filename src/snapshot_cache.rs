@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::music_api::{MusicApiType, Playlist, Song};
+
+/// Songs of the source playlists at their last seen snapshot, stored as JSON in the config
+/// directory
+///
+/// Only the platforms giving a snapshot id to their playlists (Spotify) are cached: the songs of
+/// a playlist whose snapshot didn't change are not fetched again.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct SnapshotCache {
+    /// "source platform:source playlist id" -> last seen snapshot
+    #[serde(default)]
+    pub playlists: BTreeMap<String, PlaylistSnapshot>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PlaylistSnapshot {
+    pub snapshot_id: String,
+    pub songs: Vec<Song>,
+    /// "destination platform short name:destination account" -> snapshot the destination
+    /// playlist was last synchronized to
+    #[serde(default)]
+    pub synced: BTreeMap<String, String>,
+}
+
+/// What to do with a source playlist before synchronizing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotAction {
+    /// Unknown or changed snapshot, the songs are fetched
+    Fetch,
+    /// Same snapshot, the cached songs are used
+    Reuse,
+    /// Same snapshot, already synchronized to the destination account. The cached songs are
+    /// used if the destination playlist no longer exists
+    Skip,
+}
+
+fn key(src: &MusicApiType, playlist_id: &str) -> String {
+    format!("{}:{}", src.short_name(), playlist_id)
+}
+
+fn dst_key(dst: &MusicApiType, account: &str) -> String {
+    format!("{}:{}", dst.short_name(), account)
+}
+
+impl SnapshotCache {
+    pub const FILE_NAME: &'static str = "playlist_snapshots.json";
+
+    /// Load the cache file, a missing file is equivalent to an empty cache
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let cache = serde_json::from_reader(std::fs::File::open(path)?)?;
+        Ok(cache)
+    }
+
+    /// Write the cache file if it was modified since it was loaded
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if self.dirty {
+            std::fs::write(path, serde_json::to_string(self)?)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Whether the songs of a source playlist of `src` must be fetched before synchronizing it
+    /// to the `account` of `dst`, `--force-refresh` always fetches them
+    pub fn action(
+        &self,
+        src: &MusicApiType,
+        playlist: &Playlist,
+        dst: &MusicApiType,
+        account: &str,
+        force_refresh: bool,
+    ) -> SnapshotAction {
+        let Some(snapshot_id) = &playlist.snapshot_id else {
+            return SnapshotAction::Fetch;
+        };
+        match self.playlists.get(&key(src, &playlist.id)) {
+            Some(cached) if !force_refresh && &cached.snapshot_id == snapshot_id => {
+                if cached.synced.get(&dst_key(dst, account)) == Some(snapshot_id) {
+                    SnapshotAction::Skip
+                } else {
+                    SnapshotAction::Reuse
+                }
+            }
+            _ => SnapshotAction::Fetch,
+        }
+    }
+
+    /// Songs cached for the current snapshot of a playlist
+    pub fn songs(&self, src: &MusicApiType, playlist: &Playlist) -> Option<&[Song]> {
+        let cached = self.playlists.get(&key(src, &playlist.id))?;
+        (playlist.snapshot_id.as_ref() == Some(&cached.snapshot_id)).then_some(cached.songs.as_slice())
+    }
+
+    /// Record the fetched songs of a playlist, a new snapshot is synchronized to no destination
+    pub fn insert(&mut self, src: &MusicApiType, playlist: &Playlist) {
+        let Some(snapshot_id) = &playlist.snapshot_id else {
+            return;
+        };
+        let key = key(src, &playlist.id);
+        let synced = match self.playlists.remove(&key) {
+            Some(cached) if &cached.snapshot_id == snapshot_id => cached.synced,
+            _ => BTreeMap::new(),
+        };
+        let snapshot = PlaylistSnapshot {
+            snapshot_id: snapshot_id.clone(),
+            songs: playlist.songs.clone(),
+            synced,
+        };
+        self.playlists.insert(key, snapshot);
+        self.dirty = true;
+    }
+
+    /// Record that the cached snapshot of a playlist was synchronized to the `account` of `dst`
+    pub fn mark_synced(&mut self, src: &MusicApiType, playlist_id: &str, dst: &MusicApiType, account: &str) {
+        if let Some(cached) = self.playlists.get_mut(&key(src, playlist_id)) {
+            cached.synced.insert(dst_key(dst, account), cached.snapshot_id.clone());
+            self.dirty = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::{playlist, song};
+
+    #[test]
+    fn test_snapshot_actions() {
+        let (spotify, tidal, plex) = (MusicApiType::Spotify, MusicApiType::Tidal, MusicApiType::Plex);
+        let mut cache = SnapshotCache::default();
+        let mut p1 = playlist("p1", "Chill", vec![song(MusicApiType::Spotify, "s1", "First")]);
+        p1.snapshot_id = Some("v1".to_string());
+
+        // platforms without snapshots are always fetched
+        let no_snapshot = playlist("p2", "Workout", vec![]);
+        cache.insert(&spotify, &no_snapshot);
+        assert!(cache.playlists.is_empty());
+        assert_eq!(cache.action(&spotify, &no_snapshot, &tidal, "me", false), SnapshotAction::Fetch);
+
+        // never seen, then seen but not synchronized
+        assert_eq!(cache.action(&spotify, &p1, &tidal, "me", false), SnapshotAction::Fetch);
+        cache.insert(&spotify, &p1);
+        let mut listed = p1.clone();
+        listed.songs = vec![];
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", false), SnapshotAction::Reuse);
+        assert_eq!(cache.songs(&spotify, &listed).unwrap(), p1.songs.as_slice());
+
+        // synchronized to a destination platform only
+        cache.mark_synced(&spotify, "p1", &tidal, "me");
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", false), SnapshotAction::Skip);
+        // another account of the same platform
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "other", false), SnapshotAction::Reuse);
+        assert_eq!(cache.action(&spotify, &listed, &plex, "me", false), SnapshotAction::Reuse);
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", true), SnapshotAction::Fetch);
+        // the same id on another source platform
+        assert_eq!(cache.action(&MusicApiType::YtMusic, &listed, &tidal, "me", false), SnapshotAction::Fetch);
+
+        // fetched again at the same snapshot, still synchronized
+        cache.insert(&spotify, &p1);
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", false), SnapshotAction::Skip);
+
+        // edited since
+        listed.snapshot_id = Some("v2".to_string());
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", false), SnapshotAction::Fetch);
+        assert!(cache.songs(&spotify, &listed).is_none());
+        cache.insert(&spotify, &listed);
+        assert_eq!(cache.action(&spotify, &listed, &tidal, "me", false), SnapshotAction::Reuse);
+        assert!(cache.playlists["spotify:p1"].synced.is_empty());
+    }
+}
@@ -1,39 +1,54 @@
 pub mod model;
+mod pkce;
 mod response;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use async_recursion::async_recursion;
 use async_trait::async_trait;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use color_eyre::eyre::{Result, eyre};
 use model::SpotifyUserResponse;
 use reqwest::header::HeaderMap;
-use reqwest::{Response, StatusCode};
+use reqwest::StatusCode;
 use serde::de::DeserializeOwned;
 use serde_json::json;
 use tokio::net::TcpListener;
 use tracing::{debug, info, warn};
 
 use self::model::{
-    SpotifyPageResponse, SpotifyPlaylistResponse, SpotifySnapshotResponse, SpotifySongItemResponse,
+    SpotifyAlbumSearchResponse, SpotifyArtistSearchResponse, SpotifyCursorPageResponse,
+    SpotifyFollowedArtistsResponse, SpotifyPageResponse, SpotifyPlaylistResponse, SpotifySavedAlbumItemResponse,
+    SpotifySnapshotResponse, SpotifySongItemResponse,
 };
+use self::pkce::{PkceChallenge, random_string};
+use self::response::into_artists;
 use crate::ConfigArgs;
+use crate::cover::CoverImage;
 use crate::music_api::{
-    MusicApi, MusicApiType, OAuthRefreshToken, OAuthToken, PLAYLIST_DESC, Playlist, Playlists,
-    Song, Songs,
+    Artist, DynMusicApi, MusicApi, MusicApiType, OAuthRefreshToken, OAuthToken, Playlist,
+    Playlists, SavedAlbum, Song, Songs, push_candidates,
 };
-use crate::spotify::model::SpotifySearchResponse;
+use crate::retry::{self, RetryPolicy};
+use crate::spotify::model::{SpotifySearchResponse, SpotifySongResponse, SpotifyTracksResponse};
 use crate::utils::debug_response_json;
 
+#[derive(Clone)]
 pub struct SpotifyApi {
     client: reqwest::Client,
     config: ConfigArgs,
     country_code: String,
     client_id: String,
-    client_secret: String,
+    /// `None` for the tokens obtained with PKCE
+    client_secret: Option<String>,
     oauth_token_path: PathBuf,
+    /// Base URL of the Web API
+    api_url: String,
+    /// Time spent waiting before retries, shared by the handles
+    backoff_time: Arc<Mutex<Duration>>,
 }
 
 #[derive(Debug)]
@@ -55,17 +70,33 @@ impl SpotifyApi {
         "user-read-private",
         "user-library-read",
         "user-library-modify",
+        "user-follow-read",
+        "user-follow-modify",
         "playlist-read-collaborative",
         "playlist-modify-public",
         "playlist-read-private",
         "playlist-modify-private",
+        "ugc-image-upload",
     ];
+    /// Largest base64 encoded JPEG accepted as a playlist cover
+    const MAX_COVER_SIZE: usize = 256 * 1024;
+    /// Length of the `state` of the authorization requests
+    const STATE_LEN: usize = 32;
     const LISTEN_RESPONSE: &'static str = "HTTP/1.1 200 OK\r\nContent-Length: 56\r\n\r\nAuthorization code received! You may now close this tab.";
     const RES_DEBUG_FILENAME: &'static str = MusicApiType::Spotify.short_name();
-
+    /// The waits double from a second, the rate limits wait as long as Spotify asks, up to
+    /// 5 minutes
+    const RETRY_POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 5,
+        initial_backoff: Duration::from_secs(1),
+        max_backoff: Duration::from_mins(5),
+    };
+
+    /// Connect with the token saved in `oauth_token_path`, or authorize the app in the browser.
+    /// Without a client secret, the app is authorized with PKCE
     pub async fn new(
         client_id: &str,
-        client_secret: &str,
+        client_secret: Option<&str>,
         oauth_token_path: PathBuf,
         redirect_uri: &str,
         clear_cache: bool,
@@ -77,7 +108,18 @@ impl SpotifyApi {
             Self::request_token(&config, client_id, client_secret, callback_host, callback_port, redirect_uri).await?
         } else {
             info!("refreshing token");
-            Self::refresh_token(&config, client_id, client_secret, &oauth_token_path).await?
+            let token = Self::refresh_token(&config, client_id, client_secret, &oauth_token_path).await?;
+            let missing = Self::missing_scopes(&token.scope);
+            if missing.is_empty() {
+                token
+            } else {
+                // tokens authorized by older versions lack the scopes added since
+                warn!(
+                    "the saved Spotify token lacks the {} scopes, authorizing SyncDisBoi again",
+                    missing.join(", ")
+                );
+                Self::request_token(&config, client_id, client_secret, callback_host, callback_port, redirect_uri).await?
+            }
         };
         // Write new token
         let mut file = std::fs::File::create(&oauth_token_path)?;
@@ -105,8 +147,10 @@ impl SpotifyApi {
             config,
             country_code: String::new(),
             client_id: client_id.to_string(),
-            client_secret: client_secret.to_string(),
+            client_secret: client_secret.map(str::to_string),
             oauth_token_path,
+            api_url: Self::BASE_API.to_string(),
+            backoff_time: Arc::new(Mutex::new(Duration::ZERO)),
         };
 
         let me_res: SpotifyUserResponse = spotify_api
@@ -120,31 +164,46 @@ impl SpotifyApi {
     async fn request_token(
         config: &ConfigArgs,
         client_id: &str,
-        client_secret: &str,
+        client_secret: Option<&str>,
         callback_host: &str,
         callback_port: &str,
         redirect_uri: &str,
     ) -> Result<OAuthToken> {
-        let auth_url = SpotifyApi::build_authorization_url(client_id, callback_host, callback_port, redirect_uri)?;
-        let auth_code = SpotifyApi::listen_for_code(&auth_url, callback_port).await?;
-        let final_callback_host = if callback_host == "0.0.0.0" {
-            "localhost"
+        let pkce = if client_secret.is_none() {
+            info!("no Spotify client secret given, authorizing with PKCE");
+            Some(PkceChallenge::new()?)
         } else {
-            callback_host
+            None
         };
-        let redirect_uri_url  = format!("http://{}:{}/callback", final_callback_host, callback_port);
-        let mut params = HashMap::new();
-        params.insert("grant_type", "authorization_code");
-        params.insert("code", &auth_code);
-        params.insert("redirect_uri", &redirect_uri_url);
+        // echoed back by the redirection, tells it apart from a forged one
+        let state = random_string(Self::STATE_LEN)?;
+        let auth_url = SpotifyApi::build_authorization_url(
+            client_id,
+            callback_host,
+            callback_port,
+            redirect_uri,
+            &state,
+            pkce.as_ref(),
+        )?;
+        let redirect_uri_url = Self::callback_url(callback_host, callback_port);
+        info!(
+            "the redirect URI {} must be registered in the settings of the app on the Spotify dashboard",
+            redirect_uri_url
+        );
+        let auth_code = if config.callback_manual {
+            SpotifyApi::read_pasted_callback(&auth_url, &state)?
+        } else {
+            let timeout = Duration::from_secs(config.callback_timeout_secs);
+            SpotifyApi::listen_for_code(&auth_url, callback_port, &state, timeout).await?
+        };
+        let params = Self::code_token_form(client_id, &auth_code, &redirect_uri_url, pkce.as_ref());
 
         let client = reqwest::Client::new();
-        let res = client
-            .post(Self::TOKEN_URL)
-            .basic_auth(client_id, Some(client_secret))
-            .form(&params)
-            .send()
-            .await?;
+        let mut request = client.post(Self::TOKEN_URL).form(&params);
+        if let Some(client_secret) = client_secret {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        let res = request.send().await?;
         let status = res.status();
         let token = debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await?;
         if !status.is_success() {
@@ -153,23 +212,52 @@ impl SpotifyApi {
         Ok(token)
     }
 
+    /// Form exchanging an authorization code for a token. With PKCE, the code verifier and the
+    /// client id take the place of the client secret sent with basic auth
+    fn code_token_form(
+        client_id: &str,
+        auth_code: &str,
+        redirect_uri: &str,
+        pkce: Option<&PkceChallenge>,
+    ) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("grant_type", "authorization_code".to_string()),
+            ("code", auth_code.to_string()),
+            ("redirect_uri", redirect_uri.to_string()),
+        ];
+        if let Some(pkce) = pkce {
+            params.push(("client_id", client_id.to_string()));
+            params.push(("code_verifier", pkce.verifier.clone()));
+        }
+        params
+    }
+
+    /// Form refreshing a token, the tokens obtained with PKCE are refreshed without secret
+    fn refresh_token_form(
+        client_id: &str,
+        client_secret: Option<&str>,
+        refresh_token: &str,
+    ) -> Vec<(&'static str, String)> {
+        let mut params = vec![("client_id", client_id.to_string())];
+        if let Some(client_secret) = client_secret {
+            params.push(("client_secret", client_secret.to_string()));
+        }
+        params.push(("grant_type", "refresh_token".to_string()));
+        params.push(("refresh_token", refresh_token.to_string()));
+        params
+    }
+
     async fn refresh_token(
         config: &ConfigArgs,
         client_id: &str,
-        client_secret: &str,
+        client_secret: Option<&str>,
         oauth_token_path: &PathBuf,
     ) -> Result<OAuthToken> {
         let client = reqwest::Client::new();
         let reader = std::fs::File::open(oauth_token_path)?;
         let mut oauth_token: OAuthToken = serde_json::from_reader(reader)?;
 
-        let params = json!({
-            "client_id": client_id,
-            "client_secret": client_secret,
-            "grant_type": "refresh_token",
-            "refresh_token": &oauth_token.refresh_token,
-        });
-
+        let params = Self::refresh_token_form(client_id, client_secret, &oauth_token.refresh_token);
         let res = client.post(Self::TOKEN_URL).form(&params).send().await?;
         let status = res.status();
         let refresh_token: OAuthRefreshToken =
@@ -181,22 +269,53 @@ impl SpotifyApi {
         oauth_token.access_token = refresh_token.access_token;
         oauth_token.expires_in = refresh_token.expires_in;
         oauth_token.scope = refresh_token.scope;
+        // the refresh tokens obtained with PKCE are replaced on each refresh
+        if let Some(new_refresh_token) = refresh_token.refresh_token {
+            oauth_token.refresh_token = new_refresh_token;
+        }
         Ok(oauth_token)
     }
 
-    fn build_authorization_url(client_id: &str, callback_host: &str, callback_port: &str, redirect_uri: &str) -> Result<String> {
+    /// Scopes of `SCOPES` missing from the space separated `granted` scopes of a token
+    fn missing_scopes(granted: &str) -> Vec<&'static str> {
+        let granted: Vec<&str> = granted.split_whitespace().collect();
+        Self::SCOPES
+            .iter()
+            .copied()
+            .filter(|scope| !granted.contains(scope))
+            .collect()
+    }
+
+    /// URI Spotify redirects to after the authorization, as registered in the app settings
+    fn callback_url(callback_host: &str, callback_port: &str) -> String {
         let final_callback_host = if callback_host == "0.0.0.0" {
             "localhost"
         } else {
             callback_host
         };
-        let redirect_uri_url  = format!("http://{}:{}/callback", final_callback_host, callback_port);
+        format!("http://{}:{}/callback", final_callback_host, callback_port)
+    }
+
+    fn build_authorization_url(
+        client_id: &str,
+        callback_host: &str,
+        callback_port: &str,
+        redirect_uri: &str,
+        state: &str,
+        pkce: Option<&PkceChallenge>,
+    ) -> Result<String> {
+        let redirect_uri_url = Self::callback_url(callback_host, callback_port);
         let mut params = HashMap::new();
         params.insert("response_type", "code");
         let scopes = SpotifyApi::SCOPES.iter().as_slice().join(" ");
         params.insert("scope", &scopes);
         params.insert("client_id", client_id);
         params.insert("redirect_uri", &redirect_uri_url);
+        params.insert("state", state);
+        if let Some(pkce) = pkce {
+            params.insert("code_challenge_method", "S256");
+            params.insert("code_challenge", &pkce.challenge);
+        }
         let _ = redirect_uri;
         Ok(
             reqwest::Url::parse_with_params("https://accounts.spotify.com/authorize", params)?
@@ -204,17 +323,31 @@ impl SpotifyApi {
         )
     }
 
-    async fn listen_for_code(auth_url: &str, callback_port: &str) -> Result<String> {
+    async fn listen_for_code(auth_url: &str, callback_port: &str, state: &str, timeout: Duration) -> Result<String> {
         let bind_address = format!("0.0.0.0:{}", callback_port);
 
         let listener = TcpListener::bind(&bind_address).await?;
-        if webbrowser::open(&auth_url).is_err() {
+        if webbrowser::open(auth_url).is_err() {
             info!("Please authorize the app by visiting the following URL: {}", auth_url);
         } else {
-            info!("Please authorize the app in your browser and press enter");
+            info!("Please authorize the app in your browser");
         }
+        Self::receive_callback(&listener, state, timeout).await
+    }
 
-        let (socket, _) = listener.accept().await?;
+    /// Authorization code of the first callback request received by `listener`
+    async fn receive_callback(listener: &TcpListener, state: &str, timeout: Duration) -> Result<String> {
+        let bind_address = listener.local_addr()?;
+        let (socket, _) = tokio::time::timeout(timeout, listener.accept())
+            .await
+            .map_err(|_| {
+                eyre!(
+                    "No Spotify authorization received on {} after {} seconds. If the browser can't reach \
+                    this machine (WSL, SSH...), use --callback-manual to paste the URL it was redirected to",
+                    bind_address,
+                    timeout.as_secs()
+                )
+            })??;
 
         socket.readable().await?;
         let mut buffer = [0; 1024];
@@ -225,15 +358,7 @@ impl SpotifyApi {
         if splits.len() <= 1 {
             return Err(eyre!("Invalid spotify server callback"));
         }
-        // let url = format!("{}{}", bind_address, splits[1]);
-        // HACK: dummy url to parse the code query param
-        let url = format!("http://localhost{}", splits[1]);
-        let auth_code = reqwest::Url::parse(&url)?
-            .query_pairs()
-            .find(|pair| pair.0 == "code")
-            .ok_or(eyre!("Spotify server returned no authorization code"))?
-            .1
-            .to_string();
+        let auth_code = Self::parse_callback(splits[1], state)?;
 
         socket.writable().await?;
         socket.try_write(Self::LISTEN_RESPONSE.as_bytes())?;
@@ -241,8 +366,46 @@ impl SpotifyApi {
         Ok(auth_code)
     }
 
-    fn build_endpoint(path: &str) -> String {
-        format!("{}{}", SpotifyApi::BASE_API, path)
+    /// Authorization code of the redirection URL pasted by the user, `--callback-manual`
+    fn read_pasted_callback(auth_url: &str, state: &str) -> Result<String> {
+        info!("Please authorize the app by visiting the following URL: {}", auth_url);
+        info!(
+            "The browser is then redirected to a page that fails to load: paste its full URL here and \
+            press enter"
+        );
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        Self::parse_callback(&line, state)
+    }
+
+    /// Authorization code of the URL Spotify redirected to, or of its path and query only.
+    /// The `state` must be the one of the authorization request
+    fn parse_callback(url: &str, state: &str) -> Result<String> {
+        let url = url.trim();
+        // HACK: dummy base to parse the path of the callback request
+        let url = if url.starts_with('/') {
+            reqwest::Url::parse(&format!("http://localhost{}", url))
+        } else {
+            reqwest::Url::parse(url)
+        }
+        .map_err(|e| eyre!("Invalid Spotify callback URL \"{}\": {}", url, e))?;
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        if let Some(error) = params.get("error") {
+            return Err(eyre!("Spotify authorization failed: {}", error));
+        }
+        if params.get("state").map(String::as_str) != Some(state) {
+            return Err(eyre!(
+                "The state of the Spotify callback doesn't match the authorization request, please try again"
+            ));
+        }
+        params
+            .get("code")
+            .cloned()
+            .ok_or(eyre!("Spotify server returned no authorization code"))
+    }
+
+    fn build_endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.api_url, path)
     }
 
     async fn paginated_request<T>(
@@ -264,22 +427,38 @@ impl SpotifyApi {
         Ok(res)
     }
 
-    async fn api_rate_wait(&self, res: &Response) -> Result<()> {
-        let headers = res.headers();
-        let sleep_time = headers
-            .get("Retry-After")
-            .ok_or(eyre!("Invalid Retry-After header"))?
-            .to_str()?
-            .parse::<u64>()?;
-        info!(
-            "API rate limit reached, sleeping for {} seconds",
-            sleep_time
-        );
-        tokio::time::sleep(Duration::from_secs(sleep_time)).await;
-        Ok(())
+    /// Items of all the pages of a cursor paginated endpoint, `page` extracting the page from
+    /// the response
+    async fn cursor_paginated_request<R, T>(
+        &mut self,
+        path: &str,
+        params: &[(&str, &str)],
+        limit: usize,
+        page: impl Fn(R) -> SpotifyCursorPageResponse<T>,
+    ) -> Result<Vec<T>>
+    where
+        R: DeserializeOwned,
+        T: DeserializeOwned,
+    {
+        let mut items = vec![];
+        let mut after: Option<String> = None;
+        loop {
+            let mut get_params = params.to_vec();
+            if let Some(after) = &after {
+                get_params.push(("after", after.as_str()));
+            }
+            let res = page(
+                self.make_request_json(path, &HttpMethod::Get(&get_params), limit, 0)
+                    .await?,
+            );
+            items.extend(res.items);
+            after = res.cursors.and_then(|c| c.after);
+            if res.next.is_none() || after.is_none() {
+                return Ok(items);
+            }
+        }
     }
 
-    #[async_recursion]
     async fn make_request_json<T>(
         &mut self,
         path: &str,
@@ -290,35 +469,20 @@ impl SpotifyApi {
     where
         T: DeserializeOwned,
     {
-        let endpoint = Self::build_endpoint(path);
-
-        let mut request = match method {
-            HttpMethod::Get(p) => self.client.get(endpoint).query(p),
-            HttpMethod::Post(b) => self.client.post(endpoint).json(b),
-            HttpMethod::Put(b) => self.client.put(endpoint).json(b),
-            HttpMethod::Delete(b) => self.client.delete(endpoint).json(b),
-        };
+        let mut request = self.build_request(path, method, limit, offset);
+        // rate limits and server errors are retried by the retry policy, network errors here
         let retries = 3;
-        request = request.query(&[("limit", limit), ("offset", offset)]);
+        let policy = self.config.retry_policy(&Self::RETRY_POLICY, None, None);
         for attempt in 0..=retries {
-            let mut request_clone = request.try_clone().expect("Failed to clone request");
-            request_clone = request_clone.query(&[("limit", limit), ("offset", offset)]);
-            let res = request_clone.send().await;
+            let request_clone = request.try_clone().expect("Failed to clone request");
+            let res = retry::send(request_clone, &policy, "Spotify", &self.backoff_time).await;
             match res {
                 Ok(res) => {
                     let status = res.status();
-                    if status == StatusCode::TOO_MANY_REQUESTS {
-                        self.api_rate_wait(&res).await?;
-                        // Retry request
-                        return self.make_request_json(path, method, limit, offset).await;
-                    } else if res.status().is_server_error() {
-                        warn!("Server error (attempt {}): {}. Retrying...", attempt + 1, res.status());
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue;
-                    } else if res.status() == StatusCode::UNAUTHORIZED {
+                    if status == StatusCode::UNAUTHORIZED {
                         warn!("Unauthorized (attempt {}): {}. Refreshing token...", attempt + 1, res.status());
                         // Refresh token
-                        let new_token = Self::refresh_token(&self.config, &self.client_id, &self.client_secret, &self.oauth_token_path).await?;
+                        let new_token = Self::refresh_token(&self.config, &self.client_id, self.client_secret.as_deref(), &self.oauth_token_path).await?;
                         // Save new token
                         let mut file = std::fs::File::create(&self.oauth_token_path)?;
                         serde_json::to_writer(&mut file, &new_token)?;
@@ -340,22 +504,31 @@ impl SpotifyApi {
                         self.client = client_builder.build()?;
                         tokio::time::sleep(Duration::from_secs(1)).await;
                         // Rebuild the request with the new client and retry
-                        let new_endpoint = Self::build_endpoint(path);
-                        request = match method {
-                            HttpMethod::Get(p) => self.client.get(new_endpoint).query(p),
-                            HttpMethod::Post(b) => self.client.post(new_endpoint).json(b),
-                            HttpMethod::Put(b) => self.client.put(new_endpoint).json(b),
-                            HttpMethod::Delete(b) => self.client.delete(new_endpoint).json(b),
-                        };
-                        request = request.query(&[("limit", limit), ("offset", offset)]);
+                        request = self.build_request(path, method, limit, offset);
                         continue;
                     }
+                    if status == StatusCode::FORBIDDEN {
+                        let text = res.text().await?;
+                        if text.contains("Insufficient client scope") {
+                            return Err(eyre!(
+                                "The Spotify token lacks a permission needed by {}, run SyncDisBoi once with \
+                                `spotify --clear-cache` to authorize it again",
+                                path
+                            ));
+                        }
+                        return Err(eyre!("Invalid HTTP status: {}: {}", status, text.trim()));
+                    }
                     let obj = debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
-                    if status != StatusCode::OK && status != StatusCode::CREATED {
+                    if status != StatusCode::OK
+                        && status != StatusCode::CREATED
+                        && status != StatusCode::NO_CONTENT
+                    {
                         return Err(eyre!("Invalid HTTP status: {}", status));
                     }
                     return Ok(obj);
                 }
+                // the requests still rate limited after the retries are not sent again
+                Err(err) if err.downcast_ref::<reqwest::Error>().is_none() => return Err(err),
                 Err(err) => {
                     if attempt == retries {
                         return Err(eyre!("Request failed after {} attempts: {}", retries, err));
@@ -368,6 +541,63 @@ impl SpotifyApi {
 
         Err(eyre!("Request failed after {} attempts", retries))
     }
+
+    fn build_request(
+        &self,
+        path: &str,
+        method: &HttpMethod<'_>,
+        limit: usize,
+        offset: usize,
+    ) -> reqwest::RequestBuilder {
+        let endpoint = self.build_endpoint(path);
+        let request = match method {
+            HttpMethod::Get(p) => self.client.get(endpoint).query(p),
+            HttpMethod::Post(b) => self.client.post(endpoint).json(b),
+            HttpMethod::Put(b) => self.client.put(endpoint).json(b),
+            HttpMethod::Delete(b) => self.client.delete(endpoint).json(b),
+        };
+        request.query(&[("limit", limit), ("offset", offset)])
+    }
+
+    /// The first `limit` songs found by a search query that can be played in the user's market.
+    /// The tracks relinked by Spotify are returned with the id of their playable version
+    async fn search_query(&mut self, query: &str, limit: usize) -> Result<Vec<Song>> {
+        let market = self.country_code.clone();
+        let mut get_params = vec![("type", "track"), ("q", query)];
+        if !market.is_empty() {
+            get_params.push(("market", market.as_str()));
+        }
+        let mut res: SpotifySearchResponse = self
+            .make_request_json("/search", &HttpMethod::Get(&get_params), limit, 0)
+            .await?;
+        res.tracks.items.retain(is_playable);
+        let res_songs: Songs = res.try_into()?;
+        Ok(res_songs.0)
+    }
+}
+
+/// Songs of `chunk` not liked according to `liked`, the answer of `/me/tracks/contains` in the
+/// same order. Songs without an answer are considered not liked
+fn unliked_songs<'a>(chunk: &[&'a Song], liked: &[bool]) -> Vec<&'a Song> {
+    chunk
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !liked.get(*i).copied().unwrap_or(false))
+        .map(|(_, song)| *song)
+        .collect()
+}
+
+/// Whether a track can be added to a playlist without showing greyed out, the tracks returned
+/// without a market are assumed playable
+fn is_playable(track: &SpotifySongResponse) -> bool {
+    if track.is_playable == Some(false) {
+        debug!("track {:?} unplayable in the market, skipping it", track.id);
+        return false;
+    }
+    if let Some(original) = track.linked_from.as_ref().and_then(|l| l.id.as_deref()) {
+        debug!("track {} relinked to {:?} in the market", original, track.id);
+    }
+    true
 }
 
 pub fn push_query(queries: &mut Vec<String>, query: String, max_len: usize) {
@@ -388,12 +618,26 @@ impl MusicApi for SpotifyApi {
         &self.country_code
     }
 
-    async fn create_playlist(&mut self, name: &str, public: bool) -> Result<Playlist> {
+    fn backoff_time(&self) -> Duration {
+        *self.backoff_time.lock().unwrap()
+    }
+
+    fn clone_handle(&self) -> Option<DynMusicApi> {
+        Some(Box::new(self.clone()))
+    }
+
+    async fn create_playlist(&mut self, name: &str, description: &str, public: bool) -> Result<Playlist> {
         let path = "/me/playlists";
+        // collaborative playlists can't be public
+        let collaborative = self.config.collaborative;
+        if collaborative && public {
+            warn!("collaborative playlist \"{}\" created private", name);
+        }
         let body = json!({
             "name": name,
-            "public": public,
-            "description": PLAYLIST_DESC,
+            "public": public && !collaborative,
+            "collaborative": collaborative,
+            "description": description,
         });
         let res: SpotifyPlaylistResponse = self
             .make_request_json(path, &HttpMethod::Post(&body), 50, 0)
@@ -402,6 +646,61 @@ impl MusicApi for SpotifyApi {
         Ok(playlist)
     }
 
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        let path = format!("/playlists/{}", playlist.id);
+        let mut body = json!({});
+        if let Some(description) = description {
+            body["description"] = json!(description);
+        }
+        if let Some(public) = public {
+            body["public"] = json!(public);
+        }
+        self.make_request_json::<()>(&path, &HttpMethod::Put(&body), 50, 0)
+            .await?;
+        if let Some(description) = description {
+            playlist.description = Some(description.to_string());
+        }
+        playlist.public = public.or(playlist.public);
+        Ok(())
+    }
+
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()> {
+        let path = format!("/playlists/{}", playlist.id);
+        let body = json!({
+            "name": name,
+        });
+        self.make_request_json::<()>(&path, &HttpMethod::Put(&body), 50, 0)
+            .await?;
+        playlist.name = name.to_string();
+        Ok(())
+    }
+
+    fn supports_playlist_cover(&self) -> bool {
+        true
+    }
+
+    async fn set_playlist_cover(&mut self, playlist: &Playlist, cover: &CoverImage) -> Result<()> {
+        // Spotify only accepts base64 JPEG images up to 256 KB
+        let body = BASE64_STANDARD.encode(cover.to_jpeg(Self::MAX_COVER_SIZE)?);
+        let endpoint = self.build_endpoint(&format!("/playlists/{}/images", playlist.id));
+        let res = self
+            .client
+            .put(endpoint)
+            .header("content-type", "image/jpeg")
+            .body(body)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(eyre!("failed to upload the cover: HTTP {}", res.status()));
+        }
+        Ok(())
+    }
+
     async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
         let path = "/me/playlists";
         let res: SpotifyPageResponse<SpotifyPlaylistResponse> = self
@@ -466,8 +765,10 @@ impl MusicApi for SpotifyApi {
             playlist.songs.push(song.clone());
         }
 
+        // local files can only be added from the desktop app
         let uris: Vec<String> = songs
             .iter()
+            .filter(|song| !song.is_local_file())
             .map(|song| format!("spotify:track:{}", song.id))
             .collect();
 
@@ -495,7 +796,12 @@ impl MusicApi for SpotifyApi {
         let uris: Vec<serde_json::Value> = songs
             .iter()
             .map(|song| {
-                let uri = format!("spotify:track:{}", song.id);
+                // the id of a local file is already its uri
+                let uri = if song.is_local_file() {
+                    song.id.clone()
+                } else {
+                    format!("spotify:track:{}", song.id)
+                };
                 json!({ "uri": uri })
             })
             .collect();
@@ -519,7 +825,6 @@ impl MusicApi for SpotifyApi {
     }
 
     async fn search_song(&mut self, song: &Song) -> Result<Option<Song>> {
-        let path = "/search";
         let max_len = 100;
         let mut queries = vec![];
 
@@ -567,35 +872,106 @@ impl MusicApi for SpotifyApi {
                     push_query(&mut queries, tr_ar_al_query, max_len);
                 }
             }
+            // Query: ISRC hint, its results are compared like the others
+            if let Some(isrc) = &song.isrc_hint {
+                queries.push(format!("isrc:{}", isrc));
+            }
         }
 
         while let Some(query) = queries.pop() {
-            let get_params = [("type", "track"), ("q", &query)];
-            let res: SpotifySearchResponse = self
-                .make_request_json(path, &HttpMethod::Get(&get_params), 3, 0)
-                .await?;
-            let res_songs: Songs = res.try_into()?;
-            // iterate over top 3 results
-            for res_song in res_songs.0.into_iter().take(3) {
-                if song.compare(&res_song) {
-                    return Ok(Some(res_song));
-                }
+            let res_songs = self.search_query(&query, 5).await?;
+            if let Some(res_song) = song.best_match(res_songs, &self.config.match_options_over(&self.default_match_options())) {
+                return Ok(Some(res_song));
             }
         }
         return Ok(None);
     }
 
-    async fn add_likes(&mut self, songs: &[Song]) -> Result<()> {
+    async fn search_candidates(&mut self, song: &Song, limit: usize) -> Result<Vec<Song>> {
+        let max_len = 100;
+        let mut candidates = vec![];
+        // free text queries, the field filters of search_song already found nothing
+        let mut queries = song.build_queries();
+        queries.retain(|q| q.len() <= max_len);
+        while candidates.len() < limit
+            && let Some(query) = queries.pop()
+        {
+            let res_songs = self.search_query(&query, limit).await?;
+            push_candidates(&mut candidates, res_songs, limit);
+        }
+        Ok(candidates)
+    }
+
+    fn supports_song_lookup(&self) -> bool {
+        true
+    }
+
+    async fn lookup_songs(&mut self, songs: &[Song]) -> Result<Vec<Option<Song>>> {
+        let mut res_songs = vec![];
+        // NOTE: A maximum of 50 ids can be specified in one request
+        for songs_chunk in songs.chunks(50) {
+            let ids = songs_chunk
+                .iter()
+                .map(|s| s.id.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+            // with a market, relinked tracks are returned with their current id
+            let market = self.country_code.clone();
+            let get_params = [("ids", ids.as_str()), ("market", market.as_str())];
+            let res: SpotifyTracksResponse = self
+                .make_request_json("/tracks", &HttpMethod::Get(&get_params), 50, 0)
+                .await?;
+            for track in res.tracks {
+                let song = match track.filter(is_playable).map(TryInto::<Song>::try_into).transpose() {
+                    Ok(song) => song.filter(|s| !s.id.is_empty()),
+                    Err(e) => {
+                        warn!("failed to parse song in response: {}", e);
+                        None
+                    }
+                };
+                res_songs.push(song);
+            }
+        }
+        Ok(res_songs)
+    }
+
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
+        // local files can't be liked through the API
+        let songs: Vec<&Song> = songs.iter().filter(|s| !s.is_local_file()).collect();
+        let mut not_added = vec![];
+        let mut already_liked = 0;
         // NOTE: A maximum of 50 items can be specified in one request
         for songs_chunk in songs.chunks(50) {
-            let ids: Vec<&str> = songs_chunk.iter().map(|s| s.id.as_str()).collect();
+            // the songs liked since the likes were retrieved aren't liked again
+            let ids = songs_chunk.iter().map(|s| s.id.as_str()).collect::<Vec<_>>().join(",");
+            let get_params = [("ids", ids.as_str())];
+            let liked: Vec<bool> = self
+                .make_request_json("/me/tracks/contains", &HttpMethod::Get(&get_params), 50, 0)
+                .await?;
+            let unliked = unliked_songs(songs_chunk, &liked);
+            already_liked += songs_chunk.len() - unliked.len();
+            if unliked.is_empty() {
+                continue;
+            }
+
+            let ids: Vec<&str> = unliked.iter().map(|s| s.id.as_str()).collect();
             let body = json!({
                 "ids": ids,
             });
-            self.make_request_json::<()>("/me/tracks", &HttpMethod::Put(&body), 50, 0)
-                .await?;
+            // rate limits and server errors are already retried, a chunk still failing doesn't
+            // prevent the next ones from being liked
+            if let Err(e) = self
+                .make_request_json::<()>("/me/tracks", &HttpMethod::Put(&body), 50, 0)
+                .await
+            {
+                warn!("failed to like {} songs: {}", unliked.len(), e);
+                not_added.extend(unliked.into_iter().cloned());
+            }
         }
-        Ok(())
+        if already_liked > 0 {
+            info!("{} songs were already liked on Spotify, skipped them", already_liked);
+        }
+        Ok(not_added)
     }
 
     async fn get_likes(&mut self) -> Result<Vec<Song>> {
@@ -605,6 +981,121 @@ impl MusicApi for SpotifyApi {
         let songs: Songs = res.try_into()?;
         Ok(songs.0)
     }
+
+    fn supports_like_removal(&self) -> bool {
+        true
+    }
+
+    async fn remove_likes(&mut self, songs: &[Song]) -> Result<()> {
+        let songs: Vec<&Song> = songs.iter().filter(|s| !s.is_local_file()).collect();
+        // NOTE: A maximum of 50 items can be specified in one request
+        for songs_chunk in songs.chunks(50) {
+            let ids: Vec<&str> = songs_chunk.iter().map(|s| s.id.as_str()).collect();
+            let body = json!({
+                "ids": ids,
+            });
+            self.make_request_json::<()>("/me/tracks", &HttpMethod::Delete(&body), 50, 0)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn supports_saved_albums(&self) -> bool {
+        true
+    }
+
+    async fn get_saved_albums(&mut self) -> Result<Vec<SavedAlbum>> {
+        let res: SpotifyPageResponse<SpotifySavedAlbumItemResponse> = self
+            .paginated_request("/me/albums", HttpMethod::Get(&[]), 50)
+            .await?;
+        res.items.into_iter().map(|i| i.album.try_into()).collect()
+    }
+
+    async fn add_saved_albums(&mut self, albums: &[SavedAlbum]) -> Result<()> {
+        // NOTE: A maximum of 20 ids can be specified in one request
+        for albums_chunk in albums.chunks(20) {
+            let ids: Vec<&str> = albums_chunk
+                .iter()
+                .filter_map(|a| a.album.id.as_deref())
+                .collect();
+            let body = json!({
+                "ids": ids,
+            });
+            self.make_request_json::<()>("/me/albums", &HttpMethod::Put(&body), 20, 0)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn search_album(&mut self, album: &SavedAlbum) -> Result<Option<SavedAlbum>> {
+        // search results don't include the UPC, a result of a UPC search is the release itself
+        if let Some(upc) = &album.upc {
+            let query = format!("upc:{:0>12}", upc);
+            let get_params = [("type", "album"), ("q", query.as_str())];
+            let res: SpotifyAlbumSearchResponse = self
+                .make_request_json("/search", &HttpMethod::Get(&get_params), 1, 0)
+                .await?;
+            if let Some(found) = res.albums.items.into_iter().next() {
+                return Ok(Some(found.try_into()?));
+            }
+        }
+
+        let query = match album.artists.first() {
+            Some(artist) => format!("album:{} artist:{}", album.album.name, artist.name),
+            None => format!("album:{}", album.album.name),
+        };
+        let get_params = [("type", "album"), ("q", query.as_str())];
+        let res: SpotifyAlbumSearchResponse = self
+            .make_request_json("/search", &HttpMethod::Get(&get_params), 5, 0)
+            .await?;
+        let results = res
+            .albums
+            .items
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<SavedAlbum>>>()?;
+        Ok(album.best_match(results))
+    }
+
+    fn supports_followed_artists(&self) -> bool {
+        true
+    }
+
+    async fn get_followed_artists(&mut self) -> Result<Vec<Artist>> {
+        let artists = self
+            .cursor_paginated_request(
+                "/me/following",
+                &[("type", "artist")],
+                50,
+                |res: SpotifyFollowedArtistsResponse| res.artists,
+            )
+            .await?;
+        Ok(into_artists(artists))
+    }
+
+    async fn follow_artists(&mut self, artists: &[Artist]) -> Result<()> {
+        // NOTE: A maximum of 50 ids can be specified in one request
+        for artists_chunk in artists.chunks(50) {
+            let ids: Vec<&str> = artists_chunk.iter().filter_map(|a| a.id.as_deref()).collect();
+            let body = json!({
+                "ids": ids,
+            });
+            self.make_request_json::<()>("/me/following?type=artist", &HttpMethod::Put(&body), 50, 0)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn search_artist(&mut self, artist: &Artist) -> Result<Option<Artist>> {
+        let query = format!("artist:{}", artist.name);
+        let get_params = [("type", "artist"), ("q", query.as_str())];
+        let res: SpotifyArtistSearchResponse = self
+            .make_request_json("/search", &HttpMethod::Get(&get_params), 5, 0)
+            .await?;
+        Ok(into_artists(res.artists.items)
+            .into_iter()
+            .find(|a| a.is_same(artist)))
+    }
 }
 
 #[cfg(test)]
@@ -613,6 +1104,388 @@ mod tests {
 
     // use super::*;
     // use crate::yt_music::YtMusicApi;
+    use clap::Parser;
+
+    use super::*;
+    use super::model::{SpotifyPlaylistResponse, SpotifySavedAlbumItemResponse};
+    use crate::mock_api::{playlist, serve_json, serve_json_headers, song};
+    use crate::music_api::{Playlist, SavedAlbum};
+
+    fn mocked_api(api_url: String, args: &[&str]) -> SpotifyApi {
+        SpotifyApi {
+            client: reqwest::Client::new(),
+            config: ConfigArgs::parse_from([&["sync_dis_boi"], args].concat()),
+            country_code: "US".to_string(),
+            client_id: "id".to_string(),
+            client_secret: Some("secret".to_string()),
+            oauth_token_path: PathBuf::new(),
+            api_url,
+            backoff_time: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    #[test]
+    fn test_generated_playlists() {
+        let parse = |owner: &str, name: &str| -> Playlist {
+            let json = format!(
+                r#"{{"id": "37i9dQZF1", "name": "{}", "description": "Tom &amp; Jerry&#x27;s", "public": true, "owner": {{"id": "{}"}}}}"#,
+                name, owner
+            );
+            let res: SpotifyPlaylistResponse = serde_json::from_str(&json).unwrap();
+            res.try_into().unwrap()
+        };
+        let discover = parse("spotify", "Discover Weekly");
+        assert!(discover.generated);
+        assert_eq!(discover.description.as_deref(), Some("Tom & Jerry's"));
+        // a user playlist named like a generated one
+        assert!(!parse("jane", "High Energy Mix").generated);
+
+        let json = r#"{"id": "5Ebx", "name": "Road trip", "description": "", "public": false, "collaborative": true, "snapshot_id": "AAAAB3kz",
+            "owner": {"id": "31l77y", "display_name": "Jane", "type": "user"}}"#;
+        let res: SpotifyPlaylistResponse = serde_json::from_str(json).unwrap();
+        let shared: Playlist = res.try_into().unwrap();
+        assert!(shared.collaborative);
+        assert_eq!(shared.snapshot_id.as_deref(), Some("AAAAB3kz"));
+        assert_eq!((shared.owner.as_deref(), shared.owner_name.as_deref()), (Some("31l77y"), Some("Jane")));
+    }
+
+    #[test]
+    fn test_saved_album() {
+        let json = r#"{"added_at": "2024-01-01T00:00:00Z", "album": {"id": "3XYZ", "name": "Homogenic",
+            "artists": [{"id": "7w29", "name": "Björk", "type": "artist"}],
+            "external_ids": {"upc": "0075992248722"}}}"#;
+        let res: SpotifySavedAlbumItemResponse = serde_json::from_str(json).unwrap();
+        let album: SavedAlbum = res.album.try_into().unwrap();
+        assert_eq!(album.album.id.as_deref(), Some("3XYZ"));
+        assert_eq!(album.artists[0].name, "Björk");
+        assert_eq!(album.upc.as_deref(), Some("75992248722"));
+    }
+
+    #[test]
+    fn test_token_forms() {
+        let pkce = PkceChallenge::from_verifier("verifier".to_string());
+        let url = SpotifyApi::build_authorization_url("id", "0.0.0.0", "8888", "", "st4te", Some(&pkce)).unwrap();
+        let params: HashMap<String, String> = reqwest::Url::parse(&url).unwrap().query_pairs().into_owned().collect();
+        assert_eq!(params["redirect_uri"], "http://localhost:8888/callback");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["code_challenge"], pkce.challenge);
+        assert_eq!(params["state"], "st4te");
+        let url = SpotifyApi::build_authorization_url("id", "0.0.0.0", "8888", "", "st4te", None).unwrap();
+        assert!(!url.contains("code_challenge"), "{}", url);
+
+        let form = |params: Vec<(&str, String)>| {
+            let url = reqwest::Url::parse_with_params("http://localhost/", params).unwrap();
+            url.query().unwrap().to_string()
+        };
+        // PKCE, the verifier and client id instead of the secret
+        assert_eq!(
+            form(SpotifyApi::code_token_form("id", "code", "http://localhost:8888/callback", Some(&pkce))),
+            "grant_type=authorization_code&code=code&redirect_uri=http%3A%2F%2Flocalhost%3A8888%2Fcallback\
+            &client_id=id&code_verifier=verifier"
+        );
+        assert_eq!(
+            form(SpotifyApi::code_token_form("id", "code", "http://localhost:8888/callback", None)),
+            "grant_type=authorization_code&code=code&redirect_uri=http%3A%2F%2Flocalhost%3A8888%2Fcallback"
+        );
+        assert_eq!(
+            form(SpotifyApi::refresh_token_form("id", None, "refresh")),
+            "client_id=id&grant_type=refresh_token&refresh_token=refresh"
+        );
+        assert_eq!(
+            form(SpotifyApi::refresh_token_form("id", Some("secret"), "refresh")),
+            "client_id=id&client_secret=secret&grant_type=refresh_token&refresh_token=refresh"
+        );
+
+        // tokens saved before PKCE, and refreshed tokens without a new refresh token
+        let token: OAuthToken = serde_json::from_str(
+            r#"{"scope": "user-library-read", "token_type": "Bearer", "access_token": "a", "refresh_token": "r", "expires_in": 3600}"#,
+        )
+        .unwrap();
+        assert_eq!(token.refresh_token, "r");
+        let refreshed: OAuthRefreshToken = serde_json::from_str(
+            r#"{"access_token": "b", "expires_in": 3600, "scope": "user-library-read", "token_type": "Bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(refreshed.refresh_token, None);
+    }
+
+    const LOCAL_FILE_PAGE: &str = r#"{"total": 2, "next": null, "items": [
+        {"is_local": false, "track": {"id": "4uLU", "uri": "spotify:track:4uLU", "name": "Hyperballad",
+            "duration_ms": 321000, "explicit": false, "is_local": false,
+            "artists": [{"id": "7w29", "name": "Björk", "type": "artist"}],
+            "album": {"id": "3XYZ", "name": "Post", "release_date": "1995-06-13"},
+            "external_ids": {"isrc": "GBAAN9500013"}}},
+        {"is_local": true, "track": {"id": null, "uri": "spotify:local:Burial:Untrue:Archangel:239",
+            "name": "Archangel", "duration_ms": 239000, "explicit": false, "is_local": true,
+            "artists": [{"id": null, "name": "Burial", "type": "artist", "uri": null}],
+            "album": {"id": null, "name": "Untrue", "release_date": null},
+            "external_ids": {}}}
+    ]}"#;
+
+    #[tokio::test]
+    async fn test_local_files() {
+        let (url, requests) = serve_json(vec![(200, LOCAL_FILE_PAGE), (200, r#"{"snapshot_id": "s1"}"#)]).await;
+        let mut api = mocked_api(url, &[]);
+        let songs = api.get_playlist_songs("p1").await.unwrap();
+        assert_eq!(songs.len(), 2);
+        assert!(!songs[0].is_local_file());
+        let local = &songs[1];
+        assert!(local.is_local_file());
+        assert_eq!(local.id, "spotify:local:Burial:Untrue:Archangel:239");
+        assert_eq!((local.name.as_str(), local.duration_ms), ("Archangel", 239000));
+        assert_eq!(local.artists[0].name, "Burial");
+        assert_eq!(local.album.as_ref().unwrap().name, "Untrue");
+        assert_eq!(local.isrc, None);
+        assert_eq!(local.url(), None);
+
+        // removed by their uri, never added by id
+        let mut playlist = playlist("p1", "Night", songs.clone());
+        api.remove_songs_from_playlist(&mut playlist, &songs).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[1].1).unwrap();
+        assert_eq!(body["tracks"][0]["uri"], "spotify:track:4uLU");
+        assert_eq!(body["tracks"][1]["uri"], "spotify:local:Burial:Untrue:Archangel:239");
+
+        let (url, requests) = serve_json(vec![(201, r#"{"snapshot_id": "s2"}"#)]).await;
+        let mut api = mocked_api(url, &[]);
+        api.add_songs_to_playlist(&mut playlist, &songs).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&requests.lock().unwrap()[0].1).unwrap();
+        assert_eq!(body["uris"], serde_json::json!(["spotify:track:4uLU"]));
+    }
+
+    #[tokio::test]
+    async fn test_library_paging() {
+        let album = |id: &str, name: &str| {
+            format!(
+                r#"{{"added_at": "2024-01-01T00:00:00Z", "album": {{"id": "{}", "name": "{}", "release_date": "1997",
+                "artists": [{{"id": "7w29", "name": "Björk", "type": "artist"}}], "external_ids": {{"upc": "0075992248722"}}}}}}"#,
+                id, name
+            )
+        };
+        let first = format!(
+            r#"{{"total": 2, "next": "https://api.spotify.com/v1/me/albums?offset=1", "items": [{}]}}"#,
+            album("3XYZ", "Homogenic")
+        );
+        let second = format!(r#"{{"total": 2, "next": null, "items": [{}]}}"#, album("1ABC", "Post"));
+        let (url, requests) = serve_json(vec![(200, &first), (200, &second)]).await;
+        let mut api = mocked_api(url, &[]);
+        let albums = api.get_saved_albums().await.unwrap();
+        let names: Vec<&str> = albums.iter().map(|a| a.album.name.as_str()).collect();
+        assert_eq!(names, ["Homogenic", "Post"]);
+        assert_eq!(albums[1].upc.as_deref(), Some("75992248722"));
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(lines, ["GET /me/albums?limit=50&offset=0", "GET /me/albums?limit=50&offset=1"]);
+
+        // followed artists are paged with the cursor of the last artist
+        let artist = |id: &str, name: &str| format!(r#"{{"id": "{}", "name": "{}", "type": "artist"}}"#, id, name);
+        let first = format!(
+            r#"{{"artists": {{"items": [{}, {}], "next": "https://api.spotify.com/v1/me/following?type=artist&after=0k17", "cursors": {{"after": "0k17"}}, "total": 3}}}}"#,
+            artist("7w29", "Björk"),
+            artist("0k17", "Burial")
+        );
+        let second = format!(
+            r#"{{"artists": {{"items": [{}], "next": null, "cursors": {{"after": null}}, "total": 3}}}}"#,
+            artist("4Z8W", "Radiohead")
+        );
+        let (url, requests) = serve_json(vec![(200, &first), (200, &second)]).await;
+        let mut api = mocked_api(url, &[]);
+        let artists = api.get_followed_artists().await.unwrap();
+        let names: Vec<&str> = artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["Björk", "Burial", "Radiohead"]);
+        let lines: Vec<String> = requests.lock().unwrap().iter().map(|r| r.0.clone()).collect();
+        assert_eq!(
+            lines,
+            [
+                "GET /me/following?type=artist&limit=50&offset=0",
+                "GET /me/following?type=artist&after=0k17&limit=50&offset=0"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_market_search() {
+        let track = |id: &str, album: &str, extra: &str| {
+            format!(
+                r#"{{"id": "{}", "name": "Hyperballad", "duration_ms": 321000, "explicit": false, {}
+                "artists": [{{"id": "7w29", "name": "Björk", "type": "artist"}}],
+                "album": {{"id": "3XYZ", "name": "{}", "release_date": "1995-06-13"}},
+                "external_ids": {{"isrc": "GBAAN9500013"}}}}"#,
+                id, extra, album
+            )
+        };
+        // the ISRC match of the album is unplayable, its relinked compilation version isn't
+        let search = format!(
+            r#"{{"tracks": {{"total": 2, "next": null, "items": [{}, {}]}}}}"#,
+            track("4uLU", "Post", r#""is_playable": false,"#),
+            track("0dEI", "Greatest Hits", r#""is_playable": true, "linked_from": {"id": "4uLU", "type": "track"},"#)
+        );
+        let (url, requests) = serve_json(vec![(200, &search)]).await;
+        let mut api = mocked_api(url, &[]);
+        let mut src = song(MusicApiType::YtMusic, "yt1", "Hyperballad");
+        src.artists = vec![Artist { id: None, name: "Björk".to_string() }];
+        src.duration_ms = 321000;
+        src.isrc = Some("GBAAN9500013".to_string());
+        let found = api.search_song(&src).await.unwrap().unwrap();
+        assert_eq!(found.id, "0dEI");
+        assert_eq!(
+            requests.lock().unwrap()[0].0,
+            "GET /search?type=track&q=isrc%3AGBAAN9500013&market=US&limit=5&offset=0"
+        );
+
+        // nothing playable, the song is missing rather than greyed out in the playlist
+        let search = format!(
+            r#"{{"tracks": {{"total": 1, "next": null, "items": [{}]}}}}"#,
+            track("4uLU", "Post", r#""is_playable": false,"#)
+        );
+        let (url, _) = serve_json(vec![(200, &search)]).await;
+        let mut api = mocked_api(url, &[]);
+        assert!(api.search_song(&src).await.unwrap().is_none());
+
+        // looked up ids unplayable in the market are gone
+        let tracks = format!(
+            r#"{{"tracks": [{}, null, {}]}}"#,
+            track("4uLU", "Post", r#""is_playable": false,"#),
+            track("0dEI", "Post", r#""is_playable": true, "linked_from": {"id": "5oZV", "type": "track"},"#)
+        );
+        let (url, requests) = serve_json(vec![(200, &tracks)]).await;
+        let mut api = mocked_api(url, &[]);
+        let songs = ["4uLU", "gone", "5oZV"].map(|id| song(MusicApiType::Spotify, id, "Hyperballad"));
+        let found = api.lookup_songs(&songs).await.unwrap();
+        assert!(found[0].is_none() && found[1].is_none());
+        assert_eq!(found[2].as_ref().unwrap().id, "0dEI");
+        assert!(requests.lock().unwrap()[0].0.contains("market=US"));
+    }
+
+    #[tokio::test]
+    async fn test_add_likes() {
+        let songs: Vec<Song> = (0..120)
+            .map(|i| song(MusicApiType::Spotify, &format!("t{}", i), &format!("Song {}", i)))
+            .collect();
+        let refs: Vec<&Song> = songs.iter().take(4).collect();
+        let ids = |songs: Vec<&Song>| songs.iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(unliked_songs(&refs, &[true, false, true, false])), ["t1", "t3"]);
+        assert_eq!(ids(unliked_songs(&refs, &[true])), ["t1", "t2", "t3"]);
+
+        // 50 already liked, then half of the next 50, the last 20 fail to be liked
+        let liked = |flags: Vec<bool>| serde_json::to_string(&flags).unwrap();
+        let first = liked(vec![true; 50]);
+        let second = liked((0..50).map(|i| i % 2 == 0).collect());
+        let third = liked(vec![false; 20]);
+        let (url, requests) = serve_json(vec![
+            (200, &first),
+            (200, &second),
+            (200, ""),
+            (200, &third),
+            (400, r#"{"error": {"status": 400, "message": "Invalid base62 id"}}"#),
+        ])
+        .await;
+        let mut api = mocked_api(url, &[]);
+        let not_added = api.add_likes(&songs).await.unwrap();
+        assert_eq!(not_added.len(), 20);
+        assert_eq!(not_added[0].id, "t100");
+
+        let requests = requests.lock().unwrap();
+        let lines: Vec<&str> = requests.iter().map(|r| r.0.split('?').next().unwrap()).collect();
+        assert_eq!(
+            lines,
+            ["GET /me/tracks/contains", "GET /me/tracks/contains", "PUT /me/tracks", "GET /me/tracks/contains", "PUT /me/tracks"]
+        );
+        assert!(requests[0].0.contains("ids=t0%2Ct1%2C"));
+        let body: serde_json::Value = serde_json::from_str(&requests[2].1).unwrap();
+        let put: Vec<&str> = body["ids"].as_array().unwrap().iter().map(|id| id.as_str().unwrap()).collect();
+        assert_eq!(put.len(), 25);
+        assert_eq!(put[..2], ["t51", "t53"]);
+    }
+
+    #[tokio::test]
+    async fn test_insufficient_scope() {
+        assert_eq!(SpotifyApi::missing_scopes(&SpotifyApi::SCOPES.join(" ")), Vec::<&str>::new());
+        assert_eq!(
+            SpotifyApi::missing_scopes("user-library-read playlist-read-private user-read-email user-read-private \
+                user-follow-read playlist-read-collaborative playlist-modify-public playlist-modify-private"),
+            ["user-library-modify", "user-follow-modify", "ugc-image-upload"]
+        );
+
+        let (url, _) = serve_json(vec![(403, r#"{"error": {"status": 403, "message": "Insufficient client scope"}}"#)]).await;
+        let mut api = mocked_api(url, &[]);
+        let artists = [Artist { id: Some("7w29".to_string()), name: "Björk".to_string() }];
+        let err = api.follow_artists(&artists).await.unwrap_err();
+        assert!(err.to_string().contains("spotify --clear-cache"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_callback() {
+        let parse = |url: &str| SpotifyApi::parse_callback(url, "st4te");
+        assert_eq!(parse("/callback?code=AQD1&state=st4te").unwrap(), "AQD1");
+        // pasted with --callback-manual
+        assert_eq!(parse(" http://localhost:8888/callback?code=AQD1&state=st4te\n").unwrap(), "AQD1");
+        let err = parse("http://localhost:8888/callback?code=AQD1&state=other").unwrap_err();
+        assert!(err.to_string().contains("state"), "{}", err);
+        assert!(parse("http://localhost:8888/callback?code=AQD1").is_err());
+        let err = parse("http://localhost:8888/callback?error=access_denied&state=st4te").unwrap_err();
+        assert!(err.to_string().contains("access_denied"), "{}", err);
+        assert!(parse("http://localhost:8888/callback?state=st4te").is_err());
+        assert!(parse("not a url").is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let browser = tokio::spawn(async move {
+            let mut socket = tokio::net::TcpStream::connect(address).await.unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut socket, b"GET /callback?code=AQD1&state=st4te HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut res = String::new();
+            tokio::io::AsyncReadExt::read_to_string(&mut socket, &mut res).await.unwrap();
+            res
+        });
+        let code = SpotifyApi::receive_callback(&listener, "st4te", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(code, "AQD1");
+        assert!(browser.await.unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_callback_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let err = SpotifyApi::receive_callback(&listener, "st4te", Duration::from_secs(300))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("after 300 seconds"), "{}", err);
+        assert!(err.to_string().contains("--callback-manual"), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_retries() {
+        let (url, requests) = serve_json_headers(vec![
+            (429, "Retry-After: 7", ""),
+            (429, "Retry-After: 86400", ""),
+            (503, "", ""),
+            (200, "", r#"{"id": "jane", "country": "FR"}"#),
+        ])
+        .await;
+        let mut api = mocked_api(url, &[]);
+        let start = tokio::time::Instant::now();
+        let res: serde_json::Value = api
+            .make_request_json("/me", &HttpMethod::Get(&[]), 50, 0)
+            .await
+            .unwrap();
+        assert_eq!(res["country"], "FR");
+        assert_eq!(requests.lock().unwrap().len(), 4);
+        assert_eq!(requests.lock().unwrap()[3].0, "GET /me?limit=50&offset=0");
+        // the asked 7 seconds, a day capped at 5 minutes, then the backoff of the third retry
+        let waited = Duration::from_secs(7) + Duration::from_mins(5) + Duration::from_secs(4);
+        assert_eq!(api.backoff_time(), waited);
+        assert!(start.elapsed() >= waited);
+
+        // the uniform options apply, the request is given up
+        let (url, requests) = serve_json_headers(vec![(429, "Retry-After: 1", ""); 3]).await;
+        let mut api = mocked_api(url, &["--max-retries", "1", "--max-backoff-secs", "10"]);
+        let err = api
+            .make_request_json::<serde_json::Value>("/me", &HttpMethod::Get(&[]), 50, 0)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("after 2 attempts"), "{}", err);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert_eq!(api.backoff_time(), Duration::from_secs(1));
+    }
 
     #[tokio::test]
     async fn test_spotify_search_from_ytmusic() {
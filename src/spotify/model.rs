@@ -22,6 +22,41 @@ pub struct SpotifySearchResponse {
     pub tracks: SpotifyPageResponse<SpotifySongResponse>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct SpotifyAlbumSearchResponse {
+    pub albums: SpotifyPageResponse<SpotifyFullAlbumResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyArtistSearchResponse {
+    pub artists: SpotifyPageResponse<SpotifyArtistResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyFollowedArtistsResponse {
+    pub artists: SpotifyCursorPageResponse<SpotifyArtistResponse>,
+}
+
+/// Followed artists are paginated with a cursor instead of an offset
+#[derive(Deserialize, Debug)]
+pub struct SpotifyCursorPageResponse<T: for<'d> Deserialize<'d>> {
+    #[serde(deserialize_with = "deserialize_non_null_vec")]
+    pub items: Vec<T>,
+    pub next: Option<String>,
+    pub cursors: Option<SpotifyCursorsResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyCursorsResponse {
+    pub after: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyTracksResponse {
+    // unknown ids are null
+    pub tracks: Vec<Option<SpotifySongResponse>>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct SpotifyPageResponse<T: for<'d> Deserialize<'d>> {
     #[serde(deserialize_with = "deserialize_non_null_vec")]
@@ -51,14 +86,30 @@ where
 pub struct SpotifyPlaylistResponse {
     pub id: String,
     pub name: String,
-    #[allow(dead_code)]
+    // html escaped, empty when there is none
+    pub description: Option<String>,
     pub public: Option<bool>,
-    pub owner: Owner
+    #[serde(default)]
+    pub collaborative: bool,
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    pub owner: Owner,
+    /// Widest first, null for playlists without songs nor uploaded cover
+    #[serde(default)]
+    pub images: Option<Vec<SpotifyImageResponse>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyImageResponse {
+    pub url: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Owner {
-    pub id: String
+    pub id: String,
+    // not unique, null for some users
+    #[serde(default)]
+    pub display_name: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -70,11 +121,31 @@ pub struct SpotifySongItemResponse {
 pub struct SpotifySongResponse {
     // id can be null if the song is a local import
     pub id: Option<String>,
+    /// `spotify:local:{artist}:{album}:{title}:{seconds}` for the local files
+    #[serde(default)]
+    pub uri: Option<String>,
+    /// Local file added to a playlist from the desktop app, only known by its metadata
+    #[serde(default)]
+    pub is_local: bool,
     pub name: String,
     pub duration_ms: usize,
     pub artists: Vec<SpotifyArtistResponse>,
     pub album: SpotifyAlbumResponse,
+    // empty or missing for the local files
+    #[serde(default)]
     pub external_ids: SpotifyExternalIdsResponse,
+    pub explicit: Option<bool>,
+    /// Only returned when a market is given, false for the tracks that can't be played there
+    #[serde(default)]
+    pub is_playable: Option<bool>,
+    /// Original track of a track relinked to the version playable in the market
+    #[serde(default)]
+    pub linked_from: Option<SpotifyLinkedFromResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyLinkedFromResponse {
+    pub id: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -90,12 +161,28 @@ pub struct SpotifyAlbumResponse {
     // id can be null if the song is a local import
     pub id: Option<String>,
     pub name: String,
+    // "YYYY", "YYYY-MM" or "YYYY-MM-DD" depending on the precision
+    pub release_date: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
+pub struct SpotifySavedAlbumItemResponse {
+    pub album: SpotifyFullAlbumResponse,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpotifyFullAlbumResponse {
+    pub id: String,
+    pub name: String,
+    pub artists: Vec<SpotifyArtistResponse>,
+    pub release_date: Option<String>,
+    // only returned with full album objects, not in search results
+    pub external_ids: Option<SpotifyExternalIdsResponse>,
+}
+
+#[derive(Deserialize, Debug, Default)]
 pub struct SpotifyExternalIdsResponse {
     // isrc can be null if the song is now deleted/unavailable
     pub isrc: Option<String>,
-    #[allow(dead_code)]
     pub upc: Option<String>,
 }
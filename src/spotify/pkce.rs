@@ -0,0 +1,77 @@
+//! Authorization Code with PKCE (RFC 7636), used when no client secret is given. The code
+//! verifier generated for the authorization proves the token request comes from the same client
+
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use color_eyre::eyre::{Result, eyre};
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Characters allowed in a code verifier
+const VERIFIER_CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+/// Length of the generated code verifiers, between the 43 and 128 characters allowed
+const VERIFIER_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PkceChallenge {
+    /// Sent with the token request
+    pub verifier: String,
+    /// Sent with the authorization request, the base64url SHA-256 of `verifier`
+    pub challenge: String,
+}
+
+/// Random string of `len` characters allowed in a code verifier, also used as the `state` of
+/// the authorization requests
+pub fn random_string(len: usize) -> Result<String> {
+    // the bytes from the largest multiple of the 66 characters are dropped, so that every
+    // character is as likely
+    let limit = 256 - 256 % VERIFIER_CHARSET.len();
+    let rng = SystemRandom::new();
+    let mut string = String::with_capacity(len);
+    let mut bytes = [0u8; 64];
+    while string.len() < len {
+        rng.fill(&mut bytes)
+            .map_err(|_| eyre!("Failed to generate a random string"))?;
+        string.extend(
+            bytes
+                .iter()
+                .map(|b| usize::from(*b))
+                .filter(|b| *b < limit)
+                .map(|b| char::from(VERIFIER_CHARSET[b % VERIFIER_CHARSET.len()]))
+                .take(len - string.len()),
+        );
+    }
+    Ok(string)
+}
+
+impl PkceChallenge {
+    /// Challenge of a new random verifier
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_verifier(random_string(VERIFIER_LEN)?))
+    }
+
+    pub fn from_verifier(verifier: String) -> Self {
+        let challenge = BASE64_URL_SAFE_NO_PAD.encode(digest(&SHA256, verifier.as_bytes()));
+        Self { verifier, challenge }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkce_challenge() {
+        // example of RFC 7636, appendix B
+        let pkce = PkceChallenge::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+        assert_eq!(pkce.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+
+        let first = PkceChallenge::new().unwrap();
+        assert_eq!(first.verifier.len(), VERIFIER_LEN);
+        assert!(first.verifier.bytes().all(|c| VERIFIER_CHARSET.contains(&c)));
+        assert_eq!(first.challenge.len(), 43);
+        assert_eq!(PkceChallenge::from_verifier(first.verifier.clone()), first);
+        assert_ne!(PkceChallenge::new().unwrap().verifier, first.verifier);
+        assert_eq!(random_string(200).unwrap().len(), 200);
+    }
+}
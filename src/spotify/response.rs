@@ -5,14 +5,17 @@ use serde::Deserialize;
 use tracing::{debug, error};
 
 use super::model::{
-    SpotifyPageResponse, SpotifyPlaylistResponse, SpotifySearchResponse, SpotifySongItemResponse,
-    SpotifySongResponse,
+    SpotifyArtistResponse, SpotifyFullAlbumResponse, SpotifyPageResponse, SpotifyPlaylistResponse,
+    SpotifySearchResponse, SpotifySongItemResponse, SpotifySongResponse,
 };
 use crate::{
-    music_api::{Album, Artist, MusicApiType, Playlist, Playlists, Song, Songs},
-    utils::clean_isrc,
+    music_api::{Album, Artist, MusicApiType, Playlist, Playlists, SavedAlbum, Song, Songs},
+    utils::{clean_isrc, clean_upc, parse_year},
 };
 
+/// Owner of the playlists generated by Spotify (Discover Weekly, mixes...)
+const SPOTIFY_OWNER: &str = "spotify";
+
 // multiples
 
 impl TryInto<Songs> for SpotifySearchResponse {
@@ -61,8 +64,8 @@ where
                     continue;
                 }
             };
-            // either an invalid or deleted song
-            if song.id.is_empty() || song.duration_ms == 0 || song.isrc.is_none() {
+            // either an invalid or deleted song, local files never have an ISRC
+            if song.id.is_empty() || song.duration_ms == 0 || (song.isrc.is_none() && !song.uploaded) {
                 debug!("song with invalid metadata, skipping it: '{}'", song);
                 continue;
             }
@@ -78,15 +81,39 @@ impl TryInto<Playlist> for SpotifyPlaylistResponse {
     type Error = Error;
 
     fn try_into(self) -> Result<Playlist, Self::Error> {
+        let generated = self.owner.id == SPOTIFY_OWNER;
         Ok(Playlist {
             id: self.id,
             name: self.name.trim().to_string(),
             songs: vec![],
             owner: Some(self.owner.id),
+            owner_name: self.owner.display_name.filter(|n| !n.is_empty()),
+            collaborative: self.collaborative,
+            snapshot_id: self.snapshot_id,
+            folder: None,
+            description: self
+                .description
+                .filter(|d| !d.is_empty())
+                .map(|d| unescape_html(&d)),
+            public: self.public,
+            generated,
+            cover_url: self.images.and_then(|i| i.into_iter().next()).map(|i| i.url),
+            unavailable: None,
         })
     }
 }
 
+/// Playlist descriptions are returned with HTML entities
+fn unescape_html(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
 impl TryInto<Song> for SpotifySongItemResponse {
     type Error = Error;
 
@@ -99,23 +126,19 @@ impl TryInto<Song> for SpotifySongResponse {
     type Error = Error;
 
     fn try_into(self) -> Result<Song, Self::Error> {
+        if self.is_local {
+            return Ok(local_song(self));
+        }
+
         // not a huge fan of this error handling, but it's convenient for generics over
         // SpotifyPageResponse
         let id = self.id.unwrap_or_default();
 
-        let artists = self
-            .artists
-            .into_iter()
-            .filter_map(|i| {
-                Some(Artist {
-                    id: Some(i.id?),
-                    name: i.name.unwrap_or_default(),
-                })
-            })
-            .collect();
+        let artists = into_artists(self.artists);
         let album = Album {
             id: self.album.id,
             name: self.album.name,
+            year: self.album.release_date.as_deref().and_then(parse_year),
         };
 
         let isrc = clean_isrc(self.external_ids.isrc);
@@ -129,6 +152,72 @@ impl TryInto<Song> for SpotifySongResponse {
             album: Some(album),
             artists,
             duration_ms: self.duration_ms,
+            uploaded: false,
+            explicit: self.explicit,
+            alt_names: vec![],
+            mbid: None,
+            isrc_hint: None,
+        })
+    }
+}
+
+/// Local file of a playlist, identified by its `spotify:local:` uri. It has no track id nor
+/// ISRC, only the metadata of the file to search it by
+fn local_song(track: SpotifySongResponse) -> Song {
+    let artists = track
+        .artists
+        .into_iter()
+        .filter_map(|a| a.name.filter(|n| !n.is_empty()))
+        .map(|name| Artist { id: None, name })
+        .collect();
+    let album = Some(track.album.name).filter(|n| !n.is_empty()).map(|name| Album {
+        id: None,
+        name,
+        year: track.album.release_date.as_deref().and_then(parse_year),
+    });
+    Song {
+        source: MusicApiType::Spotify,
+        id: track.uri.unwrap_or_default(),
+        sid: None,
+        isrc: None,
+        name: track.name,
+        album,
+        artists,
+        duration_ms: track.duration_ms,
+        uploaded: true,
+        explicit: None,
+        alt_names: vec![],
+        mbid: None,
+        isrc_hint: None,
+    }
+}
+
+impl TryInto<SavedAlbum> for SpotifyFullAlbumResponse {
+    type Error = Error;
+
+    fn try_into(self) -> Result<SavedAlbum, Self::Error> {
+        Ok(SavedAlbum {
+            source: MusicApiType::Spotify,
+            album: Album {
+                id: Some(self.id),
+                name: self.name,
+                year: self.release_date.as_deref().and_then(parse_year),
+            },
+            artists: into_artists(self.artists),
+            upc: clean_upc(self.external_ids.and_then(|e| e.upc)),
         })
     }
 }
+
+/// Artists with an id, the others are local imports
+pub fn into_artists(artists: Vec<SpotifyArtistResponse>) -> Vec<Artist> {
+    artists
+        .into_iter()
+        .filter_map(|i| {
+            Some(Artist {
+                id: Some(i.id?),
+                name: i.name.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
@@ -1,14 +1,36 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
 use color_eyre::eyre::{Result, eyre};
-use serde_json::json;
-use tokio::time::{sleep, Duration};
+use futures::lock::Mutex;
+use futures::stream::{self, StreamExt};
+use indicatif::ProgressBar;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
-use crate::ConfigArgs;
-use crate::music_api::{DynMusicApi, MusicApiType, Playlist, Song};
-use crate::utils::dedup_songs;
+use crate::{ConfigArgs, SyncMode};
+use crate::checkpoint::Checkpoint;
+use crate::cover::{MAX_COVER_BYTES, download_cover};
+use crate::folders::{FolderMap, folders_to_create};
+use crate::likes::sync_likes;
+use crate::enrich::{IsrcCache, IsrcEnricher};
+use crate::mapping::PlaylistMapping;
+use crate::match_cache::MatchCache;
+use crate::music_api::{
+    Artist, DynMusicApi, MusicApiType, PLAYLIST_DESC, Playlist, SavedAlbum, Song,
+    UNKNOWN_COUNTRY,
+};
+use crate::overrides::Overrides;
+use crate::report::{PlaylistReport, SyncReport};
+use crate::review::{ReviewSkips, review_song};
+use crate::snapshot_cache::{SnapshotAction, SnapshotCache};
+use crate::utils::{dedup_songs, duplicate_songs, progress_bar, utc_date};
 
-// TODO: Parse playlist owner to ignore platform-specific playlists?
-const SKIPPED_PLAYLISTS: [&str; 12] = [
+/// Default `--generated-playlist-names`, platform-generated playlists are detected by their
+/// metadata but these names are skipped when the owner of a playlist is unknown
+pub const GENERATED_PLAYLIST_NAMES: [&str; 12] = [
     // Yt Music specific
     "New playlist",
     "Your Likes",
@@ -29,15 +51,14 @@ pub async fn synchronize(
     mut src_api: DynMusicApi,
     mut dst_api: DynMusicApi,
     config: ConfigArgs,
-    skip_playlists: Vec<String>,
+    filter: PlaylistFilter,
     _src_owner: String,
     dst_owner: String,
 ) -> Result<()> {
+    // platforms whose country can't be read can't be compared
+    let unknown_country = [src_api.country_code(), dst_api.country_code()].contains(&UNKNOWN_COUNTRY);
     if !config.diff_country
-        && src_api.api_type() != MusicApiType::YtMusic
-        && dst_api.api_type() != MusicApiType::YtMusic
-        && src_api.api_type() != MusicApiType::Plex // TODO: Revert once Plex country code is added
-        && dst_api.api_type() != MusicApiType::Plex
+        && !unknown_country
         && src_api.country_code() != dst_api.country_code()
     {
         return Err(eyre!(
@@ -50,342 +71,2784 @@ pub async fn synchronize(
     }
 
     if config.debug {
-        std::fs::create_dir_all("debug")?;
+        std::fs::create_dir_all(config.debug_dir()?)?;
+    }
+
+    if let Some(plan) = &config.apply {
+        return apply_plan(plan, &mut dst_api, &config).await;
     }
 
     info!("retrieving source playlists...");
-    let src_playlists = src_api.get_playlists_full().await?;
+    let mut src_playlists = src_api.get_playlists_info().await?;
+    if config.include_uploads {
+        if let Some(uploads) = src_api.uploads_playlist() {
+            src_playlists.push(uploads);
+        } else {
+            warn!("{:?} has no uploaded songs, ignoring --include-uploads", src_api.api_type());
+        }
+    }
+    // only fetch the songs of the selected playlists
+    let src_playlists = filter.select(src_playlists)?;
+    let snapshots_path = config.config_dir()?.join(SnapshotCache::FILE_NAME);
+    let mut snapshots = SnapshotCache::load(&snapshots_path)?;
+    let (src_playlists, unchanged) =
+        fetch_changed_playlists(&mut src_api, src_playlists, &mut snapshots, &dst_api.api_type(), &dst_owner, &config)
+            .await?;
+    snapshots.save(&snapshots_path)?;
 
-    synchronize_playlists(src_playlists, &mut dst_api, &config, skip_playlists, dst_owner).await?;
+    let src_type = Some(src_api.api_type());
+    let mut report =
+        synchronize_changed_playlists(src_playlists, src_type, &unchanged, &mut dst_api, &config, dst_owner.clone()).await?;
+    if !config.dry_run {
+        // the playlists with songs left to add are synchronized again on the next run
+        let complete = report
+            .playlists
+            .iter()
+            .filter(|p| !p.skipped && p.missing.is_empty() && p.not_added.is_empty());
+        for playlist in complete {
+            snapshots.mark_synced(&src_api.api_type(), &playlist.src_id, &dst_api.api_type(), &dst_owner);
+        }
+        snapshots.save(&snapshots_path)?;
+    }
 
     if config.sync_likes {
-        synchronize_likes(&mut src_api, &mut dst_api).await?;
+        let likes = sync_likes(&mut src_api, &mut dst_api, &config).await?;
+        report.push_likes(likes);
+        if config.debug {
+            report.write_debug_files(&config.debug_dir()?)?;
+        }
+    }
+
+    if config.sync_albums {
+        sync_albums(&mut src_api, &mut dst_api, &config).await?;
+    }
+    if config.sync_artists {
+        sync_artists(&mut src_api, &mut dst_api, &config).await?;
+    }
+
+    report.totals.backoff_secs = (src_api.backoff_time() + dst_api.backoff_time()).as_secs();
+    log_summary(&mut report, &config)?;
+
+    if let Some(threshold) = &config.fail_on_missing {
+        report.check_missing(threshold)?;
+    }
+    Ok(())
+}
+
+/// Save the source saved albums on the destination platform, `--sync-albums`
+///
+/// Albums are matched by UPC when the destination platform can find one, otherwise by name
+/// and artist. Platforms without saved albums are skipped.
+pub async fn sync_albums(src_api: &mut DynMusicApi, dst_api: &mut DynMusicApi, config: &ConfigArgs) -> Result<()> {
+    for api in [&*src_api, &*dst_api] {
+        if !api.supports_saved_albums() {
+            warn!("{:?} does not support saved albums, skipping albums", api.api_type());
+            return Ok(());
+        }
+    }
+    info!("retrieving saved albums...");
+    let src_albums = src_api.get_saved_albums().await?;
+    save_albums(&src_albums, dst_api, config).await
+}
+
+/// Save `src_albums`, the saved albums of the source platform or of an export, on the
+/// destination platform
+pub async fn save_albums(src_albums: &[SavedAlbum], dst_api: &mut DynMusicApi, config: &ConfigArgs) -> Result<()> {
+    if !dst_api.supports_saved_albums() {
+        warn!("{:?} does not support saved albums, skipping albums", dst_api.api_type());
+        return Ok(());
+    }
+    let dst_albums = dst_api.get_saved_albums().await?;
+
+    let to_search: Vec<&SavedAlbum> = src_albums
+        .iter()
+        .filter(|a| !dst_albums.iter().any(|d| a.compare(d)))
+        .collect();
+    info!("searching for {} missing albums on destination platform...", to_search.len());
+    let progress = progress_bar(to_search.len(), "albums");
+    let mut albums: Vec<SavedAlbum> = vec![];
+    let mut missing = 0;
+    for album in to_search {
+        match dst_api.search_album(album).await? {
+            Some(found) if dst_albums.iter().chain(&albums).any(|d| d.compare(&found)) => {
+                debug!("discrepancy, album already saved: {}", found);
+            }
+            Some(found) => albums.push(found),
+            None => {
+                warn!("no match found for album: {}", album);
+                missing += 1;
+            }
+        }
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+
+    if albums.is_empty() {
+        info!("[ok] no new albums to save, {} not found", missing);
+    } else if config.dry_run {
+        info!("[dry-run] would save {} albums, {} not found", albums.len(), missing);
+        for album in &albums {
+            info!("[dry-run]   + {}", album);
+        }
+    } else {
+        dst_api.add_saved_albums(&albums).await?;
+        info!("[ok] saved {} albums, {} not found", albums.len(), missing);
+    }
+    Ok(())
+}
+
+/// Follow the source followed artists on the destination platform, `--sync-artists`
+///
+/// Artists are matched by name. Platforms without followed artists are skipped.
+pub async fn sync_artists(src_api: &mut DynMusicApi, dst_api: &mut DynMusicApi, config: &ConfigArgs) -> Result<()> {
+    for api in [&*src_api, &*dst_api] {
+        if !api.supports_followed_artists() {
+            warn!("{:?} does not support followed artists, skipping artists", api.api_type());
+            return Ok(());
+        }
+    }
+    info!("retrieving followed artists...");
+    let src_artists = src_api.get_followed_artists().await?;
+    let dst_artists = dst_api.get_followed_artists().await?;
+
+    let to_search: Vec<&Artist> = src_artists
+        .iter()
+        .filter(|a| !dst_artists.iter().any(|d| a.is_same(d)))
+        .collect();
+    info!("searching for {} missing artists on destination platform...", to_search.len());
+    let progress = progress_bar(to_search.len(), "artists");
+    let mut artists: Vec<Artist> = vec![];
+    let mut missing = 0;
+    for artist in to_search {
+        match dst_api.search_artist(artist).await? {
+            Some(found) if dst_artists.iter().chain(&artists).any(|d| d.id == found.id) => {
+                debug!("discrepancy, artist already followed: {}", found.name);
+            }
+            Some(found) => artists.push(found),
+            None => {
+                warn!("no match found for artist: {}", artist.name);
+                missing += 1;
+            }
+        }
+        progress.inc(1);
     }
+    progress.finish_and_clear();
 
+    if artists.is_empty() {
+        info!("[ok] no new artists to follow, {} not found", missing);
+    } else if config.dry_run {
+        info!("[dry-run] would follow {} artists, {} not found", artists.len(), missing);
+        for artist in &artists {
+            info!("[dry-run]   + {}", artist.name);
+        }
+    } else {
+        dst_api.follow_artists(&artists).await?;
+        info!("[ok] followed {} artists, {} not found", artists.len(), missing);
+    }
     Ok(())
 }
 
+/// Fetch the songs of the source playlists whose snapshot changed since the last run. The
+/// unchanged playlists get their cached songs, or are left out when they were already
+/// synchronized to `dst` at that snapshot
+async fn fetch_changed_playlists(
+    src_api: &mut DynMusicApi,
+    src_playlists: Vec<Playlist>,
+    snapshots: &mut SnapshotCache,
+    dst: &MusicApiType,
+    dst_account: &str,
+    config: &ConfigArgs,
+) -> Result<(Vec<Playlist>, HashSet<String>)> {
+    let src = src_api.api_type();
+    let mut cached = vec![];
+    let mut to_fetch = vec![];
+    let mut unchanged = HashSet::new();
+    for (i, mut playlist) in src_playlists.into_iter().enumerate() {
+        match snapshots.action(&src, &playlist, dst, dst_account, config.force_refresh) {
+            SnapshotAction::Fetch => to_fetch.push((i, playlist)),
+            action => {
+                debug!("playlist \"{}\" unchanged since the last run, using its cached songs", playlist.name);
+                if action == SnapshotAction::Skip {
+                    unchanged.insert(playlist.id.clone());
+                }
+                playlist.songs = snapshots.songs(&src, &playlist).unwrap_or_default().to_vec();
+                cached.push((i, playlist));
+            }
+        }
+    }
+
+    let (indexes, mut fetched): (Vec<usize>, Vec<Playlist>) = to_fetch.into_iter().unzip();
+    src_api.fetch_playlists_songs(&mut fetched).await?;
+    for playlist in fetched.iter().filter(|p| p.unavailable.is_none()) {
+        snapshots.insert(&src, playlist);
+    }
+
+    cached.extend(indexes.into_iter().zip(fetched));
+    cached.sort_by_key(|(i, _)| *i);
+    Ok((cached.into_iter().map(|(_, p)| p).collect(), unchanged))
+}
+
+/// Whether a playlist was made by the platform itself, `fallback_names` are used when its
+/// owner is unknown
+fn is_generated(playlist: &Playlist, fallback_names: &[String]) -> bool {
+    playlist.generated
+        || (playlist.owner.as_deref().is_none_or(str::is_empty)
+            && fallback_names.contains(&playlist.name))
+}
+
+/// Source playlists selected for synchronization
+#[derive(Default, Debug, Clone)]
+pub struct PlaylistFilter {
+    /// Names of the playlists to skip, case insensitive
+    pub skip: Vec<String>,
+    /// Only synchronize the playlists with these names, case insensitive
+    pub names: Vec<String>,
+    /// Only synchronize the playlists whose name matches, in addition to `names`
+    pub regex: Option<Regex>,
+}
+
+impl PlaylistFilter {
+    pub fn is_selected(&self, name: &str) -> bool {
+        let lower = name.to_lowercase();
+        if self.skip.iter().any(|s| s.to_lowercase() == lower) {
+            return false;
+        }
+        if self.names.is_empty() && self.regex.is_none() {
+            return true;
+        }
+        self.names.iter().any(|n| n.to_lowercase() == lower)
+            || self.regex.as_ref().is_some_and(|r| r.is_match(name))
+    }
+
+    /// Keep the selected playlists, failing when `names` or `regex` select none of them
+    pub fn select(&self, playlists: Vec<Playlist>) -> Result<Vec<Playlist>> {
+        for skipped in &self.skip {
+            if !playlists.iter().any(|p| p.name.to_lowercase() == skipped.to_lowercase()) {
+                warn!(
+                    "skip_playlists entry \"{}\" does not match any source playlist (consider removing it)",
+                    skipped
+                );
+            }
+        }
+
+        let available: Vec<String> = playlists.iter().map(|p| format!("\"{}\"", p.name)).collect();
+        let selected: Vec<Playlist> = playlists
+            .into_iter()
+            .filter(|p| self.is_selected(&p.name))
+            .collect();
+        if selected.is_empty() && (!self.names.is_empty() || self.regex.is_some()) {
+            return Err(eyre!(
+                "no source playlist matches --playlists/--playlists-regex, available playlists: {}",
+                available.join(", ")
+            ));
+        }
+        Ok(selected)
+    }
+}
+
+/// Synchronize source playlists, already selected with `PlaylistFilter::select`
+///
+/// Returns the report of the synchronization, also written with `--report`.
 pub async fn synchronize_playlists(
+    src_playlists: Vec<Playlist>,
+    src_type: Option<MusicApiType>,
+    dst_api: &mut DynMusicApi,
+    config: &ConfigArgs,
+    dst_owner: String,
+) -> Result<SyncReport> {
+    let mut report =
+        synchronize_changed_playlists(src_playlists, src_type, &HashSet::new(), dst_api, config, dst_owner).await?;
+    log_summary(&mut report, config)?;
+    Ok(report)
+}
+
+/// Log the summary table of the synchronization and write the `--report`
+fn log_summary(report: &mut SyncReport, config: &ConfigArgs) -> Result<()> {
+    for line in report.summary() {
+        info!("{}", line);
+    }
+    save_report(report, config)
+}
+
+/// Synchronize the source playlists, except the `unchanged` ones (by id) since their last
+/// synchronization whose destination playlist still exists
+async fn synchronize_changed_playlists(
     mut src_playlists: Vec<Playlist>,
+    src_type: Option<MusicApiType>,
+    unchanged: &HashSet<String>,
     dst_api: &mut DynMusicApi,
     config: &ConfigArgs,
-    skip_playlists: Vec<String>,
     dst_owner: String,
-) -> Result<()> {
-    let mut all_missing_songs = json!({});
-    let mut all_new_songs = json!({});
-    let mut no_albums = json!({});
-    let mut stats = json!({});
+) -> Result<SyncReport> {
+    let mut dry_run_plan = vec![];
+
+    if config.mode == SyncMode::Mirror && !dst_api.supports_removal() {
+        return Err(eyre!(
+            "{:?} does not support removing songs from playlists, --mode mirror can't be used",
+            dst_api.api_type()
+        ));
+    }
+    if config.dedup_dst && !dst_api.supports_removal() {
+        return Err(eyre!(
+            "{:?} does not support removing songs from playlists, --dedup-dst can't be used",
+            dst_api.api_type()
+        ));
+    }
 
+    if config.collaborative && dst_api.api_type() != MusicApiType::Spotify {
+        warn!("{:?} has no collaborative playlists, ignoring --collaborative", dst_api.api_type());
+    }
+    if let Some(path) = &config.folder_map {
+        FolderMap::load(path)?.assign(&mut src_playlists);
+    }
+
+    // songs of the destination playlists are only fetched once paired
     info!("retrieving destination playlists...");
-    let mut dst_playlists = dst_api.get_playlists_full().await?;
+    let mut dst_playlists = dst_api.get_playlists_info().await?;
     let mut dst_likes = vec![];
     if config.like_all {
         info!("retrieving destination likes...");
         dst_likes = dst_api.get_likes().await?;
     }
 
-    /* Filter to specific playlists */
-    // Filter by playlist name
-    //src_playlists.retain(|playlist| playlist.name == "sh22");
-
     // Filter by playlist owner if we want to sync only our own playlists
     // src_playlists.retain(|playlist| playlist.owner == Some(src_owner.to_string()));
 
-    // Check for skip_playlists entries that don't match any playlists
-    if !skip_playlists.is_empty() {
-        let all_playlist_names: Vec<String> = src_playlists
-            .iter()
-            .chain(dst_playlists.iter())
-            .map(|p| p.name.to_lowercase())
-            .collect();
-        
-        for skipped in &skip_playlists {
-            let skipped_lower = skipped.to_lowercase();
-            if !all_playlist_names.contains(&skipped_lower) {
-                warn!(
-                    "skip_playlists entry \"{}\" does not match any source or destination playlist (consider removing it)",
-                    skipped
-                );
-            }
-        }
-    }
-
-    // Remove skipped playlists by matching name lowercase
-    src_playlists.retain(|playlist| {
-        !skip_playlists
-            .iter()
-            .any(|skipped| playlist.name.to_lowercase() == skipped.to_lowercase())
-    });
-
-    // Remove destinaton playlists that are not owned by our user
+    // Remove destinaton playlists that are not owned by our user, or filled by the platform
+    // (e.g. Plex smart playlists) which songs can't be added to
+    let own_playlists_only = dst_api.lists_own_playlists_only();
     dst_playlists.retain(|playlist| {
-        if playlist.owner != Some(dst_owner.clone()) {
+        // the collaborative playlists of other users can be edited too
+        let owned = playlist.is_owned_by(&dst_owner) || (playlist.owner.is_none() && own_playlists_only);
+        let skipped = if !(owned || (playlist.collaborative && config.include_collaborative)) {
             warn!(
                 "destination playlist \"{}\" is not owned by user \"{}\", skipping",
                 playlist.name, dst_owner
             );
-
-            // Remove matching playlist from source playlists
-            if let Some(i) = src_playlists.iter().position(|p| p.name == playlist.name) {
-                src_playlists.remove(i);
+            true
+        } else if playlist.generated {
+            warn!(
+                "destination playlist \"{}\" is generated by the platform and can't be edited, skipping",
+                playlist.name
+            );
+            true
+        } else {
+            if !owned {
+                info!(
+                    "destination playlist \"{}\" is a collaborative playlist of another user, synchronizing it",
+                    playlist.name
+                );
             }
-            
             false
-        } else {
-            true
+        };
+        // Remove matching playlist from source playlists
+        if skipped && let Some(i) = src_playlists.iter().position(|p| p.name == playlist.name) {
+            src_playlists.remove(i);
         }
+        !skipped
     });
 
-    
-    static mut SONG_COUNTER: usize = 0;
-    static mut SLEEP_DURATION: u64 = 180; // Initial sleep duration in seconds (3 minutes)
 
-    for mut src_playlist in src_playlists
-        .into_iter()
-        .filter(|p| !SKIPPED_PLAYLISTS.contains(&p.name.as_str()) && !p.songs.is_empty())
-    {
-        if src_playlist.songs.is_empty() {
-            continue;
+    if !config.include_generated {
+        src_playlists.retain(|p| {
+            let generated = is_generated(p, &config.generated_playlist_names);
+            if generated {
+                info!("playlist \"{}\" was generated by the platform, skipping", p.name);
+            }
+            !generated
+        });
+    }
+    // inaccessible and deleted source playlists are reported, not synchronized as empty ones
+    let (unavailable, mut src_playlists): (Vec<Playlist>, Vec<Playlist>) =
+        src_playlists.into_iter().partition(|p| p.unavailable.is_some());
+    if !unavailable.is_empty() {
+        warn!("==========================================================");
+        for playlist in &unavailable {
+            warn!(
+                "source playlist \"{}\" ({}) is {}, it was NOT synchronized",
+                playlist.name,
+                playlist.id,
+                playlist.unavailable.unwrap()
+            );
         }
+        warn!("==========================================================");
+    }
+    src_playlists.retain(|p| !p.songs.is_empty());
 
-        let mut dst_playlist = match dst_playlists
-            .iter()
-            .position(|p| p.name == src_playlist.name)
-        {
-            Some(i) => dst_playlists.remove(i),
-            None => dst_api.create_playlist(&src_playlist.name, false).await?,
-        };
+    // Pair playlists by their name on the destination platform, resolving collisions
+    let mapping_path = config.config_dir()?.join(PlaylistMapping::FILE_NAME);
+    let mut mapping = PlaylistMapping::load(&mapping_path)?;
+    let (dst_names, collisions) = mapping.resolve_names(&src_playlists, &dst_api.api_type(), |name| {
+        dst_api.sanitize_playlist_name(name)
+    });
+    if !collisions.is_empty() {
+        mapping.save(&mapping_path)?;
+    }
 
-        let mut missing_songs = json!([]);
-        let mut new_songs = json!([]);
-        let no_albums_songs = json!([]);
-        let mut dst_songs = vec![];
-        let mut success = 0;
-        let mut attempts = 0;
+    let overrides = Overrides::load(&config.overrides_path()?)?;
+
+    // Progress is recorded after each added chunk of songs, to be resumed with --resume
+    let checkpoint_path = config.config_dir()?.join(Checkpoint::FILE_NAME);
+    let checkpoint = if config.dry_run {
+        None
+    } else if config.resume {
+        Some(Checkpoint::load(&checkpoint_path, src_type.clone(), dst_api.api_type())?)
+    } else {
+        Some(Checkpoint::new(&checkpoint_path, src_type.clone(), dst_api.api_type()))
+    };
+    let mut report = SyncReport::new(src_type.clone(), dst_api.api_type(), config.dry_run);
+    // the same line for every playlist, dated by the start of the synchronization
+    let provenance = (!config.no_provenance).then(|| provenance_line(src_type.as_ref(), report.started_at));
+    for playlist in unavailable {
+        report.push(PlaylistReport {
+            dst_name: dst_api.sanitize_playlist_name(&playlist.name),
+            src_id: playlist.id,
+            src_name: playlist.name,
+            created: false,
+            skipped: true,
+            unchanged: false,
+            unavailable: playlist.unavailable,
+            matched: 0,
+            attempts: 0,
+            conversion_rate: 0.0,
+            missing: vec![],
+            local_files: vec![],
+            added: vec![],
+            duplicates_skipped: 0,
+            not_added: vec![],
+            isrc_enriched: 0,
+        });
+    }
 
-        if dedup_songs(&mut src_playlist.songs) {
+    let mut jobs = vec![];
+    for (src_playlist, dst_name) in src_playlists.into_iter().zip(dst_names) {
+        if checkpoint.as_ref().is_some_and(|c| c.is_complete(&src_playlist.id)) {
+            info!(
+                "playlist \"{}\" was synchronized by the interrupted run, skipping",
+                src_playlist.name
+            );
+            continue;
+        }
+        // playlists synchronized before are found by id, even if the source playlist was renamed
+        let link = mapping.get(&src_playlist.id, &dst_api.api_type()).filter(|l| {
+            let same_kind = l.kind == dst_api.playlist_kind();
+            if !same_kind {
+                info!(
+                    "playlist \"{}\" was synchronized as a {:?}, it is now synchronized as a {:?}",
+                    src_playlist.name,
+                    l.kind,
+                    dst_api.playlist_kind()
+                );
+            }
+            same_kind
+        });
+        let confirmed = link.is_some_and(|l| l.confirmed);
+        let linked_id = link.and_then(|l| l.id.clone());
+        let linked = linked_id
+            .as_ref()
+            .and_then(|id| dst_playlists.iter().position(|p| &p.id == id));
+        if let Some(id) = &linked_id
+            && linked.is_none()
+        {
             warn!(
-                "duplicates found in source playlist \"{}\", they will be skipped",
+                "destination playlist {} of \"{}\" no longer exists, it will be created again",
+                id, src_playlist.name
+            );
+        }
+        if let Some(i) = linked
+            && unchanged.contains(&src_playlist.id)
+        {
+            info!(
+                "playlist \"{}\" unchanged since its last synchronization, skipping it (--force-refresh to synchronize it)",
                 src_playlist.name
             );
+            report.push(PlaylistReport {
+                src_id: src_playlist.id,
+                src_name: src_playlist.name,
+                dst_name: dst_playlists[i].name.clone(),
+                created: false,
+                skipped: true,
+                unchanged: true,
+                unavailable: None,
+                matched: 0,
+                attempts: 0,
+                conversion_rate: 0.0,
+                missing: vec![],
+                local_files: vec![],
+                added: vec![],
+                duplicates_skipped: 0,
+                not_added: vec![],
+                isrc_enriched: 0,
+            });
+            continue;
+        }
+        // playlists created before names were sanitized keep the source name
+        let renamed = dst_name != dst_api.sanitize_playlist_name(&src_playlist.name);
+        let mut dst_playlist = linked
+            .or_else(|| dst_playlists.iter().position(|p| p.name == dst_name))
+            .or_else(|| {
+                (!renamed)
+                    .then(|| dst_playlists.iter().position(|p| p.name == src_playlist.name))
+                    .flatten()
+            })
+            .map(|i| dst_playlists.remove(i));
+        if let Some(dst_playlist) = &mut dst_playlist
+            && dst_playlist.name != dst_name
+            && (renamed || dst_playlist.name != src_playlist.name)
+        {
+            if config.dry_run {
+                info!(
+                    "[dry-run] would rename playlist \"{}\" to \"{}\"",
+                    dst_playlist.name, dst_name
+                );
+            } else {
+                info!(
+                    "source playlist was renamed, renaming playlist \"{}\" to \"{}\"",
+                    dst_playlist.name, dst_name
+                );
+                dst_api.rename_playlist(dst_playlist, &dst_name).await?;
+            }
         }
+        jobs.push(PlaylistJob {
+            src_playlist,
+            dst_playlist,
+            dst_name,
+            confirmed,
+            provenance: provenance.clone(),
+        });
+    }
 
-        info!("synchronizing playlist \"{}\" ...", src_playlist.name);
+    let new_folders = jobs
+        .iter()
+        .filter(|job| job.dst_playlist.is_none())
+        .filter_map(|job| job.src_playlist.folder.as_deref());
+    create_folders(dst_api, new_folders, config).await?;
 
-        // 1. Search for each song in the destination playlist
-        for src_song in &src_playlist.songs {
-            // already in destination playlist
-            if dst_playlist.songs.contains(src_song) {
-                continue;
-            }
+    if config.concurrency > 1
+        && (dst_api.api_type() == MusicApiType::YtMusic || dst_api.clone_handle().is_none())
+    {
+        warn!(
+            "{:?} does not support concurrent song searches, ignoring --concurrency",
+            dst_api.api_type()
+        );
+    }
 
-            // YtMusic API rate limit workaround
-            if dst_api.api_type() == MusicApiType::YtMusic {
-                unsafe {
-                    SONG_COUNTER += 1;
-                    if SONG_COUNTER % 150 == 0 {
-                        let sleep_duration = SLEEP_DURATION;
-                        info!("Reached 150 songs, taking a {}-second break...", sleep_duration);
-                        sleep(Duration::from_secs(sleep_duration)).await;
-                        SLEEP_DURATION += 60; // Add 60 seconds to the sleep duration each time
-                    }
-                }
-            }
+    if config.sync_covers && !dst_api.supports_playlist_cover() {
+        warn!(
+            "{:?} does not support playlist covers, ignoring --sync-covers",
+            dst_api.api_type()
+        );
+    }
 
-            // no album metadata == youtube video
-            /* Commented this part out, personal preference */
-            // if src_song.album.is_none() {
-            //     warn!(
-            //         "No album metadata for source song \"{}\", skipping",
-            //         src_song
-            //     );
-            //     if config.debug {
-            //         no_albums_songs
-            //             .as_array_mut()
-            //             .unwrap()
-            //             .push(json!(src_song));
-            //     }
-            //     continue;
-            // }
-
-            attempts += 1;
-
-            let dst_song = dst_api.search_song(src_song).await?;
-            let Some(dst_song) = dst_song else {
-                debug!("no match found for song: {}", src_song);
-                if config.debug {
-                    missing_songs.as_array_mut().unwrap().push(json!(src_song));
-                }
-                continue;
-            };
-            dst_songs.push(dst_song);
-            success += 1;
-        }
-
-        // 2. Add missing songs to the destination playlist
-        if !dst_songs.is_empty() {
-            let mut to_sync = Vec::new();
-            for dst_song in &dst_songs {
-                // HACK: takes into account discrepancy for YtMusic with no ISRC
-                if dst_playlist.songs.contains(dst_song) {
-                    debug!(
-                        "discrepancy, song already in destination playlist: {}",
-                        dst_song
-                    );
-                    attempts -= 1;
-                    success -= 1;
-                    continue;
-                }
-                // Edge case: same song on different album/single that all resolve to the same
-                // song on the destination platform resulting in duplicates
-                if to_sync.contains(dst_song) {
-                    debug!(
-                        "discrepancy, duplicate song in songs to synchronize: {}",
-                        dst_song
-                    );
-                    attempts -= 1;
-                    success -= 1;
-                    continue;
-                }
-                if config.debug {
-                    new_songs.as_array_mut().unwrap().push(json!(dst_song));
-                }
-                to_sync.push(dst_song.clone());
-            }
-            debug!(
-                "adding {} songs to destination playlist \"{}\"",
-                to_sync.len(),
-                dst_playlist.name
+    // Extra destination handles for concurrent processing, when the destination supports it
+    let parallel = config.parallel_playlists.max(1);
+    let mut handles = vec![];
+    for _ in 1..parallel {
+        let Some(handle) = dst_api.clone_handle() else {
+            warn!(
+                "{:?} does not support concurrent playlist processing, ignoring --parallel-playlists",
+                dst_api.api_type()
             );
-            dst_api
-                .add_songs_to_playlist(&mut dst_playlist, &to_sync)
-                .await?;
+            break;
+        };
+        handles.push(handle);
+    }
+    let dst_type = dst_api.api_type();
+    let playlist_kind = dst_api.playlist_kind();
+    let pool: Vec<Mutex<&mut DynMusicApi>> = std::iter::once(dst_api)
+        .chain(handles.iter_mut())
+        .map(Mutex::new)
+        .collect();
 
-            // like all songs that were added
-            if config.like_all {
-                let new_likes = to_sync
-                    .iter()
-                    .filter(|s| !dst_likes.contains(s))
-                    .cloned()
-                    .collect::<Vec<Song>>();
-                dst_api.add_likes(&new_likes).await?;
-            }
+    let cache_path = config.config_dir()?.join(MatchCache::FILE_NAME);
+    let cache = if config.no_cache {
+        None
+    } else if config.clear_match_cache {
+        info!("clearing the match cache");
+        if cache_path.exists() {
+            std::fs::remove_file(&cache_path)?;
         }
+        Some(MatchCache::default())
+    } else {
+        Some(MatchCache::load(&cache_path)?)
+    };
 
-        let mut conversion_rate = 1.0;
-        if attempts != 0 {
-            conversion_rate = f64::from(success) / f64::from(attempts);
-            info!(
-                "synchronizing playlist \"{}\" [ok], {}/{} songs ({:.2}%)",
-                src_playlist.name,
-                success,
-                attempts,
-                conversion_rate * 100.0
-            );
-        } else {
-            info!(
-                "synchronizing playlist \"{}\" [ok], no new songs to add",
-                src_playlist.name
-            );
+    // searching by ISRC tells live versions and remasters apart, unlike the name search
+    let mut isrc_enriched = HashMap::new();
+    if config.enrich_isrc && dst_type != MusicApiType::YtMusic {
+        info!("looking up the ISRC of songs on MusicBrainz...");
+        let isrc_cache_path = config.config_dir()?.join(IsrcCache::FILE_NAME);
+        let mut enricher =
+            IsrcEnricher::new(IsrcCache::load(&isrc_cache_path)?, config.match_options().duration_tolerance_ms)?;
+        // songs matched by previous runs are not searched again
+        let matched = |s: &Song| cache.as_ref().is_some_and(|c| c.get(s, &dst_type).is_some());
+        for job in &mut jobs {
+            let count = enricher.enrich(&mut job.src_playlist.songs, matched).await;
+            enricher.save(&isrc_cache_path)?;
+            isrc_enriched.insert(job.src_playlist.id.clone(), count);
+        }
+        info!(
+            "{} songs will be searched with an ISRC hint",
+            isrc_enriched.values().sum::<usize>()
+        );
+    }
+    let cache = cache.map(std::sync::Mutex::new);
+    let checkpoint = checkpoint.map(std::sync::Mutex::new);
+    let skips_path = config.config_dir()?.join(ReviewSkips::FILE_NAME);
+    let skips = if config.review_matches() {
+        Some(std::sync::Mutex::new(ReviewSkips::load(&skips_path)?))
+    } else {
+        None
+    };
+
+    let dst_likes = &dst_likes;
+    let overrides = &overrides;
+    let pool = &pool;
+    let cache_ref = cache.as_ref();
+    let checkpoint_ref = checkpoint.as_ref();
+    let skips_ref = skips.as_ref();
+    let progress = progress_bar(jobs.len(), "playlists");
+    let mut results = stream::iter(jobs.into_iter().enumerate().map(|(i, job)| async move {
+        let mut dst_api = pool[i % pool.len()].lock().await;
+        synchronize_playlist(job, &mut dst_api, config, dst_likes, overrides, cache_ref, checkpoint_ref, skips_ref)
+            .await
+    }))
+    .buffered(pool.len());
+
+    // Shared reports are only updated here, in the source playlists order
+    while let Some(res) = results.next().await {
+        let mut res = res?;
+        res.report.isrc_enriched = isrc_enriched.get(&res.report.src_id).copied().unwrap_or(0);
+        if let Some(cache) = &cache {
+            cache.lock().unwrap().save(&cache_path)?;
+        }
+        if let Some(skips) = &skips {
+            skips.lock().unwrap().save(&skips_path)?;
+        }
+        if config.dry_run && !res.report.skipped {
+            dry_run_plan.push(res.plan);
+        }
+        // playlists without songs aren't created on the platforms that can't hold empty ones
+        if !config.dry_run
+            && !res.report.skipped
+            && !res.dst_id.is_empty()
+            && mapping.link(
+                &res.report.src_id,
+                &res.report.src_name,
+                &dst_type,
+                &res.dst_id,
+                &res.report.dst_name,
+                playlist_kind,
+            )
+        {
+            mapping.save(&mapping_path)?;
+        }
+        // the pairing is kept unconfirmed, for the user to confirm it in the mapping file
+        if !config.dry_run
+            && let Some(dst_id) = &res.unconfirmed_dst_id
+            && mapping.link(
+                &res.report.src_id,
+                &res.report.src_name,
+                &dst_type,
+                dst_id,
+                &res.report.dst_name,
+                playlist_kind,
+            )
+        {
+            mapping.save(&mapping_path)?;
         }
+        report.push(res.report);
+        progress.inc(1);
 
         if config.debug {
-            stats.as_object_mut().unwrap().insert(
-                src_playlist.name.clone(),
-                json!({
-                    "percentage": conversion_rate,
-                    "number": format!("{}/{}", success, attempts),
-                }),
-            );
-            std::fs::write(
-                "debug/conversion_rate.json",
-                serde_json::to_string_pretty(&stats)?,
-            )?;
-
-            if !new_songs.as_array().unwrap().is_empty() {
-                all_new_songs
-                    .as_object_mut()
-                    .unwrap()
-                    .insert(src_playlist.name.clone(), new_songs);
-                std::fs::write(
-                    "debug/new_songs.json",
-                    serde_json::to_string_pretty(&all_new_songs)?,
-                )?;
-            }
+            report.write_debug_files(&config.debug_dir()?)?;
+        }
+    }
 
-            if !missing_songs.as_array().unwrap().is_empty() {
-                all_missing_songs
-                    .as_object_mut()
-                    .unwrap()
-                    .insert(src_playlist.name.clone(), missing_songs);
-                std::fs::write(
-                    "debug/missing_songs.json",
-                    serde_json::to_string_pretty(&all_missing_songs)?,
-                )?;
-            }
+    drop(results);
+    progress.finish_and_clear();
+    // every playlist is synchronized, there is nothing to resume
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.lock().unwrap().remove()?;
+    }
 
-            if !no_albums_songs.as_array().unwrap().is_empty() {
-                no_albums
-                    .as_object_mut()
-                    .unwrap()
-                    .insert(src_playlist.name.clone(), no_albums_songs);
-                std::fs::write(
-                    "debug/song_with_no_albums.json",
-                    serde_json::to_string_pretty(&no_albums)?,
-                )?;
-            }
+    if config.dry_run {
+        if let Some(path) = &config.dry_run_report {
+            let plan = SyncPlan {
+                dst_api: dst_type,
+                playlists: dry_run_plan,
+            };
+            std::fs::write(path, serde_json::to_string_pretty(&plan)?)?;
+            let text_path = path.with_extension("txt");
+            std::fs::write(&text_path, plan_to_text(&plan))?;
+            info!("[dry-run] plan written to {:?} and {:?}, apply it with --apply", path, text_path);
         }
+        info!("[dry-run] Synchronization preview complete, nothing was modified");
+    } else {
+        info!("Synchronization complete!");
     }
 
-    info!("Synchronization complete!");
+    Ok(report)
+}
 
-    Ok(())
+/// Match of a source song on the destination platform
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlannedSong {
+    pub src: Song,
+    pub dst: Song,
+    pub confidence: f64,
+}
+
+/// Changes to a destination playlist computed in dry-run mode
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlannedPlaylist {
+    pub src_id: String,
+    pub name: String,
+    /// Existing destination playlist, `None` when it would be created
+    pub dst_id: Option<String>,
+    /// Number of songs in the destination playlist when planning
+    pub dst_song_count: usize,
+    pub success: u32,
+    pub attempts: u32,
+    /// Songs that would be added, in order
+    pub additions: Vec<PlannedSong>,
+    /// Source songs already in the destination playlist
+    pub present: Vec<Song>,
+    /// Source songs with no match on the destination platform
+    pub unmatched: Vec<Song>,
+    /// Destination songs that would be removed in mirror mode
+    #[serde(default)]
+    pub removals: Vec<Song>,
+    /// Description and visibility of the source playlist
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub public: Option<bool>,
+    /// Line added to the description, `None` with `--no-provenance`
+    #[serde(default)]
+    pub provenance: Option<String>,
+    /// Folder the playlist is created in, see `--folder-map`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+/// Output of a dry-run, that can be executed later with `--apply`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncPlan {
+    pub dst_api: MusicApiType,
+    pub playlists: Vec<PlannedPlaylist>,
+}
+
+/// A source playlist paired with its destination playlist, if it already exists
+struct PlaylistJob {
+    src_playlist: Playlist,
+    dst_playlist: Option<Playlist>,
+    dst_name: String,
+    /// The pairing was confirmed by the user in the mapping file
+    confirmed: bool,
+    /// Line added to the description of the destination playlist, `None` with `--no-provenance`
+    provenance: Option<String>,
+}
+
+struct PlaylistResult {
+    /// Destination playlist, empty when skipped or in dry-run mode
+    dst_id: String,
+    /// Destination playlist of a suspicious pairing, recorded unconfirmed in the mapping file
+    unconfirmed_dst_id: Option<String>,
+    plan: PlannedPlaylist,
+    report: PlaylistReport,
 }
 
-pub async fn synchronize_likes(src_api: &mut DynMusicApi, dst_api: &mut DynMusicApi) -> Result<()> {
-    info!("retrieving source likes...");
-    let src_likes = src_api.get_likes().await?;
-    info!("retrieving destination likes...");
-    let dst_likes = dst_api.get_likes().await?;
+#[allow(clippy::too_many_arguments)]
+async fn synchronize_playlist(
+    job: PlaylistJob,
+    dst_api: &mut DynMusicApi,
+    config: &ConfigArgs,
+    dst_likes: &[Song],
+    overrides: &Overrides,
+    cache: Option<&std::sync::Mutex<MatchCache>>,
+    checkpoint: Option<&std::sync::Mutex<Checkpoint>>,
+    review_skips: Option<&std::sync::Mutex<ReviewSkips>>,
+) -> Result<PlaylistResult> {
+    let PlaylistJob {
+        mut src_playlist,
+        mut dst_playlist,
+        dst_name,
+        confirmed,
+        provenance,
+    } = job;
+
+    if let Some(dst_playlist) = &mut dst_playlist {
+        dst_playlist.songs = dst_api.get_playlist_songs(&dst_playlist.id).await?;
+    }
+    if let Some(progress) = checkpoint.and_then(|c| c.lock().unwrap().get(&src_playlist.id).cloned()) {
+        info!(
+            "resuming playlist \"{}\", {} songs were added by the interrupted run",
+            src_playlist.name, progress.songs_added
+        );
+    }
+
+    let mut plan = PlannedPlaylist {
+        src_id: src_playlist.id.clone(),
+        name: dst_name.clone(),
+        dst_id: dst_playlist.as_ref().map(|p| p.id.clone()),
+        dst_song_count: dst_playlist.as_ref().map_or(0, |p| p.songs.len()),
+        success: 0,
+        attempts: 0,
+        additions: vec![],
+        present: vec![],
+        unmatched: vec![],
+        removals: vec![],
+        description: src_playlist.description.clone(),
+        public: src_playlist.public,
+        provenance,
+        folder: src_playlist.folder.clone(),
+    };
+
+    if let Some(dst_playlist) = &dst_playlist
+        && !confirmed
+        && !config.force_pairing
+        && let Some(overlap) = suspicious_pairing(
+            &src_playlist.songs,
+            &dst_playlist.songs,
+            config.pairing_size_ratio,
+            config.pairing_min_overlap,
+        )
+    {
+        warn!("==========================================================");
+        warn!(
+            "source playlist \"{}\" ({} songs) was paired with destination playlist \"{}\" ({} songs), \
+            but only {:.0}% of the source songs are in it, skipping",
+            src_playlist.name,
+            src_playlist.songs.len(),
+            dst_playlist.name,
+            dst_playlist.songs.len(),
+            overlap * 100.0
+        );
+        let entry = serde_json::json!({
+            &src_playlist.id: { dst_api.api_type().short_name(): {
+                "name": dst_name,
+                "id": dst_playlist.id,
+                "confirmed": true,
+            }}
+        });
+        warn!(
+            "if this pairing is right, set \"confirmed\": true in the entry of the source playlist \
+            in {:?}, or use --force-pairing: {}",
+            config.config_dir()?.join(PlaylistMapping::FILE_NAME),
+            entry
+        );
+        warn!("==========================================================");
+        let report = PlaylistReport {
+            src_id: src_playlist.id,
+            src_name: src_playlist.name,
+            dst_name,
+            created: false,
+            skipped: true,
+            unchanged: false,
+            unavailable: None,
+            matched: 0,
+            attempts: 0,
+            conversion_rate: 0.0,
+            missing: vec![],
+            local_files: vec![],
+            added: vec![],
+            duplicates_skipped: 0,
+            not_added: vec![],
+            isrc_enriched: 0,
+        };
+        return Ok(PlaylistResult {
+            dst_id: String::new(),
+            unconfirmed_dst_id: Some(dst_playlist.id.clone()),
+            plan,
+            report,
+        });
+    }
+
+    let created = dst_playlist.is_none();
+    let mut dst_playlist = match dst_playlist {
+        Some(mut p) => {
+            update_details(dst_api, &mut p, &plan, config).await?;
+            p
+        }
+        None if config.dry_run => {
+            info!("[dry-run] would create playlist \"{}\"", dst_name);
+            new_playlist(&dst_name, &plan)
+        }
+        // the playlist is created once its songs are found, with the first of them
+        None => new_playlist(&dst_name, &plan),
+    };
 
-    let mut new_likes = Vec::new();
+    let mut dst_songs = vec![];
+    let mut matched_dst_songs = vec![];
     let mut success = 0;
     let mut attempts = 0;
 
-    info!("searching for all missing likes on destination platform...");
-    for src_like in src_likes {
-        if dst_likes.contains(&src_like) {
+    let song_count = src_playlist.songs.len();
+    if dedup_songs(&mut src_playlist.songs) {
+        warn!(
+            "duplicates found in source playlist \"{}\", they will be skipped",
+            src_playlist.name
+        );
+    }
+    let mut duplicates_skipped = song_count - src_playlist.songs.len();
+
+    info!("synchronizing playlist \"{}\" ...", src_playlist.name);
+
+    // 1. Search for each song in the destination playlist
+    let mut lookups = vec![];
+    // source song id -> override key, for the songs matched by an override
+    let mut overridden = vec![];
+    for src_song in &src_playlist.songs {
+        // already in destination playlist
+        if dst_playlist.songs.contains(src_song) {
+            plan.present.push(src_song.clone());
             continue;
         }
-        attempts += 1;
-        let Some(song) = dst_api.search_song(&src_like).await? else {
-            debug!("no match found for song: {}", src_like);
-            continue;
-        };
-        // HACK: takes into account discrepancy for YtMusic with no ISRC
-        if dst_likes.contains(&song) {
-            attempts -= 1;
-            debug!("discrepancy, song already liked: {}", song);
+
+        // no album metadata == youtube video
+        /* Commented this part out, personal preference */
+        // if src_song.album.is_none() {
+        //     warn!(
+        //         "No album metadata for source song \"{}\", skipping",
+        //         src_song
+        //     );
+        //     if config.debug {
+        //         no_albums_songs
+        //             .as_array_mut()
+        //             .unwrap()
+        //             .push(json!(src_song));
+        //     }
+        //     continue;
+        // }
+
+        if overrides.is_blacklisted(src_song) {
+            debug!("song is blacklisted in the overrides, skipping: {}", src_song);
             continue;
         }
-        success += 1;
-        new_likes.push(song);
+
+        let known = known_match(src_song, dst_api, overrides, cache).map(|(dst_song, key)| {
+            if let Some(key) = key {
+                overridden.push((src_song.id.clone(), key));
+            }
+            dst_song
+        });
+        lookups.push((src_song, known));
     }
 
-    if attempts != 0 {
-        let conversion_rate = f64::from(success) / f64::from(attempts);
-        info!(
-            "synchronizing {}/{} ({:.2}%) new likes",
-            success,
-            attempts,
-            conversion_rate * 100.0
-        );
-        dst_api.add_likes(&new_likes).await?;
-        info!("[ok] synchronized new likes");
+    let to_search: Vec<&Song> = lookups
+        .iter()
+        .filter(|(_, known)| known.is_none())
+        .map(|(src_song, _)| *src_song)
+        .collect();
+    let progress = progress_bar(to_search.len(), &src_playlist.name);
+    let mut searched = search_songs(dst_api, &to_search, config, &progress).await?.into_iter();
+    progress.finish_and_clear();
+
+    for (src_song, known) in lookups {
+        attempts += 1;
+
+        let dst_song = if known.is_some() {
+            known
+        } else {
+            let mut dst_song = searched.next().flatten();
+            if let (None, Some(skips)) = (&dst_song, review_skips) {
+                dst_song = review_song(dst_api, src_song, config, skips).await?;
+            }
+            if let (Some(cache), Some(dst_song)) = (cache, &dst_song) {
+                cache.lock().unwrap().insert(src_song, dst_song);
+            }
+            dst_song
+        };
+        let Some(dst_song) = dst_song else {
+            debug!("no match found for song: {}", src_song);
+            plan.unmatched.push(src_song.clone());
+            continue;
+        };
+        matched_dst_songs.push(dst_song.clone());
+        dst_songs.push((src_song, dst_song));
+        success += 1;
+    }
+
+    // 2. Add missing songs to the destination playlist
+    for (src_song, dst_song) in dst_songs {
+        // HACK: takes into account discrepancy for YtMusic with no ISRC
+        if dst_playlist.songs.contains(&dst_song) {
+            debug!(
+                "discrepancy, song already in destination playlist: {}",
+                dst_song
+            );
+            attempts -= 1;
+            success -= 1;
+            plan.present.push(src_song.clone());
+            continue;
+        }
+        // Edge case: same song on different album/single that all resolve to the same
+        // song on the destination platform resulting in duplicates
+        if plan.additions.iter().any(|a| a.dst == dst_song) {
+            debug!(
+                "discrepancy, duplicate song in songs to synchronize: {}",
+                dst_song
+            );
+            attempts -= 1;
+            success -= 1;
+            duplicates_skipped += 1;
+            continue;
+        }
+        plan.additions.push(PlannedSong {
+            confidence: src_song.match_confidence(&dst_song),
+            src: src_song.clone(),
+            dst: dst_song,
+        });
+    }
+
+    // 3. In mirror mode, find the destination songs that are no longer in the source playlist
+    if config.mode == SyncMode::Mirror {
+        plan.removals = dst_playlist
+            .songs
+            .iter()
+            .filter(|s| !src_playlist.songs.contains(s) && !matched_dst_songs.contains(s))
+            .cloned()
+            .collect();
+    }
+
+    if !plan.additions.is_empty() {
+        let to_sync: Vec<Song> = plan.additions.iter().map(|a| a.dst.clone()).collect();
+        if config.dry_run {
+            info!(
+                "[dry-run] would add {} songs to playlist \"{}\"",
+                to_sync.len(),
+                dst_playlist.name
+            );
+            for addition in &plan.additions {
+                info!(
+                    "[dry-run]   + {} ({:.0}% confidence)",
+                    addition.dst,
+                    addition.confidence * 100.0
+                );
+            }
+        }
+        let mut on_chunk = |added| record_progress(checkpoint, &src_playlist, added, false);
+        add_songs(dst_api, &mut dst_playlist, &to_sync, config, dst_likes, &mut on_chunk).await?;
+
+        if !config.dry_run {
+            let rejected: Vec<&PlannedSong> = plan
+                .additions
+                .iter()
+                .filter(|a| !dst_playlist.songs.iter().any(|s| s.id == a.dst.id))
+                .collect();
+            for addition in &rejected {
+                if let Some((_, key)) = overridden.iter().find(|(id, _)| *id == addition.src.id) {
+                    warn!(
+                        "{:?} rejected song {} of override \"{}\", check the overrides file",
+                        dst_api.api_type(),
+                        addition.dst.id,
+                        key
+                    );
+                }
+            }
+            // songs the destination platform refused to add are searched again next time
+            if let Some(cache) = cache {
+                let mut cache = cache.lock().unwrap();
+                for addition in &rejected {
+                    cache.invalidate(&addition.src, &dst_api.api_type());
+                }
+            }
+        }
+    } else if created && !config.dry_run {
+        if dst_api.supports_empty_playlists() {
+            dst_playlist = create_playlist(dst_api, &dst_playlist, &[]).await?;
+        } else {
+            info!(
+                "no songs of playlist \"{}\" to add, {:?} can't create it empty",
+                src_playlist.name,
+                dst_api.api_type()
+            );
+        }
+    }
+
+    if !plan.removals.is_empty() {
+        if config.dry_run {
+            info!(
+                "[dry-run] would remove {} songs from playlist \"{}\"",
+                plan.removals.len(),
+                dst_playlist.name
+            );
+            for song in &plan.removals {
+                info!("[dry-run]   - {}", song);
+            }
+        } else {
+            info!(
+                "removing {} songs no longer in the source from playlist \"{}\"",
+                plan.removals.len(),
+                dst_playlist.name
+            );
+            dst_api
+                .remove_songs_from_playlist(&mut dst_playlist, &plan.removals)
+                .await?;
+        }
+    }
+
+    // a playlist that would be created has nothing to deduplicate
+    if config.dedup_dst && !dst_playlist.id.is_empty() {
+        remove_duplicates(dst_api, &mut dst_playlist, config).await?;
+    }
+
+    if created
+        && config.sync_covers
+        && dst_api.supports_playlist_cover()
+        && let Some(cover_url) = &src_playlist.cover_url
+    {
+        sync_cover(dst_api, &dst_playlist, cover_url, config).await;
+    }
+
+    let mut conversion_rate = 1.0;
+    if attempts != 0 {
+        conversion_rate = f64::from(success) / f64::from(attempts);
+        info!(
+            "synchronizing playlist \"{}\" [ok], {}/{} songs ({:.2}%)",
+            src_playlist.name,
+            success,
+            attempts,
+            conversion_rate * 100.0
+        );
     } else {
-        info!("[ok] no new likes to synchronize");
+        info!(
+            "synchronizing playlist \"{}\" [ok], no new songs to add",
+            src_playlist.name
+        );
+    }
+    plan.success = success;
+    plan.attempts = attempts;
+    record_progress(checkpoint, &src_playlist, 0, true)?;
+
+    let report = PlaylistReport {
+        src_id: src_playlist.id,
+        src_name: src_playlist.name,
+        dst_name,
+        created,
+        skipped: false,
+        unchanged: false,
+        unavailable: None,
+        matched: success,
+        attempts,
+        conversion_rate,
+        missing: plan.unmatched.clone(),
+        local_files: plan.unmatched.iter().filter(|s| s.is_local_file()).cloned().collect(),
+        added: plan.additions.iter().map(|a| a.dst.clone()).collect(),
+        duplicates_skipped,
+        not_added: vec![],
+        isrc_enriched: 0,
+    };
+    Ok(PlaylistResult {
+        dst_id: dst_playlist.id,
+        unconfirmed_dst_id: None,
+        plan,
+        report,
+    })
+}
+
+/// Remove the songs of a destination playlist with the same identity as an earlier song,
+/// `--dedup-dst`. In dry-run mode, the playlist is checked as it was before synchronizing.
+async fn remove_duplicates(
+    dst_api: &mut DynMusicApi,
+    dst_playlist: &mut Playlist,
+    config: &ConfigArgs,
+) -> Result<()> {
+    if !config.dry_run {
+        dst_playlist.songs = dst_api.get_playlist_songs(&dst_playlist.id).await?;
+    }
+    let mut duplicates = duplicate_songs(&dst_playlist.songs);
+    // without an occurrence id, removing a song removes all its occurrences
+    duplicates.retain(|d| {
+        let removable = d.sid.is_some() || dst_playlist.songs.iter().filter(|s| s.id == d.id).count() == 1;
+        if !removable {
+            warn!(
+                "song {} is several times in playlist \"{}\", {:?} can't remove only one of them",
+                d,
+                dst_playlist.name,
+                dst_api.api_type()
+            );
+        }
+        removable
+    });
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    if config.dry_run {
+        info!(
+            "[dry-run] would remove {} duplicate songs from playlist \"{}\"",
+            duplicates.len(),
+            dst_playlist.name
+        );
+        for song in &duplicates {
+            info!("[dry-run]   - {}", song);
+        }
+    } else {
+        info!(
+            "removing {} duplicate songs from playlist \"{}\"",
+            duplicates.len(),
+            dst_playlist.name
+        );
+        for song in &duplicates {
+            info!("  - {}", song);
+        }
+        dst_api.remove_songs_from_playlist(dst_playlist, &duplicates).await?;
+    }
+    Ok(())
+}
+
+/// Set the source cover on a destination playlist created by this run, `--sync-covers`.
+/// Covers are cosmetic, failures are only logged.
+async fn sync_cover(dst_api: &mut DynMusicApi, dst_playlist: &Playlist, cover_url: &str, config: &ConfigArgs) {
+    if config.dry_run {
+        info!("[dry-run] would set the cover of playlist \"{}\"", dst_playlist.name);
+        return;
+    }
+    let res = match download_cover(cover_url, MAX_COVER_BYTES, config).await {
+        Ok(cover) => dst_api.set_playlist_cover(dst_playlist, &cover).await,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(()) => debug!("cover of playlist \"{}\" set from {}", dst_playlist.name, cover_url),
+        Err(e) => warn!("failed to set the cover of playlist \"{}\": {}", dst_playlist.name, e),
+    }
+}
+
+/// Destination playlist yet to be created, with the description and visibility of its source
+/// playlist
+fn new_playlist(name: &str, plan: &PlannedPlaylist) -> Playlist {
+    // the provenance needs a description to be added to
+    let description = match &plan.provenance {
+        Some(line) => Some(with_provenance(plan.description.as_deref().unwrap_or(PLAYLIST_DESC), line)),
+        None => plan.description.clone(),
+    };
+    Playlist {
+        id: String::new(),
+        name: name.to_string(),
+        songs: vec![],
+        owner: None,
+        description,
+        public: plan.public,
+        generated: false,
+        cover_url: None,
+        unavailable: None,
+        owner_name: None,
+        collaborative: false,
+        snapshot_id: None,
+        folder: plan.folder.clone(),
+    }
+}
+
+/// Create a destination playlist planned with `new_playlist` containing `songs`, private with a
+/// default description when the source ones are unknown. It's then moved to its folder, created
+/// beforehand by `create_folders`
+async fn create_playlist(dst_api: &mut DynMusicApi, planned: &Playlist, songs: &[Song]) -> Result<Playlist> {
+    let mut playlist = dst_api
+        .create_playlist_with_songs(
+            &planned.name,
+            planned.description.as_deref().unwrap_or(PLAYLIST_DESC),
+            planned.public.unwrap_or(false),
+            songs,
+        )
+        .await?;
+    if let Some(folder) = &planned.folder
+        && dst_api.supports_folders()
+    {
+        match dst_api.move_to_folder(&playlist, folder).await {
+            Ok(()) => playlist.folder = Some(folder.clone()),
+            Err(e) => warn!("failed to move playlist \"{}\" to folder \"{}\": {}", playlist.name, folder, e),
+        }
+    }
+    Ok(playlist)
+}
+
+/// Create the folders of the playlists to create that are missing on the destination platform,
+/// parents first. Platforms without folders ignore them
+async fn create_folders<'a>(
+    dst_api: &mut DynMusicApi,
+    folders: impl IntoIterator<Item = &'a str>,
+    config: &ConfigArgs,
+) -> Result<()> {
+    let folders: Vec<&str> = folders.into_iter().collect();
+    if folders.is_empty() {
+        return Ok(());
+    }
+    if !dst_api.supports_folders() {
+        warn!("{:?} has no playlist folders, ignoring --folder-map", dst_api.api_type());
+        return Ok(());
+    }
+    let existing = dst_api.get_folders().await?;
+    for path in folders_to_create(folders, &existing) {
+        if config.dry_run {
+            info!("[dry-run] would create folder \"{}\"", path);
+        } else {
+            info!("creating folder \"{}\"", path);
+            dst_api.create_folder(&path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Line added to the description of the destination playlists, telling where and when they were
+/// synchronized from
+fn provenance_line(src_type: Option<&MusicApiType>, timestamp: u64) -> String {
+    match src_type {
+        Some(src_type) => format!("Synced from {:?} by SyncDisBoi on {}", src_type, utc_date(timestamp)),
+        None => format!("Synced by SyncDisBoi on {}", utc_date(timestamp)),
+    }
+}
+
+fn with_provenance(description: &str, provenance: &str) -> String {
+    format!("{}\n{}", description, provenance)
+}
+
+/// Description without the provenance line added by a previous synchronization
+fn without_provenance(description: &str) -> &str {
+    match description.rsplit_once('\n') {
+        Some((description, line))
+            if line.starts_with("Synced ") && line.contains(" by SyncDisBoi on ") =>
+        {
+            description
+        }
+        _ => description,
+    }
+}
+
+/// Update the description and visibility of a destination playlist when they differ from the
+/// source ones planned in `plan`, unless the destination platform doesn't provide them. The
+/// provenance line isn't compared, it's only refreshed along with the description.
+async fn update_details(
+    dst_api: &mut DynMusicApi,
+    dst_playlist: &mut Playlist,
+    plan: &PlannedPlaylist,
+    config: &ConfigArgs,
+) -> Result<()> {
+    if !dst_api.supports_playlist_details() {
+        return Ok(());
+    }
+    let description = plan
+        .description
+        .as_deref()
+        .filter(|d| dst_playlist.description.as_deref().map(without_provenance) != Some(*d))
+        .map(|d| match &plan.provenance {
+            Some(line) => with_provenance(d, line),
+            None => d.to_string(),
+        });
+    let public = plan.public.filter(|p| dst_playlist.public != Some(*p));
+    if description.is_none() && public.is_none() {
+        return Ok(());
+    }
+    if config.dry_run {
+        info!(
+            "[dry-run] would update the description/visibility of playlist \"{}\"",
+            dst_playlist.name
+        );
+        return Ok(());
+    }
+    debug!("updating the description/visibility of playlist \"{}\"", dst_playlist.name);
+    dst_api
+        .update_playlist_details(dst_playlist, description.as_deref(), public)
+        .await
+}
+
+/// Write the report of a synchronization with `--report`
+pub(crate) fn save_report(report: &mut SyncReport, config: &ConfigArgs) -> Result<()> {
+    report.finish();
+    if let Some(path) = &config.report {
+        report.save(path)?;
+        info!(
+            "report written to {:?}: {}/{} songs matched, {} missing",
+            path, report.totals.matched, report.totals.attempts, report.totals.missing
+        );
+    }
+    Ok(())
+}
+
+/// Destination song of `src_song` known without searching: pinned by an override, cached by a
+/// previous run, or the song itself when it comes from the destination platform. The key of
+/// the override is returned along with the songs matched by one.
+pub(crate) fn known_match(
+    src_song: &Song,
+    dst_api: &DynMusicApi,
+    overrides: &Overrides,
+    cache: Option<&std::sync::Mutex<MatchCache>>,
+) -> Option<(Song, Option<String>)> {
+    if let Some((key, id)) = overrides.find(src_song, &dst_api.api_type()) {
+        return Some((src_song.with_id(dst_api.api_type(), id), Some(key)));
+    }
+    if let Some(cached) = cache.and_then(|c| c.lock().unwrap().get(src_song, &dst_api.api_type()).cloned()) {
+        return Some((cached, None));
+    }
+    // ids of songs from the destination platform itself are verified on import, local files
+    // have no id to reuse and are searched like the songs of other platforms
+    if src_song.source == dst_api.api_type() && dst_api.supports_song_lookup() && !src_song.is_local_file() {
+        return Some((src_song.clone(), None));
+    }
+    None
+}
+
+/// Search songs on the destination platform, concurrently when it supports it. `progress` is
+/// advanced after each search.
+pub(crate) async fn search_songs(
+    dst_api: &mut DynMusicApi,
+    songs: &[&Song],
+    config: &ConfigArgs,
+    progress: &ProgressBar,
+) -> Result<Vec<Option<Song>>> {
+    let mut handles = vec![];
+    if dst_api.api_type() != MusicApiType::YtMusic {
+        for _ in 1..config.concurrency.max(1).min(songs.len()) {
+            let Some(handle) = dst_api.clone_handle() else {
+                break;
+            };
+            handles.push(handle);
+        }
+    }
+
+    if handles.is_empty() {
+        let mut results = vec![];
+        for song in songs {
+            results.push(dst_api.search_song(song).await?);
+            progress.inc(1);
+        }
+        return Ok(results);
     }
 
+    let pool: Vec<Mutex<&mut DynMusicApi>> = std::iter::once(dst_api)
+        .chain(handles.iter_mut())
+        .map(Mutex::new)
+        .collect();
+    let pool = &pool;
+    stream::iter(songs.iter().enumerate().map(|(i, song)| async move {
+        let res = pool[i % pool.len()].lock().await.search_song(song).await;
+        progress.inc(1);
+        res
+    }))
+    .buffered(pool.len())
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect()
+}
+
+fn record_progress(
+    checkpoint: Option<&std::sync::Mutex<Checkpoint>>,
+    src_playlist: &Playlist,
+    songs_added: usize,
+    complete: bool,
+) -> Result<()> {
+    if let Some(checkpoint) = checkpoint {
+        let mut checkpoint = checkpoint.lock().unwrap();
+        checkpoint.record(&src_playlist.id, &src_playlist.name, songs_added, complete);
+        checkpoint.save()?;
+    }
     Ok(())
 }
+
+/// Number of songs added to a playlist between two checkpoints
+const ADD_CHUNK_SIZE: usize = 50;
+
+/// Add songs to a destination playlist, liking them as well with `--like-all`
+///
+/// Songs are added by chunks, `on_chunk` is called with the size of each added chunk.
+async fn add_songs(
+    dst_api: &mut DynMusicApi,
+    dst_playlist: &mut Playlist,
+    to_sync: &[Song],
+    config: &ConfigArgs,
+    dst_likes: &[Song],
+    on_chunk: &mut (dyn FnMut(usize) -> Result<()> + Send),
+) -> Result<()> {
+    if !config.dry_run {
+        debug!(
+            "adding {} songs to destination playlist \"{}\"",
+            to_sync.len(),
+            dst_playlist.name
+        );
+        let mut remaining = to_sync;
+        // a playlist yet to be created is created with its first chunk of songs
+        if dst_playlist.id.is_empty() {
+            let (seed, rest) = to_sync.split_at(to_sync.len().min(ADD_CHUNK_SIZE));
+            *dst_playlist = create_playlist(dst_api, dst_playlist, seed).await?;
+            on_chunk(seed.len())?;
+            remaining = rest;
+        }
+        debug!(
+            "{} add requests for {} songs",
+            remaining.len().div_ceil(ADD_CHUNK_SIZE),
+            remaining.len()
+        );
+        for chunk in remaining.chunks(ADD_CHUNK_SIZE) {
+            dst_api.add_songs_to_playlist(dst_playlist, chunk).await?;
+            on_chunk(chunk.len())?;
+        }
+    }
+
+    // like all songs that were added
+    if config.like_all {
+        let new_likes = to_sync
+            .iter()
+            .filter(|s| !dst_likes.contains(s))
+            .cloned()
+            .collect::<Vec<Song>>();
+        if config.dry_run {
+            info!("[dry-run] would like {} songs", new_likes.len());
+        } else {
+            dst_api.add_likes(&new_likes).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Fraction of songs a destination playlist may have gained or lost between planning and applying
+const PLAN_MAX_DRIFT: f64 = 0.1;
+
+/// Apply a plan made in dry-run mode, adding exactly the planned songs without searching them again
+///
+/// All the planned destination playlists are checked before any modification: they must still
+/// exist (or still not exist for the ones to create), and their size must not have changed much.
+#[allow(clippy::cast_precision_loss)]
+pub async fn apply_plan(plan_path: &Path, dst_api: &mut DynMusicApi, config: &ConfigArgs) -> Result<()> {
+    let plan: SyncPlan = serde_json::from_reader(std::fs::File::open(plan_path)?)?;
+    if plan.dst_api != dst_api.api_type() {
+        return Err(eyre!(
+            "the plan was made for {:?}, not {:?}",
+            plan.dst_api,
+            dst_api.api_type()
+        ));
+    }
+
+    if plan.playlists.iter().any(|p| !p.removals.is_empty()) && !dst_api.supports_removal() {
+        return Err(eyre!(
+            "the plan removes songs, but {:?} does not support removing songs from playlists",
+            dst_api.api_type()
+        ));
+    }
+
+    info!("retrieving destination playlists...");
+    let dst_playlists = dst_api.get_playlists_full().await?;
+
+    let mut targets = vec![];
+    for planned in &plan.playlists {
+        let current = if let Some(id) = &planned.dst_id {
+            let current = dst_playlists
+                .iter()
+                .find(|p| &p.id == id)
+                .ok_or(eyre!("destination playlist \"{}\" no longer exists", planned.name))?;
+            let drift = current.songs.len().abs_diff(planned.dst_song_count);
+            if drift as f64 > (planned.dst_song_count as f64 * PLAN_MAX_DRIFT).max(2.0) {
+                return Err(eyre!(
+                    "destination playlist \"{}\" changed too much since planning ({} songs, {} when planned), \
+                    run --dry-run again",
+                    planned.name,
+                    current.songs.len(),
+                    planned.dst_song_count
+                ));
+            }
+            Some(current.clone())
+        } else {
+            if dst_playlists.iter().any(|p| p.name == planned.name) {
+                return Err(eyre!(
+                    "destination playlist \"{}\" was created since planning, run --dry-run again",
+                    planned.name
+                ));
+            }
+            None
+        };
+        targets.push((planned, current));
+    }
+
+    let new_folders = targets
+        .iter()
+        .filter(|(_, current)| current.is_none())
+        .filter_map(|(planned, _)| planned.folder.as_deref());
+    create_folders(dst_api, new_folders, config).await?;
+
+    let mut dst_likes = vec![];
+    if config.like_all {
+        info!("retrieving destination likes...");
+        dst_likes = dst_api.get_likes().await?;
+    }
+
+    for (planned, current) in targets {
+        let mut dst_playlist = match current {
+            Some(mut p) => {
+                update_details(dst_api, &mut p, planned, config).await?;
+                p
+            }
+            None => new_playlist(&planned.name, planned),
+        };
+        let to_sync: Vec<Song> = planned
+            .additions
+            .iter()
+            .map(|a| a.dst.clone())
+            .filter(|s| !dst_playlist.songs.contains(s))
+            .collect();
+        if !to_sync.is_empty() {
+            add_songs(dst_api, &mut dst_playlist, &to_sync, config, &dst_likes, &mut |_| Ok(())).await?;
+        } else if dst_playlist.id.is_empty() {
+            if !dst_api.supports_empty_playlists() {
+                info!(
+                    "no songs of playlist \"{}\" to add, {:?} can't create it empty",
+                    planned.name,
+                    dst_api.api_type()
+                );
+                continue;
+            }
+            dst_playlist = create_playlist(dst_api, &dst_playlist, &[]).await?;
+        }
+        let to_remove: Vec<Song> = planned
+            .removals
+            .iter()
+            .filter(|s| dst_playlist.songs.contains(s))
+            .cloned()
+            .collect();
+        if !to_remove.is_empty() {
+            dst_api
+                .remove_songs_from_playlist(&mut dst_playlist, &to_remove)
+                .await?;
+        }
+        info!(
+            "applied plan to playlist \"{}\", {} songs added, {} removed",
+            planned.name,
+            to_sync.len(),
+            to_remove.len()
+        );
+    }
+
+    info!("Plan applied!");
+    Ok(())
+}
+
+/// Human readable version of a plan
+fn plan_to_text(plan: &SyncPlan) -> String {
+    let mut text = String::new();
+    for p in &plan.playlists {
+        let status = if p.dst_id.is_some() {
+            format!("existing, {} songs", p.dst_song_count)
+        } else {
+            "new playlist".to_string()
+        };
+        let _ = writeln!(text, "Playlist \"{}\" ({})", p.name, status);
+        let _ = writeln!(text, "  matched {}/{} songs", p.success, p.attempts);
+        for a in &p.additions {
+            let _ = writeln!(
+                text,
+                "  + {} -> {} ({:.0}% confidence)",
+                a.src,
+                a.dst,
+                a.confidence * 100.0
+            );
+        }
+        for s in &p.present {
+            let _ = writeln!(text, "  = {}", s);
+        }
+        for s in &p.unmatched {
+            let _ = writeln!(text, "  ? {}", s);
+        }
+        for s in &p.removals {
+            let _ = writeln!(text, "  - {}", s);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Number of source songs looked up in the destination playlist to check a pairing
+const PAIRING_SAMPLE_SIZE: usize = 50;
+
+/// Detect a source playlist paired with an unrelated destination playlist sharing its name
+///
+/// Returns the fraction of sampled source songs found in the destination when the destination
+/// is at least `size_ratio` times bigger than the source and that fraction is below `min_overlap`.
+#[allow(clippy::cast_precision_loss)]
+fn suspicious_pairing(
+    src_songs: &[Song],
+    dst_songs: &[Song],
+    size_ratio: f64,
+    min_overlap: f64,
+) -> Option<f64> {
+    if src_songs.is_empty() || (dst_songs.len() as f64) < src_songs.len() as f64 * size_ratio {
+        return None;
+    }
+    let step = src_songs.len().div_ceil(PAIRING_SAMPLE_SIZE);
+    let sample: Vec<&Song> = src_songs.iter().step_by(step).collect();
+    let found = sample.iter().filter(|s| dst_songs.contains(s)).count();
+    let overlap = found as f64 / sample.len() as f64;
+    (overlap < min_overlap).then_some(overlap)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::mock_api::{MockApi, artist, playlist, saved_album, serve_image, song, test_dir};
+    use crate::music_api::Unavailability;
+
+    fn config(args: &[&str]) -> ConfigArgs {
+        // tests run concurrently, each one gets its own checkpoint
+        let config_dir = test_dir("sync");
+        let mut full = vec!["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap(), "--no-cache"];
+        full.extend_from_slice(args);
+        ConfigArgs::parse_from(full)
+    }
+
+    /// Platform of the songs of `src_playlists`
+    const SRC: Option<MusicApiType> = Some(MusicApiType::Spotify);
+
+    fn src_playlists() -> Vec<Playlist> {
+        (0..4u64)
+            .map(|i| {
+                let songs = (0..3u64)
+                    .map(|j| {
+                        let hash = (i * 3 + j + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                        let name = format!("song {}-{} {:x}", i, j, hash);
+                        song(MusicApiType::Spotify, &format!("s{}{}", i, j), &name)
+                    })
+                    .collect();
+                playlist(&format!("p{}", i), &format!("Playlist {}", i), songs)
+            })
+            .collect()
+    }
+
+    fn dst_api(concurrent: bool) -> MockApi {
+        let catalog = src_playlists()
+            .iter()
+            .flat_map(|p| &p.songs)
+            .map(|s| song(MusicApiType::Plex, &format!("d{}", s.id), &s.name))
+            .collect();
+        let mut api = MockApi::new(MusicApiType::Plex, catalog);
+        api.latency = Duration::from_millis(20);
+        api.concurrent = concurrent;
+        api
+    }
+
+    async fn run(api: &MockApi, parallel: &str) -> Duration {
+        let config = config(&["--parallel-playlists", parallel]);
+        let start = Instant::now();
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        start.elapsed()
+    }
+
+    fn assert_synchronized(api: &MockApi) {
+        let state = api.state.lock().unwrap();
+        assert_eq!(state.playlists.len(), 4);
+        for (i, p) in state.playlists.iter().enumerate() {
+            assert_eq!(p.name, format!("Playlist {}", i));
+            let ids: Vec<&str> = p.songs.iter().map(|s| s.id.as_str()).collect();
+            let expected: Vec<String> = (0..3).map(|j| format!("ds{}{}", i, j)).collect();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_playlists() {
+        let sequential_api = dst_api(true);
+        let sequential = run(&sequential_api, "1").await;
+        assert_synchronized(&sequential_api);
+        assert_eq!(sequential_api.state.lock().unwrap().max_in_flight, 1);
+
+        let parallel_api = dst_api(true);
+        let parallel = run(&parallel_api, "4").await;
+        assert_synchronized(&parallel_api);
+        assert!(parallel_api.state.lock().unwrap().max_in_flight > 1);
+        assert!(parallel * 2 < sequential, "{:?} vs {:?}", parallel, sequential);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_search() {
+        let api = dst_api(true);
+        let config = config(&["--concurrency", "3"]);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        // songs are added in the source order
+        assert_synchronized(&api);
+        assert_eq!(api.state.lock().unwrap().max_in_flight, 3);
+
+        // YtMusic searches stay sequential
+        let mut yt_api = dst_api(true);
+        yt_api.api_type = MusicApiType::YtMusic;
+        yt_api.catalog = Arc::new(
+            yt_api
+                .catalog
+                .iter()
+                .map(|s| song(MusicApiType::YtMusic, &s.id, &s.name))
+                .collect(),
+        );
+        synchronize_playlists(src_playlists(), SRC, &mut yt_api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        assert_synchronized(&yt_api);
+        assert_eq!(yt_api.state.lock().unwrap().max_in_flight, 1);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let api = dst_api(true);
+        let report = test_dir("dry_run").join("plan.json");
+        let dry_config = config(&["--dry-run", "--like-all", "--dry-run-report", report.to_str().unwrap()]);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &dry_config, "owner".to_string())
+            .await
+            .unwrap();
+        assert!(api.state.lock().unwrap().writes.is_empty());
+
+        let plan: SyncPlan = serde_json::from_reader(std::fs::File::open(&report).unwrap()).unwrap();
+        assert_eq!(plan.dst_api, MusicApiType::Plex);
+        assert_eq!(plan.playlists.len(), 4);
+        assert!(
+            plan.playlists
+                .iter()
+                .all(|p| p.dst_id.is_none() && p.success == 3 && p.attempts == 3)
+        );
+
+        let config = config(&["--like-all"]);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        for (planned, created) in plan.playlists.iter().zip(&state.playlists) {
+            assert_eq!(planned.name, created.name);
+            let planned_ids: Vec<&str> = planned.additions.iter().map(|a| a.dst.id.as_str()).collect();
+            let created_ids: Vec<&str> = created.songs.iter().map(|s| s.id.as_str()).collect();
+            assert_eq!(planned_ids, created_ids);
+        }
+        assert_eq!(state.likes.len(), 12);
+    }
+
+    /// Destination with "Playlist 0" already containing its first song, and no match for "song 1-2"
+    fn planned_dst_api() -> MockApi {
+        let mut api = dst_api(true);
+        api.catalog = Arc::new(
+            api.catalog
+                .iter()
+                .filter(|s| !s.name.starts_with("song 1-2"))
+                .cloned()
+                .collect(),
+        );
+        let first = api.catalog[0].clone();
+        api.state
+            .lock()
+            .unwrap()
+            .playlists
+            .push(playlist("d0", "Playlist 0", vec![first]));
+        api
+    }
+
+    async fn make_plan(api: &MockApi, name: &str) -> (SyncPlan, PathBuf) {
+        let report = test_dir("plan").join(name);
+        let dry_config = config(&["--dry-run", "--dry-run-report", report.to_str().unwrap()]);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &dry_config, "owner".to_string())
+            .await
+            .unwrap();
+        let plan = serde_json::from_reader(std::fs::File::open(&report).unwrap()).unwrap();
+        (plan, report)
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_plan() {
+        let api = planned_dst_api();
+        let (plan, report) = make_plan(&api, "sync_dis_boi_plan.json").await;
+
+        let p0 = &plan.playlists[0];
+        assert_eq!(p0.dst_id.as_deref(), Some("d0"));
+        assert_eq!(p0.dst_song_count, 1);
+        assert_eq!(p0.present.len(), 1);
+        assert_eq!(p0.additions.len(), 2);
+        assert_eq!(p0.additions[0].src.id, "s01");
+        assert_eq!(p0.additions[0].dst.id, "ds01");
+        assert!((p0.additions[0].confidence - 1.0).abs() < f64::EPSILON);
+        let p1 = &plan.playlists[1];
+        assert_eq!(p1.additions.len(), 2);
+        assert_eq!(p1.unmatched.len(), 1);
+        assert_eq!(p1.unmatched[0].id, "s12");
+
+        let text = std::fs::read_to_string(report.with_extension("txt")).unwrap();
+        assert!(text.contains("Playlist \"Playlist 0\" (existing, 1 songs)"));
+        assert!(text.contains("Playlist \"Playlist 1\" (new playlist)"));
+        assert!(text.contains("  + song 0-1"));
+        assert!(text.contains("  = song 0-0"));
+        assert!(text.contains("  ? song 1-2"));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_details() {
+        let mut api = dst_api(false);
+        let mut src = src_playlists();
+        src[0].description = Some("road trip".to_string());
+        src[0].public = Some(true);
+        let report =
+            synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+                .await
+                .unwrap();
+        // dated by the start of the synchronization, whatever the time of day
+        let provenance = provenance_line(Some(&MusicApiType::Spotify), report.started_at);
+        {
+            let state = api.state.lock().unwrap();
+            assert_eq!(state.playlists[0].description, Some(format!("road trip\n{}", provenance)));
+            assert_eq!(state.playlists[0].public, Some(true));
+            // unknown details get the defaults
+            assert_eq!(state.playlists[1].description, Some(format!("{}\n{}", PLAYLIST_DESC, provenance)));
+            assert_eq!(state.playlists[1].public, Some(false));
+        }
+
+        // unchanged details are not updated again
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        let updates = |api: &MockApi| {
+            let state = api.state.lock().unwrap();
+            state.writes.iter().filter(|w| w.starts_with("update_playlist_details")).count()
+        };
+        assert_eq!(updates(&api), 0);
+
+        src[0].description = Some("summer road trip".to_string());
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(updates(&api), 1);
+        {
+            let state = api.state.lock().unwrap();
+            assert_eq!(state.playlists[0].description, Some(format!("summer road trip\n{}", provenance)));
+            assert_eq!(state.playlists[0].public, Some(true));
+        }
+
+        src[0].description = Some("road trip".to_string());
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&["--no-provenance"]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(api.state.lock().unwrap().playlists[0].description.as_deref(), Some("road trip"));
+
+        // the details that can't be compared are left alone
+        api.details = false;
+        src[0].description = Some("winter road trip".to_string());
+        synchronize_playlists(src, SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(updates(&api), 2);
+        assert_eq!(api.state.lock().unwrap().playlists[0].description.as_deref(), Some("road trip"));
+    }
+
+    #[test]
+    fn test_provenance() {
+        // 2024-02-29 12:00 UTC
+        let line = provenance_line(Some(&MusicApiType::YtMusic), 1_709_208_000);
+        assert_eq!(line, "Synced from YtMusic by SyncDisBoi on 2024-02-29");
+        assert_eq!(provenance_line(None, 0), "Synced by SyncDisBoi on 1970-01-01");
+
+        let description = with_provenance("road trip\nsummer 2024", &line);
+        assert_eq!(without_provenance(&description), "road trip\nsummer 2024");
+        // the description of the user is kept
+        assert_eq!(without_provenance("road trip\nsummer 2024"), "road trip\nsummer 2024");
+        assert_eq!(without_provenance(PLAYLIST_DESC), PLAYLIST_DESC);
+    }
+
+    #[tokio::test]
+    async fn test_renamed_playlist() {
+        let api = dst_api(false);
+        let config = config(&[]);
+        let mut src = src_playlists();
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let mapping_path = config.config_dir().unwrap().join(PlaylistMapping::FILE_NAME);
+        let mapping = PlaylistMapping::load(&mapping_path).unwrap();
+        let link = mapping.get("p0", &MusicApiType::Plex).unwrap();
+        assert_eq!(link.id.as_deref(), Some("mock0"));
+
+        src[0].name = "Gym 2024".to_string();
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        {
+            let state = api.state.lock().unwrap();
+            assert_eq!(state.playlists.len(), 4);
+            assert_eq!(state.playlists[0].id, "mock0");
+            assert_eq!(state.playlists[0].name, "Gym 2024");
+            assert!(state.writes.contains(&"rename_playlist Playlist 0 Gym 2024".to_string()));
+            assert_eq!(state.writes.iter().filter(|w| w.starts_with("create_playlist")).count(), 4);
+        }
+
+        // deleted on the destination, created again
+        api.state.lock().unwrap().playlists.retain(|p| p.id != "mock0");
+        synchronize_playlists(src, SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        let recreated = state.playlists.iter().find(|p| p.name == "Gym 2024").unwrap();
+        assert_eq!(recreated.songs.len(), 3);
+        let mapping = PlaylistMapping::load(&mapping_path).unwrap();
+        let link = mapping.get("p0", &MusicApiType::Plex).unwrap();
+        assert_eq!(link.id.as_ref(), Some(&recreated.id));
+        assert_eq!(link.src_name.as_deref(), Some("Gym 2024"));
+    }
+
+    #[tokio::test]
+    async fn test_generated_playlists() {
+        let mut src = src_playlists();
+        // generated, detected by the platform
+        src[0].generated = true;
+        // user playlist named like a generated one
+        src[1].name = "High Energy Mix".to_string();
+        // unknown owner, detected by its name
+        src[2].name = "Discover Weekly".to_string();
+        src[2].owner = None;
+
+        let api = dst_api(false);
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        let names: Vec<String> = api.state.lock().unwrap().playlists.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(names, vec!["High Energy Mix", "Playlist 3"]);
+
+        let api = dst_api(false);
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&["--include-generated"]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(api.state.lock().unwrap().playlists.len(), 4);
+
+        let api = dst_api(false);
+        let config = config(&["--generated-playlist-names", "Other"]);
+        synchronize_playlists(src, SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(api.state.lock().unwrap().playlists.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_generated_dst_playlists() {
+        let api = dst_api(false);
+        let mut smart = playlist("smart0", "Playlist 0", vec![]);
+        smart.generated = true;
+        api.state.lock().unwrap().playlists.push(smart);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+
+        // neither filled nor created again under the same name
+        let state = api.state.lock().unwrap();
+        assert!(state.playlists[0].songs.is_empty());
+        let names: Vec<&str> = state.playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Playlist 0", "Playlist 1", "Playlist 2", "Playlist 3"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_changed_playlists() {
+        let src = MusicApiType::Spotify;
+        let mut listed = src_playlists();
+        for (p, snapshot) in listed.iter_mut().zip([Some("v1"), Some("v1"), Some("v2"), None]) {
+            p.snapshot_id = snapshot.map(str::to_string);
+        }
+        let api = MockApi::new(src.clone(), vec![]);
+        api.state.lock().unwrap().playlists = listed.clone();
+        for p in &mut listed {
+            p.songs = vec![];
+        }
+
+        // p0 synchronized at its snapshot, p1 only seen at it, p2 seen at an older one
+        let mut snapshots = SnapshotCache::default();
+        let cached_songs = vec![song(src.clone(), "cached", "Cached song")];
+        for (id, snapshot) in [("p0", "v1"), ("p1", "v1"), ("p2", "v1")] {
+            let mut seen = playlist(id, "Seen", cached_songs.clone());
+            seen.snapshot_id = Some(snapshot.to_string());
+            snapshots.insert(&src, &seen);
+        }
+        snapshots.mark_synced(&src, "p0", &MusicApiType::Plex, "owner");
+
+        let fetch = async |snapshots: &mut SnapshotCache, args: &[&str]| -> (Vec<Playlist>, HashSet<String>) {
+            let mut api = api.clone().boxed();
+            fetch_changed_playlists(&mut api, listed.clone(), snapshots, &MusicApiType::Plex, "owner", &config(args))
+                .await
+                .unwrap()
+        };
+        // the unchanged playlist keeps its cached songs, in case its destination playlist is gone
+        let (playlists, unchanged) = fetch(&mut snapshots, &[]).await;
+        let ids: Vec<&str> = playlists.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p0", "p1", "p2", "p3"]);
+        assert_eq!(unchanged, HashSet::from(["p0".to_string()]));
+        assert_eq!(playlists[0].songs[0].id, "cached");
+        assert_eq!(playlists[1].songs[0].id, "cached");
+        assert_eq!(playlists[2].songs[0].id, "s20");
+        assert_eq!(snapshots.playlists["spotify:p2"].snapshot_id, "v2");
+        assert!(!snapshots.playlists.contains_key("spotify:p3"));
+
+        let (playlists, unchanged) = fetch(&mut snapshots, &["--force-refresh"]).await;
+        assert_eq!(playlists.len(), 4);
+        assert!(unchanged.is_empty());
+        assert!(playlists.iter().all(|p| p.songs.len() == 3));
+        // the synchronized snapshot is kept, the songs are the current ones
+        assert_eq!(snapshots.playlists["spotify:p0"].synced["plex:owner"], "v1");
+        assert_eq!(snapshots.playlists["spotify:p1"].songs[0].id, "s10");
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_playlists() {
+        let api = dst_api(false);
+        let config = config(&[]);
+        let unchanged = HashSet::from(["p0".to_string()]);
+        let sync = async || {
+            synchronize_changed_playlists(src_playlists(), SRC, &unchanged, &mut api.clone().boxed(), &config, "owner".to_string())
+                .await
+                .unwrap()
+        };
+        // never synchronized to this account, the playlist is created
+        let report = sync().await;
+        assert!(report.playlists.iter().all(|p| !p.skipped));
+
+        let report = sync().await;
+        let skipped: Vec<&str> = report.playlists.iter().filter(|p| p.unchanged).map(|p| p.src_id.as_str()).collect();
+        assert_eq!(skipped, ["p0"]);
+        assert_eq!(report.totals.skipped_playlists, 1);
+        assert!(report.summary().iter().any(|l| l.starts_with("Playlist 0") && l.ends_with("unchanged")));
+
+        // deleted on the destination platform since, it is created again
+        api.state.lock().unwrap().playlists.retain(|p| p.name != "Playlist 0");
+        let report = sync().await;
+        assert!(report.playlists[0].created && !report.playlists[0].skipped);
+    }
+
+    #[tokio::test]
+    async fn test_dst_playlist_owners() {
+        let mut shared = playlist("d0", "Playlist 0", vec![]);
+        shared.owner = Some("friend".to_string());
+        shared.collaborative = true;
+        let mut own = playlist("d1", "Playlist 1", vec![]);
+        own.owner = Some("31l77y".to_string());
+        own.owner_name = Some("Jane".to_string());
+        let mut other = playlist("d2", "Playlist 2", vec![]);
+        other.owner = Some("friend".to_string());
+        // display names aren't unique, the user id is accepted too
+        assert!(own.is_owned_by("Jane") && own.is_owned_by("31l77y"));
+        assert!(!other.is_owned_by("Jane"));
+
+        let filled = async |args: &[&str]| -> Vec<String> {
+            let api = dst_api(false);
+            api.state.lock().unwrap().playlists = vec![shared.clone(), own.clone(), other.clone()];
+            synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config(args), "Jane".to_string())
+                .await
+                .unwrap();
+            let state = api.state.lock().unwrap();
+            state.playlists.iter().filter(|p| !p.songs.is_empty()).map(|p| p.id.clone()).collect()
+        };
+        // the playlists of other users are skipped with their source playlist, Playlist 3 is
+        // created
+        let ids = filled(&[]).await;
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], "d1");
+        let ids = filled(&["--include-collaborative"]).await;
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[..2], ["d0", "d1"]);
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let api = planned_dst_api();
+        let path = test_dir("report").join("report.json");
+        let config = config(&["--report", path.to_str().unwrap()]);
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+
+        let report: SyncReport = serde_json::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(report.src_api, Some(MusicApiType::Spotify));
+        assert_eq!(report.dst_api, MusicApiType::Plex);
+        assert!(report.started_at <= report.ended_at);
+        assert_eq!(report.totals.playlists, 4);
+        assert_eq!(report.totals.created_playlists, 3);
+        assert_eq!((report.totals.matched, report.totals.attempts), (10, 11));
+        assert_eq!(report.totals.added, 10);
+
+        let p0 = &report.playlists[0];
+        assert!(!p0.created);
+        assert_eq!(p0.added.len(), 2);
+        let p1 = &report.playlists[1];
+        assert!(p1.created);
+        assert_eq!(p1.missing.len(), 1);
+        assert!(p1.missing[0].name.starts_with("song 1-2"));
+        assert_eq!(p1.missing[0].source, MusicApiType::Spotify);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_playlists_reported() {
+        let mut src = src_playlists();
+        src[1].songs.clear();
+        src[1].unavailable = Some(Unavailability::Inaccessible);
+        src[3].songs.clear();
+        src[3].unavailable = Some(Unavailability::Deleted);
+
+        let api = dst_api(false);
+        let report = synchronize_playlists(src, SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        let names: Vec<String> = api.state.lock().unwrap().playlists.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(names, vec!["Playlist 0", "Playlist 2"]);
+
+        assert_eq!(report.totals.playlists, 4);
+        assert_eq!(report.totals.unavailable_playlists, 2);
+        assert_eq!(report.totals.skipped_playlists, 2);
+        let unavailable: Vec<(&str, Option<Unavailability>)> = report
+            .playlists
+            .iter()
+            .filter(|p| p.skipped)
+            .map(|p| (p.src_name.as_str(), p.unavailable))
+            .collect();
+        assert_eq!(
+            unavailable,
+            vec![
+                ("Playlist 1", Some(Unavailability::Inaccessible)),
+                ("Playlist 3", Some(Unavailability::Deleted))
+            ]
+        );
+        assert!(report.summary().iter().any(|l| l.starts_with("Playlist 3") && l.ends_with("deleted")));
+    }
+
+    #[tokio::test]
+    async fn test_sync_covers() {
+        let cover = [vec![0xFF, 0xD8, 0xFF], vec![0; 997]].concat();
+        let url = serve_image(cover, 1).await;
+        let mut src = src_playlists();
+        src[0].cover_url = Some(url.clone());
+        src[1].cover_url = Some(url);
+        // unreachable, the playlist is synchronized without its cover
+        src[3].cover_url = Some("http://127.0.0.1:1/cover.jpg".to_string());
+
+        let mut api = planned_dst_api();
+        api.covers = true;
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&["--sync-covers"]), "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        let covers: Vec<&String> = state.writes.iter().filter(|w| w.starts_with("set_playlist_cover")).collect();
+        // only on the created playlists
+        assert_eq!(covers, vec!["set_playlist_cover Playlist 1 1000"]);
+        assert_eq!(state.playlists.len(), 4);
+        drop(state);
+
+        let mut api = planned_dst_api();
+        api.covers = true;
+        synchronize_playlists(src, SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        assert!(!api.state.lock().unwrap().writes.iter().any(|w| w.starts_with("set_playlist_cover")));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_folders() {
+        let folder_map = test_dir("folders").join("folders.json");
+        std::fs::write(
+            &folder_map,
+            r#"{"Playlist 0": "Moods", "Playlist 1": "Sport/Running", "Playlist 2": "Sport"}"#,
+        )
+        .unwrap();
+        let config = config(&["--folder-map", folder_map.to_str().unwrap()]);
+
+        let mut api = planned_dst_api();
+        api.folders = true;
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        let folder_writes: Vec<&str> = state
+            .writes
+            .iter()
+            .filter(|w| w.contains("folder"))
+            .map(String::as_str)
+            .collect();
+        // parents first, only the folders of the created playlists
+        assert_eq!(
+            &folder_writes[..2],
+            ["create_folder Sport", "create_folder Sport/Running"]
+        );
+        let first_created = state.writes.iter().position(|w| w.starts_with("create_playlist")).unwrap();
+        let last_folder = state.writes.iter().rposition(|w| w.starts_with("create_folder")).unwrap();
+        assert!(last_folder < first_created, "{:?}", state.writes);
+        let mut moves = folder_writes[2..].to_vec();
+        moves.sort_unstable();
+        assert_eq!(
+            moves,
+            ["move_to_folder Playlist 1 Sport/Running", "move_to_folder Playlist 2 Sport"]
+        );
+        let folders: Vec<(&str, Option<&str>)> = state
+            .playlists
+            .iter()
+            .map(|p| (p.name.as_str(), p.folder.as_deref()))
+            .collect();
+        assert!(folders.contains(&("Playlist 0", None)));
+        assert!(folders.contains(&("Playlist 1", Some("Sport/Running"))));
+        assert!(folders.contains(&("Playlist 3", None)));
+        drop(state);
+
+        // the folders exist, nothing to create
+        api.state.lock().unwrap().writes.clear();
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        assert!(!api.state.lock().unwrap().writes.iter().any(|w| w.contains("folder")));
+
+        // ignored by the platforms without folders
+        let api = planned_dst_api();
+        synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        let state = api.state.lock().unwrap();
+        assert!(!state.writes.iter().any(|w| w.contains("folder")));
+        assert_eq!(state.playlists.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_apply_plan() {
+        let api = planned_dst_api();
+        let (plan, report) = make_plan(&api, "sync_dis_boi_apply.json").await;
+
+        // the plan is applied without searching, the catalog is not used anymore
+        let mut apply_api = api.clone();
+        apply_api.catalog = Arc::new(vec![]);
+        let config = config(&["--apply", report.to_str().unwrap()]);
+        apply_plan(&report, &mut apply_api.boxed(), &config).await.unwrap();
+
+        let state = api.state.lock().unwrap();
+        assert_eq!(state.playlists.len(), 4);
+        for planned in &plan.playlists {
+            let created = state.playlists.iter().find(|p| p.name == planned.name).unwrap();
+            let expected = planned.dst_song_count + planned.additions.len();
+            assert_eq!(created.songs.len(), expected);
+        }
+        assert_eq!(state.writes.iter().filter(|w| w.starts_with("create_playlist")).count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_apply_plan_validation() {
+        let api = planned_dst_api();
+        let (_, report) = make_plan(&api, "sync_dis_boi_apply_invalid.json").await;
+        let config = config(&["--apply", report.to_str().unwrap()]);
+
+        // the destination playlist changed since planning
+        let extra = songs(&MusicApiType::Plex, "extra", 5);
+        api.state.lock().unwrap().playlists[0].songs.extend(extra);
+        let err = apply_plan(&report, &mut api.boxed(), &config).await.unwrap_err();
+        assert!(err.to_string().contains("changed too much since planning"));
+
+        // a playlist to create already exists
+        api.state.lock().unwrap().playlists[0].songs.truncate(1);
+        api.state
+            .lock()
+            .unwrap()
+            .playlists
+            .push(playlist("d1", "Playlist 2", vec![]));
+        let err = apply_plan(&report, &mut api.boxed(), &config).await.unwrap_err();
+        assert!(err.to_string().contains("was created since planning"));
+
+        assert!(api.state.lock().unwrap().writes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_mode() {
+        let src = vec![playlist("p0", "Favorites", songs(&MusicApiType::Spotify, "fav", 2))];
+        let mut dst_songs = songs(&MusicApiType::Plex, "fav", 3);
+        dst_songs.push(song(MusicApiType::Plex, "gone0", "Removed from the source"));
+        let api = MockApi::new(MusicApiType::Plex, vec![]);
+        {
+            let mut state = api.state.lock().unwrap();
+            state.playlists.push(playlist("d0", "Favorites", dst_songs));
+            state.playlists.push(playlist("d1", "Only on destination", songs(&MusicApiType::Plex, "dst", 2)));
+        }
+        let ids = |i: usize| -> Vec<String> {
+            let state = api.state.lock().unwrap();
+            state.playlists[i].songs.iter().map(|s| s.id.clone()).collect()
+        };
+
+        // additive mode never removes songs
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(ids(0).len(), 4);
+
+        let mirror = config(&["--mode", "mirror"]);
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &mirror, "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(ids(0), vec!["fav0", "fav1"]);
+        assert_eq!(ids(1), vec!["dst0", "dst1"]);
+        assert_eq!(
+            api.state.lock().unwrap().writes,
+            vec!["remove_songs_from_playlist Favorites 2"]
+        );
+
+        // platforms without removal support are refused before any change
+        let mut no_removal = api.clone();
+        no_removal.removal = false;
+        let err = synchronize_playlists(src, SRC, &mut no_removal.boxed(), &mirror, "owner".to_string())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--mode mirror"));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_dst() {
+        let src = vec![playlist("p0", "Favorites", songs(&MusicApiType::Spotify, "fav", 2))];
+        let mut dst_songs = songs(&MusicApiType::Plex, "fav", 2);
+        // the single of fav0, and fav1 twice
+        dst_songs.push(dst_songs[0].with_id(MusicApiType::Plex, "single0"));
+        dst_songs.push(dst_songs[1].clone());
+        let api = MockApi::new(MusicApiType::Plex, vec![]);
+        api.state.lock().unwrap().playlists.push(playlist("d0", "Favorites", dst_songs));
+        let ids = || -> Vec<String> {
+            let state = api.state.lock().unwrap();
+            state.playlists[0].songs.iter().map(|s| s.id.clone()).collect()
+        };
+
+        let dry_run = config(&["--dedup-dst", "--dry-run"]);
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &dry_run, "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(ids().len(), 4);
+
+        // fav1 can't be removed once without an occurrence id
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&["--dedup-dst"]), "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(ids(), vec!["fav0", "fav1", "fav1"]);
+        assert_eq!(
+            api.state.lock().unwrap().writes,
+            vec!["remove_songs_from_playlist Favorites 1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_match_cache() {
+        let config_dir = test_dir("match_cache");
+        let config = |args: &[&str]| {
+            let mut full = vec!["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap()];
+            full.extend_from_slice(args);
+            ConfigArgs::parse_from(full)
+        };
+        let sync = |api: MockApi, config: ConfigArgs| async move {
+            synchronize_playlists(src_playlists(), SRC, &mut api.boxed(), &config, "owner".to_string())
+                .await
+                .unwrap();
+        };
+
+        let api = dst_api(true);
+        sync(api.clone(), config(&[])).await;
+        assert_synchronized(&api);
+        assert!(config_dir.join(MatchCache::FILE_NAME).exists());
+
+        // cached songs are not searched again
+        let mut cached_api = dst_api(true);
+        cached_api.catalog = Arc::new(vec![]);
+        sync(cached_api.clone(), config(&[])).await;
+        assert_synchronized(&cached_api);
+
+        // a cached song refused by the destination is forgotten
+        let mut refusing_api = dst_api(true);
+        refusing_api.rejected = vec!["ds00".to_string()];
+        sync(refusing_api, config(&[])).await;
+        let mut cached_api = dst_api(true);
+        cached_api.catalog = Arc::new(vec![]);
+        sync(cached_api.clone(), config(&[])).await;
+        {
+            let state = cached_api.state.lock().unwrap();
+            assert_eq!(state.playlists[0].songs.len(), 2);
+            assert_eq!(state.playlists[1].songs.len(), 3);
+        }
+
+        // without the cache, songs are searched again
+        let mut uncached_api = dst_api(true);
+        uncached_api.catalog = Arc::new(vec![]);
+        sync(uncached_api.clone(), config(&["--no-cache"])).await;
+        assert!(uncached_api.state.lock().unwrap().playlists.iter().all(|p| p.songs.is_empty()));
+        sync(uncached_api.clone(), config(&["--clear-match-cache"])).await;
+        let cache = MatchCache::load(&config_dir.join(MatchCache::FILE_NAME)).unwrap();
+        assert!(cache.matches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_overrides() {
+        let path = test_dir("overrides").join("overrides.toml");
+        std::fs::write(
+            &path,
+            r#"
+blacklist = ["spotify:s02"]
+
+[overrides."spotify:s00"]
+plex = "custom0"
+tidal = "t0"
+
+[overrides."spotify:s01"]
+plex = "rejected"
+"#,
+        )
+        .unwrap();
+        let config = config(&["--overrides", path.to_str().unwrap()]);
+        let mut api = dst_api(true);
+        api.rejected = vec!["rejected".to_string()];
+        synchronize_playlists(src_playlists(), SRC, &mut api.clone().boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+
+        {
+            let state = api.state.lock().unwrap();
+            let ids: Vec<&str> = state.playlists[0].songs.iter().map(|s| s.id.as_str()).collect();
+            assert_eq!(ids, vec!["custom0"]);
+            assert_eq!(state.playlists[1].songs.len(), 3);
+            // overridden and blacklisted songs are not searched
+            assert_eq!(state.searches, 9);
+        }
+
+        // a typo'd file stops the synchronization
+        std::fs::write(&path, "[overrides.\"spotify:s00\"]\nplx = \"custom0\"\n").unwrap();
+        let res = synchronize_playlists(src_playlists(), SRC, &mut dst_api(true).boxed(), &config, "owner".to_string()).await;
+        let err = res.unwrap_err().to_string();
+        assert!(err.contains("unknown platform \"plx\"") && err.contains("line 2"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let config_dir = test_dir("resume");
+        let config = |args: &[&str]| {
+            let mut full = vec!["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap(), "--no-cache"];
+            full.extend_from_slice(args);
+            ConfigArgs::parse_from(full)
+        };
+        let mut src = vec![
+            playlist("p0", "Small", songs(&MusicApiType::Spotify, "small", 3)),
+            playlist("p1", "Big", songs(&MusicApiType::Spotify, "big", 120)),
+        ];
+        // the names of `songs` get too close to each other past a few dozens
+        for (i, song) in src[1].songs.iter_mut().enumerate() {
+            let mut hasher = std::hash::DefaultHasher::new();
+            std::hash::Hash::hash(&i, &mut hasher);
+            song.name = format!("big {:x}", std::hash::Hasher::finish(&hasher));
+        }
+        let catalog: Vec<Song> = src
+            .iter()
+            .flat_map(|p| &p.songs)
+            .map(|s| song(MusicApiType::Plex, &format!("d{}", s.id), &s.name))
+            .collect();
+
+        // interrupted while adding the second chunk of the big playlist
+        let mut api = MockApi::new(MusicApiType::Plex, catalog);
+        api.fail_on_write = Some(4);
+        let err = synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("interrupted"));
+        let checkpoint_path = config_dir.join(Checkpoint::FILE_NAME);
+        let checkpoint = Checkpoint::load(&checkpoint_path, Some(MusicApiType::Spotify), MusicApiType::Plex).unwrap();
+        assert!(checkpoint.is_complete("p0"));
+        assert_eq!(checkpoint.get("p1").unwrap().songs_added, ADD_CHUNK_SIZE);
+        assert!(!checkpoint.is_complete("p1"));
+
+        // a checkpoint of another platform pair is ignored
+        let other = Checkpoint::load(&checkpoint_path, Some(MusicApiType::Tidal), MusicApiType::Plex).unwrap();
+        assert!(other.playlists.is_empty());
+
+        api.fail_on_write = None;
+        api.state.lock().unwrap().searches = 0;
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &config(&["--resume"]), "owner".to_string())
+            .await
+            .unwrap();
+
+        // same end state as an uninterrupted run
+        let uninterrupted = MockApi::new(MusicApiType::Plex, api.catalog.to_vec());
+        synchronize_playlists(src.clone(), SRC, &mut uninterrupted.boxed(), &config(&[]), "owner".to_string())
+            .await
+            .unwrap();
+        let ids = |api: &MockApi| -> Vec<Vec<String>> {
+            let state = api.state.lock().unwrap();
+            state
+                .playlists
+                .iter()
+                .map(|p| p.songs.iter().map(|s| s.id.clone()).collect())
+                .collect()
+        };
+        assert_eq!(ids(&api), ids(&uninterrupted));
+
+        // the complete playlist and the songs already added are not searched again
+        let searches = api.state.lock().unwrap().searches;
+        assert!(searches <= 120 - ADD_CHUNK_SIZE);
+        assert_eq!(uninterrupted.state.lock().unwrap().searches, searches + ADD_CHUNK_SIZE + 3);
+        assert!(!checkpoint_path.exists());
+    }
+
+    #[test]
+    fn test_playlist_filter() {
+        let names = |playlists: Vec<Playlist>| -> Vec<String> { playlists.into_iter().map(|p| p.name).collect() };
+
+        let filter = PlaylistFilter {
+            names: vec!["playlist 1".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(names(filter.select(src_playlists()).unwrap()), vec!["Playlist 1"]);
+
+        let filter = PlaylistFilter {
+            names: vec!["Playlist 0".to_string()],
+            regex: Some(Regex::new("[23]$").unwrap()),
+            skip: vec!["PLAYLIST 3".to_string()],
+        };
+        assert_eq!(
+            names(filter.select(src_playlists()).unwrap()),
+            vec!["Playlist 0", "Playlist 2"]
+        );
+
+        // skipping everything is not an error
+        let filter = PlaylistFilter {
+            skip: src_playlists().into_iter().map(|p| p.name).collect(),
+            ..Default::default()
+        };
+        assert!(filter.select(src_playlists()).unwrap().is_empty());
+
+        let filter = PlaylistFilter {
+            regex: Some(Regex::new("^Mix").unwrap()),
+            ..Default::default()
+        };
+        let err = filter.select(src_playlists()).unwrap_err();
+        assert!(err.to_string().contains("\"Playlist 0\", \"Playlist 1\""));
+    }
+
+    /// Songs with names distinct enough not to be matched with each other
+    fn songs(source: &MusicApiType, prefix: &str, n: u64) -> Vec<Song> {
+        (0..n)
+            .map(|i| {
+                let name = format!("{} {:x}", prefix, (i + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                song(source.clone(), &format!("{}{}", prefix, i), &name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_suspicious_pairing() {
+        let src = songs(&MusicApiType::Spotify, "fav", 5);
+        let unrelated = songs(&MusicApiType::Plex, "other", 40);
+        let mut related = unrelated.clone();
+        related.extend(songs(&MusicApiType::Plex, "fav", 1));
+
+        // much bigger and no overlap
+        assert_eq!(suspicious_pairing(&src, &unrelated, 3.0, 0.1), Some(0.0));
+        // much bigger but sharing songs
+        assert_eq!(suspicious_pairing(&src, &related, 3.0, 0.1), None);
+        assert_eq!(suspicious_pairing(&src, &related, 3.0, 0.5), Some(0.2));
+        // similar sizes are never suspicious
+        assert_eq!(suspicious_pairing(&src, &unrelated[..10], 3.0, 0.1), None);
+        assert_eq!(suspicious_pairing(&src, &[], 3.0, 0.1), None);
+
+        // big playlists are sampled
+        let big_src = songs(&MusicApiType::Spotify, "big", 200);
+        let big_dst = songs(&MusicApiType::Plex, "big", 600);
+        assert_eq!(suspicious_pairing(&big_src, &big_dst, 3.0, 0.1), None);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_pairing_skipped() {
+        let src = vec![playlist("p0", "Favorites", songs(&MusicApiType::Spotify, "fav", 3))];
+        let api = MockApi::new(MusicApiType::Plex, songs(&MusicApiType::Plex, "fav", 3));
+        api.state
+            .lock()
+            .unwrap()
+            .playlists
+            .push(playlist("d0", "Favorites", songs(&MusicApiType::Plex, "other", 20)));
+
+        let dst_playlists = api.state.lock().unwrap().playlists.clone();
+
+        let unconfirmed = config(&[]);
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &unconfirmed, "owner".to_string())
+            .await
+            .unwrap();
+        assert!(api.state.lock().unwrap().writes.is_empty());
+        // the pairing is recorded for the user to confirm it
+        let mapping_path = unconfirmed.config_dir().unwrap().join(PlaylistMapping::FILE_NAME);
+        let mut mapping = PlaylistMapping::load(&mapping_path).unwrap();
+        let link = mapping.playlists.get_mut("p0").unwrap().get_mut("plex").unwrap();
+        assert_eq!((link.id.as_deref(), link.confirmed), (Some("d0"), false));
+        link.confirmed = true;
+        mapping.save(&mapping_path).unwrap();
+        synchronize_playlists(src.clone(), SRC, &mut api.boxed(), &unconfirmed, "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(api.state.lock().unwrap().playlists[0].songs.len(), 23);
+
+        api.state.lock().unwrap().playlists = dst_playlists;
+        let config = config(&["--force-pairing"]);
+        synchronize_playlists(src, SRC, &mut api.boxed(), &config, "owner".to_string())
+            .await
+            .unwrap();
+        assert_eq!(api.state.lock().unwrap().playlists[0].songs.len(), 23);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_playlists_unsupported() {
+        let api = dst_api(false);
+        run(&api, "4").await;
+        assert_synchronized(&api);
+        assert_eq!(api.state.lock().unwrap().max_in_flight, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_albums() {
+        let spotify = MusicApiType::Spotify;
+        let tidal = MusicApiType::Tidal;
+        let src = MockApi::new(spotify.clone(), vec![]);
+        src.state.lock().unwrap().saved_albums = vec![
+            saved_album(&spotify, "s1", "Homogenic", "Björk", Some("75992248722")),
+            saved_album(&spotify, "s2", "OK Computer", "Radiohead", None),
+            saved_album(&spotify, "s3", "Discovery", "Daft Punk", None),
+            saved_album(&spotify, "s4", "Unknown Demos", "Nobody", None),
+        ];
+        let mut dst = MockApi::new(tidal.clone(), vec![]);
+        dst.album_catalog = Arc::new(vec![
+            // found by UPC despite the different name
+            saved_album(&tidal, "t1", "Homogenic (Remastered)", "Bjork", Some("75992248722")),
+            saved_album(&tidal, "t2", "OK Computer OKNOTOK", "Radiohead", None),
+            saved_album(&tidal, "t3", "Discovery", "Daft Punk", None),
+        ]);
+        dst.state.lock().unwrap().saved_albums = vec![saved_album(&tidal, "t3", "Discovery", "Daft Punk", None)];
+
+        sync_albums(&mut src.boxed(), &mut dst.boxed(), &config(&["--dry-run"])).await.unwrap();
+        assert!(dst.state.lock().unwrap().writes.is_empty());
+
+        sync_albums(&mut src.boxed(), &mut dst.boxed(), &config(&[])).await.unwrap();
+        let state = dst.state.lock().unwrap();
+        // the saved album isn't searched again
+        assert_eq!(state.searches, 6);
+        assert_eq!(state.writes, vec!["add_saved_albums 1"]);
+        let ids: Vec<&str> = state.saved_albums.iter().filter_map(|a| a.album.id.as_deref()).collect();
+        assert_eq!(ids, vec!["t3", "t1"]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_artists() {
+        let src = MockApi::new(MusicApiType::Spotify, vec![]);
+        src.state.lock().unwrap().followed_artists = vec![
+            artist("s1", "Björk"),
+            artist("s2", "Radiohead"),
+            artist("s3", "Nobody"),
+        ];
+        let mut dst = MockApi::new(MusicApiType::Tidal, vec![]);
+        dst.artist_catalog = Arc::new(vec![artist("t1", "Bjork"), artist("t2", "Radiohead")]);
+        dst.state.lock().unwrap().followed_artists = vec![artist("t2", "Radiohead")];
+
+        sync_artists(&mut src.boxed(), &mut dst.boxed(), &config(&[])).await.unwrap();
+        {
+            let state = dst.state.lock().unwrap();
+            assert_eq!(state.searches, 2);
+            assert_eq!(state.writes, vec!["follow_artists 1"]);
+            assert_eq!(state.followed_artists[1].id.as_deref(), Some("t1"));
+        }
+
+        // platforms without followed artists are skipped
+        let mut plex = MockApi::new(MusicApiType::Plex, vec![]);
+        plex.library = false;
+        sync_artists(&mut src.boxed(), &mut plex.boxed(), &config(&[])).await.unwrap();
+        sync_albums(&mut plex.boxed(), &mut dst.boxed(), &config(&[])).await.unwrap();
+        assert_eq!(dst.state.lock().unwrap().searches, 2);
+    }
+}
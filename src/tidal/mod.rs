@@ -1,33 +1,55 @@
 mod model;
 mod response;
 
-use std::collections::HashMap;
-use std::io::Read;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
 use model::{TidalMediaResponse, TidalMediaResponseSingle, TidalOAuthDeviceRes};
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, HeaderValue};
 use serde::de::{DeserializeOwned, IgnoredAny};
 use serde_json::json;
 use tracing::{info, warn};
 
 use self::model::{TidalPageResponse, TidalPlaylistResponse, TidalSongItemResponse};
 use crate::ConfigArgs;
+use crate::retry::{self, RetryPolicy};
 use crate::music_api::{
-    MusicApi, MusicApiType, OAuthRefreshToken, OAuthReqToken, OAuthToken, PLAYLIST_DESC, Playlist,
-    Playlists, Song, Songs,
+    Artist, DeviceFlowPoll, DynMusicApi, MusicApi, MusicApiType, OAuthErrorRes,
+    OAuthRefreshToken, OAuthReqToken, OAuthToken, Playlist, Playlists, SavedAlbum, Song, Songs,
+    push_candidates,
 };
-use crate::tidal::model::{TidalPlaylistCreateResponse, TidalSearchResponse};
-use crate::utils::debug_response_json;
+use crate::tidal::model::{
+    TidalAddItemsResponse, TidalAlbumSearchResponse, TidalArtistResponse,
+    TidalArtistSearchResponse, TidalCursorPageResponse, TidalFavoriteItemResponse, TidalFolderItemResponse,
+    TidalFolderPageResponse, TidalFullAlbumResponse, TidalMediaRelationshipData,
+    TidalPlaylistCreateResponse, TidalSearchResponse, TidalSearchResultsResponse,
+};
+use crate::utils::{debug_response_json, save_error_diagnostic};
 
+#[derive(Clone)]
 pub struct TidalApi {
     client: reqwest::Client,
     config: ConfigArgs,
     user_id: String,
+    /// Owner of the playlists of the user, their username
+    username: String,
     country_code: String,
+    /// Base URL of the v1 and my-collection endpoints
+    api_url: String,
+    /// Base URL of the JSON:API endpoints
+    api_v2_url: String,
+    /// Id of the `--tidal-folder` folder, once found or created
+    folder_id: Option<String>,
+    /// Ids of the folders of the collection by path, once listed or created, shared by the
+    /// handles
+    folder_ids: Arc<Mutex<HashMap<String, String>>>,
+    /// Time spent waiting before retries, shared by the handles
+    backoff_time: Arc<Mutex<Duration>>,
 }
 
 #[derive(Debug)]
@@ -35,16 +57,27 @@ enum HttpMethod<'a> {
     Get(&'a serde_json::Value),
     Post(&'a serde_json::Value),
     Put(&'a serde_json::Value),
+    Delete(&'a serde_json::Value),
 }
 
 impl TidalApi {
     const API_URL: &'static str = "https://api.tidal.com";
     const API_V2_URL: &'static str = "https://openapi.tidal.com/v2";
+    /// The waits double from 2 seconds, up to 5 minutes
+    const RETRY_POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 5,
+        initial_backoff: Duration::from_secs(2),
+        max_backoff: Duration::from_mins(5),
+    };
 
     const AUTH_URL: &'static str = "https://auth.tidal.com/v1/oauth2/device_authorization";
     const TOKEN_URL: &'static str = "https://auth.tidal.com/v1/oauth2/token";
     const SCOPE: &'static str = "r_usr w_usr w_sub";
     const RES_DEBUG_FILENAME: &'static str = MusicApiType::Tidal.short_name();
+    /// Songs added to a playlist with a single request, long lists of ids are rejected
+    const ADD_CHUNK_SIZE: usize = 100;
+    /// Retries of a chunk when the playlist was modified since its `ETag` was fetched
+    const MAX_ETAG_RETRIES: usize = 3;
 
     pub async fn new(
         client_id: &str,
@@ -89,13 +122,20 @@ impl TidalApi {
         if !status.is_success() {
             return Err(eyre!("Invalid HTTP status: {}", status));
         }
+        let username = Self::user_display_name(&me_res);
         let country_code = me_res.data.attributes.country.unwrap_or("US".into());
 
         Ok(Self {
             client,
             config,
             user_id: me_res.data.id,
+            username,
             country_code,
+            api_url: Self::API_URL.to_string(),
+            api_v2_url: Self::API_V2_URL.to_string(),
+            folder_id: None,
+            folder_ids: Arc::new(Mutex::new(HashMap::new())),
+            backoff_time: Arc::new(Mutex::new(Duration::ZERO)),
         })
     }
 
@@ -109,43 +149,34 @@ impl TidalApi {
             "client_id": client_id,
             "scope": Self::SCOPE,
         });
-        let res = client.post(Self::AUTH_URL).form(&params).send().await?;
-        let status = res.status();
         let device_res: TidalOAuthDeviceRes =
-            debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
-        }
+            Self::make_oauth_request(&client, Self::AUTH_URL, &params, None, config).await?;
 
         let url = format!("https://{}", device_res.verification_uri_complete);
 
         if webbrowser::open(&url).is_err() {
             info!("Please authorize the app by visiting the following URL: {}", url);
-        } else {
-            info!("Please authorize the app in your browser and press enter");
         }
 
-        std::io::stdin().read_exact(&mut [0])?;
-
         let auth_token = OAuthReqToken {
             client_id: client_id.to_string(),
             device_code: device_res.device_code.clone(),
             grant_type: "urn:ietf:params:oauth:grant-type:device_code".to_string(),
             scope: Self::SCOPE.to_string(),
         };
-        let res = client
-            .post(Self::TOKEN_URL)
-            .basic_auth(client_id, Some(client_secret))
-            .form(&auth_token)
-            .send()
-            .await?;
-        let status = res.status();
-        let token: OAuthToken = debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
-        }
-
-        Ok(token)
+        let mut poll = DeviceFlowPoll::new(device_res.interval, device_res.expires_in)
+            .with_error_mapping(Self::oauth_error);
+        poll.run(
+            || {
+                client
+                    .post(Self::TOKEN_URL)
+                    .basic_auth(client_id, Some(client_secret))
+                    .form(&auth_token)
+            },
+            config,
+            Self::RES_DEBUG_FILENAME,
+        )
+        .await
     }
 
     async fn refresh_token(
@@ -165,13 +196,8 @@ impl TidalApi {
             "refresh_token": &oauth_token.refresh_token,
         });
 
-        let res = client.post(Self::TOKEN_URL).form(&params).send().await?;
-        let status = res.status();
         let refresh_token: OAuthRefreshToken =
-            debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
-        }
+            Self::make_oauth_request(&client, Self::TOKEN_URL, &params, None, config).await?;
 
         oauth_token.access_token = refresh_token.access_token;
         oauth_token.expires_in = refresh_token.expires_in;
@@ -179,6 +205,59 @@ impl TidalApi {
         Ok(oauth_token)
     }
 
+    /// Post a form to an OAuth endpoint, with the client credentials when given. The status
+    /// is checked before parsing the response, the failed ones are saved in the debug directory
+    async fn make_oauth_request<T>(
+        client: &reqwest::Client,
+        url: &str,
+        form: &impl serde::Serialize,
+        basic_auth: Option<(&str, &str)>,
+        config: &ConfigArgs,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut request = client.post(url).form(form);
+        if let Some((user, password)) = basic_auth {
+            request = request.basic_auth(user, Some(password));
+        }
+        let res = request.send().await?;
+        let status = res.status();
+        if status.is_success() {
+            return debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await;
+        }
+        let text = res.text().await?;
+        let error_file = save_error_diagnostic(config, status.as_u16(), &text)?;
+        Err(eyre!(
+            "{}\nResponse saved to: {}",
+            Self::oauth_error(status, &text),
+            error_file.display()
+        ))
+    }
+
+    /// Actionable error for a failed OAuth request, the device flow errors (pending, expired or
+    /// denied authorization) are handled by `DeviceFlowPoll`
+    fn oauth_error(status: reqwest::StatusCode, text: &str) -> color_eyre::Report {
+        let Ok(res) = serde_json::from_str::<OAuthErrorRes>(text) else {
+            return eyre!("Tidal authentication failed with HTTP status {}", status);
+        };
+        match res.error.as_str() {
+            "invalid_client" | "unauthorized_client" => eyre!(
+                "Tidal rejected the client id or secret, check --client-id and --client-secret \
+                (TIDAL_CLIENT_ID and TIDAL_CLIENT_SECRET)"
+            ),
+            "invalid_grant" => eyre!(
+                "The saved Tidal token is no longer valid, run SyncDisBoi again with \
+                --clear-cache to authorize the app again"
+            ),
+            error => eyre!(
+                "Tidal authentication failed ({}): {}",
+                status,
+                res.error_description.as_deref().unwrap_or(error)
+            ),
+        }
+    }
+
     async fn paginated_request<T>(
         &self,
         url: &str,
@@ -209,25 +288,88 @@ impl TidalApi {
         Ok(res)
     }
 
-    async fn make_request_json<T>(
+    /// Send a request, retrying it after rate limits and server errors
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let policy = self.config.retry_policy(
+            &Self::RETRY_POLICY,
+            self.config.tidal_max_retries,
+            self.config.tidal_max_backoff_secs,
+        );
+        retry::send(request, &policy, "Tidal", &self.backoff_time).await
+    }
+
+    fn build_request(
         &self,
         url: &str,
         method: &HttpMethod<'_>,
         lim_off: Option<(usize, usize)>,
-    ) -> Result<T>
-    where
-        T: DeserializeOwned,
-    {
+    ) -> reqwest::RequestBuilder {
         let mut request = match method {
             HttpMethod::Get(p) => self.client.get(url).query(p),
             HttpMethod::Post(b) => self.client.post(url).form(b),
             HttpMethod::Put(b) => self.client.put(url).form(b),
+            HttpMethod::Delete(p) => self.client.delete(url).query(p),
         };
         if let Some((limit, offset)) = lim_off {
             request = request.query(&[("limit", limit), ("offset", offset)]);
         }
+        request
+    }
 
-        let res = request.send().await?;
+    /// Send a request whose response has no useful body, returning its status and raw body
+    async fn make_request_status(
+        &self,
+        url: &str,
+        method: &HttpMethod<'_>,
+    ) -> Result<(reqwest::StatusCode, String)> {
+        let res = self.send(self.build_request(url, method, None)).await?;
+        let status = res.status();
+        let body = res.text().await?;
+        Ok((status, body))
+    }
+
+    /// Request all the pages of a JSON:API response, following their cursors. The data and
+    /// the included resources of the pages are merged
+    async fn cursor_paginated_request<T>(
+        &self,
+        url: &str,
+        params: &serde_json::Value,
+    ) -> Result<TidalCursorPageResponse<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut res: TidalCursorPageResponse<T> = self
+            .make_request_json(url, &HttpMethod::Get(params), None)
+            .await?;
+        let mut cursor = res.next_cursor();
+        while let Some(current) = cursor {
+            let mut params = params.clone();
+            params["page[cursor]"] = json!(current);
+            let page: TidalCursorPageResponse<T> = self
+                .make_request_json(url, &HttpMethod::Get(&params), None)
+                .await?;
+            if page.data.is_empty() {
+                break;
+            }
+            cursor = page.next_cursor().filter(|next| *next != current);
+            res.data.extend(page.data);
+            if let Some(included) = page.included {
+                res.included.get_or_insert_default().extend(included);
+            }
+        }
+        Ok(res)
+    }
+
+    async fn make_request_json<T>(
+        &self,
+        url: &str,
+        method: &HttpMethod<'_>,
+        lim_off: Option<(usize, usize)>,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let res = self.send(self.build_request(url, method, lim_off)).await?;
         let status = res.status();
         let obj = debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
         if !status.is_success() {
@@ -235,6 +377,329 @@ impl TidalApi {
         }
         Ok(obj)
     }
+
+    /// Items of a folder of the collection, `"root"` for its top level, filtered by their
+    /// `FOLDER` or `PLAYLIST` item type
+    async fn get_folder_items(
+        &self,
+        folder_id: &str,
+        include_only: &str,
+    ) -> Result<Vec<TidalFolderItemResponse>> {
+        let url = format!("{}/v2/my-collection/playlists/folders", self.api_url);
+        let mut items = vec![];
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut params = json!({
+                "folderId": folder_id,
+                "countryCode": self.country_code,
+                "includeOnly": include_only,
+                "order": "DATE",
+                "orderDirection": "ASC",
+                "limit": 50,
+            });
+            if let Some(cursor) = &cursor {
+                params["cursor"] = json!(cursor);
+            }
+            let res: TidalFolderPageResponse = self
+                .make_request_json(&url, &HttpMethod::Get(&params), None)
+                .await?;
+            let last_page = res.items.is_empty();
+            items.extend(res.items);
+            match res.cursor {
+                Some(next) if !last_page && !next.is_empty() && cursor.as_ref() != Some(&next) => {
+                    cursor = Some(next);
+                }
+                _ => break,
+            }
+        }
+        Ok(items)
+    }
+
+    /// Id of the folder where the playlists are created, the `--tidal-folder` one is looked
+    /// up and created when missing
+    async fn destination_folder(&mut self) -> Result<String> {
+        let Some(name) = self.config.tidal_folder.clone() else {
+            return Ok("root".to_string());
+        };
+        if let Some(id) = &self.folder_id {
+            return Ok(id.clone());
+        }
+
+        let existing = self
+            .get_folder_items("root", "FOLDER")
+            .await?
+            .into_iter()
+            .find_map(|item| match item {
+                TidalFolderItemResponse::Folder(folder) if folder.name == name => Some(folder.id),
+                _ => None,
+            });
+        let id = if let Some(id) = existing {
+            id
+        } else {
+            info!("creating Tidal folder \"{}\"", name);
+            let url = format!(
+                "{}/v2/my-collection/playlists/folders/create-folder",
+                self.api_url
+            );
+            let params = json!({
+                "name": name,
+                "folderId": "root",
+                "trns": "",
+            });
+            let res: TidalFolderItemResponse = self
+                .make_request_json(&url, &HttpMethod::Put(&params), None)
+                .await?;
+            match res {
+                TidalFolderItemResponse::Folder(folder) => folder.id,
+                _ => return Err(eyre!("failed to create Tidal folder \"{}\"", name)),
+            }
+        };
+        self.folder_id = Some(id.clone());
+        Ok(id)
+    }
+
+    /// Id of the folder `path`, `"root"` for the top level
+    async fn folder_path_id(&mut self, path: &str) -> Result<String> {
+        if path.is_empty() {
+            return Ok("root".to_string());
+        }
+        if !self.folder_ids.lock().unwrap().contains_key(path) {
+            self.get_folders().await?;
+        }
+        self.folder_ids
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| eyre!("Tidal folder \"{}\" not found", path))
+    }
+
+    /// Tracks with their albums and artists, by id. The missing ones are not returned
+    async fn get_tracks(&self, ids: &[&str]) -> Result<HashMap<String, Song>> {
+        let url = format!("{}/tracks", self.api_v2_url);
+        let mut found: HashMap<String, Song> = HashMap::new();
+        // NOTE: the v2 API accepts at most 20 ids per filter
+        for ids_chunk in ids.chunks(20) {
+            let params = json!({
+                "countryCode": self.country_code,
+                "include": "albums,artists",
+                "filter[id]": ids_chunk.join(","),
+            });
+            let res: TidalMediaResponse = self
+                .make_request_json(&url, &HttpMethod::Get(&params), None)
+                .await?;
+            if res.data.is_empty() {
+                continue;
+            }
+            let res_songs: Songs = res.try_into()?;
+            found.extend(res_songs.0.into_iter().map(|s| (s.id.clone(), s)));
+        }
+        Ok(found)
+    }
+
+    /// Songs of a playlist with the v2 API, its cursors don't skip nor repeat items when the
+    /// playlist is modified during the pagination
+    async fn get_playlist_songs_v2(&self, id: &str) -> Result<Vec<Song>> {
+        let url = format!("{}/playlists/{}/relationships/items", self.api_v2_url, id);
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        let res: TidalCursorPageResponse<TidalMediaRelationshipData> =
+            self.cursor_paginated_request(&url, &params).await?;
+        // videos can be added to playlists too
+        let ids: Vec<&str> = res
+            .data
+            .iter()
+            .filter(|item| item.typ == "tracks")
+            .map(|item| item.id.as_str())
+            .collect();
+        let mut unique_ids = HashSet::new();
+        let lookup_ids: Vec<&str> = ids.iter().copied().filter(|id| unique_ids.insert(*id)).collect();
+        let tracks = self.get_tracks(&lookup_ids).await?;
+        let mut songs = Vec::with_capacity(ids.len());
+        for track_id in ids {
+            if let Some(song) = tracks.get(track_id) {
+                songs.push(song.clone());
+            } else {
+                warn!("track {} of Tidal playlist {} not found, skipping it", track_id, id);
+            }
+        }
+        Ok(songs)
+    }
+
+    async fn get_playlist_songs_v1(&self, id: &str) -> Result<Vec<Song>> {
+        let url = format!("{}/v1/playlists/{}/items", self.api_url, id);
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        // NOTE: a limit > 100 triggers a 400 error
+        let res: TidalPageResponse<TidalSongItemResponse> = self
+            .paginated_request(&url, &HttpMethod::Get(&params), 100)
+            .await?;
+        let songs: Songs = res.try_into()?;
+        Ok(songs.0)
+    }
+
+    /// `ETag` of the current version of a playlist, required to modify it
+    async fn playlist_etag(&self, playlist_id: &str) -> Result<HeaderValue> {
+        let url = format!("{}/v1/playlists/{}", self.api_url, playlist_id);
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        let res = self.send(self.client.get(&url).query(&params)).await?;
+        let status = res.status();
+        let etag = res.headers().get("ETag").cloned();
+        let _: IgnoredAny =
+            debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
+        if !status.is_success() {
+            return Err(eyre!("Invalid HTTP status: {}", status));
+        }
+        etag.ok_or(eyre!("No ETag in Tidal Response"))
+    }
+
+    /// Parameters adding `songs` to a playlist, the tracks already in it and the ones that no
+    /// longer exist are skipped instead of failing the whole request
+    fn add_items_params(songs: &[Song]) -> serde_json::Value {
+        json!({
+            "trackIds": songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>().join(","),
+            "onDuplicate": "SKIP",
+            "onArtifactNotFound": "SKIP",
+        })
+    }
+
+    /// Add a chunk of songs to a playlist with its current `ETag`, fetched again when the
+    /// playlist was modified in the meantime (412)
+    async fn add_chunk(
+        &self,
+        playlist_id: &str,
+        songs: &[Song],
+    ) -> Result<Option<TidalAddItemsResponse>> {
+        let url = format!("{}/v1/playlists/{}/items", self.api_url, playlist_id);
+        let params = Self::add_items_params(songs);
+        let mut conflicts = 0;
+        loop {
+            let etag = self.playlist_etag(playlist_id).await?;
+            let res = self
+                .send(
+                    self.client
+                        .post(&url)
+                        .header("If-None-Match", etag)
+                        .form(&params),
+                )
+                .await?;
+            let status = res.status();
+            if status == reqwest::StatusCode::PRECONDITION_FAILED
+                && conflicts < Self::MAX_ETAG_RETRIES
+            {
+                conflicts += 1;
+                warn!(
+                    "Tidal playlist {} was modified while adding songs, retrying ({}/{})",
+                    playlist_id,
+                    conflicts,
+                    Self::MAX_ETAG_RETRIES
+                );
+                continue;
+            }
+            let res: Option<TidalAddItemsResponse> =
+                debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
+            if !status.is_success() {
+                return Err(eyre!("Invalid HTTP status: {}", status));
+            }
+            return Ok(res);
+        }
+    }
+
+    /// Name of the user in a `users/me` response: their username, their nickname for the
+    /// accounts without one, or their id
+    fn user_display_name(me_res: &TidalMediaResponseSingle) -> String {
+        let attributes = &me_res.data.attributes;
+        [&attributes.username, &attributes.nickname]
+            .into_iter()
+            .flatten()
+            .find(|n| !n.trim().is_empty())
+            .cloned()
+            .unwrap_or_else(|| me_res.data.id.clone())
+    }
+
+    /// Playlists created by the user only have their id as creator, it's replaced by their
+    /// username so the owner is the same as for the playlists created by `create_playlist`
+    fn resolve_owners(playlists: &mut [Playlist], user_id: &str, username: &str) {
+        for playlist in playlists {
+            if playlist.owner.as_deref() == Some(user_id) {
+                playlist.owner = Some(username.to_string());
+            }
+        }
+    }
+
+    /// Songs with the ISRC `isrc`
+    async fn search_isrc(&self, isrc: &str) -> Result<Vec<Song>> {
+        let url = format!("{}/tracks", self.api_v2_url);
+        let params = json!({
+            "countryCode": self.country_code,
+            "include": "albums,artists",
+            "filter[isrc]": isrc.to_uppercase(),
+        });
+        let res: TidalMediaResponse = self
+            .make_request_json(&url, &HttpMethod::Get(&params), Some((1, 0)))
+            .await?;
+        if res.data.is_empty() {
+            return Ok(vec![]);
+        }
+        let songs: Songs = res.try_into()?;
+        Ok(songs.0)
+    }
+
+    /// The first `limit` songs found by a free text search, with the v2 API whose results are
+    /// better for non-Latin scripts and have an ISRC
+    async fn search_query(&self, query: &str, limit: usize) -> Result<Vec<Song>> {
+        match self.search_query_v2(query, limit).await {
+            Ok(songs) => Ok(songs),
+            Err(e) => {
+                warn!("failed to search \"{}\" on Tidal with the v2 API, using v1: {}", query, e);
+                self.search_query_v1(query, limit).await
+            }
+        }
+    }
+
+    async fn search_query_v2(&self, query: &str, limit: usize) -> Result<Vec<Song>> {
+        let url = format!(
+            "{}/searchResults/{}/relationships/tracks",
+            self.api_v2_url,
+            urlencoding::encode(query)
+        );
+        let params = json!({
+            "countryCode": self.country_code,
+            "include": "tracks.albums,tracks.artists",
+        });
+        let res: TidalSearchResultsResponse = self
+            .make_request_json(&url, &HttpMethod::Get(&params), None)
+            .await?;
+        let mut res_songs: Songs = res.try_into()?;
+        res_songs.0.truncate(limit);
+        Ok(res_songs.0)
+    }
+
+    async fn search_query_v1(&self, query: &str, limit: usize) -> Result<Vec<Song>> {
+        let url = format!("{}/v1/search", self.api_url);
+        let params = json!({
+            "countryCode": self.country_code,
+            "query": query,
+            "type": "TRACKS",
+        });
+        let res: TidalSearchResponse = self
+            .make_request_json(&url, &HttpMethod::Get(&params), Some((limit, 0)))
+            .await?;
+        let res_songs: Songs = res.try_into()?;
+        Ok(res_songs.0)
+    }
+}
+
+/// Split the songs sent to a playlist between the ones Tidal added and the ones it skipped
+fn partition_added(songs: &[Song], added_ids: &[String]) -> (Vec<Song>, Vec<Song>) {
+    songs
+        .iter()
+        .cloned()
+        .partition(|s| added_ids.contains(&s.id))
 }
 
 #[async_trait]
@@ -247,16 +712,33 @@ impl MusicApi for TidalApi {
         &self.country_code
     }
 
-    async fn create_playlist(&mut self, name: &str, public: bool) -> Result<Playlist> {
+    fn display_name(&self) -> Option<&str> {
+        Some(&self.username)
+    }
+
+    fn backoff_time(&self) -> Duration {
+        *self.backoff_time.lock().unwrap()
+    }
+
+    fn clone_handle(&self) -> Option<DynMusicApi> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn supports_removal(&self) -> bool {
+        false
+    }
+
+    async fn create_playlist(&mut self, name: &str, description: &str, public: bool) -> Result<Playlist> {
         let url = format!(
             "{}/v2/my-collection/playlists/folders/create-playlist",
-            Self::API_URL
+            self.api_url
         );
+        let folder_id = self.destination_folder().await?;
         let params = json!({
             "name": name,
-            "description": PLAYLIST_DESC,
+            "description": description,
             "public": public,
-            "folderId": "root"
+            "folderId": folder_id,
         });
         let res: TidalPlaylistCreateResponse = self
             .make_request_json(&url, &HttpMethod::Put(&params), Some((5, 0)))
@@ -266,19 +748,159 @@ impl MusicApi for TidalApi {
             id: res.data.uuid,
             name: name.to_string(),
             songs: vec![],
-            owner: Some("".to_string()) // TODO: get the owner
+            owner: Some(self.username.clone()),
+            description: Some(description.to_string()),
+            public: Some(public),
+            generated: false,
+            cover_url: None,
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
         })
     }
 
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        if let Some(description) = description {
+            // the title is required, even when unchanged
+            let url = format!("{}/v1/playlists/{}", self.api_url, playlist.id);
+            let params = json!({
+                "title": playlist.name,
+                "description": description,
+            });
+            let _: IgnoredAny = self
+                .make_request_json(&url, &HttpMethod::Post(&params), None)
+                .await?;
+            playlist.description = Some(description.to_string());
+        }
+        if let Some(public) = public {
+            let action = if public { "set-public" } else { "set-private" };
+            let url = format!("{}/v2/playlists/{}/{}", self.api_url, playlist.id, action);
+            let _: IgnoredAny = self
+                .make_request_json(&url, &HttpMethod::Put(&json!({})), None)
+                .await?;
+            playlist.public = Some(public);
+        }
+        Ok(())
+    }
+
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()> {
+        let url = format!("{}/v1/playlists/{}", self.api_url, playlist.id);
+        let mut params = json!({
+            "title": name,
+        });
+        // the description would be cleared otherwise
+        if let Some(description) = &playlist.description {
+            params["description"] = json!(description);
+        }
+        let _: IgnoredAny = self
+            .make_request_json(&url, &HttpMethod::Post(&params), None)
+            .await?;
+        playlist.name = name.to_string();
+        Ok(())
+    }
+
+    fn supports_folders(&self) -> bool {
+        true
+    }
+
+    async fn get_folders(&mut self) -> Result<Vec<String>> {
+        let mut found = HashMap::new();
+        // folders can contain folders
+        let mut parents = vec![(String::new(), "root".to_string())];
+        while let Some((parent_path, parent_id)) = parents.pop() {
+            for item in self.get_folder_items(&parent_id, "FOLDER").await? {
+                if let TidalFolderItemResponse::Folder(folder) = item {
+                    let path = if parent_path.is_empty() {
+                        folder.name
+                    } else {
+                        format!("{}/{}", parent_path, folder.name)
+                    };
+                    found.insert(path.clone(), folder.id.clone());
+                    parents.push((path, folder.id));
+                }
+            }
+        }
+        let paths = found.keys().cloned().collect();
+        self.folder_ids.lock().unwrap().extend(found);
+        Ok(paths)
+    }
+
+    async fn create_folder(&mut self, path: &str) -> Result<()> {
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let parent_id = self.folder_path_id(parent).await?;
+        let url = format!(
+            "{}/v2/my-collection/playlists/folders/create-folder",
+            self.api_url
+        );
+        let params = json!({
+            "name": name,
+            "folderId": parent_id,
+            "trns": "",
+        });
+        let res: TidalFolderItemResponse = self
+            .make_request_json(&url, &HttpMethod::Put(&params), None)
+            .await?;
+        match res {
+            TidalFolderItemResponse::Folder(folder) => {
+                self.folder_ids.lock().unwrap().insert(path.to_string(), folder.id);
+                Ok(())
+            }
+            _ => Err(eyre!("failed to create Tidal folder \"{}\"", path)),
+        }
+    }
+
+    async fn move_to_folder(&mut self, playlist: &Playlist, path: &str) -> Result<()> {
+        let folder_id = self.folder_path_id(path).await?;
+        let url = format!("{}/v2/my-collection/playlists/folders/move", self.api_url);
+        let params = json!({
+            "folderId": folder_id,
+            "trns": format!("trn:playlist:{}", playlist.id),
+        });
+        let (status, body) = self
+            .make_request_status(&url, &HttpMethod::Put(&params))
+            .await?;
+        if !status.is_success() {
+            return Err(eyre!("HTTP status {}: {}", status, body.trim()));
+        }
+        Ok(())
+    }
+
     async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
-        let url = format!("{}/v1/users/{}/playlists", Self::API_URL, self.user_id);
+        let url = format!("{}/v1/users/{}/playlists", self.api_url, self.user_id);
         let params = json!({
             "countryCode": self.country_code,
         });
         let res: TidalPageResponse<TidalPlaylistResponse> = self
             .paginated_request(&url, &HttpMethod::Get(&params), 100)
             .await?;
-        let playlists: Playlists = res.try_into()?;
+        let mut playlists: Playlists = res.try_into()?;
+
+        // the playlists moved to a folder are not always listed with the others
+        for folder in self.get_folder_items("root", "FOLDER").await? {
+            let TidalFolderItemResponse::Folder(folder) = folder else {
+                continue;
+            };
+            for item in self.get_folder_items(&folder.id, "PLAYLIST").await? {
+                let TidalFolderItemResponse::Playlist(playlist) = item else {
+                    continue;
+                };
+                if playlists.0.iter().any(|p| p.id == playlist.uuid) {
+                    continue;
+                }
+                match playlist.try_into() {
+                    Ok(playlist) => playlists.0.push(playlist),
+                    Err(e) => warn!("failed to parse playlist of folder \"{}\": {}", folder.name, e),
+                }
+            }
+        }
+        Self::resolve_owners(&mut playlists.0, &self.user_id, &self.username);
         
         // Deduplicate playlists by ID to handle Tidal API returning duplicates
         let mut seen_ids = HashMap::new();
@@ -302,16 +924,16 @@ impl MusicApi for TidalApi {
     }
 
     async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>> {
-        let url = format!("{}/v1/playlists/{}/items", Self::API_URL, id);
-        let params = json!({
-            "countryCode": self.country_code,
-        });
-        // NOTE: a limit > 100 triggers a 400 error
-        let res: TidalPageResponse<TidalSongItemResponse> = self
-            .paginated_request(&url, &HttpMethod::Get(&params), 100)
-            .await?;
-        let songs: Songs = res.try_into()?;
-        Ok(songs.0)
+        match self.get_playlist_songs_v2(id).await {
+            Ok(songs) => Ok(songs),
+            Err(e) => {
+                warn!(
+                    "failed to fetch the songs of Tidal playlist {} with the v2 API, using v1: {}",
+                    id, e
+                );
+                self.get_playlist_songs_v1(id).await
+            }
+        }
     }
 
     async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
@@ -319,39 +941,44 @@ impl MusicApi for TidalApi {
             return Ok(());
         }
 
-        // 1. query playlist ETag
-        let url = format!("{}/v1/playlists/{}", Self::API_URL, playlist.id);
-        let params = json!({
-            "countryCode": self.country_code,
-        });
-        let res = self.client.get(&url).query(&params).send().await?;
-        let status = res.status();
-        let etag = res.headers().get("ETag").cloned();
-        let _: IgnoredAny =
-            debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
-        let etag = etag.ok_or(eyre!("No ETag in Tidal Response"))?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
-        }
+        // the same track sent twice would be counted twice
+        let mut unique_ids = HashSet::new();
+        let songs: Vec<Song> = songs
+            .iter()
+            .filter(|s| unique_ids.insert(s.id.clone()))
+            .cloned()
+            .collect();
 
-        // 2. add songs to playlist
-        let url = format!("{}/v1/playlists/{}/items", Self::API_URL, playlist.id);
-        let params = json!({
-            "trackIds": songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>().join(","),
-            "onDuplicate": "FAIL",
-            "onArtifactNotFound": "FAIL",
-        });
-        let res = self
-            .client
-            .post(url)
-            .header("If-None-Match", etag)
-            .form(&params)
-            .send()
-            .await?;
-        let status = res.status();
-        let () = debug_response_json(&self.config, res, Self::RES_DEBUG_FILENAME).await?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
+        for chunk in songs.chunks(Self::ADD_CHUNK_SIZE) {
+            let res = self.add_chunk(&playlist.id, chunk).await.map_err(|e| {
+                eyre!("failed to add songs to Tidal playlist \"{}\": {}", playlist.name, e)
+            })?;
+
+            // account for the skipped tracks
+            let added_ids: Vec<String> = match res.and_then(|r| r.added_item_ids) {
+                Some(ids) => ids.iter().map(ToString::to_string).collect(),
+                None => self
+                    .get_playlist_songs(&playlist.id)
+                    .await?
+                    .into_iter()
+                    .map(|s| s.id)
+                    .collect(),
+            };
+            let (added, skipped) = partition_added(chunk, &added_ids);
+            for song in &skipped {
+                if playlist.songs.iter().any(|s| s.id == song.id) {
+                    info!(
+                        "Tidal skipped track {} ({}) in playlist \"{}\", it is already in it",
+                        song.id, song, playlist.name
+                    );
+                } else {
+                    warn!(
+                        "Tidal skipped track {} ({}) in playlist \"{}\", it no longer exists",
+                        song.id, song, playlist.name
+                    );
+                }
+            }
+            playlist.songs.extend(added);
         }
 
         Ok(())
@@ -362,97 +989,150 @@ impl MusicApi for TidalApi {
         _playlist: &mut Playlist,
         _songs_ids: &[Song],
     ) -> Result<()> {
-        todo!()
+        Err(eyre!("removing songs from Tidal playlists is not supported yet"))
     }
 
     async fn delete_playlist(&mut self, playlist: Playlist) -> Result<()> {
+        let err = |reason: String| {
+            eyre!(
+                "failed to delete Tidal playlist \"{}\" ({}): {}",
+                playlist.name,
+                playlist.id,
+                reason
+            )
+        };
+
         let url = format!(
             "{}/v2/my-collection/playlists/folders/remove",
-            Self::API_URL
+            self.api_url
         );
         let params = json!({
             "trns": format!("trn:playlist:{}", playlist.id),
         });
-        let _: IgnoredAny = self
-            .make_request_json(&url, &HttpMethod::Put(&params), None)
+        let (status, body) = self
+            .make_request_status(&url, &HttpMethod::Put(&params))
+            .await?;
+        if status.is_success() {
+            return Ok(());
+        }
+        // Playlists that were never in the collection folders are not known by their trn
+        if status != reqwest::StatusCode::NOT_FOUND && !is_not_found(&body) {
+            return Err(err(format!("HTTP status {}: {}", status, body.trim())));
+        }
+
+        let url = format!("{}/v1/playlists/{}", self.api_url, playlist.id);
+        let (status, body) = self
+            .make_request_status(&url, &HttpMethod::Delete(&json!({})))
             .await?;
+        if !status.is_success() {
+            return Err(err(format!("HTTP status {}: {}", status, body.trim())));
+        }
         Ok(())
     }
 
     async fn search_song(&mut self, song: &Song) -> Result<Option<Song>> {
         if let Some(isrc) = &song.isrc {
-            let url = format!("{}/tracks", Self::API_V2_URL);
-            let params = json!({
-                "countryCode": self.country_code,
-                "include": "albums,artists",
-                "filter[isrc]": isrc.to_uppercase(),
-            });
-            let res: TidalMediaResponse = self
-                .make_request_json(&url, &HttpMethod::Get(&params), Some((1, 0)))
-                .await?;
-            if res.data.is_empty() {
-                return Ok(None);
-            }
-            let mut res_songs: Songs = res.try_into()?;
-            if res_songs.0.is_empty() {
-                return Ok(None);
+            return Ok(self.search_isrc(isrc).await?.into_iter().next());
+        }
+        // the songs of the ISRC hint are compared like the search results
+        if let Some(isrc) = &song.isrc_hint {
+            let res_songs = self.search_isrc(isrc).await?;
+            if let Some(res_song) = song.best_match(res_songs, &self.config.match_options_over(&self.default_match_options())) {
+                return Ok(Some(res_song));
             }
-            return Ok(Some(res_songs.0.remove(0)));
         }
 
-        let url = format!("{}/v1/search", Self::API_URL);
         let mut queries = song.build_queries();
 
         while let Some(query) = queries.pop() {
-            let params = json!({
-                "countryCode": self.country_code,
-                "query": query,
-                "type": "TRACKS",
-            });
-            let res: TidalSearchResponse = self
-                .make_request_json(&url, &HttpMethod::Get(&params), Some((3, 0)))
-                .await?;
-            let res_songs: Songs = res.try_into()?;
-            // iterate over top 3 results
-            for res_song in res_songs.0.into_iter().take(3) {
-                if song.compare(&res_song) {
-                    return Ok(Some(res_song));
-                }
+            let res_songs = self.search_query(&query, 5).await?;
+            if let Some(res_song) = song.best_match(res_songs, &self.config.match_options_over(&self.default_match_options())) {
+                return Ok(Some(res_song));
             }
         }
         Ok(None)
     }
 
-    async fn add_likes(&mut self, songs: &[Song]) -> Result<()> {
+    async fn search_candidates(&mut self, song: &Song, limit: usize) -> Result<Vec<Song>> {
+        let mut candidates = vec![];
+        let mut queries = song.build_queries();
+        while candidates.len() < limit
+            && let Some(query) = queries.pop()
+        {
+            let res_songs = self.search_query(&query, limit).await?;
+            push_candidates(&mut candidates, res_songs, limit);
+        }
+        Ok(candidates)
+    }
+
+    fn supports_song_lookup(&self) -> bool {
+        true
+    }
+
+    async fn lookup_songs(&mut self, songs: &[Song]) -> Result<Vec<Option<Song>>> {
+        let ids: Vec<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+        let found = self.get_tracks(&ids).await?;
+        Ok(songs.iter().map(|s| found.get(&s.id).cloned()).collect())
+    }
+
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
         if songs.is_empty() {
-            return Ok(());
+            return Ok(vec![]);
         }
 
         let url = format!(
             "{}/v1/users/{}/favorites/tracks",
-            Self::API_URL,
+            self.api_url,
             self.user_id
         );
-        let tracks = songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        let on_not_found = if self.config.tidal_strict_likes { "FAIL" } else { "SKIP" };
 
+        let mut not_added = vec![];
+        let mut last_error = None;
         // NOTE: we get error 500 if we like too much songs at once
-        for tracks_chunk in tracks.chunks(100) {
+        for songs_chunk in songs.chunks(100) {
+            let tracks = songs_chunk.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
             let params = json!({
                 "countryCode": self.country_code,
-                "trackIds": tracks_chunk.join(","),
-                "onArtifactNotFound": "FAIL",
+                "trackIds": tracks.join(","),
+                "onArtifactNotFound": on_not_found,
             });
-            let () = self
+            // the other chunks are still liked when one fails
+            let res: Option<TidalAddItemsResponse> = match self
                 .make_request_json(&url, &HttpMethod::Post(&params), None)
-                .await?;
+                .await
+            {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!("failed to like {} Tidal tracks: {}", songs_chunk.len(), e);
+                    not_added.extend_from_slice(songs_chunk);
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            // without the ids of the liked tracks, they are assumed to be all liked
+            let Some(added_ids) = res.and_then(|r| r.added_item_ids) else {
+                continue;
+            };
+            let added_ids: Vec<String> = added_ids.iter().map(ToString::to_string).collect();
+            let (_, skipped) = partition_added(songs_chunk, &added_ids);
+            for song in &skipped {
+                warn!("Tidal skipped like of track {} ({}), it is not available", song.id, song);
+            }
+            not_added.extend(skipped);
         }
-        Ok(())
+        if not_added.len() == songs.len()
+            && let Some(e) = last_error
+        {
+            return Err(e);
+        }
+        Ok(not_added)
     }
 
     async fn get_likes(&mut self) -> Result<Vec<Song>> {
         let url = format!(
             "{}/v1/users/{}/favorites/tracks",
-            Self::API_URL,
+            self.api_url,
             self.user_id
         );
         let params = json!({
@@ -464,4 +1144,1013 @@ impl MusicApi for TidalApi {
         let songs: Songs = res.try_into()?;
         Ok(songs.0)
     }
+
+    fn supports_like_removal(&self) -> bool {
+        true
+    }
+
+    async fn remove_likes(&mut self, songs: &[Song]) -> Result<()> {
+        let tracks = songs.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        for tracks_chunk in tracks.chunks(100) {
+            let url = format!(
+                "{}/v1/users/{}/favorites/tracks/{}",
+                self.api_url,
+                self.user_id,
+                tracks_chunk.join(",")
+            );
+            let () = self
+                .make_request_json(&url, &HttpMethod::Delete(&params), None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn supports_saved_albums(&self) -> bool {
+        true
+    }
+
+    async fn get_saved_albums(&mut self) -> Result<Vec<SavedAlbum>> {
+        let url = format!(
+            "{}/v1/users/{}/favorites/albums",
+            self.api_url,
+            self.user_id
+        );
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        let res: TidalPageResponse<TidalFavoriteItemResponse<TidalFullAlbumResponse>> = self
+            .paginated_request(&url, &HttpMethod::Get(&params), 1000)
+            .await?;
+        Ok(res.items.into_iter().map(|i| i.item.into()).collect())
+    }
+
+    async fn add_saved_albums(&mut self, albums: &[SavedAlbum]) -> Result<()> {
+        let url = format!(
+            "{}/v1/users/{}/favorites/albums",
+            self.api_url,
+            self.user_id
+        );
+        let ids = albums
+            .iter()
+            .filter_map(|a| a.album.id.as_deref())
+            .collect::<Vec<_>>();
+        for ids_chunk in ids.chunks(100) {
+            let params = json!({
+                "countryCode": self.country_code,
+                "albumIds": ids_chunk.join(","),
+                "onArtifactNotFound": "FAIL",
+            });
+            let () = self
+                .make_request_json(&url, &HttpMethod::Post(&params), None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn search_album(&mut self, album: &SavedAlbum) -> Result<Option<SavedAlbum>> {
+        // the UPC can't be searched, but it's returned with the results
+        let mut query = album.album.name.clone();
+        if let Some(artist) = album.artists.first() {
+            query.push(' ');
+            query.push_str(&artist.name);
+        }
+        let url = format!("{}/v1/search", self.api_url);
+        let params = json!({
+            "countryCode": self.country_code,
+            "query": query,
+            "type": "ALBUMS",
+        });
+        let res: TidalAlbumSearchResponse = self
+            .make_request_json(&url, &HttpMethod::Get(&params), Some((5, 0)))
+            .await?;
+        let results = res.albums.items.into_iter().map(Into::into).collect();
+        Ok(album.best_match(results))
+    }
+
+    fn supports_followed_artists(&self) -> bool {
+        true
+    }
+
+    async fn get_followed_artists(&mut self) -> Result<Vec<Artist>> {
+        let url = format!(
+            "{}/v1/users/{}/favorites/artists",
+            self.api_url,
+            self.user_id
+        );
+        let params = json!({
+            "countryCode": self.country_code,
+        });
+        let res: TidalPageResponse<TidalFavoriteItemResponse<TidalArtistResponse>> = self
+            .paginated_request(&url, &HttpMethod::Get(&params), 1000)
+            .await?;
+        Ok(res.items.into_iter().map(|i| i.item.into()).collect())
+    }
+
+    async fn follow_artists(&mut self, artists: &[Artist]) -> Result<()> {
+        let url = format!(
+            "{}/v1/users/{}/favorites/artists",
+            self.api_url,
+            self.user_id
+        );
+        let ids = artists
+            .iter()
+            .filter_map(|a| a.id.as_deref())
+            .collect::<Vec<_>>();
+        for ids_chunk in ids.chunks(100) {
+            let params = json!({
+                "countryCode": self.country_code,
+                "artistIds": ids_chunk.join(","),
+                "onArtifactNotFound": "FAIL",
+            });
+            let () = self
+                .make_request_json(&url, &HttpMethod::Post(&params), None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn search_artist(&mut self, artist: &Artist) -> Result<Option<Artist>> {
+        let url = format!("{}/v1/search", self.api_url);
+        let params = json!({
+            "countryCode": self.country_code,
+            "query": artist.name,
+            "type": "ARTISTS",
+        });
+        let res: TidalArtistSearchResponse = self
+            .make_request_json(&url, &HttpMethod::Get(&params), Some((5, 0)))
+            .await?;
+        Ok(res
+            .artists
+            .items
+            .into_iter()
+            .map(Artist::from)
+            .find(|a| a.is_same(artist)))
+    }
+}
+
+/// Whether a Tidal error body reports a missing resource
+fn is_not_found(body: &str) -> bool {
+    let body = body.to_lowercase();
+    body.contains("not found") || body.contains("does not exist")
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::mock_api::{Requests, playlist, serve_json, serve_json_headers, test_dir};
+
+    fn request_lines(requests: &Requests) -> Vec<String> {
+        requests.lock().unwrap().iter().map(|(line, _)| line.clone()).collect()
+    }
+
+    fn mocked_api(api_url: String) -> TidalApi {
+        TidalApi {
+            client: reqwest::Client::new(),
+            config: ConfigArgs::parse_from(["sync_dis_boi"]),
+            user_id: "1234".to_string(),
+            username: "jane".to_string(),
+            country_code: "US".to_string(),
+            api_v2_url: api_url.clone(),
+            api_url,
+            folder_id: None,
+            folder_ids: Arc::new(Mutex::new(HashMap::new())),
+            backoff_time: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    fn song(id: &str) -> Song {
+        crate::mock_api::song(MusicApiType::Tidal, id, &format!("Song {}", id))
+    }
+
+    #[test]
+    fn test_user_display_name() {
+        // captured from users/me, trimmed
+        let me = r#"{
+            "data": {
+                "id": "197458393",
+                "type": "users",
+                "attributes": {"username": "jdoe", "country": "FR", "email": "jdoe@example.com", "emailVerified": true}
+            },
+            "links": {"self": "/users/me"}
+        }"#;
+        let me_res: TidalMediaResponseSingle = serde_json::from_str(me).unwrap();
+        assert_eq!(TidalApi::user_display_name(&me_res), "jdoe");
+        assert_eq!(me_res.data.attributes.country.as_deref(), Some("FR"));
+
+        let me = r#"{"data": {"id": "197458393", "type": "users", "attributes": {"username": "", "nickname": "Jane"}}}"#;
+        let me_res: TidalMediaResponseSingle = serde_json::from_str(me).unwrap();
+        assert_eq!(TidalApi::user_display_name(&me_res), "Jane");
+
+        let me = r#"{"data": {"id": "197458393", "type": "users", "attributes": {"country": "US"}}}"#;
+        let me_res: TidalMediaResponseSingle = serde_json::from_str(me).unwrap();
+        assert_eq!(TidalApi::user_display_name(&me_res), "197458393");
+
+        let api = mocked_api("http://localhost".to_string());
+        assert_eq!(api.display_name(), Some(api.username.as_str()));
+    }
+
+    #[test]
+    fn test_playlist_owner() {
+        let parse = |creator: &str| -> Playlist {
+            let json = format!(
+                r#"{{"uuid": "0b5f", "title": "Road trip", "description": "", "publicPlaylist": false{}}}"#,
+                creator
+            );
+            let res: TidalPlaylistResponse = serde_json::from_str(&json).unwrap();
+            res.try_into().unwrap()
+        };
+        let mut playlists = vec![
+            parse(r#", "creator": {"id": 1234}"#),
+            parse(r#", "creator": {"id": 0, "name": "TIDAL"}"#),
+            parse(r#", "creator": {"id": 5678, "name": ""}"#),
+            parse(""),
+        ];
+        TidalApi::resolve_owners(&mut playlists, "1234", "jane");
+        let owners: Vec<Option<&str>> = playlists.iter().map(|p| p.owner.as_deref()).collect();
+        assert_eq!(owners, vec![Some("jane"), Some("TIDAL"), Some("5678"), None]);
+    }
+
+    #[test]
+    fn test_playlists_owners() {
+        // captured from the v1 user playlists endpoint, trimmed
+        let json = r#"{
+            "limit": 100,
+            "offset": 0,
+            "totalNumberOfItems": 3,
+            "items": [
+                {
+                    "uuid": "0b5f1c9e-2f0a-4b7e-9a43-7d2f4e8c1a90",
+                    "title": "Road trip ",
+                    "numberOfTracks": 12,
+                    "creator": {"id": 1234},
+                    "description": "",
+                    "type": "USER",
+                    "publicPlaylist": false,
+                    "squareImage": "6f0a2c4d-91e3-4b8a-a1d2-5c7e9b3f0d18"
+                },
+                {
+                    "uuid": "36ea71a8-445e-41a4-82ab-6628c581535d",
+                    "title": "Shared by a friend",
+                    "numberOfTracks": 40,
+                    "creator": {"id": 5678, "name": null, "picture": null, "type": "USER"},
+                    "description": "Summer songs",
+                    "type": "USER",
+                    "publicPlaylist": true,
+                    "squareImage": null
+                },
+                {
+                    "uuid": "1b087082-ab54-4e7d-a0d3-b1cf1cf18ebc",
+                    "title": "Hip-Hop: Rap Life",
+                    "numberOfTracks": 50,
+                    "creator": {"id": 0, "name": "TIDAL"},
+                    "description": "The hottest hip-hop right now.",
+                    "type": "EDITORIAL",
+                    "publicPlaylist": true,
+                    "squareImage": "2b5e7a3c-5d9f-4e21-8c6b-0f4d2a9e7b13"
+                }
+            ]
+        }"#;
+        let res: TidalPageResponse<TidalPlaylistResponse> = serde_json::from_str(json).unwrap();
+        let mut playlists: Playlists = res.try_into().unwrap();
+        TidalApi::resolve_owners(&mut playlists.0, "1234", "jane");
+
+        let owners: Vec<(&str, Option<&str>)> = playlists
+            .0
+            .iter()
+            .map(|p| (p.name.as_str(), p.owner.as_deref()))
+            .collect();
+        assert_eq!(
+            owners,
+            vec![
+                ("Road trip", Some("jane")),
+                ("Shared by a friend", Some("5678")),
+                ("Hip-Hop: Rap Life", Some("TIDAL")),
+            ]
+        );
+        assert_eq!(playlists.0[0].description, None);
+        assert_eq!(playlists.0[1].public, Some(true));
+    }
+
+    #[test]
+    fn test_generated_playlists() {
+        let json = r#"{"offset": 0, "totalNumberOfItems": 5, "items": [
+            {"uuid": "a", "title": "Road trip", "creator": {"id": 1234}, "type": "USER"},
+            {"uuid": "b", "title": "Shared by a friend", "creator": {"id": 5678}, "type": "USER"},
+            {"uuid": "c", "title": "Hip-Hop: Rap Life", "creator": {"id": 0, "name": "TIDAL"}, "type": "EDITORIAL"},
+            {"uuid": "d", "title": "Daft Punk: Essentials", "creator": {"id": 0}, "type": "ARTIST"},
+            {"uuid": "e", "title": "My Daily Discovery", "creator": {"id": 0}}
+        ]}"#;
+        let res: TidalPageResponse<TidalPlaylistResponse> = serde_json::from_str(json).unwrap();
+        let playlists: Playlists = res.try_into().unwrap();
+        let user_playlists: Vec<&str> = playlists
+            .0
+            .iter()
+            .filter(|p| !p.generated)
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(user_playlists, vec!["Road trip", "Shared by a friend"]);
+    }
+
+    #[test]
+    fn test_likes_isrc() {
+        // captured from the v1 favorite tracks endpoint, trimmed
+        let json = r#"{
+            "limit": 1000,
+            "offset": 0,
+            "totalNumberOfItems": 2,
+            "items": [
+                {
+                    "created": "2024-03-02T21:15:33.000+0000",
+                    "item": {
+                        "id": 77646170,
+                        "title": "Get Lucky",
+                        "duration": 369,
+                        "explicit": false,
+                        "isrc": "USQX91300108",
+                        "artists": [{"id": 8847, "name": "Daft Punk", "type": "MAIN"}],
+                        "album": {"id": 77646168, "title": "Random Access Memories", "releaseDate": "2013-05-17"}
+                    }
+                },
+                {
+                    "created": "2024-03-01T08:02:11.000+0000",
+                    "item": {
+                        "id": 1781923,
+                        "title": "Windowlicker",
+                        "duration": 367,
+                        "explicit": false,
+                        "isrc": null,
+                        "artists": [{"id": 3764, "name": "Aphex Twin", "type": "MAIN"}],
+                        "album": {"id": 1781922, "title": "Windowlicker"}
+                    }
+                }
+            ]
+        }"#;
+        let res: TidalPageResponse<TidalSongItemResponse> = serde_json::from_str(json).unwrap();
+        let songs: Songs = res.try_into().unwrap();
+        assert_eq!(songs.0.len(), 2);
+        assert_eq!(songs.0[0].isrc.as_deref(), Some("USQX91300108"));
+        assert_eq!(songs.0[0].album.as_ref().unwrap().year, Some(2013));
+        assert_eq!(songs.0[1].isrc, None);
+    }
+
+    #[test]
+    fn test_partition_added() {
+        let songs: Vec<Song> = ["1", "2", "3", "4"].into_iter().map(song).collect();
+        let res: Option<TidalAddItemsResponse> =
+            serde_json::from_str(r#"{"lastUpdated": 1700000000000, "addedItemIds": [1, 3, 4]}"#)
+                .unwrap();
+        let added_ids: Vec<String> = res
+            .unwrap()
+            .added_item_ids
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        let (added, skipped) = partition_added(&songs, &added_ids);
+        let added: Vec<&str> = added.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(added, vec!["1", "3", "4"]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].id, "2");
+
+        // empty response body
+        let res: Option<TidalAddItemsResponse> = serde_json::from_str("null").unwrap();
+        assert!(res.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_playlist() {
+        // in a folder
+        let (url, requests) = serve_json(vec![(200, "")]).await;
+        let mut api = mocked_api(url);
+        api.delete_playlist(playlist("1", "Road trip", vec![])).await.unwrap();
+        assert_eq!(
+            request_lines(&requests),
+            vec!["PUT /v2/my-collection/playlists/folders/remove"]
+        );
+
+        // not in a folder, falls back to deleting the playlist itself
+        let (url, requests) = serve_json(vec![
+            (404, r#"{"status": 404, "userMessage": "Resource not found"}"#),
+            (204, ""),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        let road_trip = playlist("0b5f", "Road trip", vec![]);
+        api.delete_playlist(road_trip.clone()).await.unwrap();
+        assert_eq!(
+            request_lines(&requests),
+            vec![
+                "PUT /v2/my-collection/playlists/folders/remove",
+                "DELETE /v1/playlists/0b5f"
+            ]
+        );
+
+        // unknown playlist
+        let (url, _) = serve_json(vec![
+            (404, r#"{"status": 404, "userMessage": "Resource not found"}"#),
+            (404, r#"{"status": 404, "userMessage": "Playlist does not exist"}"#),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        let err = api.delete_playlist(road_trip.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("\"Road trip\" (0b5f)"), "{}", err);
+        assert!(err.to_string().contains("does not exist"), "{}", err);
+
+        // other failures don't fall back
+        let (url, requests) = serve_json(vec![(403, r#"{"userMessage": "Forbidden"}"#)]).await;
+        let mut api = mocked_api(url);
+        let err = api.delete_playlist(road_trip).await.unwrap_err();
+        assert!(err.to_string().contains("403"), "{}", err);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_folder_items() {
+        // captured from the my-collection folders endpoint, trimmed
+        let page = r#"{
+            "lastModifiedAt": "2024-05-02T18:12:09.713+0000",
+            "items": [
+                {
+                    "trn": "trn:folder:3d5b2f7e-8c41-4f4a-9d4e-0b1c2a7f6e55",
+                    "itemType": "FOLDER",
+                    "addedAt": "2024-05-02T18:12:09.713+0000",
+                    "lastModifiedAt": "2024-05-02T18:12:09.713+0000",
+                    "name": "SyncDisBoi",
+                    "parent": null,
+                    "data": {
+                        "trn": "trn:folder:3d5b2f7e-8c41-4f4a-9d4e-0b1c2a7f6e55",
+                        "itemType": "FOLDER",
+                        "addedAt": "2024-05-02T18:12:09.713+0000",
+                        "lastModifiedAt": "2024-05-02T18:12:09.713+0000",
+                        "name": "SyncDisBoi",
+                        "parent": null,
+                        "id": "3d5b2f7e-8c41-4f4a-9d4e-0b1c2a7f6e55",
+                        "createdAt": "2024-05-02T18:12:09.713+0000",
+                        "numberOfItems": 2,
+                        "totalNumberOfItems": 2
+                    }
+                },
+                {
+                    "trn": "trn:playlist:0b5f1c9e-2f0a-4b7e-9a43-7d2f4e8c1a90",
+                    "itemType": "PLAYLIST",
+                    "addedAt": "2024-04-11T09:30:00.000+0000",
+                    "lastModifiedAt": "2024-04-11T09:30:00.000+0000",
+                    "name": "Road trip",
+                    "parent": null,
+                    "data": {
+                        "uuid": "0b5f1c9e-2f0a-4b7e-9a43-7d2f4e8c1a90",
+                        "type": "USER",
+                        "creator": {"id": 1234},
+                        "title": "Road trip",
+                        "description": "",
+                        "numberOfTracks": 12,
+                        "publicPlaylist": false,
+                        "squareImage": "6f0a2c4d-91e3-4b8a-a1d2-5c7e9b3f0d18"
+                    }
+                },
+                {
+                    "trn": "trn:mix:0123456789abcdef",
+                    "itemType": "MIX",
+                    "name": "My Mix 1",
+                    "data": {"id": "0123456789abcdef"}
+                }
+            ],
+            "totalNumberOfItems": 4,
+            "cursor": "MjAyNC0wNC0xMVQwOTozMDowMC4wMDBa"
+        }"#;
+        let res: TidalFolderPageResponse = serde_json::from_str(page).unwrap();
+        assert_eq!(res.cursor.as_deref(), Some("MjAyNC0wNC0xMVQwOTozMDowMC4wMDBa"));
+        assert!(matches!(
+            &res.items[0],
+            TidalFolderItemResponse::Folder(f) if f.name == "SyncDisBoi"
+                && f.id == "3d5b2f7e-8c41-4f4a-9d4e-0b1c2a7f6e55"
+        ));
+        assert!(matches!(
+            &res.items[1],
+            TidalFolderItemResponse::Playlist(p) if p.title == "Road trip"
+        ));
+        assert!(matches!(res.items[2], TidalFolderItemResponse::Other(_)));
+
+        // the next pages are requested with the cursor until it's missing
+        let last = r#"{"items": [{"trn": "trn:folder:1", "itemType": "FOLDER", "data": {"id": "1", "name": "Old"}}], "totalNumberOfItems": 4, "cursor": null}"#;
+        let (url, requests) = serve_json(vec![(200, page), (200, last)]).await;
+        let api = mocked_api(url);
+        let items = api.get_folder_items("root", "FOLDER").await.unwrap();
+        assert_eq!(items.len(), 4);
+        let lines = request_lines(&requests);
+        assert!(lines[0].starts_with("GET /v2/my-collection/playlists/folders?"));
+        assert!(lines[0].contains("folderId=root"));
+        assert!(!lines[0].contains("cursor="));
+        assert!(lines[1].contains("cursor=MjAyNC0wNC0xMVQwOTozMDowMC4wMDBa"));
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_in_folder() {
+        let folders = r#"{"items": [
+            {"trn": "trn:folder:1", "itemType": "FOLDER", "data": {"id": "1", "name": "Old"}},
+            {"trn": "trn:folder:2", "itemType": "FOLDER", "data": {"id": "2", "name": "Synced"}}
+        ], "cursor": null}"#;
+        let created = r#"{"trn": "trn:playlist:0b5f", "data": {"uuid": "0b5f"}}"#;
+
+        // existing folder, looked up once
+        let (url, requests) = serve_json(vec![(200, folders), (200, created), (200, created)]).await;
+        let mut api = mocked_api(url);
+        api.config.tidal_folder = Some("Synced".to_string());
+        api.create_playlist("Road trip", "", false).await.unwrap();
+        api.create_playlist("Road trip 2", "", false).await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].0.starts_with("PUT /v2/my-collection/playlists/folders/create-playlist"));
+        assert!(requests[1].1.contains("folderId=2"), "{}", requests[1].1);
+        assert!(requests[2].1.contains("folderId=2"), "{}", requests[2].1);
+        drop(requests);
+
+        // missing folder
+        let new_folder = r#"{"trn": "trn:folder:3", "itemType": "FOLDER", "data": {"id": "3", "name": "New"}}"#;
+        let (url, requests) = serve_json(vec![(200, folders), (200, new_folder), (200, created)]).await;
+        let mut api = mocked_api(url);
+        api.config.tidal_folder = Some("New".to_string());
+        api.create_playlist("Road trip", "", false).await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert!(requests[1].0.starts_with("PUT /v2/my-collection/playlists/folders/create-folder"));
+        assert!(requests[1].1.contains("name=New"), "{}", requests[1].1);
+        assert!(requests[2].1.contains("folderId=3"), "{}", requests[2].1);
+        drop(requests);
+
+        // no folder
+        let (url, requests) = serve_json(vec![(200, created)]).await;
+        let mut api = mocked_api(url);
+        api.create_playlist("Road trip", "", false).await.unwrap();
+        assert!(requests.lock().unwrap()[0].1.contains("folderId=root"));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_folders() {
+        let root = r#"{"items": [
+            {"trn": "trn:folder:1", "itemType": "FOLDER", "data": {"id": "1", "name": "Sport"}}
+        ], "cursor": null}"#;
+        let sport = r#"{"items": [
+            {"trn": "trn:folder:2", "itemType": "FOLDER", "data": {"id": "2", "name": "Running"}}
+        ], "cursor": null}"#;
+        let empty = r#"{"items": [], "cursor": null}"#;
+        let new_folder = r#"{"trn": "trn:folder:3", "itemType": "FOLDER", "data": {"id": "3", "name": "Trail"}}"#;
+        let (url, requests) = serve_json(vec![
+            (200, root),
+            (200, sport),
+            (200, empty),
+            (200, new_folder),
+            (200, ""),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        let mut folders = api.get_folders().await.unwrap();
+        folders.sort();
+        assert_eq!(folders, vec!["Sport", "Sport/Running"]);
+        let lines = request_lines(&requests);
+        assert!(lines[1].contains("folderId=1"), "{}", lines[1]);
+        assert!(lines[2].contains("folderId=2"), "{}", lines[2]);
+
+        // created in its parent, then known without listing the folders again
+        api.create_folder("Sport/Running/Trail").await.unwrap();
+        api.move_to_folder(&playlist("0b5f", "Road trip", vec![]), "Sport/Running/Trail")
+            .await
+            .unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[3].0.starts_with("PUT /v2/my-collection/playlists/folders/create-folder"));
+        assert!(requests[3].1.contains("name=Trail"), "{}", requests[3].1);
+        assert!(requests[3].1.contains("folderId=2"), "{}", requests[3].1);
+        assert!(requests[4].0.starts_with("PUT /v2/my-collection/playlists/folders/move"));
+        assert!(requests[4].1.contains("folderId=3"), "{}", requests[4].1);
+        assert!(requests[4].1.contains("trns=trn%3Aplaylist%3A0b5f"), "{}", requests[4].1);
+    }
+
+    #[tokio::test]
+    async fn test_update_playlist_details() {
+        let created = r#"{"trn": "trn:playlist:0b5f", "data": {"uuid": "0b5f"}}"#;
+        let (url, requests) = serve_json(vec![(200, created)]).await;
+        let mut api = mocked_api(url);
+        let mut road_trip = api.create_playlist("Road trip", "Summer songs", true).await.unwrap();
+        assert_eq!(road_trip.description.as_deref(), Some("Summer songs"));
+        assert_eq!(road_trip.public, Some(true));
+        let body = requests.lock().unwrap()[0].1.clone();
+        assert!(body.contains("description=Summer+songs"), "{}", body);
+        assert!(body.contains("public=true"), "{}", body);
+
+        // both changed
+        let (url, requests) = serve_json(vec![(200, "{}"), (200, "")]).await;
+        let mut api = mocked_api(url);
+        api.update_playlist_details(&mut road_trip, Some("Winter songs"), Some(false))
+            .await
+            .unwrap();
+        assert_eq!(
+            request_lines(&requests),
+            vec!["POST /v1/playlists/0b5f", "PUT /v2/playlists/0b5f/set-private"]
+        );
+        let body = requests.lock().unwrap()[0].1.clone();
+        assert!(body.contains("title=Road+trip"), "{}", body);
+        assert!(body.contains("description=Winter+songs"), "{}", body);
+        assert_eq!(road_trip.description.as_deref(), Some("Winter songs"));
+        assert_eq!(road_trip.public, Some(false));
+
+        // only the visibility
+        let (url, requests) = serve_json(vec![(200, "")]).await;
+        let mut api = mocked_api(url);
+        api.update_playlist_details(&mut road_trip, None, Some(true)).await.unwrap();
+        assert_eq!(request_lines(&requests), vec!["PUT /v2/playlists/0b5f/set-public"]);
+        assert_eq!(road_trip.description.as_deref(), Some("Winter songs"));
+
+        // nothing changed
+        let (url, requests) = serve_json(vec![]).await;
+        let mut api = mocked_api(url);
+        api.update_playlist_details(&mut road_trip, None, None).await.unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_playlists_in_folders() {
+        let playlists = r#"{"items": [
+            {"uuid": "a", "title": "Root", "creator": {"id": 1234}}
+        ], "offset": 0, "totalNumberOfItems": 1}"#;
+        let folders = r#"{"items": [
+            {"trn": "trn:folder:2", "itemType": "FOLDER", "data": {"id": "2", "name": "Synced"}}
+        ], "cursor": null}"#;
+        let folder_playlists = r#"{"items": [
+            {"trn": "trn:playlist:a", "itemType": "PLAYLIST", "data": {"uuid": "a", "title": "Root", "creator": {"id": 1234}}},
+            {"trn": "trn:playlist:b", "itemType": "PLAYLIST", "data": {"uuid": "b", "title": "Synced", "creator": {"id": 1234}}}
+        ], "cursor": null}"#;
+        let (url, _) = serve_json(vec![(200, playlists), (200, folders), (200, folder_playlists)]).await;
+        let mut api = mocked_api(url);
+        let playlists = api.get_playlists_info().await.unwrap();
+        let names: Vec<&str> = playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Root", "Synced"]);
+        assert_eq!(playlists[1].owner.as_deref(), Some("jane"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_errors() {
+        let playlists = r#"{"items": [], "offset": 0, "totalNumberOfItems": 0}"#;
+        let (url, requests) = serve_json(vec![(503, ""), (502, ""), (200, playlists)]).await;
+        let mut api = mocked_api(url);
+        api.config.tidal_max_backoff_secs = Some(0);
+        let url = format!("{}/v1/playlists", api.api_url);
+        let res: TidalPageResponse<TidalPlaylistResponse> = api
+            .make_request_json(&url, &HttpMethod::Get(&json!({})), None)
+            .await
+            .unwrap();
+        assert!(res.items.is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        let (url, requests) = serve_json(vec![(500, ""); 3]).await;
+        let mut api = mocked_api(url);
+        api.config.tidal_max_retries = Some(2);
+        api.config.tidal_max_backoff_secs = Some(0);
+        let url = format!("{}/v1/playlists", api.api_url);
+        let err = api
+            .make_request_json::<IgnoredAny>(&url, &HttpMethod::Get(&json!({})), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("after 3 attempts"), "{}", err);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_add_items_params() {
+        let songs: Vec<Song> = ["1", "2", "3"].into_iter().map(song).collect();
+        let params = TidalApi::add_items_params(&songs);
+        assert_eq!(params["trackIds"], "1,2,3");
+        assert_eq!(params["onDuplicate"], "SKIP");
+        assert_eq!(params["onArtifactNotFound"], "SKIP");
+    }
+
+    #[tokio::test]
+    async fn test_add_songs_chunks() {
+        // 205 songs, one of them twice and one already in the playlist
+        let mut songs: Vec<Song> = (1..=205).map(|i| song(&i.to_string())).collect();
+        songs.push(song("7"));
+        let added = |ids: std::ops::RangeInclusive<usize>| {
+            let ids: Vec<String> = ids.filter(|i| *i != 150).map(|i| i.to_string()).collect();
+            format!(r#"{{"lastUpdated": 1700000000000, "addedItemIds": [{}]}}"#, ids.join(","))
+        };
+        let (first, second, third) = (added(1..=100), added(101..=200), added(201..=205));
+        let etag = r#"ETag: "1700000000000""#;
+        let playlist_json = r#"{"uuid": "0b5f", "title": "Road trip"}"#;
+        let conflict = r#"{"status": 412, "subStatus": 2001, "userMessage": "Precondition failed"}"#;
+        let (url, requests) = serve_json_headers(vec![
+            (200, etag, playlist_json),
+            (200, "", &first),
+            // modified between the ETag and the addition
+            (200, etag, playlist_json),
+            (412, "", conflict),
+            (200, etag, playlist_json),
+            (200, "", &second),
+            (200, etag, playlist_json),
+            (200, "", &third),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        let mut road_trip = playlist("0b5f", "Road trip", vec![song("150")]);
+        api.add_songs_to_playlist(&mut road_trip, &songs).await.unwrap();
+
+        assert_eq!(road_trip.songs.len(), 205);
+        let requests = requests.lock().unwrap();
+        let additions: Vec<&(String, String)> =
+            requests.iter().filter(|(line, _)| line.starts_with("POST")).collect();
+        assert_eq!(additions.len(), 4);
+        assert!(additions.iter().all(|(line, _)| line == "POST /v1/playlists/0b5f/items"));
+        let chunk_sizes: Vec<usize> = additions
+            .iter()
+            .map(|(_, body)| {
+                assert!(body.contains("onDuplicate=SKIP"), "{}", body);
+                let ids = body.split('&').find_map(|p| p.strip_prefix("trackIds=")).unwrap();
+                ids.split("%2C").count()
+            })
+            .collect();
+        assert_eq!(chunk_sizes, vec![100, 100, 100, 5]);
+        assert_eq!(additions[1].1, additions[2].1);
+
+        // too many conflicts
+        let (url, _) = serve_json_headers(
+            [(200, etag, playlist_json), (412, "", conflict)].repeat(4),
+        )
+        .await;
+        let mut api = mocked_api(url);
+        let mut road_trip = playlist("0b5f", "Road trip", vec![]);
+        let err = api
+            .add_songs_to_playlist(&mut road_trip, &songs[..1])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("\"Road trip\""), "{}", err);
+        assert!(err.to_string().contains("412"), "{}", err);
+        assert!(road_trip.songs.is_empty());
+    }
+
+    // captured from the v2 playlist items relationship, trimmed
+    const ITEMS_PAGE_1: &str = r#"{
+        "data": [
+            {"id": "251380837", "type": "tracks", "meta": {"itemId": "4f3c0a6e", "addedAt": "2024-04-11T09:30:00Z"}},
+            {"id": "77646170", "type": "tracks", "meta": {"itemId": "8e91b2d4", "addedAt": "2024-04-11T09:31:00Z"}}
+        ],
+        "links": {
+            "self": "/playlists/0b5f/relationships/items?countryCode=US",
+            "next": "/playlists/0b5f/relationships/items?countryCode=US&page%5Bcursor%5D=Mnw0",
+            "meta": {"nextCursor": "Mnw0"}
+        }
+    }"#;
+    const ITEMS_PAGE_2: &str = r#"{
+        "data": [
+            {"id": "302114985", "type": "videos", "meta": {"itemId": "0a7d3c1f", "addedAt": "2024-04-12T10:00:00Z"}},
+            {"id": "251380837", "type": "tracks", "meta": {"itemId": "c5e2f8a9", "addedAt": "2024-04-12T10:01:00Z"}}
+        ],
+        "links": {"self": "/playlists/0b5f/relationships/items?countryCode=US&page%5Bcursor%5D=Mnw0"}
+    }"#;
+    const TRACKS: &str = r#"{
+        "data": [
+            {
+                "id": "77646170",
+                "type": "tracks",
+                "attributes": {"title": "Get Lucky", "isrc": "USQX91300108", "duration": "PT6M9S", "explicit": false, "popularity": 0.8},
+                "relationships": {
+                    "albums": {"data": [{"id": "77646168", "type": "albums"}]},
+                    "artists": {"data": [{"id": "8847", "type": "artists"}]}
+                }
+            },
+            {
+                "id": "251380837",
+                "type": "tracks",
+                "attributes": {"title": "One More Time", "isrc": "GBDUW0000053", "duration": "PT5M20S", "explicit": false, "popularity": 0.9},
+                "relationships": {
+                    "albums": {"data": [{"id": "251380836", "type": "albums"}]},
+                    "artists": {"data": [{"id": "8847", "type": "artists"}]}
+                }
+            }
+        ],
+        "included": [
+            {"id": "77646168", "type": "albums", "attributes": {"title": "Random Access Memories", "releaseDate": "2013-05-17"}},
+            {"id": "251380836", "type": "albums", "attributes": {"title": "Discovery", "releaseDate": "2001-03-12"}},
+            {"id": "8847", "type": "artists", "attributes": {"name": "Daft Punk"}}
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_playlist_songs_v2() {
+        let res: TidalCursorPageResponse<TidalMediaRelationshipData> =
+            serde_json::from_str(ITEMS_PAGE_1).unwrap();
+        assert_eq!(res.next_cursor().as_deref(), Some("Mnw0"));
+        let res: TidalCursorPageResponse<TidalMediaRelationshipData> =
+            serde_json::from_str(ITEMS_PAGE_2).unwrap();
+        assert_eq!(res.next_cursor(), None);
+
+        let (url, requests) =
+            serve_json(vec![(200, ITEMS_PAGE_1), (200, ITEMS_PAGE_2), (200, TRACKS)]).await;
+        let mut api = mocked_api(url);
+        let songs = api.get_playlist_songs("0b5f").await.unwrap();
+
+        // in the playlist order, without the video
+        let names: Vec<&str> = songs.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["One More Time", "Get Lucky", "One More Time"]);
+        assert_eq!(songs[1].isrc.as_deref(), Some("USQX91300108"));
+        assert_eq!(songs[1].album.as_ref().unwrap().name, "Random Access Memories");
+        assert_eq!(songs[1].artists[0].name, "Daft Punk");
+        assert_eq!(songs[1].duration_ms, 369_000);
+
+        let lines = request_lines(&requests);
+        assert!(lines[0].starts_with("GET /playlists/0b5f/relationships/items?"));
+        assert!(!lines[0].contains("cursor"));
+        assert!(lines[1].contains("page%5Bcursor%5D=Mnw0"), "{}", lines[1]);
+        assert!(lines[2].starts_with("GET /tracks?"));
+        assert!(lines[2].contains("filter%5Bid%5D=251380837%2C77646170&"), "{}", lines[2]);
+    }
+
+    #[tokio::test]
+    async fn test_playlist_songs_v1_fallback() {
+        let v1_items = r#"{"offset": 0, "totalNumberOfItems": 1, "items": [{"item": {
+            "id": 77646170,
+            "title": "Get Lucky",
+            "isrc": "USQX91300108",
+            "duration": 369,
+            "artists": [{"id": 8847, "name": "Daft Punk"}],
+            "album": {"id": 77646168, "title": "Random Access Memories"}
+        }}]}"#;
+        let (url, requests) = serve_json(vec![
+            (404, r#"{"errors": [{"code": "NOT_FOUND", "detail": "Playlist not found"}]}"#),
+            (200, v1_items),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        let songs = api.get_playlist_songs("0b5f").await.unwrap();
+        assert_eq!(songs.len(), 1);
+        assert_eq!(songs[0].name, "Get Lucky");
+        assert!(request_lines(&requests)[1].starts_with("GET /v1/playlists/0b5f/items?"));
+    }
+
+    // captured from the v2 search results tracks relationship, trimmed
+    const SEARCH_RESULTS: &str = r#"{
+        "data": [
+            {"id": "108395640", "type": "tracks"},
+            {"id": "71829563", "type": "tracks"}
+        ],
+        "included": [
+            {"id": "27367", "type": "artists", "attributes": {"name": "米津玄師"}},
+            {
+                "id": "71829563",
+                "type": "tracks",
+                "attributes": {"title": "Lemon", "isrc": "JPU901800031", "duration": "PT4M15S", "explicit": false, "popularity": 0.9},
+                "relationships": {
+                    "albums": {"data": [{"id": "71829562", "type": "albums"}]},
+                    "artists": {"data": [{"id": "27367", "type": "artists"}]}
+                }
+            },
+            {
+                "id": "108395640",
+                "type": "tracks",
+                "attributes": {"title": "Lemon", "isrc": "JPU901800124", "duration": "PT4M16S", "explicit": false, "popularity": 0.4},
+                "relationships": {
+                    "albums": {"data": [{"id": "108395639", "type": "albums"}]},
+                    "artists": {"data": [{"id": "27367", "type": "artists"}]}
+                }
+            },
+            {"id": "71829562", "type": "albums", "attributes": {"title": "Lemon", "releaseDate": "2018-03-14"}},
+            {"id": "108395639", "type": "albums", "attributes": {"title": "STRAY SHEEP", "releaseDate": "2020-08-05"}}
+        ],
+        "links": {"self": "/searchResults/Lemon/relationships/tracks?countryCode=JP"}
+    }"#;
+
+    #[test]
+    fn test_search_results_v2() {
+        let res: TidalSearchResultsResponse = serde_json::from_str(SEARCH_RESULTS).unwrap();
+        let songs: Songs = res.try_into().unwrap();
+        // by relevance, not by popularity
+        let ids: Vec<&str> = songs.0.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["108395640", "71829563"]);
+        assert_eq!(songs.0[0].isrc.as_deref(), Some("JPU901800124"));
+        assert_eq!(songs.0[0].album.as_ref().unwrap().name, "STRAY SHEEP");
+        assert_eq!(songs.0[0].album.as_ref().unwrap().year, Some(2020));
+        assert_eq!(songs.0[1].artists[0].name, "米津玄師");
+        assert_eq!(songs.0[1].duration_ms, 255_000);
+
+        // no results
+        let res: TidalSearchResultsResponse =
+            serde_json::from_str(r#"{"data": [], "links": {"self": "/searchResults/x"}}"#).unwrap();
+        let songs: Songs = res.try_into().unwrap();
+        assert!(songs.0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_query() {
+        let (url, requests) = serve_json(vec![(200, SEARCH_RESULTS)]).await;
+        let api = mocked_api(url);
+        let songs = api.search_query("米津玄師 Lemon", 1).await.unwrap();
+        assert_eq!(songs.len(), 1);
+        let lines = request_lines(&requests);
+        assert!(
+            lines[0].starts_with(
+                "GET /searchResults/%E7%B1%B3%E6%B4%A5%E7%8E%84%E5%B8%AB%20Lemon/relationships/tracks?"
+            ),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[0].contains("include=tracks.albums%2Ctracks.artists"), "{}", lines[0]);
+
+        // falls back to v1
+        let v1_results = r#"{"tracks": {"offset": 0, "totalNumberOfItems": 1, "items": [{
+            "id": 71829563,
+            "title": "Lemon",
+            "duration": 255,
+            "artists": [{"id": 27367, "name": "Kenshi Yonezu"}],
+            "album": {"id": 71829562, "title": "Lemon"}
+        }]}}"#;
+        let (url, requests) = serve_json(vec![
+            (400, r#"{"errors": [{"code": "INVALID_ENUM_VALUE", "detail": "Bad request"}]}"#),
+            (200, v1_results),
+        ])
+        .await;
+        let api = mocked_api(url);
+        let songs = api.search_query("Lemon", 5).await.unwrap();
+        assert_eq!(songs[0].id, "71829563");
+        assert!(request_lines(&requests)[1].starts_with("GET /v1/search?"));
+    }
+
+    #[test]
+    fn test_oauth_error() {
+        use reqwest::StatusCode;
+
+        let error = |status: StatusCode, text: &str| TidalApi::oauth_error(status, text).to_string();
+        let client = r#"{"status":401,"error":"invalid_client","sub_status":1002,"error_description":"Client not found"}"#;
+        assert!(error(StatusCode::UNAUTHORIZED, client).contains("TIDAL_CLIENT_SECRET"));
+        let grant = r#"{"status":400,"error":"invalid_grant","error_description":"Token could not be refreshed"}"#;
+        assert!(error(StatusCode::BAD_REQUEST, grant).contains("--clear-cache"));
+        let other = r#"{"status":400,"error":"invalid_scope","error_description":"Scope r_usr is not allowed"}"#;
+        assert!(error(StatusCode::BAD_REQUEST, other).contains("Scope r_usr is not allowed"));
+        assert!(error(StatusCode::BAD_GATEWAY, "<html></html>").contains("502"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_flow_error() {
+        // a rejected client while polling keeps the credentials hint and the saved response
+        let debug_dir = test_dir("tidal_poll");
+        let config =
+            ConfigArgs::parse_from(["sync_dis_boi", "--debug-dir", debug_dir.to_str().unwrap()]);
+        let pending = r#"{"status":400,"error":"authorization_pending","sub_status":1002,"error_description":"Device Authorization code is not authorized yet"}"#;
+        let client_error = r#"{"status":401,"error":"invalid_client","sub_status":1002,"error_description":"Client not found"}"#;
+        let (url, requests) = serve_json(vec![(400, pending), (401, client_error)]).await;
+        let mut poll = DeviceFlowPoll::new(2, 300).with_error_mapping(TidalApi::oauth_error);
+        let client = reqwest::Client::new();
+        let err = poll
+            .run::<OAuthToken, _>(|| client.post(&url), &config, TidalApi::RES_DEBUG_FILENAME)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert!(err.contains("TIDAL_CLIENT_SECRET"));
+        let saved = err.split("Response saved to: ").nth(1).unwrap();
+        assert_eq!(std::fs::read_to_string(saved).unwrap(), client_error);
+        std::fs::remove_dir_all(debug_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_likes_skipped() {
+        let songs: Vec<Song> = (1..=150).map(|i| song(&i.to_string())).collect();
+        // captured from the favorite tracks endpoint with onArtifactNotFound=SKIP, trimmed
+        let ids: Vec<String> = (1..=100).filter(|i| *i != 42).map(|i| i.to_string()).collect();
+        let first = format!(r#"{{"lastUpdated": 1700000000000, "addedItemIds": [{}]}}"#, ids.join(","));
+        let res: Option<TidalAddItemsResponse> = serde_json::from_str(&first).unwrap();
+        assert_eq!(res.unwrap().added_item_ids.unwrap().len(), 99);
+
+        // a failed chunk doesn't stop the next ones
+        let (url, requests) = serve_json(vec![
+            (200, &first),
+            (500, r#"{"status": 500, "userMessage": "Internal error"}"#),
+        ])
+        .await;
+        let mut api = mocked_api(url);
+        api.config.tidal_max_retries = Some(0);
+        let not_added = api.add_likes(&songs).await.unwrap();
+        let not_added: Vec<&str> = not_added.iter().map(|s| s.id.as_str()).collect();
+        let mut expected = vec!["42".to_string()];
+        expected.extend((101..=150).map(|i| i.to_string()));
+        assert_eq!(not_added, expected);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].1.contains("onArtifactNotFound=SKIP"), "{}", requests[0].1);
+        drop(requests);
+
+        // an empty body means all the tracks were liked, strict likes fail on unavailable ones
+        let (url, requests) = serve_json(vec![(200, "")]).await;
+        let mut api = mocked_api(url);
+        api.config.tidal_strict_likes = true;
+        assert!(api.add_likes(&songs[..3]).await.unwrap().is_empty());
+        assert!(requests.lock().unwrap()[0].1.contains("onArtifactNotFound=FAIL"));
+
+        // every chunk failed
+        let (url, _) = serve_json(vec![(401, r#"{"status": 401, "userMessage": "Unauthorized"}"#)]).await;
+        let mut api = mocked_api(url);
+        assert!(api.add_likes(&songs[..3]).await.is_err());
+    }
 }
@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use serde::de::IgnoredAny;
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -8,7 +9,16 @@ pub struct TidalOAuthDeviceRes {
     pub device_code: String,
     pub verification_uri: String,
     pub verification_uri_complete: String,
-    pub expires_in: u32,
+    pub expires_in: u64,
+    /// Minimum number of seconds between two polls of the token endpoint
+    #[serde(default = "TidalOAuthDeviceRes::default_interval")]
+    pub interval: u64,
+}
+
+impl TidalOAuthDeviceRes {
+    fn default_interval() -> u64 {
+        5
+    }
 }
 
 ////////////
@@ -29,6 +39,23 @@ pub struct TidalPageResponse<T> {
 pub struct TidalPlaylistResponse {
     pub uuid: String,
     pub title: String,
+    pub description: Option<String>,
+    pub public_playlist: Option<bool>,
+    pub creator: Option<TidalCreatorResponse>,
+    /// `USER` for the playlists of users, `EDITORIAL`, `ARTIST` or `MIX` for the ones made by
+    /// Tidal
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    /// Uuid of the square cover image, `None` for playlists without songs
+    pub square_image: Option<String>,
+}
+
+/// Creator of a playlist, only editorial playlists have a name
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalCreatorResponse {
+    pub id: Option<u64>,
+    pub name: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,6 +73,7 @@ pub struct TidalSongResponse {
     pub duration: usize,
     pub artists: Vec<TidalArtistResponse>,
     pub album: Option<TidalAlbumResponse>,
+    pub explicit: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -53,6 +81,7 @@ pub struct TidalSongResponse {
 pub struct TidalAlbumResponse {
     pub id: usize,
     pub title: String,
+    pub release_date: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -67,6 +96,32 @@ pub struct TidalSearchResponse {
     pub tracks: TidalPageResponse<TidalSongResponse>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalFullAlbumResponse {
+    pub id: usize,
+    pub title: String,
+    #[serde(default)]
+    pub artists: Vec<TidalArtistResponse>,
+    pub upc: Option<String>,
+    pub release_date: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TidalFavoriteItemResponse<T> {
+    pub item: T,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TidalAlbumSearchResponse {
+    pub albums: TidalPageResponse<TidalFullAlbumResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TidalArtistSearchResponse {
+    pub artists: TidalPageResponse<TidalArtistResponse>,
+}
+
 ////////////
 // V2 API //
 ////////////
@@ -78,11 +133,71 @@ pub struct TidalPlaylistCreateResponse {
     pub data: TidalPlaylistDataResponse,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalAddItemsResponse {
+    #[serde(default)]
+    pub added_item_ids: Option<Vec<u64>>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TidalPlaylistDataResponse {
     pub uuid: String,
 }
 
+/// Page of the items of a folder of the collection, the following page is requested with
+/// its `cursor`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalFolderPageResponse {
+    pub items: Vec<TidalFolderItemResponse>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "itemType", content = "data", rename_all = "UPPERCASE")]
+pub enum TidalFolderItemResponse {
+    Folder(TidalFolderResponse),
+    Playlist(TidalPlaylistResponse),
+    /// Mixes and the item types added later
+    #[serde(untagged)]
+    Other(IgnoredAny),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TidalFolderResponse {
+    pub id: String,
+    pub name: String,
+}
+
+/// Page of a JSON:API response, the following page is requested with the `page[cursor]`
+/// found in its links
+#[derive(Deserialize, Debug)]
+pub struct TidalCursorPageResponse<T> {
+    pub data: Vec<T>,
+    pub included: Option<Vec<TidalMediaData>>,
+    pub links: Option<TidalLinksResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TidalLinksResponse {
+    pub meta: Option<TidalLinksMetaResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalLinksMetaResponse {
+    pub next_cursor: Option<String>,
+}
+
+/// Tracks found by a v2 search, by relevance. The tracks, their albums and artists are in
+/// `included`
+#[derive(Deserialize, Debug)]
+pub struct TidalSearchResultsResponse {
+    pub data: Vec<TidalMediaRelationshipData>,
+    pub included: Option<Vec<TidalMediaData>>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TidalMediaResponseSingle {
     pub data: TidalMediaData,
@@ -101,7 +216,6 @@ pub struct TidalMediaData {
     pub id: String,
     pub attributes: TidalMediaAttributes,
     pub relationships: Option<TidalMediaRelationships>,
-    #[allow(dead_code)]
     #[serde(rename = "type")]
     pub typ: String,
 }
@@ -116,9 +230,12 @@ pub struct TidalMediaAttributes {
     pub duration: Option<String>,
     pub barcode_id: Option<String>,
     pub popularity: Option<f32>,
+    pub release_date: Option<String>,
+    pub explicit: Option<bool>,
 
     // user attributes
     pub username: Option<String>,
+    pub nickname: Option<String>,
     pub country: Option<String>,
     pub email: Option<String>,
 }
@@ -137,7 +254,6 @@ pub struct TidalMediaRelationship {
 #[derive(Deserialize, Debug)]
 pub struct TidalMediaRelationshipData {
     pub id: String,
-    #[allow(dead_code)]
     #[serde(rename = "type")]
     pub typ: String,
 }
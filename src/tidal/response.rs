@@ -2,12 +2,13 @@ use color_eyre::eyre::{Error, OptionExt, Result, eyre};
 use tracing::error;
 
 use super::model::{
-    TidalMediaData, TidalMediaResponse, TidalPageResponse, TidalPlaylistResponse,
-    TidalSearchResponse, TidalSongItemResponse, TidalSongResponse,
+    TidalArtistResponse, TidalCursorPageResponse, TidalFullAlbumResponse, TidalMediaData, TidalMediaResponse,
+    TidalPageResponse, TidalPlaylistResponse, TidalSearchResponse, TidalSearchResultsResponse, TidalSongItemResponse,
+    TidalSongResponse,
 };
 use crate::{
-    music_api::{Album, Artist, MusicApiType, Playlist, Playlists, Song, Songs},
-    utils::clean_isrc,
+    music_api::{Album, Artist, MusicApiType, Playlist, Playlists, SavedAlbum, Song, Songs},
+    utils::{clean_isrc, clean_upc, parse_year},
 };
 
 // multiples
@@ -72,14 +73,38 @@ impl TryInto<Songs> for TidalSearchResponse {
 
 // singles
 
+/// URL of the 640x640 JPEG version of a Tidal image, the dashes of its uuid being replaced by
+/// slashes
+fn image_url(image_id: &str) -> String {
+    format!(
+        "https://resources.tidal.com/images/{}/640x640.jpg",
+        image_id.replace('-', "/")
+    )
+}
+
 impl TryInto<Playlist> for TidalPlaylistResponse {
     type Error = Error;
     fn try_into(self) -> Result<Playlist, Self::Error> {
+        // the playlists of Tidal are created by the user 0
+        let generated = matches!(self.typ.as_deref(), Some("EDITORIAL" | "ARTIST" | "MIX"))
+            || self.creator.as_ref().is_some_and(|c| c.id == Some(0));
         Ok(Playlist {
             id: self.uuid,
             name: self.title.trim().to_string(),
             songs: vec![],
-            owner: Some("".to_string()) // TODO: get the owner
+            // the id of users is replaced by their name with `TidalApi::resolve_owners`
+            owner: self
+                .creator
+                .and_then(|c| c.name.filter(|n| !n.is_empty()).or(c.id.map(|id| id.to_string()))),
+            description: self.description.filter(|d| !d.is_empty()),
+            public: self.public_playlist,
+            generated,
+            cover_url: self.square_image.filter(|i| !i.is_empty()).map(|i| image_url(&i)),
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
         })
     }
 }
@@ -93,15 +118,9 @@ impl TryInto<Song> for TidalSongResponse {
         let album = Album {
             id: Some(album.id.to_string()),
             name: album.title,
+            year: album.release_date.as_deref().and_then(parse_year),
         };
-        let artists = self
-            .artists
-            .into_iter()
-            .map(|a| Artist {
-                id: Some(a.id.to_string()),
-                name: a.name,
-            })
-            .collect();
+        let artists = self.artists.into_iter().map(Into::into).collect();
 
         Ok(Song {
             source: MusicApiType::Tidal,
@@ -112,10 +131,39 @@ impl TryInto<Song> for TidalSongResponse {
             album: Some(album),
             artists,
             duration_ms: self.duration * 1000,
+            uploaded: false,
+            explicit: self.explicit,
+            alt_names: vec![],
+            mbid: None,
+            isrc_hint: None,
         })
     }
 }
 
+impl From<TidalArtistResponse> for Artist {
+    fn from(artist: TidalArtistResponse) -> Self {
+        Artist {
+            id: Some(artist.id.to_string()),
+            name: artist.name,
+        }
+    }
+}
+
+impl From<TidalFullAlbumResponse> for SavedAlbum {
+    fn from(album: TidalFullAlbumResponse) -> Self {
+        SavedAlbum {
+            source: MusicApiType::Tidal,
+            album: Album {
+                id: Some(album.id.to_string()),
+                name: album.title,
+                year: album.release_date.as_deref().and_then(parse_year),
+            },
+            artists: album.artists.into_iter().map(Into::into).collect(),
+            upc: clean_upc(album.upc),
+        }
+    }
+}
+
 impl TryInto<Songs> for TidalMediaResponse {
     type Error = Error;
 
@@ -146,6 +194,43 @@ impl TryInto<Songs> for TidalMediaResponse {
     }
 }
 
+impl TryInto<Songs> for TidalSearchResultsResponse {
+    type Error = Error;
+
+    fn try_into(self) -> Result<Songs, Self::Error> {
+        let mut included = self.included.unwrap_or_default();
+        let mut songs = Vec::new();
+        // keep the relevance order of the results
+        for track in self.data.iter().filter(|t| t.typ == "tracks") {
+            let Some(pos) = included
+                .iter()
+                .position(|i| i.typ == "tracks" && i.id == track.id)
+            else {
+                error!("missing data of song {} in response, skipping it", track.id);
+                continue;
+            };
+            let data = included.swap_remove(pos);
+            match media_data_to_song(data, &included) {
+                Ok(s) => songs.push(s),
+                Err(e) => {
+                    error!("failed to parse song in response, skipping it: {}", e);
+                }
+            }
+        }
+        Ok(Songs(songs))
+    }
+}
+
+impl<T> TidalCursorPageResponse<T> {
+    pub fn next_cursor(&self) -> Option<String> {
+        self.links
+            .as_ref()
+            .and_then(|l| l.meta.as_ref())
+            .and_then(|m| m.next_cursor.clone())
+            .filter(|c| !c.is_empty())
+    }
+}
+
 fn media_data_to_song(data: TidalMediaData, included: &[TidalMediaData]) -> Result<Song> {
     let duration = &data
         .attributes
@@ -198,6 +283,7 @@ fn media_data_to_song(data: TidalMediaData, included: &[TidalMediaData]) -> Resu
         album = Some(Album {
             id: Some(album_data.id.clone()),
             name: title,
+            year: album_data.attributes.release_date.as_deref().and_then(parse_year),
         });
     }
     if let Some(artists_rel) = data
@@ -232,5 +318,10 @@ fn media_data_to_song(data: TidalMediaData, included: &[TidalMediaData]) -> Resu
         album,
         artists,
         duration_ms: duration,
+        uploaded: false,
+        explicit: data.attributes.explicit,
+        alt_names: vec![],
+        mbid: None,
+        isrc_hint: None,
     })
 }
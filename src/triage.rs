@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::ConfigArgs;
+use crate::music_api::{MusicApiType, Song};
+use crate::overrides::Overrides;
+use crate::report::SyncReport;
+
+pub struct TriageOptions {
+    /// Destination platform the overrides are written for
+    pub dst: MusicApiType,
+    /// Open the song URLs in the browser, this many at a time
+    pub open_batch: Option<usize>,
+    /// Prompt for a decision on each song, otherwise only list them
+    pub interactive: bool,
+}
+
+/// Unmatched songs, per playlist name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UnmatchedReport {
+    /// Report written with `--report`
+    Sync(Box<SyncReport>),
+    /// `missing_songs.json` of the debug directory
+    Missing(BTreeMap<String, Vec<Song>>),
+}
+
+/// Load an unmatched songs report: `missing_songs.json` of the debug directory, or the report
+/// written with `--report`
+pub fn load_unmatched(report: &Path) -> Result<Vec<(String, Song)>> {
+    let report: UnmatchedReport = serde_json::from_reader(std::fs::File::open(report)?)
+        .map_err(|e| eyre!("{:?} is neither a missing songs nor a sync report: {}", report, e))?;
+    let playlists: Vec<(String, Vec<Song>)> = match report {
+        UnmatchedReport::Sync(report) => report
+            .playlists
+            .into_iter()
+            .chain(report.likes)
+            .map(|p| (p.src_name, p.missing))
+            .collect(),
+        UnmatchedReport::Missing(missing) => missing.into_iter().collect(),
+    };
+    Ok(playlists
+        .into_iter()
+        .flat_map(|(playlist, songs)| songs.into_iter().map(move |s| (playlist.clone(), s)))
+        .collect())
+}
+
+pub fn triage(
+    report: &Path,
+    overrides_path: &Path,
+    dst: MusicApiType,
+    open_batch: Option<usize>,
+    config: &ConfigArgs,
+) -> Result<()> {
+    let songs = load_unmatched(report)?;
+    let mut overrides = Overrides::load(overrides_path)?;
+    let options = TriageOptions {
+        dst,
+        open_batch,
+        interactive: config.is_interactive(),
+    };
+
+    let stdin = std::io::stdin();
+    run_triage(
+        &songs,
+        &mut overrides,
+        &options,
+        stdin.lock(),
+        std::io::stdout(),
+        |url| {
+            if webbrowser::open(url).is_err() {
+                warn!("failed to open {} in the browser", url);
+            }
+        },
+    )?;
+
+    if options.interactive {
+        overrides.save(overrides_path)?;
+        info!("overrides saved to {:?}", overrides_path);
+    }
+    Ok(())
+}
+
+/// Walk through the unmatched songs, songs already overridden or blacklisted are skipped
+///
+/// For each song the user can enter a destination song id, `b` to blacklist it,
+/// nothing to skip it or `q` to stop.
+pub fn run_triage<R: BufRead, W: Write>(
+    songs: &[(String, Song)],
+    overrides: &mut Overrides,
+    options: &TriageOptions,
+    mut input: R,
+    mut output: W,
+    mut open_url: impl FnMut(&str),
+) -> Result<()> {
+    let pending = songs
+        .iter()
+        .filter(|(_, s)| overrides.get(s, &options.dst).is_none() && !overrides.is_blacklisted(s))
+        .collect::<Vec<_>>();
+    writeln!(output, "{} unmatched songs to triage", pending.len())?;
+
+    let batch_size = options.open_batch.unwrap_or(1).max(1);
+    for batch in pending.chunks(batch_size) {
+        if options.interactive && options.open_batch.is_some() {
+            for url in batch.iter().filter_map(|(_, s)| s.url()) {
+                open_url(&url);
+            }
+        }
+
+        for (playlist, song) in batch {
+            let url = song.url().unwrap_or_else(|| "no url".to_string());
+            writeln!(output, "[{}] {} ({})", playlist, song, url)?;
+            if !options.interactive {
+                continue;
+            }
+
+            write!(
+                output,
+                "{} song id, [b]lacklist, [q]uit or empty to skip: ",
+                options.dst.short_name()
+            )?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match line.trim() {
+                "" => {}
+                "q" => return Ok(()),
+                "b" => overrides.add_to_blacklist(song),
+                id => overrides.set(song, &options.dst, id),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_api::song;
+    use crate::report::PlaylistReport;
+
+    fn unmatched(ids: &[&str]) -> Vec<(String, Song)> {
+        ids.iter()
+            .map(|id| {
+                let song = Song {
+                    duration_ms: 0,
+                    ..song(MusicApiType::Spotify, id, &format!("Song {}", id))
+                };
+                ("Playlist".to_string(), song)
+            })
+            .collect()
+    }
+
+    fn options(open_batch: Option<usize>, interactive: bool) -> TriageOptions {
+        TriageOptions {
+            dst: MusicApiType::YtMusic,
+            open_batch,
+            interactive,
+        }
+    }
+
+    #[test]
+    fn test_triage_scripted_input() {
+        let songs = unmatched(&["s1", "s2", "s3", "s4"]);
+        let mut overrides = Overrides::default();
+        let mut opened = vec![];
+        let mut output = vec![];
+        run_triage(
+            &songs,
+            &mut overrides,
+            &options(Some(2), true),
+            "yt1\nb\n\nq\n".as_bytes(),
+            &mut output,
+            |url| opened.push(url.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(overrides.get(&songs[0].1, &MusicApiType::YtMusic), Some("yt1"));
+        assert!(overrides.is_blacklisted(&songs[1].1));
+        assert_eq!(overrides.overrides.len(), 1);
+        assert_eq!(overrides.blacklist.len(), 1);
+        assert_eq!(
+            opened,
+            vec![
+                "https://open.spotify.com/track/s1",
+                "https://open.spotify.com/track/s2",
+                "https://open.spotify.com/track/s3",
+                "https://open.spotify.com/track/s4",
+            ]
+        );
+
+        // decided songs are not asked again
+        let mut output = vec![];
+        run_triage(
+            &songs,
+            &mut overrides,
+            &options(None, true),
+            "".as_bytes(),
+            &mut output,
+            |_| panic!("no url should be opened"),
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("2 unmatched songs to triage"));
+    }
+
+    #[test]
+    fn test_triage_non_interactive() {
+        let songs = unmatched(&["s1", "s2"]);
+        let mut overrides = Overrides::default();
+        let mut output = vec![];
+        run_triage(
+            &songs,
+            &mut overrides,
+            &options(Some(2), false),
+            "yt1\nyt2\n".as_bytes(),
+            &mut output,
+            |_| panic!("no url should be opened"),
+        )
+        .unwrap();
+
+        assert_eq!(overrides, Overrides::default());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("https://open.spotify.com/track/s2"));
+        assert!(!output.contains("[q]uit"));
+    }
+
+    #[test]
+    fn test_load_unmatched() {
+        let dir = crate::mock_api::test_dir("triage");
+        let songs = unmatched(&["s1", "s2", "s3"]);
+        let song = |i: usize| songs[i].1.clone();
+
+        let missing_path = dir.join("missing_songs.json");
+        let missing = BTreeMap::from([("Mix".to_string(), vec![song(0), song(1)])]);
+        std::fs::write(&missing_path, serde_json::to_string(&missing).unwrap()).unwrap();
+        let loaded = load_unmatched(&missing_path).unwrap();
+        assert_eq!(loaded, vec![("Mix".to_string(), song(0)), ("Mix".to_string(), song(1))]);
+
+        // the report written with --report, the missing songs are listed per playlist
+        let report_path = dir.join("report.json");
+        let mut report = SyncReport::new(Some(MusicApiType::Spotify), MusicApiType::YtMusic, false);
+        for (name, missing) in [("Mix", vec![song(0)]), ("Chill", vec![]), ("Road", vec![song(1), song(2)])] {
+            report.push(PlaylistReport {
+                src_id: name.to_lowercase(),
+                src_name: name.to_string(),
+                dst_name: name.to_string(),
+                created: false,
+                skipped: false,
+                unchanged: false,
+                unavailable: None,
+                matched: 0,
+                attempts: 0,
+                conversion_rate: 0.0,
+                missing,
+                local_files: vec![],
+                added: vec![],
+                duplicates_skipped: 0,
+                not_added: vec![],
+                isrc_enriched: 0,
+            });
+        }
+        report.save(&report_path).unwrap();
+        let loaded = load_unmatched(&report_path).unwrap();
+        let ids: Vec<(&str, &str)> =
+            loaded.iter().map(|(playlist, song)| (playlist.as_str(), song.id.as_str())).collect();
+        assert_eq!(ids, vec![("Mix", "s1"), ("Road", "s2"), ("Road", "s3")]);
+
+        std::fs::write(&report_path, r#"{"version": 1}"#).unwrap();
+        assert!(load_unmatched(&report_path).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,7 +1,17 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use color_eyre::Result;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use regex::Regex;
 use serde::de::DeserializeOwned;
-use tracing::{error};
+use tracing::{error, info};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
 
 use crate::{ConfigArgs, music_api::Song};
 
@@ -36,6 +46,13 @@ pub fn clean_enclosure(name: &str, start_tag: char, end_tag: char) -> String {
 pub fn generic_name_clean(name: &str) -> String {
     let mut name = name.to_lowercase();
     let replaces = [
+        ("\u{2019}", ""),
+        ("\u{2018}", ""),
+        ("\u{201c}", ""),
+        ("\u{201d}", ""),
+        ("\u{2013}", "-"),
+        ("\u{2014}", "-"),
+        ("\u{a0}", " "),
         ("'", ""),
         ("\"", ""),
         (":", " "),
@@ -53,7 +70,84 @@ pub fn generic_name_clean(name: &str) -> String {
     }
     let name = clean_enclosure(&name, '(', ')');
     let name = clean_enclosure(&name, '[', ']');
-    name.trim_end().to_string()
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Name folded for comparison only: compatibility decomposition (NFKD), diacritics
+/// stripped and lowercased, "Mötley Crüe" and "ＹＯＡＳＯＢＩ" become "motley crue" and
+/// "yoasobi"
+///
+/// Never used in search queries, stripping the marks of non-Latin scripts changes the words
+pub fn fold_name(name: &str) -> String {
+    name.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            // letters without a decomposition
+            '\u{f8}' => "o".to_string(),
+            '\u{142}' => "l".to_string(),
+            '\u{111}' => "d".to_string(),
+            '\u{e6}' => "ae".to_string(),
+            '\u{153}' => "oe".to_string(),
+            '\u{df}' => "ss".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Song title normalized for comparison, along with the artists credited in it
+///
+/// Qualifiers such as "(Remastered)", "[Deluxe Edition]" or "- Radio Edit" are
+/// stripped, and "feat."/"ft." credits are moved out of the title
+pub fn normalize_title(name: &str) -> (String, Vec<String>) {
+    static PATTERNS: OnceLock<[Regex; 3]> = OnceLock::new();
+    let [enclosed_re, inline_re, split_re] = PATTERNS.get_or_init(|| {
+        [
+            Regex::new(r"(?i)[(\[]\s*(?:feat\.?|ft\.?|featuring)\s+([^)\]]+)[)\]]").unwrap(),
+            Regex::new(r"(?i)\s(?:feat\.?|ft\.?|featuring)\s+(.+?)(?:\s+[-\u{2013}\u{2014}]\s|\s*[(\[]|$)").unwrap(),
+            Regex::new(r"(?i)\s*(?:,|&|\sand\s)\s*").unwrap(),
+        ]
+    });
+
+    let mut featured = vec![];
+    let mut credits = |artists: &str| {
+        featured.extend(
+            split_re
+                .split(artists)
+                .map(generic_name_clean)
+                .filter(|a| !a.is_empty()),
+        );
+    };
+    for cap in enclosed_re.captures_iter(name) {
+        credits(&cap[1]);
+    }
+    let mut title = enclosed_re.replace_all(name, " ").to_string();
+    if let Some(cap) = inline_re.captures(&title) {
+        credits(&cap[1]);
+        let (start, end) = (cap.get(0).unwrap().start(), cap.get(1).unwrap().end());
+        title = format!("{} {}", &title[..start], &title[end..]);
+    }
+
+    let title = generic_name_clean(&title);
+    let title = title.split(" - ").next().unwrap_or(&title);
+    let title = title.split(" pts. ").next().unwrap_or(title);
+    (title.trim_end().to_string(), featured)
+}
+
+/// Playlist name as it will be stored on the destination platform
+pub fn sanitize_playlist_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_control() || ['/', '\\', '|'].contains(&c) {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[inline]
@@ -69,21 +163,189 @@ pub fn clean_isrc(isrc: Option<String>) -> Option<String> {
     None
 }
 
+/// Barcode of an album without the leading zeros, so the 12-digit UPC and the 13-digit EAN
+/// of a release are equal
+pub fn clean_upc(upc: Option<String>) -> Option<String> {
+    let upc = upc?.trim().trim_start_matches('0').to_string();
+    if upc.is_empty() || !upc.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(upc)
+}
+
+/// Year of a release date like `1987-07-27`, `1987-07` or `1987`
+pub fn parse_year(date: &str) -> Option<u16> {
+    let year = date.trim().get(..4)?;
+    if !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    year.parse().ok().filter(|year| *year > 0)
+}
+
 pub fn dedup_songs(songs: &mut Vec<Song>) -> bool {
-    let mut seen = std::collections::HashSet::new();
-    let mut dups = false;
+    !dedup_songs_by(songs, |s| s.id.clone()).is_empty()
+}
+
+/// Remove the songs with the same `key` as an earlier song, the first occurrence is kept.
+/// Returns the removed songs, in playlist order
+pub fn dedup_songs_by<K: Eq + Hash>(songs: &mut Vec<Song>, key: impl Fn(&Song) -> K) -> Vec<Song> {
+    let mut seen = HashSet::new();
+    let mut removed = vec![];
     let mut i = 0;
-    let mut len = songs.len();
-    while i < len {
-        if seen.insert(songs[i].id.clone()) {
+    while i < songs.len() {
+        if seen.insert(key(&songs[i])) {
             i += 1;
         } else {
-            songs.remove(i);
-            dups = true;
-            len -= 1;
+            removed.push(songs.remove(i));
         }
     }
-    dups
+    removed
+}
+
+/// Width of the duration ranges songs are grouped in by `song_identity`
+const IDENTITY_DURATION_MS: usize = 5000;
+
+/// Identity of a song regardless of the release it's from: its ISRC, otherwise its
+/// normalized title, primary artist and duration rounded to 5 seconds
+///
+/// The album and single releases of a song have different ids but the same identity.
+pub fn song_identity(song: &Song) -> String {
+    if let Some(isrc) = &song.isrc {
+        return format!("isrc:{}", isrc);
+    }
+    let title = fold_name(&normalize_title(&song.name).0);
+    let artist = song
+        .artists
+        .first()
+        .map(|a| fold_name(&generic_name_clean(&a.name)))
+        .unwrap_or_default();
+    let duration = (song.duration_ms + IDENTITY_DURATION_MS / 2) / IDENTITY_DURATION_MS;
+    format!("{}|{}|{}", title, artist, duration)
+}
+
+/// Songs of a playlist with the same `song_identity` as an earlier song
+pub fn duplicate_songs(songs: &[Song]) -> Vec<Song> {
+    dedup_songs_by(&mut songs.to_vec(), song_identity)
+}
+
+/// Current time as seconds since the Unix epoch
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// UTC date of a Unix timestamp, as YYYY-MM-DD
+pub fn utc_date(timestamp: u64) -> String {
+    // days since the epoch to the proleptic Gregorian calendar, in eras of 400 years
+    let days = i64::try_from(timestamp / 86400).unwrap_or_default() + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Milliseconds since the Unix epoch of an RFC 3339 date and time, e.g.
+/// `2025-01-10T10:00:05.000+02:00`
+pub fn unix_millis(datetime: &str) -> Option<i64> {
+    let iso8601::DateTime { date, time } = iso8601::datetime(datetime).ok()?;
+    let iso8601::Date::YMD { year, month, day } = date else {
+        return None;
+    };
+    // proleptic Gregorian calendar to days since the epoch, the inverse of `utc_date`
+    let (month, day) = (i64::from(month), i64::from(day));
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86400 + i64::from(time.hour) * 3600 + i64::from(time.minute) * 60 + i64::from(time.second)
+        - i64::from(time.tz_offset_hours) * 3600
+        - i64::from(time.tz_offset_minutes) * 60;
+    Some(seconds * 1000 + i64::from(time.millisecond))
+}
+
+/// Remove the secrets and personal data of a response body before it gets written to disk:
+/// cookie values, SAPISIDHASH and bearer authorizations, and email addresses
+pub fn redact_secrets(text: &str) -> String {
+    static PATTERNS: OnceLock<[(Regex, &str); 5]> = OnceLock::new();
+    let patterns = PATTERNS.get_or_init(|| {
+        [
+            (Regex::new(r#"(?i)("(?:set-)?cookie"\s*:\s*")(?:[^"\\]|\\.)*"#).unwrap(), "${1}REDACTED"),
+            (
+                Regex::new(r"(?i)\b((?:__Secure-[0-9A-Z]+|SAPISID|APISID|SSID|HSID|SID|SIDCC|LOGIN_INFO)=)[^;\s\x22']+")
+                    .unwrap(),
+                "${1}REDACTED",
+            ),
+            (Regex::new(r"SAPISIDHASH\s+[0-9]+_[0-9a-fA-F]+").unwrap(), "SAPISIDHASH REDACTED"),
+            (Regex::new(r"(?i)\bBearer\s+[A-Za-z0-9._~+/=-]+").unwrap(), "Bearer REDACTED"),
+            (Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(), "redacted@example.com"),
+        ]
+    });
+    let mut text = text.to_string();
+    for (re, replacement) in patterns {
+        text = re.replace_all(&text, *replacement).to_string();
+    }
+    text
+}
+
+/// Write a debug file to the debug directory, redacted, returning its path
+pub fn write_diagnostic(config: &ConfigArgs, name: &str, body: &[u8]) -> Result<PathBuf> {
+    let dir = config.debug_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    std::fs::write(&path, redact_secrets(&String::from_utf8_lossy(body)))?;
+    Ok(path)
+}
+
+/// Save the body of a failed request as `error_<status>_<timestamp>.<ext>` in the debug
+/// directory, with the extension of its content, keeping only the newest
+/// `--keep-diagnostics` error files
+pub fn save_error_diagnostic(config: &ConfigArgs, status: u16, text: &str) -> Result<PathBuf> {
+    let extension = if text.trim_start().to_lowercase().starts_with("<html") {
+        "html"
+    } else if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        "json"
+    } else {
+        "txt"
+    };
+    let dir = config.debug_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = unix_timestamp();
+    let mut name = format!("error_{}_{}.{}", status, timestamp, extension);
+    // Don't overwrite a diagnostic saved earlier in the same second (e.g. on refetch)
+    let mut n = 1;
+    while dir.join(&name).exists() {
+        name = format!("error_{}_{}_{}.{}", status, timestamp, n, extension);
+        n += 1;
+    }
+    let path = write_diagnostic(config, &name, text.as_bytes())?;
+    rotate_diagnostics(&dir, config.keep_diagnostics)?;
+    Ok(path)
+}
+
+/// Remove the oldest error diagnostic files of `dir` beyond the `keep` newest ones
+fn rotate_diagnostics(dir: &Path, keep: usize) -> Result<()> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with("error_") {
+            files.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    // newest first, the name breaks the ties of files written in the same instant
+    files.sort_by(|a, b| b.cmp(a));
+    for (_, path) in files.into_iter().skip(keep) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
 }
 
 pub async fn debug_response_json<T>(
@@ -94,19 +356,14 @@ pub async fn debug_response_json<T>(
 where
     T: DeserializeOwned,
 {
-    const DEBUG_FOLDER: &str = "debug";
-
     let res = if config.debug {
         let full = res.bytes().await?;
-        std::fs::write(
-            format!("{}/{}_last_res.json", DEBUG_FOLDER, platform),
-            &full,
-        )?;
+        write_diagnostic(config, &format!("{}_last_res.json", platform), &full)?;
         if full.is_empty() {
             serde_json::from_str("null")?
         } else {
             serde_json::from_slice(&full).inspect_err(|_| {
-                let _ = std::fs::write(format!("debug/{}_last_error.json", platform), &full);
+                let _ = write_diagnostic(config, &format!("{}_last_error.json", platform), &full);
             })?
         }
     } else {
@@ -123,10 +380,111 @@ where
     Ok(res)
 }
 
+/// Pause every `every` calls to an API, the pause growing by `backoff` each time
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    every: usize,
+    pause: Duration,
+    backoff: Duration,
+    calls: usize,
+}
+
+impl RateLimiter {
+    /// `every` = 0 disables the pauses
+    pub fn new(every: usize, pause: Duration, backoff: Duration) -> Self {
+        Self {
+            every,
+            pause,
+            backoff,
+            calls: 0,
+        }
+    }
+
+    /// Register a call, returning how long to wait before making it
+    pub fn tick(&mut self) -> Option<Duration> {
+        if self.every == 0 {
+            return None;
+        }
+        self.calls += 1;
+        if !self.calls.is_multiple_of(self.every) {
+            return None;
+        }
+        let pause = self.pause;
+        self.pause += self.backoff;
+        Some(pause)
+    }
+
+    /// Register a call and wait before making it when needed
+    pub async fn wait(&mut self) {
+        if let Some(pause) = self.tick() {
+            info!(
+                "Reached {} requests, taking a {}-second break...",
+                self.calls,
+                pause.as_secs()
+            );
+            tokio::time::sleep(pause).await;
+        }
+    }
+}
+
+/// Progress bars of the run, `None` until `enable_progress` is called
+static PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Draw the progress bars made by `progress_bar`, only called when stdout is a terminal
+/// without debug logs, so the output stays parseable otherwise
+pub fn enable_progress() {
+    let _ = PROGRESS.set(MultiProgress::new());
+}
+
+/// Progress bar of `len` steps labelled `prefix`, hidden unless `enable_progress` was called
+pub fn progress_bar(len: usize, prefix: &str) -> ProgressBar {
+    let Some(multi) = PROGRESS.get() else {
+        return ProgressBar::hidden();
+    };
+    let style = ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} (ETA {eta}) {wide_msg}")
+        .unwrap()
+        .progress_chars("=> ");
+    let bar = multi.add(ProgressBar::new(len as u64).with_style(style));
+    bar.set_prefix(prefix.to_string());
+    bar
+}
+
+/// Run `f` with the progress bars hidden, for anything written to the terminal
+pub fn suspend_progress<R>(f: impl FnOnce() -> R) -> R {
+    match PROGRESS.get() {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
+
+/// Stdout writer of the logs, printed above the progress bars
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        suspend_progress(|| std::io::stdout().write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_unix_millis() {
+        assert_eq!(unix_millis("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(unix_millis("2025-01-10T10:00:05.250Z"), Some(1_736_503_205_250));
+        // the offsets are taken into account, the same instant in another time zone
+        assert_eq!(unix_millis("2025-01-10T12:30:05.250+02:30"), unix_millis("2025-01-10T10:00:05.250Z"));
+        assert_eq!(unix_millis("2024-02-29T23:00:00-01:00"), unix_millis("2024-03-01T00:00:00Z"));
+        assert_eq!(utc_date(u64::try_from(unix_millis("2024-03-01T00:00:00Z").unwrap() / 1000).unwrap()), "2024-03-01");
+        assert_eq!(unix_millis("yesterday"), None);
+    }
+
     #[test]
     fn test_clean_enclosure() {
         let name =
@@ -138,4 +496,157 @@ mod tests {
         let res = clean_enclosure(name, '(', ')');
         assert_eq!(res, "test  test");
     }
+
+    #[test]
+    fn test_normalize_title() {
+        let cases: [(&str, &str, &[&str]); 24] = [
+            ("Bohemian Rhapsody - Remastered 2011", "bohemian rhapsody", &[]),
+            ("Here Comes The Sun - 2019 Mix", "here comes the sun", &[]),
+            ("Wish You Were Here (Remastered)", "wish you were here", &[]),
+            ("Hotel California (2013 Remaster)", "hotel california", &[]),
+            ("Don\u{2019}t Stop Me Now \u{2013} 2011 Remaster", "dont stop me now", &[]),
+            ("Don't Stop Me Now", "dont stop me now", &[]),
+            ("Smells Like Teen Spirit [Deluxe Edition]", "smells like teen spirit", &[]),
+            ("Blinding Lights - Single Version", "blinding lights", &[]),
+            ("Rolling in the Deep (Radio Edit)", "rolling in the deep", &[]),
+            ("Lose Yourself - From \"8 Mile\" Soundtrack", "lose yourself", &[]),
+            ("One More Time (Radio Edit) - 2001 Remaster", "one more time", &[]),
+            ("Levitating (feat. DaBaby)", "levitating", &["dababy"]),
+            ("Uptown Funk (feat. Bruno Mars)", "uptown funk", &["bruno mars"]),
+            ("Titanium (feat. Sia) [Radio Edit]", "titanium", &["sia"]),
+            (
+                "Get Lucky (feat. Pharrell Williams & Nile Rodgers) - Radio Edit",
+                "get lucky",
+                &["pharrell williams", "nile rodgers"],
+            ),
+            ("Old Town Road (feat. Billy Ray Cyrus) - Remix", "old town road", &["billy ray cyrus"]),
+            ("Sicko Mode ft. Drake", "sicko mode", &["drake"]),
+            ("Lean On feat. MØ & DJ Snake - Radio Edit", "lean on", &["mø", "dj snake"]),
+            ("Empire State of Mind [ft. Alicia Keys]", "empire state of mind", &["alicia keys"]),
+            ("No Role Modelz featuring J. Cole, Kendrick Lamar", "no role modelz", &["j. cole", "kendrick lamar"]),
+            ("It\u{2019}s My Life", "its my life", &[]),
+            ("Sweet Child O\u{2019} Mine", "sweet child o mine", &[]),
+            ("Viva  La\u{a0}Vida ", "viva la vida", &[]),
+            ("Mr. Brightside", "mr. brightside", &[]),
+        ];
+        for (raw, title, featured) in cases {
+            let (res_title, res_featured) = normalize_title(raw);
+            assert_eq!(res_title, title, "title of '{raw}'");
+            assert_eq!(res_featured, featured, "featured artists of '{raw}'");
+        }
+    }
+
+    #[test]
+    fn test_sanitize_playlist_name() {
+        assert_eq!(sanitize_playlist_name("Mix/2023"), "Mix 2023");
+        assert_eq!(sanitize_playlist_name(" Mix  2023\t"), "Mix 2023");
+        assert_eq!(sanitize_playlist_name("Rock | Metal"), "Rock Metal");
+    }
+
+    #[test]
+    fn test_duplicate_songs() {
+        use crate::mock_api::{self, credited};
+        use crate::music_api::MusicApiType;
+
+        let song = |id: &str, name: &str, album: &str, artist: &str, duration_ms: usize| Song {
+            album: Some(mock_api::album(album)),
+            artists: vec![credited(artist)],
+            duration_ms,
+            ..mock_api::song(MusicApiType::YtMusic, id, name)
+        };
+        let mut playlist = vec![
+            song("a1", "Blinding Lights", "After Hours", "The Weeknd", 200_040),
+            song("b1", "Heroes", "Heroes", "David Bowie", 371_000),
+            // single of a song already in the playlist
+            song("a2", "Blinding Lights", "Blinding Lights", "The Weeknd", 201_500),
+            song("b2", "Heroes - 2017 Remaster", "Heroes (2017 Remaster)", "David Bowie", 371_900),
+            // same title, but a cover and an extended version
+            song("c1", "Blinding Lights", "Covers", "Sing King", 200_000),
+            song("a3", "Blinding Lights (Extended Mix)", "After Hours", "The Weeknd", 260_000),
+            song("d1", "Déjà Vu", "B'Day", "Beyoncé", 240_000),
+            song("d2", "Deja Vu", "B'Day (Deluxe)", "BEYONCE", 240_000),
+            song("a1", "Blinding Lights", "After Hours", "The Weeknd", 200_040),
+        ];
+        let ids = |songs: &[Song]| songs.iter().map(|s| s.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&duplicate_songs(&playlist)), vec!["a2", "b2", "d2", "a1"]);
+
+        // songs with an ISRC are only grouped by ISRC
+        playlist[0].isrc = Some("USUG11904206".to_string());
+        playlist[2].isrc = Some("USUG11904207".to_string());
+        playlist[4].isrc = Some("USUG11904206".to_string());
+        assert_eq!(ids(&duplicate_songs(&playlist)), vec!["b2", "c1", "d2"]);
+
+        let mut removed = playlist.clone();
+        assert!(dedup_songs(&mut removed));
+        assert_eq!(removed.len(), playlist.len() - 1);
+        assert!(!dedup_songs(&mut removed));
+    }
+
+    #[test]
+    fn test_progress_disabled() {
+        // tests never enable the progress bars, like runs outside of a terminal
+        let bar = progress_bar(10, "songs");
+        assert!(bar.is_hidden());
+        bar.inc(1);
+        assert_eq!(suspend_progress(|| 1), 1);
+    }
+
+    #[test]
+    fn test_rate_limiter_schedule() {
+        let mut limiter = RateLimiter::new(3, Duration::from_mins(3), Duration::from_mins(1));
+        let pauses: Vec<Option<u64>> = (0..10).map(|_| limiter.tick().map(|d| d.as_secs())).collect();
+        assert_eq!(
+            pauses,
+            vec![None, None, Some(180), None, None, Some(240), None, None, Some(300), None]
+        );
+
+        let mut disabled = RateLimiter::new(0, Duration::from_mins(3), Duration::from_mins(1));
+        assert!((0..10).all(|_| disabled.tick().is_none()));
+    }
+
+    #[test]
+    fn test_redact_secrets() {
+        let text = r#"{"cookie": "SID=abc; __Secure-3PSID=def", "Authorization": "SAPISIDHASH 1712345678_0123abcdef", "email": "someone@gmail.com"}"#;
+        let redacted = redact_secrets(text);
+        assert_eq!(
+            redacted,
+            r#"{"cookie": "REDACTED", "Authorization": "SAPISIDHASH REDACTED", "email": "redacted@example.com"}"#
+        );
+
+        let text = "Set-Cookie: SIDCC=xyz; path=/\nauthorization: Bearer ya29.a0AfH6-token";
+        assert_eq!(
+            redact_secrets(text),
+            "Set-Cookie: SIDCC=REDACTED; path=/\nauthorization: Bearer REDACTED"
+        );
+        assert_eq!(redact_secrets("no secret here"), "no secret here");
+    }
+
+    #[test]
+    fn test_error_diagnostics_rotation() {
+        use clap::Parser;
+
+        let dir = crate::mock_api::test_dir("diagnostics");
+        let config = ConfigArgs::parse_from([
+            "sync_dis_boi",
+            "--debug-dir",
+            dir.to_str().unwrap(),
+            "--keep-diagnostics",
+            "2",
+        ]);
+
+        let first = save_error_diagnostic(&config, 500, "<html>oops</html>").unwrap();
+        assert!(first.extension().unwrap() == "html");
+        std::thread::sleep(Duration::from_millis(20));
+        let second = save_error_diagnostic(&config, 401, r#"{"email": "me@gmail.com"}"#).unwrap();
+        assert!(second.extension().unwrap() == "json");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), r#"{"email": "redacted@example.com"}"#);
+        std::thread::sleep(Duration::from_millis(20));
+        let third = save_error_diagnostic(&config, 429, "slow down").unwrap();
+        assert!(third.extension().unwrap() == "txt");
+
+        // only the 2 newest error files are kept
+        assert!(!first.exists());
+        assert!(second.exists() && third.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,45 +1,63 @@
 pub mod model;
 mod response;
+mod transport;
 
 use std::collections::HashMap;
 use std::fmt::Write;
-use std::io::{self, Read, BufRead};
-use std::path::PathBuf;
-use std::sync::LazyLock;
-use std::thread::sleep;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use atty::Stream;
 
 use async_trait::async_trait;
 use color_eyre::eyre::{Result, eyre};
-use model::{YtMusicAddLikeResponse, YtMusicOAuthDeviceRes};
+use model::{Har, YtMusicAddLikeResponse, YtMusicOAuthDeviceRes};
 use reqwest::header::{HeaderMap, HeaderName};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha1::{Sha1, Digest};
 use tokio::time::Instant;
 use tracing::{debug, info, warn};
 
-use self::model::{YtMusicContinuationResponse, YtMusicPlaylistEditResponse, YtMusicResponse};
+use self::model::{
+    YtMusicContinuationResponse, YtMusicNextResponse, YtMusicPlaylistEditResponse, YtMusicPlaylistPage,
+    YtMusicResponse,
+};
 use crate::ConfigArgs;
+use crate::retry::{RetryAction, RetryPolicy};
 use crate::music_api::{
-    MusicApi, MusicApiType, OAuthRefreshToken, OAuthToken, PLAYLIST_DESC, Playlist, Playlists,
-    Song, Songs,
+    Artist, DeviceFlowPoll, MusicApi, MusicApiType, OAuthRefreshToken, OAuthToken, Playlist,
+    Playlists, Song, Songs, UNKNOWN_COUNTRY, push_candidates,
+};
+use crate::utils::{
+    RateLimiter, debug_response_json, save_error_diagnostic, unix_millis, unix_timestamp,
+    write_diagnostic,
 };
-use crate::utils::debug_response_json;
 use crate::yt_music::model::{YtMusicPlaylistCreateResponse, YtMusicPlaylistDeleteResponse};
-use crate::yt_music::response::{SearchSongUnique, SearchSongs};
+use crate::yt_music::response::{
+    SearchArtists, SearchSongUnique, SearchSongs, SubscribedArtists, UploadedSongs,
+    playlist_unavailable, watch_song,
+};
+use crate::yt_music::transport::{
+    HttpTransport, RecordingTransport, ReplayTransport, YtMusicTransport,
+};
 
-static CONTEXT: LazyLock<serde_json::Value> = LazyLock::new(|| {
-    json!({
+/// `InnerTube` context sent with every request, for the `hl` language and the `gl` region
+fn context(hl: &str, gl: Option<&str>) -> serde_json::Value {
+    let mut context = json!({
         "client": {
             "clientName": "WEB_REMIX",
             "clientVersion": "1.20251006.01.00",
-            "hl": "en"
+            "hl": hl
         },
         "user": {}
-    })
-});
+    });
+    if let Some(gl) = gl {
+        context["client"]["gl"] = json!(gl);
+    }
+    context
+}
 
 #[derive(Debug, Clone)]
 pub enum YtMusicAuthType {
@@ -55,21 +73,131 @@ pub enum YtMusicAuthType {
     },
 }
 
+/// Content of a browser headers file
+struct BrowserHeaders {
+    headers: HeaderMap,
+    sapisid: String,
+    origin: String,
+    auth_user: Option<String>,
+    saved_ytcfg: Option<SavedYtCfg>,
+}
+
+/// Ytcfg of the homepage saved in the browser headers file, to skip fetching the homepage
+/// while the visitor id is recent
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct SavedYtCfg {
+    visitor_data: String,
+    gl: Option<String>,
+    /// Unix timestamp of the fetch, in seconds
+    fetched_at: u64,
+}
+
+impl SavedYtCfg {
+    /// Key of the saved ytcfg in the headers file, its object value isn't sent as a header
+    const KEY: &'static str = "sync_dis_boi_ytcfg";
+
+    fn new(ytcfg: &YtCfg, fetched_at: u64) -> Option<Self> {
+        Some(Self {
+            visitor_data: ytcfg.visitor_data.clone()?,
+            gl: ytcfg.gl.clone(),
+            fetched_at,
+        })
+    }
+
+    fn is_fresh(&self, ttl: Duration, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < ttl.as_secs()
+    }
+
+    fn ytcfg(&self) -> YtCfg {
+        YtCfg {
+            visitor_data: Some(self.visitor_data.clone()),
+            gl: self.gl.clone(),
+        }
+    }
+
+    /// Write the ytcfg to the headers file, keeping its headers
+    fn save(&self, headers_path: &Path) -> Result<()> {
+        let header_data = std::fs::read_to_string(headers_path)?;
+        let mut header_json: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&header_data)?;
+        header_json.insert(Self::KEY.to_string(), serde_json::to_value(self)?);
+        std::fs::write(headers_path, serde_json::to_string_pretty(&header_json)?)?;
+        Ok(())
+    }
+}
+
 pub struct YtMusicApi {
     client: reqwest::Client,
+    transport: Box<dyn YtMusicTransport>,
     auth_type: YtMusicAuthType,
     last_token_refresh: Instant,
+    /// Pauses between searches and playlist/likes modifications
+    pacing: RateLimiter,
+    /// Country of the account, read from the ytcfg of the homepage
+    country_code: String,
+    /// `InnerTube` context of the requests, see `context`
+    context: serde_json::Value,
+    /// x-goog-authuser sent with every request, the account index of the browser cookies
+    auth_user: Option<String>,
+    /// x-goog-visitor-id sent with every request of the browser authentication
+    visitor_id: Option<String>,
+    /// Whether `visitor_id` was fetched from the homepage during this run
+    visitor_id_fetched: bool,
+    /// Time spent waiting before retrying rate limited or failed requests
+    backoff_time: Duration,
     config: ConfigArgs,
 }
 
-/// Action to take after rate limit detection
-enum RateLimitAction {
-    /// Retry the request after the specified backoff duration
-    Retry(Duration),
-    /// Rate limit was not detected, continue with normal processing
-    Continue,
-    /// Maximum retries exceeded, abort the request
-    MaxRetriesExceeded,
+/// Configuration set by the `ytcfg.set({...})` calls of the music.youtube.com homepage
+#[derive(Debug, Default, PartialEq)]
+struct YtCfg {
+    visitor_data: Option<String>,
+    /// `INNERTUBE_CONTEXT_GL`, the country of the account, or `GL` when it's missing
+    gl: Option<String>,
+}
+
+impl YtCfg {
+    fn parse(html: &str) -> Result<Self> {
+        let re = regex::Regex::new(r"ytcfg\.set\s*\(\s*(\{.+?\})\s*\)\s*;")?;
+        let mut ytcfg = Self::default();
+        let mut page_gl = None;
+        for captures in re.captures_iter(html) {
+            let Ok(values) = serde_json::from_str::<serde_json::Value>(&captures[1]) else {
+                continue;
+            };
+            let value = |key: &str| {
+                values
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+            };
+            ytcfg.visitor_data = ytcfg.visitor_data.or_else(|| value("VISITOR_DATA"));
+            ytcfg.gl = ytcfg.gl.or_else(|| value("INNERTUBE_CONTEXT_GL"));
+            page_gl = page_gl.or_else(|| value("GL"));
+        }
+        // the country of the page itself, when the one of the client isn't set
+        ytcfg.gl = ytcfg.gl.or(page_gl);
+        if ytcfg.visitor_data.is_none() && ytcfg.gl.is_none() {
+            return Err(eyre!("Could not extract the ytcfg from YouTube Music response"));
+        }
+        Ok(ytcfg)
+    }
+
+    /// Country code of the account, `UNKNOWN` when it wasn't found
+    fn country_code(&self) -> String {
+        match &self.gl {
+            Some(gl) if gl.len() == 2 && gl.chars().all(|c| c.is_ascii_alphabetic()) => {
+                gl.to_uppercase()
+            }
+            _ => {
+                warn!(
+                    "country of the YouTube Music account not found, it can't be compared to the other platform"
+                );
+                UNKNOWN_COUNTRY.to_string()
+            }
+        }
+    }
 }
 
 impl YtMusicApi {
@@ -79,17 +207,121 @@ impl YtMusicApi {
     const OAUTH_SCOPE: &'static str = "https://www.googleapis.com/auth/youtube";
     const OAUTH_CODE_URL: &'static str = "https://www.youtube.com/o/oauth2/device/code";
     const OAUTH_TOKEN_URL: &'static str = "https://oauth2.googleapis.com/token";
+    /// Growth of the rate limit pause after each pause
+    const PAUSE_BACKOFF: Duration = Duration::from_mins(1);
     const OAUTH_GRANT_TYPE: &'static str = "http://oauth.net/grant_type/device/1.0";
     const OAUTH_USER_AGENT: &'static str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:88.0) Gecko/20100101 Firefox/88.0 Cobalt/Version";
     const RES_DEBUG_FILENAME: &'static str = MusicApiType::YtMusic.short_name();
+    /// Id of the "Your Likes" playlist, listed with the library playlists
+    pub const LIKES_PLAYLIST_ID: &'static str = "LM";
+    /// Browse id of the uploaded songs, also the id of their pseudo-playlist
+    pub const UPLOADS_PLAYLIST_ID: &'static str = "FEmusic_library_privately_owned_tracks";
+    /// Browse id of the artists the library is subscribed to
+    const SUBSCRIPTIONS_BROWSE_ID: &'static str = "FEmusic_library_corpus_artists";
+    /// Number of likes between two progress logs
+    const LIKES_PROGRESS_EVERY: usize = 50;
     
-    // Retry configuration for rate limiting
-    const MAX_RETRIES: u32 = 5;  // 6 total attempts (0-5)
-    const MAX_BACKOFF_SECS: u64 = 900;  // Cap exponential backoff at 900 seconds (15 minutes)
+    // Refetches allowed when a 200 response is not valid JSON (truncated body, HTML interstitial)
+    const MAX_PARSE_RETRIES: usize = 1;
+    /// Retries of the rate limits, the waits double from a minute.
+    /// With the default cap: 60s, 120s, 240s, 480s, 900s, 900s
+    const RATE_LIMIT_POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 5,
+        initial_backoff: Duration::from_mins(1),
+        max_backoff: Duration::from_mins(15),
+    };
+    /// Retries of the server errors and dropped connections, counted apart from rate limits.
+    /// Sequence: 2s, 4s, 8s, 16s for 5 total attempts
+    const TRANSIENT_POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 4,
+        initial_backoff: Duration::from_secs(2),
+        max_backoff: Duration::from_mins(1),
+    };
 
     /// Create a new YtMusicApi instance using browser authentication
     pub async fn new_browser(headers_path: PathBuf, config: ConfigArgs) -> Result<Self> {
-        let header_data = std::fs::read_to_string(&headers_path)?;
+        let BrowserHeaders { mut headers, sapisid, origin, auth_user, saved_ytcfg } =
+            Self::read_browser_headers(&headers_path, &config)?;
+        info!("Browser auth initialized with origin: {}", origin);
+
+        let ttl = Duration::from_secs(config.yt_visitor_id_ttl_hours * 3600);
+        let (ytcfg, visitor_id_fetched) = match saved_ytcfg {
+            Some(saved) if saved.is_fresh(ttl, unix_timestamp()) => {
+                debug!("Reusing the saved X-Goog-Visitor-Id: {}", saved.visitor_data);
+                (saved.ytcfg(), false)
+            }
+            saved => {
+                // Fetch X-Goog-Visitor-Id from YouTube Music when the saved one is missing or old
+                debug!("Fetching X-Goog-Visitor-Id from YouTube Music...");
+
+                // Build a temporary client with base headers only
+                let temp_client = reqwest::Client::builder()
+                    .cookie_store(true)
+                    .default_headers(headers.clone())
+                    .build()?;
+
+                let ytcfg = Self::fetch_ytcfg(&temp_client, &origin).await.unwrap_or_default();
+                match (SavedYtCfg::new(&ytcfg, unix_timestamp()), saved) {
+                    (Some(fetched), _) => {
+                        debug!("Fetched X-Goog-Visitor-Id: {}", fetched.visitor_data);
+                        if let Err(e) = fetched.save(&headers_path) {
+                            warn!("Failed to save the X-Goog-Visitor-Id: {}", e);
+                        }
+                        (ytcfg, true)
+                    }
+                    (None, Some(saved)) => {
+                        warn!("Failed to fetch X-Goog-Visitor-Id, reusing the saved one");
+                        (saved.ytcfg(), false)
+                    }
+                    (None, None) => {
+                        warn!("Failed to fetch X-Goog-Visitor-Id, stopping initialization");
+                        return Err(eyre!("Failed to fetch X-Goog-Visitor-Id, cannot continue."));
+                    }
+                }
+            }
+        };
+        
+        // Remove encoding headers that can cause issues
+        headers.remove("accept-encoding");
+        headers.remove("content-encoding");
+        headers.remove("content-length");
+
+        let mut client = reqwest::ClientBuilder::new()
+            .cookie_store(true)
+            .default_headers(headers);
+
+        if let Some(proxy) = &config.proxy {
+            client = client
+                .proxy(reqwest::Proxy::all(proxy)?)
+                .danger_accept_invalid_certs(true);
+        }
+        let client = client.build()?;
+
+        let auth_type = YtMusicAuthType::Browser { 
+            headers_path,
+            sapisid,
+            origin,
+        };
+
+        Ok(YtMusicApi { 
+            client, 
+            transport: Box::new(HttpTransport),
+            auth_type,
+            last_token_refresh: Instant::now(), 
+            pacing: Self::pacing(&config),
+            country_code: ytcfg.country_code(),
+            context: Self::request_context(&config, &ytcfg.country_code()),
+            auth_user,
+            visitor_id: ytcfg.visitor_data,
+            visitor_id_fetched,
+            backoff_time: Duration::ZERO,
+            config 
+        })
+    }
+    
+    /// Read the headers file of the browser authentication, without any request
+    fn read_browser_headers(headers_path: &Path, config: &ConfigArgs) -> Result<BrowserHeaders> {
+        let header_data = std::fs::read_to_string(headers_path)?;
         let header_json: serde_json::Map<String, serde_json::Value> =
             serde_json::from_str(&header_data)?;
         
@@ -109,6 +341,18 @@ impl YtMusicApi {
         
         // Extract __Secure-3PAPISID from cookies for SAPISIDHASH generation
         let sapisid = Self::extract_sapisid(cookie_str)?;
+
+        // The account index of the cookies, the one saved with the headers by default
+        let saved_auth_user = header_json
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("x-goog-authuser"))
+            .and_then(|(_, v)| v.as_str());
+        let auth_user = Self::auth_user(config, saved_auth_user);
+
+        // an unreadable saved ytcfg is fetched again
+        let saved_ytcfg = header_json
+            .get(SavedYtCfg::KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
         
         // Get origin URL
         let origin = header_json
@@ -118,8 +362,6 @@ impl YtMusicApi {
             .unwrap_or("https://music.youtube.com")
             .to_string();
         
-        info!("Browser auth initialized with origin: {}", origin);
-        
         let mut headers = HeaderMap::new();
         
         for (key, val) in header_json.into_iter() {
@@ -129,7 +371,7 @@ impl YtMusicApi {
                     continue;
                 }
                 
-                // Skip X-Goog-Visitor-Id - we'll always fetch it fresh
+                // Skip X-Goog-Visitor-Id - it's sent with each request, fetched or saved
                 if key.to_lowercase() == "x-goog-visitor-id" {
                     continue;
                 }
@@ -150,54 +392,9 @@ impl YtMusicApi {
                 );
             }
         }
-        
-        // Always fetch X-Goog-Visitor-Id fresh from YouTube Music on initialization
-        debug!("Fetching X-Goog-Visitor-Id from YouTube Music...");
-        
-        // Build a temporary client with base headers only
-        let temp_client = reqwest::Client::builder()
-            .cookie_store(true)
-            .default_headers(headers.clone())
-            .build()?;
-        
-        if let Ok(visitor_id) = Self::fetch_visitor_id(&temp_client, &origin).await {
-            debug!("Fetched X-Goog-Visitor-Id: {}", visitor_id);
-            headers.insert("x-goog-visitor-id", visitor_id.parse()?);
-        } else {
-            warn!("Failed to fetch X-Goog-Visitor-Id, stopping initialization");
-            return Err(eyre!("Failed to fetch X-Goog-Visitor-Id, cannot continue."));
-        }
-        
-        // Remove encoding headers that can cause issues
-        headers.remove("accept-encoding");
-        headers.remove("content-encoding");
-        headers.remove("content-length");
-
-        let mut client = reqwest::ClientBuilder::new()
-            .cookie_store(true)
-            .default_headers(headers);
-
-        if let Some(proxy) = &config.proxy {
-            client = client
-                .proxy(reqwest::Proxy::all(proxy)?)
-                .danger_accept_invalid_certs(true)
-        }
-        let client = client.build()?;
-
-        let auth_type = YtMusicAuthType::Browser { 
-            headers_path,
-            sapisid,
-            origin,
-        };
-
-        Ok(YtMusicApi { 
-            client, 
-            auth_type,
-            last_token_refresh: Instant::now(), 
-            config 
-        })
+        Ok(BrowserHeaders { headers, sapisid, origin, auth_user, saved_ytcfg })
     }
-    
+
     /// Ensure SOCS=CAI cookie is present in cookie string
     /// SOCS (Sign Out Cookie Status) is required by Google to acknowledge cookie policy
     /// See: https://policies.google.com/technologies/cookies
@@ -221,8 +418,8 @@ impl YtMusicApi {
         }
     }
     
-    /// Fetch X-Goog-Visitor-Id from YouTube Music homepage
-    async fn fetch_visitor_id(client: &reqwest::Client, origin: &str) -> Result<String> {
+    /// Fetch the ytcfg of the music.youtube.com homepage
+    async fn fetch_ytcfg(client: &reqwest::Client, origin: &str) -> Result<YtCfg> {
         // reqwest with gzip feature automatically handles decompression
         let response = client
             .get(origin)
@@ -230,26 +427,7 @@ impl YtMusicApi {
             .await?
             .text()
             .await?;
-        
-        // Look for ytcfg.set({...}) in the response
-        let re = regex::Regex::new(r"ytcfg\.set\s*\(\s*(\{.+?\})\s*\)\s*;")?;
-        
-        for captures in re.captures_iter(&response) {
-            if let Some(json_str) = captures.get(1) {
-                // Try to parse as JSON
-                if let Ok(ytcfg) = serde_json::from_str::<serde_json::Value>(json_str.as_str()) {
-                    if let Some(visitor_data) = ytcfg.get("VISITOR_DATA") {
-                        if let Some(visitor_id) = visitor_data.as_str() {
-                            if !visitor_id.is_empty() {
-                                return Ok(visitor_id.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        
-        Err(eyre!("Could not extract VISITOR_DATA from YouTube Music response"))
+        YtCfg::parse(&response)
     }
     
     /// Extract __Secure-3PAPISID from cookie string
@@ -340,10 +518,25 @@ impl YtMusicApi {
             oauth_token_path,
         };
 
+        // the web pages ignore the OAuth token, their region would be the one of the IP
+        // address rather than the country of the account
+        info!(
+            "the country of the YouTube Music account isn't known with OAuth, it can't be \
+            compared to the other platform"
+        );
+
         Ok(YtMusicApi { 
             client, 
+            transport: Box::new(HttpTransport),
             auth_type,
             last_token_refresh: Instant::now(), 
+            pacing: Self::pacing(&config),
+            country_code: UNKNOWN_COUNTRY.to_string(),
+            context: Self::request_context(&config, UNKNOWN_COUNTRY),
+            auth_user: Self::auth_user(&config, None),
+            visitor_id: None,
+            visitor_id_fetched: false,
+            backoff_time: Duration::ZERO,
             config 
         })
     }
@@ -380,6 +573,54 @@ impl YtMusicApi {
         Self::setup_browser_from_raw(&headers_raw, output_file)
     }
 
+    /// Setup browser authentication from a HAR file exported by the browser (non-interactive)
+    /// 
+    /// The headers of the newest request to the `YouTube` Music API are used, so the HAR can be
+    /// recorded while browsing music.youtube.com logged in, whatever the account.
+    /// 
+    /// # Arguments
+    /// * `har_file` - Path to the HAR file exported from the network tab of the `DevTools`
+    /// * `output_file` - Optional path to save the processed headers JSON
+    /// 
+    /// # Returns
+    /// JSON string of processed headers
+    pub fn setup_browser_from_har(har_file: &PathBuf, output_file: Option<PathBuf>) -> Result<String> {
+        let har = std::fs::read_to_string(har_file)?;
+        Self::parse_and_save_headers(Self::har_headers(&har)?, output_file)
+    }
+
+    /// Headers of the newest `youtubei/v1` request of a HAR, as "name: value" lines
+    fn har_headers(har: &str) -> Result<Vec<String>> {
+        let har: Har = serde_json::from_str(har).map_err(|e| eyre!("Invalid HAR file: {}", e))?;
+        let (_, entry) = har
+            .log
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.request.url.contains("music.youtube.com/youtubei/v1/"))
+            // entries are usually sorted already, the position breaks ties. The times can have
+            // different offsets, an entry whose time can't be parsed is the oldest
+            .max_by_key(|(i, e)| (unix_millis(&e.started_date_time), *i))
+            .ok_or_else(|| {
+                eyre!(
+                    "No request to music.youtube.com/youtubei/v1/ found in the HAR file. \
+                    Record it while browsing YouTube Music logged in, e.g. while opening your library."
+                )
+            })?;
+        if !entry.request.headers.iter().any(|h| h.name.eq_ignore_ascii_case("cookie")) {
+            return Err(eyre!(
+                "The YouTube Music requests of the HAR file have no cookie. \
+                Export the HAR with its sensitive data (\"Export HAR (with sensitive data)\" in Chrome)."
+            ));
+        }
+        Ok(entry
+            .request
+            .headers
+            .iter()
+            .map(|h| format!("{}: {}", h.name, h.value))
+            .collect())
+    }
+
     /// Setup browser authentication interactively
     /// 
     /// This method prompts the user to paste headers from stdin.
@@ -555,113 +796,372 @@ impl YtMusicApi {
         );
         if webbrowser::open(&auth_url).is_err() {
             info!("Please authorize the app by visiting the following URL: {}", auth_url);
-        } else {
-            info!("Please authorize the app in your browser and press enter");
-        }
-
-        if atty::is(Stream::Stdin) {
-            // Interactive: wait for user input
-            std::io::stdin().read_exact(&mut [0])?;
-        } else {
-            for i in (0..60).step_by(5) {
-                info!("Waiting for user authorization... {} seconds remaining", 60 - i);
-                sleep(Duration::from_secs(5));
-            }
-            info!("Countdown finished. Proceeding with next steps.");
         }
 
-        // 2. request the token
+        // 2. poll the token endpoint until the app is authorized
         let mut params = HashMap::new();
         params.insert("client_id", client_id);
         params.insert("code", &oauth_res.device_code);
         params.insert("client_secret", client_secret);
         params.insert("grant_type", Self::OAUTH_GRANT_TYPE);
-        let res = client
-            .post(Self::OAUTH_TOKEN_URL)
-            .form(&params)
-            .send()
-            .await?;
-        let status = res.status();
-        let token: OAuthToken = debug_response_json(config, res, Self::RES_DEBUG_FILENAME).await?;
-        if !status.is_success() {
-            return Err(eyre!("Invalid HTTP status: {}", status));
+        let mut poll = DeviceFlowPoll::new(oauth_res.interval, oauth_res.expires_in);
+        poll.run(
+            || client.post(Self::OAUTH_TOKEN_URL).form(&params),
+            config,
+            Self::RES_DEBUG_FILENAME,
+        )
+        .await
+    }
+
+    /// Create a new `YtMusicApi` instance from a browser headers file, falling back to `OAuth2`
+    /// authentication when the headers are no longer logged in and a client id and secret
+    /// are given
+    pub async fn new_headers(
+        headers_path: PathBuf,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+        oauth_token_path: PathBuf,
+        clear_cache: bool,
+        config: ConfigArgs,
+    ) -> Result<Self> {
+        let api = Self::new_browser(headers_path, config.clone()).await?;
+        api.or_oauth(client_id, client_secret, oauth_token_path, clear_cache, config).await
+    }
+
+    /// Keep the browser authentication while it is logged in, `OAuth2` is the fallback once it
+    /// is not
+    async fn or_oauth(
+        mut self,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+        oauth_token_path: PathBuf,
+        clear_cache: bool,
+        config: ConfigArgs,
+    ) -> Result<Self> {
+        if self.probe_logged_in().await? {
+            return Ok(self);
         }
+        let (Some(client_id), Some(client_secret)) = (client_id, client_secret) else {
+            return Err(eyre!(
+                "Authentication failed: the browser headers are no longer logged in (logged_in=0).\n\
+                Please refresh your headers by running:\n  \
+                cargo run --example setup_ytmusic_browser\n\
+                or provide --client-id and --client-secret to fall back to OAuth authentication."
+            ));
+        };
+        warn!("the browser headers are no longer logged in, falling back to OAuth authentication");
+        Self::new_oauth(client_id, client_secret, oauth_token_path, clear_cache, config).await
+    }
 
-        Ok(token)
+    /// Whether the credentials are still logged in, probed with a request to the library
+    async fn probe_logged_in(&mut self) -> Result<bool> {
+        let body = self.add_context(&json!({"browseId": "FEmusic_liked_playlists"}));
+        let endpoint = Self::build_endpoint("browse", None);
+        let res = self
+            .transport
+            .post(&self.client, &endpoint, &body, self.request_headers()?)
+            .await?;
+        Ok(!Self::not_logged_in(&res.text))
     }
 
-    /*pub fn new_headers(headers: &PathBuf, config: ConfigArgs) -> Result<Self> {
-        let header_data = std::fs::read_to_string(headers)?;
-        let header_json: serde_json::Map<String, serde_json::Value> =
-            serde_json::from_str(&header_data)?;
-        let mut headers = HeaderMap::new();
-        for (key, val) in header_json {
-            if let serde_json::Value::String(s) = val {
-                headers.insert(
-                    HeaderName::from_bytes(key.to_lowercase().as_bytes())?,
-                    s.parse()?,
-                );
+    /// Create an offline instance answering requests with the fixtures recorded in `fixtures_dir`
+    ///
+    /// This is a developer tool to step through the parsing of responses recorded by a user
+    /// with `record_fixtures`, no request reaches the network.
+    pub fn new_replay(fixtures_dir: PathBuf, config: ConfigArgs) -> Result<Self> {
+        info!("replaying YtMusic responses from {:?}", fixtures_dir);
+        Ok(Self::with_transport(ReplayTransport::new(fixtures_dir)?, config))
+    }
+
+    /// Write every response received from now on to `fixtures_dir`, with personal data redacted
+    pub fn record_fixtures(mut self, fixtures_dir: PathBuf) -> Result<Self> {
+        info!("recording YtMusic responses to {:?}", fixtures_dir);
+        self.transport = Box::new(RecordingTransport::new(self.transport, fixtures_dir)?);
+        Ok(self)
+    }
+
+    fn pacing(config: &ConfigArgs) -> RateLimiter {
+        RateLimiter::new(
+            config.yt_pause_every,
+            Duration::from_secs(config.yt_pause_secs),
+            Self::PAUSE_BACKOFF,
+        )
+    }
+
+    /// Create an offline instance sending its requests through the given transport
+    fn with_transport(transport: impl YtMusicTransport + 'static, config: ConfigArgs) -> Self {
+        YtMusicApi {
+            client: reqwest::Client::new(),
+            transport: Box::new(transport),
+            auth_type: YtMusicAuthType::Browser {
+                headers_path: PathBuf::new(),
+                sapisid: String::new(),
+                origin: "https://music.youtube.com".to_string(),
+            },
+            last_token_refresh: Instant::now(),
+            pacing: Self::pacing(&config),
+            country_code: UNKNOWN_COUNTRY.to_string(),
+            context: Self::request_context(&config, UNKNOWN_COUNTRY),
+            auth_user: Self::auth_user(&config, None),
+            visitor_id: None,
+            visitor_id_fetched: false,
+            backoff_time: Duration::ZERO,
+            config,
+        }
+    }
+
+    /// Move `song` of `playlist` right before `before`, or to the end of the playlist
+    pub async fn move_song_before(
+        &mut self,
+        playlist: &mut Playlist,
+        song: &Song,
+        before: Option<&Song>,
+    ) -> Result<()> {
+        self.ensure_set_video_ids(playlist).await?;
+        let not_found = |s: &Song| eyre!("Song {} not found in playlist {}", s, playlist.name);
+        let from = Self::find_song(&playlist.songs, song, &[]).ok_or_else(|| not_found(song))?;
+        let to = match before {
+            Some(before) => {
+                Some(Self::find_song(&playlist.songs, before, &[]).ok_or_else(|| not_found(before))?)
             }
+            None => None,
+        };
+        // already in place
+        if to.unwrap_or(playlist.songs.len()) == from + 1 || to == Some(from) {
+            return Ok(());
         }
-        headers.remove("accept-encoding");
-        headers.remove("content-encoding");
 
-        let mut client = reqwest::ClientBuilder::new()
-            .cookie_store(true)
-            .default_headers(headers);
+        let sid = |i: usize| playlist.songs[i].sid.as_deref().ok_or(eyre!("Song setVideoId not found"));
+        let action = Self::move_action(sid(from)?, to.map(sid).transpose()?);
+        self.edit_playlist_order(&playlist.id, vec![action]).await?;
 
-        if let Some(proxy) = &config.proxy {
-            client = client
-                .proxy(reqwest::Proxy::all(proxy)?)
-                .danger_accept_invalid_certs(true);
+        let moved = playlist.songs.remove(from);
+        let to = to.map_or(playlist.songs.len(), |to| if to > from { to - 1 } else { to });
+        playlist.songs.insert(to, moved);
+        Ok(())
+    }
+
+    /// Refetch the songs of `playlist` when some miss their setVideoId, songs added during
+    /// this run don't have one until the playlist is fetched again
+    async fn ensure_set_video_ids(&mut self, playlist: &mut Playlist) -> Result<()> {
+        if playlist.songs.iter().all(|s| s.sid.is_some()) {
+            return Ok(());
         }
-        let client = client.build()?;
+        debug!("refetching playlist {} to learn its setVideoIds", playlist.name);
+        playlist.songs = self.get_playlist_songs(&playlist.id).await?;
+        Ok(())
+    }
 
-        Ok(YtMusicApi { client, auth_type, last_token_refresh: Instant::now(), config  })
-    }*/
+    /// Position of `song` in `songs`, by setVideoId when it has one, else by video id among
+    /// the positions not `taken` yet
+    fn find_song(songs: &[Song], song: &Song, taken: &[bool]) -> Option<usize> {
+        let free = |i: &usize| taken.get(*i) != Some(&true);
+        if let Some(sid) = &song.sid
+            && let Some(i) = songs.iter().position(|s| s.sid.as_ref() == Some(sid))
+        {
+            return free(&i).then_some(i);
+        }
+        (0..songs.len()).filter(free).find(|&i| songs[i].id == song.id)
+    }
 
-    fn build_endpoint(path: &str, ctoken: Option<&str>) -> String {
-        let mut endpoint = format!("{}{}{}", Self::BASE_API, path, Self::BASE_PARAMS,);
-        if let Some(c) = ctoken {
-            std::write!(&mut endpoint, "&ctoken={c}&continuation={c}", c = c).unwrap();
+    /// Action moving the song with the setVideoId `sid` before the one with `successor`,
+    /// or to the end of the playlist
+    fn move_action(sid: &str, successor: Option<&str>) -> serde_json::Value {
+        let mut action = json!({
+            "action": "ACTION_MOVE_VIDEO_BEFORE",
+            "setVideoId": sid,
+        });
+        if let Some(successor) = successor {
+            action["movedSetVideoIdSuccessor"] = json!(successor);
         }
-        endpoint
+        action
     }
 
-    fn add_context(body: &serde_json::Value) -> serde_json::Value {
-        let mut body = body.clone();
-        match body.as_object_mut() {
-            Some(o) => o.insert("context".to_string(), CONTEXT.clone()),
-            _ => unreachable!(),
-        };
-        body
+    /// Moves turning the `current` order of the setVideoIds of a playlist into `target`,
+    /// a permutation of it
+    fn move_actions(current: &[String], target: &[String]) -> Vec<serde_json::Value> {
+        let mut current = current.to_vec();
+        let mut actions = vec![];
+        for (i, sid) in target.iter().enumerate() {
+            let Some(from) = current[i..].iter().position(|s| s == sid).map(|p| p + i) else {
+                continue;
+            };
+            if from == i {
+                continue;
+            }
+            actions.push(Self::move_action(sid, Some(&current[i])));
+            let moved = current.remove(from);
+            current.insert(i, moved);
+        }
+        actions
     }
 
-    /// Check for authentication errors in the response
-    fn check_authentication_errors(&self, text: &str) -> Result<()> {
-        // YouTube Music can return not-logged-in status in two formats:
-        // 1. Direct: "logged_in": "0" 
-        // 2. ServiceTrackingParams: {"key": "logged_in", "value": "0"}
-        let is_not_logged_in = 
-            // Check for direct format
-            text.contains(r#""logged_in":"0"#) || text.contains(r#""logged_in": "0"#)
-            // Check for key-value format in serviceTrackingParams
-            || ((text.contains(r#""key":"logged_in""#) || text.contains(r#""key": "logged_in""#)) 
-                && (text.contains(r#""value":"0""#) || text.contains(r#""value": "0""#)));
-        
-        if is_not_logged_in {
-            if matches!(self.auth_type, YtMusicAuthType::Browser { .. }) {
-                return Err(eyre!(
-                    "Authentication failed: Not logged in (logged_in=0).\n\
-                    Your browser authentication tokens have expired or are invalid.\n\
-                    This usually means your cookies or SAPISID have expired.\n\n\
-                    Please refresh your headers by running:\n  \
-                    cargo run --example setup_ytmusic_browser\n\n\
-                    Make sure you:\n\
-                    1. Are logged into YouTube Music in your browser\n\
-                    2. Copy headers from an authenticated request (like /browse)\n\
-                    3. Include ALL cookies, especially __Secure-3PAPISID"
-                ));
+    /// Send a request of the `like` endpoint `path` for each song, one at a time as they can't
+    /// be batched. `what` names the requests in the logs.
+    async fn rate_songs(&mut self, path: &str, songs: &[Song], what: &str) -> Result<()> {
+        let delay = Duration::from_millis(self.config.yt_like_delay_ms);
+        let mut failed = vec![];
+        for (i, song) in songs.iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(delay).await;
+            }
+            self.pacing.wait().await;
+            let body = json!({
+                "target": {
+                    "videoId": song.id,
+                }
+            });
+            // a rejected song shouldn't prevent rating the following ones
+            if let Err(e) = self.make_request::<YtMusicAddLikeResponse>(path, &body, None).await {
+                warn!("Failed {} request for {}: {}", path, song.id, e);
+                failed.push(song.id.as_str());
+            }
+            if (i + 1) % Self::LIKES_PROGRESS_EVERY == 0 {
+                info!("Sent {}/{} {} ({} failed)", i + 1, songs.len(), what, failed.len());
+            }
+        }
+        Self::likes_outcome(&failed, songs.len(), what, self.config.yt_like_failure_tolerance)
+    }
+
+    /// Summary of the like requests that failed, only a warning when they are at most
+    /// `tolerance` of the `total` requests
+    #[allow(clippy::cast_precision_loss)]
+    fn likes_outcome(failed: &[&str], total: usize, what: &str, tolerance: f64) -> Result<()> {
+        if failed.is_empty() {
+            return Ok(());
+        }
+        let summary = format!(
+            "{} of the {} {} failed, video ids: {}",
+            failed.len(),
+            total,
+            what,
+            failed.join(", ")
+        );
+        if failed.len() as f64 <= total as f64 * tolerance {
+            warn!("{}", summary);
+            Ok(())
+        } else {
+            Err(eyre!(summary))
+        }
+    }
+
+    async fn edit_playlist_order(&mut self, id: &str, actions: Vec<serde_json::Value>) -> Result<()> {
+        self.pacing.wait().await;
+        let body = json!({
+            "playlistId": id,
+            "actions": actions,
+        });
+        let response = self
+            .make_request::<YtMusicPlaylistEditResponse>("browse/edit_playlist", &body, None)
+            .await?;
+        if response.success() {
+            Ok(())
+        } else {
+            Err(eyre!("Error reordering playlist"))
+        }
+    }
+
+    fn build_endpoint(path: &str, ctoken: Option<&str>) -> String {
+        let mut endpoint = format!("{}{}{}", Self::BASE_API, path, Self::BASE_PARAMS,);
+        if let Some(c) = ctoken {
+            std::write!(&mut endpoint, "&ctoken={c}&continuation={c}", c = c).unwrap();
+        }
+        endpoint
+    }
+
+    /// Context of the `--yt-lang` language and the `--yt-region` region, or the country of
+    /// the account when it's known
+    fn request_context(config: &ConfigArgs, country_code: &str) -> serde_json::Value {
+        let gl = config
+            .yt_region
+            .as_deref()
+            .or((country_code != UNKNOWN_COUNTRY).then_some(country_code));
+        context(&config.yt_lang, gl)
+    }
+
+    fn add_context(&self, body: &serde_json::Value) -> serde_json::Value {
+        let mut context = self.context.clone();
+        if let Some(brand_account) = &self.config.yt_brand_account {
+            context["user"]["onBehalfOfUser"] = json!(brand_account);
+        }
+        let mut body = body.clone();
+        match body.as_object_mut() {
+            Some(o) => o.insert("context".to_string(), context),
+            _ => unreachable!(),
+        };
+        body
+    }
+
+    /// Account index of the cookies, `--yt-auth-user` takes precedence over the saved headers
+    fn auth_user(config: &ConfigArgs, saved: Option<&str>) -> Option<String> {
+        let auth_user = config.yt_auth_user.map(|i| i.to_string());
+        if let (Some(auth_user), Some(saved)) = (&auth_user, saved)
+            && auth_user != saved
+        {
+            info!("using the account {} instead of the account {} of the saved headers", auth_user, saved);
+        }
+        auth_user.or_else(|| saved.map(str::to_string))
+    }
+
+    /// Headers of a request to the API, on top of the default headers of the client
+    fn request_headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        // For browser auth, generate a fresh authorization header with current timestamp
+        if let YtMusicAuthType::Browser { sapisid, origin, .. } = &self.auth_type {
+            headers.insert("authorization", Self::generate_sapisidhash(sapisid, origin).parse()?);
+        }
+        if let Some(auth_user) = &self.auth_user {
+            headers.insert("x-goog-authuser", auth_user.parse()?);
+        }
+        if let Some(visitor_id) = &self.visitor_id {
+            headers.insert("x-goog-visitor-id", visitor_id.parse()?);
+        }
+        Ok(headers)
+    }
+
+    /// Fetch the visitor id from the homepage again and save it to the headers file
+    async fn refresh_visitor_id(&mut self) -> Result<()> {
+        self.visitor_id_fetched = true;
+        let YtMusicAuthType::Browser { headers_path, origin, .. } = &self.auth_type else {
+            return Ok(());
+        };
+        let ytcfg = Self::fetch_ytcfg(&self.client, origin).await?;
+        let fetched = SavedYtCfg::new(&ytcfg, unix_timestamp())
+            .ok_or_else(|| eyre!("Failed to fetch X-Goog-Visitor-Id, cannot continue."))?;
+        if let Err(e) = fetched.save(headers_path) {
+            warn!("Failed to save the X-Goog-Visitor-Id: {}", e);
+        }
+        self.visitor_id = Some(fetched.visitor_data);
+        Ok(())
+    }
+
+    /// Whether a response tells the request was not logged in
+    fn not_logged_in(text: &str) -> bool {
+        // YouTube Music can return not-logged-in status in two formats:
+        // 1. Direct: "logged_in": "0" 
+        // 2. ServiceTrackingParams: {"key": "logged_in", "value": "0"}
+        // Check for direct format
+        text.contains(r#""logged_in":"0"#) || text.contains(r#""logged_in": "0"#)
+            // Check for key-value format in serviceTrackingParams
+            || ((text.contains(r#""key":"logged_in""#) || text.contains(r#""key": "logged_in""#)) 
+                && (text.contains(r#""value":"0""#) || text.contains(r#""value": "0""#)))
+    }
+
+    /// Check for authentication errors in the response
+    fn check_authentication_errors(&self, text: &str) -> Result<()> {
+        if Self::not_logged_in(text) {
+            if matches!(self.auth_type, YtMusicAuthType::Browser { .. }) {
+                return Err(eyre!(
+                    "Authentication failed: Not logged in (logged_in=0).\n\
+                    Your browser authentication tokens have expired or are invalid.\n\
+                    This usually means your cookies or SAPISID have expired.\n\n\
+                    Please refresh your headers by running:\n  \
+                    cargo run --example setup_ytmusic_browser\n\n\
+                    Make sure you:\n\
+                    1. Are logged into YouTube Music in your browser\n\
+                    2. Copy headers from an authenticated request (like /browse)\n\
+                    3. Include ALL cookies, especially __Secure-3PAPISID"
+                ));
             } else {
                 return Err(eyre!(
                     "Authentication failed: Not logged in (logged_in=0).\n\
@@ -691,71 +1191,132 @@ impl YtMusicApi {
         Ok(())
     }
 
+    /// Retry policy of the rate limits, `--yt-max-retries` and `--yt-max-backoff-secs` first
+    fn rate_limit_policy(&self) -> RetryPolicy {
+        self.config.retry_policy(
+            &Self::RATE_LIMIT_POLICY,
+            self.config.yt_max_retries,
+            self.config.yt_max_backoff_secs,
+        )
+    }
+
     /// Handle rate limiting detection and retry logic
-    async fn handle_rate_limit_with_retry(
+    fn handle_rate_limit_with_retry(
+        &self,
         status: reqwest::StatusCode,
         text: &str,
         retry_count: u32,
-    ) -> Result<RateLimitAction> {
+    ) -> RetryAction {
         // Detect rate limiting: HTTP 429 or Google's HTML "automated queries" response
         let is_rate_limited = status.as_u16() == 429 
             || (status.is_client_error() && text.contains("automated queries"));
         
         if !is_rate_limited {
-            return Ok(RateLimitAction::Continue);
+            return RetryAction::Continue;
         }
         
-        if retry_count >= Self::MAX_RETRIES {
-            return Ok(RateLimitAction::MaxRetriesExceeded);
+        let policy = self.rate_limit_policy();
+        if retry_count >= policy.max_retries {
+            return RetryAction::MaxRetriesExceeded;
         }
         
-        // Calculate exponential backoff: 60 * 2^retry_count seconds, capped at MAX_BACKOFF_SECS
-        // Sequence: 60s, 120s, 240s, 480s, 900s, 900s
-        let backoff_secs = (60u64 * 2u64.pow(retry_count)).min(Self::MAX_BACKOFF_SECS);
+        let backoff = policy.backoff(retry_count);
         warn!(
             "Rate limit hit (attempt {}/{}). Waiting {} seconds before retry...",
             retry_count + 1,
-            Self::MAX_RETRIES + 1,
-            backoff_secs
+            policy.max_retries + 1,
+            backoff.as_secs()
         );
         
-        Ok(RateLimitAction::Retry(Duration::from_secs(backoff_secs)))
+        RetryAction::Retry(backoff)
+    }
+
+    /// Server errors that usually go away when the request is sent again
+    fn is_transient_status(status: reqwest::StatusCode) -> bool {
+        matches!(status.as_u16(), 500 | 502 | 503 | 504)
+    }
+
+    /// Network errors that usually go away when the request is sent again: refused or reset
+    /// connections, timeouts, bodies cut short. Invalid requests are not retried.
+    fn is_transient_error(error: &color_eyre::Report) -> bool {
+        error
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+    }
+
+    /// Save HTTP error diagnostic data to the debug directory and return the file path
+    fn save_http_error_diagnostic(&self, status: reqwest::StatusCode, text: &str) -> Result<String> {
+        let path = save_error_diagnostic(&self.config, status.as_u16(), text)?;
+        Ok(path.display().to_string())
     }
 
-    /// Save HTTP error diagnostic data with auto-detected file type and return the file path
-    fn save_http_error_diagnostic(status: reqwest::StatusCode, text: &str) -> Result<String> {
-        // Ensure debug directory exists
-        let debug_dir = std::path::Path::new("debug");
-        if !debug_dir.exists() {
-            let _ = std::fs::create_dir("debug");
+    /// Parse a successful response body, rejecting HTML pages served with a 200 status
+    fn parse_body<T: DeserializeOwned>(text: &str) -> Result<T> {
+        let trimmed = text.trim_start().to_lowercase();
+        if trimmed.starts_with("<!doctype html") || trimmed.starts_with("<html") {
+            return Err(eyre!("received an HTML page instead of JSON"));
         }
-        
-        // Detect file type based on content
-        let extension = if text.trim_start().to_lowercase().starts_with("<html") {
-            "html"
-        } else if serde_json::from_str::<serde_json::Value>(text).is_ok() {
-            "json"
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Wait before sending a request again after a transient failure, or give up once
+    /// `TRANSIENT_POLICY` retries failed too
+    async fn wait_transient_retry(&mut self, failures: &[String], error: &str) -> Result<()> {
+        let retry_count = u32::try_from(failures.len() - 1)?;
+        if retry_count >= Self::TRANSIENT_POLICY.max_retries {
+            return Err(eyre!(
+                "{} after {} attempts\n\
+                Failed attempts: {}",
+                error,
+                failures.len(),
+                failures.join(", ")
+            ));
+        }
+        let backoff = Self::TRANSIENT_POLICY.backoff(retry_count);
+        warn!(
+            "{} (attempt {}/{}). Retrying in {} seconds...",
+            error,
+            failures.len(),
+            Self::TRANSIENT_POLICY.max_retries + 1,
+            backoff.as_secs()
+        );
+        tokio::time::sleep(backoff).await;
+        self.backoff_time += backoff;
+        Ok(())
+    }
+
+    async fn paginated_request(
+        &mut self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<YtMusicResponse> {
+        let response: YtMusicResponse = self.make_request(path, body, None).await?;
+        self.next_pages(response, path, body).await
+    }
+
+    /// Page of a playlist with all its songs. Private and deleted playlists have alerts instead
+    /// of contents
+    async fn playlist_page(&mut self, id: &str) -> Result<YtMusicResponse> {
+        let browse_id = if id.starts_with("VL") {
+            id.to_string()
         } else {
-            "txt"
+            format!("VL{}", id)
         };
-        
-        // Save diagnostic data with timestamp and detected extension
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let error_file = format!("debug/error_{}_{}.{}", status.as_u16(), timestamp, extension);
-        std::fs::write(&error_file, text)?;
-        
-        Ok(error_file)
+
+        let body = json!({ "browseId": browse_id });
+        match self.make_request("browse", &body, None).await? {
+            YtMusicPlaylistPage::Playlist(response) => self.next_pages(response, "browse", &body).await,
+            YtMusicPlaylistPage::Alerts(alerts) => Err(playlist_unavailable(id, &alerts).into()),
+        }
     }
 
-    async fn paginated_request(
+    /// Append the continuations of a first page already requested with `path` and `body`
+    async fn next_pages(
         &mut self,
+        mut response: YtMusicResponse,
         path: &str,
         body: &serde_json::Value,
     ) -> Result<YtMusicResponse> {
-        let mut response: YtMusicResponse = self.make_request(path, body, None).await?;
         let mut continuation = response.get_continuation();
 
         while let Some(cont) = continuation {
@@ -785,7 +1346,7 @@ impl YtMusicApi {
             self.update_refresh_token().await?;
         }
 
-        let body = Self::add_context(body);
+        let body = self.add_context(body);
         let endpoint = Self::build_endpoint(path, ctoken);
 
         /* Switch to info for dev env */
@@ -793,56 +1354,86 @@ impl YtMusicApi {
 
         // Retry loop with exponential backoff for rate limiting
         let mut retry_count = 0;
+        // Diagnostic files of responses that could not be parsed
+        let mut parse_failures: Vec<String> = vec![];
+        // Diagnostic files of server errors, or messages of network errors
+        let mut transient_failures: Vec<String> = vec![];
         loop {
-            // For browser auth, generate a fresh authorization header with current timestamp
-            let mut request = self.client.post(&endpoint).json(&body);
-            
-            if let YtMusicAuthType::Browser { sapisid, origin, .. } = &self.auth_type {
-                let auth_header = Self::generate_sapisidhash(sapisid, origin);
-                request = request.header("authorization", auth_header);
-            }
-            
-            let res = request.send().await?;
-            
+            let res = match self
+                .transport
+                .post(&self.client, &endpoint, &body, self.request_headers()?)
+                .await
+            {
+                Ok(res) => res,
+                Err(e) if Self::is_transient_error(&e) => {
+                    transient_failures.push(e.to_string());
+                    self.wait_transient_retry(&transient_failures, &e.to_string()).await?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
             // For browser auth, capture and update cookies from response headers
-            let response_headers = res.headers().clone();
-            
+            let response_headers = res.headers;
+
             // Extract status and text
-            let status = res.status();
-            let text = res.text().await?;
+            let status = res.status;
+            let text = res.text;
             
             // Debug mode: save ALL responses
             if self.config.debug {
-                std::fs::write(Self::RES_DEBUG_FILENAME, &text)?;
+                write_diagnostic(
+                    &self.config,
+                    &format!("{}_last_res.json", Self::RES_DEBUG_FILENAME),
+                    text.as_bytes(),
+                )?;
             }
             
-            // Check for authentication errors
-            self.check_authentication_errors(&text)?;
+            // Check for authentication errors, a saved visitor id may be outdated
+            if let Err(e) = self.check_authentication_errors(&text) {
+                if self.visitor_id.is_some() && !self.visitor_id_fetched {
+                    warn!("Authentication error with the saved X-Goog-Visitor-Id, fetching it again");
+                    self.refresh_visitor_id().await?;
+                    continue;
+                }
+                return Err(e);
+            }
             
             // Handle rate limiting with retry
-            match Self::handle_rate_limit_with_retry(status, &text, retry_count).await? {
-                RateLimitAction::Retry(backoff_duration) => {
+            match self.handle_rate_limit_with_retry(status, &text, retry_count) {
+                RetryAction::Retry(backoff_duration) => {
                     tokio::time::sleep(backoff_duration).await;
+                    self.backoff_time += backoff_duration;
                     retry_count += 1;
                     continue;
                 }
-                RateLimitAction::MaxRetriesExceeded => {
-                    let error_file = Self::save_http_error_diagnostic(status, &text)?;
+                RetryAction::MaxRetriesExceeded => {
+                    let error_file = self.save_http_error_diagnostic(status, &text)?;
                     return Err(eyre!(
                         "Rate limit exceeded after {} attempts. Please wait before retrying manually.\n\
                         Response saved to: {}",
-                        Self::MAX_RETRIES + 1,
+                        self.rate_limit_policy().max_retries + 1,
                         error_file
                     ));
                 }
-                RateLimitAction::Continue => {
+                RetryAction::Continue => {
                     // Not rate limited, continue with normal processing
                 }
             }
             
+            // Retry server errors, the diagnostic data of each attempt is kept
+            if Self::is_transient_status(status) {
+                let error_file = self.save_http_error_diagnostic(status, &text)?;
+                warn!("HTTP Error {} - Response saved to: {}", status, error_file);
+                transient_failures.push(error_file);
+                self.wait_transient_retry(&transient_failures, &format!("HTTP Error {status}"))
+                    .await?;
+                continue;
+            }
+
             // Check for HTTP errors and save diagnostic data
             if status.is_client_error() || status.is_server_error() {
-                let error_file = Self::save_http_error_diagnostic(status, &text)?;
+                let error_file = self.save_http_error_diagnostic(status, &text)?;
                 warn!("HTTP Error {} - Response saved to: {}", status, error_file);
                 return Err(eyre!(
                     "HTTP Error {}: {}\n\
@@ -854,9 +1445,33 @@ impl YtMusicApi {
                 ));
             }
             
-            // Parse the JSON response
-            let obj: T = serde_json::from_str(&text)?;
-            
+            // Parse the JSON response, a truncated body or an HTML interstitial served with
+            // a 200 status usually parses fine when requested again
+            let obj: T = match Self::parse_body(&text) {
+                Ok(obj) => obj,
+                Err(e) => {
+                    let error_file = self.save_http_error_diagnostic(status, &text)?;
+                    parse_failures.push(error_file);
+                    if parse_failures.len() > Self::MAX_PARSE_RETRIES {
+                        return Err(eyre!(
+                            "Failed to parse YtMusic response after {} attempts: {}\n\
+                            Responses saved to: {}",
+                            parse_failures.len(),
+                            e,
+                            parse_failures.join(", ")
+                        ));
+                    }
+                    warn!(
+                        "Invalid YtMusic response ({}), saved to: {}. Retrying request...",
+                        e,
+                        parse_failures.last().unwrap()
+                    );
+                    // the refetch counts as a request of its own
+                    self.pacing.wait().await;
+                    continue;
+                }
+            };
+
             // For browser auth, update cookies from response headers (after parsing JSON)
             if matches!(self.auth_type, YtMusicAuthType::Browser { .. }) {
                 if let Err(e) = self.update_browser_cookies(&response_headers).await {
@@ -875,6 +1490,122 @@ impl YtMusicApi {
         id.to_string()
     }
 
+    /// Playlists made by `YtMusic` itself: mixes, whose ids start with `RD`, the liked songs,
+    /// the episodes for later, and those owned by `YouTube Music`
+    pub fn is_generated_playlist(id: &str, owner: &str) -> bool {
+        id.starts_with("RD") || id == Self::LIKES_PLAYLIST_ID || id == "SE" || owner == "YouTube Music"
+    }
+
+    /// Owner of a playlist, read from the header of its page. The playlist is already created
+    /// when it's needed, so failures are only logged.
+    async fn get_playlist_owner(&mut self, id: &str) -> Option<String> {
+        let body = json!({ "browseId": format!("VL{}", id) });
+        let owner = match self.make_request::<YtMusicResponse>("browse", &body, None).await {
+            Ok(response) => response.get_playlist_owner(),
+            Err(e) => {
+                warn!("failed to retrieve the playlist {}: {}", id, e);
+                None
+            }
+        };
+        if owner.is_none() {
+            warn!("owner of the playlist {} not found in its header", id);
+        }
+        owner
+    }
+
+    /// Song of a video id, read from the watch queue of the video. `None` when the video
+    /// doesn't exist.
+    pub async fn get_song(&mut self, video_id: &str) -> Result<Option<Song>> {
+        self.pacing.wait().await;
+        let body = json!({ "videoId": video_id, "isAudioOnly": true });
+        let response: YtMusicNextResponse = self.make_request("next", &body, None).await?;
+        Ok(watch_song(video_id, &response)?.0)
+    }
+
+    /// Songs of `songs` whose video exists, for `--verify-ids`. A song whose video can't be
+    /// looked up isn't verified either
+    async fn verified_songs(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
+        let mut verified = vec![];
+        for song in songs {
+            match self.get_song(&song.id).await {
+                Ok(Some(_)) => verified.push(song.clone()),
+                Ok(None) => warn!("video {} of song {} does not exist, it is not added", song.id, song),
+                Err(e) => warn!("failed to look up video {} of song {}, it is not added: {}", song.id, song, e),
+            }
+        }
+        Ok(verified)
+    }
+
+    /// Add `songs` at the end of `playlist` by chunks of `--yt-add-chunk-size`, the duplicates
+    /// are skipped
+    async fn add_playlist_songs(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
+        for chunk in songs.chunks(self.config.yt_add_chunk_size.max(1)) {
+            self.add_playlist_chunk(playlist, chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Add `songs` with a single request. When some of them are already in the playlist `YtMusic`
+    /// answers with a "Duplicates" confirm dialog, they are then sent once more skipping the
+    /// duplicates.
+    async fn add_playlist_chunk(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
+        let mut response = self.edit_playlist_add(&playlist.id, songs, "DEDUPE_OPTION_CHECK").await?;
+        if !response.success() && response.duplicates_dialog() {
+            debug!(
+                "duplicates among the {} songs added to playlist \"{}\", sending them again without",
+                songs.len(),
+                playlist.name
+            );
+            response = self.edit_playlist_add(&playlist.id, songs, "DEDUPE_OPTION_SKIP").await?;
+        }
+        if !response.success() {
+            if !response.already_in_playlist() {
+                return Err(eyre!("Error adding song to playlist. Response: {:?}", response.status));
+            }
+            info!("Ignoring song already in playlist: {:?}", songs[0]);
+        }
+        playlist.songs.extend_from_slice(songs);
+        Ok(())
+    }
+
+    async fn edit_playlist_add(
+        &mut self,
+        playlist_id: &str,
+        songs: &[Song],
+        dedupe_option: &str,
+    ) -> Result<YtMusicPlaylistEditResponse> {
+        self.pacing.wait().await;
+        let actions: Vec<_> = songs
+            .iter()
+            .map(|song| {
+                json!({
+                    "action": "ACTION_ADD_VIDEO",
+                    "addedVideoId": song.id,
+                    "dedupeOption": dedupe_option,
+                })
+            })
+            .collect();
+        let body = json!({
+            "playlistId": playlist_id,
+            "actions": actions,
+        });
+        self.make_request("browse/edit_playlist", &body, None).await
+    }
+
+    /// Songs uploaded to the library by the user. They have no ISRC and their video ids only
+    /// exist for this account, other platforms can only find them by their metadata.
+    pub async fn get_uploaded_songs(&mut self) -> Result<Vec<Song>> {
+        let body = json!({ "browseId": Self::UPLOADS_PLAYLIST_ID });
+        let response = self.paginated_request("browse", &body).await?;
+        let songs: UploadedSongs = response.try_into()?;
+        info!("Fetched {} uploaded songs", songs.0.len());
+        Ok(songs.0)
+    }
+
+    fn privacy_status(public: bool) -> &'static str {
+        if public { "PUBLIC" } else { "PRIVATE" }
+    }
+
     async fn update_browser_cookies(&mut self, response_headers: &HeaderMap) -> Result<()> {
         if let YtMusicAuthType::Browser { headers_path, sapisid: _, origin } = &self.auth_type {
             // Check if response has any Set-Cookie headers
@@ -1068,6 +1799,21 @@ impl YtMusicApi {
         
         Ok(())
     }
+
+    /// Songs found by a free text search, in the order `YtMusic` ranks them
+    async fn search_query(&mut self, query: &str) -> Result<Vec<Song>> {
+        let ignore_spelling = "AUICCAFqDBAOEAoQAxAEEAkQBQ%3D%3D";
+        let params = format!("EgWKAQ{}{}", "II", ignore_spelling);
+        let body = json!({
+            "query": query,
+            "params": params,
+        });
+        let response = self
+            .make_request::<YtMusicResponse>("search", &body, None)
+            .await?;
+        let res_songs: SearchSongs = response.try_into()?;
+        Ok(res_songs.0)
+    }
 }
 
 #[async_trait]
@@ -1076,29 +1822,132 @@ impl MusicApi for YtMusicApi {
         MusicApiType::YtMusic
     }
 
-    fn country_code(&self) -> &'static str {
-        // TODO: it seems impossible to get the country code from YtMusic
-        "UNKNOWN"
+    fn backoff_time(&self) -> Duration {
+        self.backoff_time
     }
 
-    async fn create_playlist(&mut self, name: &str, public: bool) -> Result<Playlist> {
-        let privacy_status = if public { "PUBLIC" } else { "PRIVATE" };
-        let body = json!({
+    fn country_code(&self) -> &str {
+        &self.country_code
+    }
+
+    async fn create_playlist(&mut self, name: &str, description: &str, public: bool) -> Result<Playlist> {
+        self.create_playlist_with_songs(name, description, public, &[]).await
+    }
+
+    async fn create_playlist_with_songs(
+        &mut self,
+        name: &str,
+        description: &str,
+        public: bool,
+        songs: &[Song],
+    ) -> Result<Playlist> {
+        let songs = if self.config.verify_ids && !songs.is_empty() {
+            self.verified_songs(songs).await?
+        } else {
+            songs.to_vec()
+        };
+        let privacy_status = Self::privacy_status(public);
+        let mut body = json!({
             "title": name,
-            "description": PLAYLIST_DESC,
+            "description": description,
             "privacyStatus": privacy_status,
         });
+        if !songs.is_empty() {
+            body["videoIds"] = json!(songs.iter().map(|s| &s.id).collect::<Vec<_>>());
+            self.pacing.wait().await;
+        }
         let response: YtMusicPlaylistCreateResponse =
             self.make_request("playlist/create", &body, None).await?;
+        if !songs.is_empty() {
+            debug!(
+                "created playlist \"{}\" with {} songs in a single request, 1 edit_playlist request saved",
+                name,
+                songs.len()
+            );
+        }
         let id = Self::clean_playlist_id(&response.playlist_id);
+        let owner = self.get_playlist_owner(&id).await;
         Ok(Playlist {
             id,
             name: name.to_string(),
-            songs: vec![],
-            owner: Some("".to_string()) // TODO: get the owner
+            songs,
+            owner,
+            description: Some(description.to_string()),
+            public: Some(public),
+            generated: false,
+            cover_url: None,
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
         })
     }
 
+    fn supports_playlist_details(&self) -> bool {
+        // the library doesn't show the description nor the privacy of playlists
+        false
+    }
+
+    async fn update_playlist_details(
+        &mut self,
+        playlist: &mut Playlist,
+        description: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        let mut actions = vec![];
+        if let Some(description) = description {
+            actions.push(json!({
+                "action": "ACTION_SET_PLAYLIST_DESCRIPTION",
+                "playlistDescription": description,
+            }));
+        }
+        if let Some(public) = public {
+            actions.push(json!({
+                "action": "ACTION_SET_PLAYLIST_PRIVACY",
+                "playlistPrivacy": Self::privacy_status(public),
+            }));
+        }
+        if actions.is_empty() {
+            return Ok(());
+        }
+        let body = json!({
+            "playlistId": playlist.id,
+            "actions": actions,
+        });
+        self.pacing.wait().await;
+        let response = self
+            .make_request::<YtMusicPlaylistEditResponse>("browse/edit_playlist", &body, None)
+            .await?;
+        if !response.success() {
+            return Err(eyre!("Error updating the details of playlist \"{}\"", playlist.name));
+        }
+        if let Some(description) = description {
+            playlist.description = Some(description.to_string());
+        }
+        playlist.public = public.or(playlist.public);
+        Ok(())
+    }
+
+    async fn rename_playlist(&mut self, playlist: &mut Playlist, name: &str) -> Result<()> {
+        let body = json!({
+            "playlistId": playlist.id,
+            "actions": [{
+                "action": "ACTION_SET_PLAYLIST_NAME",
+                "playlistName": name,
+            }],
+        });
+        self.pacing.wait().await;
+        let response = self
+            .make_request::<YtMusicPlaylistEditResponse>("browse/edit_playlist", &body, None)
+            .await?;
+        if !response.success() {
+            return Err(eyre!("Error renaming playlist \"{}\"", playlist.name));
+        }
+        playlist.name = name.to_string();
+        Ok(())
+    }
+
     async fn get_playlists_info(&mut self) -> Result<Vec<Playlist>> {
         let browse_id = "FEmusic_liked_playlists";
         let body = json!({ "browseId": browse_id });
@@ -1126,118 +1975,56 @@ impl MusicApi for YtMusicApi {
         Ok(deduplicated)
     }
 
-    async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>> {
-        let browse_id = if id.starts_with("VL") {
-            id.to_string()
-        } else {
-            format!("VL{}", id)
-        };
+    fn uploads_playlist(&self) -> Option<Playlist> {
+        Some(Playlist {
+            id: Self::UPLOADS_PLAYLIST_ID.to_string(),
+            name: "Uploads".to_string(),
+            songs: vec![],
+            owner: None,
+            description: None,
+            public: Some(false),
+            generated: false,
+            cover_url: None,
+            unavailable: None,
+            owner_name: None,
+            collaborative: false,
+            snapshot_id: None,
+            folder: None,
+        })
+    }
 
-        let body = json!({ "browseId": browse_id });
-        
-        // Make request with error handling for empty/inaccessible playlists
-        let response = match self.paginated_request("browse", &body).await {
-            Ok(r) => r,
-            Err(e) => {
-                // Check if this is a parsing error due to missing contents
-                if e.to_string().contains("missing field `contents`") {
-                    warn!("Playlist {} appears to be empty, inaccessible, or deleted. Skipping.", id);
-                    return Ok(vec![]);
-                }
-                return Err(e);
-            }
-        };
-        
-        let songs: Songs = match response.try_into() {
-            Ok(s) => s,
-            Err(e) => {
-                // Handle parsing errors gracefully
-                if e.to_string().contains("missing field") || e.to_string().contains("contents") {
-                    warn!("Could not parse songs from playlist {}. It may be empty, private, or deleted. Error: {}", id, e);
-                    return Ok(vec![]);
-                }
-                return Err(e);
-            }
-        };
-        
+    async fn get_playlist_songs(&mut self, id: &str) -> Result<Vec<Song>> {
+        if id == Self::UPLOADS_PLAYLIST_ID {
+            return self.get_uploaded_songs().await;
+        }
+        // an empty playlist has no shelf, its songs are an empty list
+        let songs: Songs = self.playlist_page(id).await?.try_into()?;
         Ok(songs.0)
     }
 
-    async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
-        for song in songs {
-            playlist.songs.push(song.clone());
+    async fn fetch_playlist(&mut self, playlist: &mut Playlist) -> Result<()> {
+        if playlist.id == Self::UPLOADS_PLAYLIST_ID {
+            playlist.songs = self.get_uploaded_songs().await?;
+            return Ok(());
         }
+        // the library doesn't list the details of the playlists, their page does
+        let page = self.playlist_page(&playlist.id).await?;
+        playlist.description = page.get_playlist_description();
+        playlist.public = page.get_playlist_public();
+        let songs: Songs = page.try_into()?;
+        playlist.songs = songs.0;
+        Ok(())
+    }
 
-        let mut actions = vec![];
-        for song in songs {
-            let action = json!({
-                "action": "ACTION_ADD_VIDEO",
-                "addedVideoId": song.id,
-                "dedupeOption": "DEDUPE_OPTION_CHECK", // Allow youtube to check for duplicates
-            });
-            actions.push(action);
+    async fn add_songs_to_playlist(&mut self, playlist: &mut Playlist, songs: &[Song]) -> Result<()> {
+        if !self.config.verify_ids {
+            return self.add_playlist_songs(playlist, songs).await;
         }
-        let body = json!({
-            "playlistId": playlist.id,
-            "actions": actions,
-        });
-        let response: YtMusicPlaylistEditResponse = self
-            .make_request("browse/edit_playlist", &body, None)
-            .await?;
-        if !response.success() {
-            
-            if let Some(actions) = response.actions {
-                for action in actions {
-
-                    // Youtube Music sometimes returns a confirm dialog when adding duplicates
-                    // This is a workaround to handle that by splitting the list and retrying
-                    if let Some(confirm_dialog) = action.confirm_dialog_endpoint {
-                        let title_contains_duplicates = confirm_dialog
-                            .content
-                            .confirm_dialog_renderer
-                            .title
-                            .runs
-                            .iter()
-                            .any(|run| run.text == "Duplicates");
-    
-                        if title_contains_duplicates {
-                            // Handle duplicates by splitting the list and retrying
-                            if songs.len() > 1 {
-                                let mid = songs.len() / 2;
-                                self.add_songs_to_playlist(playlist, &songs[..mid]).await?;
-                                // Add a delay before the next recursive call
-                                tokio::time::sleep(Duration::from_secs(3)).await;
-                                self.add_songs_to_playlist(playlist, &songs[mid..]).await?;
-                            } else {
-                                info!("Ignoring song already in playlist: {:?}", songs[0]);
-                            }
-                            return Ok(());
-                        }
-                    }
-
-                    // Once we have split the list enough times to reach a single element
-                    // we know that we have reached the song that is already in the playlist
-                    // and we can ignore it
-                    if let Some(add_to_toast_action) = action.add_to_toast_action {
-                        let message_contains_already_in_playlist = add_to_toast_action
-                            .item
-                            .notification_action_renderer
-                            .response_text
-                            .runs
-                            .iter()
-                            .any(|run| run.text.contains("This track is already in the playlist"));
-
-                        if message_contains_already_in_playlist {
-                            info!("Ignoring song already in playlist: {:?}", songs[0]);
-                            return Ok(());
-                        }
-                    }
-                }
-            }
-
-            return Err(eyre!("Error adding song to playlist. Response: {:?}", response.status));
+        let songs = self.verified_songs(songs).await?;
+        if songs.is_empty() {
+            return Ok(());
         }
-        Ok(())
+        self.add_playlist_songs(playlist, &songs).await
     }
 
     async fn remove_songs_from_playlist(
@@ -1271,6 +2058,37 @@ impl MusicApi for YtMusicApi {
         }
     }
 
+    fn supports_reorder(&self) -> bool {
+        true
+    }
+
+    async fn reorder_playlist(&mut self, playlist: &mut Playlist, order: &[Song]) -> Result<()> {
+        self.ensure_set_video_ids(playlist).await?;
+        let mut taken = vec![false; playlist.songs.len()];
+        let mut positions = vec![];
+        for song in order {
+            if let Some(i) = Self::find_song(&playlist.songs, song, &taken) {
+                taken[i] = true;
+                positions.push(i);
+            }
+        }
+        positions.extend((0..taken.len()).filter(|&i| !taken[i]));
+
+        let sids = playlist
+            .songs
+            .iter()
+            .map(|s| s.sid.clone().ok_or(eyre!("Song setVideoId not found")))
+            .collect::<Result<Vec<_>>>()?;
+        let target: Vec<String> = positions.iter().map(|&i| sids[i].clone()).collect();
+        let actions = Self::move_actions(&sids, &target);
+        if actions.is_empty() {
+            return Ok(());
+        }
+        self.edit_playlist_order(&playlist.id, actions).await?;
+        playlist.songs = positions.iter().map(|&i| playlist.songs[i].clone()).collect();
+        Ok(())
+    }
+
     async fn delete_playlist(&mut self, playlist: Playlist) -> Result<()> {
         let body = json!({
             "playlistId": playlist.id,
@@ -1281,6 +2099,7 @@ impl MusicApi for YtMusicApi {
     }
 
     async fn search_song(&mut self, song: &Song) -> Result<Option<Song>> {
+        self.pacing.wait().await;
         debug!(
             "Searching for song: {} by {}",
             song.name,
@@ -1300,47 +2119,876 @@ impl MusicApi for YtMusicApi {
                 return Ok(Some(res_song));
             }
         } else {
-            let ignore_spelling = "AUICCAFqDBAOEAoQAxAEEAkQBQ%3D%3D";
-            let params = format!("EgWKAQ{}{}", "II", ignore_spelling);
             let mut queries = song.build_queries();
             while let Some(query) = queries.pop() {
-                let body = json!({
-                    "query": query,
-                    "params": params,
-                });
-                let response = self
-                    .make_request::<YtMusicResponse>("search", &body, None)
-                    .await?;
-                let res_songs: SearchSongs = response.try_into()?;
-                // iterate over top 3 results
-                for res_song in res_songs.0.into_iter().take(3) {
-                    if song.compare(&res_song) {
-                        return Ok(Some(res_song));
-                    }
+                let res_songs = self.search_query(&query).await?;
+                if let Some(res_song) = song.best_match(res_songs, &self.config.match_options_over(&self.default_match_options())) {
+                    return Ok(Some(res_song));
                 }
             }
         }
         Ok(None)
     }
 
-    async fn add_likes(&mut self, songs: &[Song]) -> Result<()> {
-        // TODO: find a way to bulk-like
+    async fn search_candidates(&mut self, song: &Song, limit: usize) -> Result<Vec<Song>> {
+        let mut candidates = vec![];
+        let mut queries = song.build_queries();
+        while candidates.len() < limit
+            && let Some(query) = queries.pop()
+        {
+            self.pacing.wait().await;
+            let res_songs = self.search_query(&query).await?;
+            push_candidates(&mut candidates, res_songs, limit);
+        }
+        Ok(candidates)
+    }
+
+    fn supports_song_lookup(&self) -> bool {
+        true
+    }
+
+    async fn lookup_songs(&mut self, songs: &[Song]) -> Result<Vec<Option<Song>>> {
+        let mut res_songs = vec![];
         for song in songs {
+            res_songs.push(self.get_song(&song.id).await?);
+        }
+        Ok(res_songs)
+    }
+
+    async fn add_likes(&mut self, songs: &[Song]) -> Result<Vec<Song>> {
+        // TODO: find a way to bulk-like
+        self.rate_songs("like/like", songs, "likes").await?;
+        Ok(vec![])
+    }
+
+    async fn get_likes(&mut self) -> Result<Vec<Song>> {
+        let songs = self.get_playlist_songs(Self::LIKES_PLAYLIST_ID).await?;
+        Ok(songs)
+    }
+
+    fn supports_like_removal(&self) -> bool {
+        true
+    }
+
+    async fn remove_likes(&mut self, songs: &[Song]) -> Result<()> {
+        self.rate_songs("like/removelike", songs, "like removals").await
+    }
+
+    fn supports_followed_artists(&self) -> bool {
+        true
+    }
+
+    async fn get_followed_artists(&mut self) -> Result<Vec<Artist>> {
+        let body = json!({ "browseId": Self::SUBSCRIPTIONS_BROWSE_ID });
+        let response = self.paginated_request("browse", &body).await?;
+        let artists: SubscribedArtists = response.try_into()?;
+        Ok(artists.0)
+    }
+
+    async fn follow_artists(&mut self, artists: &[Artist]) -> Result<()> {
+        let ids: Vec<&str> = artists.iter().filter_map(|a| a.id.as_deref()).collect();
+        for ids_chunk in ids.chunks(50) {
+            self.pacing.wait().await;
             let body = json!({
-                "target": {
-                    "videoId": song.id,
-                }
+                "channelIds": ids_chunk,
             });
-            let _: YtMusicAddLikeResponse = self.make_request("like/like", &body, None).await?;
+            self.make_request::<YtMusicAddLikeResponse>("subscription/subscribe", &body, None)
+                .await?;
         }
         Ok(())
     }
 
-    async fn get_likes(&mut self) -> Result<Vec<Song>> {
-        let songs = self.get_playlist_songs("LM").await?;
-        Ok(songs)
+    async fn search_artist(&mut self, artist: &Artist) -> Result<Option<Artist>> {
+        self.pacing.wait().await;
+        let body = json!({
+            "query": artist.name,
+            "params": "EgWKAQIgAWoMEA4QChADEAQQCRAF",
+        });
+        let response = self
+            .make_request::<YtMusicResponse>("search", &body, None)
+            .await?;
+        let res_artists: SearchArtists = response.try_into()?;
+        let mut found: Vec<Artist> = vec![];
+        for res_artist in res_artists.0 {
+            if res_artist.is_same(artist) && !found.iter().any(|a| a.id == res_artist.id) {
+                found.push(res_artist);
+            }
+        }
+        // nothing tells homonyms apart, subscribing to the wrong one is worse than none
+        if found.len() > 1 {
+            let ids: Vec<&str> = found.iter().filter_map(|a| a.id.as_deref()).collect();
+            warn!(
+                "several artists named \"{}\", follow the right one manually: {}",
+                artist.name,
+                ids.join(", ")
+            );
+            return Ok(None);
+        }
+        Ok(found.pop())
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::sync::Arc;
+
+    use clap::Parser;
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::mock_api::{playlist, song, test_dir};
+    use crate::music_api::Unavailability;
+    use crate::yt_music::transport::mock::ScriptedTransport;
+
+    const VALID: &str = r#"{"playlistId": "PL123"}"#;
+    const SUCCEEDED: &str = r#"{"status": "STATUS_SUCCEEDED"}"#;
+
+    fn api(responses: Vec<(u16, &str)>) -> (YtMusicApi, Arc<ScriptedTransport>) {
+        let transport = Arc::new(ScriptedTransport::new(responses));
+        // the bodies that fail to parse are saved in the debug directory of the config directory
+        let config_dir = test_dir("yt_music");
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--config-dir", config_dir.to_str().unwrap()]);
+        (YtMusicApi::with_transport(transport.clone(), config), transport)
+    }
+
+    #[test]
+    fn test_generated_playlists() {
+        assert!(YtMusicApi::is_generated_playlist("RDTMAK5uy_kset8DisdE7LSD4TNjEVvrKRTmG7a56sY", "YouTube Music"));
+        assert!(YtMusicApi::is_generated_playlist("LM", "Jane"));
+        assert!(YtMusicApi::is_generated_playlist("PLabc", "YouTube Music"));
+        assert!(!YtMusicApi::is_generated_playlist("PLabc", "Jane"));
+    }
+
+    #[test]
+    fn test_read_browser_headers() {
+        // a headers file is enough, no OAuth argument is needed
+        let path = test_dir("browser_headers").join("browser.json");
+        let file = json!({
+            "cookie": "SID=abc; __Secure-3PAPISID=xyz/123",
+            "x-goog-authuser": "2",
+            "authorization": "SAPISIDHASH stale",
+            "x-goog-visitor-id": "stale",
+            "user-agent": "Mozilla/5.0",
+        });
+        std::fs::write(&path, file.to_string()).unwrap();
+        let config = ConfigArgs::parse_from(["sync_dis_boi"]);
+        let browser = YtMusicApi::read_browser_headers(&path, &config).unwrap();
+        assert_eq!(browser.sapisid, "xyz/123");
+        assert_eq!(browser.origin, "https://music.youtube.com");
+        assert_eq!(browser.auth_user.as_deref(), Some("2"));
+        assert_eq!(browser.headers["cookie"], "SID=abc; __Secure-3PAPISID=xyz/123; SOCS=CAI");
+        assert!(!browser.headers.contains_key("authorization"));
+        assert!(!browser.headers.contains_key("x-goog-visitor-id"));
+
+        std::fs::write(&path, json!({"user-agent": "Mozilla/5.0"}).to_string()).unwrap();
+        assert!(YtMusicApi::read_browser_headers(&path, &config).is_err());
+    }
+
+    #[test]
+    fn test_saved_ytcfg() {
+        let ytcfg = YtCfg { visitor_data: Some("CgtYZk".to_string()), gl: Some("FR".to_string()) };
+        let saved = SavedYtCfg::new(&ytcfg, 1_000_000).unwrap();
+        let week = Duration::from_secs(7 * 24 * 3600);
+        assert!(saved.is_fresh(week, 1_000_000 + 3600));
+        assert!(!saved.is_fresh(week, 1_000_000 + week.as_secs()));
+        assert!(!saved.is_fresh(Duration::ZERO, 1_000_000));
+        assert_eq!(saved.ytcfg(), ytcfg);
+        assert!(SavedYtCfg::new(&YtCfg::default(), 0).is_none());
+
+        // saved along the headers, which are kept and don't include it
+        let path = test_dir("saved_ytcfg").join("browser.json");
+        let file = json!({"cookie": "__Secure-3PAPISID=xyz", "user-agent": "Mozilla/5.0"});
+        std::fs::write(&path, file.to_string()).unwrap();
+        let config = ConfigArgs::parse_from(["sync_dis_boi"]);
+        assert!(YtMusicApi::read_browser_headers(&path, &config).unwrap().saved_ytcfg.is_none());
+        saved.save(&path).unwrap();
+        let browser = YtMusicApi::read_browser_headers(&path, &config).unwrap();
+        assert_eq!(browser.saved_ytcfg, Some(saved));
+        assert_eq!(browser.headers.len(), 2);
+        assert_eq!(browser.sapisid, "xyz");
+    }
+
+    #[tokio::test]
+    async fn test_probe_logged_in() {
+        let stale = r#"{"responseContext": {"serviceTrackingParams": [{"params": [{"key": "logged_in", "value": "0"}]}]}}"#;
+        let (mut yt_music, transport) = api(vec![(200, stale), (200, r#"{"contents": {}}"#)]);
+        assert!(!yt_music.probe_logged_in().await.unwrap());
+        assert!(yt_music.probe_logged_in().await.unwrap());
+        assert!(transport.requests.lock().unwrap()[0].0.contains("browse"));
+    }
+
+    #[tokio::test]
+    async fn test_headers_without_oauth() {
+        let stale = r#"{"responseContext": {"serviceTrackingParams": [{"params": [{"key": "logged_in", "value": "0"}]}]}}"#;
+        let token_path = test_dir("unused_oauth").join("ytmusic_oauth.json");
+        let config = || ConfigArgs::parse_from(["sync_dis_boi"]);
+
+        // the headers are kept while they are logged in, no OAuth credentials needed
+        let (logged_in, transport) = api(vec![(200, r#"{"contents": {}}"#)]);
+        let logged_in = logged_in.or_oauth(None, None, token_path.clone(), false, config()).await;
+        assert!(logged_in.is_ok());
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+
+        // without OAuth credentials to fall back to, the stale headers are an error
+        let (stale, _) = api(vec![(200, stale)]);
+        let Err(e) = stale.or_oauth(None, None, token_path, false, config()).await else {
+            panic!("stale headers without OAuth credentials must fail");
+        };
+        assert!(e.to_string().contains("no longer logged in"), "{e}");
+        assert!(e.to_string().contains("--client-id"), "{e}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refetch_after_truncated_json() {
+        let (mut api, transport) = api(vec![(200, r#"{"playlistId": "PL1"#), (200, VALID)]);
+        // pause before every request, the refetch is paced like the other requests
+        api.pacing = RateLimiter::new(1, Duration::from_secs(10), Duration::ZERO);
+        let start = tokio::time::Instant::now();
+        let res: YtMusicPlaylistCreateResponse =
+            api.make_request("playlist/create", &json!({}), None).await.unwrap();
+        assert_eq!(res.playlist_id, "PL123");
+        assert_eq!(transport.request_count(), 2);
+        assert!(start.elapsed() >= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_setup_browser_from_har() {
+        let entry = |time: &str, url: &str, authuser: &str| {
+            format!(
+                r#"{{"startedDateTime": "{}", "request": {{"method": "POST", "url": "{}", "headers": [
+                    {{"name": ":authority", "value": "music.youtube.com"}},
+                    {{"name": "cookie", "value": "SID=abc; __Secure-3PAPISID=xyz"}},
+                    {{"name": "x-goog-authuser", "value": "{}"}},
+                    {{"name": "x-origin", "value": "https://music.youtube.com"}}
+                ]}}}}"#,
+                time, url, authuser
+            )
+        };
+        let browse = "https://music.youtube.com/youtubei/v1/browse?prettyPrint=false";
+        let har = format!(
+            r#"{{"log": {{"version": "1.2", "entries": [{}, {}, {}, {}]}}}}"#,
+            entry("2025-01-10T10:00:05.000Z", browse, "1"),
+            entry("2025-01-10T10:00:01.000Z", browse, "0"),
+            // later as a string, but earlier once its offset is applied
+            entry("2025-01-10T11:00:07.000+02:00", browse, "3"),
+            entry("2025-01-10T10:00:09.000Z", "https://music.youtube.com/verify_session", "2"),
+        );
+        let headers = YtMusicApi::har_headers(&har).unwrap();
+        // the newest session wins, requests outside of the API are ignored
+        assert!(headers.contains(&"x-goog-authuser: 1".to_string()));
+        let json: serde_json::Value =
+            serde_json::from_str(&YtMusicApi::parse_and_save_headers(headers, None).unwrap()).unwrap();
+        assert_eq!(json["cookie"], "SID=abc; __Secure-3PAPISID=xyz");
+        assert_eq!(json["x-origin"], "https://music.youtube.com");
+        assert!(json.get(":authority").is_none());
+
+        let no_api = format!(r#"{{"log": {{"entries": [{}]}}}}"#, entry("2025-01-10T10:00:09.000Z", "https://www.youtube.com/", "0"));
+        let err = YtMusicApi::har_headers(&no_api).unwrap_err().to_string();
+        assert!(err.contains("No request to music.youtube.com/youtubei/v1/"));
+        let sanitized = har.replace(r#""name": "cookie""#, r#""name": "referer""#);
+        assert!(YtMusicApi::har_headers(&sanitized).unwrap_err().to_string().contains("no cookie"));
+    }
+
+    #[test]
+    fn test_parse_ytcfg() {
+        // trimmed from the homepage of a signed in account
+        let html = r#"<!DOCTYPE html><html><head><script nonce="abc">ytcfg.set({"CLIENT_CANARY_STATE":"none","DEVICE":"cbr\u003dChrome","GL":"US"});</script>
+<script nonce="abc">ytcfg.set({"HL":"en","INNERTUBE_CONTEXT_GL":"FR","INNERTUBE_CONTEXT_HL":"en","VISITOR_DATA":"CgtYZkhOdFd0R2d5cyiAxv2qBg%3D%3D","INNERTUBE_CONTEXT":{"client":{"gl":"FR"}}});</script>
+</head><body></body></html>"#;
+        let ytcfg = YtCfg::parse(html).unwrap();
+        assert_eq!(
+            ytcfg.visitor_data.as_deref(),
+            Some("CgtYZkhOdFd0R2d5cyiAxv2qBg%3D%3D")
+        );
+        // the country of the client is preferred to the one of the page
+        assert_eq!(ytcfg.country_code(), "FR");
+
+        let ytcfg = YtCfg::parse(r#"<script>ytcfg.set({"GL":"de"});</script>"#).unwrap();
+        assert_eq!(
+            (ytcfg.visitor_data.as_deref(), ytcfg.country_code().as_str()),
+            (None, "DE")
+        );
+        assert_eq!(YtCfg::default().country_code(), UNKNOWN_COUNTRY);
+        assert!(YtCfg::parse("<html>consent page</html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_playlist_details() {
+        // header of a playlist of the user, trimmed from a browse response
+        let own = r#"{"contents": {"twoColumnBrowseResultsRenderer": {
+            "tabs": [{"tabRenderer": {"content": {"sectionListRenderer": {"contents": [
+                {"musicEditablePlaylistDetailHeaderRenderer": {
+                    "header": {"musicResponsiveHeaderRenderer": {
+                        "title": {"runs": [{"text": "Road trip"}]},
+                        "straplineTextOne": {"runs": [{"text": "Jane"}]},
+                        "description": {"musicDescriptionShelfRenderer": {
+                            "description": {"runs": [{"text": "Songs for "}, {"text": "the road"}]}
+                        }}
+                    }},
+                    "editHeader": {"musicPlaylistEditHeaderRenderer": {"privacy": "UNLISTED"}}
+                }}
+            ]}}}}],
+            "secondaryContents": {}
+        }}}"#;
+        // the playlists of other users have no editable header nor known visibility
+        let other = r#"{"contents": {"twoColumnBrowseResultsRenderer": {
+            "tabs": [{"tabRenderer": {"content": {"sectionListRenderer": {"contents": [
+                {"musicResponsiveHeaderRenderer": {"title": {"runs": [{"text": "Hits"}]}}}
+            ]}}}}],
+            "secondaryContents": {}
+        }}}"#;
+        let (mut yt_music, transport) = api(vec![(200, own), (200, other)]);
+        let mut playlists = vec![playlist("PL1", "Road trip", vec![]), playlist("PL2", "Hits", vec![])];
+        yt_music.fetch_playlists_songs(&mut playlists).await.unwrap();
+        assert_eq!(transport.requests.lock().unwrap()[0].1["browseId"], "VLPL1");
+
+        assert_eq!(playlists[0].description.as_deref(), Some("Songs for the road"));
+        assert_eq!(playlists[0].public, Some(false));
+        assert_eq!((playlists[1].description.as_deref(), playlists[1].public), (None, None));
+        assert!(playlists.iter().all(|p| p.songs.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_created_playlist_owner() {
+        // header of the playlist page, trimmed from a browse response
+        let header = r#"{"contents": {"twoColumnBrowseResultsRenderer": {
+            "tabs": [{"tabRenderer": {"content": {"sectionListRenderer": {"contents": [
+                {"musicResponsiveHeaderRenderer": {
+                    "title": {"runs": [{"text": "Road trip"}]},
+                    "straplineTextOne": {"runs": [{"text": "Jane",
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": "UCjane"}}}]}
+                }}
+            ]}}}}],
+            "secondaryContents": {}
+        }}}"#;
+        let (mut yt_music, transport) = api(vec![(200, VALID), (200, header)]);
+        let playlist = yt_music.create_playlist("Road trip", "", false).await.unwrap();
+        assert_eq!(playlist.owner.as_deref(), Some("Jane"));
+        assert_eq!(transport.requests.lock().unwrap()[1].1["browseId"], "VLPL123");
+
+        // the playlist is created even when its owner can't be retrieved
+        let (mut no_header, _) = api(vec![(200, VALID), (200, r#"{"contents": {}}"#)]);
+        let playlist = no_header.create_playlist("Road trip", "", false).await.unwrap();
+        assert_eq!((playlist.id.as_str(), playlist.owner), ("PL123", None));
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_with_songs() {
+        let (mut yt_music, transport) = api(vec![(200, VALID), (200, r#"{"contents": {}}"#)]);
+        let songs: Vec<Song> = ["a", "b"].iter().map(|id| song(MusicApiType::YtMusic, id, id)).collect();
+        let playlist = yt_music
+            .create_playlist_with_songs("Road trip", "", false, &songs)
+            .await
+            .unwrap();
+        assert_eq!(playlist.songs.len(), 2);
+
+        // the songs are sent with the creation, without an edit_playlist request
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].0.contains("playlist/create"));
+        assert_eq!(requests[0].1["videoIds"], json!(["a", "b"]));
+        assert!(!requests.iter().any(|(url, _)| url.contains("edit_playlist")));
+    }
+
+    #[tokio::test]
+    async fn test_add_songs_duplicates_dialog() {
+        let duplicates = r#"{"status": "STATUS_FAILED", "actions": [{"confirmDialogEndpoint": {"content": {"confirmDialogRenderer": {"title": {"runs": [{"text": "Duplicates"}]}, "dialogMessages": []}}}}]}"#;
+        let add_api = |responses| {
+            let transport = Arc::new(ScriptedTransport::new(responses));
+            let config = ConfigArgs::parse_from(["sync_dis_boi", "--yt-add-chunk-size", "2"]);
+            (YtMusicApi::with_transport(transport.clone(), config), transport)
+        };
+        let songs: Vec<Song> = ["a", "b", "c"].iter().map(|id| song(MusicApiType::YtMusic, id, id)).collect();
+
+        // songs are sent by chunks, a chunk with duplicates is sent again once skipping them
+        let (mut yt_music, transport) = add_api(vec![(200, duplicates), (200, SUCCEEDED), (200, SUCCEEDED)]);
+        let mut dst = playlist("PL1", "Mix", vec![]);
+        yt_music.add_songs_to_playlist(&mut dst, &songs).await.unwrap();
+        assert_eq!(dst.songs.len(), 3);
+        {
+            let requests = transport.requests.lock().unwrap();
+            let options: Vec<_> = requests
+                .iter()
+                .map(|(_, body)| (body["actions"].as_array().unwrap().len(), body["actions"][0]["dedupeOption"].clone()))
+                .collect();
+            assert_eq!(
+                options,
+                vec![
+                    (2, json!("DEDUPE_OPTION_CHECK")),
+                    (2, json!("DEDUPE_OPTION_SKIP")),
+                    (1, json!("DEDUPE_OPTION_CHECK")),
+                ]
+            );
+        }
+
+        // a single retry per chunk
+        let (mut yt_music, transport) = add_api(vec![(200, duplicates), (200, duplicates)]);
+        let mut dst = playlist("PL1", "Mix", vec![]);
+        assert!(yt_music.add_songs_to_playlist(&mut dst, &songs).await.is_err());
+        assert_eq!(transport.request_count(), 2);
+
+        // the toast of a song already in the playlist is ignored
+        let toast = r#"{"status": "STATUS_FAILED", "actions": [{"addToToastAction": {"item": {"notificationActionRenderer": {"responseText": {"runs": [{"text": "This track is already in the playlist"}]}}}}}]}"#;
+        let (mut yt_music, _) = add_api(vec![(200, toast)]);
+        let mut dst = playlist("PL1", "Mix", vec![]);
+        assert!(yt_music.add_songs_to_playlist(&mut dst, &songs[..1]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_search_and_follow_artists() {
+        let results = |ids: &[&str]| {
+            let items: Vec<_> = ids
+                .iter()
+                .map(|id| {
+                    json!({"musicResponsiveListItemRenderer": {
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": id}},
+                        "flexColumns": [{"musicResponsiveListItemFlexColumnRenderer":
+                            {"text": {"runs": [{"text": if id.ends_with('2') { "Other" } else { "Nirvana" }}]}}}]
+                    }})
+                })
+                .collect();
+            json!({"contents": {"tabbedSearchResultsRenderer": {"tabs": [{"tabRenderer": {"content":
+                {"sectionListRenderer": {"contents": [{"musicShelfRenderer": {"contents": items}}]}}}}]}}})
+            .to_string()
+        };
+        let single = results(&["UCnirvana1", "UCnirvana2"]);
+        let homonyms = results(&["UCnirvana1", "UCnirvana3"]);
+        let (mut yt_music, transport) =
+            api(vec![(200, &single), (200, &homonyms), (200, r#"{"responseContext": {}}"#)]);
+        let nirvana = Artist { id: None, name: "Nirvana".to_string() };
+
+        let found = yt_music.search_artist(&nirvana).await.unwrap().unwrap();
+        assert_eq!(found.id.as_deref(), Some("UCnirvana1"));
+        // artists with the same name are left for a manual review
+        assert!(yt_music.search_artist(&nirvana).await.unwrap().is_none());
+
+        yt_music.follow_artists(&[found]).await.unwrap();
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[2].0.contains("subscription/subscribe"));
+        assert_eq!(requests[2].1["channelIds"], json!(["UCnirvana1"]));
+    }
+
+    fn songs_with_sids(ids: &[&str]) -> Vec<Song> {
+        ids.iter()
+            .map(|id| {
+                let mut song = song(MusicApiType::YtMusic, id, id);
+                song.sid = Some(format!("S{}", id));
+                song
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_move_actions() {
+        let sids = |ids: &[&str]| ids.iter().map(|id| (*id).to_string()).collect::<Vec<_>>();
+        let current = sids(&["A", "B", "C", "D"]);
+        assert!(YtMusicApi::move_actions(&current, &current).is_empty());
+        assert_eq!(
+            YtMusicApi::move_actions(&current, &sids(&["D", "A", "B", "C"])),
+            vec![json!({"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "D", "movedSetVideoIdSuccessor": "A"})]
+        );
+        assert_eq!(
+            YtMusicApi::move_actions(&current, &sids(&["B", "A", "D", "C"])),
+            vec![
+                json!({"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "B", "movedSetVideoIdSuccessor": "A"}),
+                json!({"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "D", "movedSetVideoIdSuccessor": "C"}),
+            ]
+        );
+        assert_eq!(
+            YtMusicApi::move_action("A", None),
+            json!({"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "A"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_move_song_before() {
+        let (mut yt_music, transport) = api(vec![(200, SUCCEEDED); 3]);
+        let songs = songs_with_sids(&["a", "b", "c"]);
+        let mut pl = playlist("PL1", "Mix", songs.clone());
+
+        yt_music.move_song_before(&mut pl, &songs[2], Some(&songs[0])).await.unwrap();
+        yt_music.move_song_before(&mut pl, &songs[0], None).await.unwrap();
+        // the song is found by its video id when its setVideoId isn't known
+        let mut moved = songs[2].clone();
+        moved.sid = None;
+        yt_music.move_song_before(&mut pl, &moved, Some(&songs[0])).await.unwrap();
+        // already in place, nothing is sent
+        yt_music.move_song_before(&mut pl, &songs[1], Some(&songs[2])).await.unwrap();
+        let ids: Vec<_> = pl.songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c", "a"]);
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].1["playlistId"], "PL1");
+        assert_eq!(
+            requests[0].1["actions"],
+            json!([{"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "Sc", "movedSetVideoIdSuccessor": "Sa"}])
+        );
+        assert_eq!(
+            requests[1].1["actions"],
+            json!([{"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "Sa"}])
+        );
+        assert_eq!(
+            requests[2].1["actions"],
+            json!([{"action": "ACTION_MOVE_VIDEO_BEFORE", "setVideoId": "Sc", "movedSetVideoIdSuccessor": "Sa"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reorder_playlist() {
+        let (mut yt_music, transport) = api(vec![(200, SUCCEEDED)]);
+        let songs = songs_with_sids(&["a", "b", "c", "d"]);
+        let mut pl = playlist("PL1", "Mix", songs.clone());
+
+        // "b" isn't in the order, it stays after the ordered songs
+        let order = vec![songs[3].clone(), songs[2].clone(), songs[0].clone()];
+        yt_music.reorder_playlist(&mut pl, &order).await.unwrap();
+        let ids: Vec<_> = pl.songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["d", "c", "a", "b"]);
+        assert_eq!(transport.request_count(), 1);
+
+        // already ordered, nothing is sent
+        yt_music.reorder_playlist(&mut pl, &order).await.unwrap();
+        assert_eq!(transport.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_likes_failures() {
+        let liked = r#"{"responseContext": {}}"#;
+        let rejected = r#"{"error": {"code": 400, "status": "INVALID_ARGUMENT"}}"#;
+        let songs: Vec<_> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|id| song(MusicApiType::YtMusic, id, id))
+            .collect();
+        let likes_api = |responses, tolerance: &str| {
+            let transport = Arc::new(ScriptedTransport::new(responses));
+            let config = ConfigArgs::parse_from([
+                "sync_dis_boi",
+                "--yt-like-delay-ms",
+                "0",
+                "--yt-like-failure-tolerance",
+                tolerance,
+            ]);
+            (YtMusicApi::with_transport(transport.clone(), config), transport)
+        };
+
+        // a failure doesn't stop the following likes, the failed ids are listed
+        let responses = vec![(200, liked), (400, rejected), (200, liked), (400, rejected)];
+        let (mut yt_music, transport) = likes_api(responses.clone(), "0");
+        let err = yt_music.add_likes(&songs).await.unwrap_err().to_string();
+        assert_eq!(transport.request_count(), 4);
+        assert_eq!(err, "2 of the 4 likes failed, video ids: b, d");
+
+        // tolerated failures are only a warning
+        let (mut yt_music, _) = likes_api(responses.clone(), "0.5");
+        assert!(yt_music.add_likes(&songs).await.is_ok());
+        let (mut yt_music, _) = likes_api(responses, "0.25");
+        assert!(yt_music.add_likes(&songs).await.is_err());
+
+        let (mut yt_music, _) = likes_api(vec![(200, liked); 4], "0");
+        assert!(yt_music.add_likes(&songs).await.is_ok());
+
+        // like removals are sent the same way
+        let (mut yt_music, transport) = likes_api(vec![(200, liked), (400, rejected)], "0");
+        let err = yt_music.remove_likes(&songs[..2]).await.unwrap_err().to_string();
+        assert_eq!(err, "1 of the 2 like removals failed, video ids: b");
+        assert!(transport.requests.lock().unwrap()[0].0.contains("like/removelike"));
+    }
+
+    #[tokio::test]
+    async fn test_brand_account_requests() {
+        let transport = Arc::new(ScriptedTransport::new(vec![(200, VALID)]));
+        let args = ["sync_dis_boi", "--yt-auth-user", "1", "--yt-brand-account", "112233"];
+        let config = ConfigArgs::parse_from(args);
+        let mut yt_music = YtMusicApi::with_transport(transport.clone(), config.clone());
+        let _: YtMusicPlaylistCreateResponse =
+            yt_music.make_request("playlist/create", &json!({"title": "Mix"}), None).await.unwrap();
+        {
+            let body = &transport.requests.lock().unwrap()[0].1;
+            assert_eq!(body["title"], "Mix");
+            assert_eq!(body["context"]["user"], json!({"onBehalfOfUser": "112233"}));
+            let headers = &transport.request_headers.lock().unwrap()[0];
+            assert_eq!(headers["x-goog-authuser"], "1");
+            assert!(headers["authorization"].to_str().unwrap().starts_with("SAPISIDHASH "));
+        }
+
+        // the main account by default
+        let (mut yt_music, transport) = api(vec![(200, VALID)]);
+        let _: YtMusicPlaylistCreateResponse =
+            yt_music.make_request("playlist/create", &json!({}), None).await.unwrap();
+        assert_eq!(transport.requests.lock().unwrap()[0].1["context"]["user"], json!({}));
+        assert!(!transport.request_headers.lock().unwrap()[0].contains_key("x-goog-authuser"));
+
+        // the index saved with the headers is used unless overridden
+        let default = ConfigArgs::parse_from(["sync_dis_boi"]);
+        assert_eq!(YtMusicApi::auth_user(&default, Some("2")).as_deref(), Some("2"));
+        assert_eq!(YtMusicApi::auth_user(&config, Some("2")).as_deref(), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_context_language_and_region() {
+        let transport = Arc::new(ScriptedTransport::new(vec![(200, VALID)]));
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--yt-lang", "ja", "--yt-region", "JP"]);
+        let mut yt_music = YtMusicApi::with_transport(transport.clone(), config.clone());
+        let _: YtMusicPlaylistCreateResponse =
+            yt_music.make_request("playlist/create", &json!({}), None).await.unwrap();
+        let client = &transport.requests.lock().unwrap()[0].1["context"]["client"];
+        assert_eq!((client["hl"].as_str(), client["gl"].as_str()), (Some("ja"), Some("JP")));
+        assert_eq!(client["clientName"], "WEB_REMIX");
+
+        // English, and the region of the account once it's known
+        let default = ConfigArgs::parse_from(["sync_dis_boi"]);
+        let client = &YtMusicApi::request_context(&default, UNKNOWN_COUNTRY)["client"];
+        assert_eq!((client["hl"].as_str(), client.get("gl")), (Some("en"), None));
+        assert_eq!(YtMusicApi::request_context(&default, "FR")["client"]["gl"], "FR");
+        assert_eq!(YtMusicApi::request_context(&config, "FR")["client"]["gl"], "JP");
+    }
+
+    #[tokio::test]
+    async fn test_uploads_playlist() {
+        let item = |id: &str, name: &str| {
+            json!({"musicResponsiveListItemRenderer": {
+                "menu": {"menuRenderer": {"items": []}},
+                "overlay": {"musicItemThumbnailOverlayRenderer": {"content": {"musicPlayButtonRenderer": {
+                    "playNavigationEndpoint": {"watchEndpoint": {"videoId": id}}}}}},
+                "flexColumns": [
+                    {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": name}]}}},
+                    {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Jane"}]}}}
+                ],
+                "fixedColumns": [
+                    {"musicResponsiveListItemFixedColumnRenderer": {"text": {"runs": [{"text": "2:00"}]}}}
+                ]
+            }})
+        };
+        let page_1 = json!({"contents": {"singleColumnBrowseResultsRenderer": {"tabs": [{"tabRenderer":
+            {"content": {"sectionListRenderer": {"contents": [{"musicShelfRenderer": {
+                "contents": [item("upl1", "First demo")],
+                "continuations": [{"nextContinuationData": {"continuation": "CONT1"}}]
+            }}]}}}}]}}})
+        .to_string();
+        let page_2 = json!({"continuationContents": {"musicShelfContinuation": {
+            "contents": [item("upl2", "Second demo")]
+        }}})
+        .to_string();
+        let (mut yt_music, transport) = api(vec![(200, &page_1), (200, &page_2)]);
+
+        let uploads = yt_music.uploads_playlist().unwrap();
+        assert_eq!(uploads.name, "Uploads");
+        let songs = yt_music.get_playlist_songs(&uploads.id).await.unwrap();
+        let ids: Vec<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["upl1", "upl2"]);
+        assert!(songs.iter().all(|s| s.uploaded));
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].1["browseId"], YtMusicApi::UPLOADS_PLAYLIST_ID);
+        assert!(requests[1].0.contains("ctoken=CONT1"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_unavailable_playlists() {
+        let private = r#"{"alerts": [{"alertWithButtonRenderer": {"type": "ERROR",
+            "text": {"simpleText": "This playlist is private."}}}]}"#;
+        let deleted = r#"{"alerts": [{"alertRenderer": {"type": "ERROR",
+            "text": {"runs": [{"text": "The playlist does not exist."}]}}}]}"#;
+        let empty = r#"{"contents": {"twoColumnBrowseResultsRenderer": {"secondaryContents": {}}}}"#;
+        let (mut yt_music, transport) = api(vec![(200, private), (200, deleted), (200, empty)]);
+
+        let mut playlists = vec![
+            playlist("PLprivate", "Private", vec![]),
+            playlist("PLdeleted", "Deleted", vec![]),
+            playlist("PLempty", "Empty", vec![]),
+        ];
+        yt_music.fetch_playlists_songs(&mut playlists).await.unwrap();
+        let unavailable: Vec<Option<Unavailability>> = playlists.iter().map(|p| p.unavailable).collect();
+        assert_eq!(
+            unavailable,
+            vec![Some(Unavailability::Inaccessible), Some(Unavailability::Deleted), None]
+        );
+        // alerts are not retried like unparsable responses
+        assert_eq!(transport.request_count(), 3);
+        assert_eq!(transport.requests.lock().unwrap()[0].1["browseId"], "VLPLprivate");
+    }
+
+    #[tokio::test]
+    async fn test_verify_ids() {
+        let next = |id: &str| {
+            json!({"contents": {"singleColumnMusicWatchNextResultsRenderer": {"tabbedRenderer": {
+                "watchNextTabbedResultsRenderer": {"tabs": [{"tabRenderer": {"content": {"musicQueueRenderer": {
+                    "content": {"playlistPanelRenderer": {"contents": [{"playlistPanelVideoRenderer": {
+                        "videoId": id,
+                        "title": {"runs": [{"text": "Song"}]},
+                        "lengthText": {"runs": [{"text": "3:00"}]}
+                    }}]}}
+                }}}}]}
+            }}}})
+            .to_string()
+        };
+        // the queue of unknown videos is empty
+        let unknown = r#"{"contents": {"singleColumnMusicWatchNextResultsRenderer": {"tabbedRenderer": {
+            "watchNextTabbedResultsRenderer": {"tabs": [{"tabRenderer": {}}]}}}}}"#;
+        let (next_a, next_c, next_x) = (next("a"), next("c"), next("x"));
+        // the queue of "d" starts with another video, the lookup of "e" fails
+        let responses = vec![
+            (200, next_a.as_str()),
+            (200, unknown),
+            (200, next_c.as_str()),
+            (200, next_x.as_str()),
+            (400, r#"{"error": {"code": 400, "status": "INVALID_ARGUMENT"}}"#),
+            (200, SUCCEEDED),
+        ];
+        let transport = Arc::new(ScriptedTransport::new(responses));
+        let config_dir = test_dir("yt_music");
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--verify-ids", "--config-dir", config_dir.to_str().unwrap()]);
+        let mut yt_music = YtMusicApi::with_transport(transport.clone(), config);
+
+        let mut pl = playlist("PL1", "Mix", vec![]);
+        let songs: Vec<Song> =
+            ["a", "b", "c", "d", "e"].iter().map(|id| song(MusicApiType::YtMusic, id, id)).collect();
+        yt_music.add_songs_to_playlist(&mut pl, &songs).await.unwrap();
+        let ids: Vec<&str> = pl.songs.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 6);
+        assert_eq!(requests[1].1["videoId"], "b");
+        let added: Vec<&str> = requests[5].1["actions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| a["addedVideoId"].as_str().unwrap())
+            .collect();
+        assert_eq!(added, vec!["a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_refetch_after_html_interstitial() {
+        let (mut api, transport) = api(vec![
+            (200, "<!DOCTYPE html><html><body>Please wait...</body></html>"),
+            (200, VALID),
+        ]);
+        let res: YtMusicPlaylistCreateResponse =
+            api.make_request("playlist/create", &json!({}), None).await.unwrap();
+        assert_eq!(res.playlist_id, "PL123");
+        assert_eq!(transport.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures() {
+        for code in [500, 502, 503, 504] {
+            assert!(YtMusicApi::is_transient_status(StatusCode::from_u16(code).unwrap()));
+        }
+        // 429 goes through the rate limit handling, other errors fail fast
+        for code in [400, 401, 403, 404, 429, 501] {
+            assert!(!YtMusicApi::is_transient_status(StatusCode::from_u16(code).unwrap()));
+        }
+
+        let client = reqwest::Client::new();
+        // nothing listens anymore on the port of a dropped listener
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let refused = client.get(&closed).send().await.unwrap_err();
+        assert!(YtMusicApi::is_transient_error(&refused.into()));
+        let invalid = client.get("not a url").send().await.unwrap_err();
+        assert!(!YtMusicApi::is_transient_error(&invalid.into()));
+        assert!(!YtMusicApi::is_transient_error(&eyre!("invalid response")));
+
+        assert_eq!(YtMusicApi::TRANSIENT_POLICY.backoff(0), Duration::from_secs(2));
+        assert_eq!(YtMusicApi::TRANSIENT_POLICY.backoff(3), Duration::from_secs(16));
+        assert_eq!(YtMusicApi::TRANSIENT_POLICY.backoff(10), Duration::from_mins(1));
+    }
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        let config = ConfigArgs::parse_from(["sync_dis_boi"]);
+        let yt_music = YtMusicApi::with_transport(ScriptedTransport::new(vec![]), config);
+        let schedule: Vec<u64> = (0..7).map(|n| yt_music.rate_limit_policy().backoff(n).as_secs()).collect();
+        assert_eq!(schedule, vec![60, 120, 240, 480, 900, 900, 900]);
+
+        let config = ConfigArgs::parse_from(["sync_dis_boi", "--yt-max-retries", "1", "--yt-max-backoff-secs", "100"]);
+        let yt_music = YtMusicApi::with_transport(ScriptedTransport::new(vec![]), config);
+        assert_eq!(yt_music.rate_limit_policy().backoff(0), Duration::from_mins(1));
+        assert_eq!(yt_music.rate_limit_policy().backoff(1), Duration::from_secs(100));
+        // no overflow with large retry budgets
+        assert_eq!(yt_music.rate_limit_policy().backoff(200), Duration::from_secs(100));
+
+        let too_many = StatusCode::TOO_MANY_REQUESTS;
+        assert!(matches!(
+            yt_music.handle_rate_limit_with_retry(too_many, "", 0),
+            RetryAction::Retry(backoff) if backoff == Duration::from_mins(1)
+        ));
+        assert!(matches!(
+            yt_music.handle_rate_limit_with_retry(too_many, "", 1),
+            RetryAction::MaxRetriesExceeded
+        ));
+        assert!(matches!(
+            yt_music.handle_rate_limit_with_retry(StatusCode::OK, "", 1),
+            RetryAction::Continue
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after_server_error() {
+        let (mut yt_music, transport) = api(vec![(503, "Service Unavailable"), (200, VALID)]);
+        let res: YtMusicPlaylistCreateResponse =
+            yt_music.make_request("playlist/create", &json!({}), None).await.unwrap();
+        assert_eq!(res.playlist_id, "PL123");
+        assert_eq!(transport.request_count(), 2);
+
+        let (mut yt_music, transport) = api(vec![(404, "Not Found"), (200, VALID)]);
+        let res = yt_music
+            .make_request::<YtMusicPlaylistCreateResponse>("playlist/create", &json!({}), None)
+            .await;
+        assert!(res.is_err());
+        assert_eq!(transport.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refetch_only_once() {
+        let (mut api, transport) = api(vec![(200, "{"), (200, "<html></html>"), (200, VALID)]);
+        let err = api
+            .make_request::<YtMusicPlaylistCreateResponse>("playlist/create", &json!({}), None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(transport.request_count(), 2);
+        // both saved bodies are referenced in the error
+        let saved = err.split("Responses saved to: ").nth(1).unwrap();
+        assert_eq!(saved.split(", ").count(), 2);
+        assert!(saved.contains(".txt") && saved.contains(".html"));
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay_fixtures() {
+        let dir = test_dir("ytmusic_fixtures");
+        let page_1 = r#"{"playlistId": "PL1", "header": {"accountName": "Jane", "email": "jane@gmail.com"}}"#;
+        let page_2 = r#"{"playlistId": "PL2"}"#;
+        let (api, _) = api(vec![(200, page_1), (200, page_2)]);
+        let mut api = api.record_fixtures(dir.clone()).unwrap();
+        let body = json!({"browseId": "FEmusic_liked_playlists"});
+        let _: serde_json::Value = api.make_request("browse", &body, None).await.unwrap();
+        let _: serde_json::Value = api.make_request("browse", &body, Some("CONT")).await.unwrap();
+
+        let recorded = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(recorded, 2);
+        let all = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|f| std::fs::read_to_string(f.unwrap().path()).unwrap())
+            .collect::<String>();
+        assert!(!all.contains("Jane") && !all.contains("jane@gmail.com"));
+
+        let config = ConfigArgs::parse_from(["sync_dis_boi"]);
+        let mut replay = YtMusicApi::new_replay(dir.clone(), config).unwrap();
+        let res: YtMusicPlaylistCreateResponse =
+            replay.make_request("browse", &body, Some("CONT")).await.unwrap();
+        assert_eq!(res.playlist_id, "PL2");
+        let res: serde_json::Value = replay.make_request("browse", &body, None).await.unwrap();
+        assert_eq!(res["header"]["accountName"], "REDACTED");
+
+        let err = replay
+            .make_request::<serde_json::Value>("browse", &json!({"browseId": "other"}), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no recorded fixture"));
+    }
+}
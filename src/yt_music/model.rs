@@ -1,10 +1,58 @@
 use serde::Deserialize;
+use serde::de::IgnoredAny;
 
 #[derive(Deserialize, Debug)]
 pub struct YtMusicOAuthDeviceRes {
     pub verification_url: String,
     pub user_code: String,
     pub device_code: String,
+    /// Minimum number of seconds between two polls of the token endpoint
+    #[serde(default = "YtMusicOAuthDeviceRes::default_interval")]
+    pub interval: u64,
+    /// Number of seconds before the device code expires
+    #[serde(default = "YtMusicOAuthDeviceRes::default_expires_in")]
+    pub expires_in: u64,
+}
+
+impl YtMusicOAuthDeviceRes {
+    // defaults of RFC 8628 when the server doesn't send them
+    fn default_interval() -> u64 {
+        5
+    }
+
+    fn default_expires_in() -> u64 {
+        1800
+    }
+}
+
+/// HAR file exported by the `DevTools` of a browser, only the request headers are read
+#[derive(Deserialize, Debug)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HarLog {
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    pub request: HarRequest,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HarRequest {
+    pub url: String,
+    pub headers: Vec<HarHeader>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -52,6 +100,17 @@ impl YtMusicResponse {
                     .as_mut()?,
             );
             Some(())
+        } else if let Some(msr) = self.get_music_shelf_renderer() {
+            msr.contents.as_mut()?.append(
+                other
+                    .continuation_contents
+                    .as_mut()?
+                    .music_shelf_continuation
+                    .as_mut()?
+                    .contents
+                    .as_mut()?,
+            );
+            Some(())
         } else {
             None
         }
@@ -140,6 +199,90 @@ impl YtMusicResponse {
         }
     }
 
+    /// Contents of the first tab of a playlist page, holding its header
+    fn playlist_header_contents(&self) -> Option<&Vec<SectionRendererContent>> {
+        self.contents
+            .two_column_browse_results_renderer
+            .as_ref()?
+            .tabs
+            .as_ref()?
+            .first()?
+            .tab_renderer
+            .content
+            .section_list_renderer
+            .contents
+            .as_ref()
+    }
+
+    /// Header of a playlist page, the playlists of the user have it inside an editable header
+    fn get_playlist_header(&self) -> Option<&MusicResponsiveHeaderRenderer> {
+        self.playlist_header_contents()?.iter().find_map(|c| {
+            c.music_responsive_header_renderer.as_ref().or_else(|| {
+                c.music_editable_playlist_detail_header_renderer
+                    .as_ref()?
+                    .header
+                    .as_ref()?
+                    .music_responsive_header_renderer
+                    .as_ref()
+            })
+        })
+    }
+
+    /// Owner shown in the header of a playlist page
+    pub fn get_playlist_owner(&self) -> Option<String> {
+        self.get_playlist_header()?
+            .strapline_text_one
+            .as_ref()?
+            .runs
+            .as_ref()?
+            .first()
+            .map(Run::get_text)
+    }
+
+    /// Description shown in the header of a playlist page, `None` when it has none
+    pub fn get_playlist_description(&self) -> Option<String> {
+        let runs = self
+            .get_playlist_header()?
+            .description
+            .as_ref()?
+            .music_description_shelf_renderer
+            .description
+            .runs
+            .as_ref()?;
+        Some(runs.iter().map(Run::get_text).collect())
+    }
+
+    /// Whether a playlist is public, only known for the playlists of the user. Unlisted
+    /// playlists aren't public
+    pub fn get_playlist_public(&self) -> Option<bool> {
+        let privacy = self
+            .playlist_header_contents()?
+            .iter()
+            .find_map(|c| c.music_editable_playlist_detail_header_renderer.as_ref())?
+            .edit_header
+            .as_ref()?
+            .music_playlist_edit_header_renderer
+            .privacy
+            .as_deref()?;
+        Some(privacy == "PUBLIC")
+    }
+
+    /// Songs shelf of the library pages listing songs, like the uploaded songs
+    pub fn get_music_shelf_renderer(&mut self) -> Option<&mut MusicPlaylistShelfRenderer> {
+        self.contents
+            .single_column_browse_results_renderer
+            .as_mut()?
+            .tabs
+            .first_mut()?
+            .tab_renderer
+            .content
+            .section_list_renderer
+            .contents
+            .as_mut()?
+            .iter_mut()
+            .find_map(|item| item.music_shelf_renderer.as_mut())
+    }
+
     pub fn get_grid_renderer(&mut self) -> Option<&mut GridRenderer> {
         self.get_section_renderer_content()?.grid_renderer.as_mut()
     }
@@ -185,6 +328,10 @@ impl YtMusicResponse {
             }
         }
 
+        if continuation.is_none() && self.get_section_renderer_content().is_none() {
+            continuation = self.get_music_shelf_renderer().and_then(|msr| msr.get_continuation());
+        }
+
         /* Not necessary for getting more than 100 songs */
         // if continuation.is_none() {
         //     if let Some(slr) = self.get_section_list_renderer() {
@@ -235,6 +382,8 @@ pub struct ResponseContent {
 #[serde(rename_all = "camelCase")]
 pub struct TwoColumnBrowseResultsRenderer {
     pub tab_renderer: Option<TabRendererContent>,
+    /// The header of playlist pages is in the first tab
+    pub tabs: Option<Vec<Tab>>,
     pub secondary_contents: SecondaryContents,
 }
 #[derive(Deserialize, Debug)]
@@ -270,9 +419,52 @@ pub struct SectionRendererContent {
     pub grid_renderer: Option<GridRenderer>,
     pub music_shelf_renderer: Option<MusicPlaylistShelfRenderer>,
     pub music_card_shelf_renderer: Option<MusicCardShelfRenderer>,
+    pub music_responsive_header_renderer: Option<MusicResponsiveHeaderRenderer>,
+    /// Header of the playlists of the user, wrapping the responsive one
+    pub music_editable_playlist_detail_header_renderer: Option<MusicEditablePlaylistDetailHeaderRenderer>,
     //pub item_section_renderer: Option<ContentsSingle<ItemSectionRendererContent>>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicResponsiveHeaderRenderer {
+    /// Owner of the playlist, with a link to their channel
+    pub strapline_text_one: Option<Text>,
+    pub description: Option<MusicDescription>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicDescription {
+    pub music_description_shelf_renderer: MusicDescriptionShelfRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicDescriptionShelfRenderer {
+    pub description: Text,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicEditablePlaylistDetailHeaderRenderer {
+    pub header: Option<MusicEditableHeader>,
+    pub edit_header: Option<MusicEditHeader>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicEditableHeader {
+    pub music_responsive_header_renderer: Option<MusicResponsiveHeaderRenderer>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicEditHeader {
+    pub music_playlist_edit_header_renderer: MusicPlaylistEditHeaderRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicPlaylistEditHeaderRenderer {
+    /// "PUBLIC", "UNLISTED" or "PRIVATE"
+    pub privacy: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct MusicPlaylistShelfRenderer {
@@ -304,8 +496,22 @@ pub struct MusicResponsiveListItemRenderer {
     pub flex_columns: Option<Vec<FlexColumn>>,
     pub fixed_columns: Option<Vec<FixedColumn>>,
     pub playlist_item_data: Option<PlaylistItemData>,
+    pub navigation_endpoint: Option<NavigationEndpoint>,
+    pub badges: Option<Vec<Badge>>,
 }
 impl MusicResponsiveListItemRenderer {
+    const PODCAST_EPISODE_TYPE: &'static str = "MUSIC_VIDEO_TYPE_PODCAST_EPISODE";
+    const EXPLICIT_BADGE_ICON: &'static str = "MUSIC_EXPLICIT_BADGE";
+
+    /// Whether the item shows the "Explicit" badge next to its artists
+    pub fn is_explicit(&self) -> bool {
+        self.badges
+            .iter()
+            .flatten()
+            .filter_map(|b| b.music_inline_badge_renderer.as_ref())
+            .any(|b| b.icon.icon_type == Self::EXPLICIT_BADGE_ICON)
+    }
+
     pub fn get_set_id(&self) -> Option<String> {
         self.playlist_item_data
             .as_ref()?
@@ -317,6 +523,39 @@ impl MusicResponsiveListItemRenderer {
         Some(self.playlist_item_data.as_ref()?.video_id.clone())
     }
 
+    /// Browse id of the page the item links to, the channel id of artists
+    pub fn get_browse_id(&self) -> Option<String> {
+        Some(self.navigation_endpoint.as_ref()?.browse_endpoint.as_ref()?.browse_id.clone())
+    }
+
+    /// Video id of the play button, the only id of the songs outside of playlists (uploads)
+    pub fn get_play_id(&self) -> Option<String> {
+        Some(self.get_play_endpoint()?.video_id.clone())
+    }
+
+    fn get_play_endpoint(&self) -> Option<&WatchEndpoint> {
+        self.overlay
+            .as_ref()?
+            .music_item_thumbnail_overlay_renderer
+            .content
+            .music_play_button_renderer
+            .play_navigation_endpoint
+            .as_ref()?
+            .watch_endpoint
+            .as_ref()
+    }
+
+    /// Whether the item is a podcast episode, read from its play button or its title link
+    pub fn is_podcast_episode(&self) -> bool {
+        let title_endpoint = self
+            .get_col_runs(0, true)
+            .and_then(|runs| runs.first()?.navigation_endpoint.as_ref()?.watch_endpoint.as_ref());
+        [self.get_play_endpoint(), title_endpoint]
+            .into_iter()
+            .flatten()
+            .any(|we| we.music_video_type() == Some(Self::PODCAST_EPISODE_TYPE))
+    }
+
     pub fn get_col_run_text(&self, idx: usize, run_i: usize, flex: bool) -> Option<String> {
         Some(self.get_col_runs(idx, flex)?.get(run_i)?.get_text())
     }
@@ -325,6 +564,20 @@ impl MusicResponsiveListItemRenderer {
         self.get_col_runs(idx, flex)?.get(run_i)?.get_id()
     }
 
+    /// Runs of the title column after the title itself, like its version in another script
+    pub fn get_alt_titles(&self) -> Vec<String> {
+        let Some(runs) = self.get_col_runs(0, true) else {
+            return vec![];
+        };
+        let title = runs.first().map(|r| r.text.trim());
+        runs.iter()
+            .skip(1)
+            .map(|r| r.text.trim_matches(|c: char| c.is_whitespace() || "()[]-/·•".contains(c)))
+            .filter(|t| !t.is_empty() && Some(*t) != title)
+            .map(str::to_string)
+            .collect()
+    }
+
     pub fn get_col_runs(&self, idx: usize, flex: bool) -> Option<&Vec<Run>> {
         let mrlifcr = if flex {
             &self
@@ -405,6 +658,15 @@ impl MusicTwoRowItemRenderer {
     pub fn get_owner(&self) -> Option<String> {
         Some(self.subtitle.as_ref()?.runs.as_ref()?.first()?.get_text())
     }
+
+    /// Whether the item is the "New playlist" entry of the library, which opens the playlist
+    /// creation dialog instead of a playlist
+    pub fn is_create_playlist(&self) -> bool {
+        self.on_tap
+            .as_ref()
+            .is_some_and(|ne| ne.create_playlist_endpoint.is_some())
+            || self.get_id().is_none()
+    }
 }
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -484,6 +746,7 @@ impl Run {
 pub struct NavigationEndpoint {
     pub browse_endpoint: Option<BrowseEndpoint>,
     pub watch_endpoint: Option<WatchEndpoint>,
+    pub create_playlist_endpoint: Option<IgnoredAny>,
 }
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -491,6 +754,22 @@ pub struct BrowseEndpoint {
     pub browse_id: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Badge {
+    pub music_inline_badge_renderer: Option<MusicInlineBadgeRenderer>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicInlineBadgeRenderer {
+    pub icon: Icon,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Icon {
+    pub icon_type: String,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Menu {
@@ -605,6 +884,27 @@ pub struct MusicPlayButtonRenderer {
 pub struct WatchEndpoint {
     pub playlist_id: Option<String>,
     pub video_id: String,
+    pub watch_endpoint_music_supported_configs: Option<WatchEndpointMusicSupportedConfigs>,
+}
+impl WatchEndpoint {
+    /// Kind of video played, e.g. `MUSIC_VIDEO_TYPE_ATV` for songs
+    pub fn music_video_type(&self) -> Option<&str> {
+        self.watch_endpoint_music_supported_configs
+            .as_ref()?
+            .watch_endpoint_music_config
+            .music_video_type
+            .as_deref()
+    }
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchEndpointMusicSupportedConfigs {
+    pub watch_endpoint_music_config: WatchEndpointMusicConfig,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchEndpointMusicConfig {
+    pub music_video_type: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -642,6 +942,8 @@ impl YtMusicContinuationResponse {
                 g.get_continuation()
             } else if let Some(m) = &cc.music_playlist_shelf_continuation {
                 m.get_continuation()
+            } else if let Some(m) = &cc.music_shelf_continuation {
+                m.get_continuation()
             } else if let Some(s) = &cc.section_list_continuation {
                 s.get_continuation()
             } else {
@@ -657,6 +959,7 @@ impl YtMusicContinuationResponse {
 pub struct ContinuationContents {
     pub grid_continuation: Option<GridRenderer>,
     pub music_playlist_shelf_continuation: Option<MusicPlaylistShelfRenderer>,
+    pub music_shelf_continuation: Option<MusicPlaylistShelfRenderer>,
     pub section_list_continuation: Option<SectionListContinuation>,
 }
 
@@ -713,6 +1016,25 @@ impl YtMusicPlaylistEditResponse {
     pub fn success(&self) -> bool {
         self.status == "STATUS_SUCCEEDED"
     }
+
+    fn actions(&self) -> impl Iterator<Item = &Action> {
+        self.actions.iter().flatten()
+    }
+
+    /// `YtMusic` asks for a confirmation when some of the added songs are already in the playlist
+    pub fn duplicates_dialog(&self) -> bool {
+        self.actions()
+            .filter_map(|a| a.confirm_dialog_endpoint.as_ref())
+            .any(|d| d.content.confirm_dialog_renderer.title.runs.iter().any(|r| r.text == "Duplicates"))
+    }
+
+    /// Toast shown when the only added song is already in the playlist
+    pub fn already_in_playlist(&self) -> bool {
+        self.actions()
+            .filter_map(|a| a.add_to_toast_action.as_ref())
+            .flat_map(|t| &t.item.notification_action_renderer.response_text.runs)
+            .any(|r| r.text.contains("This track is already in the playlist"))
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -731,6 +1053,153 @@ pub struct HandlePlaylistDeletionCommand {
     pub playlist_id: String,
 }
 
+/// First page of a `VL...` browse: the playlist, or the alerts telling why it can't be shown
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum YtMusicPlaylistPage {
+    Playlist(YtMusicResponse),
+    Alerts(YtMusicAlertsResponse),
+}
+
+/// Browse response without contents, e.g. for a private or deleted playlist
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct YtMusicAlertsResponse {
+    pub alerts: Vec<AlertContent>,
+}
+impl YtMusicAlertsResponse {
+    /// Texts of the alerts, in order
+    pub fn messages(&self) -> Vec<String> {
+        self.alerts
+            .iter()
+            .filter_map(|a| a.alert_renderer.as_ref().or(a.alert_with_button_renderer.as_ref()))
+            .map(|r| r.text.get_text())
+            .filter(|t| !t.is_empty())
+            .collect()
+    }
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertContent {
+    pub alert_renderer: Option<AlertRenderer>,
+    pub alert_with_button_renderer: Option<AlertRenderer>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRenderer {
+    pub text: AlertText,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertText {
+    pub runs: Option<Vec<Run>>,
+    pub simple_text: Option<String>,
+}
+impl AlertText {
+    pub fn get_text(&self) -> String {
+        match (&self.simple_text, &self.runs) {
+            (Some(text), _) => text.clone(),
+            (None, Some(runs)) => runs.iter().map(Run::get_text).collect(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Response of the `next` endpoint, the watch queue starting with the requested video
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct YtMusicNextResponse {
+    pub contents: NextContents,
+}
+impl YtMusicNextResponse {
+    /// Video `video_id` of the queue, missing when the video doesn't exist and the queue
+    /// starts with another one
+    pub fn get_video(&self, video_id: &str) -> Option<&PlaylistPanelVideoRenderer> {
+        self.contents
+            .single_column_music_watch_next_results_renderer
+            .as_ref()?
+            .tabbed_renderer
+            .watch_next_tabbed_results_renderer
+            .tabs
+            .first()?
+            .tab_renderer
+            .content
+            .as_ref()?
+            .music_queue_renderer
+            .content
+            .as_ref()?
+            .playlist_panel_renderer
+            .contents
+            .iter()
+            .filter_map(|item| item.playlist_panel_video_renderer.as_ref())
+            .find(|video| video.video_id == video_id)
+    }
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NextContents {
+    pub single_column_music_watch_next_results_renderer: Option<SingleColumnMusicWatchNextResultsRenderer>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SingleColumnMusicWatchNextResultsRenderer {
+    pub tabbed_renderer: TabbedRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TabbedRenderer {
+    pub watch_next_tabbed_results_renderer: WatchNextTabbedResultsRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchNextTabbedResultsRenderer {
+    pub tabs: Vec<WatchNextTab>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchNextTab {
+    pub tab_renderer: WatchNextTabRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchNextTabRenderer {
+    pub content: Option<WatchNextTabContent>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchNextTabContent {
+    pub music_queue_renderer: MusicQueueRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicQueueRenderer {
+    pub content: Option<MusicQueueContent>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicQueueContent {
+    pub playlist_panel_renderer: PlaylistPanelRenderer,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistPanelRenderer {
+    pub contents: Vec<PlaylistPanelItem>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistPanelItem {
+    pub playlist_panel_video_renderer: Option<PlaylistPanelVideoRenderer>,
+}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistPanelVideoRenderer {
+    pub video_id: String,
+    pub title: Text,
+    /// Artists, album and year separated by " • "
+    pub long_byline_text: Option<Text>,
+    pub length_text: Option<Text>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct YtMusicAddLikeResponse {
@@ -1,11 +1,16 @@
 use color_eyre::eyre::{Error, Result, eyre};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, warn};
 
 use super::YtMusicApi;
-use super::model::YtMusicResponse;
-use crate::music_api::{Album, Artist, MusicApiType, Playlist, Playlists, Song, Songs};
+use super::model::{
+    MusicResponsiveListItemRenderer, YtMusicAlertsResponse, YtMusicNextResponse, YtMusicResponse,
+};
+use crate::music_api::{
+    Album, Artist, MusicApiType, Playlist, PlaylistUnavailable, Playlists, Song, Songs, Unavailability,
+};
+use crate::utils::parse_year;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SearchSongs(pub Vec<Song>);
@@ -13,6 +18,22 @@ pub struct SearchSongs(pub Vec<Song>);
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SearchSongUnique(pub Option<Song>);
 
+/// Song of a video id read from its watch queue, `None` when the video doesn't exist
+#[derive(Deserialize, Serialize, Debug)]
+pub struct WatchSong(pub Option<Song>);
+
+/// Songs uploaded to the library, listed by the `FEmusic_library_privately_owned_tracks` page
+#[derive(Deserialize, Serialize, Debug)]
+pub struct UploadedSongs(pub Vec<Song>);
+
+/// Artists the library is subscribed to, listed by the `FEmusic_library_corpus_artists` page
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SubscribedArtists(pub Vec<Artist>);
+
+/// Artists found by a search filtered on artists, in the order `YtMusic` ranks them
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SearchArtists(pub Vec<Artist>);
+
 pub fn parse_duration(duration_str: &str) -> Result<usize> {
     let multipliers = [1, 60, 3600];
     let mut seconds = 0;
@@ -27,16 +48,23 @@ impl TryInto<Playlists> for YtMusicResponse {
 
     fn try_into(mut self) -> Result<Playlists, Self::Error> {
         let mut playlists = vec![];
-        for mtrir in self
+        let mtrirs = self
             .get_mtrirs()
-            .ok_or(eyre!("No mtrirs found in response, are you authenticated? You may need to refresh your cookie/token."))?
-            .iter()
-            // Ignore the first "New Playlist" playlist
-            // Ignore the second "Your Likes" playlist
-            .skip(2)
-        {
+            .ok_or(eyre!("No mtrirs found in response, are you authenticated? You may need to refresh your cookie/token."))?;
+        for mtrir in mtrirs {
+            // the position of the automatic entries of the library isn't stable, they are
+            // recognized by their id instead
+            if mtrir.is_create_playlist() {
+                debug!("skipping the \"New playlist\" entry of the library");
+                continue;
+            }
             let id = mtrir.get_id().ok_or(eyre!("No playlist id"))?;
             let id = YtMusicApi::clean_playlist_id(&id);
+            // liked songs are synchronized with --sync-likes
+            if id == YtMusicApi::LIKES_PLAYLIST_ID {
+                debug!("skipping the \"Your Likes\" playlist of the library");
+                continue;
+            }
             let name = mtrir
                 .get_name()
                 .ok_or(eyre!("No playlist name"))?
@@ -47,11 +75,22 @@ impl TryInto<Playlists> for YtMusicResponse {
                 .ok_or(eyre!("No playlist owner name"))?
                 .trim()
                 .to_string();
+            let generated = YtMusicApi::is_generated_playlist(&id, &owner);
+            // the library doesn't show the description nor the privacy of playlists
             let playlist = Playlist {
                 id,
                 name,
                 songs: vec![],
-                owner: Some(owner)
+                owner: Some(owner),
+                description: None,
+                public: None,
+                generated,
+                cover_url: None,
+                unavailable: None,
+                owner_name: None,
+                collaborative: false,
+                snapshot_id: None,
+                folder: None,
             };
             playlists.push(playlist);
         }
@@ -61,6 +100,45 @@ impl TryInto<Playlists> for YtMusicResponse {
     }
 }
 
+/// Duration column of a playlist item, `None` for the items that aren't songs (podcast
+/// episodes) or are unavailable
+/// Error of a playlist whose page only has alerts: "This playlist is private" for an
+/// inaccessible playlist, "The playlist does not exist" for a deleted one
+pub fn playlist_unavailable(id: &str, response: &YtMusicAlertsResponse) -> PlaylistUnavailable {
+    let messages = response.messages();
+    let deleted = messages.iter().any(|m| {
+        let m = m.to_lowercase();
+        m.contains("does not exist") || m.contains("doesn't exist") || m.contains("deleted")
+    });
+    PlaylistUnavailable {
+        id: id.to_string(),
+        reason: if deleted {
+            Unavailability::Deleted
+        } else {
+            Unavailability::Inaccessible
+        },
+        message: if messages.is_empty() {
+            "no reason given".to_string()
+        } else {
+            messages.join(" ")
+        },
+    }
+}
+
+fn item_duration(mrlir: &MusicResponsiveListItemRenderer) -> Option<String> {
+    let is_duration = |text: &String| text.contains(':');
+    // the duration is in the first fixed column, or in the third flex column, some items
+    // have it in the fourth one
+    let duration = if mrlir.fixed_columns.is_some() {
+        mrlir.get_col_run_text(0, 0, false)
+    } else {
+        mrlir.get_col_run_text(2, 0, true)
+    };
+    duration
+        .filter(is_duration)
+        .or_else(|| mrlir.get_col_run_text(3, 0, true).filter(is_duration))
+}
+
 impl TryInto<Songs> for YtMusicResponse {
     type Error = Error;
 
@@ -71,105 +149,26 @@ impl TryInto<Songs> for YtMusicResponse {
             return Ok(Songs(songs_vec));
         };
 
-        for mrlir in mrlirs
-            .iter()
-            // Ignore unavailable songs
-            .filter(|item| item.playlist_item_data.is_some())
-        {
-            let id = mrlir.get_id().ok_or(eyre!("No song id"))?;
-            let set_id = mrlir.get_set_id().ok_or(eyre!("No song set_id"))?;
-
-            let mut duration_str = {
-                // Check both fixed_columns and flex_columns for the duration
-                let fixed_cols = mrlir.fixed_columns.as_ref();
-                let flex_cols = mrlir.flex_columns.as_ref();
-
-                if let Some(fixed_cols) = fixed_cols {
-                    // debug!("Found {} fixed_columns", fixed_cols.len());
-                    // Get the first fixed column
-                    let fixed_col = fixed_cols.get(0)
-                        .ok_or(eyre!("No fixed column at index 0"))?;
-                    // debug!("Fixed column 0: {:?}", fixed_col);
-                    
-                    // Access the renderer text
-                    let text = &fixed_col.music_responsive_list_item_fixed_column_renderer.text;
-                    // debug!("Fixed column text field: {:?}", text);
-                    
-                    // Get the runs array
-                    let runs = text.runs.as_ref()
-                        .ok_or(eyre!("No runs found in fixed column text"))?;
-                    // debug!("Runs in fixed column: {:?}", runs);
-                    
-                    // Get the first run and extract its text
-                    let first_run = runs.first()
-                        .ok_or(eyre!("No first run in fixed column text"))?;
-                    // debug!("First run: {:?}", first_run);
-                    
-                    first_run.text.clone()
-                } else if let Some(flex_cols) = flex_cols {
-                    // debug!("Found {} flex_columns", flex_cols.len());
-                    // Get the third flex column (assuming duration is in the third column)
-                    let flex_col = flex_cols.get(2)
-                        .ok_or(eyre!("No flex column at index 2"))?;
-                    // debug!("Flex column 2: {:?}", flex_col);
-                    
-                    // Access the renderer text
-                    let text = &flex_col.music_responsive_list_item_flex_column_renderer.text;
-                    // debug!("Flex column text field: {:?}", text);
-                    
-                    // Get the runs array
-                    let runs = text.runs.as_ref()
-                        .ok_or(eyre!("No runs found in flex column text"))?;
-                    // debug!("Runs in flex column: {:?}", runs);
-                    
-                    // Get the first run and extract its text
-                    let first_run = runs.first()
-                        .ok_or(eyre!("No first run in flex column text"))?;
-                    // debug!("First run: {:?}", first_run);
-                    
-                    first_run.text.clone()
-                } else {
-                    return Err(eyre!("No fixed_columns or flex_columns in item {:?}", mrlir));
-                }
-            };
-
-            if duration_str.is_empty() || !duration_str.contains(":") {
-                // Attempt to find duration from another source or format
-                let alternative_duration_str = {
-                    if let Some(flex_cols) = &mrlir.flex_columns {
-                        if let Some(flex_col) = flex_cols.get(3) {
-                            if let Some(runs) = &flex_col.music_responsive_list_item_flex_column_renderer.text.runs {
-                                if let Some(run) = runs.first() {
-                                    run.text.clone()
-                                } else {
-                                    String::new()
-                                }
-                            } else {
-                                String::new()
-                            }
-                        } else {
-                            String::new()
-                        }
-                    } else {
-                        String::new()
-                    }
-                };
-            
-                if alternative_duration_str.is_empty() || !alternative_duration_str.contains(":") {
-                    return Err(eyre!("Failed to extract duration from fixed_columns or alternative source"));
-                } else {
-                    duration_str = alternative_duration_str;
-                }
+        // podcast episodes and unavailable songs are skipped, one bad item must not lose the
+        // whole playlist
+        for mrlir in mrlirs {
+            let title = mrlir.get_col_run_text(0, 0, true).unwrap_or_default();
+            if mrlir.playlist_item_data.is_none() {
+                warn!("skipping unavailable item \"{}\"", title);
+                continue;
+            }
+            if mrlir.is_podcast_episode() {
+                warn!("skipping podcast episode \"{}\"", title);
+                continue;
+            }
+            let Some(duration_str) = item_duration(mrlir) else {
+                warn!("skipping item \"{}\" without duration, it's unavailable or not a song", title);
+                debug!("item without duration: {:?}", mrlir);
+                continue;
             };
 
-            if duration_str.is_empty() || !duration_str.contains(":") {
-                info!("Full item data: {:?}", mrlir);
-                info!("Flex columns: {:?}", mrlir.flex_columns);
-                info!("Fixed columns: {:?}", mrlir.fixed_columns);
-                return Err(eyre!("Failed to extract duration from fixed_columns"));
-                // Crash the program if the duration is not found
-            };
-            
+            let id = mrlir.get_id().ok_or(eyre!("No song id"))?;
+            let set_id = mrlir.get_set_id().ok_or(eyre!("No song set_id"))?;
             let duration = parse_duration(&duration_str)?;
             debug!("Parsed duration (ms): {}", duration);
 
@@ -182,6 +181,7 @@ impl TryInto<Songs> for YtMusicResponse {
                 Some(Album {
                     id: mrlir.get_col_run_id(2, 0, true),
                     name: mrlir.get_col_run_text(2, 0, true)?,
+                    year: None,
                 })
             });
             let mut artists: Vec<Artist> = vec![];
@@ -205,6 +205,11 @@ impl TryInto<Songs> for YtMusicResponse {
                 artists,
                 album,
                 duration_ms: duration,
+                uploaded: false,
+                explicit: Some(mrlir.is_explicit()),
+                alt_names: mrlir.get_alt_titles(),
+                mbid: None,
+                isrc_hint: None,
             };
 
             songs_vec.push(song);
@@ -215,6 +220,174 @@ impl TryInto<Songs> for YtMusicResponse {
     }
 }
 
+impl TryInto<UploadedSongs> for YtMusicResponse {
+    type Error = Error;
+
+    fn try_into(mut self) -> Result<UploadedSongs, Self::Error> {
+        let mut songs = vec![];
+        let Some(msr) = self.get_music_shelf_renderer() else {
+            // no shelf at all when nothing was uploaded
+            return Ok(UploadedSongs(songs));
+        };
+        let items = msr
+            .contents
+            .iter()
+            .flatten()
+            .filter_map(|item| item.music_responsive_list_item_renderer.as_ref())
+            // the "Shuffle all" entry has no menu
+            .filter(|item| item.menu.is_some());
+
+        // fc0 = song title
+        // fc1 = artists
+        // fc2 = album
+        // fixed c0 = duration
+        for mrlir in items {
+            let id = mrlir.get_play_id().ok_or(eyre!("No uploaded song id"))?;
+            let name = mrlir.get_col_run_text(0, 0, true).ok_or(eyre!("No name"))?;
+            let duration_str = mrlir
+                .get_col_run_text(0, 0, false)
+                .ok_or(eyre!("No duration for uploaded song {}", name))?;
+            let duration = parse_duration(&duration_str)?;
+            // the artists and albums of uploads only link to the library, their ids are dropped
+            let album = mrlir
+                .get_col_run_text(2, 0, true)
+                .map(|name| Album { id: None, name, year: None });
+            let artists = mrlir
+                .get_col_runs(1, true)
+                .into_iter()
+                .flatten()
+                .step_by(2)
+                .map(|run| Artist {
+                    id: None,
+                    name: run.get_text(),
+                })
+                .collect();
+            songs.push(Song {
+                source: MusicApiType::YtMusic,
+                id,
+                sid: None,
+                isrc: None,
+                name,
+                album,
+                artists,
+                duration_ms: duration,
+                uploaded: true,
+                explicit: None,
+                alt_names: vec![],
+                mbid: None,
+                isrc_hint: None,
+            });
+        }
+        Ok(UploadedSongs(songs))
+    }
+}
+
+/// Artist linked by a list item, `None` for the items that aren't artists
+fn item_artist(mrlir: &MusicResponsiveListItemRenderer) -> Option<Artist> {
+    // fc0 = artist name
+    // fc1 = "Artist • 1.2M subscribers" or the number of songs
+    // the channel of some artists is prefixed by MPLA
+    let id = mrlir.get_browse_id()?.trim_start_matches("MPLA").to_string();
+    if !id.starts_with("UC") {
+        return None;
+    }
+    let name = mrlir.get_col_run_text(0, 0, true)?;
+    Some(Artist { id: Some(id), name })
+}
+
+impl TryInto<SubscribedArtists> for YtMusicResponse {
+    type Error = Error;
+
+    fn try_into(mut self) -> Result<SubscribedArtists, Self::Error> {
+        let Some(msr) = self.get_music_shelf_renderer() else {
+            // no shelf at all without subscriptions
+            return Ok(SubscribedArtists(vec![]));
+        };
+        let artists = msr
+            .contents
+            .iter()
+            .flatten()
+            .filter_map(|item| item.music_responsive_list_item_renderer.as_ref())
+            .filter_map(item_artist)
+            .collect();
+        Ok(SubscribedArtists(artists))
+    }
+}
+
+impl TryInto<SearchArtists> for YtMusicResponse {
+    type Error = Error;
+
+    fn try_into(mut self) -> Result<SearchArtists, Self::Error> {
+        let artists = self
+            .get_mrlirs()
+            .into_iter()
+            .flatten()
+            .filter_map(item_artist)
+            .collect();
+        Ok(SearchArtists(artists))
+    }
+}
+
+/// Song of `video_id` read from its watch queue
+pub fn watch_song(video_id: &str, response: &YtMusicNextResponse) -> Result<WatchSong> {
+    let Some(video) = response.get_video(video_id) else {
+        return Ok(WatchSong(None));
+    };
+    let name = video
+        .title
+        .runs
+        .as_ref()
+        .and_then(|runs| runs.first())
+        .ok_or(eyre!("No name for video {}", video.video_id))?
+        .get_text();
+    let duration_str = video
+        .length_text
+        .as_ref()
+        .and_then(|text| text.runs.as_ref()?.first())
+        .ok_or(eyre!("No duration for video {}", video.video_id))?
+        .get_text();
+
+    // the byline alternates items and separators: artists, album (linked to a MPRE...
+    // browse id), year, or number of views for videos
+    let mut album = None;
+    let mut artists = vec![];
+    let mut year = None;
+    let byline = video.long_byline_text.as_ref().and_then(|text| text.runs.as_ref());
+    for run in byline.into_iter().flatten().step_by(2) {
+        let id = run.get_id();
+        if id.as_deref().is_some_and(|id| id.starts_with("MPRE")) {
+            album = Some(Album { id, name: run.get_text(), year: None });
+        } else if id.is_none() && is_year(&run.text) {
+            year = parse_year(&run.text);
+        } else if id.is_some() || !run.text.contains("views") {
+            artists.push(Artist { id, name: run.get_text() });
+        }
+    }
+    if let Some(album) = &mut album {
+        album.year = year;
+    }
+
+    Ok(WatchSong(Some(Song {
+        source: MusicApiType::YtMusic,
+        id: video.video_id.clone(),
+        sid: None,
+        isrc: None,
+        name,
+        album,
+        artists,
+        duration_ms: parse_duration(&duration_str)?,
+        uploaded: false,
+        explicit: None,
+        alt_names: vec![],
+        mbid: None,
+        isrc_hint: None,
+    })))
+}
+
+fn is_year(text: &str) -> bool {
+    text.len() == 4 && text.chars().all(|c| c.is_ascii_digit())
+}
+
 impl TryInto<SearchSongs> for YtMusicResponse {
     type Error = Error;
 
@@ -240,6 +413,7 @@ impl TryInto<SearchSongs> for YtMusicResponse {
             let mut album = None;
             let mut artists: Vec<Artist> = vec![];
             let mut duration = 0;
+            let mut year = None;
 
             for run in mrlir
                 .get_col_runs(1, true)
@@ -259,6 +433,7 @@ impl TryInto<SearchSongs> for YtMusicResponse {
                         album = Some(Album {
                             id: Some(id),
                             name: text,
+                            year: None,
                         });
                     } else {
                         artists.push(Artist {
@@ -268,6 +443,8 @@ impl TryInto<SearchSongs> for YtMusicResponse {
                     }
                 } else if re_duration.is_match(&text) {
                     duration = parse_duration(&text)?;
+                } else if is_year(&text) {
+                    year = parse_year(&text);
                 } else {
                     debug!("artist without id: {}", text);
                     artists.push(Artist {
@@ -280,6 +457,9 @@ impl TryInto<SearchSongs> for YtMusicResponse {
                 debug!("skipping song with missing data: {}", name);
                 continue;
             }
+            if let Some(album) = &mut album {
+                album.year = year;
+            }
             let song = Song {
                 source: MusicApiType::YtMusic,
                 id,
@@ -289,6 +469,11 @@ impl TryInto<SearchSongs> for YtMusicResponse {
                 artists,
                 album,
                 duration_ms: duration,
+                uploaded: false,
+                explicit: Some(mrlir.is_explicit()),
+                alt_names: mrlir.get_alt_titles(),
+                mbid: None,
+                isrc_hint: None,
             };
 
             songs_vec.push(song);
@@ -316,6 +501,7 @@ impl TryInto<SearchSongUnique> for YtMusicResponse {
         let mut album = None;
         let mut artists: Vec<Artist> = vec![];
         let mut duration = 0;
+        let mut year = None;
         let re_duration = Regex::new(r"^(\d+:)*\d+:\d+$")?;
 
         for run in card_shelf
@@ -342,6 +528,7 @@ impl TryInto<SearchSongUnique> for YtMusicResponse {
                     album = Some(Album {
                         id: Some(id),
                         name: text,
+                        year: None,
                     });
                 } else {
                     artists.push(Artist {
@@ -351,6 +538,8 @@ impl TryInto<SearchSongUnique> for YtMusicResponse {
                 }
             } else if re_duration.is_match(&text) {
                 duration = parse_duration(&text)?;
+            } else if is_year(&text) {
+                year = parse_year(&text);
             } else {
                 debug!("artist without id: {}", text);
                 artists.push(Artist {
@@ -366,6 +555,9 @@ impl TryInto<SearchSongUnique> for YtMusicResponse {
         //    debug!("skipping song with missing data: {}", name);
         //    return Ok(SearchSongUnique(None));
         //}
+        if let Some(album) = &mut album {
+            album.year = year;
+        }
 
         let song = Song {
             source: MusicApiType::YtMusic,
@@ -376,7 +568,442 @@ impl TryInto<SearchSongUnique> for YtMusicResponse {
             artists,
             album,
             duration_ms: duration,
+            uploaded: false,
+            explicit: None,
+            alt_names: vec![],
+            mbid: None,
+            isrc_hint: None,
         };
         Ok(SearchSongUnique(Some(song)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::yt_music::model::YtMusicPlaylistPage;
+
+    /// Library playlists grid, trimmed from a `FEmusic_liked_playlists` browse response, with
+    /// the "New playlist" entry after a playlist and "Your Likes" last
+    const LIBRARY_RESPONSE: &str = r#"{
+        "contents": {"singleColumnBrowseResultsRenderer": {"tabs": [{"tabRenderer": {"content": {
+            "sectionListRenderer": {"contents": [{"gridRenderer": {"items": [
+                {"musicTwoRowItemRenderer": {
+                    "title": {"runs": [{"text": "Road trip",
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": "VLPLroadtrip"}}}]},
+                    "subtitle": {"runs": [{"text": "Jane"}, {"text": " • "}, {"text": "42 songs"}]}
+                }},
+                {"musicTwoRowItemRenderer": {
+                    "title": {"runs": [{"text": "New playlist"}]},
+                    "onTap": {"createPlaylistEndpoint": {}}
+                }},
+                {"musicTwoRowItemRenderer": {
+                    "title": {"runs": [{"text": "Episodes for Later",
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": "VLSE"}}}]},
+                    "subtitle": {"runs": [{"text": "Episodes you save for later"}]}
+                }},
+                {"musicTwoRowItemRenderer": {
+                    "title": {"runs": [{"text": "Chill",
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": "VLPLchill"}}}]},
+                    "subtitle": {"runs": [{"text": "Jane"}, {"text": " • "}, {"text": "7 songs"}]}
+                }},
+                {"musicTwoRowItemRenderer": {
+                    "title": {"runs": [{"text": "Your Likes",
+                        "navigationEndpoint": {"browseEndpoint": {"browseId": "VLLM"}}}]},
+                    "subtitle": {"runs": [{"text": "Auto playlist"}]}
+                }}
+            ]}}]}
+        }}}]}}
+    }"#;
+
+    /// Playlist page trimmed from a `VL...` browse response, with a podcast episode, a deleted
+    /// video and a song without duration between two songs
+    const PLAYLIST_RESPONSE: &str = r#"{
+        "contents": {"twoColumnBrowseResultsRenderer": {"secondaryContents": {"sectionListRenderer": {
+            "contents": [{"musicPlaylistShelfRenderer": {"contents": [
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "song1", "playlistSetVideoId": "set1"},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Song one"}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Jane"}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Album"}]}}}
+                    ],
+                    "fixedColumns": [
+                        {"musicResponsiveListItemFixedColumnRenderer": {"text": {"runs": [{"text": "3:00"}]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "episode1", "playlistSetVideoId": "set2"},
+                    "overlay": {"musicItemThumbnailOverlayRenderer": {"content": {"musicPlayButtonRenderer": {
+                        "playNavigationEndpoint": {"watchEndpoint": {"videoId": "episode1",
+                            "watchEndpointMusicSupportedConfigs": {"watchEndpointMusicConfig": {
+                                "musicVideoType": "MUSIC_VIDEO_TYPE_PODCAST_EPISODE"}}}}}}}},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Episode 12"}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "The Podcast"}]}}}
+                    ],
+                    "fixedColumns": [
+                        {"musicResponsiveListItemFixedColumnRenderer": {"text": {"runs": [{"text": "58:12"}]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Video deleted"}]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "blocked1", "playlistSetVideoId": "set3"},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Blocked"}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Jane"}]}}}
+                    ],
+                    "fixedColumns": [
+                        {"musicResponsiveListItemFixedColumnRenderer": {"text": {}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "song2", "playlistSetVideoId": "set4"},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                            {"text": "Song two", "navigationEndpoint": {"watchEndpoint": {"videoId": "song2",
+                                "watchEndpointMusicSupportedConfigs": {"watchEndpointMusicConfig": {
+                                    "musicVideoType": "MUSIC_VIDEO_TYPE_ATV"}}}}}
+                        ]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                            {"text": "Jane"}, {"text": " & "}, {"text": "John"}
+                        ]}}}
+                    ],
+                    "fixedColumns": [
+                        {"musicResponsiveListItemFixedColumnRenderer": {"text": {"runs": [{"text": "4:05"}]}}}
+                    ]
+                }}
+            ]}}]
+        }}}}
+    }"#;
+
+    #[test]
+    fn test_playlist_skips_episodes_and_unavailable_items() {
+        let res: YtMusicResponse = serde_json::from_str(PLAYLIST_RESPONSE).unwrap();
+        let songs: Songs = res.try_into().unwrap();
+        let songs: Vec<(&str, Option<&str>, usize, usize)> = songs
+            .0
+            .iter()
+            .map(|s| (s.id.as_str(), s.sid.as_deref(), s.artists.len(), s.duration_ms))
+            .collect();
+        assert_eq!(
+            songs,
+            vec![("song1", Some("set1"), 1, 180_000), ("song2", Some("set4"), 2, 245_000)]
+        );
+    }
+
+    /// Browse response of a private playlist of another user, trimmed
+    const PRIVATE_PLAYLIST_RESPONSE: &str = r#"{
+        "responseContext": {"visitorData": "CgtYZk"},
+        "alerts": [{"alertWithButtonRenderer": {
+            "type": "ERROR",
+            "text": {"simpleText": "This playlist is private."},
+            "dismissButton": {"buttonRenderer": {"icon": {"iconType": "CLOSE"}}}
+        }}]
+    }"#;
+
+    /// Browse response of a deleted playlist, trimmed
+    const DELETED_PLAYLIST_RESPONSE: &str = r#"{
+        "responseContext": {"visitorData": "CgtYZk"},
+        "alerts": [{"alertRenderer": {
+            "type": "ERROR",
+            "text": {"runs": [{"text": "The playlist does not exist."}]}
+        }}]
+    }"#;
+
+    /// Browse response of an empty playlist: its header, without shelf of songs
+    const EMPTY_PLAYLIST_RESPONSE: &str = r#"{
+        "contents": {"twoColumnBrowseResultsRenderer": {
+            "tabs": [{"tabRenderer": {"content": {"sectionListRenderer": {"contents": [
+                {"musicResponsiveHeaderRenderer": {"title": {"runs": [{"text": "Empty"}]}}}
+            ]}}}}],
+            "secondaryContents": {"sectionListRenderer": {"contents": []}}
+        }}
+    }"#;
+
+    #[test]
+    fn test_unavailable_playlist_pages() {
+        let unavailable = |response: &str| match serde_json::from_str(response).unwrap() {
+            YtMusicPlaylistPage::Alerts(alerts) => Some(playlist_unavailable("PL1", &alerts)),
+            YtMusicPlaylistPage::Playlist(_) => None,
+        };
+
+        let private = unavailable(PRIVATE_PLAYLIST_RESPONSE).unwrap();
+        assert_eq!(private.reason, Unavailability::Inaccessible);
+        assert_eq!(private.message, "This playlist is private.");
+        let deleted = unavailable(DELETED_PLAYLIST_RESPONSE).unwrap();
+        assert_eq!(deleted.reason, Unavailability::Deleted);
+        assert_eq!(deleted.to_string(), "playlist PL1 is deleted: The playlist does not exist.");
+
+        // an empty playlist is a playlist, with no songs
+        assert!(unavailable(EMPTY_PLAYLIST_RESPONSE).is_none());
+        let res: YtMusicResponse = serde_json::from_str(EMPTY_PLAYLIST_RESPONSE).unwrap();
+        let songs: Songs = res.try_into().unwrap();
+        assert!(songs.0.is_empty());
+        assert!(matches!(
+            serde_json::from_str(PLAYLIST_RESPONSE).unwrap(),
+            YtMusicPlaylistPage::Playlist(_)
+        ));
+    }
+
+    /// Watch queue trimmed from a `next` response, the requested song first
+    const NEXT_RESPONSE: &str = r#"{
+        "contents": {"singleColumnMusicWatchNextResultsRenderer": {"tabbedRenderer": {
+            "watchNextTabbedResultsRenderer": {"tabs": [{"tabRenderer": {"content": {"musicQueueRenderer": {
+                "content": {"playlistPanelRenderer": {"contents": [
+                    {"playlistPanelVideoRenderer": {
+                        "videoId": "dQw4w9WgXcQ",
+                        "title": {"runs": [{"text": "Never Gonna Give You Up"}]},
+                        "longBylineText": {"runs": [
+                            {"text": "Rick Astley", "navigationEndpoint": {"browseEndpoint": {"browseId": "UCuAXFkgsw1L7xaCfnd5JJOw"}}},
+                            {"text": " & "},
+                            {"text": "Guest"},
+                            {"text": " • "},
+                            {"text": "Whenever You Need Somebody", "navigationEndpoint": {"browseEndpoint": {"browseId": "MPREb_Tb3yXUb7tBn"}}},
+                            {"text": " • "},
+                            {"text": "1987"}
+                        ]},
+                        "lengthText": {"runs": [{"text": "3:33"}]}
+                    }},
+                    {"playlistPanelVideoRenderer": {
+                        "videoId": "next1",
+                        "title": {"runs": [{"text": "Up next"}]},
+                        "lengthText": {"runs": [{"text": "4:00"}]}
+                    }}
+                ]}}
+            }}}}]}
+        }}}
+    }"#;
+
+    #[test]
+    fn test_watch_song() {
+        let res: YtMusicNextResponse = serde_json::from_str(NEXT_RESPONSE).unwrap();
+        let song = watch_song("dQw4w9WgXcQ", &res).unwrap().0.unwrap();
+        assert_eq!(song.id, "dQw4w9WgXcQ");
+        assert_eq!(song.name, "Never Gonna Give You Up");
+        assert_eq!(song.duration_ms, 213_000);
+        let artists: Vec<(&str, Option<&str>)> =
+            song.artists.iter().map(|a| (a.name.as_str(), a.id.as_deref())).collect();
+        assert_eq!(artists, vec![("Rick Astley", Some("UCuAXFkgsw1L7xaCfnd5JJOw")), ("Guest", None)]);
+        let album = song.album.unwrap();
+        assert_eq!(album.name, "Whenever You Need Somebody");
+        assert_eq!(album.id.as_deref(), Some("MPREb_Tb3yXUb7tBn"));
+
+        // videos show their number of views instead of an album
+        let video = NEXT_RESPONSE.replace(
+            r#"{"text": "Whenever You Need Somebody", "navigationEndpoint": {"browseEndpoint": {"browseId": "MPREb_Tb3yXUb7tBn"}}}"#,
+            r#"{"text": "1.6B views"}"#,
+        );
+        let res: YtMusicNextResponse = serde_json::from_str(&video).unwrap();
+        let song = watch_song("dQw4w9WgXcQ", &res).unwrap().0.unwrap();
+        assert!(song.album.is_none());
+        assert_eq!(song.artists.len(), 2);
+
+        // the queue of a missing video starts with another one
+        let res: YtMusicNextResponse = serde_json::from_str(NEXT_RESPONSE).unwrap();
+        assert!(watch_song("missing0000", &res).unwrap().0.is_none());
+        let up_next = watch_song("next1", &res).unwrap().0.unwrap();
+        assert_eq!(up_next.name, "Up next");
+
+        let res: YtMusicNextResponse = serde_json::from_str(r#"{"contents": {}}"#).unwrap();
+        assert!(watch_song("dQw4w9WgXcQ", &res).unwrap().0.is_none());
+    }
+
+    /// Uploaded songs shelf, trimmed from a `FEmusic_library_privately_owned_tracks` browse
+    /// response, with the "Shuffle all" entry first
+    const UPLOADS_RESPONSE: &str = r#"{
+        "contents": {"singleColumnBrowseResultsRenderer": {"tabs": [{"tabRenderer": {"content": {
+            "sectionListRenderer": {"contents": [
+                {"itemSectionRenderer": {"contents": []}},
+                {"musicShelfRenderer": {"contents": [
+                    {"musicResponsiveListItemRenderer": {"flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Shuffle all"}]}}}
+                    ]}},
+                    {"musicResponsiveListItemRenderer": {
+                        "menu": {"menuRenderer": {"items": [{"menuNavigationItemRenderer": {}}]}},
+                        "overlay": {"musicItemThumbnailOverlayRenderer": {"content": {"musicPlayButtonRenderer": {
+                            "playNavigationEndpoint": {"watchEndpoint": {"videoId": "upl0ad1"}}}}}},
+                        "flexColumns": [
+                            {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Garage demo"}]}}},
+                            {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                                {"text": "The Band", "navigationEndpoint": {"browseEndpoint": {
+                                    "browseId": "FEmusic_library_privately_owned_artist_detailab12"}}},
+                                {"text": " & "},
+                                {"text": "Jane"}
+                            ]}}},
+                            {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                                {"text": "Demos", "navigationEndpoint": {"browseEndpoint": {
+                                    "browseId": "FEmusic_library_privately_owned_release_detailcd34"}}}
+                            ]}}}
+                        ],
+                        "fixedColumns": [
+                            {"musicResponsiveListItemFixedColumnRenderer": {"text": {"runs": [{"text": "3:25"}]}}}
+                        ]
+                    }}
+                ], "continuations": [{"nextContinuationData": {"continuation": "CONT1"}}]}}
+            ]}
+        }}}]}}
+    }"#;
+
+    #[test]
+    fn test_uploaded_songs() {
+        let mut res: YtMusicResponse = serde_json::from_str(UPLOADS_RESPONSE).unwrap();
+        assert_eq!(res.get_continuation().as_deref(), Some("CONT1"));
+        let songs: UploadedSongs = res.try_into().unwrap();
+        assert_eq!(songs.0.len(), 1);
+        let song = &songs.0[0];
+        assert_eq!(song.id, "upl0ad1");
+        assert_eq!(song.name, "Garage demo");
+        assert_eq!(song.duration_ms, 205_000);
+        assert!(song.uploaded && song.isrc.is_none() && song.sid.is_none());
+        let artists: Vec<(&str, Option<&str>)> =
+            song.artists.iter().map(|a| (a.name.as_str(), a.id.as_deref())).collect();
+        assert_eq!(artists, vec![("The Band", None), ("Jane", None)]);
+        let album = song.album.as_ref().unwrap();
+        assert_eq!((album.name.as_str(), album.id.as_deref()), ("Demos", None));
+
+        // no shelf when nothing was uploaded
+        let empty = r#"{"contents": {"singleColumnBrowseResultsRenderer": {"tabs": [{"tabRenderer":
+            {"content": {"sectionListRenderer": {"contents": [{"itemSectionRenderer": {}}]}}}}]}}}"#;
+        let res: YtMusicResponse = serde_json::from_str(empty).unwrap();
+        let songs: UploadedSongs = res.try_into().unwrap();
+        assert!(songs.0.is_empty());
+    }
+
+    /// Subscriptions page trimmed from a `FEmusic_library_corpus_artists` browse response,
+    /// with a channel prefixed by MPLA
+    const SUBSCRIPTIONS_RESPONSE: &str = r#"{
+        "contents": {"singleColumnBrowseResultsRenderer": {"tabs": [{"tabRenderer": {"content": {
+            "sectionListRenderer": {"contents": [{"musicShelfRenderer": {"contents": [
+                {"musicResponsiveListItemRenderer": {
+                    "navigationEndpoint": {"browseEndpoint": {"browseId": "UCradiohead"}},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Radiohead"}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "7.1M subscribers"}]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "navigationEndpoint": {"browseEndpoint": {"browseId": "MPLAUCdaftpunk"}},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Daft Punk"}]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "navigationEndpoint": {"browseEndpoint": {"browseId": "VLPLmix"}},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "Mix"}]}}}
+                    ]
+                }}
+            ]}}]}
+        }}}]}}
+    }"#;
+
+    #[test]
+    fn test_subscribed_artists() {
+        let res: YtMusicResponse = serde_json::from_str(SUBSCRIPTIONS_RESPONSE).unwrap();
+        let artists: SubscribedArtists = res.try_into().unwrap();
+        let artists: Vec<(&str, Option<&str>)> =
+            artists.0.iter().map(|a| (a.name.as_str(), a.id.as_deref())).collect();
+        assert_eq!(artists, vec![("Radiohead", Some("UCradiohead")), ("Daft Punk", Some("UCdaftpunk"))]);
+
+        // the same items in search results
+        let search = SUBSCRIPTIONS_RESPONSE
+            .replace("singleColumnBrowseResultsRenderer", "tabbedSearchResultsRenderer");
+        let res: YtMusicResponse = serde_json::from_str(&search).unwrap();
+        let artists: SearchArtists = res.try_into().unwrap();
+        assert_eq!(artists.0.len(), 2);
+    }
+
+    /// Songs search trimmed from a `search` response, the explicit version of a song and its
+    /// clean radio edit
+    const SEARCH_RESPONSE: &str = r#"{
+        "contents": {"tabbedSearchResultsRenderer": {"tabs": [{"tabRenderer": {"content": {
+            "sectionListRenderer": {"contents": [{"musicShelfRenderer": {"contents": [
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "explicit1"},
+                    "badges": [{"musicInlineBadgeRenderer": {"icon": {"iconType": "MUSIC_EXPLICIT_BADGE"},
+                        "accessibilityData": {"accessibilityData": {"label": "Explicit"}}}}],
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "HUMBLE."}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                            {"text": "Kendrick Lamar", "navigationEndpoint": {"browseEndpoint": {"browseId": "UCkendrick"}}},
+                            {"text": " • "},
+                            {"text": "DAMN.", "navigationEndpoint": {"browseEndpoint": {"browseId": "MPREdamn"}}},
+                            {"text": " • "},
+                            {"text": "2:57"}
+                        ]}}}
+                    ]
+                }},
+                {"musicResponsiveListItemRenderer": {
+                    "playlistItemData": {"videoId": "clean1"},
+                    "flexColumns": [
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "HUMBLE."}]}}},
+                        {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                            {"text": "Kendrick Lamar", "navigationEndpoint": {"browseEndpoint": {"browseId": "UCkendrick"}}},
+                            {"text": " • "},
+                            {"text": "HUMBLE.", "navigationEndpoint": {"browseEndpoint": {"browseId": "MPREhumble"}}},
+                            {"text": " • "},
+                            {"text": "2:57"}
+                        ]}}}
+                    ]
+                }}
+            ]}}]}
+        }}}]}}
+    }"#;
+
+    #[test]
+    fn test_search_songs_explicit() {
+        let res: YtMusicResponse = serde_json::from_str(SEARCH_RESPONSE).unwrap();
+        let songs: SearchSongs = res.try_into().unwrap();
+        let songs: Vec<(&str, Option<bool>, usize)> =
+            songs.0.iter().map(|s| (s.id.as_str(), s.explicit, s.duration_ms)).collect();
+        assert_eq!(songs, vec![("explicit1", Some(true), 177_000), ("clean1", Some(false), 177_000)]);
+    }
+
+    #[test]
+    fn test_alt_titles() {
+        let item = json!({"musicResponsiveListItemRenderer": {
+            "playlistItemData": {"videoId": "yoru1"},
+            "flexColumns": [
+                {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [
+                    {"text": "Yoru ni Kakeru"}, {"text": " ("}, {"text": "夜に駆ける"}, {"text": ")"}
+                ]}}},
+                {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "YOASOBI"}]}}}
+            ]
+        }});
+        let mrlir: MusicResponsiveListItemRenderer =
+            serde_json::from_value(item["musicResponsiveListItemRenderer"].clone()).unwrap();
+        assert_eq!(mrlir.get_alt_titles(), vec!["夜に駆ける"]);
+
+        let item = json!({"flexColumns": [
+            {"musicResponsiveListItemFlexColumnRenderer": {"text": {"runs": [{"text": "HUMBLE."}]}}}
+        ]});
+        let mrlir: MusicResponsiveListItemRenderer = serde_json::from_value(item).unwrap();
+        assert!(mrlir.get_alt_titles().is_empty());
+    }
+
+    #[test]
+    fn test_library_playlists() {
+        let res: YtMusicResponse = serde_json::from_str(LIBRARY_RESPONSE).unwrap();
+        let playlists: Playlists = res.try_into().unwrap();
+        let playlists: Vec<(&str, &str, bool)> = playlists
+            .0
+            .iter()
+            .map(|p| (p.id.as_str(), p.name.as_str(), p.generated))
+            .collect();
+        assert_eq!(
+            playlists,
+            vec![
+                ("PLroadtrip", "Road trip", false),
+                ("SE", "Episodes for Later", true),
+                ("PLchill", "Chill", false),
+            ]
+        );
+    }
+}
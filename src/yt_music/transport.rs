@@ -0,0 +1,277 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, eyre};
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tracing::debug;
+
+use crate::utils::redact_secrets;
+
+/// Raw response as seen by `YtMusicApi::make_request`, before any parsing
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub text: String,
+}
+
+/// Request layer used by `YtMusicApi::make_request`
+///
+/// The reqwest client is owned by `YtMusicApi` (it gets rebuilt on token/cookie refresh),
+/// so it is passed on each call instead of being stored in the transport.
+#[async_trait]
+pub trait YtMusicTransport: Send + Sync {
+    async fn post(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        body: &serde_json::Value,
+        headers: HeaderMap,
+    ) -> Result<RawResponse>;
+}
+
+/// Default transport sending requests over the network
+pub struct HttpTransport;
+
+#[async_trait]
+impl YtMusicTransport for HttpTransport {
+    async fn post(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        body: &serde_json::Value,
+        headers: HeaderMap,
+    ) -> Result<RawResponse> {
+        // headers of the request take precedence over the default headers of the client
+        let res = client.post(endpoint).headers(headers).json(body).send().await?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let text = res.text().await?;
+        Ok(RawResponse {
+            status,
+            headers,
+            text,
+        })
+    }
+}
+
+/// Name of the fixture file recording the response to a request
+///
+/// The request context is left out of the hash so fixtures recorded with another
+/// client version still replay.
+pub fn fixture_name(endpoint: &str, body: &serde_json::Value) -> String {
+    let (path, query) = endpoint.split_once('?').unwrap_or((endpoint, ""));
+    let path = path.rsplit_once("/v1/").map_or(path, |(_, p)| p);
+    let query = query
+        .split('&')
+        .filter(|param| !param.starts_with("key=") && !param.starts_with("alt="))
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut body = body.clone();
+    if let Some(o) = body.as_object_mut() {
+        o.remove("context");
+    }
+
+    let mut hasher = Sha1::new();
+    hasher.update(query.as_bytes());
+    hasher.update(body.to_string().as_bytes());
+    let hash = format!("{:x}", hasher.finalize());
+    format!("{}_{}.json", path.replace('/', "_"), &hash[..12])
+}
+
+/// Keys whose values identify the user in `YtMusic` responses
+const REDACTED_KEYS: [&str; 6] = [
+    "accountName",
+    "accountPhoto",
+    "channelHandle",
+    "email",
+    "datasyncId",
+    "visitorData",
+];
+
+fn redact_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(o) => {
+            for (key, v) in o.iter_mut() {
+                if REDACTED_KEYS.contains(&key.as_str()) {
+                    *v = serde_json::Value::String("REDACTED".to_string());
+                } else {
+                    redact_value(v);
+                }
+            }
+        }
+        serde_json::Value::Array(a) => a.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// Remove personal data from a response body before it gets written to disk
+pub fn redact(text: &str) -> String {
+    let text = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => text.to_string(),
+    };
+    redact_secrets(&text)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    status: u16,
+    body: String,
+}
+
+/// Transport writing every response to a fixtures directory, for later replay
+pub struct RecordingTransport {
+    inner: Box<dyn YtMusicTransport>,
+    dir: PathBuf,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Box<dyn YtMusicTransport>, dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { inner, dir })
+    }
+}
+
+#[async_trait]
+impl YtMusicTransport for RecordingTransport {
+    async fn post(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        body: &serde_json::Value,
+        headers: HeaderMap,
+    ) -> Result<RawResponse> {
+        let res = self.inner.post(client, endpoint, body, headers).await?;
+        let fixture = Fixture {
+            status: res.status.as_u16(),
+            body: redact(&res.text),
+        };
+        let path = self.dir.join(fixture_name(endpoint, body));
+        std::fs::write(&path, serde_json::to_string_pretty(&fixture)?)?;
+        debug!("recorded fixture {:?}", path);
+        Ok(res)
+    }
+}
+
+/// Offline transport answering requests with fixtures recorded by `RecordingTransport`
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(eyre!("fixtures directory {:?} does not exist", dir));
+        }
+        Ok(Self { dir })
+    }
+}
+
+#[async_trait]
+impl YtMusicTransport for ReplayTransport {
+    async fn post(
+        &self,
+        _client: &reqwest::Client,
+        endpoint: &str,
+        body: &serde_json::Value,
+        _headers: HeaderMap,
+    ) -> Result<RawResponse> {
+        let path = self.dir.join(fixture_name(endpoint, body));
+        let Ok(fixture) = std::fs::read_to_string(&path) else {
+            return Err(eyre!(
+                "no recorded fixture {:?} for request to {} with body {}",
+                path,
+                endpoint,
+                body
+            ));
+        };
+        let fixture: Fixture = serde_json::from_str(&fixture)?;
+        debug!("replaying fixture {:?}", path);
+        Ok(RawResponse {
+            status: StatusCode::from_u16(fixture.status)?,
+            headers: HeaderMap::new(),
+            text: fixture.body,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use super::{HeaderMap, RawResponse, Result, StatusCode, YtMusicTransport, async_trait};
+
+    /// Transport replaying a fixed sequence of responses, recording the requested bodies and
+    /// headers
+    pub struct ScriptedTransport {
+        responses: Mutex<VecDeque<(StatusCode, String)>>,
+        pub requests: Mutex<Vec<(String, serde_json::Value)>>,
+        pub request_headers: Mutex<Vec<HeaderMap>>,
+    }
+
+    impl ScriptedTransport {
+        pub fn new(responses: Vec<(u16, &str)>) -> Self {
+            let responses = responses
+                .into_iter()
+                .map(|(status, text)| (StatusCode::from_u16(status).unwrap(), text.to_string()))
+                .collect();
+            Self {
+                responses: Mutex::new(responses),
+                requests: Mutex::new(vec![]),
+                request_headers: Mutex::new(vec![]),
+            }
+        }
+
+        pub fn request_count(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl YtMusicTransport for std::sync::Arc<ScriptedTransport> {
+        async fn post(
+            &self,
+            client: &reqwest::Client,
+            endpoint: &str,
+            body: &serde_json::Value,
+            headers: HeaderMap,
+        ) -> Result<RawResponse> {
+            self.as_ref().post(client, endpoint, body, headers).await
+        }
+    }
+
+    #[async_trait]
+    impl YtMusicTransport for ScriptedTransport {
+        async fn post(
+            &self,
+            _client: &reqwest::Client,
+            endpoint: &str,
+            body: &serde_json::Value,
+            headers: HeaderMap,
+        ) -> Result<RawResponse> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((endpoint.to_string(), body.clone()));
+            self.request_headers.lock().unwrap().push(headers);
+            let (status, text) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no scripted response left");
+            Ok(RawResponse {
+                status,
+                headers: HeaderMap::new(),
+                text,
+            })
+        }
+    }
+}